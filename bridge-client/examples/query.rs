@@ -0,0 +1,28 @@
+// Run one statement through a bridge and print the rows as JSON:
+//   cargo run -p bridge-client --example query -- ws://127.0.0.1:8080 "SELECT now()"
+// Set BRIDGE_TOKEN when the bridge requires a key.
+
+use bridge_client::Client;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let (Some(url), Some(sql)) = (args.next(), args.next()) else {
+        eprintln!("usage: query <ws-url> <sql>");
+        std::process::exit(2);
+    };
+    let client = Client::connect(&url).await?;
+    if let Ok(token) = std::env::var("BRIDGE_TOKEN") {
+        client.auth(&token).await?;
+    }
+    let result = client.query(&sql, Vec::new()).await?;
+    for row in &result.rows {
+        println!("{}", row);
+    }
+    eprintln!(
+        "{} row(s) in {:.1} ms",
+        result.row_count, result.execution_time
+    );
+    client.close();
+    Ok(())
+}
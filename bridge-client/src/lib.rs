@@ -0,0 +1,250 @@
+// Native bridge client: the wire types and handshake of the wasm crate's `protocol`
+// layer over tokio-tungstenite, for CLI tools and integration tests outside a browser.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::Message;
+use wasm_postgres_learning::protocol::{
+    chunking::DEFAULT_MAX_MESSAGE_SIZE, message_type, negotiate, to_value, Capabilities,
+    HelloPayload, Negotiated, NotificationPayload, QueryPayload, QueryResult, WebSocketMessage,
+};
+use wasm_postgres_learning::types::BridgeError;
+
+// Offered in `hello`: only what this client decodes. Compressed, chunked and encrypted
+// frames are still browser-only.
+pub const NATIVE_CAPABILITIES: Capabilities = Capabilities {
+    binary: false,
+    streaming: false,
+    compression: false,
+    notifications: true,
+    chunking: false,
+    progress: false,
+    cancel: false,
+    encryption: false,
+    resume: false,
+    result_cache: false,
+    continuation: false,
+    schema_cache: false,
+    schema_events: false,
+    row_deltas: false,
+    throttling: false,
+};
+
+type Pending = HashMap<String, oneshot::Sender<WebSocketMessage>>;
+
+// Hand a reply to the request waiting on its id. Returns notifications; anything else
+// (the welcome message, replies nobody waits for) is dropped.
+fn route(pending: &mut Pending, message: WebSocketMessage) -> Option<NotificationPayload> {
+    match message.message_type.as_str() {
+        message_type::RESULT | message_type::ERROR => {
+            if let Some(waiter) = message.id.as_ref().and_then(|id| pending.remove(id)) {
+                let _ = waiter.send(message);
+            }
+            None
+        }
+        message_type::NOTIFICATION => serde_json::from_value(message.payload).ok(),
+        _ => None,
+    }
+}
+
+pub struct Client {
+    out: mpsc::UnboundedSender<Message>,
+    pending: Arc<Mutex<Pending>>,
+    notifications: mpsc::UnboundedReceiver<NotificationPayload>,
+    counter: AtomicU64,
+    negotiated: Negotiated,
+}
+
+impl Client {
+    // Connect and exchange `hello`. Bridges that require a key take it as `?token=` on
+    // the URL or through `auth`.
+    pub async fn connect(url: &str) -> Result<Client, BridgeError> {
+        let (socket, _) = tokio_tungstenite::connect_async(url).await.map_err(|e| {
+            BridgeError::connection(&format!("Failed to connect to {}: {}", url, e))
+        })?;
+        let (mut sink, mut stream) = socket.split();
+
+        let (out, mut outgoing) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(frame) = outgoing.recv().await {
+                if sink.send(frame).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        let pending = Arc::new(Mutex::new(Pending::new()));
+        let (notify, notifications) = mpsc::unbounded_channel();
+        let replies = pending.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(frame)) = stream.next().await {
+                let Message::Text(text) = frame else {
+                    continue;
+                };
+                let Ok(message) = serde_json::from_str::<WebSocketMessage>(&text) else {
+                    continue;
+                };
+                if let Some(notification) = route(&mut replies.lock().unwrap(), message) {
+                    let _ = notify.send(notification);
+                }
+            }
+            // Dropping the senders fails every request still waiting
+            replies.lock().unwrap().clear();
+        });
+
+        let mut client = Client {
+            out,
+            pending,
+            notifications,
+            counter: AtomicU64::new(0),
+            negotiated: Negotiated::default(),
+        };
+        let ours = HelloPayload::new(0, NATIVE_CAPABILITIES, DEFAULT_MAX_MESSAGE_SIZE);
+        let reply = client.send(message_type::HELLO, to_value(&ours)).await?;
+        match negotiate(&ours, &reply.message_type, &reply.payload) {
+            Ok(negotiated) => {
+                client.negotiated = negotiated;
+                Ok(client)
+            }
+            Err(reason) => {
+                client.close();
+                Err(BridgeError::connection(&reason))
+            }
+        }
+    }
+
+    // Protocol version and features agreed in `hello`
+    pub fn negotiated(&self) -> &Negotiated {
+        &self.negotiated
+    }
+
+    fn next_message_id(&self, kind: &str) -> String {
+        format!(
+            "native_{}_{}",
+            kind,
+            self.counter.fetch_add(1, Ordering::Relaxed) + 1
+        )
+    }
+
+    // Send a message and wait for the reply with its id, `result` or `error` alike
+    async fn send(&self, kind: &str, payload: Value) -> Result<WebSocketMessage, BridgeError> {
+        let id = self.next_message_id(kind);
+        let (waiter, reply) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), waiter);
+        let frame = WebSocketMessage::new(kind, payload, Some(id.clone())).to_json();
+        if self.out.send(Message::Text(frame)).is_err() {
+            self.pending.lock().unwrap().remove(&id);
+        }
+        reply
+            .await
+            .map_err(|_| BridgeError::connection("Connection closed").with_query_id(&id))
+    }
+
+    // The `result` payload, or the bridge's `error` as a query error
+    async fn request(&self, kind: &str, payload: Value) -> Result<Value, BridgeError> {
+        let reply = self.send(kind, payload).await?;
+        if reply.message_type == message_type::ERROR {
+            return Err(BridgeError::from_error_payload(&reply.payload, reply.id));
+        }
+        Ok(reply.payload)
+    }
+
+    pub async fn auth(&self, token: &str) -> Result<(), BridgeError> {
+        self.request(message_type::AUTH, json!({ "token": token }))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn ping(&self) -> Result<Value, BridgeError> {
+        self.request(message_type::PING, json!({})).await
+    }
+
+    pub async fn query(&self, sql: &str, params: Vec<Value>) -> Result<QueryResult, BridgeError> {
+        self.query_with(QueryPayload {
+            sql: sql.to_string(),
+            params: Some(params),
+            traceparent: None,
+            idempotency_key: None,
+            statement_timeout_ms: None,
+            tenant_id: None,
+            raw: false,
+            cache: None,
+            page_size: None,
+            binary_params: None,
+            tags: None,
+            types: None,
+        })
+        .await
+    }
+
+    // Run a fully specified query, for the options `query` leaves unset
+    pub async fn query_with(&self, query: QueryPayload) -> Result<QueryResult, BridgeError> {
+        let payload = self.request(message_type::QUERY, to_value(&query)).await?;
+        serde_json::from_value(payload).map_err(|e| {
+            BridgeError::connection(&format!("Invalid query result from bridge: {}", e))
+        })
+    }
+
+    pub async fn listen(&self, channel: &str) -> Result<(), BridgeError> {
+        self.request(message_type::LISTEN, json!({ "channel": channel }))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn unlisten(&self, channel: &str) -> Result<(), BridgeError> {
+        self.request(message_type::UNLISTEN, json!({ "channel": channel }))
+            .await
+            .map(|_| ())
+    }
+
+    // The next NOTIFY on a listened channel; `None` once the connection is gone
+    pub async fn next_notification(&mut self) -> Option<NotificationPayload> {
+        self.notifications.recv().await
+    }
+
+    // Close the socket; requests still waiting fail with a connection error
+    pub fn close(&self) {
+        let _ = self.out.send(Message::Close(None));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_matches_replies_by_id() {
+        let mut pending = Pending::new();
+        let (waiter, mut reply) = oneshot::channel();
+        pending.insert("native_query_1".to_string(), waiter);
+
+        let welcome =
+            WebSocketMessage::result(Some("welcome".to_string()), json!({"authRequired": false}));
+        assert!(route(&mut pending, welcome).is_none());
+        assert!(reply.try_recv().is_err());
+
+        let error =
+            WebSocketMessage::error(Some("native_query_1".to_string()), "DATABASE_ERROR", "boom");
+        assert!(route(&mut pending, error).is_none());
+        assert!(pending.is_empty());
+        let reply = reply.try_recv().unwrap();
+        let error = BridgeError::from_error_payload(&reply.payload, reply.id);
+        assert_eq!(
+            (error.code(), error.query_id()),
+            (Some("DATABASE_ERROR"), Some("native_query_1"))
+        );
+
+        let notification =
+            route(&mut pending, WebSocketMessage::notification("jobs", "7")).unwrap();
+        assert_eq!(
+            (notification.channel.as_str(), notification.payload.as_str()),
+            ("jobs", "7")
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+// Microseconds from the Unix epoch to 2000-01-01, where Postgres timestamps count from
+pub const POSTGRES_EPOCH_UNIX_MICROS: i64 = 946_684_800_000_000;
+
+// Types a parameter can be sent as in binary. Scalars use Postgres' binary send format;
+// arrays are their elements back to back, big-endian, without NULLs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub enum BinaryType {
+    #[serde(rename = "bool")]
+    Bool,
+    #[serde(rename = "int2")]
+    Int2,
+    #[serde(rename = "int4")]
+    Int4,
+    #[serde(rename = "int8")]
+    Int8,
+    #[serde(rename = "float4")]
+    Float4,
+    #[serde(rename = "float8")]
+    Float8,
+    #[serde(rename = "uuid")]
+    Uuid,
+    #[serde(rename = "timestamptz")]
+    TimestampTz,
+    #[serde(rename = "bytea")]
+    Bytea,
+    #[serde(rename = "int4[]")]
+    Int4Array,
+    #[serde(rename = "int8[]")]
+    Int8Array,
+    #[serde(rename = "float4[]")]
+    Float4Array,
+    #[serde(rename = "float8[]")]
+    Float8Array,
+}
+
+// One entry of a query's `binaryParams`: the bytes of a parameter, base64-encoded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct BinaryParam {
+    #[serde(rename = "type")]
+    pub param_type: BinaryType,
+    pub data: String,
+}
+
+// A decoded binary parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryValue {
+    Bool(bool),
+    Int2(i16),
+    Int4(i32),
+    Int8(i64),
+    Float4(f32),
+    Float8(f64),
+    Uuid([u8; 16]),
+    // Microseconds since 2000-01-01 UTC
+    TimestampTz(i64),
+    Bytea(Vec<u8>),
+    Int4Array(Vec<i32>),
+    Int8Array(Vec<i64>),
+    Float4Array(Vec<f32>),
+    Float8Array(Vec<f64>),
+}
+
+// Split `bytes` into `N`-byte elements, or fail if they don't divide evenly
+fn elements<const N: usize>(bytes: &[u8], param_type: BinaryType) -> Result<Vec<[u8; N]>, String> {
+    if !bytes.len().is_multiple_of(N) {
+        return Err(format!("{} bytes are not a whole number of {:?} elements", bytes.len(), param_type));
+    }
+    Ok(bytes.chunks_exact(N).map(|chunk| chunk.try_into().expect("chunks are N bytes")).collect())
+}
+
+fn scalar<const N: usize>(bytes: &[u8], param_type: BinaryType) -> Result<[u8; N], String> {
+    bytes.try_into().map_err(|_| format!("A {:?} parameter is {} bytes, not {}", param_type, N, bytes.len()))
+}
+
+impl BinaryValue {
+    pub fn timestamptz_from_unix_ms(ms: f64) -> BinaryValue {
+        BinaryValue::TimestampTz((ms * 1000.0) as i64 - POSTGRES_EPOCH_UNIX_MICROS)
+    }
+
+    pub fn binary_type(&self) -> BinaryType {
+        match self {
+            BinaryValue::Bool(_) => BinaryType::Bool,
+            BinaryValue::Int2(_) => BinaryType::Int2,
+            BinaryValue::Int4(_) => BinaryType::Int4,
+            BinaryValue::Int8(_) => BinaryType::Int8,
+            BinaryValue::Float4(_) => BinaryType::Float4,
+            BinaryValue::Float8(_) => BinaryType::Float8,
+            BinaryValue::Uuid(_) => BinaryType::Uuid,
+            BinaryValue::TimestampTz(_) => BinaryType::TimestampTz,
+            BinaryValue::Bytea(_) => BinaryType::Bytea,
+            BinaryValue::Int4Array(_) => BinaryType::Int4Array,
+            BinaryValue::Int8Array(_) => BinaryType::Int8Array,
+            BinaryValue::Float4Array(_) => BinaryType::Float4Array,
+            BinaryValue::Float8Array(_) => BinaryType::Float8Array,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            BinaryValue::Bool(v) => alloc::vec![u8::from(*v)],
+            BinaryValue::Int2(v) => v.to_be_bytes().to_vec(),
+            BinaryValue::Int4(v) => v.to_be_bytes().to_vec(),
+            BinaryValue::Int8(v) | BinaryValue::TimestampTz(v) => v.to_be_bytes().to_vec(),
+            BinaryValue::Float4(v) => v.to_be_bytes().to_vec(),
+            BinaryValue::Float8(v) => v.to_be_bytes().to_vec(),
+            BinaryValue::Uuid(v) => v.to_vec(),
+            BinaryValue::Bytea(v) => v.clone(),
+            BinaryValue::Int4Array(v) => v.iter().flat_map(|n| n.to_be_bytes()).collect(),
+            BinaryValue::Int8Array(v) => v.iter().flat_map(|n| n.to_be_bytes()).collect(),
+            BinaryValue::Float4Array(v) => v.iter().flat_map(|n| n.to_be_bytes()).collect(),
+            BinaryValue::Float8Array(v) => v.iter().flat_map(|n| n.to_be_bytes()).collect(),
+        }
+    }
+
+    pub fn from_bytes(param_type: BinaryType, bytes: &[u8]) -> Result<BinaryValue, String> {
+        let value = match param_type {
+            BinaryType::Bool => BinaryValue::Bool(scalar::<1>(bytes, param_type)?[0] != 0),
+            BinaryType::Int2 => BinaryValue::Int2(i16::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Int4 => BinaryValue::Int4(i32::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Int8 => BinaryValue::Int8(i64::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Float4 => BinaryValue::Float4(f32::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Float8 => BinaryValue::Float8(f64::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Uuid => BinaryValue::Uuid(scalar(bytes, param_type)?),
+            BinaryType::TimestampTz => BinaryValue::TimestampTz(i64::from_be_bytes(scalar(bytes, param_type)?)),
+            BinaryType::Bytea => BinaryValue::Bytea(bytes.to_vec()),
+            BinaryType::Int4Array => BinaryValue::Int4Array(elements(bytes, param_type)?.into_iter().map(i32::from_be_bytes).collect()),
+            BinaryType::Int8Array => BinaryValue::Int8Array(elements(bytes, param_type)?.into_iter().map(i64::from_be_bytes).collect()),
+            BinaryType::Float4Array => BinaryValue::Float4Array(elements(bytes, param_type)?.into_iter().map(f32::from_be_bytes).collect()),
+            BinaryType::Float8Array => BinaryValue::Float8Array(elements(bytes, param_type)?.into_iter().map(f64::from_be_bytes).collect()),
+        };
+        Ok(value)
+    }
+
+    pub fn to_param(&self) -> BinaryParam {
+        BinaryParam { param_type: self.binary_type(), data: STANDARD.encode(self.to_bytes()) }
+    }
+}
+
+impl BinaryParam {
+    pub fn decode(&self) -> Result<BinaryValue, String> {
+        let bytes = STANDARD.decode(&self.data).map_err(|e| format!("Invalid base64 in a binary parameter: {}", e))?;
+        BinaryValue::from_bytes(self.param_type, &bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_values_round_trip() {
+        let values = [
+            BinaryValue::Bool(true),
+            BinaryValue::Int2(-2),
+            BinaryValue::Int8(i64::MAX),
+            BinaryValue::Float8(1.5),
+            BinaryValue::Uuid([7; 16]),
+            BinaryValue::timestamptz_from_unix_ms(946_684_800_001.0),
+            BinaryValue::Bytea(vec![0, 255, 10]),
+            BinaryValue::Float8Array(vec![0.25, -1.0, f64::MAX]),
+            BinaryValue::Int4Array(Vec::new()),
+        ];
+        for value in values {
+            assert_eq!(value.to_param().decode().unwrap(), value);
+        }
+        assert_eq!(BinaryValue::timestamptz_from_unix_ms(946_684_800_001.0), BinaryValue::TimestampTz(1000));
+        assert_eq!(BinaryValue::Int4(1).to_param().data, "AAAAAQ==");
+        assert_eq!(serde_json::to_value(BinaryType::Float8Array).unwrap(), "float8[]");
+
+        let truncated = BinaryParam { param_type: BinaryType::Int8Array, data: STANDARD.encode([0u8; 12]) };
+        assert!(truncated.decode().is_err());
+        assert!(BinaryValue::from_bytes(BinaryType::Int4, &[0, 1]).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::integrity::{checksum, verify};
+use crate::{message_type, to_value, ChunkPayload, FrameError, WebSocketMessage};
+
+// Frame limit used when nothing else is configured; matches common proxy defaults
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+// Upper bound on the bytes of half-received messages, so a peer can't make us buffer
+// without limit
+pub const DEFAULT_MAX_ASSEMBLED_SIZE: usize = 64 * 1024 * 1024;
+
+// Half-received messages a peer may have open at once
+pub const MAX_PARTIAL_MESSAGES: usize = 16;
+
+// A half-received message is dropped once no chunk of it has arrived for this long
+pub const CHUNK_TIMEOUT_MS: u64 = 30_000;
+
+// Bytes `c` occupies once escaped inside a JSON string
+fn escaped_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\u{08}' | '\u{0c}' | '\n' | '\r' | '\t' => 2,
+        c if (c as u32) < 0x20 => 6,
+        c => c.len_utf8(),
+    }
+}
+
+// Split a serialized frame into `chunk` messages that each serialize to at most
+// `max_size` bytes
+pub fn split_frame(frame: &str, max_size: usize, message_id: &str) -> Result<Vec<WebSocketMessage>, FrameError> {
+    let checksum = checksum(frame);
+    // Envelope size with the widest possible seq/total, so every piece fits
+    let widest = frame.len() as u32;
+    let envelope = WebSocketMessage::new(
+        message_type::CHUNK,
+        to_value(&ChunkPayload {
+            message_id: message_id.into(),
+            seq: widest,
+            total: widest,
+            data: String::new(),
+            checksum: Some(checksum.clone()),
+        }),
+        None,
+    )
+    .to_json()
+    .len();
+    let budget = max_size.saturating_sub(envelope);
+    if budget < 6 {
+        return Err(FrameError::TooLarge(format!("Max message size {} is too small to carry chunks", max_size)));
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    for (i, c) in frame.char_indices() {
+        let len = escaped_len(c);
+        if used + len > budget {
+            pieces.push(&frame[start..i]);
+            start = i;
+            used = 0;
+        }
+        used += len;
+    }
+    pieces.push(&frame[start..]);
+
+    let total = pieces.len() as u32;
+    Ok(pieces
+        .into_iter()
+        .enumerate()
+        .map(|(seq, data)| {
+            let payload = ChunkPayload {
+                message_id: message_id.into(),
+                seq: seq as u32,
+                total,
+                data: data.into(),
+                checksum: Some(checksum.clone()),
+            };
+            WebSocketMessage::new(message_type::CHUNK, to_value(&payload), None)
+        })
+        .collect())
+}
+
+struct Partial {
+    total: u32,
+    checksum: Option<String>,
+    parts: BTreeMap<u32, String>,
+    bytes: usize,
+    last_chunk_at: u64,
+}
+
+// Collects `chunk` messages until every piece of a message has arrived
+pub struct ChunkAssembler {
+    partial: BTreeMap<String, Partial>,
+    // Limit on the bytes buffered across every half-received message
+    max_size: usize,
+    buffered: usize,
+}
+
+impl Default for ChunkAssembler {
+    fn default() -> ChunkAssembler {
+        ChunkAssembler::new(DEFAULT_MAX_ASSEMBLED_SIZE)
+    }
+}
+
+impl ChunkAssembler {
+    pub fn new(max_size: usize) -> ChunkAssembler {
+        ChunkAssembler { partial: BTreeMap::new(), max_size, buffered: 0 }
+    }
+
+    fn discard(&mut self, message_id: &str) -> Option<Partial> {
+        let partial = self.partial.remove(message_id)?;
+        self.buffered -= partial.bytes;
+        Some(partial)
+    }
+
+    // Add one piece, received at `now_ms`; returns the original frame once it is complete
+    // and verified
+    pub fn accept(&mut self, chunk: ChunkPayload, now_ms: u64) -> Result<Option<String>, FrameError> {
+        self.evict_stale(now_ms);
+        if chunk.seq >= chunk.total {
+            self.discard(&chunk.message_id);
+            return Err(FrameError::Chunk(format!(
+                "Chunk {} of message {} is out of range (total {})",
+                chunk.seq, chunk.message_id, chunk.total
+            )));
+        }
+        // Every chunk carries at least one byte
+        if chunk.total as usize > self.max_size {
+            self.discard(&chunk.message_id);
+            return Err(FrameError::TooLarge(format!("Chunked message {} of {} pieces exceeds {} bytes", chunk.message_id, chunk.total, self.max_size)));
+        }
+        if !self.partial.contains_key(&chunk.message_id) && self.partial.len() >= MAX_PARTIAL_MESSAGES {
+            return Err(FrameError::Chunk(format!("More than {} chunked messages are in progress", MAX_PARTIAL_MESSAGES)));
+        }
+        let partial = self.partial.entry(chunk.message_id.clone()).or_insert_with(|| Partial {
+            total: chunk.total,
+            checksum: chunk.checksum.clone(),
+            parts: BTreeMap::new(),
+            bytes: 0,
+            last_chunk_at: now_ms,
+        });
+        if partial.total != chunk.total || partial.checksum != chunk.checksum {
+            self.discard(&chunk.message_id);
+            return Err(FrameError::Chunk(format!("Chunks of message {} disagree on their count or checksum", chunk.message_id)));
+        }
+        partial.last_chunk_at = now_ms;
+        if !partial.parts.contains_key(&chunk.seq) {
+            partial.bytes += chunk.data.len();
+            self.buffered += chunk.data.len();
+            partial.parts.insert(chunk.seq, chunk.data);
+        }
+        if self.buffered > self.max_size {
+            self.discard(&chunk.message_id);
+            return Err(FrameError::TooLarge(format!("Chunked message {} exceeds {} bytes", chunk.message_id, self.max_size)));
+        }
+        if partial.parts.len() < partial.total as usize {
+            return Ok(None);
+        }
+        let partial = self.discard(&chunk.message_id).expect("partial message exists");
+        let frame: String = partial.parts.into_values().collect();
+        verify(&frame, partial.checksum.as_deref())?;
+        Ok(Some(frame))
+    }
+
+    // Drop messages no chunk has arrived for in `CHUNK_TIMEOUT_MS`
+    pub fn evict_stale(&mut self, now_ms: u64) {
+        let stale: Vec<String> = self
+            .partial
+            .iter()
+            .filter(|(_, partial)| now_ms.saturating_sub(partial.last_chunk_at) > CHUNK_TIMEOUT_MS)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in stale {
+            self.discard(&id);
+        }
+    }
+
+    // Drop half-received messages, e.g. after a reconnect
+    pub fn clear(&mut self) {
+        self.partial.clear();
+        self.buffered = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(message: &WebSocketMessage) -> ChunkPayload {
+        serde_json::from_value(message.payload.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let frame = r#"{"type":"result","payload":{"rows":["a\"b","é\n","xyz"]},"id":"q1"}"#.repeat(20);
+        let chunks = split_frame(&frame, 200, "q1").unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|c| c.to_json().len() <= 200));
+
+        let mut assembler = ChunkAssembler::default();
+        let mut result = None;
+        // Arrival order doesn't matter
+        for chunk in chunks.iter().rev() {
+            result = assembler.accept(payload(chunk), 0).unwrap();
+        }
+        assert_eq!(result.unwrap(), frame);
+    }
+
+    #[test]
+    fn test_assembler_limits() {
+        let mut assembler = ChunkAssembler::new(4);
+        let chunk = |seq, data: &str| ChunkPayload { message_id: "m".into(), seq, total: 2, data: data.into(), checksum: None };
+        assert_eq!(assembler.accept(chunk(0, "abc"), 0).unwrap(), None);
+        assert!(matches!(assembler.accept(chunk(1, "de"), 0), Err(FrameError::TooLarge(_))));
+        assert!(matches!(assembler.accept(chunk(2, "x"), 0), Err(FrameError::Chunk(_))));
+        assert!(split_frame("abc", 10, "m").is_err());
+        // A declared count above the byte limit is refused up front
+        let huge = ChunkPayload { message_id: "h".into(), seq: 0, total: u32::MAX, data: "a".into(), checksum: None };
+        assert!(matches!(assembler.accept(huge, 0), Err(FrameError::TooLarge(_))));
+    }
+
+    #[test]
+    fn test_partial_messages_are_bounded() {
+        let mut assembler = ChunkAssembler::new(40);
+        let chunk = |id: usize, data: &str| ChunkPayload { message_id: format!("m{}", id), seq: 0, total: 2, data: data.into(), checksum: None };
+        // The byte limit covers every half-received message together
+        for id in 0..4 {
+            assert_eq!(assembler.accept(chunk(id, "0123456789"), 0).unwrap(), None);
+        }
+        assert!(matches!(assembler.accept(chunk(4, "x"), 0), Err(FrameError::TooLarge(_))));
+
+        let mut assembler = ChunkAssembler::default();
+        for id in 0..MAX_PARTIAL_MESSAGES {
+            assembler.accept(chunk(id, "a"), 0).unwrap();
+        }
+        assert!(matches!(assembler.accept(chunk(99, "a"), 0), Err(FrameError::Chunk(_))));
+        // Stale ones make room again
+        assert_eq!(assembler.accept(chunk(99, "a"), CHUNK_TIMEOUT_MS + 1).unwrap(), None);
+        assert_eq!(assembler.partial.len(), 1);
+    }
+
+    #[test]
+    fn test_corrupted_chunk_fails_integrity_check() {
+        let frame = "0123456789".repeat(50);
+        let mut chunks: Vec<ChunkPayload> = split_frame(&frame, 200, "m").unwrap().iter().map(payload).collect();
+        chunks[1].data.replace_range(0..1, "X");
+        let mut assembler = ChunkAssembler::default();
+        let results: Vec<_> = chunks.into_iter().map(|chunk| assembler.accept(chunk, 0)).collect();
+        assert!(matches!(results.last(), Some(Err(FrameError::Integrity(_)))));
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::integrity::{checksum, verify};
+use crate::{message_type, to_value, FrameError, WebSocketMessage};
+
+// Messages at least this large are compressed when both sides support it
+pub const DEFAULT_COMPRESSION_THRESHOLD: usize = 16 * 1024;
+
+const DEFLATE: &str = "deflate";
+const LEVEL: u8 = 6;
+
+// Payload of a `compressed` message: a whole serialized frame, deflated and
+// base64-encoded, with the CRC-32 of the original frame
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct CompressedPayload {
+    pub encoding: String,
+    pub data: String,
+    #[serde(rename = "originalSize")]
+    pub original_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub checksum: Option<String>,
+}
+
+// Wrap `frame` in a `compressed` message carrying `id`, or None when compression
+// wouldn't make it smaller
+pub fn compress_frame(frame: &str, id: Option<String>) -> Option<WebSocketMessage> {
+    let deflated = miniz_oxide::deflate::compress_to_vec(frame.as_bytes(), LEVEL);
+    let payload = CompressedPayload {
+        encoding: DEFLATE.to_string(),
+        data: STANDARD.encode(deflated),
+        original_size: frame.len(),
+        checksum: Some(checksum(frame)),
+    };
+    let message = WebSocketMessage::new(message_type::COMPRESSED, to_value(&payload), id);
+    (message.to_json().len() < frame.len()).then_some(message)
+}
+
+// Recover the original frame, refusing to inflate beyond `max_size` bytes. Corrupt
+// data is reported as an integrity error when the sender included a checksum.
+pub fn decompress_frame(payload: &CompressedPayload, max_size: usize) -> Result<String, FrameError> {
+    if payload.encoding != DEFLATE {
+        return Err(FrameError::Compression(format!("Unsupported compression encoding '{}'", payload.encoding)));
+    }
+    if payload.original_size > max_size {
+        return Err(FrameError::TooLarge(format!(
+            "Compressed message expands to {} bytes, above the {} byte limit",
+            payload.original_size, max_size
+        )));
+    }
+    // With a checksum present, undecodable data can only mean corruption in transit
+    let corrupt = |message: String| match payload.checksum {
+        Some(_) => FrameError::Integrity(message),
+        None => FrameError::Compression(message),
+    };
+    let deflated = STANDARD
+        .decode(payload.data.as_bytes())
+        .map_err(|e| corrupt(format!("Compressed data is not valid base64: {}", e)))?;
+    let inflated = miniz_oxide::inflate::decompress_to_vec_with_limit(&deflated, max_size)
+        .map_err(|e| corrupt(format!("Failed to inflate message: {:?}", e.status)))?;
+    let frame = String::from_utf8(inflated).map_err(|e| corrupt(format!("Inflated message is not valid UTF-8: {}", e)))?;
+    verify(&frame, payload.checksum.as_deref())?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_limits() {
+        let frame = r#"{"type":"result","payload":{"rows":[{"id":1,"name":"row"}]},"id":"q1"}"#.repeat(200);
+        let message = compress_frame(&frame, Some("q1".to_string())).unwrap();
+        assert!(message.to_json().len() < frame.len() / 5);
+        assert_eq!(message.id.as_deref(), Some("q1"));
+
+        let payload: CompressedPayload = serde_json::from_value(message.payload).unwrap();
+        assert_eq!(decompress_frame(&payload, usize::MAX).unwrap(), frame);
+        assert!(decompress_frame(&payload, 100).is_err());
+
+        // Tiny frames grow when compressed and are left alone
+        assert!(compress_frame(r#"{"type":"ping"}"#, None).is_none());
+    }
+}
@@ -0,0 +1,289 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use aes_gcm::aead::{Aead, KeyInit as _, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{message_type, to_value, FrameError, WebSocketMessage};
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Shortest accepted key, in bytes
+pub const KEY_SIZE: usize = 32;
+// Random bytes each side sends in `hello`, so every connection gets keys of its own
+pub const SALT_SIZE: usize = 16;
+// How far behind the newest counter a frame may still arrive. The bridge answers
+// `cancel` ahead of the frames queued before it, so frames don't always open in order.
+pub const REPLAY_WINDOW: u64 = 128;
+
+const ALGORITHM: &str = "aes-256-gcm";
+
+// Payload of an `encrypted` message: a whole serialized frame, encrypted and
+// authenticated together with the message id. The counter makes up the nonce.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct EncryptedPayload {
+    pub algorithm: String,
+    pub counter: u64,
+    // Ciphertext followed by the 16-byte tag
+    pub data: String,
+}
+
+// Which end of the connection a cipher is for; each direction has its own key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Bridge,
+}
+
+// The shared secret, from which every connection's keys are derived
+#[derive(Clone)]
+pub struct FrameKey {
+    master: HmacSha256,
+}
+
+impl core::fmt::Debug for FrameKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("FrameKey(..)")
+    }
+}
+
+fn keyed(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC accepts any key length")
+}
+
+impl FrameKey {
+    pub fn new(secret: &[u8]) -> Result<FrameKey, String> {
+        if secret.len() < KEY_SIZE {
+            return Err(format!("Encryption key must be at least {} bytes, got {}", KEY_SIZE, secret.len()));
+        }
+        let master = keyed(secret).chain_update(b"bridge-aes-256-gcm").finalize().into_bytes();
+        Ok(FrameKey { master: keyed(&master) })
+    }
+
+    pub fn from_base64(secret: &str) -> Result<FrameKey, String> {
+        let secret = STANDARD.decode(secret.trim()).map_err(|e| format!("Encryption key is not valid base64: {}", e))?;
+        FrameKey::new(&secret)
+    }
+
+    // Keys for one connection, from the salts both sides sent in `hello`
+    pub fn cipher(&self, side: Side, client_salt: &[u8], bridge_salt: &[u8]) -> FrameCipher {
+        let derive = |label: &[u8]| {
+            let key = self
+                .master
+                .clone()
+                .chain_update(label)
+                .chain_update((client_salt.len() as u64).to_be_bytes())
+                .chain_update(client_salt)
+                .chain_update(bridge_salt)
+                .finalize()
+                .into_bytes();
+            Aes256Gcm::new_from_slice(&key).expect("HMAC-SHA256 output is an AES-256 key")
+        };
+        let (to_bridge, to_client) = (derive(b"client-to-bridge"), derive(b"bridge-to-client"));
+        let (sealing, opening) = match side {
+            Side::Client => (to_bridge, to_client),
+            Side::Bridge => (to_client, to_bridge),
+        };
+        FrameCipher { sealing, opening, sent: 0, newest: 0, seen: 0 }
+    }
+
+    // `cipher`, from the base64 salts as `hello` carries them
+    pub fn connection(&self, side: Side, client_salt: &str, bridge_salt: &str) -> Result<FrameCipher, FrameError> {
+        let decode = |salt: &str| match STANDARD.decode(salt) {
+            Ok(salt) if salt.len() == SALT_SIZE => Ok(salt),
+            _ => Err(FrameError::Encryption(format!("encryptionSalt must be {} base64 bytes", SALT_SIZE))),
+        };
+        Ok(self.cipher(side, &decode(client_salt)?, &decode(bridge_salt)?))
+    }
+}
+
+pub fn encode_salt(salt: &[u8; SALT_SIZE]) -> String {
+    STANDARD.encode(salt)
+}
+
+// A connection's keys, the counter of the last frame sent and the counters already
+// opened: the newest and, as bits, the `REPLAY_WINDOW` before it
+#[derive(Clone)]
+pub struct FrameCipher {
+    sealing: Aes256Gcm,
+    opening: Aes256Gcm,
+    sent: u64,
+    newest: u64,
+    seen: u128,
+}
+
+impl core::fmt::Debug for FrameCipher {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FrameCipher").field("sent", &self.sent).field("newest", &self.newest).finish_non_exhaustive()
+    }
+}
+
+// Four zero bytes, then the counter; counters never repeat under one key
+fn nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+// Binds the id so a response can't be replayed under another request's id
+fn associated_data(id: Option<&str>) -> Vec<u8> {
+    let id = id.unwrap_or_default().as_bytes();
+    let mut aad = (id.len() as u64).to_be_bytes().to_vec();
+    aad.extend_from_slice(id);
+    aad
+}
+
+impl FrameCipher {
+    // Wrap `frame` in an `encrypted` message carrying `id`, under the next counter
+    pub fn seal(&mut self, frame: &str, id: Option<String>) -> WebSocketMessage {
+        self.sent += 1;
+        let aad = associated_data(id.as_deref());
+        let data = self
+            .sealing
+            .encrypt(Nonce::from_slice(&nonce(self.sent)), Payload { msg: frame.as_bytes(), aad: &aad })
+            .expect("AES-GCM encrypts any frame that fits in memory");
+        let payload = EncryptedPayload { algorithm: ALGORITHM.to_string(), counter: self.sent, data: STANDARD.encode(data) };
+        WebSocketMessage::new(message_type::ENCRYPTED, to_value(&payload), id)
+    }
+
+    // Recover a frame without recording its counter, for a peek that may hand the
+    // frame on to be opened for real
+    pub fn decrypt(&self, payload: &EncryptedPayload, id: Option<&str>) -> Result<String, FrameError> {
+        if payload.algorithm != ALGORITHM {
+            return Err(FrameError::Encryption(format!("Unsupported encryption algorithm '{}'", payload.algorithm)));
+        }
+        if !self.is_fresh(payload.counter) {
+            return Err(FrameError::Encryption(format!("Rejected a replayed or outdated message (counter {})", payload.counter)));
+        }
+        let data = STANDARD
+            .decode(&payload.data)
+            .map_err(|e| FrameError::Encryption(format!("Invalid encrypted data: {}", e)))?;
+        let aad = associated_data(id);
+        let frame = self
+            .opening
+            .decrypt(Nonce::from_slice(&nonce(payload.counter)), Payload { msg: &data, aad: &aad })
+            .map_err(|_| FrameError::Encryption("Encrypted message failed authentication".to_string()))?;
+        String::from_utf8(frame).map_err(|_| FrameError::Encryption("Decrypted message is not valid UTF-8".to_string()))
+    }
+
+    // Recover a frame and record its counter, so the same frame can't be opened again
+    pub fn open(&mut self, payload: &EncryptedPayload, id: Option<&str>) -> Result<String, FrameError> {
+        let frame = self.decrypt(payload, id)?;
+        self.accept(payload.counter);
+        Ok(frame)
+    }
+
+    // Record a counter `decrypt` accepted
+    pub fn accept(&mut self, counter: u64) {
+        if counter > self.newest {
+            let shift = counter - self.newest;
+            self.seen = if shift >= REPLAY_WINDOW { 0 } else { self.seen << shift };
+            // The old newest is now `shift` behind
+            if shift <= REPLAY_WINDOW && self.newest > 0 {
+                self.seen |= 1 << (shift - 1);
+            }
+            self.newest = counter;
+        } else if counter < self.newest {
+            self.seen |= 1 << (self.newest - counter - 1);
+        }
+    }
+
+    // Counters start at 1; one is fresh when it's newer than any seen, or unseen and
+    // within the window
+    fn is_fresh(&self, counter: u64) -> bool {
+        if counter == 0 || counter == self.newest {
+            return false;
+        }
+        if counter > self.newest {
+            return true;
+        }
+        let behind = self.newest - counter;
+        behind <= REPLAY_WINDOW && self.seen & (1 << (behind - 1)) == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(message: &WebSocketMessage) -> EncryptedPayload {
+        serde_json::from_value(message.payload.clone()).unwrap()
+    }
+
+    fn pair() -> (FrameCipher, FrameCipher) {
+        let key = FrameKey::new(&[7u8; 32]).unwrap();
+        (key.cipher(Side::Client, &[1u8; SALT_SIZE], &[2u8; SALT_SIZE]), key.cipher(Side::Bridge, &[1u8; SALT_SIZE], &[2u8; SALT_SIZE]))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (mut client, mut bridge) = pair();
+        let frame = r#"{"type":"query","payload":{"sql":"SELECT * FROM secrets WHERE note = 'a longer frame than one block'"},"id":"q1"}"#;
+        let message = client.seal(frame, Some("q1".to_string()));
+        assert!(!message.to_json().contains("secrets"));
+        assert_eq!(payload(&message).counter, 1);
+        assert_eq!(bridge.open(&payload(&message), Some("q1")).unwrap(), frame);
+        let reply = bridge.seal(r#"{"type":"result"}"#, Some("q1".to_string()));
+        assert_eq!(client.open(&payload(&reply), Some("q1")).unwrap(), r#"{"type":"result"}"#);
+        // Each direction has its own key
+        assert!(client.decrypt(&payload(&client.clone().seal(frame, None)), None).is_err());
+
+        let key = FrameKey::new(&[7u8; 32]).unwrap();
+        let salt = encode_salt(&[1u8; SALT_SIZE]);
+        assert!(key.connection(Side::Client, &salt, &encode_salt(&[2u8; SALT_SIZE])).is_ok());
+        assert!(key.connection(Side::Client, &salt, "c2hvcnQ=").is_err());
+        assert!(FrameKey::new(&[7u8; 16]).is_err());
+        assert!(FrameKey::from_base64(&STANDARD.encode([7u8; 32])).is_ok());
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let (mut client, bridge) = pair();
+        let message = client.seal(r#"{"type":"query"}"#, Some("q1".to_string()));
+        // Moved to another request
+        assert!(bridge.decrypt(&payload(&message), Some("q2")).is_err());
+        // Another connection's keys
+        let key = FrameKey::new(&[7u8; 32]).unwrap();
+        let other = key.cipher(Side::Bridge, &[1u8; SALT_SIZE], &[3u8; SALT_SIZE]);
+        assert!(other.decrypt(&payload(&message), Some("q1")).is_err());
+        // Flipped bit, or a counter other than the one it was sealed under
+        let mut flipped = payload(&message);
+        let mut data = STANDARD.decode(&flipped.data).unwrap();
+        data[0] ^= 1;
+        flipped.data = STANDARD.encode(data);
+        assert_eq!(bridge.decrypt(&flipped, Some("q1")).unwrap_err().code(), "ENCRYPTION_ERROR");
+        let recounted = EncryptedPayload { counter: 2, ..payload(&message) };
+        assert!(bridge.decrypt(&recounted, Some("q1")).is_err());
+    }
+
+    #[test]
+    fn test_replayed_frames_rejected() {
+        let (mut client, mut bridge) = pair();
+        let frames: Vec<EncryptedPayload> = (0..4).map(|i| payload(&client.seal(&format!("{{\"n\":{}}}", i), None))).collect();
+        assert!(bridge.open(&frames[0], None).is_ok());
+        assert!(bridge.open(&frames[0], None).is_err());
+        // Out of order within the window is fine, once
+        assert!(bridge.open(&frames[2], None).is_ok());
+        assert!(bridge.open(&frames[1], None).is_ok());
+        assert!(bridge.open(&frames[1], None).is_err());
+        assert!(bridge.open(&frames[2], None).is_err());
+        // A peek doesn't use the counter up
+        assert!(bridge.decrypt(&frames[3], None).is_ok());
+        assert!(bridge.open(&frames[3], None).is_ok());
+
+        // Too far behind the newest
+        let late = payload(&client.seal("{}", None));
+        for _ in 0..=REPLAY_WINDOW {
+            let frame = payload(&client.seal("{}", None));
+            bridge.open(&frame, None).unwrap();
+        }
+        assert!(bridge.open(&late, None).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+use alloc::format;
+use alloc::string::String;
+
+use crate::FrameError;
+
+// CRC-32 (IEEE 802.3), table built at compile time
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc = CRC32_TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// Checksum carried alongside chunked and compressed frames
+pub fn checksum(frame: &str) -> String {
+    format!("{:08x}", crc32(frame.as_bytes()))
+}
+
+// Compare a received frame against the checksum the sender computed, if it sent one
+pub fn verify(frame: &str, expected: Option<&str>) -> Result<(), FrameError> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&checksum(frame)) => Err(FrameError::Integrity(format!(
+            "Checksum mismatch: expected {}, got {} ({} bytes); the message was corrupted in transit",
+            expected,
+            checksum(frame),
+            frame.len()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(checksum("a"), "e8b7be43");
+        assert!(verify("a", Some("e8b7be43")).is_ok());
+        assert!(verify("a", None).is_ok());
+        assert!(matches!(verify("b", Some("e8b7be43")), Err(FrameError::Integrity(_))));
+    }
+}
@@ -0,0 +1,952 @@
+// Wire types shared by the WASM client and the bridge server. Only `alloc` is
+// required, so the crate builds for any target either side runs on.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+// Values of `WebSocketMessage::message_type`
+pub mod message_type {
+    pub const HELLO: &str = "hello";
+    pub const AUTH: &str = "auth";
+    pub const PING: &str = "ping";
+    pub const QUERY: &str = "query";
+    pub const RESULT: &str = "result";
+    pub const ERROR: &str = "error";
+    pub const POOL_STATS: &str = "pool_stats";
+    pub const LISTEN: &str = "listen";
+    pub const UNLISTEN: &str = "unlisten";
+    pub const NOTIFICATION: &str = "notification";
+    pub const LISTEN_LOST: &str = "listen_lost";
+    pub const SUBSCRIBE_CHANGES: &str = "subscribe_changes";
+    pub const UNSUBSCRIBE_CHANGES: &str = "unsubscribe_changes";
+    pub const CHANGE: &str = "change";
+    pub const CHUNK: &str = "chunk";
+    pub const COMPRESSED: &str = "compressed";
+    pub const PROGRESS: &str = "progress";
+    pub const CANCEL: &str = "cancel";
+    pub const ENCRYPTED: &str = "encrypted";
+    pub const PREPARED_STATEMENTS: &str = "prepared_statements";
+    pub const DEALLOCATE: &str = "deallocate";
+    pub const ADMIN_STATS: &str = "admin_stats";
+    pub const SHUTDOWN: &str = "shutdown";
+    pub const FETCH_MORE: &str = "fetch_more";
+    pub const PIN: &str = "pin";
+    pub const UNPIN: &str = "unpin";
+    pub const DESCRIBE: &str = "describe";
+    pub const SCHEMA_CHANGE: &str = "schema_change";
+    pub const ROW_DELTA: &str = "row_delta";
+    pub const THROTTLED: &str = "throttled";
+}
+
+// WebSocket close codes a bridge closes with to say why. The close reason starts with
+// the matching `close_reason` token, e.g. "auth_expired: token expired", so bridges that
+// must use a standard code can still be told apart.
+pub mod close_code {
+    pub const AUTH_EXPIRED: u16 = 4001;
+    pub const SHUTDOWN: u16 = 4002;
+    pub const PROTOCOL_VIOLATION: u16 = 4003;
+    pub const IDLE_TIMEOUT: u16 = 4004;
+}
+
+pub mod close_reason {
+    pub const AUTH_EXPIRED: &str = "auth_expired";
+    pub const SHUTDOWN: &str = "shutdown";
+    pub const PROTOCOL_VIOLATION: &str = "protocol_violation";
+    pub const IDLE_TIMEOUT: &str = "idle_timeout";
+}
+
+// Version spoken by this build; bumped on incompatible wire changes
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Optional features a peer supports; anything a peer doesn't mention is off
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct Capabilities {
+    // Queries may send parameters as packed bytes in `binaryParams`
+    #[serde(default)]
+    pub binary: bool,
+    #[serde(default)]
+    pub streaming: bool,
+    #[serde(default)]
+    pub compression: bool,
+    #[serde(default)]
+    pub notifications: bool,
+    #[serde(default)]
+    pub chunking: bool,
+    #[serde(default)]
+    pub progress: bool,
+    #[serde(default)]
+    pub cancel: bool,
+    // Frames after `hello` are encrypted with a key both sides were given out of band
+    #[serde(default)]
+    pub encryption: bool,
+    // The bridge keeps a disconnected session's backend for a while so it can be resumed
+    #[serde(default)]
+    pub resume: bool,
+    // The bridge can answer reads from its own result cache when a query asks it to
+    #[serde(rename = "resultCache", default)]
+    pub result_cache: bool,
+    // Queries with a `pageSize` return one page and a token for the rest, which stays on
+    // the bridge in a cursor until fetched with `fetch_more`
+    #[serde(default)]
+    pub continuation: bool,
+    // Raw results send their `columns` once per shape; later results with the same shape
+    // carry only its `schemaId`, which the client resolves from what it was sent
+    #[serde(rename = "schemaCache", default)]
+    pub schema_cache: bool,
+    // The bridge forwards DDL seen by its event triggers as `schema_change` messages
+    #[serde(rename = "schemaEvents", default)]
+    pub schema_events: bool,
+    // NOTIFY payloads that are row deltas arrive as `row_delta` messages, which live
+    // queries apply to their last result instead of re-running
+    #[serde(rename = "rowDeltas", default)]
+    pub row_deltas: bool,
+    // A query over the bridge's rate limit is answered with `throttled` and a retry-after
+    // instead of an error, and the client sends it again once that has passed
+    #[serde(default)]
+    pub throttling: bool,
+}
+
+impl Capabilities {
+    // Features both sides support
+    pub fn intersect(&self, other: &Capabilities) -> Capabilities {
+        Capabilities {
+            binary: self.binary && other.binary,
+            streaming: self.streaming && other.streaming,
+            compression: self.compression && other.compression,
+            notifications: self.notifications && other.notifications,
+            chunking: self.chunking && other.chunking,
+            progress: self.progress && other.progress,
+            cancel: self.cancel && other.cancel,
+            encryption: self.encryption && other.encryption,
+            resume: self.resume && other.resume,
+            result_cache: self.result_cache && other.result_cache,
+            continuation: self.continuation && other.continuation,
+            schema_cache: self.schema_cache && other.schema_cache,
+            schema_events: self.schema_events && other.schema_events,
+            row_deltas: self.row_deltas && other.row_deltas,
+            throttling: self.throttling && other.throttling,
+        }
+    }
+}
+
+// Payload of `hello`, sent by the client after connecting and echoed back by the server
+// with its own values
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct HelloPayload {
+    pub version: u32,
+    // Oldest peer version this side still talks to
+    #[serde(rename = "minVersion", default)]
+    pub min_version: u32,
+    #[serde(default)]
+    pub capabilities: Capabilities,
+    // Largest frame this side accepts
+    #[serde(rename = "maxMessageSize", skip_serializing_if = "Option::is_none", default)]
+    pub max_message_size: Option<usize>,
+    // Statement policy the client enforces, so the bridge can enforce it too
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub policy: Option<StatementPolicy>,
+    // From the client, a session to resume; from the server, the token that resumes this one
+    #[serde(rename = "sessionToken", skip_serializing_if = "Option::is_none", default)]
+    pub session_token: Option<String>,
+    // Set by the server when it reattached the session named by the client's token
+    #[serde(skip_serializing_if = "core::ops::Not::not", default)]
+    pub resumed: bool,
+    // From the client, the pool behavior it wants; from the server, what it granted
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub pool: Option<PoolRequest>,
+    // Base64 random bytes the connection's encryption keys are derived from, when both
+    // sides encrypt
+    #[serde(rename = "encryptionSalt", skip_serializing_if = "Option::is_none", default)]
+    pub encryption_salt: Option<String>,
+}
+
+// How the bridge backs a session with Postgres connections
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(rename_all = "lowercase")]
+pub enum PoolMode {
+    // Back to the pool after every query outside a transaction: the most clients per
+    // backend, but session settings don't last
+    Pooled,
+    // One pooled connection for the whole session
+    #[default]
+    Session,
+    // A connection of its own, opened outside the pool
+    Dedicated,
+}
+
+// Pool behavior negotiated in `hello`; unset fields take the bridge's defaults
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(default)]
+pub struct PoolRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<PoolMode>,
+    // Keep prepared statements for repeated SQL
+    #[serde(rename = "statementCache", skip_serializing_if = "Option::is_none")]
+    pub statement_cache: Option<bool>,
+    // How many, up to the bridge's own limit
+    #[serde(rename = "maxStatements", skip_serializing_if = "Option::is_none")]
+    pub max_statements: Option<usize>,
+    // Idle pool connections to keep warm for the session's queries
+    #[serde(rename = "minIdle", skip_serializing_if = "Option::is_none")]
+    pub min_idle: Option<usize>,
+    // Connections the session hands back are closed instead of kept once this many are idle
+    #[serde(rename = "maxIdle", skip_serializing_if = "Option::is_none")]
+    pub max_idle: Option<usize>,
+}
+
+// Outcome of a handshake: the version both sides speak and the shared features.
+// Version 0 means the peer predates `hello`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct Negotiated {
+    pub version: u32,
+    pub capabilities: Capabilities,
+    // Largest frame the peer accepts; outgoing messages above it are chunked
+    #[serde(rename = "peerMaxMessageSize")]
+    pub peer_max_message_size: Option<usize>,
+}
+
+impl HelloPayload {
+    pub fn new(min_version: u32, capabilities: Capabilities, max_message_size: usize) -> HelloPayload {
+        HelloPayload {
+            version: PROTOCOL_VERSION,
+            min_version,
+            capabilities,
+            max_message_size: Some(max_message_size),
+            policy: None,
+            session_token: None,
+            resumed: false,
+            pool: None,
+            encryption_salt: None,
+        }
+    }
+
+    // Agree on a version and feature set with a peer, or explain why there is none
+    pub fn negotiate(&self, peer: &HelloPayload) -> Result<Negotiated, String> {
+        if peer.min_version > self.version {
+            return Err(alloc::format!(
+                "Peer requires protocol version {} or newer, this side speaks {}",
+                peer.min_version, self.version
+            ));
+        }
+        if self.min_version > peer.version {
+            return Err(alloc::format!(
+                "Peer speaks protocol version {}, at least {} is required",
+                peer.version, self.min_version
+            ));
+        }
+        Ok(Negotiated {
+            version: self.version.min(peer.version),
+            capabilities: self.capabilities.intersect(&peer.capabilities),
+            peer_max_message_size: peer.max_message_size,
+        })
+    }
+}
+
+// Envelope for every frame in either direction
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct WebSocketMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub payload: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+impl WebSocketMessage {
+    pub fn new(message_type: &str, payload: Value, id: Option<String>) -> WebSocketMessage {
+        WebSocketMessage { message_type: message_type.to_string(), payload, id }
+    }
+
+    pub fn result(id: Option<String>, payload: Value) -> WebSocketMessage {
+        WebSocketMessage::new(message_type::RESULT, payload, id)
+    }
+
+    pub fn error(id: Option<String>, code: &str, message: impl Into<String>) -> WebSocketMessage {
+        WebSocketMessage::new(message_type::ERROR, json!({ "message": message.into(), "code": code }), id)
+    }
+
+    pub fn notification(channel: &str, payload: &str) -> WebSocketMessage {
+        let payload = NotificationPayload { channel: channel.to_string(), payload: payload.to_string() };
+        WebSocketMessage::new(message_type::NOTIFICATION, to_value(&payload), None)
+    }
+
+    pub fn row_delta(channel: &str, delta: RowDelta) -> WebSocketMessage {
+        let payload = RowDeltaPayload { channel: channel.to_string(), delta };
+        WebSocketMessage::new(message_type::ROW_DELTA, to_value(&payload), None)
+    }
+
+    pub fn throttled(id: Option<String>, retry_after_ms: u64, reason: impl Into<String>) -> WebSocketMessage {
+        let payload = ThrottledPayload { retry_after_ms, reason: reason.into() };
+        WebSocketMessage::new(message_type::THROTTLED, to_value(&payload), id)
+    }
+
+    pub fn listen_lost(reason: &str) -> WebSocketMessage {
+        WebSocketMessage::new(message_type::LISTEN_LOST, to_value(&ListenLostPayload { reason: reason.to_string() }), None)
+    }
+
+    pub fn progress(id: Option<String>, rows: u64, elapsed_ms: f64) -> WebSocketMessage {
+        WebSocketMessage::new(message_type::PROGRESS, to_value(&ProgressPayload { rows, elapsed_ms }), id)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| {
+            r#"{"type":"error","payload":{"message":"Unserializable response","code":"INTERNAL"}}"#.to_string()
+        })
+    }
+}
+
+// Serialize a payload struct; the types in this crate always serialize
+pub fn to_value<T: Serialize>(payload: &T) -> Value {
+    serde_json::to_value(payload).unwrap_or(Value::Null)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct QueryPayload {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub traceparent: Option<String>,
+    #[serde(rename = "idempotencyKey", skip_serializing_if = "Option::is_none", default)]
+    pub idempotency_key: Option<String>,
+    // Run under this statement_timeout instead of the session's
+    #[serde(rename = "statementTimeoutMs", skip_serializing_if = "Option::is_none", default)]
+    pub statement_timeout_ms: Option<u32>,
+    // The client's tenant, checked by bridges that bind keys to tenants
+    #[serde(rename = "tenantId", skip_serializing_if = "Option::is_none", default)]
+    pub tenant_id: Option<String>,
+    // Return cells undecoded, as rows of hex strings, with the column OIDs in `columns`
+    #[serde(skip_serializing_if = "core::ops::Not::not", default)]
+    pub raw: bool,
+    // Lets the bridge answer a read from its result cache
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache: Option<CacheControl>,
+    // Return at most this many rows, plus a continuation token when more remain
+    #[serde(rename = "pageSize", skip_serializing_if = "Option::is_none", default)]
+    pub page_size: Option<u32>,
+    // By position, parameters sent in binary instead of in `params`, whose entry is null
+    #[serde(rename = "binaryParams", skip_serializing_if = "Option::is_none", default)]
+    pub binary_params: Option<Vec<Option<binary::BinaryParam>>>,
+    // Caller-chosen labels, like `{ feature: "orders-grid" }`, for attributing load
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tags: Option<QueryTags>,
+    // Postgres type names to prepare the statement with, by position, like `"int8"` or
+    // `"uuid[]"`; null leaves a parameter's type to be inferred
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub types: Option<Vec<Option<String>>>,
+}
+
+// Labels attached to a query, echoed in its result and written to the bridge's log
+pub type QueryTags = BTreeMap<String, String>;
+
+// Cache hints for one read query, honored by bridges with the `resultCache` capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(default)]
+pub struct CacheControl {
+    // Oldest cached result the query accepts; without it the cache isn't read
+    #[serde(rename = "ttlMs", skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+    // Names the cached result; defaults to the SQL and parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    // Run the query even if a cached result is fresh, and cache its result
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub bypass: bool,
+}
+
+// How a result relates to the bridge's cache, on results of queries that sent hints
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct CacheStatus {
+    pub hit: bool,
+    // Time since the result was produced; 0 for a result that was just run
+    #[serde(rename = "ageMs")]
+    pub age_ms: u64,
+}
+
+// Where a query's time went, in fractional milliseconds. The bridge sets the first two;
+// the client adds its share when the result arrives.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(default)]
+pub struct QueryTiming {
+    // From the query message arriving to its result being sent, including waits for a
+    // pooled connection and for session setup
+    #[serde(rename = "bridgeMs", skip_serializing_if = "Option::is_none")]
+    pub bridge_ms: Option<f64>,
+    // Executing the statement and reading its rows; 0 for a result served from a cache
+    #[serde(rename = "databaseMs", skip_serializing_if = "Option::is_none")]
+    pub database_ms: Option<f64>,
+    // Waiting in the client for the rate and concurrency limits
+    #[serde(rename = "queueMs", skip_serializing_if = "Option::is_none")]
+    pub queue_ms: Option<f64>,
+    // From sending the query to its result arriving
+    #[serde(rename = "roundTripMs", skip_serializing_if = "Option::is_none")]
+    pub round_trip_ms: Option<f64>,
+    // The round trip less the bridge's part: the network and both ends' message handling
+    #[serde(rename = "networkMs", skip_serializing_if = "Option::is_none")]
+    pub network_ms: Option<f64>,
+    #[serde(rename = "totalMs", skip_serializing_if = "Option::is_none")]
+    pub total_ms: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct QueryResult {
+    pub sql: String,
+    pub params: Vec<Value>,
+    pub rows: Vec<Value>,
+    #[serde(rename = "rowCount")]
+    pub row_count: usize,
+    #[serde(rename = "executionTime")]
+    pub execution_time: f64,
+    pub timestamp: String,
+    // Only for raw queries
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub columns: Option<Vec<RawColumn>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache: Option<CacheStatus>,
+    // Set when a paged query has rows left; pass it to `fetch_more` for the next page
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub continuation: Option<String>,
+    // The query's tags, as sent
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tags: Option<QueryTags>,
+    // Names the shape of a raw result's `columns`; see `Capabilities::schema_cache`
+    #[serde(rename = "schemaId", skip_serializing_if = "Option::is_none", default)]
+    pub schema_id: Option<String>,
+    // Set by bridges that time their work; clients add their own share
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timing: Option<QueryTiming>,
+    // How busy the bridge was as it answered
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub load: Option<BridgeLoad>,
+}
+
+// Pressure on the bridge and its database, sent with query results so clients can back
+// off before queries start timing out
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct BridgeLoad {
+    // Messages from this connection waiting behind the one just answered
+    pub queued: usize,
+    // Sessions waiting for a pooled connection
+    #[serde(rename = "poolWaiting")]
+    pub pool_waiting: usize,
+    // Share of the pool checked out, from 0 to 1
+    pub saturation: f64,
+}
+
+// Payload of `throttled`: the query was refused for now and may be sent again after
+// `retryAfterMs`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ThrottledPayload {
+    #[serde(rename = "retryAfterMs")]
+    pub retry_after_ms: u64,
+    pub reason: String,
+}
+
+// Describes a raw result column the way Postgres does in RowDescription
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct RawColumn {
+    pub name: String,
+    #[serde(rename = "typeOid")]
+    pub type_oid: u32,
+    // 0 for text, 1 for binary
+    pub format: i16,
+}
+
+// Payload of `error` messages; only `message` is guaranteed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ErrorPayload {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub detail: Option<String>,
+    #[serde(rename = "sqlState", skip_serializing_if = "Option::is_none", default)]
+    pub sql_state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sql: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub params: Option<Vec<Value>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct AuthPayload {
+    pub token: String,
+}
+
+// SQLSTATEs of a database going down or not yet back: admin_shutdown, crash_shutdown and
+// cannot_connect_now
+pub const RESTART_SQL_STATES: [&str; 3] = ["57P01", "57P02", "57P03"];
+
+pub fn is_restart_sql_state(code: &str) -> bool {
+    RESTART_SQL_STATES.contains(&code)
+}
+
+// Sent by a bridge that is about to stop, before it closes the connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ShutdownPayload {
+    pub reason: String,
+    // How long clients should wait before reconnecting
+    #[serde(rename = "retryAfterMs", skip_serializing_if = "Option::is_none", default)]
+    pub retry_after_ms: Option<u64>,
+}
+
+// Channel the bridge's DDL event triggers NOTIFY on, each payload a `SchemaChange`
+pub const SCHEMA_CHANGE_CHANNEL: &str = "bridge_schema_change";
+
+// Payload of `schema_change`: one DDL command, as `pg_event_trigger_ddl_commands()` or
+// `pg_event_trigger_dropped_objects()` describe it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct SchemaChange {
+    // e.g. "ALTER TABLE"
+    #[serde(rename = "commandTag")]
+    pub command_tag: String,
+    // e.g. "table"
+    #[serde(rename = "objectType", skip_serializing_if = "Option::is_none", default)]
+    pub object_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub schema: Option<String>,
+    // The object's qualified name, e.g. "public.orders"
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub object: Option<String>,
+}
+
+// Payload of `listen` and `unlisten`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ChannelPayload {
+    pub channel: String,
+}
+
+// Reply to `listen` and `unlisten`: whether the session now LISTENs on the channel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ListenAck {
+    pub channel: String,
+    pub listening: bool,
+}
+
+// Payload of `listen_lost`, sent by the bridge when the connection holding a session's
+// LISTENs fails; every channel has to be LISTENed again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ListenLostPayload {
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct NotificationPayload {
+    pub channel: String,
+    pub payload: String,
+}
+
+// A change to rows a NOTIFY describes itself, e.g. from a trigger calling
+// `pg_notify('orders', json_build_object('upsert', json_build_array(row_to_json(NEW)))::text)`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(deny_unknown_fields)]
+pub struct RowDelta {
+    // Rows to insert, or to merge into the row with the same key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upsert: Vec<Value>,
+    // Rows (or just their keys) to remove
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delete: Vec<Value>,
+    // JSON Patch operations on the result, e.g. `{ "op": "replace", "path": "/rows/0/status" }`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patch: Vec<PatchOperation>,
+}
+
+impl RowDelta {
+    // The delta a NOTIFY payload carries; None for any other payload
+    pub fn parse(payload: &str) -> Option<RowDelta> {
+        let delta: RowDelta = serde_json::from_str(payload).ok()?;
+        (!delta.upsert.is_empty() || !delta.delete.is_empty() || !delta.patch.is_empty()).then_some(delta)
+    }
+}
+
+// One RFC 6902 operation; `value` is required by add and replace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct PatchOperation {
+    pub op: PatchOp,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub value: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(rename_all = "lowercase")]
+pub enum PatchOp {
+    Add,
+    Remove,
+    Replace,
+}
+
+// Payload of `row_delta`: a NOTIFY on `channel` whose payload was a `RowDelta`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct RowDeltaPayload {
+    pub channel: String,
+    pub delta: RowDelta,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct SubscribeChangesPayload {
+    pub tables: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ChangePayload {
+    pub subscription: String,
+    pub change: Value,
+}
+
+// Sent under a running query's id while its rows are still being read
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ProgressPayload {
+    // Rows produced so far
+    pub rows: u64,
+    #[serde(rename = "elapsedMs")]
+    pub elapsed_ms: f64,
+}
+
+// Asks the bridge to cancel the running query with id `queryId` on the backend
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct CancelPayload {
+    #[serde(rename = "queryId")]
+    pub query_id: String,
+}
+
+// One piece of a message too large for a single frame. Pieces share `messageId`
+// (the original message's id when it has one) and are joined in `seq` order;
+// `checksum` is the CRC-32 of the complete frame.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct ChunkPayload {
+    #[serde(rename = "messageId")]
+    pub message_id: String,
+    pub seq: u32,
+    pub total: u32,
+    pub data: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub checksum: Option<String>,
+}
+
+// Why a chunked, compressed or encrypted frame could not be turned back into a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    // Content doesn't match the sender's checksum
+    Integrity(String),
+    TooLarge(String),
+    Chunk(String),
+    Compression(String),
+    // Undecryptable, tampered with, or sent in the clear when encryption is required
+    Encryption(String),
+}
+
+impl FrameError {
+    // Error code reported to the peer
+    pub fn code(&self) -> &'static str {
+        match self {
+            FrameError::Integrity(_) => "INTEGRITY_ERROR",
+            FrameError::TooLarge(_) => "MESSAGE_TOO_LARGE",
+            FrameError::Chunk(_) => "CHUNK_ERROR",
+            FrameError::Compression(_) => "COMPRESSION_ERROR",
+            FrameError::Encryption(_) => "ENCRYPTION_ERROR",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FrameError::Integrity(message)
+            | FrameError::TooLarge(message)
+            | FrameError::Chunk(message)
+            | FrameError::Compression(message)
+            | FrameError::Encryption(message) => message,
+        }
+    }
+}
+
+impl core::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct PoolStats {
+    #[serde(rename = "totalCount")]
+    pub total_count: usize,
+    #[serde(rename = "idleCount")]
+    pub idle_count: usize,
+    #[serde(rename = "waitingCount")]
+    pub waiting_count: usize,
+}
+
+// Reply to `pin` and `unpin`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct PinStatus {
+    // Whether the session now keeps its backend between queries, whatever its pool mode
+    pub pinned: bool,
+    // The backend the session holds, if it holds one
+    #[serde(rename = "backendPid", skip_serializing_if = "Option::is_none", default)]
+    pub backend_pid: Option<i32>,
+}
+
+// One server-side prepared statement the session keeps, as listed by `prepared_statements`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct PreparedStatementInfo {
+    // Names the statement in `deallocate`
+    pub handle: String,
+    pub sql: String,
+    // Queries it has served
+    pub uses: u64,
+}
+
+// Payload of `deallocate`; without a handle every kept statement is closed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct DeallocatePayload {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub handle: Option<String>,
+}
+
+// Payload of `describe`: the statement to prepare without running it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct DescribePayload {
+    pub sql: String,
+    // As in `QueryPayload::types`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub types: Option<Vec<Option<String>>>,
+    // Keep the statement prepared for the session's later queries of the same SQL, as
+    // if it had run often enough to be promoted
+    #[serde(skip_serializing_if = "core::ops::Not::not", default)]
+    pub keep: bool,
+}
+
+// A parameter or result column type as Postgres resolved it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct DescribedType {
+    #[serde(rename = "typeOid")]
+    pub type_oid: u32,
+    #[serde(rename = "typeName")]
+    pub type_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct DescribedColumn {
+    pub name: String,
+    #[serde(rename = "typeOid")]
+    pub type_oid: u32,
+    #[serde(rename = "typeName")]
+    pub type_name: String,
+}
+
+// Reply to `describe`: what a statement takes and the shape of what it returns, which is
+// empty for statements that return no rows
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct StatementDescription {
+    pub params: Vec<DescribedType>,
+    pub columns: Vec<DescribedColumn>,
+}
+
+// Payload of `fetch_more`: the next page of a paged query, or with `close` the end of it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(default)]
+pub struct FetchMorePayload {
+    pub token: String,
+    // Defaults to the page size the query was run with
+    #[serde(rename = "pageSize", skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<u32>,
+    #[serde(skip_serializing_if = "core::ops::Not::not")]
+    pub close: bool,
+}
+
+// Payload of the `admin` messages, which carry the bridge's admin token instead of
+// relying on the session's authentication
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct AdminPayload {
+    pub token: String,
+}
+
+// One connected client, as `admin_stats` reports it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct AdminClient {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub peer: String,
+    #[serde(rename = "connectedSecs")]
+    pub connected_secs: u64,
+    // PID of the session's backend, once it has acquired one
+    #[serde(rename = "backendPid", skip_serializing_if = "Option::is_none", default)]
+    pub backend_pid: Option<i32>,
+    pub queries: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+pub struct AdminStats {
+    #[serde(rename = "activeClients")]
+    pub active_clients: usize,
+    pub clients: Vec<AdminClient>,
+    pub pool: PoolStats,
+    #[serde(rename = "poolMaxSize")]
+    pub pool_max_size: usize,
+    // Share of the pool's maximum size checked out, 0 to 1
+    #[serde(rename = "poolUtilization")]
+    pub pool_utilization: f64,
+    // Averaged over the last minute
+    #[serde(rename = "queriesPerSecond")]
+    pub queries_per_second: f64,
+    #[serde(rename = "totalQueries")]
+    pub total_queries: u64,
+    #[serde(rename = "parkedSessions")]
+    pub parked_sessions: usize,
+    #[serde(rename = "uptimeSecs")]
+    pub uptime_secs: u64,
+}
+
+pub mod binary;
+pub mod chunking;
+pub mod compression;
+pub mod encryption;
+pub mod integrity;
+pub mod policy;
+pub mod sql;
+
+pub use policy::StatementPolicy;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_names() {
+        let result = QueryResult {
+            sql: "SELECT 1".to_string(),
+            params: Vec::new(),
+            rows: vec![json!({"n": 1})],
+            row_count: 1,
+            execution_time: 2.0,
+            timestamp: "2024-01-01T00:00:00.000Z".to_string(),
+            columns: None,
+            cache: Some(CacheStatus { hit: true, age_ms: 1500 }),
+            continuation: Some("c1".to_string()),
+            tags: Some(BTreeMap::from([("feature".to_string(), "orders-grid".to_string())])),
+            schema_id: Some("s1".to_string()),
+            timing: Some(QueryTiming { bridge_ms: Some(3.5), database_ms: Some(2.25), ..QueryTiming::default() }),
+            load: Some(BridgeLoad { queued: 2, pool_waiting: 0, saturation: 0.5 }),
+        };
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["rowCount"], 1);
+        assert_eq!(value["executionTime"], 2.0);
+        assert!(value.get("columns").is_none());
+        assert_eq!(value["cache"], json!({"hit": true, "ageMs": 1500}));
+        assert_eq!(value["continuation"], "c1");
+        assert_eq!(value["tags"], json!({"feature": "orders-grid"}));
+        assert_eq!(value["schemaId"], "s1");
+        assert_eq!(value["timing"], json!({"bridgeMs": 3.5, "databaseMs": 2.25}));
+        assert_eq!(value["load"], json!({"queued": 2, "poolWaiting": 0, "saturation": 0.5}));
+        let column = RawColumn { name: "n".to_string(), type_oid: 23, format: 1 };
+        assert_eq!(to_value(&column), json!({"name": "n", "typeOid": 23, "format": 1}));
+
+        let stats = to_value(&PoolStats { total_count: 3, idle_count: 2, waiting_count: 0 });
+        assert_eq!(stats, json!({"totalCount": 3, "idleCount": 2, "waitingCount": 0}));
+        assert_eq!(to_value(&PinStatus { pinned: true, backend_pid: Some(42) }), json!({"pinned": true, "backendPid": 42}));
+        assert_eq!(WebSocketMessage::listen_lost("gone").payload, json!({"reason": "gone"}));
+        let change: SchemaChange = serde_json::from_value(json!({"commandTag": "ALTER TABLE", "object": "public.orders"})).unwrap();
+        assert_eq!((change.command_tag.as_str(), change.object_type), ("ALTER TABLE", None));
+        let delta = RowDelta::parse(r#"{"delete": [{"id": 3}], "patch": [{"op": "replace", "path": "/rows/0/n", "value": 2}]}"#).unwrap();
+        assert_eq!(delta.patch[0].op, PatchOp::Replace);
+        assert_eq!(to_value(&delta), json!({"delete": [{"id": 3}], "patch": [{"op": "replace", "path": "/rows/0/n", "value": 2}]}));
+        // Ordinary payloads stay notifications
+        assert_eq!(RowDelta::parse(r#"{"id": 3}"#), None);
+        assert_eq!(RowDelta::parse("{}"), None);
+    }
+
+    #[test]
+    fn test_hello_negotiation() {
+        let client =
+            HelloPayload::new(0, Capabilities { notifications: true, compression: true, ..Capabilities::default() }, 1024);
+        let server: HelloPayload = serde_json::from_value(
+            json!({"version": 1, "capabilities": {"notifications": true}, "maxMessageSize": 4096}),
+        )
+        .unwrap();
+        assert_eq!(
+            client.negotiate(&server).unwrap(),
+            Negotiated {
+                version: 1,
+                capabilities: Capabilities { notifications: true, ..Capabilities::default() },
+                peer_max_message_size: Some(4096),
+            }
+        );
+
+        let newer = HelloPayload { version: 3, min_version: 2, capabilities: Capabilities::default(), ..client.clone() };
+        assert!(client.negotiate(&newer).unwrap_err().contains("requires protocol version 2"));
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let message: WebSocketMessage =
+            serde_json::from_str(r#"{"type":"query","payload":{"sql":"SELECT 1","params":null},"id":"q1"}"#).unwrap();
+        let query: QueryPayload = serde_json::from_value(message.payload).unwrap();
+        assert_eq!(query.sql, "SELECT 1");
+        assert!(query.params.is_none());
+
+        // Key order depends on serde_json's preserve_order feature, so compare values
+        let parse = |message: WebSocketMessage| serde_json::from_str::<Value>(&message.to_json()).unwrap();
+        assert_eq!(
+            parse(WebSocketMessage::error(Some("q1".to_string()), "DATABASE_ERROR", "boom")),
+            json!({"type": "error", "payload": {"message": "boom", "code": "DATABASE_ERROR"}, "id": "q1"})
+        );
+        assert_eq!(
+            parse(WebSocketMessage::notification("jobs", "7")),
+            json!({"type": "notification", "payload": {"channel": "jobs", "payload": "7"}})
+        );
+        assert_eq!(
+            parse(WebSocketMessage::progress(Some("q1".to_string()), 5000, 250.0)),
+            json!({"type": "progress", "payload": {"rows": 5000, "elapsedMs": 250.0}, "id": "q1"})
+        );
+    }
+}
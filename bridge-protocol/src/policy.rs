@@ -0,0 +1,130 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sql::{statements, Token};
+
+// Which statements a deployment lets through. Kinds match a statement's leading
+// keywords ("drop", "alter table"); patterns are case-insensitive `*`/`?` globs
+// matched against the whole SQL with whitespace collapsed. Denials win over allows,
+// and an empty allow list allows everything.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "typescript", derive(tsify::Tsify))]
+#[serde(default)]
+pub struct StatementPolicy {
+    #[serde(rename = "allowKinds", skip_serializing_if = "Vec::is_empty")]
+    pub allow_kinds: Vec<String>,
+    #[serde(rename = "denyKinds", skip_serializing_if = "Vec::is_empty")]
+    pub deny_kinds: Vec<String>,
+    #[serde(rename = "allowPatterns", skip_serializing_if = "Vec::is_empty")]
+    pub allow_patterns: Vec<String>,
+    #[serde(rename = "denyPatterns", skip_serializing_if = "Vec::is_empty")]
+    pub deny_patterns: Vec<String>,
+}
+
+// Whether the statement starts with every word of `kind`
+fn is_kind(tokens: &[Token], kind: &str) -> bool {
+    let mut words = tokens.iter().filter_map(|token| match token {
+        Token::Word(word) => Some(word.as_str()),
+        _ => None,
+    });
+    kind.split_whitespace().all(|expected| words.next().is_some_and(|word| word.eq_ignore_ascii_case(expected)))
+}
+
+fn normalize(sql: &str) -> String {
+    sql.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// `*` matches any run of characters, `?` exactly one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Last `*` seen and the text position it currently covers up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl StatementPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow_kinds.is_empty()
+            && self.deny_kinds.is_empty()
+            && self.allow_patterns.is_empty()
+            && self.deny_patterns.is_empty()
+    }
+
+    // Reject SQL the policy doesn't let through, naming the rule it broke
+    pub fn check(&self, sql: &str) -> Result<(), String> {
+        let normalized = normalize(sql);
+        let matches = |pattern: &String| glob_match(&normalize(pattern), &normalized);
+        if let Some(pattern) = self.deny_patterns.iter().find(|p| matches(p)) {
+            return Err(format!("Statement policy: SQL matches denied pattern '{}'", pattern));
+        }
+        if !self.allow_patterns.is_empty() && !self.allow_patterns.iter().any(matches) {
+            return Err("Statement policy: SQL matches no allowed pattern".into());
+        }
+        for tokens in statements(sql) {
+            if let Some(kind) = self.deny_kinds.iter().find(|kind| is_kind(&tokens, kind)) {
+                return Err(format!("Statement policy: {} statements are denied", kind.to_uppercase()));
+            }
+            if !self.allow_kinds.is_empty() && !self.allow_kinds.iter().any(|kind| is_kind(&tokens, kind)) {
+                return Err("Statement policy: statement kind is not allowed".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("select * from users*", "select * from users where id = 1"));
+        assert!(glob_match("*pg_?atalog*", "select 1 from pg_catalog.pg_class"));
+        assert!(!glob_match("select * from users", "select * from users_archive"));
+        assert!(glob_match("**", ""));
+    }
+
+    #[test]
+    fn test_kinds_and_patterns() {
+        let policy = StatementPolicy {
+            deny_kinds: vec!["drop".to_string(), "truncate".to_string(), "alter table".to_string()],
+            deny_patterns: vec!["*pg_catalog*".to_string()],
+            ..StatementPolicy::default()
+        };
+        assert!(policy.check("SELECT 'drop table users' FROM t; ALTER ROLE app SET x = 1").is_ok());
+        assert_eq!(policy.check("select 1;\n  drop table users").unwrap_err(), "Statement policy: DROP statements are denied");
+        assert!(policy.check("Alter   TABLE users ADD c int").is_err());
+        assert!(policy.check("SELECT * FROM PG_CATALOG.pg_class").is_err());
+
+        let reads = StatementPolicy { allow_kinds: vec!["select".to_string(), "with".to_string()], ..StatementPolicy::default() };
+        assert!(reads.check("WITH x AS (SELECT 1) SELECT * FROM x").is_ok());
+        assert!(reads.check("SELECT 1; DELETE FROM users").is_err());
+    }
+}
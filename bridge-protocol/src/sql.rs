@@ -0,0 +1,156 @@
+// Just enough of a SQL lexer to find statement boundaries and keywords, shared by
+// the client and the bridge so both classify statements the same way
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// What the lexer keeps of a statement: bare words (lowercased) and punctuation. String
+// literals, quoted identifiers, dollar-quoted bodies and comments are skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(String),
+    Punct(char),
+    // A literal or quoted identifier
+    Quoted,
+    // `$1`, `$2`, ...
+    Param,
+}
+
+// Index just past a quoted section opened at `start`; `''`/`""` doubling continues it
+fn skip_quoted(chars: &[char], start: usize, quote: char, backslash_escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if backslash_escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return i + 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+// Split SQL into tokens the way Postgres' scanner sees them, enough to find keywords
+pub fn tokenize(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if c == '\'' {
+            i = skip_quoted(&chars, i, '\'', false);
+            tokens.push(Token::Quoted);
+        } else if c == '"' {
+            i = skip_quoted(&chars, i, '"', false);
+            tokens.push(Token::Quoted);
+        } else if (c == 'e' || c == 'E') && next == Some('\'') {
+            i = skip_quoted(&chars, i + 1, '\'', true);
+            tokens.push(Token::Quoted);
+        } else if c == '$' && next.is_some_and(|n| n.is_ascii_digit()) {
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            tokens.push(Token::Param);
+        } else if c == '$' {
+            // Dollar quoting: $$...$$ or $tag$...$tag$
+            let tag_end = (i + 1..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'));
+            match tag_end.filter(|&j| chars[j] == '$') {
+                Some(j) => {
+                    let tag: String = chars[i..=j].iter().collect();
+                    let body: String = chars[j + 1..].iter().collect();
+                    i = match body.find(&tag) {
+                        Some(offset) => j + 1 + body[..offset].chars().count() + tag.chars().count(),
+                        None => chars.len(),
+                    };
+                    tokens.push(Token::Quoted);
+                }
+                None => {
+                    tokens.push(Token::Punct(c));
+                    i += 1;
+                }
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Word(chars[start..i].iter().collect::<String>().to_lowercase()));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+// Tokens of each statement, split on top-level semicolons; empty statements are dropped
+pub fn statements(sql: &str) -> Vec<Vec<Token>> {
+    tokenize(sql)
+        .split(|token| *token == Token::Punct(';'))
+        .filter(|statement| !statement.is_empty())
+        .map(|statement| statement.to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    #[test]
+    fn test_tokenize_skips_literals_and_comments() {
+        let word = |w: &str| Token::Word(w.to_string());
+        let sql = "SELECT 'a; DROP' , E'x\\'y', $$ DELETE $$ -- UPDATE\n/* INSERT /* nested */ */ FROM \"Drop\" WHERE id = $1; delete";
+        let statements = statements(sql);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0],
+            vec![
+                word("select"),
+                Token::Quoted,
+                Token::Punct(','),
+                Token::Quoted,
+                Token::Punct(','),
+                Token::Quoted,
+                word("from"),
+                Token::Quoted,
+                word("where"),
+                word("id"),
+                Token::Punct('='),
+                Token::Param,
+            ]
+        );
+        assert_eq!(statements[1], vec![word("delete")]);
+    }
+}
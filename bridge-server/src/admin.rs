@@ -0,0 +1,122 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use bridge_protocol::AdminClient;
+
+// Seconds of history behind `queriesPerSecond`
+const RATE_WINDOW_SECS: u64 = 60;
+
+struct ClientEntry {
+    peer: String,
+    connected_at: Instant,
+    backend_pid: Option<i32>,
+    queries: u64,
+}
+
+// What the bridge knows about its connections, for `admin_stats`
+pub struct AdminState {
+    started: Instant,
+    clients: Mutex<BTreeMap<String, ClientEntry>>,
+    rate: Mutex<QueryRate>,
+}
+
+impl AdminState {
+    pub fn new(started: Instant) -> AdminState {
+        AdminState { started, clients: Mutex::new(BTreeMap::new()), rate: Mutex::new(QueryRate::default()) }
+    }
+
+    pub fn connected(&self, client_id: &str, peer: String) {
+        let entry = ClientEntry { peer, connected_at: Instant::now(), backend_pid: None, queries: 0 };
+        self.clients.lock().unwrap().insert(client_id.to_string(), entry);
+    }
+
+    pub fn disconnected(&self, client_id: &str) {
+        self.clients.lock().unwrap().remove(client_id);
+    }
+
+    pub fn set_backend_pid(&self, client_id: &str, pid: Option<i32>) {
+        if let Some(entry) = self.clients.lock().unwrap().get_mut(client_id) {
+            entry.backend_pid = pid;
+        }
+    }
+
+    pub fn record_query(&self, client_id: &str) {
+        if let Some(entry) = self.clients.lock().unwrap().get_mut(client_id) {
+            entry.queries += 1;
+        }
+        self.rate.lock().unwrap().record(self.started.elapsed().as_secs());
+    }
+
+    pub fn clients(&self) -> Vec<AdminClient> {
+        self.clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(client_id, entry)| AdminClient {
+                client_id: client_id.clone(),
+                peer: entry.peer.clone(),
+                connected_secs: entry.connected_at.elapsed().as_secs(),
+                backend_pid: entry.backend_pid,
+                queries: entry.queries,
+            })
+            .collect()
+    }
+
+    // (queries per second over the last minute, queries since start)
+    pub fn query_rate(&self) -> (f64, u64) {
+        let elapsed = self.started.elapsed().as_secs();
+        let rate = self.rate.lock().unwrap();
+        (rate.per_second(elapsed), rate.total)
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+// Query counts per second of uptime, kept for the last `RATE_WINDOW_SECS`
+#[derive(Debug, Default)]
+pub struct QueryRate {
+    buckets: VecDeque<(u64, u64)>,
+    total: u64,
+}
+
+impl QueryRate {
+    pub fn record(&mut self, second: u64) {
+        self.total += 1;
+        match self.buckets.back_mut() {
+            Some((last, count)) if *last == second => *count += 1,
+            _ => self.buckets.push_back((second, 1)),
+        }
+        while self.buckets.front().is_some_and(|(first, _)| first + RATE_WINDOW_SECS <= second) {
+            self.buckets.pop_front();
+        }
+    }
+
+    // Average over the window ending at `now`, or over the uptime while it is shorter
+    pub fn per_second(&self, now: u64) -> f64 {
+        let recent: u64 = self.buckets.iter().filter(|(second, _)| second + RATE_WINDOW_SECS > now).map(|(_, count)| count).sum();
+        recent as f64 / (now + 1).min(RATE_WINDOW_SECS) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_rate_window() {
+        let mut rate = QueryRate::default();
+        for _ in 0..30 {
+            rate.record(0);
+        }
+        rate.record(1);
+        assert_eq!(rate.per_second(1), 15.5);
+        // A minute later only the newer bucket is in the window
+        assert_eq!(rate.per_second(60), 1.0 / 60.0);
+        rate.record(120);
+        assert_eq!(rate.buckets.len(), 1);
+        assert_eq!(rate.total, 32);
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::{Arc, Mutex};
+
+use bridge_protocol::{message_type, CancelPayload, WebSocketMessage};
+use serde_json::json;
+use tokio_postgres::{CancelToken, NoTls};
+
+use crate::encryption::{self, DirectOut, SharedCipher};
+
+// The query a session is running, with the backend PID and secret key needed to
+// cancel it. Shared with the connection's reader, which handles `cancel` messages
+// while the session is still busy with that query.
+#[derive(Clone, Default)]
+pub struct RunningQuery {
+    current: Arc<Mutex<Option<(String, CancelToken)>>>,
+}
+
+// Clears the running query when dropped, however the query ends
+pub struct RunningGuard {
+    current: Arc<Mutex<Option<(String, CancelToken)>>>,
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        *self.current.lock().unwrap() = None;
+    }
+}
+
+impl RunningQuery {
+    pub fn start(&self, id: &str, token: CancelToken) -> RunningGuard {
+        *self.current.lock().unwrap() = Some((id.to_string(), token));
+        RunningGuard { current: self.current.clone() }
+    }
+
+    fn token_for(&self, id: &str) -> Option<CancelToken> {
+        let current = self.current.lock().unwrap();
+        current.as_ref().filter(|(running, _)| running == id).map(|(_, token)| token.clone())
+    }
+}
+
+// Larger frames are never a `cancel`, so the reader doesn't decrypt them to find out
+const MAX_CANCEL_FRAME: usize = 1024;
+
+fn cancel_payload(message: WebSocketMessage) -> Option<(Option<String>, CancelPayload)> {
+    if message.message_type != message_type::CANCEL {
+        return None;
+    }
+    Some((message.id, serde_json::from_value(message.payload).ok()?))
+}
+
+// The message id and target of a `cancel` frame, or None for any other frame. With
+// a cipher, only encrypted frames count; a cancel's counter is used up here, while
+// other frames are left for the session to open.
+pub fn cancel_request(text: &str, cipher: Option<&SharedCipher>) -> Option<(Option<String>, CancelPayload)> {
+    if text.len() > MAX_CANCEL_FRAME {
+        return None;
+    }
+    let message = serde_json::from_str::<WebSocketMessage>(text).ok()?;
+    let Some(cipher) = cipher else {
+        return cancel_payload(message);
+    };
+    if message.message_type != message_type::ENCRYPTED {
+        return None;
+    }
+    let id = message.id.clone();
+    let payload = encryption::payload(message).ok()?;
+    let mut cipher = cipher.lock().unwrap();
+    let cipher = cipher.as_mut()?;
+    let request = cancel_payload(serde_json::from_str(&cipher.decrypt(&payload, id.as_deref()).ok()?).ok()?)?;
+    cipher.accept(payload.counter);
+    Some(request)
+}
+
+// Answer a `cancel` frame straight away. Returns false for frames the session handles.
+pub fn handle_frame(running: &RunningQuery, text: &str, out: &DirectOut) -> bool {
+    let Some((id, cancel)) = cancel_request(text, out.encrypted().then(|| out.cipher())) else {
+        return false;
+    };
+    let Some(token) = running.token_for(&cancel.query_id) else {
+        // Finished already, or still queued behind other messages
+        out.send(WebSocketMessage::result(id, json!({ "cancelled": false })));
+        return true;
+    };
+    let out = out.clone();
+    tokio::spawn(async move {
+        // The query itself then fails with SQLSTATE 57014 (query_canceled)
+        let response = match token.cancel_query(NoTls).await {
+            Ok(()) => WebSocketMessage::result(id, json!({ "cancelled": true })),
+            Err(e) => WebSocketMessage::error(id, "CANCEL_FAILED", format!("Failed to cancel query: {}", e)),
+        };
+        out.send(response);
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_protocol::encryption::{FrameKey, Side, SALT_SIZE};
+
+    #[test]
+    fn test_cancel_request() {
+        let frame = r#"{"type":"cancel","payload":{"queryId":"q7"},"id":"c1"}"#;
+        let (id, cancel) = cancel_request(frame, None).unwrap();
+        assert_eq!(id.as_deref(), Some("c1"));
+        assert_eq!(cancel.query_id, "q7");
+        assert!(cancel_request(r#"{"type":"query","payload":{"sql":"SELECT 1"},"id":"q8"}"#, None).is_none());
+        assert!(cancel_request("not json", None).is_none());
+
+        let key = FrameKey::new(&[3u8; 32]).unwrap();
+        let mut client = key.cipher(Side::Client, &[1u8; SALT_SIZE], &[2u8; SALT_SIZE]);
+        let cipher: SharedCipher = Arc::new(Mutex::new(Some(key.cipher(Side::Bridge, &[1u8; SALT_SIZE], &[2u8; SALT_SIZE]))));
+        assert!(cancel_request(frame, Some(&cipher)).is_none());
+        let sealed = client.seal(frame, Some("c1".to_string())).to_json();
+        assert_eq!(cancel_request(&sealed, Some(&cipher)).unwrap().1.query_id, "q7");
+        // Used up, while a frame for the session is left to open there
+        assert!(cancel_request(&sealed, Some(&cipher)).is_none());
+        let query = client.seal(r#"{"type":"query","payload":{"sql":"SELECT 1"},"id":"q8"}"#, Some("q8".to_string()));
+        assert!(cancel_request(&query.to_json(), Some(&cipher)).is_none());
+        assert!(encryption::open(&cipher, query).is_ok());
+        assert!(RunningQuery::default().token_for("q7").is_none());
+    }
+}
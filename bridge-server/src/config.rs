@@ -0,0 +1,170 @@
+use std::env;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bridge_protocol::chunking::DEFAULT_MAX_MESSAGE_SIZE;
+use bridge_protocol::compression::DEFAULT_COMPRESSION_THRESHOLD;
+use bridge_protocol::encryption::FrameKey;
+
+// Runtime settings, read from the environment
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub addr: SocketAddr,
+    pub database_url: String,
+    // Accepted `auth` credentials; authentication is disabled when empty
+    pub api_keys: Vec<String>,
+    // Keys bound to one tenant: accepted like `api_keys`, but every query on the
+    // connection must carry that tenant's id
+    pub tenant_keys: Vec<(String, String)>,
+    pub pool_size: usize,
+    // Connections opened outside the pool for clients that ask for a dedicated backend
+    pub dedicated_backends: usize,
+    // Largest frame accepted from a client; bigger messages must arrive as chunks
+    pub max_message_size: usize,
+    // Responses of at least this many bytes are deflated for clients that support it
+    pub compression_threshold: usize,
+    // Shared secret for end-to-end payload encryption; when set, every frame after
+    // `hello` must be encrypted
+    pub encryption_key: Option<FrameKey>,
+    // How long a disconnected session's backend is kept for the client to resume; zero
+    // turns resumption off
+    pub resume_window: Duration,
+    // Prepared statements each session keeps, and how often SQL must run to earn one
+    pub prepared_statements: usize,
+    pub prepare_threshold: u32,
+    // Grants `admin_*` messages; they are refused while unset
+    pub admin_token: Option<String>,
+    // How long the bridge keeps running after SIGINT or SIGTERM, for clients to finish
+    // and leave; also the reconnect delay it suggests to them
+    pub shutdown_grace: Duration,
+    // Results of hinted reads kept for any client to be served from; 0 turns the
+    // result cache off
+    pub result_cache_size: usize,
+    // LISTEN for the DDL event triggers' NOTIFYs and pass them on to clients
+    pub schema_events: bool,
+    // Queries each client may run per second, in bursts of up to `query_burst`; those
+    // over it are refused with a retry-after. 0 turns the limit off.
+    pub query_rate: usize,
+    pub query_burst: usize,
+}
+
+pub fn parse_api_keys(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// `key=tenant` pairs, comma-separated
+pub fn parse_tenant_keys(value: &str) -> Result<Vec<(String, String)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, tenant)) if !key.trim().is_empty() && !tenant.trim().is_empty() => {
+                Ok((key.trim().to_string(), tenant.trim().to_string()))
+            }
+            _ => Err(format!("Invalid BRIDGE_TENANT_KEYS entry '{}'; expected key=tenant", pair)),
+        })
+        .collect()
+}
+
+fn usize_var(name: &str, default: usize) -> Result<usize, String> {
+    match env::var(name) {
+        Ok(value) => value.parse().map_err(|e| format!("Invalid {} '{}': {}", name, value, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Result<Config, String> {
+        let addr = env::var("BRIDGE_ADDR").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+        let addr = addr
+            .parse()
+            .map_err(|e| format!("Invalid BRIDGE_ADDR '{}': {}", addr, e))?;
+        let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL must be set".to_string())?;
+        let api_keys = env::var("BRIDGE_API_KEYS").map(|v| parse_api_keys(&v)).unwrap_or_default();
+        let tenant_keys = match env::var("BRIDGE_TENANT_KEYS") {
+            Ok(value) => parse_tenant_keys(&value)?,
+            Err(_) => Vec::new(),
+        };
+        let pool_size = usize_var("BRIDGE_POOL_SIZE", 16)?;
+        let dedicated_backends = usize_var("BRIDGE_DEDICATED_BACKENDS", 0)?;
+        let max_message_size = usize_var("BRIDGE_MAX_MESSAGE_SIZE", DEFAULT_MAX_MESSAGE_SIZE)?;
+        let compression_threshold = usize_var("BRIDGE_COMPRESSION_THRESHOLD", DEFAULT_COMPRESSION_THRESHOLD)?;
+        let encryption_key = match env::var("BRIDGE_ENCRYPTION_KEY") {
+            Ok(key) => Some(FrameKey::from_base64(&key).map_err(|e| format!("Invalid BRIDGE_ENCRYPTION_KEY: {}", e))?),
+            Err(_) => None,
+        };
+        let resume_window = Duration::from_secs(usize_var("BRIDGE_RESUME_WINDOW_SECS", 30)? as u64);
+        let prepared_statements = usize_var("BRIDGE_PREPARED_STATEMENTS", 100)?;
+        let prepare_threshold = usize_var("BRIDGE_PREPARE_THRESHOLD", 3)?.clamp(1, u32::MAX as usize) as u32;
+        let admin_token = env::var("BRIDGE_ADMIN_TOKEN").ok().filter(|token| !token.is_empty());
+        let shutdown_grace = Duration::from_millis(usize_var("BRIDGE_SHUTDOWN_GRACE_MS", 5000)? as u64);
+        let result_cache_size = usize_var("BRIDGE_RESULT_CACHE_SIZE", 0)?;
+        let schema_events = usize_var("BRIDGE_SCHEMA_EVENTS", 0)? > 0;
+        let query_rate = usize_var("BRIDGE_QUERY_RATE", 0)?;
+        let query_burst = usize_var("BRIDGE_QUERY_BURST", query_rate)?.max(1);
+        Ok(Config {
+            addr,
+            database_url,
+            api_keys,
+            tenant_keys,
+            pool_size,
+            dedicated_backends,
+            max_message_size,
+            compression_threshold,
+            encryption_key,
+            resume_window,
+            prepared_statements,
+            prepare_threshold,
+            admin_token,
+            shutdown_grace,
+            result_cache_size,
+            schema_events,
+            query_rate,
+            query_burst,
+        })
+    }
+
+    pub fn auth_required(&self) -> bool {
+        !self.api_keys.is_empty() || !self.tenant_keys.is_empty()
+    }
+
+    pub fn accepts(&self, key: &str) -> bool {
+        self.api_keys.iter().any(|k| k == key) || self.tenant_for(key).is_some()
+    }
+
+    // The tenant a key is bound to, if any
+    pub fn tenant_for(&self, key: &str) -> Option<&str> {
+        self.tenant_keys.iter().find(|(k, _)| k == key).map(|(_, tenant)| tenant.as_str())
+    }
+
+    pub fn accepts_admin(&self, token: &str) -> bool {
+        self.admin_token.as_deref() == Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_keys() {
+        assert_eq!(parse_api_keys(" a, b ,,c"), vec!["a", "b", "c"]);
+        assert!(parse_api_keys("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_tenant_keys() {
+        assert_eq!(
+            parse_tenant_keys("k1=acme, k2 = globex,").unwrap(),
+            vec![("k1".to_string(), "acme".to_string()), ("k2".to_string(), "globex".to_string())]
+        );
+        assert!(parse_tenant_keys("k1").is_err());
+        assert!(parse_tenant_keys("=acme").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::resume;
+
+// Paged queries a session may leave unfinished at once; each holds a cursor on the backend
+pub const MAX_OPEN_CONTINUATIONS: usize = 16;
+
+// A paged query with rows left: the cursor they wait in, and what the client asked
+#[derive(Debug, Clone, PartialEq)]
+pub struct Continuation {
+    pub cursor: String,
+    pub sql: String,
+    pub params: Vec<Value>,
+    pub raw: bool,
+    pub page_size: u32,
+}
+
+// The session's open continuations by token. Cursors live on the backend connection, so
+// these are dropped whenever the session gives it up.
+#[derive(Debug, Default)]
+pub struct Continuations {
+    open: HashMap<String, Continuation>,
+    next_cursor: u64,
+}
+
+pub fn declare_sql(cursor: &str, sql: &str) -> String {
+    // WITH HOLD keeps the cursor past the implicit transaction of the DECLARE
+    format!("DECLARE \"{}\" NO SCROLL CURSOR WITH HOLD FOR {}", cursor, sql)
+}
+
+pub fn fetch_sql(cursor: &str, rows: u32) -> String {
+    format!("FETCH FORWARD {} FROM \"{}\"", rows, cursor)
+}
+
+pub fn close_sql(cursor: &str) -> String {
+    format!("CLOSE \"{}\"", cursor)
+}
+
+impl Continuations {
+    // Register a paged query, returning its token and the name of the cursor to declare
+    pub fn open(&mut self, sql: String, params: Vec<Value>, raw: bool, page_size: u32) -> Result<(String, String), String> {
+        if self.open.len() >= MAX_OPEN_CONTINUATIONS {
+            return Err(format!(
+                "{} paged queries are already open; fetch them to the end or close them first",
+                MAX_OPEN_CONTINUATIONS
+            ));
+        }
+        self.next_cursor += 1;
+        let cursor = format!("bridge_continuation_{}", self.next_cursor);
+        let token = resume::new_token();
+        self.open.insert(token.clone(), Continuation { cursor: cursor.clone(), sql, params, raw, page_size });
+        Ok((token, cursor))
+    }
+
+    pub fn get(&self, token: &str) -> Option<&Continuation> {
+        self.open.get(token)
+    }
+
+    pub fn remove(&mut self, token: &str) -> Option<Continuation> {
+        self.open.remove(token)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.open.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_name_cursors() {
+        let mut continuations = Continuations::default();
+        let (token, cursor) = continuations.open("SELECT * FROM t".to_string(), Vec::new(), false, 50).unwrap();
+        assert_eq!(cursor, "bridge_continuation_1");
+        assert_eq!(declare_sql(&cursor, "SELECT * FROM t"), "DECLARE \"bridge_continuation_1\" NO SCROLL CURSOR WITH HOLD FOR SELECT * FROM t");
+        assert_eq!(fetch_sql(&cursor, 50), "FETCH FORWARD 50 FROM \"bridge_continuation_1\"");
+        assert_eq!(continuations.get(&token).map(|c| c.page_size), Some(50));
+        assert!(continuations.remove(&token).is_some());
+        assert!(continuations.is_empty());
+
+        for _ in 0..MAX_OPEN_CONTINUATIONS {
+            continuations.open("SELECT 1".to_string(), Vec::new(), false, 1).unwrap();
+        }
+        assert!(continuations.open("SELECT 1".to_string(), Vec::new(), false, 1).is_err());
+    }
+}
@@ -0,0 +1,553 @@
+use std::error::Error;
+
+use bridge_protocol::binary::{BinaryParam, BinaryValue, POSTGRES_EPOCH_UNIX_MICROS};
+use bridge_protocol::{DescribedColumn, DescribedType, RawColumn, StatementDescription};
+use bytes::BytesMut;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use postgres_types::{to_sql_checked, FromSql, IsNull, Kind, ToSql, Type};
+use serde_json::{Map, Number, Value};
+use tokio_postgres::{Column, Row, SimpleQueryMessage, Statement};
+use uuid::Uuid;
+
+type BoxError = Box<dyn Error + Sync + Send>;
+
+// How a query's parameters are bound, besides their JSON values
+#[derive(Debug, Clone, Default)]
+pub struct Binding {
+    // By position, parameters the client sent in binary
+    pub binary: Vec<Option<BinaryParam>>,
+    // Types to prepare the statement with; empty leaves them all to Postgres
+    pub types: Vec<Type>,
+}
+
+// A JSON parameter coerced to the type Postgres inferred for its placeholder
+#[derive(Debug, PartialEq)]
+pub enum SqlParam {
+    Null,
+    Bool(bool),
+    Int2(i16),
+    Int4(i32),
+    Int8(i64),
+    Float4(f32),
+    Float8(f64),
+    Text(String),
+    Json(Value),
+    Uuid(Uuid),
+    Timestamp(NaiveDateTime),
+    TimestampTz(DateTime<Utc>),
+    Date(NaiveDate),
+    Bytea(Vec<u8>),
+    Int4Array(Vec<i32>),
+    Int8Array(Vec<i64>),
+    Float4Array(Vec<f32>),
+    Float8Array(Vec<f64>),
+    // A JSON array bound to an array placeholder, element by element
+    Array(Vec<SqlParam>),
+}
+
+impl ToSql for SqlParam {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, BoxError> {
+        match self {
+            SqlParam::Null => Ok(IsNull::Yes),
+            SqlParam::Bool(v) => v.to_sql(ty, out),
+            SqlParam::Int2(v) => v.to_sql(ty, out),
+            SqlParam::Int4(v) => v.to_sql(ty, out),
+            SqlParam::Int8(v) => v.to_sql(ty, out),
+            SqlParam::Float4(v) => v.to_sql(ty, out),
+            SqlParam::Float8(v) => v.to_sql(ty, out),
+            SqlParam::Text(v) => v.to_sql(ty, out),
+            SqlParam::Json(v) => v.to_sql(ty, out),
+            SqlParam::Uuid(v) => v.to_sql(ty, out),
+            SqlParam::Timestamp(v) => v.to_sql(ty, out),
+            SqlParam::TimestampTz(v) => v.to_sql(ty, out),
+            SqlParam::Date(v) => v.to_sql(ty, out),
+            SqlParam::Bytea(v) => v.to_sql(ty, out),
+            SqlParam::Int4Array(v) => v.to_sql(ty, out),
+            SqlParam::Int8Array(v) => v.to_sql(ty, out),
+            SqlParam::Float4Array(v) => v.to_sql(ty, out),
+            SqlParam::Float8Array(v) => v.to_sql(ty, out),
+            SqlParam::Array(v) => v.to_sql(ty, out),
+        }
+    }
+
+    // Each variant is only built for a type it can encode
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn int_param(value: &Value, ty: &Type) -> Result<i64, String> {
+    match value {
+        Value::Number(n) => n.as_i64().ok_or_else(|| format!("{} is not a valid {}", n, ty.name())),
+        Value::String(s) => s.trim().parse().map_err(|_| format!("'{}' is not a valid {}", s, ty.name())),
+        other => Err(format!("{} is not a valid {}", other, ty.name())),
+    }
+}
+
+fn float_param(value: &Value, ty: &Type) -> Result<f64, String> {
+    match value {
+        Value::Number(n) => n.as_f64().ok_or_else(|| format!("{} is not a valid {}", n, ty.name())),
+        Value::String(s) => s.trim().parse().map_err(|_| format!("'{}' is not a valid {}", s, ty.name())),
+        other => Err(format!("{} is not a valid {}", other, ty.name())),
+    }
+}
+
+fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+}
+
+// Coerce one JSON parameter to the placeholder's type
+pub fn to_param(value: &Value, ty: &Type) -> Result<SqlParam, String> {
+    if value.is_null() {
+        return Ok(SqlParam::Null);
+    }
+    if let Kind::Array(member) = ty.kind() {
+        return match value {
+            Value::Array(items) => items.iter().map(|item| to_param(item, member)).collect::<Result<_, _>>().map(SqlParam::Array),
+            other => Err(format!("{} is not a valid {}[]", other, member.name())),
+        };
+    }
+    let param = match *ty {
+        Type::BOOL => match value {
+            Value::Bool(b) => SqlParam::Bool(*b),
+            Value::String(s) if s == "true" || s == "t" => SqlParam::Bool(true),
+            Value::String(s) if s == "false" || s == "f" => SqlParam::Bool(false),
+            other => return Err(format!("{} is not a valid bool", other)),
+        },
+        Type::INT2 => SqlParam::Int2(
+            int_param(value, ty)?
+                .try_into()
+                .map_err(|_| format!("{} is out of range for int2", value))?,
+        ),
+        Type::INT4 => SqlParam::Int4(
+            int_param(value, ty)?
+                .try_into()
+                .map_err(|_| format!("{} is out of range for int4", value))?,
+        ),
+        Type::INT8 => SqlParam::Int8(int_param(value, ty)?),
+        Type::FLOAT4 => SqlParam::Float4(float_param(value, ty)? as f32),
+        Type::FLOAT8 => SqlParam::Float8(float_param(value, ty)?),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => SqlParam::Text(as_text(value)),
+        Type::JSON | Type::JSONB => SqlParam::Json(value.clone()),
+        Type::UUID => SqlParam::Uuid(
+            Uuid::parse_str(&as_text(value)).map_err(|e| format!("{} is not a valid uuid: {}", value, e))?,
+        ),
+        Type::TIMESTAMP => SqlParam::Timestamp(
+            parse_timestamp(&as_text(value)).ok_or_else(|| format!("{} is not a valid timestamp", value))?,
+        ),
+        Type::TIMESTAMPTZ => SqlParam::TimestampTz(
+            DateTime::parse_from_rfc3339(&as_text(value))
+                .map_err(|e| format!("{} is not a valid timestamptz: {}", value, e))?
+                .with_timezone(&Utc),
+        ),
+        Type::DATE => SqlParam::Date(
+            NaiveDate::parse_from_str(&as_text(value), "%Y-%m-%d")
+                .map_err(|e| format!("{} is not a valid date: {}", value, e))?,
+        ),
+        _ => {
+            return Err(format!(
+                "Parameters of type {} are not supported; cast the placeholder, e.g. $1::text::{}",
+                ty.name(),
+                ty.name()
+            ))
+        }
+    };
+    Ok(param)
+}
+
+// Bind a parameter the client sent in binary. Integers and floats may go to a placeholder
+// of another width when they fit; everything else needs the placeholder's own type.
+pub fn binary_param(param: &BinaryParam, ty: &Type) -> Result<SqlParam, String> {
+    let value = param.decode()?;
+    let integer = match value {
+        BinaryValue::Int2(n) => Some(i64::from(n)),
+        BinaryValue::Int4(n) => Some(i64::from(n)),
+        BinaryValue::Int8(n) => Some(n),
+        _ => None,
+    };
+    let out_of_range = || format!("{:?} is out of range for {}", value, ty.name());
+    let param = match (value.clone(), ty) {
+        (BinaryValue::Bool(v), &Type::BOOL) => SqlParam::Bool(v),
+        (_, &Type::INT2) if integer.is_some() => SqlParam::Int2(integer.unwrap_or_default().try_into().map_err(|_| out_of_range())?),
+        (_, &Type::INT4) if integer.is_some() => SqlParam::Int4(integer.unwrap_or_default().try_into().map_err(|_| out_of_range())?),
+        (_, &Type::INT8) if integer.is_some() => SqlParam::Int8(integer.unwrap_or_default()),
+        (BinaryValue::Float4(v), &Type::FLOAT4) => SqlParam::Float4(v),
+        (BinaryValue::Float4(v), &Type::FLOAT8) => SqlParam::Float8(f64::from(v)),
+        (BinaryValue::Float8(v), &Type::FLOAT8) => SqlParam::Float8(v),
+        (BinaryValue::Uuid(v), &Type::UUID) => SqlParam::Uuid(Uuid::from_bytes(v)),
+        (BinaryValue::TimestampTz(v), &Type::TIMESTAMPTZ) => SqlParam::TimestampTz(
+            DateTime::from_timestamp_micros(v.saturating_add(POSTGRES_EPOCH_UNIX_MICROS)).ok_or_else(out_of_range)?,
+        ),
+        (BinaryValue::Bytea(v), &Type::BYTEA) => SqlParam::Bytea(v),
+        (BinaryValue::Int4Array(v), &Type::INT4_ARRAY) => SqlParam::Int4Array(v),
+        (BinaryValue::Int4Array(v), &Type::INT8_ARRAY) => SqlParam::Int8Array(v.into_iter().map(i64::from).collect()),
+        (BinaryValue::Int8Array(v), &Type::INT8_ARRAY) => SqlParam::Int8Array(v),
+        (BinaryValue::Float4Array(v), &Type::FLOAT4_ARRAY) => SqlParam::Float4Array(v),
+        (BinaryValue::Float4Array(v), &Type::FLOAT8_ARRAY) => SqlParam::Float8Array(v.into_iter().map(f64::from).collect()),
+        (BinaryValue::Float8Array(v), &Type::FLOAT8_ARRAY) => SqlParam::Float8Array(v),
+        (value, ty) => {
+            let sent = serde_json::to_value(value.binary_type()).unwrap_or_default();
+            return Err(format!(
+                "A binary {} can't bind to a {} placeholder; cast it, e.g. $1::{}",
+                sent.as_str().unwrap_or_default(),
+                ty.name(),
+                sent.as_str().unwrap_or_default()
+            ));
+        }
+    };
+    Ok(param)
+}
+
+// Bind every parameter: from `binary` where the client sent one there, else from `values`
+pub fn to_params(values: &[Value], binary: &[Option<BinaryParam>], types: &[Type]) -> Result<Vec<SqlParam>, String> {
+    let given = values.len().max(binary.len());
+    if given != types.len() {
+        return Err(format!("Query expects {} parameter(s) but {} were given", types.len(), given));
+    }
+    types
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| {
+            let bound = match binary.get(i) {
+                Some(Some(param)) => binary_param(param, ty),
+                _ => to_param(values.get(i).unwrap_or(&Value::Null), ty),
+            };
+            bound.map_err(|e| format!("Parameter ${}: {}", i + 1, e))
+        })
+        .collect()
+}
+
+// Type names a query may give its parameters in `types`; each also takes `[]`
+const PARAM_TYPES: [(&[&str], Type, Type); 16] = [
+    (&["bool", "boolean"], Type::BOOL, Type::BOOL_ARRAY),
+    (&["int2", "smallint"], Type::INT2, Type::INT2_ARRAY),
+    (&["int4", "int", "integer"], Type::INT4, Type::INT4_ARRAY),
+    (&["int8", "bigint"], Type::INT8, Type::INT8_ARRAY),
+    (&["float4", "real"], Type::FLOAT4, Type::FLOAT4_ARRAY),
+    (&["float8", "double precision"], Type::FLOAT8, Type::FLOAT8_ARRAY),
+    (&["numeric", "decimal"], Type::NUMERIC, Type::NUMERIC_ARRAY),
+    (&["text"], Type::TEXT, Type::TEXT_ARRAY),
+    (&["varchar"], Type::VARCHAR, Type::VARCHAR_ARRAY),
+    (&["json"], Type::JSON, Type::JSON_ARRAY),
+    (&["jsonb"], Type::JSONB, Type::JSONB_ARRAY),
+    (&["uuid"], Type::UUID, Type::UUID_ARRAY),
+    (&["timestamp"], Type::TIMESTAMP, Type::TIMESTAMP_ARRAY),
+    (&["timestamptz"], Type::TIMESTAMPTZ, Type::TIMESTAMPTZ_ARRAY),
+    (&["date"], Type::DATE, Type::DATE_ARRAY),
+    (&["bytea"], Type::BYTEA, Type::BYTEA_ARRAY),
+];
+
+// The type a `types` entry names
+pub fn param_type(name: &str) -> Result<Type, String> {
+    let name = name.trim().to_ascii_lowercase();
+    let (element, array) = match name.strip_suffix("[]") {
+        Some(element) => (element.trim_end(), true),
+        None => (name.as_str(), false),
+    };
+    let (_, scalar, array_type) = PARAM_TYPES
+        .iter()
+        .find(|(names, _, _)| names.contains(&element))
+        .ok_or_else(|| format!("Unknown parameter type '{}'; cast the placeholder in the SQL instead", name))?;
+    Ok(if array { array_type.clone() } else { scalar.clone() })
+}
+
+// The types to prepare a statement with. A null entry, like a missing one, is left for
+// Postgres to infer, which the protocol spells as OID 0.
+pub fn param_types(names: &[Option<String>]) -> Result<Vec<Type>, String> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| match name {
+            Some(name) => param_type(name).map_err(|e| format!("Parameter ${}: {}", i + 1, e)),
+            None => Ok(Type::new("inferred".to_string(), 0, Kind::Pseudo, "pg_catalog".to_string())),
+        })
+        .collect()
+}
+
+// Postgres NUMERIC in binary form, rendered as a decimal string so no precision is lost
+struct Numeric(String);
+
+impl<'a> FromSql<'a> for Numeric {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Numeric, BoxError> {
+        numeric_to_string(raw).map(Numeric)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+pub fn numeric_to_string(raw: &[u8]) -> Result<String, BoxError> {
+    let read = |i: usize| -> Result<i16, BoxError> {
+        raw.get(i * 2..i * 2 + 2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(|| "truncated numeric".into())
+    };
+    let ndigits = read(0)?.max(0) as usize;
+    let weight = read(1)? as i32;
+    let sign = read(2)? as u16;
+    let dscale = read(3)?.max(0) as usize;
+    let digits = (0..ndigits).map(|i| read(4 + i)).collect::<Result<Vec<_>, _>>()?;
+    let digit = |i: i32| if i < 0 { 0 } else { digits.get(i as usize).copied().unwrap_or(0) };
+
+    match sign {
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+
+    let mut text = String::new();
+    if sign == 0x4000 {
+        text.push('-');
+    }
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for i in 1..=weight {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        fraction.truncate(dscale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Ok(text)
+}
+
+// Fallback for types without a dedicated mapping (enums, citext, ...): text when the
+// binary form is valid UTF-8, hex otherwise
+struct Raw(Value);
+
+impl<'a> FromSql<'a> for Raw {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<Raw, BoxError> {
+        Ok(Raw(match std::str::from_utf8(raw) {
+            Ok(text) => Value::String(text.to_string()),
+            Err(_) => Value::String(hex(raw)),
+        }))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut text = String::from("\\x");
+    for b in bytes {
+        text.push_str(&format!("{:02x}", b));
+    }
+    text
+}
+
+fn float_value(value: Option<f64>) -> Value {
+    match value {
+        Some(v) => Number::from_f64(v).map(Value::Number).unwrap_or_else(|| Value::String(v.to_string())),
+        None => Value::Null,
+    }
+}
+
+fn column_value(row: &Row, i: usize, ty: &Type) -> Result<Value, tokio_postgres::Error> {
+    let value = match *ty {
+        Type::BOOL => row.try_get::<_, Option<bool>>(i)?.map(Value::Bool).unwrap_or(Value::Null),
+        Type::INT2 => row.try_get::<_, Option<i16>>(i)?.map(Value::from).unwrap_or(Value::Null),
+        Type::INT4 => row.try_get::<_, Option<i32>>(i)?.map(Value::from).unwrap_or(Value::Null),
+        Type::INT8 => row.try_get::<_, Option<i64>>(i)?.map(Value::from).unwrap_or(Value::Null),
+        Type::OID => row.try_get::<_, Option<u32>>(i)?.map(Value::from).unwrap_or(Value::Null),
+        Type::FLOAT4 => float_value(row.try_get::<_, Option<f32>>(i)?.map(f64::from)),
+        Type::FLOAT8 => float_value(row.try_get::<_, Option<f64>>(i)?),
+        Type::NUMERIC => row.try_get::<_, Option<Numeric>>(i)?.map(|n| Value::String(n.0)).unwrap_or(Value::Null),
+        Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME => {
+            row.try_get::<_, Option<String>>(i)?.map(Value::String).unwrap_or(Value::Null)
+        }
+        Type::JSON | Type::JSONB => row.try_get::<_, Option<Value>>(i)?.unwrap_or(Value::Null),
+        Type::UUID => row.try_get::<_, Option<Uuid>>(i)?.map(|u| Value::String(u.to_string())).unwrap_or(Value::Null),
+        Type::TIMESTAMP => row
+            .try_get::<_, Option<NaiveDateTime>>(i)?
+            .map(|t| Value::String(t.format("%Y-%m-%dT%H:%M:%S%.3f").to_string()))
+            .unwrap_or(Value::Null),
+        Type::TIMESTAMPTZ => row
+            .try_get::<_, Option<DateTime<Utc>>>(i)?
+            .map(|t| Value::String(t.to_rfc3339_opts(SecondsFormat::Millis, true)))
+            .unwrap_or(Value::Null),
+        Type::DATE => row
+            .try_get::<_, Option<NaiveDate>>(i)?
+            .map(|d| Value::String(d.to_string()))
+            .unwrap_or(Value::Null),
+        Type::BYTEA => row.try_get::<_, Option<Vec<u8>>>(i)?.map(|b| Value::String(hex(&b))).unwrap_or(Value::Null),
+        _ => row.try_get::<_, Option<Raw>>(i)?.map(|r| r.0).unwrap_or(Value::Null),
+    };
+    Ok(value)
+}
+
+// Rows as JSON objects keyed by column name, the shape the node bridge returns
+pub fn row_to_json(row: &Row) -> Result<Value, tokio_postgres::Error> {
+    let mut object = Map::with_capacity(row.len());
+    for (i, column) in row.columns().iter().enumerate() {
+        object.insert(column.name().to_string(), column_value(row, i, column.type_())?);
+    }
+    Ok(Value::Object(object))
+}
+
+// The rows and row count of a script's last statement. The simple query protocol
+// sends every cell as text, so the rows hold strings and nulls.
+pub fn script_result(messages: &[SimpleQueryMessage]) -> (Vec<Value>, usize) {
+    let (mut rows, mut last, mut row_count) = (Vec::new(), Vec::new(), 0);
+    for message in messages {
+        match message {
+            SimpleQueryMessage::Row(row) => {
+                let object = row
+                    .columns()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| (column.name().to_string(), row.get(i).map_or(Value::Null, |cell| Value::String(cell.to_string()))))
+                    .collect();
+                rows.push(Value::Object(object));
+            }
+            SimpleQueryMessage::CommandComplete(count) => {
+                last = std::mem::take(&mut rows);
+                row_count = *count as usize;
+            }
+            _ => {}
+        }
+    }
+    (last, row_count)
+}
+
+// A cell exactly as the backend sent it
+struct RawBytes<'a>(&'a [u8]);
+
+impl<'a> FromSql<'a> for RawBytes<'a> {
+    fn from_sql(_ty: &Type, raw: &'a [u8]) -> Result<RawBytes<'a>, BoxError> {
+        Ok(RawBytes(raw))
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+}
+
+// Rows as arrays of undecoded cells, hex encoded like bytea, for raw queries
+pub fn row_to_raw(row: &Row) -> Result<Value, tokio_postgres::Error> {
+    let mut cells = Vec::with_capacity(row.len());
+    for i in 0..row.len() {
+        let cell = row.try_get::<_, Option<RawBytes>>(i)?;
+        cells.push(cell.map(|bytes| Value::String(hex(bytes.0))).unwrap_or(Value::Null));
+    }
+    Ok(Value::Array(cells))
+}
+
+// Statements run through the extended protocol, which asks for every column in binary
+pub fn raw_columns(columns: &[Column]) -> Vec<RawColumn> {
+    columns
+        .iter()
+        .map(|column| RawColumn { name: column.name().to_string(), type_oid: column.type_().oid(), format: 1 })
+        .collect()
+}
+
+// What `describe` reports: the parameter types Postgres resolved and the result columns
+pub fn describe_statement(statement: &Statement) -> StatementDescription {
+    StatementDescription {
+        params: statement.params().iter().map(|ty| DescribedType { type_oid: ty.oid(), type_name: ty.name().to_string() }).collect(),
+        columns: statement
+            .columns()
+            .iter()
+            .map(|column| DescribedColumn { name: column.name().to_string(), type_oid: column.type_().oid(), type_name: column.type_().name().to_string() })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_params_follow_placeholder_types() {
+        let params = to_params(
+            &[json!(42), json!("7"), json!(null), json!({"a": 1}), json!(true)],
+            &[],
+            &[Type::INT4, Type::INT8, Type::TEXT, Type::JSONB, Type::BOOL],
+        )
+        .unwrap();
+        assert_eq!(
+            params,
+            vec![
+                SqlParam::Int4(42),
+                SqlParam::Int8(7),
+                SqlParam::Null,
+                SqlParam::Json(json!({"a": 1})),
+                SqlParam::Bool(true),
+            ]
+        );
+
+        assert!(to_param(&json!(70000), &Type::INT2).unwrap_err().contains("out of range"));
+        assert!(to_params(&[json!(1)], &[], &[]).unwrap_err().contains("expects 0"));
+    }
+
+    #[test]
+    fn test_param_type_hints() {
+        let names = [Some("INT8".to_string()), None, Some("uuid[]".to_string()), Some("double precision".to_string())];
+        let types = param_types(&names).unwrap();
+        assert_eq!(types[0], Type::INT8);
+        assert_eq!(types[1].oid(), 0);
+        assert_eq!(types[2], Type::UUID_ARRAY);
+        assert_eq!(types[3], Type::FLOAT8);
+        assert_eq!(param_types(&[None, Some("money".to_string())]).unwrap_err(), "Parameter $2: Unknown parameter type 'money'; cast the placeholder in the SQL instead");
+
+        let id = "6f1c1a3e-8a0b-4c84-9a52-0b1f1f0e4b7d";
+        let array = to_param(&json!([id, null]), &Type::UUID_ARRAY).unwrap();
+        assert_eq!(array, SqlParam::Array(vec![SqlParam::Uuid(Uuid::parse_str(id).unwrap()), SqlParam::Null]));
+        assert!(to_param(&json!("x"), &Type::INT4_ARRAY).unwrap_err().contains("not a valid int4[]"));
+    }
+
+    #[test]
+    fn test_binary_params_bind_by_position() {
+        let binary = [None, Some(BinaryValue::Float8Array(vec![0.5, 2.0]).to_param()), Some(BinaryValue::Int4(7).to_param())];
+        let params = to_params(&[json!("a")], &binary, &[Type::TEXT, Type::FLOAT8_ARRAY, Type::INT8]).unwrap();
+        assert_eq!(params, vec![SqlParam::Text("a".to_string()), SqlParam::Float8Array(vec![0.5, 2.0]), SqlParam::Int8(7)]);
+
+        let epoch = BinaryValue::timestamptz_from_unix_ms(0.0).to_param();
+        assert_eq!(binary_param(&epoch, &Type::TIMESTAMPTZ).unwrap(), SqlParam::TimestampTz(DateTime::UNIX_EPOCH));
+        assert!(binary_param(&BinaryValue::Int8(1 << 40).to_param(), &Type::INT4).unwrap_err().contains("out of range"));
+        let bytes = BinaryValue::Bytea(vec![1, 2]).to_param();
+        assert_eq!(binary_param(&bytes, &Type::TEXT).unwrap_err(), "A binary bytea can't bind to a text placeholder; cast it, e.g. $1::bytea");
+    }
+
+    #[test]
+    fn test_numeric_to_string() {
+        fn encode(ndigits: i16, weight: i16, sign: u16, dscale: i16, digits: &[i16]) -> Vec<u8> {
+            let mut raw = Vec::new();
+            for v in [ndigits, weight, sign as i16, dscale].iter().chain(digits) {
+                raw.extend_from_slice(&v.to_be_bytes());
+            }
+            raw
+        }
+        // 12345.678
+        assert_eq!(numeric_to_string(&encode(3, 1, 0, 3, &[1, 2345, 6780])).unwrap(), "12345.678");
+        // -0.0005
+        assert_eq!(numeric_to_string(&encode(1, -1, 0x4000, 4, &[5])).unwrap(), "-0.0005");
+        // 10000 (trailing zero group omitted on the wire)
+        assert_eq!(numeric_to_string(&encode(1, 1, 0, 0, &[1])).unwrap(), "10000");
+        assert_eq!(numeric_to_string(&encode(0, 0, 0xC000, 0, &[])).unwrap(), "NaN");
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use bridge_protocol::encryption::{EncryptedPayload, FrameCipher};
+use bridge_protocol::{FrameError, WebSocketMessage};
+use tokio::sync::mpsc::UnboundedSender;
+
+// The connection's cipher, set once `hello` agreed on keys. Shared by the session, the
+// reader answering `cancel` and the tasks sending progress and notifications.
+pub type SharedCipher = Arc<Mutex<Option<FrameCipher>>>;
+
+pub fn payload(message: WebSocketMessage) -> Result<EncryptedPayload, FrameError> {
+    serde_json::from_value::<EncryptedPayload>(message.payload).map_err(|e| FrameError::Encryption(format!("Invalid encrypted payload: {}", e)))
+}
+
+// The frame inside an `encrypted` message; its counter can't be opened again
+pub fn open(cipher: &SharedCipher, message: WebSocketMessage) -> Result<String, FrameError> {
+    let id = message.id.clone();
+    let payload = payload(message)?;
+    match cipher.lock().unwrap().as_mut() {
+        Some(cipher) => cipher.open(&payload, id.as_deref()),
+        None => Err(FrameError::Encryption("Send `hello` before encrypted messages".to_string())),
+    }
+}
+
+// Sends frames from outside the session loop: cancel replies, progress and
+// notifications. They are small, so they skip compression and chunking, but not
+// encryption.
+#[derive(Clone)]
+pub struct DirectOut {
+    out: UnboundedSender<String>,
+    cipher: SharedCipher,
+    // Whether the bridge has a key, so only encrypted frames count
+    encrypted: bool,
+}
+
+impl DirectOut {
+    pub fn new(out: UnboundedSender<String>, cipher: SharedCipher, encrypted: bool) -> DirectOut {
+        DirectOut { out, cipher, encrypted }
+    }
+
+    pub fn encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub fn cipher(&self) -> &SharedCipher {
+        &self.cipher
+    }
+
+    // False once the connection's writer has gone away
+    pub fn send(&self, message: WebSocketMessage) -> bool {
+        // Queued under the lock, so frames leave in counter order
+        let mut cipher = self.cipher.lock().unwrap();
+        let frame = match cipher.as_mut() {
+            Some(cipher) => cipher.seal(&message.to_json(), message.id.clone()).to_json(),
+            None => message.to_json(),
+        };
+        self.out.send(frame).is_ok()
+    }
+}
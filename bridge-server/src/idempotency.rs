@@ -0,0 +1,64 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde_json::Value;
+
+// Results of replayed writes, keyed by the client's tenant and idempotency key, so a
+// key never answers for another tenant's write. Oldest entries are evicted first once
+// `capacity` is reached.
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    results: HashMap<String, Value>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+fn scoped(tenant: Option<&str>, key: &str) -> String {
+    format!("{}\u{0}{}", tenant.unwrap_or_default(), key)
+}
+
+impl IdempotencyCache {
+    pub fn new(capacity: usize) -> IdempotencyCache {
+        IdempotencyCache { results: HashMap::new(), order: VecDeque::new(), capacity }
+    }
+
+    pub fn get(&self, tenant: Option<&str>, key: &str) -> Option<&Value> {
+        self.results.get(&scoped(tenant, key))
+    }
+
+    pub fn insert(&mut self, tenant: Option<&str>, key: &str, result: Value) {
+        let key = scoped(tenant, key);
+        if self.results.insert(key.clone(), result).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_evicts_oldest() {
+        let mut cache = IdempotencyCache::new(2);
+        cache.insert(None, "a", json!(1));
+        cache.insert(None, "b", json!(2));
+        cache.insert(None, "c", json!(3));
+        assert!(cache.get(None, "a").is_none());
+        assert_eq!(cache.get(None, "c"), Some(&json!(3)));
+    }
+
+    #[test]
+    fn test_keys_are_per_tenant() {
+        let mut cache = IdempotencyCache::new(2);
+        cache.insert(Some("acme"), "a", json!(1));
+        assert_eq!(cache.get(Some("acme"), "a"), Some(&json!(1)));
+        assert!(cache.get(Some("globex"), "a").is_none());
+        assert!(cache.get(None, "a").is_none());
+    }
+}
@@ -0,0 +1,61 @@
+mod admin;
+mod cancel;
+mod config;
+mod continuation;
+mod convert;
+mod encryption;
+mod idempotency;
+mod pooling;
+mod prepared;
+mod rate_limit;
+mod result_cache;
+mod resume;
+mod schema_events;
+mod schemas;
+mod server;
+mod session;
+
+use std::sync::Arc;
+
+use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use tokio_postgres::NoTls;
+
+use crate::config::Config;
+use crate::server::Server;
+
+#[tokio::main]
+async fn main() {
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("[bridge] {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let pg_config: tokio_postgres::Config = match config.database_url.parse() {
+        Ok(pg_config) => pg_config,
+        Err(e) => {
+            eprintln!("[bridge] Invalid DATABASE_URL: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // Sessions may leave SET, LISTEN or temp tables behind; clean them before reuse
+    let manager = Manager::from_config(pg_config, NoTls, ManagerConfig { recycling_method: RecyclingMethod::Clean });
+    let pool = match Pool::builder(manager).max_size(config.pool_size).build() {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("[bridge] Failed to create connection pool: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if !config.auth_required() {
+        println!("[bridge] BRIDGE_API_KEYS is not set; accepting unauthenticated clients");
+    }
+    let server = Arc::new(Server::new(config, pool));
+    if let Err(e) = server.run().await {
+        eprintln!("[bridge] Server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,173 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use bridge_protocol::sql::{statements, Token};
+use bridge_protocol::{PoolMode, PoolRequest};
+use tokio_postgres::NoTls;
+
+use crate::server::Server;
+
+// The Postgres connection behind a session: borrowed from the pool, or opened for the
+// session alone
+pub enum Backend {
+    Pooled(deadpool_postgres::Object),
+    // Holds its slot until dropped
+    Dedicated { client: tokio_postgres::Client, _slot: DedicatedSlot },
+}
+
+impl Deref for Backend {
+    type Target = tokio_postgres::Client;
+
+    fn deref(&self) -> &tokio_postgres::Client {
+        match self {
+            Backend::Pooled(object) => object,
+            Backend::Dedicated { client, .. } => client,
+        }
+    }
+}
+
+// One of the `BRIDGE_DEDICATED_BACKENDS`, given back when dropped
+pub struct DedicatedSlot(Arc<AtomicUsize>);
+
+impl DedicatedSlot {
+    pub fn reserve(in_use: &Arc<AtomicUsize>, limit: usize) -> Option<DedicatedSlot> {
+        in_use
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < limit).then_some(n + 1))
+            .ok()
+            .map(|_| DedicatedSlot(in_use.clone()))
+    }
+}
+
+impl Drop for DedicatedSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Backend {
+    pub async fn acquire(server: &Server, mode: PoolMode) -> Result<Backend, String> {
+        if mode == PoolMode::Dedicated {
+            match DedicatedSlot::reserve(&server.dedicated, server.config.dedicated_backends) {
+                Some(slot) => {
+                    let (client, connection) = tokio_postgres::connect(&server.config.database_url, NoTls)
+                        .await
+                        .map_err(|e| format!("Failed to open a dedicated database connection: {}", e))?;
+                    tokio::spawn(async move {
+                        if let Err(e) = connection.await {
+                            eprintln!("[bridge] Dedicated database connection failed: {}", e);
+                        }
+                    });
+                    return Ok(Backend::Dedicated { client, _slot: slot });
+                }
+                // Others took the slots since `hello`; share the pool instead
+                None => eprintln!("[bridge] No dedicated backend left, using the pool"),
+            }
+        }
+        let object = server.pool.get().await.map_err(|e| format!("Failed to acquire a database connection: {}", e))?;
+        Ok(Backend::Pooled(object))
+    }
+}
+
+// Settle a client's pool request within the bridge's limits: dedicated backends only
+// while some are free, no more kept statements than `max_statements` and idle bounds
+// within the pool's size
+pub fn grant(request: &PoolRequest, max_statements: usize, pool_size: usize, dedicated_free: bool) -> PoolRequest {
+    let mode = match request.mode.unwrap_or_default() {
+        PoolMode::Dedicated if !dedicated_free => PoolMode::Session,
+        mode => mode,
+    };
+    // A pooled session's statements would be lost with every release
+    let statement_cache = request.statement_cache.unwrap_or(true) && max_statements > 0 && mode != PoolMode::Pooled;
+    let max_statements = match statement_cache {
+        true => request.max_statements.map_or(max_statements, |n| n.min(max_statements)),
+        false => 0,
+    };
+    // A dedicated session never touches the pool
+    let (min_idle, max_idle) = match mode {
+        PoolMode::Dedicated => (None, None),
+        _ => {
+            let max_idle = request.max_idle.map(|n| n.min(pool_size));
+            let min_idle = request.min_idle.map(|n| n.min(max_idle.unwrap_or(pool_size)));
+            (min_idle, max_idle)
+        }
+    };
+    PoolRequest { mode: Some(mode), statement_cache: Some(statement_cache), max_statements: Some(max_statements), min_idle, max_idle }
+}
+
+// Open pool connections until at least `min_idle` sit idle, or the pool is full
+pub async fn warm(pool: &deadpool_postgres::Pool, min_idle: usize) {
+    let mut held = Vec::new();
+    while held.len() < min_idle {
+        let status = pool.status();
+        if status.available == 0 && status.size >= status.max_size {
+            break;
+        }
+        match pool.get().await {
+            Ok(object) => held.push(object),
+            Err(e) => {
+                eprintln!("[bridge] Failed to warm the pool: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+// Whether a transaction is open once `sql` has run successfully, given whether one was
+// open before it
+pub fn in_transaction_after(sql: &str, before: bool) -> bool {
+    statements(sql).iter().fold(before, |open, tokens| {
+        let words: Vec<&str> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Word(word) => Some(word.as_str()),
+                _ => None,
+            })
+            .collect();
+        match words.first().copied() {
+            Some("begin" | "start") => true,
+            Some("commit" | "end" | "abort") => false,
+            // ROLLBACK TO SAVEPOINT stays in the transaction
+            Some("rollback") => words.contains(&"to"),
+            Some("prepare") if words.get(1) == Some(&"transaction") => false,
+            _ => open,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_within_bounds() {
+        let dedicated = PoolRequest { mode: Some(PoolMode::Dedicated), max_statements: Some(500), ..Default::default() };
+        let granted = grant(&dedicated, 100, 16, true);
+        assert_eq!(granted.mode, Some(PoolMode::Dedicated));
+        assert_eq!(granted.max_statements, Some(100));
+        assert_eq!(grant(&dedicated, 100, 16, false).mode, Some(PoolMode::Session));
+
+        let pooled = PoolRequest { mode: Some(PoolMode::Pooled), ..Default::default() };
+        assert_eq!(grant(&pooled, 100, 16, true).statement_cache, Some(false));
+        let no_cache = PoolRequest { statement_cache: Some(false), ..Default::default() };
+        assert_eq!(grant(&no_cache, 100, 16, true).max_statements, Some(0));
+
+        let idle = PoolRequest { mode: Some(PoolMode::Pooled), min_idle: Some(50), max_idle: Some(40), ..Default::default() };
+        let granted = grant(&idle, 100, 16, true);
+        assert_eq!((granted.min_idle, granted.max_idle), (Some(16), Some(16)));
+        let granted = grant(&PoolRequest { min_idle: Some(8), max_idle: Some(4), ..Default::default() }, 100, 16, true);
+        assert_eq!((granted.min_idle, granted.max_idle), (Some(4), Some(4)));
+        let granted = grant(&PoolRequest { mode: Some(PoolMode::Dedicated), min_idle: Some(2), ..Default::default() }, 100, 16, true);
+        assert_eq!(granted.min_idle, None);
+    }
+
+    #[test]
+    fn test_transaction_tracking() {
+        assert!(in_transaction_after("BEGIN", false));
+        assert!(in_transaction_after("SELECT 1", true));
+        assert!(in_transaction_after("ROLLBACK TO SAVEPOINT a", true));
+        assert!(!in_transaction_after("rollback", true));
+        assert!(!in_transaction_after("BEGIN; INSERT INTO t VALUES (1); COMMIT", false));
+        assert!(!in_transaction_after("DO $$ BEGIN PERFORM 1; END $$", false));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, VecDeque};
+
+// Server-side prepared statements a session keeps for SQL it repeats. SQL is promoted
+// once it has run `threshold` times; anything rarer is prepared for one query and
+// closed again. The least recently used statement is evicted, and so closed, once
+// `capacity` is reached.
+#[derive(Debug)]
+pub struct PreparedCache<S> {
+    statements: HashMap<String, Kept<S>>,
+    // Least recently used first
+    order: VecDeque<String>,
+    // Runs of SQL not promoted yet, oldest first; bounded like the statements
+    uses: VecDeque<(String, u32)>,
+    capacity: usize,
+    threshold: u32,
+    // Numbers the handles clients use to refer to kept statements
+    promoted: u64,
+}
+
+#[derive(Debug)]
+struct Kept<S> {
+    statement: S,
+    handle: String,
+    // Queries it has served
+    uses: u64,
+}
+
+impl<S: Clone> PreparedCache<S> {
+    pub fn new(capacity: usize, threshold: u32) -> PreparedCache<S> {
+        PreparedCache {
+            statements: HashMap::new(),
+            order: VecDeque::new(),
+            uses: VecDeque::new(),
+            capacity,
+            threshold,
+            promoted: 0,
+        }
+    }
+
+    // The promoted statement for `sql`, marking it most recently used
+    pub fn get(&mut self, sql: &str) -> Option<S> {
+        let kept = self.statements.get_mut(sql)?;
+        kept.uses += 1;
+        let statement = kept.statement.clone();
+        if let Some(position) = self.order.iter().position(|s| s == sql) {
+            let sql = self.order.remove(position).expect("position is in range");
+            self.order.push_back(sql);
+        }
+        Some(statement)
+    }
+
+    // The promoted statement for `sql`, without counting a use
+    pub fn peek(&self, sql: &str) -> Option<S> {
+        self.statements.get(sql).map(|kept| kept.statement.clone())
+    }
+
+    // Count a run of SQL that isn't promoted; true once it has run often enough to be
+    pub fn record_use(&mut self, sql: &str) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+        let count = match self.uses.iter().position(|(s, _)| s == sql) {
+            Some(position) => self.uses.remove(position).expect("position is in range").1 + 1,
+            None => 1,
+        };
+        if count >= self.threshold {
+            return true;
+        }
+        self.uses.push_back((sql.to_string(), count));
+        while self.uses.len() > self.capacity * 4 {
+            self.uses.pop_front();
+        }
+        false
+    }
+
+    // Keep `statement` for `sql`, returning the SQL it evicted
+    pub fn insert(&mut self, sql: &str, statement: S) -> Option<String> {
+        self.promoted += 1;
+        let kept = Kept { statement, handle: format!("stmt_{}", self.promoted), uses: u64::from(self.threshold) };
+        if self.statements.insert(sql.to_string(), kept).is_none() {
+            self.order.push_back(sql.to_string());
+        }
+        if self.order.len() <= self.capacity {
+            return None;
+        }
+        let evicted = self.order.pop_front()?;
+        self.statements.remove(&evicted);
+        Some(evicted)
+    }
+
+    pub fn remove(&mut self, sql: &str) -> bool {
+        self.order.retain(|s| s != sql);
+        self.statements.remove(sql).is_some()
+    }
+
+    // The SQL of the statement kept under `handle`
+    pub fn find(&self, handle: &str) -> Option<String> {
+        self.order.iter().find(|sql| self.statements.get(*sql).is_some_and(|kept| kept.handle == handle)).cloned()
+    }
+
+    // Handles, SQL and use counts of the kept statements, least recently used first
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, u64)> {
+        self.order
+            .iter()
+            .filter_map(|sql| self.statements.get(sql).map(|kept| (kept.handle.as_str(), sql.as_str(), kept.uses)))
+    }
+
+    // Forget everything, e.g. for a different backend connection; returns how many
+    // statements were kept
+    pub fn clear(&mut self) -> usize {
+        let kept = self.statements.len();
+        self.statements.clear();
+        self.order.clear();
+        self.uses.clear();
+        kept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promotes_repeated_sql_and_evicts_lru() {
+        let mut cache = PreparedCache::new(2, 2);
+        assert!(!cache.record_use("SELECT 1"));
+        assert!(cache.record_use("SELECT 1"));
+        cache.insert("SELECT 1", 1);
+        cache.insert("SELECT 2", 2);
+        assert_eq!(cache.get("SELECT 1"), Some(1));
+        // SELECT 2 is now the least recently used
+        assert_eq!(cache.insert("SELECT 3", 3).as_deref(), Some("SELECT 2"));
+        assert_eq!(cache.get("SELECT 2"), None);
+        assert_eq!(cache.get("SELECT 3"), Some(3));
+        let entries: Vec<(&str, &str, u64)> = cache.entries().collect();
+        assert_eq!(entries, vec![("stmt_1", "SELECT 1", 3), ("stmt_3", "SELECT 3", 3)]);
+        assert_eq!(cache.find("stmt_3").as_deref(), Some("SELECT 3"));
+        assert_eq!(cache.clear(), 2);
+
+        let mut disabled: PreparedCache<u32> = PreparedCache::new(0, 1);
+        assert!(!disabled.record_use("SELECT 1"));
+    }
+}
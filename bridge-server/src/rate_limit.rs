@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+// Token bucket limiting the queries one session runs: `burst` at once, refilled at
+// `rate` per second
+#[derive(Debug)]
+pub struct QueryRateLimit {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl QueryRateLimit {
+    pub fn new(rate: f64, burst: f64, now: Instant) -> QueryRateLimit {
+        QueryRateLimit { rate, burst, tokens: burst, updated: now }
+    }
+
+    // Take a token for one query, or say how long until the next one is available
+    pub fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refills_at_the_rate() {
+        let start = Instant::now();
+        let mut limit = QueryRateLimit::new(10.0, 2.0, start);
+        assert!(limit.take(start).is_ok());
+        assert!(limit.take(start).is_ok());
+        assert_eq!(limit.take(start).unwrap_err().as_millis(), 100);
+        assert_eq!(limit.take(start + Duration::from_millis(50)).unwrap_err().as_millis(), 50);
+        assert!(limit.take(start + Duration::from_millis(100)).is_ok());
+        // Idle time never saves up more than the burst
+        let later = start + Duration::from_secs(60);
+        assert!(limit.take(later).is_ok() && limit.take(later).is_ok());
+        assert!(limit.take(later).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use bridge_protocol::sql::{statements, tokenize, Token};
+use serde_json::Value;
+
+// Results of hinted read queries, shared by every session. Oldest entries are evicted
+// first once `capacity` is reached; a capacity of 0 turns the cache off.
+#[derive(Debug)]
+pub struct ResultCache {
+    results: HashMap<String, (Instant, Value)>,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+// Only a single plain read may be served from the cache. Locking reads and SELECT INTO
+// have effects, and so do data-modifying CTEs and set_config calls.
+pub fn is_cacheable(sql: &str) -> bool {
+    let statements = statements(sql);
+    let [tokens] = statements.as_slice() else {
+        return false;
+    };
+    let words: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Word(word) => Some(word.as_str()),
+            _ => None,
+        })
+        .collect();
+    let has = |word: &str| words.contains(&word);
+    matches!(words.first(), Some(&("select" | "values" | "table" | "with")))
+        && !["into", "insert", "update", "delete", "merge", "for", "set_config"].iter().any(|word| has(word))
+}
+
+// Whether `sql` may change what the session's later reads return: SET (ROLE,
+// search_path, TimeZone, the GUCs behind row-level security), RESET, DISCARD and
+// set_config calls
+pub fn changes_settings(sql: &str) -> bool {
+    let sets = statements(sql)
+        .iter()
+        .any(|tokens| matches!(tokens.first(), Some(Token::Word(word)) if matches!(word.as_str(), "set" | "reset" | "discard")));
+    sets || tokenize(sql).iter().any(|token| matches!(token, Token::Word(word) if word == "set_config"))
+}
+
+// Fold a settings change into a session's fingerprint, which starts at 0
+pub fn fold_settings(fingerprint: u64, sql: &str, params: &[Value]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    fingerprint.hash(&mut hasher);
+    sql.hash(&mut hasher);
+    Value::Array(params.to_vec()).to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+// Entries are per tenant and per history of settings changes, so a key never sees
+// another tenant's rows or rows read under other settings. The SQL and parameters are
+// always part of it; a client's `key` only narrows it further.
+pub fn cache_key(tenant: Option<&str>, settings: u64, key: Option<&str>, sql: &str, params: &[Value]) -> String {
+    format!(
+        "{}\u{0}{:016x}\u{0}{}\u{0}{}\u{0}{}",
+        tenant.unwrap_or_default(),
+        settings,
+        key.unwrap_or_default(),
+        sql,
+        Value::Array(params.to_vec())
+    )
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> ResultCache {
+        ResultCache { results: HashMap::new(), order: VecDeque::new(), capacity }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    // The cached result for `key` and its age, unless it is older than `max_age`
+    pub fn get(&self, key: &str, max_age: Duration, now: Instant) -> Option<(Value, Duration)> {
+        let (stored, result) = self.results.get(key)?;
+        let age = now.saturating_duration_since(*stored);
+        (age <= max_age).then(|| (result.clone(), age))
+    }
+
+    pub fn insert(&mut self, key: String, result: Value, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+        if self.results.insert(key.clone(), (now, result)).is_some() {
+            self.order.retain(|k| *k != key);
+        }
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+
+    // Drop every result, e.g. after DDL may have changed what they would be
+    pub fn clear(&mut self) {
+        self.results.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_serves_fresh_reads_only() {
+        assert!(is_cacheable("SELECT * FROM products WHERE id = $1"));
+        assert!(is_cacheable("WITH t AS (SELECT 1) SELECT * FROM t"));
+        assert!(!is_cacheable("SELECT * FROM jobs FOR UPDATE SKIP LOCKED"));
+        assert!(!is_cacheable("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"));
+        assert!(!is_cacheable("SELECT 1; SELECT 2"));
+        assert!(!is_cacheable("UPDATE t SET n = 1"));
+        assert!(!is_cacheable("SELECT set_config('role', 'admin', false)"));
+        assert_ne!(cache_key(Some("a"), 0, None, "SELECT 1", &[]), cache_key(Some("b"), 0, None, "SELECT 1", &[]));
+        assert_ne!(cache_key(None, 0, None, "SELECT $1", &[json!(1)]), cache_key(None, 0, None, "SELECT $1", &[json!(2)]));
+        // A client key can't make another query's result its own
+        assert_ne!(cache_key(None, 0, Some("x"), "SELECT * FROM a", &[]), cache_key(None, 0, Some("x"), "SELECT * FROM b", &[]));
+
+        let start = Instant::now();
+        let mut cache = ResultCache::new(2);
+        cache.insert("a".to_string(), json!(1), start);
+        let later = start + Duration::from_secs(5);
+        assert_eq!(cache.get("a", Duration::from_secs(10), later), Some((json!(1), Duration::from_secs(5))));
+        assert!(cache.get("a", Duration::from_secs(1), later).is_none());
+
+        cache.insert("b".to_string(), json!(2), start);
+        cache.insert("a".to_string(), json!(3), later);
+        cache.insert("c".to_string(), json!(4), later);
+        assert!(cache.get("b", Duration::MAX, later).is_none());
+        assert_eq!(cache.get("a", Duration::MAX, later).map(|(value, _)| value), Some(json!(3)));
+
+        let mut off = ResultCache::new(0);
+        off.insert("a".to_string(), json!(1), start);
+        assert!(off.get("a", Duration::MAX, start).is_none());
+    }
+
+    #[test]
+    fn test_settings_changes_split_the_cache() {
+        assert!(changes_settings("SET search_path TO tenant_a"));
+        assert!(changes_settings("reset role"));
+        assert!(changes_settings("SELECT set_config($1, $2, false)"));
+        assert!(changes_settings("SELECT 1; SET TIME ZONE 'UTC'"));
+        assert!(!changes_settings("SELECT * FROM settings WHERE name = 'set'"));
+        assert!(!changes_settings("UPDATE t SET n = 1"));
+
+        let changed = fold_settings(0, "SET search_path TO tenant_a", &[]);
+        assert_ne!(changed, 0);
+        assert_eq!(changed, fold_settings(0, "SET search_path TO tenant_a", &[]));
+        assert_ne!(changed, fold_settings(0, "SET search_path TO tenant_b", &[]));
+        assert_ne!(cache_key(None, 0, None, "SELECT 1", &[]), cache_key(None, changed, None, "SELECT 1", &[]));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Sessions whose client disconnected, kept under their resume token until the window
+// closes. Dropping one returns its connections to the pool.
+pub struct ParkedSessions<T> {
+    sessions: Mutex<HashMap<String, (T, Instant)>>,
+}
+
+impl<T> Default for ParkedSessions<T> {
+    fn default() -> Self {
+        ParkedSessions { sessions: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl<T> ParkedSessions<T> {
+    pub fn park(&self, token: &str, session: T, window: Duration, now: Instant) {
+        self.sessions.lock().unwrap().insert(token.to_string(), (session, now + window));
+    }
+
+    // The session parked under `token`, if it hasn't expired and `owns` it. One that
+    // isn't owned stays parked for its rightful client.
+    pub fn take(&self, token: &str, now: Instant, owns: impl FnOnce(&T) -> bool) -> Option<T> {
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.get(token).is_some_and(|(session, _)| owns(session)) {
+            return None;
+        }
+        let (session, expires) = sessions.remove(token)?;
+        (expires > now).then_some(session)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    // Drop sessions whose window has closed
+    pub fn sweep(&self, now: Instant) {
+        self.sessions.lock().unwrap().retain(|_, (_, expires)| *expires > now);
+    }
+}
+
+// 128 random bits; the token is all it takes to reattach a session, like a cookie
+pub fn new_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_park_take_and_expire() {
+        let parked = ParkedSessions::default();
+        let now = Instant::now();
+        let window = Duration::from_secs(30);
+        parked.park("a", 1, window, now);
+        parked.park("b", 2, window, now);
+        assert_eq!(parked.take("a", now + Duration::from_secs(10), |_| true), Some(1));
+        assert_eq!(parked.take("a", now, |_| true), None);
+        // Another client's session stays parked
+        assert_eq!(parked.take("b", now, |session| *session == 1), None);
+        assert_eq!(parked.len(), 1);
+
+        parked.sweep(now + Duration::from_secs(31));
+        assert_eq!(parked.take("b", now, |_| true), None);
+        assert_eq!(new_token().len(), 32);
+    }
+}
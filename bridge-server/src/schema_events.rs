@@ -0,0 +1,63 @@
+use std::future::poll_fn;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bridge_protocol::{SchemaChange, SCHEMA_CHANGE_CHANNEL};
+use tokio_postgres::{AsyncMessage, NoTls};
+
+use crate::server::Server;
+
+// Wait before opening a new LISTEN connection after one fails
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// A NOTIFY payload from the event triggers; anything else sent on the channel is ignored
+pub fn parse(payload: &str) -> Option<SchemaChange> {
+    serde_json::from_str(payload).ok()
+}
+
+// LISTEN on `SCHEMA_CHANGE_CHANNEL` for as long as the bridge runs, passing each change
+// to `Server::schema_changed`
+pub async fn watch(server: Arc<Server>) {
+    loop {
+        if let Err(e) = listen(&server).await {
+            eprintln!("[bridge] Schema change LISTEN failed: {}; retrying in {}s", e, RETRY_DELAY.as_secs());
+        }
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+async fn listen(server: &Arc<Server>) -> Result<(), tokio_postgres::Error> {
+    let (client, mut connection) = tokio_postgres::connect(&server.config.database_url, NoTls).await?;
+    let forward = server.clone();
+    // Ends when the connection does, which dropping `client` brings about
+    let driver = tokio::spawn(async move {
+        while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => match parse(n.payload()) {
+                    Some(change) => forward.schema_changed(change),
+                    None => eprintln!("[bridge] Ignoring malformed schema change: {}", n.payload()),
+                },
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    });
+    client.batch_execute(&format!("LISTEN {}", SCHEMA_CHANGE_CHANNEL)).await?;
+    println!("[bridge] Listening for schema changes on {}", SCHEMA_CHANGE_CHANNEL);
+    let outcome = driver.await.unwrap_or(Ok(()));
+    drop(client);
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let change = parse(r#"{"commandTag":"ALTER TABLE","objectType":"table","schema":"public","object":"public.orders"}"#).unwrap();
+        assert_eq!(change.object.as_deref(), Some("public.orders"));
+        assert!(parse("vacuum done").is_none());
+    }
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use bridge_protocol::RawColumn;
+
+// Distinct result shapes a session tracks; past this, new shapes are always sent whole
+pub const MAX_SCHEMAS: usize = 256;
+
+// Raw result shapes the client has been sent, so a statement run again can answer with
+// the shape's id instead of its columns. Ids are numbered per session, and a resumed
+// session keeps them along with the client's copy.
+#[derive(Debug, Default)]
+pub struct SentSchemas {
+    ids: HashMap<String, String>,
+}
+
+impl SentSchemas {
+    // The id for `columns`, and whether the client already has them; None once the
+    // session tracks as many shapes as it may
+    pub fn share(&mut self, columns: &[RawColumn]) -> Option<(String, bool)> {
+        let signature: Vec<String> = columns.iter().map(|column| format!("{}:{}:{}", column.type_oid, column.format, column.name)).collect();
+        let signature = signature.join(",");
+        if let Some(id) = self.ids.get(&signature) {
+            return Some((id.clone(), true));
+        }
+        if self.ids.len() == MAX_SCHEMAS {
+            return None;
+        }
+        let id = format!("schema_{}", self.ids.len() + 1);
+        self.ids.insert(signature, id.clone());
+        Some((id, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, type_oid: u32) -> RawColumn {
+        RawColumn { name: name.to_string(), type_oid, format: 1 }
+    }
+
+    #[test]
+    fn test_shares_each_shape_once() {
+        let mut sent = SentSchemas::default();
+        let shape = vec![column("id", 23), column("name", 25)];
+        assert_eq!(sent.share(&shape), Some(("schema_1".to_string(), false)));
+        assert_eq!(sent.share(&shape), Some(("schema_1".to_string(), true)));
+        assert_eq!(sent.share(&[column("id", 20)]), Some(("schema_2".to_string(), false)));
+        for n in 0..MAX_SCHEMAS {
+            sent.share(&[column(&n.to_string(), 25)]);
+        }
+        assert_eq!(sent.share(&[column("late", 25)]), None);
+        assert_eq!(sent.share(&shape), Some(("schema_1".to_string(), true)));
+    }
+}
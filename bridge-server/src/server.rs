@@ -0,0 +1,260 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bridge_protocol::{close_code, close_reason, message_type, to_value, SchemaChange, ShutdownPayload, WebSocketMessage};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::admin::AdminState;
+use crate::cancel;
+use crate::config::Config;
+use crate::idempotency::IdempotencyCache;
+use crate::result_cache::ResultCache;
+use crate::resume::ParkedSessions;
+use crate::schema_events;
+use crate::session::{ParkedSession, Session};
+
+const MAX_IDEMPOTENT_RESULTS: usize = 1000;
+// Schema changes a busy session may fall behind by before it misses some
+const SCHEMA_CHANGE_BACKLOG: usize = 64;
+
+// Frames read ahead of the one a session is handling. Once full the reader stops reading,
+// and TCP pushes back on a client that keeps sending; a `cancel` behind a full backlog is
+// only read once there's room again.
+const FRAME_BACKLOG: usize = 64;
+
+// State shared by every connection
+pub struct Server {
+    pub config: Config,
+    pub pool: deadpool_postgres::Pool,
+    pub idempotent_results: Mutex<IdempotencyCache>,
+    pub result_cache: Mutex<ResultCache>,
+    pub parked: ParkedSessions<ParkedSession>,
+    pub admin: AdminState,
+    // Dedicated backends open right now
+    pub dedicated: Arc<AtomicUsize>,
+    // Flips to true once the bridge is stopping
+    shutdown: watch::Sender<bool>,
+    // DDL reported by the event triggers, for every session
+    schema_changes: broadcast::Sender<SchemaChange>,
+    next_client: AtomicU64,
+}
+
+// `?token=` on the connection URL, for clients that authenticate during the handshake
+pub fn token_from_query(query: Option<&str>) -> Option<String> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "token")
+        .map(|(_, value)| value.to_string())
+}
+
+impl Server {
+    pub fn new(config: Config, pool: deadpool_postgres::Pool) -> Server {
+        let result_cache = Mutex::new(ResultCache::new(config.result_cache_size));
+        Server {
+            config,
+            pool,
+            idempotent_results: Mutex::new(IdempotencyCache::new(MAX_IDEMPOTENT_RESULTS)),
+            result_cache,
+            parked: ParkedSessions::default(),
+            admin: AdminState::new(std::time::Instant::now()),
+            dedicated: Arc::new(AtomicUsize::new(0)),
+            shutdown: watch::Sender::new(false),
+            schema_changes: broadcast::channel(SCHEMA_CHANGE_BACKLOG).0,
+            next_client: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn run(self: Arc<Server>) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.config.addr).await?;
+        println!("[bridge] Listening on ws://{}", self.config.addr);
+        if self.config.schema_events {
+            tokio::spawn(schema_events::watch(self.clone()));
+        }
+        let stopping = shutdown_signal();
+        tokio::pin!(stopping);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    tokio::spawn(self.clone().handle_connection(stream, peer));
+                }
+                _ = &mut stopping => break,
+            }
+        }
+
+        // Sessions tell their clients between messages, so running queries get to finish
+        let grace = self.config.shutdown_grace;
+        println!("[bridge] Shutting down; telling clients to reconnect in {}ms", grace.as_millis());
+        self.shutdown.send_replace(true);
+        tokio::time::sleep(grace).await;
+        Ok(())
+    }
+
+    // Cached results may no longer be what the changed schema would give
+    pub fn schema_changed(&self, change: SchemaChange) {
+        println!("[bridge] Schema changed: {} {}", change.command_tag, change.object.as_deref().unwrap_or_default());
+        self.result_cache.lock().unwrap().clear();
+        let _ = self.schema_changes.send(change);
+    }
+
+    fn next_client_id(&self) -> String {
+        let n = self.next_client.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("client_{}_{}", n, chrono::Utc::now().timestamp_millis())
+    }
+
+    async fn handle_connection(self: Arc<Server>, stream: TcpStream, peer: SocketAddr) {
+        let mut handshake_token = None;
+        // The callback signature is fixed by tungstenite
+        #[allow(clippy::result_large_err)]
+        let callback = |request: &Request, response: Response| {
+            handshake_token = token_from_query(request.uri().query());
+            Ok(response)
+        };
+        // Oversized frames are refused while being read, before they are buffered whole
+        let limits = WebSocketConfig {
+            max_message_size: Some(self.config.max_message_size),
+            max_frame_size: Some(self.config.max_message_size),
+            ..WebSocketConfig::default()
+        };
+        let socket = match tokio_tungstenite::accept_hdr_async_with_config(stream, callback, Some(limits)).await {
+            Ok(socket) => socket,
+            Err(e) => {
+                eprintln!("[bridge] WebSocket handshake with {} failed: {}", peer, e);
+                return;
+            }
+        };
+
+        let authenticated =
+            !self.config.auth_required() || handshake_token.as_ref().is_some_and(|token| self.config.accepts(token));
+        let tenant = handshake_token.and_then(|token| self.config.tenant_for(&token).map(String::from));
+        let client_id = self.next_client_id();
+        println!("[bridge] New connection established: {} from {}", client_id, peer);
+        self.admin.connected(&client_id, peer.to_string());
+
+        let (mut sink, mut incoming) = socket.split();
+        let (out, mut outgoing) = mpsc::unbounded_channel::<String>();
+        // The close frame's code and reason, when the bridge has one to give
+        let closing: Arc<Mutex<Option<CloseFrame<'static>>>> = Arc::default();
+        let close_with = closing.clone();
+        let writer = tokio::spawn(async move {
+            while let Some(frame) = outgoing.recv().await {
+                if sink.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+            }
+            let frame = close_with.lock().unwrap().take();
+            if let Some(frame) = frame {
+                let _ = sink.send(Message::Close(Some(frame))).await;
+            }
+            let _ = sink.close().await;
+        });
+
+        let mut session = Session::new(self.clone(), client_id, authenticated, out.clone());
+        session.bind_tenant(tenant);
+        session.send(WebSocketMessage::result(
+            Some("welcome".to_string()),
+            json!({
+                "message": "Connected to WebSocket server",
+                "clientId": session.client_id(),
+                "authRequired": !authenticated,
+            }),
+        ));
+
+        // The reader answers `cancel` itself, since the session may be busy with the
+        // very query it targets
+        let (frames_in, mut frames) = mpsc::channel::<String>(FRAME_BACKLOG);
+        let running = session.running_query();
+        let reader_id = session.client_id().to_string();
+        let direct = session.direct_out();
+        let reader = tokio::spawn(async move {
+            while let Some(frame) = incoming.next().await {
+                match frame {
+                    Ok(Message::Text(text)) => {
+                        if !cancel::handle_frame(&running, &text, &direct) && frames_in.send(text).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        eprintln!("[bridge] Connection error for {}: {}", reader_id, e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        // Messages are handled one at a time, in order, on the session's connection
+        let mut stopping = self.shutdown.subscribe();
+        let mut schema_changes = self.schema_changes.subscribe();
+        loop {
+            tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(text) => {
+                        session.set_queued(frames.len());
+                        session.receive(&text).await
+                    }
+                    None => break,
+                },
+                change = schema_changes.recv(), if self.config.schema_events => match change {
+                    Ok(change) => session.schema_changed(Some(&change)),
+                    Err(RecvError::Lagged(_)) => session.schema_changed(None),
+                    Err(RecvError::Closed) => {}
+                },
+                _ = stopping.changed() => {
+                    let notice = ShutdownPayload {
+                        reason: "The bridge is shutting down".to_string(),
+                        retry_after_ms: Some(self.config.shutdown_grace.as_millis() as u64),
+                    };
+                    session.send(WebSocketMessage::new(message_type::SHUTDOWN, to_value(&notice), None));
+                    *closing.lock().unwrap() = Some(CloseFrame {
+                        code: CloseCode::from(close_code::SHUTDOWN),
+                        reason: format!("{}: {}", close_reason::SHUTDOWN, notice.reason).into(),
+                    });
+                    break;
+                }
+            }
+        }
+
+        println!("[bridge] Connection closed: {}", session.client_id());
+        self.admin.disconnected(session.client_id());
+        session.close().await;
+        let _ = reader.await;
+        let _ = writer.await;
+    }
+}
+
+// SIGINT, or SIGTERM where there is one
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    if let Ok(mut terminate) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+        return;
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_from_query() {
+        assert_eq!(token_from_query(Some("a=1&token=secret")), Some("secret".to_string()));
+        assert_eq!(token_from_query(Some("a=1")), None);
+        assert_eq!(token_from_query(None), None);
+    }
+}
@@ -0,0 +1,1217 @@
+use std::collections::HashSet;
+use std::future::poll_fn;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bridge_protocol::chunking::{split_frame, ChunkAssembler, DEFAULT_MAX_ASSEMBLED_SIZE};
+use bridge_protocol::compression::{compress_frame, decompress_frame, CompressedPayload};
+use bridge_protocol::encryption::{encode_salt, Side};
+use bridge_protocol::{
+    message_type, to_value, AdminPayload, AdminStats, BridgeLoad, Capabilities, ChannelPayload, ChunkPayload, DeallocatePayload, DescribePayload,
+    CacheStatus, ErrorPayload, FetchMorePayload, FrameError, HelloPayload, ListenAck, Negotiated, PinStatus, PoolMode, PoolRequest, PoolStats, PreparedStatementInfo,
+    QueryPayload, QueryResult, QueryTags, QueryTiming, RawColumn, RowDelta, SchemaChange, StatementPolicy, WebSocketMessage,
+};
+use bridge_protocol::is_restart_sql_state;
+use bridge_protocol::sql::statements;
+use chrono::{SecondsFormat, Utc};
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::types::Type;
+use tokio_postgres::{AsyncMessage, NoTls, Statement};
+
+use crate::cancel::RunningQuery;
+use crate::continuation::{self, Continuations};
+use crate::convert::{self, Binding};
+use crate::encryption::{self, DirectOut, SharedCipher};
+use crate::pooling::{self, Backend};
+use crate::prepared::PreparedCache;
+use crate::rate_limit::QueryRateLimit;
+use crate::result_cache;
+use crate::resume;
+use crate::schemas::SentSchemas;
+use crate::server::Server;
+
+// Dedicated connection holding this session's LISTEN registrations; notifications
+// are forwarded straight to the client
+pub struct Listener {
+    client: tokio_postgres::Client,
+    channels: HashSet<String>,
+    // Swapped for the new connection's when a parked session is resumed
+    out: Arc<Mutex<DirectOut>>,
+}
+
+// What a disconnected session leaves behind for its client to resume: the backend
+// with its settings and prepared statements, and the LISTEN connection with its channels
+pub struct ParkedSession {
+    db: Option<Backend>,
+    backend_pid: Option<i32>,
+    pinned: bool,
+    prepared: PreparedCache<Statement>,
+    continuations: Continuations,
+    schemas: SentSchemas,
+    listener: Option<Listener>,
+    settings: u64,
+    // Only a client authenticated for the same tenant may take it over
+    tenant: Option<String>,
+}
+
+// One client connection: its own pooled Postgres session (so BEGIN/COMMIT span
+// messages) plus an optional LISTEN connection
+pub struct Session {
+    server: Arc<Server>,
+    client_id: String,
+    authenticated: bool,
+    // Set when the client authenticated with a tenant-bound key
+    tenant: Option<String>,
+    db: Option<Backend>,
+    backend_pid: Option<i32>,
+    // Negotiated in `hello`; a pooled session hands `db` back between transactions
+    pool_mode: PoolMode,
+    // Negotiated `maxIdle`: `db` is closed instead of handed back once the pool has this
+    // many idle connections
+    max_idle: Option<usize>,
+    // Set by `pin`: keep `db` between queries even in pooled mode, for temp tables,
+    // advisory locks and other state that lives on the backend
+    pinned: bool,
+    in_transaction: bool,
+    // Statements prepared on `db`; emptied whenever a different connection is acquired
+    prepared: PreparedCache<Statement>,
+    // Paged queries with rows left in a cursor on `db`
+    continuations: Continuations,
+    // Raw result shapes the client already has
+    schemas: SentSchemas,
+    listener: Option<Listener>,
+    protocol: Negotiated,
+    chunks: ChunkAssembler,
+    chunk_counter: u64,
+    // Serialized frames for the writer task
+    out: UnboundedSender<String>,
+    running: RunningQuery,
+    // Statement policy the client declared in `hello`, enforced here as well
+    policy: Option<StatementPolicy>,
+    // Resumes this session after a disconnect, when the client negotiated `resume`
+    session_token: Option<String>,
+    // Token of the parked session the client asked for in `hello`, taken over once it
+    // has authenticated
+    resume_request: Option<String>,
+    // Set by the first `hello`; the protocol and keys it agreed on hold for the connection
+    greeted: bool,
+    // Messages from the client waiting behind the one being handled
+    queued: usize,
+    // Set when `BRIDGE_QUERY_RATE` limits each client
+    rate_limit: Option<QueryRateLimit>,
+    // Fingerprint of the settings changes run on this session, part of its result
+    // cache keys
+    settings: u64,
+    // Keys agreed in `hello` when the bridge encrypts
+    cipher: SharedCipher,
+}
+
+// How often a running query reports its row count to clients that asked for it
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+// Oldest client protocol version still served
+const MIN_CLIENT_VERSION: u32 = 0;
+
+const SERVER_CAPABILITIES: Capabilities = Capabilities {
+    binary: true,
+    streaming: false,
+    compression: true,
+    notifications: true,
+    chunking: true,
+    progress: true,
+    cancel: true,
+    encryption: false,
+    resume: false,
+    result_cache: false,
+    continuation: true,
+    schema_cache: true,
+    schema_events: false,
+    row_deltas: true,
+    throttling: true,
+};
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Fractional milliseconds since `since`, for result timings
+// What running a query produced, before it becomes a `result`
+struct Executed {
+    rows: Vec<Value>,
+    row_count: usize,
+    columns: Option<Vec<RawColumn>>,
+    database_ms: f64,
+}
+
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+// "cached plan must not change result type": a kept statement's result columns changed
+// under it, which preparing it again fixes
+fn is_stale_plan(error: &tokio_postgres::Error) -> bool {
+    error.code() == Some(&SqlState::FEATURE_NOT_SUPPORTED) && error.as_db_error().is_some_and(|e| e.message().contains("cached plan must not change result type"))
+}
+
+// Where a statement is kept in the prepared cache. Typed statements are kept apart from
+// the same SQL prepared with inferred types.
+fn statement_key(sql: &str, types: &[Type]) -> String {
+    if types.is_empty() {
+        return sql.to_string();
+    }
+    let names: Vec<&str> = types.iter().map(|ty| ty.name()).collect();
+    format!("{} /* types: {} */", sql, names.join(", "))
+}
+
+// `feature=orders-grid user=42`, for log lines
+fn describe_tags(tags: &QueryTags) -> String {
+    tags.iter().map(|(name, value)| format!("{}={}", name, value)).collect::<Vec<_>>().join(" ")
+}
+
+// Error payload for a failed statement; the client reads `message`, `code` and `detail`
+fn database_error(error: &tokio_postgres::Error, sql: &str, params: &[Value]) -> ErrorPayload {
+    let db_error = error.as_db_error();
+    ErrorPayload {
+        message: format!(
+            "Database query failed: {}",
+            db_error.map(|e| e.message().to_string()).unwrap_or_else(|| error.to_string())
+        ),
+        code: Some("DATABASE_ERROR".to_string()),
+        detail: db_error.and_then(|e| e.detail()).map(String::from),
+        sql_state: db_error.map(|e| e.code().code().to_string()),
+        sql: Some(sql.to_string()),
+        params: Some(params.to_vec()),
+    }
+}
+
+impl Session {
+    pub fn new(server: Arc<Server>, client_id: String, authenticated: bool, out: UnboundedSender<String>) -> Session {
+        let prepared = PreparedCache::new(server.config.prepared_statements, server.config.prepare_threshold);
+        let config = &server.config;
+        let rate_limit = (config.query_rate > 0).then(|| QueryRateLimit::new(config.query_rate as f64, config.query_burst as f64, Instant::now()));
+        Session {
+            server,
+            client_id,
+            authenticated,
+            tenant: None,
+            db: None,
+            backend_pid: None,
+            pool_mode: PoolMode::default(),
+            max_idle: None,
+            pinned: false,
+            in_transaction: false,
+            prepared,
+            continuations: Continuations::default(),
+            schemas: SentSchemas::default(),
+            listener: None,
+            protocol: Negotiated::default(),
+            chunks: ChunkAssembler::default(),
+            chunk_counter: 0,
+            out,
+            running: RunningQuery::default(),
+            policy: None,
+            session_token: None,
+            resume_request: None,
+            greeted: false,
+            queued: 0,
+            rate_limit,
+            settings: 0,
+            cipher: SharedCipher::default(),
+        }
+    }
+
+    pub fn bind_tenant(&mut self, tenant: Option<String>) {
+        self.tenant = tenant;
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn set_queued(&mut self, queued: usize) {
+        self.queued = queued;
+    }
+
+    // This connection's backlog and the pool's, for clients to back off under pressure
+    fn load(&self) -> BridgeLoad {
+        let status = self.server.pool.status();
+        let in_use = status.size.saturating_sub(status.available);
+        BridgeLoad {
+            queued: self.queued,
+            pool_waiting: status.waiting,
+            saturation: if status.max_size == 0 { 0.0 } else { in_use as f64 / status.max_size as f64 },
+        }
+    }
+
+    // Handle for cancelling whatever query this session is running
+    pub fn running_query(&self) -> RunningQuery {
+        self.running.clone()
+    }
+
+    // For frames sent from other tasks, encrypted like everything else once negotiated
+    pub fn direct_out(&self) -> DirectOut {
+        DirectOut::new(self.out.clone(), self.cipher.clone(), self.server.config.encryption_key.is_some())
+    }
+
+    // Queue a message, compressed, encrypted and/or split into chunks as negotiated
+    pub fn send(&mut self, message: WebSocketMessage) {
+        let mut frame = message.to_json();
+        if self.protocol.capabilities.compression && frame.len() >= self.server.config.compression_threshold {
+            if let Some(compressed) = compress_frame(&frame, message.id.clone()) {
+                frame = compressed.to_json();
+            }
+        }
+        if let Some(cipher) = self.cipher.lock().unwrap().as_mut() {
+            frame = cipher.seal(&frame, message.id.clone()).to_json();
+        }
+        match self.protocol.peer_max_message_size {
+            Some(limit) if frame.len() > limit && self.protocol.capabilities.chunking => {
+                self.chunk_counter += 1;
+                let group = message.id.clone().unwrap_or_else(|| format!("chunk_{}", self.chunk_counter));
+                match split_frame(&frame, limit, &group) {
+                    Ok(chunks) => {
+                        for chunk in chunks {
+                            let _ = self.out.send(chunk.to_json());
+                        }
+                    }
+                    Err(e) => self.send(WebSocketMessage::error(message.id, e.code(), e.message())),
+                }
+            }
+            _ => {
+                let _ = self.out.send(frame);
+            }
+        }
+    }
+
+    // Entry point for every text frame: enforce the size limit, join chunks, decrypt
+    // and inflate compressed messages
+    pub async fn receive(&mut self, text: &str) {
+        let limit = self.server.config.max_message_size;
+        if text.len() > limit {
+            let id = serde_json::from_str::<WebSocketMessage>(text).ok().and_then(|m| m.id);
+            let message = format!("Received a {} byte frame, above the {} byte limit", text.len(), limit);
+            self.send(WebSocketMessage::error(id, "MESSAGE_TOO_LARGE", message));
+            return;
+        }
+        let mut message = match serde_json::from_str::<WebSocketMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                self.send(WebSocketMessage::error(None, "INVALID_MESSAGE", format!("Invalid message format: {}", e)));
+                return;
+            }
+        };
+
+        if message.message_type == message_type::CHUNK {
+            let chunk: ChunkPayload = match serde_json::from_value(message.payload) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    self.send(WebSocketMessage::error(None, "CHUNK_ERROR", format!("Invalid chunk: {}", e)));
+                    return;
+                }
+            };
+            let message_id = chunk.message_id.clone();
+            let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+            message = match self.chunks.accept(chunk, now_ms).map(|frame| frame.map(|f| serde_json::from_str(&f))) {
+                Ok(Some(Ok(message))) => message,
+                Ok(None) => return,
+                Ok(Some(Err(e))) => {
+                    let error = format!("Invalid message format: {}", e);
+                    self.send(WebSocketMessage::error(Some(message_id), "INVALID_MESSAGE", error));
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("[bridge] Failed to reassemble message {} from {}: {}", message_id, self.client_id, e);
+                    self.send(WebSocketMessage::error(Some(message_id), e.code(), e.message()));
+                    return;
+                }
+            };
+        }
+
+        // With a key configured, only `hello` may arrive in the clear
+        if self.server.config.encryption_key.is_some() && message.message_type != message_type::HELLO {
+            let id = message.id.clone();
+            let opened = match message.message_type.as_str() {
+                message_type::ENCRYPTED => encryption::open(&self.cipher, message),
+                _ => Err(FrameError::Encryption("This bridge only accepts encrypted messages".to_string())),
+            };
+            let frame = match opened {
+                Ok(frame) => frame,
+                Err(e) => {
+                    eprintln!("[bridge] Rejected a message from {}: {}", self.client_id, e);
+                    self.send(WebSocketMessage::error(id, e.code(), e.message()));
+                    return;
+                }
+            };
+            message = match serde_json::from_str(&frame) {
+                Ok(message) => message,
+                Err(e) => {
+                    self.send(WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid message format: {}", e)));
+                    return;
+                }
+            };
+        }
+
+        if message.message_type == message_type::COMPRESSED {
+            let id = message.id.clone();
+            let inflated = serde_json::from_value::<CompressedPayload>(message.payload)
+                .map_err(|e| FrameError::Compression(format!("Invalid compressed payload: {}", e)))
+                .and_then(|payload| decompress_frame(&payload, DEFAULT_MAX_ASSEMBLED_SIZE));
+            let frame = match inflated {
+                Ok(frame) => frame,
+                Err(e) => {
+                    eprintln!("[bridge] Failed to decompress message from {}: {}", self.client_id, e);
+                    self.send(WebSocketMessage::error(id, e.code(), e.message()));
+                    return;
+                }
+            };
+            message = match serde_json::from_str(&frame) {
+                Ok(message) => message,
+                Err(e) => {
+                    self.send(WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid message format: {}", e)));
+                    return;
+                }
+            };
+        }
+
+        self.handle(message).await;
+    }
+
+    pub async fn handle(&mut self, message: WebSocketMessage) {
+        let id = message.id.clone();
+        let response = match message.message_type.as_str() {
+            message_type::HELLO => return self.hello(id, message.payload),
+            message_type::AUTH => self.auth(id, &message.payload),
+            message_type::PING => self.ping(id, message.payload),
+            // The admin token stands in for the session's authentication
+            message_type::ADMIN_STATS => self.admin_stats(id, message.payload),
+            _ if !self.authenticated => {
+                WebSocketMessage::error(id, "AUTH_REQUIRED", "Authenticate with an `auth` message first")
+            }
+            message_type::QUERY => self.query(id, message.payload).await,
+            message_type::POOL_STATS => self.pool_stats(id),
+            message_type::LISTEN => self.listen(id, message.payload).await,
+            message_type::UNLISTEN => self.unlisten(id, message.payload).await,
+            message_type::PREPARED_STATEMENTS => self.prepared_statements(id),
+            message_type::DEALLOCATE => self.deallocate(id, message.payload),
+            message_type::FETCH_MORE => self.fetch_more(id, message.payload).await,
+            message_type::PIN => self.pin(id).await,
+            message_type::UNPIN => self.unpin(id),
+            message_type::DESCRIBE => self.describe(id, message.payload).await,
+            other => WebSocketMessage::error(id, "UNSUPPORTED_TYPE", format!("Unsupported message type: {}", other)),
+        };
+        self.send(response);
+    }
+
+    // Reply with the server's own hello; the client computes the shared feature set.
+    // The reply goes out in the clear, since the client needs its salt for the keys
+    // everything after it is encrypted with.
+    fn hello(&mut self, id: Option<String>, payload: Value) {
+        if self.greeted {
+            let message = "`hello` was already exchanged on this connection; reconnect to negotiate again";
+            return self.send(WebSocketMessage::error(id, "INVALID_MESSAGE", message));
+        }
+        let bridge = self.server.clone();
+        let config = &bridge.config;
+        let capabilities = Capabilities {
+            encryption: config.encryption_key.is_some(),
+            resume: !config.resume_window.is_zero(),
+            result_cache: config.result_cache_size > 0,
+            schema_events: config.schema_events,
+            ..SERVER_CAPABILITIES
+        };
+        let server = HelloPayload::new(MIN_CLIENT_VERSION, capabilities, config.max_message_size);
+        let client: HelloPayload = match serde_json::from_value(payload) {
+            Ok(client) => client,
+            Err(e) => return self.send(WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid hello payload: {}", e))),
+        };
+        let negotiated = match server.negotiate(&client) {
+            Ok(negotiated) => negotiated,
+            Err(reason) => return self.send(WebSocketMessage::error(id, "UNSUPPORTED_PROTOCOL", reason)),
+        };
+        // Keys of this connection alone, from both sides' salts
+        let mut cipher = None;
+        if let Some(key) = &config.encryption_key {
+            let salt = encode_salt(&rand::random());
+            let client_salt = client.encryption_salt.as_deref().filter(|_| negotiated.capabilities.encryption);
+            let derived = client_salt
+                .ok_or_else(|| FrameError::Encryption("This bridge only accepts encrypted messages".to_string()))
+                .and_then(|client_salt| key.connection(Side::Bridge, client_salt, &salt));
+            match derived {
+                Ok(derived) => cipher = Some((derived, salt)),
+                Err(e) => return self.send(WebSocketMessage::error(id, e.code(), e.message())),
+            }
+        }
+        println!("[bridge] {} speaks protocol v{}: {:?}", self.client_id, negotiated.version, negotiated.capabilities);
+        self.greeted = true;
+        self.protocol = negotiated;
+        self.policy = client.policy.filter(|policy| !policy.is_empty());
+        let pool = client.pool.map(|request| self.apply_pool(&request));
+        // A client that still has to authenticate learns about its session from `auth`
+        self.resume_request = client.session_token;
+        let resumed = negotiated.capabilities.resume && self.authenticated && self.resume();
+        let (cipher, encryption_salt) = cipher.unzip();
+        let server = HelloPayload { session_token: self.session_token.clone(), resumed, pool, encryption_salt, ..server };
+        self.send(WebSocketMessage::result(id, to_value(&server)));
+        *self.cipher.lock().unwrap() = cipher;
+    }
+
+    // Grant what the bridge allows of a client's pool request and switch to it
+    fn apply_pool(&mut self, request: &PoolRequest) -> PoolRequest {
+        let config = &self.server.config;
+        let dedicated_free = self.server.dedicated.load(Ordering::Acquire) < config.dedicated_backends;
+        let granted = pooling::grant(request, config.prepared_statements, config.pool_size, dedicated_free);
+        self.pool_mode = granted.mode.unwrap_or_default();
+        self.max_idle = granted.max_idle;
+        if let Some(min_idle) = granted.min_idle.filter(|&n| n > 0) {
+            let server = self.server.clone();
+            tokio::spawn(async move { pooling::warm(&server.pool, min_idle).await });
+        }
+        self.prepared = PreparedCache::new(granted.max_statements.unwrap_or_default(), config.prepare_threshold);
+        println!("[bridge] {} uses pool settings {:?}", self.client_id, granted);
+        granted
+    }
+
+    fn auth(&mut self, id: Option<String>, payload: &Value) -> WebSocketMessage {
+        let token = payload.get("token").or_else(|| payload.get("apiKey")).and_then(|t| t.as_str());
+        match token {
+            Some(token) if self.server.config.accepts(token) => {
+                self.authenticated = true;
+                self.tenant = self.server.config.tenant_for(token).map(String::from);
+                let mut reply = json!({ "authenticated": true, "clientId": self.client_id });
+                if self.protocol.capabilities.resume && self.session_token.is_none() {
+                    reply["resumed"] = json!(self.resume());
+                    reply["sessionToken"] = json!(self.session_token);
+                }
+                WebSocketMessage::result(id, reply)
+            }
+            _ => WebSocketMessage::error(id, "AUTH_FAILED", "Invalid credentials"),
+        }
+    }
+
+    fn ping(&self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        WebSocketMessage::result(
+            id,
+            json!({ "message": "pong", "echo": payload, "timestamp": now(), "clientId": self.client_id }),
+        )
+    }
+
+    fn pool_stats(&self, id: Option<String>) -> WebSocketMessage {
+        let status = self.server.pool.status();
+        let stats = PoolStats { total_count: status.size, idle_count: status.available, waiting_count: status.waiting };
+        WebSocketMessage::result(id, to_value(&stats))
+    }
+
+    fn admin_stats(&self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        if self.server.config.admin_token.is_none() {
+            return WebSocketMessage::error(id, "ADMIN_DISABLED", "Set BRIDGE_ADMIN_TOKEN to enable admin messages");
+        }
+        let token = serde_json::from_value::<AdminPayload>(payload).map(|p| p.token).unwrap_or_default();
+        if !self.server.config.accepts_admin(&token) {
+            return WebSocketMessage::error(id, "AUTH_FAILED", "Invalid admin token");
+        }
+        let status = self.server.pool.status();
+        let in_use = status.size.saturating_sub(status.available);
+        let (queries_per_second, total_queries) = self.server.admin.query_rate();
+        let clients = self.server.admin.clients();
+        let stats = AdminStats {
+            active_clients: clients.len(),
+            clients,
+            pool: PoolStats { total_count: status.size, idle_count: status.available, waiting_count: status.waiting },
+            pool_max_size: status.max_size,
+            pool_utilization: if status.max_size == 0 { 0.0 } else { in_use as f64 / status.max_size as f64 },
+            queries_per_second,
+            total_queries,
+            parked_sessions: self.server.parked.len(),
+            uptime_secs: self.server.admin.uptime_secs(),
+        };
+        WebSocketMessage::result(id, to_value(&stats))
+    }
+
+    // Hold a backend for the session until `unpin`, acquiring it now so the reply can
+    // name it
+    async fn pin(&mut self, id: Option<String>) -> WebSocketMessage {
+        if let Err(e) = self.db().await {
+            return WebSocketMessage::error(id, "DATABASE_ERROR", e);
+        }
+        self.pinned = true;
+        WebSocketMessage::result(id, to_value(&PinStatus { pinned: true, backend_pid: self.backend_pid }))
+    }
+
+    // Go back to the pool mode's own behavior; a pooled session gives its backend up now
+    // unless a transaction or cursor still needs it
+    fn unpin(&mut self, id: Option<String>) -> WebSocketMessage {
+        self.pinned = false;
+        self.release_if_idle();
+        WebSocketMessage::result(id, to_value(&PinStatus { pinned: false, backend_pid: self.backend_pid }))
+    }
+
+    // Prepare a statement without running it and report its parameter and result types.
+    // A statement the session keeps is described from what it already knows; `keep`
+    // keeps a new one, e.g. to warm up SQL a client is about to run.
+    async fn describe(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let request: DescribePayload = match serde_json::from_value(payload) {
+            Ok(request) => request,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid describe payload: {}", e)),
+        };
+        if let Some(Err(reason)) = self.policy.as_ref().map(|policy| policy.check(&request.sql)) {
+            return WebSocketMessage::error(id, "POLICY_VIOLATION", reason);
+        }
+        let types = match convert::param_types(&request.types.unwrap_or_default()) {
+            Ok(types) => types,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_PARAMS", e),
+        };
+        if let Err(e) = self.db().await {
+            return WebSocketMessage::error(id, "DATABASE_ERROR", e);
+        }
+        let key = statement_key(&request.sql, &types);
+        let statement = match self.prepared.peek(&key) {
+            Some(statement) => Ok(statement),
+            None => {
+                let statement = self.db.as_ref().expect("session connection was just acquired").prepare_typed(&request.sql, &types).await;
+                if let (Ok(statement), true) = (&statement, request.keep) {
+                    self.prepared.insert(&key, statement.clone());
+                }
+                statement
+            }
+        };
+        let response = match statement {
+            Ok(statement) => WebSocketMessage::result(id, to_value(&convert::describe_statement(&statement))),
+            Err(e) => WebSocketMessage::new(message_type::ERROR, to_value(&database_error(&e, &request.sql, &[])), id),
+        };
+        self.release_if_idle();
+        response
+    }
+
+    fn prepared_statements(&self, id: Option<String>) -> WebSocketMessage {
+        let statements: Vec<PreparedStatementInfo> = self
+            .prepared
+            .entries()
+            .map(|(handle, sql, uses)| PreparedStatementInfo { handle: handle.to_string(), sql: sql.to_string(), uses })
+            .collect();
+        WebSocketMessage::result(id, to_value(&statements))
+    }
+
+    // Close one kept statement, or all of them; a closed statement is prepared again
+    // the next time its SQL runs
+    fn deallocate(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let request: DeallocatePayload = match serde_json::from_value(payload) {
+            Ok(request) => request,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid deallocate payload: {}", e)),
+        };
+        let deallocated = match request.handle {
+            Some(handle) => match self.prepared.find(&handle) {
+                Some(sql) => usize::from(self.prepared.remove(&sql)),
+                None => 0,
+            },
+            None => self.prepared.clear(),
+        };
+        WebSocketMessage::result(id, json!({ "deallocated": deallocated }))
+    }
+
+    async fn db(&mut self) -> Result<&Backend, String> {
+        if self.db.is_none() {
+            let client = Backend::acquire(&self.server, self.pool_mode).await?;
+            self.prepared.clear();
+            self.continuations.clear();
+            self.backend_pid = client.query_one("SELECT pg_backend_pid()", &[]).await.ok().map(|row| row.get(0));
+            self.server.admin.set_backend_pid(&self.client_id, self.backend_pid);
+            self.db = Some(client);
+        }
+        Ok(self.db.as_ref().expect("session connection was just acquired"))
+    }
+
+    async fn query(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let received = Instant::now();
+        if let Some(Err(wait)) = self.rate_limit.as_mut().map(|limit| limit.take(received)) {
+            // Clients that negotiated `throttling` wait and send the query again themselves
+            let retry_after_ms = wait.as_millis().max(1) as u64;
+            return if self.protocol.capabilities.throttling {
+                WebSocketMessage::throttled(id, retry_after_ms, "Query rate limit exceeded")
+            } else {
+                WebSocketMessage::error(id, "RATE_LIMITED", format!("Query rate limit exceeded; retry in {}ms", retry_after_ms))
+            };
+        }
+        let mut query: QueryPayload = match serde_json::from_value(payload.clone()) {
+            Ok(query) => query,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid query payload: {}", e)),
+        };
+        if self.tenant.is_some() && query.tenant_id != self.tenant {
+            return WebSocketMessage::error(id, "TENANT_MISMATCH", "Queries on this connection must carry the tenant id its key is bound to");
+        }
+        let params = query.params.take().unwrap_or_default();
+        if let Some(Err(reason)) = self.policy.as_ref().map(|policy| policy.check(&query.sql)) {
+            return WebSocketMessage::error(id, "POLICY_VIOLATION", reason);
+        }
+
+        // Replayed writes carry an idempotency key; answer repeats from the cache
+        let idempotency_key = payload.get("idempotencyKey").and_then(|k| k.as_str()).map(String::from);
+        if let Some(key) = &idempotency_key {
+            if let Some(result) = self.server.idempotent_results.lock().unwrap().get(self.tenant.as_deref(), key) {
+                let mut result = result.clone();
+                result["timing"] = to_value(&QueryTiming { bridge_ms: Some(elapsed_ms(received)), database_ms: Some(0.0), ..QueryTiming::default() });
+                return WebSocketMessage::result(id, result);
+            }
+        }
+
+        // A hinted read outside a transaction may be answered from the result cache
+        let cache_key = self.cache_key(&query, &params);
+        if let (Some(key), Some(control)) = (&cache_key, &query.cache) {
+            let max_age = control.ttl_ms.filter(|_| !control.bypass).map(Duration::from_millis);
+            let cached = max_age.and_then(|max_age| self.server.result_cache.lock().unwrap().get(key, max_age, Instant::now()));
+            if let Some((mut result, age)) = cached {
+                result["cache"] = to_value(&CacheStatus { hit: true, age_ms: age.as_millis() as u64 });
+                result["timing"] = to_value(&QueryTiming { bridge_ms: Some(elapsed_ms(received)), database_ms: Some(0.0), ..QueryTiming::default() });
+                if let Some(tags) = &query.tags {
+                    println!("[bridge] Cached result served to {} [{}]", self.client_id, describe_tags(tags));
+                    result["tags"] = to_value(tags);
+                }
+                return WebSocketMessage::result(id, result);
+            }
+        }
+
+        let sql = query.sql.clone();
+        // Later reads of this session are cached apart from other sessions' once it
+        // changes a setting, whether or not the change went through
+        if result_cache::changes_settings(&sql) {
+            self.settings = result_cache::fold_settings(self.settings, &sql, &params);
+        }
+        let tags = query.tags.take();
+        let page_size = query.page_size.filter(|size| *size > 0 && self.protocol.capabilities.continuation);
+        let mut response = match query.statement_timeout_ms {
+            None => self.run(id, query, params, idempotency_key, page_size).await,
+            Some(timeout_ms) => {
+                let previous = match self.swap_setting("statement_timeout", &format!("{}ms", timeout_ms)).await {
+                    Ok(previous) => previous,
+                    Err(e) => return WebSocketMessage::error(id, "DATABASE_ERROR", e),
+                };
+                let response = self.run(id, query, params, idempotency_key, page_size).await;
+                // Inside a transaction that has since failed this errors, but the ROLLBACK
+                // then undoes the override as well
+                if let Err(e) = self.swap_setting("statement_timeout", &previous).await {
+                    eprintln!("[bridge] Failed to restore statement_timeout for {}: {}", self.client_id, e);
+                }
+                response
+            }
+        };
+        // A backend that went down with the database is useless; the next query
+        // acquires a fresh one
+        let restarted = response.payload.get("sqlState").and_then(|code| code.as_str()).is_some_and(is_restart_sql_state);
+        if response.message_type == message_type::ERROR && (restarted || self.db.as_ref().is_some_and(|db| db.is_closed())) {
+            self.in_transaction = false;
+            self.release_db();
+            return response;
+        }
+        if response.message_type != message_type::ERROR {
+            self.in_transaction = pooling::in_transaction_after(&sql, self.in_transaction);
+            if let Some(key) = cache_key {
+                self.server.result_cache.lock().unwrap().insert(key, response.payload.clone(), Instant::now());
+                response.payload["cache"] = to_value(&CacheStatus { hit: false, age_ms: 0 });
+            }
+        }
+        // Tagged queries get a second log line naming who to charge them to
+        if let Some(tags) = tags {
+            if response.message_type == message_type::ERROR {
+                println!("[bridge] Tagged query for {} failed [{}]", self.client_id, describe_tags(&tags));
+            } else {
+                let ms = response.payload.get("executionTime").and_then(|ms| ms.as_f64()).unwrap_or_default();
+                println!("[bridge] Tagged query for {} took {}ms [{}]", self.client_id, ms, describe_tags(&tags));
+                response.payload["tags"] = to_value(&tags);
+            }
+        }
+        self.release_if_idle();
+        if let Some(timing) = response.payload.get_mut("timing") {
+            timing["bridgeMs"] = Value::from(elapsed_ms(received));
+        }
+        if response.message_type == message_type::RESULT {
+            response.payload["load"] = to_value(&self.load());
+        }
+        response
+    }
+
+    // A pooled session gives its backend back between transactions, unless it is pinned
+    // or a paged query still has rows waiting in a cursor on it
+    fn release_if_idle(&mut self) {
+        if self.pool_mode == PoolMode::Pooled && !self.pinned && !self.in_transaction && self.continuations.is_empty() {
+            self.release_db();
+        }
+    }
+
+    async fn run(
+        &mut self,
+        id: Option<String>,
+        query: QueryPayload,
+        params: Vec<Value>,
+        idempotency_key: Option<String>,
+        page_size: Option<u32>,
+    ) -> WebSocketMessage {
+        let types = match convert::param_types(&query.types.unwrap_or_default()) {
+            Ok(types) => types,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_PARAMS", e),
+        };
+        let binding = Binding { binary: query.binary_params.unwrap_or_default(), types };
+        match page_size {
+            Some(page_size) => self.open_continuation(id, query.sql, params, binding, query.raw, page_size).await,
+            None => self.run_query(id, query.sql, params, binding, idempotency_key, query.raw).await,
+        }
+    }
+
+    // Declare a cursor for a paged query and return its first page
+    async fn open_continuation(
+        &mut self,
+        id: Option<String>,
+        sql: String,
+        params: Vec<Value>,
+        binding: Binding,
+        raw: bool,
+        page_size: u32,
+    ) -> WebSocketMessage {
+        let (token, cursor) = match self.continuations.open(sql.clone(), params.clone(), raw, page_size) {
+            Ok(opened) => opened,
+            Err(e) => return WebSocketMessage::error(id, "TOO_MANY_CONTINUATIONS", e),
+        };
+        let declared = self.run_query(id.clone(), continuation::declare_sql(&cursor, &sql), params, binding, None, false).await;
+        if declared.message_type == message_type::ERROR {
+            self.continuations.remove(&token);
+            return declared;
+        }
+        self.fetch_page(id, &token, page_size).await
+    }
+
+    // The next `page_size` rows of a paged query. The token comes back while rows may
+    // remain; a short page closes the cursor.
+    async fn fetch_page(&mut self, id: Option<String>, token: &str, page_size: u32) -> WebSocketMessage {
+        let Some(open) = self.continuations.get(token).cloned() else {
+            return WebSocketMessage::error(id, "UNKNOWN_CONTINUATION", "No paged query is open under this token; it finished or its connection was lost");
+        };
+        let mut response = self.run_query(id, continuation::fetch_sql(&open.cursor, page_size), Vec::new(), Binding::default(), None, open.raw).await;
+        if response.message_type == message_type::ERROR {
+            self.close_continuation(token).await;
+            return response;
+        }
+        // Report the paged statement, not the FETCH
+        response.payload["sql"] = json!(open.sql);
+        response.payload["params"] = json!(open.params);
+        let rows = response.payload.get("rowCount").and_then(|n| n.as_u64()).unwrap_or_default();
+        if rows < page_size as u64 {
+            self.close_continuation(token).await;
+        } else {
+            response.payload["continuation"] = json!(token);
+        }
+        response
+    }
+
+    // Forget a paged query and close its cursor; false if it wasn't open
+    async fn close_continuation(&mut self, token: &str) -> bool {
+        let Some(open) = self.continuations.remove(token) else {
+            return false;
+        };
+        if let Some(db) = &self.db {
+            if let Err(e) = db.batch_execute(&continuation::close_sql(&open.cursor)).await {
+                eprintln!("[bridge] Failed to close cursor {} for {}: {}", open.cursor, self.client_id, e);
+            }
+        }
+        true
+    }
+
+    async fn fetch_more(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let request: FetchMorePayload = match serde_json::from_value(payload) {
+            Ok(request) => request,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_MESSAGE", format!("Invalid fetch_more payload: {}", e)),
+        };
+        let response = if request.close {
+            let closed = self.close_continuation(&request.token).await;
+            WebSocketMessage::result(id, json!({ "closed": closed }))
+        } else {
+            let page_size = request
+                .page_size
+                .filter(|size| *size > 0)
+                .or_else(|| self.continuations.get(&request.token).map(|open| open.page_size))
+                .unwrap_or(1);
+            self.fetch_page(id, &request.token, page_size).await
+        };
+        self.release_if_idle();
+        response
+    }
+
+    // Where a query's result lives in the result cache, if it may be cached at all: it
+    // must carry hints, be a plain read and run outside a transaction, whose snapshot
+    // other sessions don't share
+    fn cache_key(&self, query: &QueryPayload, params: &[Value]) -> Option<String> {
+        let control = query.cache.as_ref()?;
+        let unsupported = !self.protocol.capabilities.result_cache || query.page_size.is_some() || query.binary_params.is_some() || query.types.is_some();
+        if unsupported || self.in_transaction || query.raw || !result_cache::is_cacheable(&query.sql) {
+            return None;
+        }
+        Some(result_cache::cache_key(self.tenant.as_deref(), self.settings, control.key.as_deref(), &query.sql, params))
+    }
+
+    // Hand the backend back to the pool, which resets it before anyone else gets it
+    fn release_db(&mut self) {
+        if let Some(db) = self.db.take() {
+            match db {
+                Backend::Pooled(object) if self.max_idle.is_some_and(|max| self.server.pool.status().available >= max) => {
+                    drop(deadpool_postgres::Object::take(object));
+                }
+                db => drop(db),
+            }
+            self.continuations.clear();
+            self.backend_pid = None;
+            self.server.admin.set_backend_pid(&self.client_id, None);
+        }
+    }
+
+    // A statement for `sql` on the session's connection: reused from the cache, promoted
+    // into it once the SQL has run often enough, or prepared for this query alone
+    async fn statement(&mut self, sql: &str, types: &[Type]) -> Result<Statement, tokio_postgres::Error> {
+        let key = statement_key(sql, types);
+        if let Some(statement) = self.prepared.get(&key) {
+            return Ok(statement);
+        }
+        let client = self.db.as_ref().expect("statements are prepared on an acquired connection");
+        let statement = client.prepare_typed(sql, types).await?;
+        if self.prepared.record_use(&key) {
+            self.prepared.insert(&key, statement.clone());
+        }
+        Ok(statement)
+    }
+
+    // Set a session setting, returning the value it replaced
+    async fn swap_setting(&mut self, name: &str, value: &str) -> Result<String, String> {
+        let client = self.db().await?;
+        let row = client
+            .query_one("SELECT current_setting($1), set_config($1, $2, false)", &[&name, &value])
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(row.get(0))
+    }
+
+    async fn run_query(
+        &mut self,
+        id: Option<String>,
+        sql: String,
+        params: Vec<Value>,
+        binding: Binding,
+        idempotency_key: Option<String>,
+        raw: bool,
+    ) -> WebSocketMessage {
+        let start = Instant::now();
+        self.server.admin.record_query(&self.client_id);
+        let progress_out = self.protocol.capabilities.progress.then(|| self.direct_out());
+        let running = self.running.clone();
+        if let Err(e) = self.db().await {
+            return WebSocketMessage::error(id, "DATABASE_ERROR", e);
+        }
+        // Several statements can't be prepared. Without parameters they run as one simple
+        // query, which answers for the last of them.
+        if params.is_empty() && binding.binary.is_empty() && binding.types.is_empty() && statements(&sql).len() > 1 {
+            let client = self.db.as_ref().expect("session connection was just acquired");
+            let _running = id.as_deref().map(|id| running.start(id, client.cancel_token()));
+            let executing = Instant::now();
+            let (rows, row_count) = match client.simple_query(&sql).await {
+                Ok(messages) => convert::script_result(&messages),
+                Err(e) => return WebSocketMessage::new(message_type::ERROR, to_value(&database_error(&e, &sql, &params)), id),
+            };
+            let executed = Executed { rows, row_count, columns: None, database_ms: elapsed_ms(executing) };
+            return self.query_result(id, sql, params, executed, start, idempotency_key);
+        }
+        let statement = match self.statement(&sql, &binding.types).await {
+            Ok(statement) => statement,
+            Err(e) => return WebSocketMessage::new(message_type::ERROR, to_value(&database_error(&e, &sql, &params)), id),
+        };
+        let client = self.db.as_ref().expect("session connection was just acquired");
+        let bound = match convert::to_params(&params, &binding.binary, statement.params()) {
+            Ok(bound) => bound,
+            Err(e) => return WebSocketMessage::error(id, "INVALID_PARAMS", e),
+        };
+        let refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = bound.iter().map(|p| p as _).collect();
+        let _running = id.as_deref().map(|id| running.start(id, client.cancel_token()));
+        let executing = Instant::now();
+        let stream = match client.query_raw(&statement, refs.clone()).await {
+            // The backend lost a cached statement, e.g. to DISCARD ALL, or DDL changed what
+            // it returns: prepare it afresh
+            Err(e) if e.code() == Some(&SqlState::INVALID_SQL_STATEMENT_NAME) || is_stale_plan(&e) => {
+                self.prepared.remove(&statement_key(&sql, &binding.types));
+                match client.prepare_typed(&sql, &binding.types).await {
+                    Ok(statement) => client.query_raw(&statement, refs).await,
+                    Err(e) => Err(e),
+                }
+            }
+            other => other,
+        };
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => return WebSocketMessage::new(message_type::ERROR, to_value(&database_error(&e, &sql, &params)), id),
+        };
+        let mut stream = std::pin::pin!(stream);
+        let mut rows = Vec::new();
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+        loop {
+            tokio::select! {
+                row = stream.next() => match row {
+                    Some(Ok(row)) => rows.push(row),
+                    Some(Err(e)) => {
+                        return WebSocketMessage::new(message_type::ERROR, to_value(&database_error(&e, &sql, &params)), id)
+                    }
+                    None => break,
+                },
+                _ = ticker.tick(), if progress_out.is_some() => {
+                    let elapsed = start.elapsed().as_millis() as f64;
+                    let message = WebSocketMessage::progress(id.clone(), rows.len() as u64, elapsed);
+                    if let Some(out) = &progress_out {
+                        out.send(message);
+                    }
+                }
+            }
+        }
+        let database_ms = elapsed_ms(executing);
+        let columns = raw.then(|| convert::raw_columns(statement.columns()));
+        let decode = if raw { convert::row_to_raw } else { convert::row_to_json };
+        let rows = match rows.iter().map(decode).collect::<Result<Vec<_>, _>>() {
+            Ok(rows) => rows,
+            Err(e) => return WebSocketMessage::error(id, "DATABASE_ERROR", format!("Failed to decode result: {}", e)),
+        };
+        let executed = Executed { row_count: rows.len(), rows, columns, database_ms };
+        self.query_result(id, sql, params, executed, start, idempotency_key)
+    }
+
+    // The `result` for a query that ran, remembered under its idempotency key
+    fn query_result(
+        &mut self,
+        id: Option<String>,
+        sql: String,
+        params: Vec<Value>,
+        executed: Executed,
+        start: Instant,
+        idempotency_key: Option<String>,
+    ) -> WebSocketMessage {
+        let Executed { rows, row_count, columns, database_ms } = executed;
+        let execution_time = start.elapsed().as_millis() as f64;
+        println!("[bridge] Query executed for {}: {} rows in {}ms", self.client_id, rows.len(), execution_time);
+
+        let mut result = to_value(&QueryResult {
+            sql,
+            params,
+            row_count,
+            rows,
+            execution_time,
+            timestamp: now(),
+            columns: columns.clone(),
+            cache: None,
+            continuation: None,
+            tags: None,
+            schema_id: None,
+            // `query` widens bridgeMs to the whole time the message spent in the bridge
+            timing: Some(QueryTiming { bridge_ms: Some(elapsed_ms(start)), database_ms: Some(database_ms), ..QueryTiming::default() }),
+            load: None,
+        });
+        if let Some(key) = idempotency_key {
+            self.server.idempotent_results.lock().unwrap().insert(self.tenant.as_deref(), &key, result.clone());
+        }
+        // Replays above keep their columns: the session they reach may not have the shape
+        if let Some(columns) = columns.filter(|_| self.protocol.capabilities.schema_cache) {
+            if let Some((schema_id, known)) = self.schemas.share(&columns) {
+                result["schemaId"] = Value::String(schema_id);
+                if let (true, Some(result)) = (known, result.as_object_mut()) {
+                    result.remove("columns");
+                }
+            }
+        }
+        WebSocketMessage::result(id, result)
+    }
+
+    async fn listener(&mut self) -> Result<&mut Listener, tokio_postgres::Error> {
+        // After `listen_lost` the client LISTENs again, on a fresh connection
+        if self.listener.as_ref().is_some_and(|listener| listener.client.is_closed()) {
+            self.listener = None;
+        }
+        if self.listener.is_none() {
+            let (client, mut connection) = tokio_postgres::connect(&self.server.config.database_url, NoTls).await?;
+            let out = Arc::new(Mutex::new(self.direct_out()));
+            let forward = out.clone();
+            let client_id = self.client_id.clone();
+            let row_deltas = self.protocol.capabilities.row_deltas;
+            // Ends when the listener's client is dropped and the connection closes
+            tokio::spawn(async move {
+                while let Some(message) = poll_fn(|cx| connection.poll_message(cx)).await {
+                    match message {
+                        Ok(AsyncMessage::Notification(n)) => {
+                            // NOTIFY payloads are capped at 8000 bytes, so these never need chunking.
+                            // While the session is parked they are dropped.
+                            let message = match RowDelta::parse(n.payload()).filter(|_| row_deltas) {
+                                Some(delta) => WebSocketMessage::row_delta(n.channel(), delta),
+                                None => WebSocketMessage::notification(n.channel(), n.payload()),
+                            };
+                            forward.lock().unwrap().send(message);
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            eprintln!("[bridge] LISTEN connection for {} failed: {}", client_id, e);
+                            forward.lock().unwrap().send(WebSocketMessage::listen_lost(&e.to_string()));
+                            break;
+                        }
+                    }
+                }
+            });
+            self.listener = Some(Listener { client, channels: HashSet::new(), out });
+        }
+        Ok(self.listener.as_mut().expect("listener was just created"))
+    }
+
+    fn channel(payload: Value) -> Option<String> {
+        serde_json::from_value::<ChannelPayload>(payload)
+            .ok()
+            .map(|p| p.channel)
+            .filter(|c| !c.is_empty())
+    }
+
+    async fn listen(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let Some(channel) = Session::channel(payload) else {
+            return WebSocketMessage::error(id, "INVALID_MESSAGE", "listen requires a channel");
+        };
+        let listener = match self.listener().await {
+            Ok(listener) => listener,
+            Err(e) => return WebSocketMessage::error(id, "DATABASE_ERROR", e.to_string()),
+        };
+        if !listener.channels.contains(&channel) {
+            if let Err(e) = listener.client.batch_execute(&format!("LISTEN {}", quote_ident(&channel))).await {
+                return WebSocketMessage::error(id, "DATABASE_ERROR", e.to_string());
+            }
+            listener.channels.insert(channel.clone());
+        }
+        WebSocketMessage::result(id, to_value(&ListenAck { channel, listening: true }))
+    }
+
+    async fn unlisten(&mut self, id: Option<String>, payload: Value) -> WebSocketMessage {
+        let Some(channel) = Session::channel(payload) else {
+            return WebSocketMessage::error(id, "INVALID_MESSAGE", "unlisten requires a channel");
+        };
+        if let Some(listener) = &mut self.listener {
+            if listener.channels.remove(&channel) {
+                if let Err(e) = listener.client.batch_execute(&format!("UNLISTEN {}", quote_ident(&channel))).await {
+                    return WebSocketMessage::error(id, "DATABASE_ERROR", e.to_string());
+                }
+            }
+        }
+        WebSocketMessage::result(id, to_value(&ListenAck { channel, listening: false }))
+    }
+
+    // DDL may have changed what kept statements return, so they are prepared afresh.
+    // `None` when changes were missed; clients only hear about the ones that weren't.
+    pub fn schema_changed(&mut self, change: Option<&SchemaChange>) {
+        self.prepared.clear();
+        if let Some(change) = change.filter(|_| self.protocol.capabilities.schema_events) {
+            self.send(WebSocketMessage::new(message_type::SCHEMA_CHANGE, to_value(change), None));
+        }
+    }
+
+    // Roll back anything the client left open, then either park the session for the
+    // client to resume or let its connections return to the pool
+    pub async fn close(mut self) {
+        if let Some(db) = &self.db {
+            if let Err(e) = db.batch_execute("ROLLBACK").await {
+                eprintln!("[bridge] Failed to reset session for {}: {}", self.client_id, e);
+                self.db = None;
+            }
+        }
+        let Some(token) = self.session_token.take().filter(|_| self.protocol.capabilities.resume) else {
+            return;
+        };
+        if self.db.is_none() && self.listener.is_none() {
+            return;
+        }
+        let window = self.server.config.resume_window;
+        let prepared = PreparedCache::new(0, 1);
+        let parked = ParkedSession {
+            db: self.db.take(),
+            backend_pid: self.backend_pid,
+            pinned: self.pinned,
+            prepared: std::mem::replace(&mut self.prepared, prepared),
+            continuations: std::mem::take(&mut self.continuations),
+            schemas: std::mem::take(&mut self.schemas),
+            listener: self.listener.take(),
+            settings: self.settings,
+            tenant: self.tenant.clone(),
+        };
+        self.server.parked.park(&token, parked, window, Instant::now());
+        let server = self.server.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(window).await;
+            server.parked.sweep(Instant::now());
+        });
+    }
+
+    // Reattach the session the client asked for in `hello`, or start a new resumable one.
+    // Either way the session gets a fresh token, so a token works at most once.
+    fn resume(&mut self) -> bool {
+        self.session_token = Some(resume::new_token());
+        let token = self.resume_request.take();
+        let tenant = self.tenant.clone();
+        let parked = token.and_then(|token| self.server.parked.take(&token, Instant::now(), |parked| parked.tenant == tenant));
+        let Some(parked) = parked else {
+            return false;
+        };
+        println!("[bridge] {} resumed a parked session", self.client_id);
+        self.db = parked.db;
+        self.prepared = parked.prepared;
+        self.continuations = parked.continuations;
+        self.schemas = parked.schemas;
+        self.backend_pid = parked.backend_pid;
+        self.pinned = parked.pinned;
+        self.settings = parked.settings;
+        self.server.admin.set_backend_pid(&self.client_id, self.backend_pid);
+        if let Some(listener) = parked.listener {
+            *listener.out.lock().unwrap() = self.direct_out();
+            self.listener = Some(listener);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+
+    use crate::config::Config;
+
+    // Send one query and wait for its answer
+    async fn ask(session: &mut Session, frames: &mut tokio::sync::mpsc::UnboundedReceiver<String>, id: &str, sql: &str) -> WebSocketMessage {
+        session.handle(WebSocketMessage::new(message_type::QUERY, json!({ "sql": sql }), Some(id.to_string()))).await;
+        loop {
+            let reply: WebSocketMessage = serde_json::from_str(&frames.recv().await.unwrap()).unwrap();
+            if reply.id.as_deref() == Some(id) {
+                return reply;
+            }
+        }
+    }
+
+    // Needs a database: skipped unless DATABASE_URL is set
+    #[tokio::test]
+    async fn test_script_runs_in_one_message() {
+        let Ok(config) = Config::from_env() else {
+            return;
+        };
+        let pg_config = config.database_url.parse().unwrap();
+        let manager = Manager::from_config(pg_config, NoTls, ManagerConfig { recycling_method: RecyclingMethod::Clean });
+        let pool = Pool::builder(manager).max_size(1).build().unwrap();
+        let (out, mut frames) = tokio::sync::mpsc::unbounded_channel();
+        let mut session = Session::new(Arc::new(Server::new(config, pool)), "script".to_string(), true, out);
+
+        let created = ask(&mut session, &mut frames, "q1", "CREATE TEMP TABLE script_test (id int); INSERT INTO script_test VALUES (1), (2)").await;
+        assert_eq!(created.message_type, message_type::RESULT, "{}", created.payload);
+        assert_eq!(created.payload["rowCount"], 2);
+
+        let sql = "CREATE TEMP TABLE IF NOT EXISTS script_test (id int); INSERT INTO script_test VALUES (3); SELECT id FROM script_test ORDER BY id DESC LIMIT 1";
+        let selected = ask(&mut session, &mut frames, "q2", sql).await;
+        assert_eq!(selected.payload["rows"], json!([{ "id": "3" }]));
+        assert_eq!(selected.payload["rowCount"], 1);
+    }
+}
@@ -67,6 +67,9 @@ export class PostgreSQLClient implements DatabaseClient {
     cacheHitRate: 0,
   };
   private performanceMonitor: PerformanceMonitor;
+  // Dedicated connection holding LISTEN registrations; pooled clients can't receive notifications
+  private listenClient: PoolClient | null = null;
+  private notificationHandlers: Map<string, Set<(payload: string) => void>> = new Map();
 
   constructor(config?: DatabaseConfig) {
     // Parse connection string or use provided config
@@ -231,6 +234,11 @@ export class PostgreSQLClient implements DatabaseClient {
    * Close all connections in the pool
    */
   async disconnect(): Promise<void> {
+    if (this.listenClient) {
+      this.listenClient.release();
+      this.listenClient = null;
+      this.notificationHandlers.clear();
+    }
     if (this.pool) {
       await this.pool.end();
       this.pool = null;
@@ -238,6 +246,130 @@ export class PostgreSQLClient implements DatabaseClient {
     }
   }
 
+  /**
+   * Subscribe a handler to NOTIFY events on a channel
+   */
+  async listen(channel: string, handler: (payload: string) => void): Promise<void> {
+    if (!this.pool) {
+      throw new Error('Database not connected. Call connect() first.');
+    }
+
+    if (!this.listenClient) {
+      this.listenClient = await this.pool.connect();
+      this.listenClient.on('notification', (msg) => {
+        // Data changed underneath the cached SELECT results that read from it
+        this.invalidateForNotification(msg.channel, msg.payload ?? '');
+        this.notificationHandlers.get(msg.channel)?.forEach(h => h(msg.payload ?? ''));
+      });
+    }
+
+    let handlers = this.notificationHandlers.get(channel);
+    if (!handlers) {
+      handlers = new Set();
+      this.notificationHandlers.set(channel, handlers);
+      await this.listenClient.query(`LISTEN ${this.quoteIdent(channel)}`);
+    }
+    handlers.add(handler);
+  }
+
+  /**
+   * Remove a NOTIFY handler, issuing UNLISTEN once a channel has none left
+   */
+  async unlisten(channel: string, handler: (payload: string) => void): Promise<void> {
+    const handlers = this.notificationHandlers.get(channel);
+    if (!handlers) {
+      return;
+    }
+
+    handlers.delete(handler);
+    if (handlers.size === 0) {
+      this.notificationHandlers.delete(channel);
+      await this.listenClient?.query(`UNLISTEN ${this.quoteIdent(channel)}`);
+    }
+  }
+
+  /**
+   * Stream logical decoding output (wal2json format 2) for the given tables.
+   * Uses a temporary replication slot on a dedicated connection, polled at `intervalMs`;
+   * the returned function stops polling and drops the slot with the connection.
+   */
+  async openChangeStream(
+    slot: string,
+    tables: string[],
+    onChange: (change: any) => void,
+    intervalMs: number = 500
+  ): Promise<() => void> {
+    if (!this.pool) {
+      throw new Error('Database not connected. Call connect() first.');
+    }
+
+    const client = await this.pool.connect();
+    try {
+      await client.query("SELECT pg_create_logical_replication_slot($1, 'wal2json', true)", [slot]);
+    } catch (error) {
+      client.release(true);
+      throw error;
+    }
+
+    const addTables = tables.map(t => (t.includes('.') ? t : `public.${t}`)).join(',');
+    let polling = false;
+    const timer = setInterval(async () => {
+      if (polling) {
+        return;
+      }
+      polling = true;
+      try {
+        const result = await client.query(
+          "SELECT data FROM pg_logical_slot_get_changes($1, NULL, NULL, 'format-version', '2', 'include-transaction', 'false', 'add-tables', $2)",
+          [slot, addTables]
+        );
+        for (const row of result.rows) {
+          onChange(JSON.parse(row.data));
+        }
+      } catch (error) {
+        console.error(`[CDC] Failed to read changes from slot ${slot}:`, error);
+      } finally {
+        polling = false;
+      }
+    }, intervalMs);
+
+    return () => {
+      clearInterval(timer);
+      // Temporary slots are dropped when their session ends
+      client.release(true);
+    };
+  }
+
+  /**
+   * Drop cached results whose SQL mentions the notified channel, taken as a table name,
+   * or the `table` a JSON payload names. Other channels leave the cache alone.
+   */
+  private invalidateForNotification(channel: string, payload: string): void {
+    const tables = [channel];
+    try {
+      const parsed = JSON.parse(payload);
+      if (typeof parsed?.table === 'string') {
+        tables.push(parsed.table);
+      }
+    } catch {
+      // Not JSON: only the channel name applies
+    }
+
+    const patterns = tables.map(table => {
+      const name = table.toLowerCase().replace(/[.*+?^${}()|[\]\\]/g, '\\$&');
+      return new RegExp(`(^|[^a-z0-9_$])${name}($|[^a-z0-9_$])`);
+    });
+    for (const key of [...this.queryCache.keys()]) {
+      if (patterns.some(pattern => pattern.test(key))) {
+        this.queryCache.delete(key);
+      }
+    }
+  }
+
+  private quoteIdent(name: string): string {
+    return `"${name.replace(/"/g, '""')}"`;
+  }
+
   /**
    * Get connection status
    */
@@ -17,7 +17,9 @@ if (typeof process !== 'undefined' && process.versions && process.versions.node)
 }
 
 // Define the interface for our WASM module functions
-export interface WasmModule {
+// Example exports, only present in builds made with the `demo` cargo feature
+// (`npm run build:wasm:demo`)
+export interface WasmDemoFunctions {
   // Arithmetic functions
   add(a: number, b: number): number;
   subtract(a: number, b: number): number;
@@ -36,11 +38,15 @@ export interface WasmModule {
   
   // Error handling demonstration
   safe_parse_int(input: string): number;
-  
+
+  wasm_query_database(websocket_url: string, sql: string, params_json?: string): any;
+}
+
+// Define the interface for our WASM module functions
+export interface WasmModule extends Partial<WasmDemoFunctions> {
   // WebSocket functionality
   WasmWebSocketClient: any;
   create_websocket_client(url: string): any;
-  wasm_query_database(websocket_url: string, sql: string, params_json?: string): any;
   
   // Initialization
   main(): void;
@@ -242,19 +248,19 @@ export class WasmFunctionBinder {
       add: (a: number, b: number): number => {
         this.validateNumber(a, 'first argument');
         this.validateNumber(b, 'second argument');
-        return this.wasmModule.add(a, b);
+        return this.demo('add')(a, b);
       },
       
       subtract: (a: number, b: number): number => {
         this.validateNumber(a, 'first argument');
         this.validateNumber(b, 'second argument');
-        return this.wasmModule.subtract(a, b);
+        return this.demo('subtract')(a, b);
       },
       
       multiply: (a: number, b: number): number => {
         this.validateNumber(a, 'first argument');
         this.validateNumber(b, 'second argument');
-        return this.wasmModule.multiply(a, b);
+        return this.demo('multiply')(a, b);
       },
       
       divide: (a: number, b: number): number => {
@@ -263,7 +269,7 @@ export class WasmFunctionBinder {
         if (b === 0) {
           throw new Error('Division by zero is not allowed');
         }
-        return this.wasmModule.divide(a, b);
+        return this.demo('divide')(a, b);
       }
     };
   }
@@ -273,22 +279,22 @@ export class WasmFunctionBinder {
     return {
       reverse: (input: string): string => {
         this.validateString(input, 'input');
-        return this.wasmModule.reverse_string(input);
+        return this.demo('reverse_string')(input);
       },
       
       toUppercase: (input: string): string => {
         this.validateString(input, 'input');
-        return this.wasmModule.to_uppercase(input);
+        return this.demo('to_uppercase')(input);
       },
       
       countWords: (input: string): number => {
         this.validateString(input, 'input');
-        return this.wasmModule.count_words(input);
+        return this.demo('count_words')(input);
       },
       
       process: (input: string): string => {
         this.validateString(input, 'input');
-        return this.wasmModule.process_string(input);
+        return this.demo('process_string')(input);
       }
     };
   }
@@ -304,14 +310,14 @@ export class WasmFunctionBinder {
         if (size > 1000000) {
           throw new Error('Array size too large (max: 1,000,000)');
         }
-        return this.wasmModule.create_array(size);
+        return this.demo('create_array')(size);
       },
       
       sumArray: (arr: Int32Array): number => {
         if (!(arr instanceof Int32Array)) {
           throw new Error('Input must be an Int32Array');
         }
-        return this.wasmModule.sum_array(arr);
+        return this.demo('sum_array')(arr);
       }
     };
   }
@@ -322,7 +328,7 @@ export class WasmFunctionBinder {
       parseIntSafe: (input: string): number => {
         this.validateString(input, 'input');
         try {
-          return this.wasmModule.safe_parse_int(input);
+          return this.demo('safe_parse_int')(input);
         } catch (error) {
           throw new Error(`Failed to parse '${input}' as integer: ${error instanceof Error ? error.message : String(error)}`);
         }
@@ -340,6 +346,14 @@ export class WasmFunctionBinder {
     };
   }
 
+  private demo<K extends keyof WasmDemoFunctions>(name: K): WasmDemoFunctions[K] {
+    const fn = this.wasmModule[name];
+    if (!fn) {
+      throw new Error(`${name} is only exported by demo builds; build with npm run build:wasm:demo`);
+    }
+    return fn as WasmDemoFunctions[K];
+  }
+
   private validateNumber(value: any, paramName: string): void {
     if (typeof value !== 'number' || isNaN(value)) {
       throw new Error(`${paramName} must be a valid number`);
@@ -0,0 +1,192 @@
+/**
+ * Single-connection coordination across tabs
+ * Uses the Web Locks API to elect one leader tab that owns the live WasmWebSocketClient;
+ * other tabs proxy method calls through it over a BroadcastChannel. When the leader tab
+ * closes its lock is released, the next waiting tab takes over and followers re-send
+ * any calls that were still in flight. The leader also relays every NOTIFY and row change
+ * its client receives, numbered, so each tab's TabFanoutFilter can restore their order and
+ * keep the channels and tables it wants.
+ */
+
+import { isCallbackRef } from './worker-host';
+
+type TabMessage =
+  | { kind: 'leader'; tabId: string }
+  | { kind: 'call'; tabId: string; callId: number; method: string; args: unknown[] }
+  | { kind: 'return'; tabId: string; callId: number; value: unknown }
+  | { kind: 'throw'; tabId: string; callId: number; error: { name: string; message: string; code?: string } }
+  | { kind: 'callback'; tabId: string; callbackId: number; args: unknown[] }
+  | { kind: 'event'; event: unknown };
+
+interface PendingCall {
+  method: string;
+  args: unknown[];
+  resolve: (value: any) => void;
+  reject: (error: Error) => void;
+}
+
+export interface TabCoordinatorOptions {
+  // Lock and channel name; tabs sharing it share one connection
+  name?: string;
+  // Creates and connects the client when this tab becomes leader
+  createClient: () => Promise<any>;
+  onLeadershipChange?: (isLeader: boolean) => void;
+  // Receive the NOTIFYs and row changes the leader relays; `filter` is this tab's
+  // `new TabFanoutFilter({ channels, tables })` from the WASM module
+  fanout?: {
+    filter: { accept(event: unknown): unknown[] };
+    onEvent: (event: any) => void;
+  };
+}
+
+export class TabCoordinator {
+  private readonly tabId = `tab_${Date.now()}_${Math.random().toString(36).slice(2, 10)}`;
+  private readonly channel: BroadcastChannel;
+  private readonly name: string;
+  private client: any = null;
+  private leader = false;
+  private nextCallId = 0;
+  private nextCallbackId = 0;
+  private pending: Map<number, PendingCall> = new Map();
+  private callbacks: Map<number, (...args: unknown[]) => void> = new Map();
+
+  constructor(private options: TabCoordinatorOptions) {
+    this.name = options.name ?? 'wasm-postgres-bridge';
+    this.channel = new BroadcastChannel(this.name);
+    this.channel.onmessage = (event: MessageEvent<TabMessage>) => this.handleMessage(event.data);
+  }
+
+  static isSupported(): boolean {
+    return typeof navigator !== 'undefined' && 'locks' in navigator && typeof BroadcastChannel !== 'undefined';
+  }
+
+  /**
+   * Queue for the leader lock; resolves immediately, leadership may arrive later
+   */
+  start(): void {
+    (navigator as any).locks.request(`${this.name}:leader`, async () => {
+      this.client = await this.options.createClient();
+      if (this.options.fanout) {
+        // BroadcastChannel doesn't echo to the sender, so the leader delivers its own copy
+        this.client.set_tab_fanout((event: unknown) => {
+          this.channel.postMessage({ kind: 'event', event });
+          this.receiveEvent(event);
+        });
+      }
+      this.leader = true;
+      this.options.onLeadershipChange?.(true);
+      this.channel.postMessage({ kind: 'leader', tabId: this.tabId });
+      this.retryPending();
+      // Hold the lock for the lifetime of the tab
+      await new Promise(() => undefined);
+    });
+  }
+
+  isLeader(): boolean {
+    return this.leader;
+  }
+
+  /**
+   * Invoke a client method, locally when leader or through the leader tab otherwise
+   */
+  call<T = any>(method: string, ...args: unknown[]): Promise<T> {
+    if (this.leader) {
+      return Promise.resolve(this.client[method](...args));
+    }
+
+    const callId = ++this.nextCallId;
+    const encodedArgs = args.map(arg => {
+      if (typeof arg !== 'function') {
+        return arg;
+      }
+      const callbackId = ++this.nextCallbackId;
+      this.callbacks.set(callbackId, arg as (...args: unknown[]) => void);
+      return { __workerCallback: callbackId };
+    });
+
+    return new Promise<T>((resolve, reject) => {
+      this.pending.set(callId, { method, args: encodedArgs, resolve, reject });
+      this.channel.postMessage({ kind: 'call', tabId: this.tabId, callId, method, args: encodedArgs });
+    });
+  }
+
+  close(): void {
+    this.channel.close();
+    this.pending.forEach(call => call.reject(new Error('Tab coordinator closed')));
+    this.pending.clear();
+  }
+
+  private retryPending(): void {
+    this.pending.forEach((call, callId) => {
+      if (this.leader) {
+        this.pending.delete(callId);
+        Promise.resolve(this.client[call.method](...call.args)).then(call.resolve, call.reject);
+      } else {
+        this.channel.postMessage({ kind: 'call', tabId: this.tabId, callId, method: call.method, args: call.args });
+      }
+    });
+  }
+
+  private receiveEvent(event: unknown): void {
+    const fanout = this.options.fanout;
+    fanout?.filter.accept(event).forEach(ready => fanout.onEvent(ready));
+  }
+
+  private async handleMessage(message: TabMessage): Promise<void> {
+    switch (message.kind) {
+      case 'leader':
+        // A new leader took over; anything sent to the old one may be lost
+        this.retryPending();
+        break;
+      case 'call':
+        if (this.leader) {
+          await this.serveCall(message);
+        }
+        break;
+      case 'return':
+      case 'throw': {
+        if (message.tabId !== this.tabId) {
+          return;
+        }
+        const call = this.pending.get(message.callId);
+        this.pending.delete(message.callId);
+        if (message.kind === 'return') {
+          call?.resolve(message.value);
+        } else {
+          const error = new Error(message.error.message) as Error & { code?: string };
+          error.name = message.error.name;
+          error.code = message.error.code;
+          call?.reject(error);
+        }
+        break;
+      }
+      case 'callback':
+        if (message.tabId === this.tabId) {
+          this.callbacks.get(message.callbackId)?.(...message.args);
+        }
+        break;
+      case 'event':
+        this.receiveEvent(message.event);
+        break;
+    }
+  }
+
+  private async serveCall(message: Extract<TabMessage, { kind: 'call' }>): Promise<void> {
+    const { tabId, callId } = message;
+    const args = message.args.map(arg => (isCallbackRef(arg)
+      ? (...callbackArgs: unknown[]) => this.channel.postMessage({ kind: 'callback', tabId, callbackId: arg.__workerCallback, args: callbackArgs })
+      : arg));
+    try {
+      const value = await this.client[message.method](...args);
+      this.channel.postMessage({ kind: 'return', tabId, callId, value });
+    } catch (error) {
+      const e = error as Error & { code?: string };
+      this.channel.postMessage({
+        kind: 'throw',
+        tabId,
+        callId,
+        error: { name: e?.name ?? 'Error', message: e?.message ?? String(error), code: e?.code }
+      });
+    }
+  }
+}
@@ -0,0 +1,95 @@
+/**
+ * Web Worker execution mode (worker side)
+ * Hosts a WasmWebSocketClient inside a dedicated worker and serves method calls
+ * posted by WorkerClientProxy, so result deserialization stays off the UI thread.
+ *
+ * Usage inside the worker script:
+ *   import init, * as wasm from './pkg/wasm_postgres_learning';
+ *   hostClientInWorker(async () => { await init(); return wasm; });
+ */
+
+// Messages exchanged between the proxy and the worker host
+export type WorkerRequest =
+  | { kind: 'create'; url: string }
+  | { kind: 'call'; callId: number; method: string; args: unknown[]; transfer?: boolean };
+
+export type WorkerResponse =
+  | { kind: 'ready' }
+  | { kind: 'return'; callId: number; value: unknown }
+  | { kind: 'throw'; callId: number; error: { name: string; message: string; code?: string; detail?: string; queryId?: string } }
+  | { kind: 'callback'; callbackId: number; args: unknown[] };
+
+// Placeholder the proxy sends instead of a function argument
+export interface CallbackRef {
+  __workerCallback: number;
+}
+
+export function isCallbackRef(value: unknown): value is CallbackRef {
+  return typeof value === 'object' && value !== null && typeof (value as CallbackRef).__workerCallback === 'number';
+}
+
+/**
+ * ArrayBuffers backing the typed arrays and buffers inside a result (for example
+ * columnar query results), so they can be moved to the main thread instead of copied.
+ * Typed arrays returned by the client own their buffers; none of them view WASM memory.
+ */
+export function collectTransferables(value: unknown, found: Set<ArrayBuffer> = new Set()): ArrayBuffer[] {
+  if (value instanceof ArrayBuffer) {
+    found.add(value);
+  } else if (ArrayBuffer.isView(value)) {
+    if (value.buffer instanceof ArrayBuffer) {
+      found.add(value.buffer);
+    }
+  } else if (Array.isArray(value)) {
+    value.forEach(item => collectTransferables(item, found));
+  } else if (typeof value === 'object' && value !== null && Object.getPrototypeOf(value) === Object.prototype) {
+    Object.values(value).forEach(item => collectTransferables(item, found));
+  }
+  return Array.from(found);
+}
+
+function serializeError(error: unknown): { name: string; message: string; code?: string; detail?: string; queryId?: string } {
+  if (error instanceof Error) {
+    const e = error as Error & { code?: string; detail?: string; queryId?: string };
+    return { name: e.name, message: e.message, code: e.code, detail: e.detail, queryId: e.queryId };
+  }
+  return { name: 'Error', message: String(error) };
+}
+
+export function hostClientInWorker(loadModule: () => Promise<any>): void {
+  const scope = self as unknown as DedicatedWorkerGlobalScope;
+  let client: any = null;
+  const modulePromise = loadModule();
+
+  const post = (message: WorkerResponse, transfer: Transferable[] = []) => scope.postMessage(message, transfer);
+
+  // Replace callback placeholders with functions that forward invocations to the main thread
+  const reviveArgs = (args: unknown[]) =>
+    args.map(arg => (isCallbackRef(arg)
+      ? (...callbackArgs: unknown[]) => post({ kind: 'callback', callbackId: arg.__workerCallback, args: callbackArgs })
+      : arg));
+
+  scope.onmessage = async (event: MessageEvent<WorkerRequest>) => {
+    const request = event.data;
+    if (request.kind === 'create') {
+      const wasm = await modulePromise;
+      client = new wasm.WasmWebSocketClient(request.url);
+      post({ kind: 'ready' });
+      return;
+    }
+
+    try {
+      if (!client) {
+        throw new Error('Worker client has not been created');
+      }
+      if (typeof client[request.method] !== 'function') {
+        throw new Error(`Unknown client method: ${request.method}`);
+      }
+      const value = await client[request.method](...reviveArgs(request.args));
+      // Transferred buffers become unusable here, which is fine: the worker keeps no reference
+      post({ kind: 'return', callId: request.callId, value }, request.transfer ? collectTransferables(value) : []);
+    } catch (error) {
+      post({ kind: 'throw', callId: request.callId, error: serializeError(error) });
+    }
+  };
+}
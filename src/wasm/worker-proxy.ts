@@ -0,0 +1,119 @@
+/**
+ * Web Worker execution mode (main-thread side)
+ * Thin postMessage proxy for a WasmWebSocketClient hosted by hostClientInWorker.
+ * Every client method becomes async; function arguments (message handlers, live
+ * query callbacks) are forwarded back from the worker when invoked.
+ */
+
+import { WorkerRequest, WorkerResponse } from './worker-host';
+
+export class WorkerClientError extends Error {
+  constructor(name: string, message: string, public code?: string, public detail?: string, public queryId?: string) {
+    super(message);
+    this.name = name;
+  }
+}
+
+export class WorkerClientProxy {
+  private nextCallId = 0;
+  private nextCallbackId = 0;
+  private calls: Map<number, { resolve: (value: any) => void; reject: (error: Error) => void }> = new Map();
+  private callbacks: Map<number, (...args: unknown[]) => void> = new Map();
+  private ready: Promise<void>;
+
+  constructor(private worker: Worker, url: string) {
+    this.ready = new Promise(resolve => {
+      this.worker.onmessage = (event: MessageEvent<WorkerResponse>) => {
+        if (event.data.kind === 'ready') {
+          resolve();
+        } else {
+          this.handleResponse(event.data);
+        }
+      };
+    });
+    this.post({ kind: 'create', url });
+  }
+
+  /**
+   * Invoke a WasmWebSocketClient method inside the worker
+   */
+  async call<T = any>(method: string, ...args: unknown[]): Promise<T> {
+    return this.invoke<T>(method, args, false);
+  }
+
+  /**
+   * Like call(), but the worker hands over the ArrayBuffers in the result instead of
+   * copying them. Worth it for large typed-array results such as columnar queries.
+   */
+  async callWithTransfer<T = any>(method: string, ...args: unknown[]): Promise<T> {
+    return this.invoke<T>(method, args, true);
+  }
+
+  private async invoke<T>(method: string, args: unknown[], transfer: boolean): Promise<T> {
+    await this.ready;
+    const callId = ++this.nextCallId;
+    const encodedArgs = args.map(arg => {
+      if (typeof arg !== 'function') {
+        return arg;
+      }
+      const callbackId = ++this.nextCallbackId;
+      this.callbacks.set(callbackId, arg as (...args: unknown[]) => void);
+      return { __workerCallback: callbackId };
+    });
+
+    return new Promise<T>((resolve, reject) => {
+      this.calls.set(callId, { resolve, reject });
+      this.post({ kind: 'call', callId, method, args: encodedArgs, transfer });
+    });
+  }
+
+  connect(): Promise<void> {
+    return this.call('connect');
+  }
+
+  disconnect(): Promise<void> {
+    return this.call('disconnect');
+  }
+
+  isConnected(): Promise<boolean> {
+    return this.call('is_connected');
+  }
+
+  /**
+   * `options.transfer` moves result buffers to this thread instead of copying them;
+   * other options are passed to the client's query()
+   */
+  query<T = any>(sql: string, paramsJson?: string, options?: { transfer?: boolean; [key: string]: unknown }): Promise<T> {
+    const { transfer = false, ...queryOptions } = options ?? {};
+    return this.invoke('query', [sql, paramsJson, options ? queryOptions : undefined], transfer);
+  }
+
+  terminate(): void {
+    this.worker.terminate();
+    this.calls.forEach(({ reject }) => reject(new WorkerClientError('BridgeConnectionError', 'Worker terminated')));
+    this.calls.clear();
+    this.callbacks.clear();
+  }
+
+  private post(message: WorkerRequest): void {
+    this.worker.postMessage(message);
+  }
+
+  private handleResponse(response: WorkerResponse): void {
+    switch (response.kind) {
+      case 'return':
+        this.calls.get(response.callId)?.resolve(response.value);
+        this.calls.delete(response.callId);
+        break;
+      case 'throw': {
+        const { name, message, code, detail, queryId } = response.error;
+        this.calls.get(response.callId)?.reject(new WorkerClientError(name, message, code, detail, queryId));
+        this.calls.delete(response.callId);
+        break;
+      }
+      case 'callback':
+        this.callbacks.get(response.callbackId)?.(...response.args);
+        break;
+    }
+  }
+}
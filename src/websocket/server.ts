@@ -1,5 +1,6 @@
 import { WebSocketServer as WSServer, WebSocket } from 'ws';
-import { createServer, Server } from 'http';
+import { createServer, IncomingMessage, Server, ServerResponse } from 'http';
+import { createHash } from 'crypto';
 import { PostgreSQLClient, DatabaseClient } from '../database/client';
 
 /**
@@ -8,7 +9,7 @@ import { PostgreSQLClient, DatabaseClient } from '../database/client';
  */
 
 export interface WebSocketMessage {
-  type: 'query' | 'result' | 'error' | 'ping';
+  type: 'hello' | 'query' | 'result' | 'error' | 'ping' | 'pool_stats' | 'listen' | 'unlisten' | 'notification' | 'subscribe_changes' | 'unsubscribe_changes' | 'change';
   payload: any;
   id?: string;
 }
@@ -24,6 +25,18 @@ export class BasicWebSocketServer implements WebSocketServer {
   private httpServer: Server | null = null;
   private clients: Set<WebSocket> = new Set();
   private dbClient: DatabaseClient;
+  // Results of recently executed idempotent queries, keyed by tenant and idempotency key
+  private idempotentResults: Map<string, any> = new Map();
+  private static readonly MAX_IDEMPOTENT_RESULTS = 1000;
+  // Wire protocol version exchanged in `hello`, and the oldest client version served
+  private static readonly PROTOCOL_VERSION = 1;
+  private static readonly MIN_CLIENT_PROTOCOL_VERSION = 0;
+  // Per-connection LISTEN registrations: channel -> forwarding handler
+  private listeners: Map<WebSocket, Map<string, (payload: string) => void>> = new Map();
+  // HTTP fallback sessions: session id -> SSE stream standing in for a WebSocket
+  private sseSessions: Map<string, WebSocket> = new Map();
+  // Per-connection change streams: subscription id -> stop function
+  private changeStreams: Map<WebSocket, Map<string, () => void>> = new Map();
 
   constructor(dbClient?: DatabaseClient) {
     this.dbClient = dbClient || new PostgreSQLClient();
@@ -38,7 +51,8 @@ export class BasicWebSocketServer implements WebSocketServer {
         console.log('[WebSocket] Database connected successfully');
 
         // Create HTTP server for WebSocket upgrade
-        this.httpServer = createServer();
+        // Plain HTTP requests serve the SSE fallback; upgrades go to the WebSocket server
+        this.httpServer = createServer((req, res) => this.handleHttpRequest(req, res));
         
         // Create WebSocket server
         this.wss = new WSServer({ server: this.httpServer });
@@ -76,6 +90,8 @@ export class BasicWebSocketServer implements WebSocketServer {
           ws.on('close', (code: number, reason: Buffer) => {
             console.log(`[WebSocket] Connection closed: ${clientId}, code: ${code}, reason: ${reason.toString()}`);
             this.clients.delete(ws);
+            this.releaseListeners(ws);
+            this.releaseChangeStreams(ws);
           });
 
           // Handle connection errors
@@ -191,7 +207,7 @@ export class BasicWebSocketServer implements WebSocketServer {
     }
 
     // Validate message type
-    const validTypes = ['query', 'result', 'error', 'ping'];
+    const validTypes = ['hello', 'query', 'result', 'error', 'ping', 'pool_stats', 'listen', 'unlisten', 'subscribe_changes', 'unsubscribe_changes'];
     if (!validTypes.includes(parsed.type)) {
       throw new Error(`Invalid message type "${parsed.type}". Valid types: ${validTypes.join(', ')}`);
     }
@@ -206,8 +222,27 @@ export class BasicWebSocketServer implements WebSocketServer {
       case 'query':
         this.validateQueryMessage(parsed);
         break;
+      case 'hello':
       case 'ping':
-        // Ping messages can have any payload
+      case 'pool_stats':
+        // Hello, ping and pool_stats messages can have any payload
+        break;
+      case 'listen':
+      case 'unlisten':
+        if (!parsed.payload || typeof parsed.payload.channel !== 'string' || !parsed.payload.channel) {
+          throw new Error(`${parsed.type} message must have a valid "channel" field in payload`);
+        }
+        break;
+      case 'subscribe_changes':
+        if (!parsed.payload || !Array.isArray(parsed.payload.tables) || parsed.payload.tables.length === 0 ||
+            !parsed.payload.tables.every((t: any) => typeof t === 'string' && t)) {
+          throw new Error('subscribe_changes message must have a non-empty "tables" array in payload');
+        }
+        break;
+      case 'unsubscribe_changes':
+        if (!parsed.payload || typeof parsed.payload.subscription !== 'string') {
+          throw new Error('unsubscribe_changes message must have a valid "subscription" field in payload');
+        }
         break;
       case 'result':
       case 'error':
@@ -237,6 +272,10 @@ export class BasicWebSocketServer implements WebSocketServer {
 
   private async handleMessage(ws: WebSocket, message: WebSocketMessage, clientId: string): Promise<void> {
     switch (message.type) {
+      case 'hello':
+        this.handleHelloMessage(ws, message, clientId);
+        break;
+
       case 'ping':
         this.handlePingMessage(ws, message, clientId);
         break;
@@ -244,6 +283,26 @@ export class BasicWebSocketServer implements WebSocketServer {
       case 'query':
         await this.handleQueryMessage(ws, message, clientId);
         break;
+
+      case 'pool_stats':
+        this.handlePoolStatsMessage(ws, message);
+        break;
+
+      case 'listen':
+        await this.handleListenMessage(ws, message);
+        break;
+
+      case 'unlisten':
+        await this.handleUnlistenMessage(ws, message);
+        break;
+
+      case 'subscribe_changes':
+        await this.handleSubscribeChangesMessage(ws, message, clientId);
+        break;
+
+      case 'unsubscribe_changes':
+        this.handleUnsubscribeChangesMessage(ws, message);
+        break;
       
       default:
         this.sendToClient(ws, {
@@ -257,6 +316,50 @@ export class BasicWebSocketServer implements WebSocketServer {
     }
   }
 
+  /**
+   * Protocol negotiation: reply with this server's version and capabilities; the
+   * client keeps the features both sides support.
+   */
+  private handleHelloMessage(ws: WebSocket, message: WebSocketMessage, clientId: string): void {
+    const clientVersion = Number(message.payload?.version ?? 0);
+    if (clientVersion < BasicWebSocketServer.MIN_CLIENT_PROTOCOL_VERSION) {
+      this.sendToClient(ws, {
+        type: 'error',
+        payload: {
+          message: `Peer speaks protocol version ${clientVersion}, at least ${BasicWebSocketServer.MIN_CLIENT_PROTOCOL_VERSION} is required`,
+          code: 'UNSUPPORTED_PROTOCOL'
+        },
+        id: message.id
+      });
+      return;
+    }
+
+    // A client that needs a newer protocol than this server speaks can't be served
+    const clientMinVersion = Number(message.payload?.minVersion ?? 0);
+    if (clientMinVersion > BasicWebSocketServer.PROTOCOL_VERSION) {
+      this.sendToClient(ws, {
+        type: 'error',
+        payload: {
+          message: `Peer requires protocol version ${clientMinVersion}, this server speaks ${BasicWebSocketServer.PROTOCOL_VERSION}`,
+          code: 'UNSUPPORTED_PROTOCOL'
+        },
+        id: message.id
+      });
+      return;
+    }
+
+    console.log(`[WebSocket] ${clientId} speaks protocol v${clientVersion}`);
+    this.sendToClient(ws, {
+      type: 'result',
+      payload: {
+        version: BasicWebSocketServer.PROTOCOL_VERSION,
+        minVersion: BasicWebSocketServer.MIN_CLIENT_PROTOCOL_VERSION,
+        capabilities: { binary: false, streaming: false, compression: false, notifications: true }
+      },
+      id: message.id
+    });
+  }
+
   private handlePingMessage(ws: WebSocket, message: WebSocketMessage, clientId: string): void {
     console.log(`[WebSocket] Handling ping from ${clientId}`);
     
@@ -272,6 +375,131 @@ export class BasicWebSocketServer implements WebSocketServer {
     });
   }
 
+  private handlePoolStatsMessage(ws: WebSocket, message: WebSocketMessage): void {
+    const poolStats = this.dbClient instanceof PostgreSQLClient ? this.dbClient.getPoolStats() : null;
+
+    this.sendToClient(ws, {
+      type: 'result',
+      payload: poolStats || { totalCount: 0, idleCount: 0, waitingCount: 0 },
+      id: message.id
+    });
+  }
+
+  private async handleListenMessage(ws: WebSocket, message: WebSocketMessage): Promise<void> {
+    const channel: string = message.payload.channel;
+    if (!(this.dbClient instanceof PostgreSQLClient)) {
+      this.sendToClient(ws, {
+        type: 'error',
+        payload: { message: 'LISTEN is not supported by this database client', code: 'UNSUPPORTED_TYPE' },
+        id: message.id
+      });
+      return;
+    }
+
+    let channels = this.listeners.get(ws);
+    if (!channels) {
+      channels = new Map();
+      this.listeners.set(ws, channels);
+    }
+    if (!channels.has(channel)) {
+      const handler = (payload: string) => {
+        if (ws.readyState === WebSocket.OPEN) {
+          this.sendToClient(ws, { type: 'notification', payload: { channel, payload }, id: undefined });
+        }
+      };
+      channels.set(channel, handler);
+      try {
+        await this.dbClient.listen(channel, handler);
+      } catch (error) {
+        channels.delete(channel);
+        this.sendToClient(ws, {
+          type: 'error',
+          payload: { message: error instanceof Error ? error.message : 'LISTEN failed', code: 'DATABASE_ERROR' },
+          id: message.id
+        });
+        return;
+      }
+    }
+
+    this.sendToClient(ws, { type: 'result', payload: { channel, listening: true }, id: message.id });
+  }
+
+  private async handleUnlistenMessage(ws: WebSocket, message: WebSocketMessage): Promise<void> {
+    const channel: string = message.payload.channel;
+    const handler = this.listeners.get(ws)?.get(channel);
+    if (handler && this.dbClient instanceof PostgreSQLClient) {
+      this.listeners.get(ws)?.delete(channel);
+      await this.dbClient.unlisten(channel, handler);
+    }
+    this.sendToClient(ws, { type: 'result', payload: { channel, listening: false }, id: message.id });
+  }
+
+  private releaseListeners(ws: WebSocket): void {
+    const channels = this.listeners.get(ws);
+    this.listeners.delete(ws);
+    if (channels && this.dbClient instanceof PostgreSQLClient) {
+      const dbClient = this.dbClient;
+      channels.forEach((handler, channel) => {
+        dbClient.unlisten(channel, handler).catch(error =>
+          console.error(`[WebSocket] Failed to UNLISTEN ${channel}:`, error));
+      });
+    }
+  }
+
+  private async handleSubscribeChangesMessage(ws: WebSocket, message: WebSocketMessage, clientId: string): Promise<void> {
+    if (!(this.dbClient instanceof PostgreSQLClient)) {
+      this.sendToClient(ws, {
+        type: 'error',
+        payload: { message: 'Change streams are not supported by this database client', code: 'UNSUPPORTED_TYPE' },
+        id: message.id
+      });
+      return;
+    }
+
+    // Slot names are capped at 63 characters, so the client and its tables are hashed
+    const tables: string[] = message.payload.tables ?? [];
+    const digest = createHash('sha256').update(`${clientId}\u0000${tables.join(',')}`).digest('hex').slice(0, 16);
+    const subscription = `cdc_${digest}_${Date.now().toString(36)}`;
+    try {
+      const stop = await this.dbClient.openChangeStream(subscription, tables, (change) => {
+        if (ws.readyState === WebSocket.OPEN) {
+          this.sendToClient(ws, { type: 'change', payload: { subscription, change }, id: undefined });
+        }
+      });
+      let streams = this.changeStreams.get(ws);
+      if (!streams) {
+        streams = new Map();
+        this.changeStreams.set(ws, streams);
+      }
+      streams.set(subscription, stop);
+    } catch (error) {
+      this.sendToClient(ws, {
+        type: 'error',
+        payload: { message: error instanceof Error ? error.message : 'Failed to start change stream', code: 'DATABASE_ERROR' },
+        id: message.id
+      });
+      return;
+    }
+
+    this.sendToClient(ws, { type: 'result', payload: { subscription }, id: message.id });
+  }
+
+  private handleUnsubscribeChangesMessage(ws: WebSocket, message: WebSocketMessage): void {
+    const subscription: string = message.payload.subscription;
+    const streams = this.changeStreams.get(ws);
+    const stop = streams?.get(subscription);
+    if (stop) {
+      streams?.delete(subscription);
+      stop();
+    }
+    this.sendToClient(ws, { type: 'result', payload: { unsubscribed: subscription }, id: message.id });
+  }
+
+  private releaseChangeStreams(ws: WebSocket): void {
+    this.changeStreams.get(ws)?.forEach(stop => stop());
+    this.changeStreams.delete(ws);
+  }
+
   private async handleQueryMessage(ws: WebSocket, message: WebSocketMessage, clientId: string): Promise<void> {
     console.log(`[WebSocket] Handling query from ${clientId}:`, message.payload);
     
@@ -311,6 +539,20 @@ export class BasicWebSocketServer implements WebSocketServer {
         return;
       }
 
+      // Replayed writes carry an idempotency key; answer repeats from the cache
+      // Scoped by tenant, so a key never answers for another tenant's write
+      const idempotencyKey: string | undefined = message.payload.idempotencyKey
+        ? `${message.payload.tenantId ?? ''}\u0000${message.payload.idempotencyKey}`
+        : undefined;
+      if (idempotencyKey && this.idempotentResults.has(idempotencyKey)) {
+        this.sendToClient(ws, {
+          type: 'result',
+          payload: this.idempotentResults.get(idempotencyKey),
+          id: message.id
+        });
+        return;
+      }
+
       // Execute the query against the database
       const startTime = Date.now();
       const rows = await this.dbClient.query(message.payload.sql, message.payload.params);
@@ -326,6 +568,16 @@ export class BasicWebSocketServer implements WebSocketServer {
         timestamp: new Date().toISOString()
       };
 
+      if (idempotencyKey) {
+        this.idempotentResults.set(idempotencyKey, result);
+        if (this.idempotentResults.size > BasicWebSocketServer.MAX_IDEMPOTENT_RESULTS) {
+          const oldest = this.idempotentResults.keys().next().value;
+          if (oldest !== undefined) {
+            this.idempotentResults.delete(oldest);
+          }
+        }
+      }
+
       this.sendToClient(ws, {
         type: 'result',
         payload: result,
@@ -352,6 +604,92 @@ export class BasicWebSocketServer implements WebSocketServer {
     }
   }
 
+  /**
+   * HTTP fallback for WebSocket-hostile networks: clients POST messages to
+   * /messages?session=ID and receive every response and server push (notifications,
+   * change events) over an EventSource opened on /events?session=ID.
+   */
+  private handleHttpRequest(req: IncomingMessage, res: ServerResponse): void {
+    const url = new URL(req.url || '/', 'http://localhost');
+    const sessionId = url.searchParams.get('session');
+    res.setHeader('Access-Control-Allow-Origin', '*');
+    res.setHeader('Access-Control-Allow-Headers', 'Content-Type');
+
+    if (req.method === 'OPTIONS') {
+      res.writeHead(204).end();
+      return;
+    }
+    if (!sessionId || !/^[A-Za-z0-9_-]{1,64}$/.test(sessionId)) {
+      res.writeHead(400).end('Missing or invalid session parameter');
+      return;
+    }
+
+    if (req.method === 'GET' && url.pathname === '/events') {
+      this.openSseSession(sessionId, req, res);
+    } else if (req.method === 'POST' && url.pathname === '/messages') {
+      this.receiveSseMessage(sessionId, req, res);
+    } else {
+      res.writeHead(404).end();
+    }
+  }
+
+  private openSseSession(sessionId: string, req: IncomingMessage, res: ServerResponse): void {
+    res.writeHead(200, {
+      'Content-Type': 'text/event-stream',
+      'Cache-Control': 'no-cache',
+      Connection: 'keep-alive'
+    });
+
+    // Only the parts of the WebSocket interface the message handlers use
+    const stream = {
+      readyState: WebSocket.OPEN,
+      send: (data: string) => res.write(`data: ${data}\n\n`)
+    } as unknown as WebSocket;
+    this.sseSessions.set(sessionId, stream);
+    console.log(`[WebSocket] SSE session opened: ${sessionId}`);
+
+    req.on('close', () => {
+      (stream as any).readyState = WebSocket.CLOSED;
+      this.sseSessions.delete(sessionId);
+      this.releaseListeners(stream);
+      this.releaseChangeStreams(stream);
+      console.log(`[WebSocket] SSE session closed: ${sessionId}`);
+    });
+
+    this.sendToClient(stream, {
+      type: 'result',
+      payload: { message: 'Connected to WebSocket server', clientId: sessionId },
+      id: 'welcome'
+    });
+  }
+
+  private receiveSseMessage(sessionId: string, req: IncomingMessage, res: ServerResponse): void {
+    const stream = this.sseSessions.get(sessionId);
+    if (!stream) {
+      res.writeHead(404).end('Unknown session; open /events first');
+      return;
+    }
+
+    const chunks: Buffer[] = [];
+    req.on('data', (chunk: Buffer) => chunks.push(chunk));
+    req.on('end', async () => {
+      res.writeHead(202).end();
+      try {
+        const message = this.parseMessage(Buffer.concat(chunks));
+        await this.handleMessage(stream, message, sessionId);
+      } catch (error) {
+        this.sendToClient(stream, {
+          type: 'error',
+          payload: {
+            message: error instanceof Error ? error.message : 'Invalid message format',
+            code: 'PARSE_ERROR'
+          },
+          id: undefined
+        });
+      }
+    });
+  }
+
   private sendToClient(client: WebSocket, message: WebSocketMessage): void {
     try {
       if (client.readyState === WebSocket.OPEN) {
@@ -203,6 +203,46 @@ describe('WebSocket Communication', () => {
     });
   }, 10000);
 
+  test('should reject a hello whose minVersion is above the server protocol', async () => {
+    await server.start(TEST_PORT);
+    
+    const client = new WebSocket(`ws://localhost:${TEST_PORT}`);
+    
+    await new Promise<void>((resolve, reject) => {
+      let welcomeReceived = false;
+      
+      client.on('open', () => {
+        const hello: WebSocketMessage = {
+          type: 'hello',
+          payload: { version: 99, minVersion: 99 },
+          id: 'future-hello'
+        };
+        
+        client.send(JSON.stringify(hello));
+      });
+      
+      client.on('message', (data) => {
+        const message = JSON.parse(data.toString());
+        
+        if (!welcomeReceived && message.id === 'welcome') {
+          welcomeReceived = true;
+          return;
+        }
+        
+        if (message.id === 'future-hello') {
+          expect(message.type).toBe('error');
+          expect(message.payload.code).toBe('UNSUPPORTED_PROTOCOL');
+          client.close();
+          resolve();
+        }
+      });
+      
+      client.on('error', reject);
+      
+      setTimeout(() => reject(new Error('Hello test timeout')), 5000);
+    });
+  }, 10000);
+
   test('should broadcast messages to all clients', async () => {
     await server.start(TEST_PORT);
     
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+
+// Options accepted by `server_activity`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActivityOptions {
+    // Also list backends sitting idle between queries
+    #[serde(rename = "includeIdle")]
+    pub include_idle: bool,
+    // Also list the backend running this very query
+    #[serde(rename = "includeSelf")]
+    pub include_self: bool,
+}
+
+// One backend from pg_stat_activity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Activity {
+    pub pid: i64,
+    pub state: Option<String>,
+    pub query: Option<String>,
+    #[serde(rename = "waitEventType")]
+    pub wait_event_type: Option<String>,
+    #[serde(rename = "waitEvent")]
+    pub wait_event: Option<String>,
+    #[serde(rename = "applicationName")]
+    pub application_name: Option<String>,
+    #[serde(rename = "clientAddr")]
+    pub client_addr: Option<String>,
+    #[serde(rename = "backendStart")]
+    pub backend_start: Option<String>,
+    #[serde(rename = "queryStart")]
+    pub query_start: Option<String>,
+    // Since the current (or last) query started
+    #[serde(rename = "durationMs")]
+    pub duration_ms: Option<f64>,
+    // Since the open transaction started; absent outside one
+    #[serde(rename = "transactionMs")]
+    pub transaction_ms: Option<f64>,
+}
+
+// Backends logged in as the bridge's role, longest-running query first. The durations are
+// cast to float8 so they come back as numbers rather than numeric strings.
+pub fn activity_sql(options: ActivityOptions) -> String {
+    let mut filters = vec!["usename = current_user"];
+    if !options.include_idle {
+        filters.push("state IS DISTINCT FROM 'idle'");
+    }
+    if !options.include_self {
+        filters.push("pid <> pg_backend_pid()");
+    }
+    format!(
+        "SELECT pid AS \"pid\", state AS \"state\", query AS \"query\", \
+         wait_event_type AS \"waitEventType\", wait_event AS \"waitEvent\", \
+         application_name AS \"applicationName\", client_addr::text AS \"clientAddr\", \
+         backend_start AS \"backendStart\", query_start AS \"queryStart\", \
+         (extract(epoch FROM now() - query_start) * 1000)::float8 AS \"durationMs\", \
+         (extract(epoch FROM now() - xact_start) * 1000)::float8 AS \"transactionMs\" \
+         FROM pg_stat_activity WHERE {} ORDER BY query_start NULLS LAST",
+        filters.join(" AND ")
+    )
+}
+
+pub fn parse_activity(result: &Value) -> Result<Vec<Activity>, String> {
+    let rows = result.get("rows").cloned().unwrap_or(Value::Array(Vec::new()));
+    serde_json::from_value(rows).map_err(|e| format!("Unexpected pg_stat_activity row: {}", e))
+}
+
+pub(crate) async fn server_activity(state: &SharedState, options: ActivityOptions) -> Result<Vec<Activity>, JsValue> {
+    let result = execute_query(state, &activity_sql(options), None).await?;
+    parse_activity(&result).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_activity_sql_filters() {
+        let sql = activity_sql(ActivityOptions::default());
+        assert!(sql.contains("WHERE usename = current_user AND state IS DISTINCT FROM 'idle' AND pid <> pg_backend_pid() ORDER BY"));
+        let sql = activity_sql(ActivityOptions { include_idle: true, include_self: true });
+        assert!(sql.contains("WHERE usename = current_user ORDER BY"));
+    }
+
+    #[test]
+    fn test_parse_activity_rows() {
+        let result = json!({"rows": [{
+            "pid": 4242, "state": "active", "query": "SELECT pg_sleep(5)",
+            "waitEventType": "Timeout", "waitEvent": "PgSleep", "applicationName": "",
+            "clientAddr": "10.0.0.5/32", "backendStart": "2026-10-16T09:00:00.000Z",
+            "queryStart": "2026-10-16T09:30:00.000Z", "durationMs": 1250.5, "transactionMs": null
+        }]});
+        let activity = parse_activity(&result).unwrap();
+        assert_eq!(activity[0].pid, 4242);
+        assert_eq!(activity[0].wait_event.as_deref(), Some("PgSleep"));
+        assert_eq!(activity[0].transaction_ms, None);
+        assert!(parse_activity(&json!({"rows": [{"state": "active"}]})).is_err());
+    }
+}
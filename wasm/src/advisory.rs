@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::connection::{execute_query, SharedState};
+use crate::errors::BridgeError;
+
+// Key of a Postgres advisory lock: a 64-bit integer, or a name Postgres hashes to one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockKey {
+    Id(i64),
+    Name(String),
+}
+
+impl LockKey {
+    // A safe integer, a BigInt or a non-empty string
+    pub fn from_js(key: &JsValue) -> Result<LockKey, JsValue> {
+        if let Some(name) = key.as_string() {
+            return LockKey::name(name);
+        }
+        if let Some(n) = key.as_f64() {
+            return LockKey::number(n);
+        }
+        if key.is_bigint() {
+            let text = String::from(js_sys::BigInt::unchecked_from_js_ref(key).to_string(10).unwrap_or_default());
+            return text.parse().map(LockKey::Id).map_err(|_| invalid("BigInt lock keys must fit in 64 bits"));
+        }
+        Err(invalid("Lock keys must be integers or strings"))
+    }
+
+    fn name(name: String) -> Result<LockKey, JsValue> {
+        if name.is_empty() {
+            return Err(invalid("Lock names must not be empty"));
+        }
+        Ok(LockKey::Name(name))
+    }
+
+    fn number(n: f64) -> Result<LockKey, JsValue> {
+        const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+        if n.fract() != 0.0 || n.abs() > MAX_SAFE_INTEGER {
+            return Err(invalid("Numeric lock keys must be safe integers; pass a BigInt for larger ones"));
+        }
+        Ok(LockKey::Id(n as i64))
+    }
+
+    // `SELECT function(key) AS locked` with the key as `$1`
+    pub fn sql(&self, function: &str) -> String {
+        let key = match self {
+            LockKey::Id(_) => "$1::bigint",
+            LockKey::Name(_) => "hashtextextended($1, 0)",
+        };
+        format!("SELECT {}({}) AS locked", function, key)
+    }
+
+    pub fn param(&self) -> Value {
+        match self {
+            // As a string: JSON numbers lose precision beyond 2^53
+            LockKey::Id(id) => json!(id.to_string()),
+            LockKey::Name(name) => json!(name),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            LockKey::Id(id) => id.to_string(),
+            LockKey::Name(name) => name.clone(),
+        }
+    }
+}
+
+fn invalid(message: &str) -> JsValue {
+    BridgeError::from_error_payload(&json!({ "message": message, "code": "INVALID_LOCK_KEY" }), None).into()
+}
+
+// What a `pg_try_advisory_*` or `pg_advisory_unlock` call returned
+pub fn locked(result: &Value) -> bool {
+    result.pointer("/rows/0/locked").and_then(|locked| locked.as_bool()).unwrap_or(false)
+}
+
+// Session-level locks taken through the client, by key label. Postgres stacks them, so a
+// key taken twice needs two unlocks.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AdvisoryLocks {
+    held: BTreeMap<String, u32>,
+}
+
+impl AdvisoryLocks {
+    pub fn acquired(&mut self, label: String) {
+        *self.held.entry(label).or_default() += 1;
+    }
+
+    pub fn released(&mut self, label: &str) {
+        if let Some(count) = self.held.get_mut(label) {
+            *count -= 1;
+            if *count == 0 {
+                self.held.remove(label);
+            }
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        self.held.keys().cloned().collect()
+    }
+
+    pub fn take(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.held).into_keys().collect()
+    }
+}
+
+// Detail of the `advisorylockslost` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LocksLost {
+    pub keys: Vec<String>,
+    pub reason: String,
+}
+
+// Session locks live on the bridge's backend connection. One that can be handed to
+// another client after each query would leave them held by whoever gets it next.
+fn check_pinned(state: &SharedState) -> Result<(), JsValue> {
+    let pooled = state.borrow().protocol.pool.and_then(|pool| pool.mode) == Some(bridge_protocol::PoolMode::Pooled);
+    if pooled {
+        let message = "Session advisory locks need a session or dedicated pool mode; take a transaction lock instead";
+        return Err(BridgeError::from_error_payload(&json!({ "message": message, "code": "POOLED_SESSION" }), None).into());
+    }
+    Ok(())
+}
+
+// `pg_advisory_lock`: wait until the lock is free, then hold it until unlocked or the
+// session ends
+pub(crate) async fn lock(state: &SharedState, key: LockKey) -> Result<(), JsValue> {
+    check_pinned(state)?;
+    execute_query(state, &key.sql("pg_advisory_lock"), Some(vec![key.param()])).await?;
+    state.borrow_mut().advisory_locks.acquired(key.label());
+    Ok(())
+}
+
+// `pg_try_advisory_lock`: take the lock if it is free, without waiting
+pub(crate) async fn try_lock(state: &SharedState, key: LockKey) -> Result<bool, JsValue> {
+    check_pinned(state)?;
+    let result = execute_query(state, &key.sql("pg_try_advisory_lock"), Some(vec![key.param()])).await?;
+    let acquired = locked(&result);
+    if acquired {
+        state.borrow_mut().advisory_locks.acquired(key.label());
+    }
+    Ok(acquired)
+}
+
+// `pg_advisory_unlock`; false when this session didn't hold the lock
+pub(crate) async fn unlock(state: &SharedState, key: LockKey) -> Result<bool, JsValue> {
+    let result = execute_query(state, &key.sql("pg_advisory_unlock"), Some(vec![key.param()])).await?;
+    let released = locked(&result);
+    if released {
+        state.borrow_mut().advisory_locks.released(&key.label());
+    }
+    Ok(released)
+}
+
+// The backend session holding the client's locks is gone, and Postgres released them with
+// it: tell the app, which may have a job or edit that is no longer protected
+pub(crate) fn lost(state: &SharedState, reason: &str) {
+    let keys = state.borrow_mut().advisory_locks.take();
+    if keys.is_empty() {
+        return;
+    }
+    log_warn!("WASM lost {} advisory locks: {}", keys.len(), reason);
+    crate::events::emit(state, "advisorylockslost", &LocksLost { keys, reason: reason.to_string() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_and_held_locks() {
+        let id = LockKey::Id(42);
+        assert_eq!(id.sql("pg_try_advisory_lock"), "SELECT pg_try_advisory_lock($1::bigint) AS locked");
+        assert_eq!(id.param(), json!("42"));
+        let name = LockKey::Name("nightly-report".to_string());
+        assert_eq!(name.sql("pg_advisory_xact_lock"), "SELECT pg_advisory_xact_lock(hashtextextended($1, 0)) AS locked");
+        assert!(locked(&json!({ "rows": [{ "locked": true }] })));
+        assert!(!locked(&json!({ "rows": [{ "locked": null }] })));
+
+        let mut locks = AdvisoryLocks::default();
+        locks.acquired(id.label());
+        locks.acquired(id.label());
+        locks.acquired(name.label());
+        locks.released("42");
+        assert_eq!(locks.keys(), vec!["42", "nightly-report"]);
+        locks.released("42");
+        assert_eq!(locks.take(), vec!["nightly-report"]);
+        assert!(locks.keys().is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+
+use bridge_protocol::{message_type, QueryTags, WebSocketMessage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+use crate::hashing::hash_hex;
+use crate::query_cache::normalize_sql;
+
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+// Options accepted by `enable_audit_log`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default)]
+pub struct AuditOptions {
+    // User or session label stamped on every entry
+    pub tag: Option<String>,
+    // Keep the SQL text as well as its hash
+    #[serde(rename = "includeSql")]
+    pub include_sql: bool,
+    #[serde(rename = "maxEntries")]
+    pub max_entries: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    // The database rejected the statement
+    Error,
+    // No answer: the connection dropped or the request was refused before sending
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    // Consecutive from 1, so dropped or removed entries leave a visible gap
+    pub sequence: u64,
+    #[serde(rename = "statementHash")]
+    pub statement_hash: String,
+    #[serde(rename = "paramsHash")]
+    pub params_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    // The query's own `tags` option
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<QueryTags>,
+    // Milliseconds since the epoch when the statement was sent
+    pub timestamp: f64,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: f64,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+// Append-only trail of the statements this client sent. Once `maxEntries` is reached
+// the oldest entries are dropped, so export them through `on_audit_entry` to keep them.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    options: Option<AuditOptions>,
+    entries: VecDeque<AuditEntry>,
+    sequence: u64,
+}
+
+impl AuditLog {
+    pub fn enable(&mut self, options: AuditOptions) {
+        self.options = Some(options);
+        while self.entries.len() > self.capacity() {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn disable(&mut self) {
+        self.options = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.options.is_some()
+    }
+
+    fn capacity(&self) -> usize {
+        self.options.as_ref().and_then(|o| o.max_entries).unwrap_or(DEFAULT_MAX_ENTRIES).max(1)
+    }
+
+    pub fn record(
+        &mut self,
+        sql: &str,
+        params: &Option<Vec<Value>>,
+        tags: Option<&QueryTags>,
+        timestamp: f64,
+        duration_ms: f64,
+        (outcome, code): (Outcome, Option<String>),
+    ) -> Option<AuditEntry> {
+        let options = self.options.as_ref()?;
+        let params = params.as_ref().map_or(Value::Array(Vec::new()), |p| Value::from(p.clone()));
+        self.sequence += 1;
+        let entry = AuditEntry {
+            sequence: self.sequence,
+            statement_hash: hash_hex(normalize_sql(sql).as_bytes()),
+            params_hash: hash_hex(params.to_string().as_bytes()),
+            sql: options.include_sql.then(|| sql.to_string()),
+            tag: options.tag.clone(),
+            tags: tags.cloned(),
+            timestamp,
+            duration_ms,
+            outcome,
+            code,
+        };
+        if self.entries.len() >= self.capacity() {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry.clone());
+        Some(entry)
+    }
+
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    // One JSON object per line, oldest first
+    pub fn export(&self) -> String {
+        self.entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect()
+    }
+}
+
+// Record a query's round trip and pass the entry to the export hook
+pub(crate) fn observe(
+    state: &SharedState,
+    sql: &str,
+    params: &Option<Vec<Value>>,
+    tags: Option<&QueryTags>,
+    started: f64,
+    response: &Result<WebSocketMessage, JsValue>,
+) {
+    let outcome = match response {
+        Ok(message) if message.message_type == message_type::ERROR => {
+            let code = message.payload.get("sqlState").or_else(|| message.payload.get("code"));
+            (Outcome::Error, code.and_then(|c| c.as_str()).map(String::from))
+        }
+        Ok(_) => (Outcome::Ok, None),
+        Err(_) => (Outcome::Failed, None),
+    };
+    let now = js_sys::Date::now();
+    let (entry, hook) = {
+        let mut state = state.borrow_mut();
+        let entry = state.audit.record(sql, params, tags, started, now - started, outcome);
+        (entry, state.audit_hook.clone())
+    };
+    if let (Some(entry), Some(hook)) = (entry, hook) {
+        if let Ok(entry) = crate::to_js(&entry) {
+            let _ = hook.call1(&JsValue::NULL, &entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_disabled_until_enabled() {
+        let mut log = AuditLog::default();
+        assert!(log.record("SELECT 1", &None, None, 0.0, 1.0, (Outcome::Ok, None)).is_none());
+        log.enable(AuditOptions { tag: Some("alice".to_string()), ..AuditOptions::default() });
+        let tags = QueryTags::from([("feature".to_string(), "orders-grid".to_string())]);
+        let entry = log.record("SELECT  1;", &Some(vec![json!(1)]), Some(&tags), 5.0, 2.0, (Outcome::Ok, None)).unwrap();
+        assert_eq!(entry.sequence, 1);
+        assert_eq!(entry.statement_hash, hash_hex(b"SELECT 1"));
+        assert_eq!(entry.params_hash, hash_hex(b"[1]"));
+        assert_eq!(entry.sql, None);
+        assert_eq!(entry.tag.as_deref(), Some("alice"));
+        assert_eq!(serde_json::to_value(&entry).unwrap()["tags"], json!({ "feature": "orders-grid" }));
+    }
+
+    #[test]
+    fn test_capacity_and_export() {
+        let mut log = AuditLog::default();
+        log.enable(AuditOptions { include_sql: true, max_entries: Some(2), ..AuditOptions::default() });
+        for sql in ["SELECT 1", "SELECT 2", "SELECT 3"] {
+            log.record(sql, &None, None, 0.0, 1.0, (Outcome::Error, Some("42P01".to_string())));
+        }
+        let sequences: Vec<u64> = log.entries().iter().map(|e| e.sequence).collect();
+        assert_eq!(sequences, vec![2, 3]);
+        let first = log.export().lines().next().unwrap().to_string();
+        let first: Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(first["sql"], "SELECT 2");
+        assert_eq!(first["outcome"], "error");
+        assert_eq!(first["code"], "42P01");
+    }
+}
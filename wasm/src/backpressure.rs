@@ -0,0 +1,92 @@
+use bridge_protocol::BridgeLoad;
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+
+// A load report older than this says nothing about the bridge now
+const STALE_MS: f64 = 5000.0;
+
+// Options accepted by `set_adaptive_throttle`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleOptions {
+    // Pool saturation, from 0 to 1, above which queries start to be delayed
+    pub saturation: f64,
+    // The delay at full saturation, or while sessions wait for a pooled connection
+    #[serde(rename = "maxDelayMs")]
+    pub max_delay_ms: f64,
+}
+
+impl Default for ThrottleOptions {
+    fn default() -> Self {
+        ThrottleOptions { saturation: 0.8, max_delay_ms: 1000.0 }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct BackpressureState {
+    // The load the bridge reported with its latest result, and when it arrived
+    pub latest: Option<(BridgeLoad, f64)>,
+    pub throttle: Option<ThrottleOptions>,
+}
+
+impl BackpressureState {
+    pub fn observe(&mut self, payload: &Value, now: f64) {
+        if let Some(load) = payload.get("load").and_then(|load| serde_json::from_value::<BridgeLoad>(load.clone()).ok()) {
+            self.latest = Some((load, now));
+        }
+    }
+
+    // How long the next query should wait before it is sent
+    pub fn delay_ms(&self, now: f64) -> f64 {
+        match (self.throttle, self.latest) {
+            (Some(options), Some((load, at))) if now - at < STALE_MS => delay_ms(&load, &options),
+            _ => 0.0,
+        }
+    }
+}
+
+// Nothing below the threshold, then rising linearly to the full delay at saturation 1.
+// Sessions already waiting for a connection mean the pool is exhausted.
+pub fn delay_ms(load: &BridgeLoad, options: &ThrottleOptions) -> f64 {
+    if load.pool_waiting > 0 {
+        return options.max_delay_ms;
+    }
+    if load.saturation <= options.saturation || options.saturation >= 1.0 {
+        return 0.0;
+    }
+    let over = (load.saturation - options.saturation) / (1.0 - options.saturation);
+    over.min(1.0) * options.max_delay_ms
+}
+
+// Hold a query back while the bridge reports it is under pressure
+pub(crate) async fn throttle(state: &SharedState) -> Result<(), JsValue> {
+    let delay = state.borrow().backpressure.delay_ms(js_sys::Date::now());
+    if delay > 0.0 {
+        log_debug!("WASM delaying a query {:.0}ms for bridge load", delay);
+        crate::retry::sleep(delay).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_grows_above_the_threshold() {
+        let options = ThrottleOptions::default();
+        let load = |saturation: f64, pool_waiting: usize| BridgeLoad { queued: 0, pool_waiting, saturation };
+        assert_eq!(delay_ms(&load(0.5, 0), &options), 0.0);
+        assert_eq!(delay_ms(&load(0.9, 0), &options).round(), 500.0);
+        assert_eq!(delay_ms(&load(0.5, 3), &options), 1000.0);
+
+        let mut state = BackpressureState { throttle: Some(options), ..Default::default() };
+        state.observe(&serde_json::json!({ "rows": [], "load": { "queued": 0, "poolWaiting": 1, "saturation": 1.0 } }), 0.0);
+        assert_eq!(state.delay_ms(100.0), 1000.0);
+        assert_eq!(state.delay_ms(STALE_MS + 1.0), 0.0);
+    }
+}
@@ -0,0 +1,41 @@
+// Generate TypeScript or Rust row types from introspected column metadata.
+//
+// Usage: bridge-codegen <ts|rust> < columns.json
+//
+// `columns.json` is the JSON array of rows returned by the introspection query,
+// printed with `bridge-codegen sql`.
+use std::io::Read;
+
+use wasm_postgres_learning::codegen::{generate_rust, generate_typescript, group_tables, ColumnInfo, INTROSPECTION_SQL};
+
+fn main() {
+    let target = std::env::args().nth(1).unwrap_or_default();
+    if target == "sql" {
+        println!("{};", INTROSPECTION_SQL);
+        return;
+    }
+    if target != "ts" && target != "rust" {
+        eprintln!("Usage: bridge-codegen <ts|rust|sql> < columns.json");
+        std::process::exit(2);
+    }
+
+    let mut input = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+        eprintln!("Failed to read stdin: {}", e);
+        std::process::exit(1);
+    }
+    let columns: Vec<ColumnInfo> = match serde_json::from_str(&input) {
+        Ok(columns) => columns,
+        Err(e) => {
+            eprintln!("Invalid column metadata JSON: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let tables = group_tables(columns);
+    if target == "ts" {
+        print!("{}", generate_typescript(&tables));
+    } else {
+        print!("{}", generate_rust(&tables));
+    }
+}
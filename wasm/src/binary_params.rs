@@ -0,0 +1,84 @@
+use bridge_protocol::binary::{BinaryParam, BinaryValue};
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::errors::BridgeError;
+use crate::null_policy::param_from_js;
+
+// A query's `binaryParams`: by position, the parameters sent as bytes instead of JSON
+pub type BinaryParams = Vec<Option<BinaryParam>>;
+
+// The binary form of a JS parameter that has one: typed arrays, byte buffers, dates and
+// BigInts. Everything else is sent as JSON.
+fn binary_value(param: &JsValue) -> Option<BinaryValue> {
+    if let Some(bytes) = param.dyn_ref::<js_sys::Uint8Array>() {
+        return Some(BinaryValue::Bytea(bytes.to_vec()));
+    }
+    if param.is_instance_of::<js_sys::ArrayBuffer>() {
+        return Some(BinaryValue::Bytea(js_sys::Uint8Array::new(param).to_vec()));
+    }
+    if let Some(array) = param.dyn_ref::<js_sys::Int32Array>() {
+        return Some(BinaryValue::Int4Array(array.to_vec()));
+    }
+    if let Some(array) = param.dyn_ref::<js_sys::BigInt64Array>() {
+        return Some(BinaryValue::Int8Array(array.to_vec()));
+    }
+    if let Some(array) = param.dyn_ref::<js_sys::Float32Array>() {
+        return Some(BinaryValue::Float4Array(array.to_vec()));
+    }
+    if let Some(array) = param.dyn_ref::<js_sys::Float64Array>() {
+        return Some(BinaryValue::Float8Array(array.to_vec()));
+    }
+    if let Some(date) = param.dyn_ref::<js_sys::Date>() {
+        return Some(BinaryValue::timestamptz_from_unix_ms(date.get_time()));
+    }
+    if param.is_bigint() {
+        let text = String::from(js_sys::BigInt::unchecked_from_js_ref(param).to_string(10).unwrap_or_default());
+        return text.parse().ok().map(BinaryValue::Int8);
+    }
+    None
+}
+
+// `binaryParams` for a query, or None when no parameter went binary
+pub fn pack(values: Vec<Option<BinaryValue>>) -> Option<BinaryParams> {
+    values.iter().any(Option::is_some).then(|| values.iter().map(|value| value.as_ref().map(BinaryValue::to_param)).collect())
+}
+
+// Split a JS parameter array into JSON `params`, with null where a parameter is sent in
+// binary, and the `binaryParams` that fill those places
+pub fn params_from_js(params: &js_sys::Array, strict: bool) -> Result<(Vec<Value>, Option<BinaryParams>), JsValue> {
+    let mut values = Vec::with_capacity(params.length() as usize);
+    let mut binary = Vec::with_capacity(params.length() as usize);
+    for (index, param) in params.iter().enumerate() {
+        match binary_value(&param) {
+            Some(value) => {
+                values.push(Value::Null);
+                binary.push(Some(value));
+            }
+            None => {
+                values.push(param_from_js(index, param, strict)?);
+                binary.push(None);
+            }
+        }
+    }
+    Ok((values, pack(binary)))
+}
+
+pub fn unsupported() -> JsValue {
+    let message = "The bridge did not negotiate binary parameters; send these values as JSON instead";
+    BridgeError::from_error_payload(&json!({ "message": message, "code": "BINARY_UNSUPPORTED" }), None).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_only_when_something_is_binary() {
+        assert_eq!(pack(vec![None, None]), None);
+        let packed = pack(vec![None, Some(BinaryValue::Bytea(vec![1, 2, 3]))]).unwrap();
+        assert_eq!(packed[0], None);
+        assert_eq!(serde_json::to_value(&packed[1]).unwrap(), json!({ "type": "bytea", "data": "AQID" }));
+    }
+}
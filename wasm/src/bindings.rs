@@ -0,0 +1,2532 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bridge_protocol::chunking::{ChunkAssembler, DEFAULT_MAX_ASSEMBLED_SIZE};
+use bridge_protocol::encryption::FrameKey;
+use bridge_protocol::{message_type, AdminPayload, DeallocatePayload, DescribePayload, StatementDescription};
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::config::WasmClientConfig;
+use crate::connection::{self, ClientState, QueryOptions, SendOptions, SharedState};
+use crate::errors::BridgeError;
+use crate::events::Listener;
+use crate::logging::set_log_level;
+use crate::migrations::{Migration, MigrationSet};
+use crate::null_policy::NullPolicy;
+use crate::query_builder::QueryBuilder;
+use crate::query_status::QueryStatus;
+use crate::template::SqlTemplate;
+use crate::{
+    activity, advisory, audit, backpressure, binary_params, bulk, cache_invalidation, cdc, chaos, codegen, column_case, columnar, conflict, continuation, cost_guard, cursor, diagnostics, explain, export, fixtures, ids, idle, in_list, intern, large_object, limit_guard,
+    live, local_settings, migrations, named_subscriptions, notifications, null_policy, opfs, optimistic, outbox, pagination, payload_size, placeholders, prewarm,
+    prometheus, query_cache, rate_limit, raw, read_only, reconnect, replay, result_budget, result_cache, resume, retry, routines, row_transform,
+    session_state, sql_validate, sse, statement_kind, subscription_store, sync, temp_table, throttled, time_zone, tracing, transaction, transport, visibility, webtransport,
+};
+use crate::{parse_params_json, to_js, StatementPolicy, WebSocketMessage};
+
+// Close one kept prepared statement, or all of them; returns how many were closed
+async fn deallocate(state: &SharedState, handle: Option<String>) -> Result<u64, JsValue> {
+    let payload = serde_json::to_value(DeallocatePayload { handle })
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize deallocate: {}", e)))?;
+    let response = connection::request(state, "deallocate", message_type::DEALLOCATE, payload).await?;
+    Ok(response.payload.get("deallocated").and_then(|n| n.as_u64()).unwrap_or(0))
+}
+
+// WebSocket client functionality
+#[wasm_bindgen]
+pub struct WasmWebSocketClient {
+    url: String,
+    state: SharedState,
+    null_policy: NullPolicy,
+    strict_params: bool,
+    migrations: MigrationSet,
+    result_cache_ttl_ms: f64,
+    websocket_impl: Option<js_sys::Function>,
+    // Shape `query()` resolves with when the call doesn't choose one
+    result_format: columnar::ResultFormat,
+    // Rewrites `?` placeholders in `query()` SQL when "question"
+    placeholders: placeholders::PlaceholderStyle,
+    // Result column naming when the query doesn't choose one
+    column_case: column_case::ColumnCase,
+    // Row transforms queries can name in their `transform` option
+    row_transforms: row_transform::Registry,
+}
+
+#[wasm_bindgen]
+impl WasmWebSocketClient {
+    // Accepts the bridge URL, or a config object:
+    // `{ url, auth, timeouts: { connectMs, requestMs }, reconnect: { enabled, maxAttempts,
+    // baseDelayMs, maxDelayMs }, encoding, placeholders, columnCase, logLevel, searchPath }`
+    #[wasm_bindgen(constructor)]
+    pub fn new(#[wasm_bindgen(unchecked_param_type = "string | WasmClientConfig")] config: JsValue) -> Result<WasmWebSocketClient, JsValue> {
+        let config = WasmClientConfig::from_js(config).map_err(|e| JsValue::from_str(&e))?;
+        if let Some(level) = &config.log_level {
+            set_log_level(level)?;
+        }
+        Ok(WasmWebSocketClient::with_config(config))
+    }
+
+    #[wasm_bindgen]
+    pub fn connect(&mut self) -> Result<(), JsValue> {
+        log_info!("Connecting to WebSocket server: {}", self.url);
+        connection::admit_connection(&self.state)?;
+        let url = self.url.clone();
+        let websocket_impl = self.websocket_impl.clone();
+        self.open_with(Rc::new(move |state: &SharedState| {
+            let ws = transport::open_socket(&url, websocket_impl.as_ref())?;
+            let events = transport::TransportEvents::new(state);
+            state.borrow_mut().transport = Some(Box::new(transport::WebSocketTransport::attach(ws, events)));
+            Ok(())
+        }))
+    }
+
+    // Connect from Cloudflare Workers and other WinterCG runtimes, where outbound
+    // sockets come from a `fetch` upgrade rather than `new WebSocket()`
+    #[wasm_bindgen]
+    pub fn connect_edge(&self) -> Promise {
+        log_info!("Connecting to WebSocket server via fetch upgrade: {}", self.url);
+        let state = self.state.clone();
+        let url = self.url.clone();
+        future_to_promise(async move {
+            connection::admit_connection(&state)?;
+            state.borrow_mut().idle.reconnect = None;
+            let ws = transport::open_edge_socket(&url).await?;
+            let events = transport::TransportEvents::new(&state);
+            state.borrow_mut().transport = Some(Box::new(transport::WebSocketTransport::attach(ws, events)));
+            // The socket is accepted already open, so no open event will fire
+            transport::TransportEvents::new(&state).opened();
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Connect over WebTransport (HTTP/3) at `webtransport_url`, falling back to the
+    // WebSocket URL when WebTransport is unavailable or the session fails to open.
+    // Resolves with the transport in use: "webtransport" or "websocket".
+    #[wasm_bindgen]
+    pub fn connect_webtransport(&mut self, webtransport_url: String) -> Promise {
+        let state = self.state.clone();
+        let url = self.url.clone();
+        let websocket_impl = self.websocket_impl.clone();
+        future_to_promise(async move {
+            connection::admit_connection(&state)?;
+            state.borrow_mut().idle.reconnect = None;
+            let events = transport::TransportEvents::new(&state);
+            match webtransport::WebTransportTransport::connect(&webtransport_url, events.clone()).await {
+                Ok(session) => {
+                    state.borrow_mut().transport = Some(Box::new(session));
+                    events.opened();
+                    Ok(JsValue::from_str("webtransport"))
+                }
+                Err(e) => {
+                    log_warn!("WASM WebTransport unavailable, falling back to WebSocket: {:?}", e);
+                    let ws = transport::open_socket(&url, websocket_impl.as_ref())?;
+                    state.borrow_mut().transport = Some(Box::new(transport::WebSocketTransport::attach(ws, events)));
+                    Ok(JsValue::from_str("websocket"))
+                }
+            }
+        })
+    }
+
+    // Connect over the HTTP fallback (POST for requests, Server-Sent Events for
+    // everything the bridge pushes) for networks that block WebSocket upgrades.
+    // `base_url` defaults to the client's URL.
+    #[wasm_bindgen]
+    pub fn connect_sse(&mut self, base_url: Option<String>) -> Result<(), JsValue> {
+        let base = base_url.unwrap_or_else(|| self.url.clone());
+        log_info!("Connecting to bridge over SSE fallback: {}", base);
+        connection::admit_connection(&self.state)?;
+        self.open_with(Rc::new(move |state: &SharedState| {
+            let session = format!(
+                "sse_{}_{:08x}",
+                js_sys::Date::now() as u64,
+                (js_sys::Math::random() * u32::MAX as f64) as u32
+            );
+            let events = transport::TransportEvents::new(state);
+            let transport = sse::SseTransport::connect(&base, &session, events)?;
+            state.borrow_mut().transport = Some(Box::new(transport));
+            Ok(())
+        }))
+    }
+
+    // Connect through a custom transport: `factory(url, events)` must return an object
+    // with `send(text)`, `close()` and `isOpen()`, and report activity via `events.open()`,
+    // `events.message(text)`, `events.close(code, reason)` and `events.error(message)`
+    #[wasm_bindgen]
+    pub fn connect_with_transport(&mut self, factory: js_sys::Function) -> Result<(), JsValue> {
+        connection::admit_connection(&self.state)?;
+        let url = self.url.clone();
+        self.open_with(Rc::new(move |state: &SharedState| {
+            let events = transport::TransportEvents::new(state);
+            let transport = transport::JsTransport::create(&factory, &url, events)?;
+            state.borrow_mut().transport = Some(Box::new(transport));
+            Ok(())
+        }))
+    }
+
+    // WebSocket constructor used by `connect`, for runtimes without a global one
+    // (e.g. `client.set_websocket_impl(require('ws'))` in Node)
+    #[wasm_bindgen]
+    pub fn set_websocket_impl(&mut self, constructor: js_sys::Function) {
+        self.websocket_impl = Some(constructor);
+    }
+
+    // Open a connection now and remember how, so an idle-suspended or dropped client can
+    // reopen it. Every attempt is abandoned if it doesn't open within the connect timeout.
+    fn open_with(&mut self, open: idle::Reconnect) -> Result<(), JsValue> {
+        let open: idle::Reconnect = match self.state.borrow().timeouts.connect_ms {
+            Some(timeout_ms) => Rc::new(move |state: &SharedState| {
+                open(state)?;
+                reconnect::watch_connect(state, timeout_ms);
+                Ok(())
+            }),
+            None => open,
+        };
+        open(&self.state)?;
+        let mut state = self.state.borrow_mut();
+        state.idle.reconnect = Some(open);
+        state.idle.suspended = false;
+        state.reconnect.armed = true;
+        Ok(())
+    }
+
+    // Get the connection ready during app bootstrap so the first query doesn't wait for
+    // it: connect unless already connecting, wait for the handshake, auth and session
+    // setup, then prepare the statements given to `set_hot_statements`. Resolves with
+    // `{ readyMs, prepared, failed }`.
+    #[wasm_bindgen]
+    pub fn prewarm(&mut self) -> Result<Promise, JsValue> {
+        // A suspended idle connection is reopened by `prewarm` itself
+        let opened = {
+            let state = self.state.borrow();
+            state.transport.is_some() || state.idle.suspended
+        };
+        if !opened {
+            self.connect()?;
+        }
+        let state = self.state.clone();
+        Ok(future_to_promise(async move { to_js(&prewarm::prewarm(&state).await?) }))
+    }
+
+    // SQL `prewarm` prepares and the bridge keeps for the session, as `[{ sql, types }]`.
+    // Statements are only kept while the session holds its connection, so this helps most
+    // with the dedicated pool mode.
+    #[wasm_bindgen]
+    pub fn set_hot_statements(&self, #[wasm_bindgen(unchecked_param_type = "HotStatement[]")] statements: JsValue) -> Result<(), JsValue> {
+        let mut statements: Vec<prewarm::HotStatement> =
+            serde_wasm_bindgen::from_value(statements).map_err(|e| JsValue::from_str(&format!("Invalid hot statements: {}", e)))?;
+        if self.placeholders == placeholders::PlaceholderStyle::Question {
+            for statement in &mut statements {
+                statement.sql = placeholders::translate(&statement.sql).map_err(|reason| JsValue::from_str(&reason))?;
+            }
+        }
+        self.state.borrow_mut().hot_statements = statements;
+        Ok(())
+    }
+
+    // Close the connection after `minutes` without requests and reopen it on the next
+    // query; undefined disables. Not used while live queries or change subscriptions
+    // are active, since those need the connection. Edge and WebTransport connections
+    // can't be reopened automatically.
+    #[wasm_bindgen]
+    pub fn set_idle_timeout(&mut self, minutes: Option<f64>) {
+        self.state.borrow_mut().idle.timeout_ms = minutes.map(|m| m * 60_000.0);
+        if self.state.borrow().is_connected() {
+            idle::start_timer(&self.state);
+        }
+    }
+
+    // What the client does while its page is hidden. `options` accepts `{ pauseLiveQueries,
+    // heartbeatMs, hiddenHeartbeatMs, suspendAfterMs }`; visibility follows the document's
+    // `visibilitychange`. Null turns it all off.
+    #[wasm_bindgen]
+    pub fn set_visibility_options(&self, #[wasm_bindgen(unchecked_optional_param_type = "VisibilityOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        if options.is_undefined() || options.is_null() {
+            visibility::reset(&self.state);
+            return Ok(());
+        }
+        let options: visibility::VisibilityOptions =
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid visibility options: {}", e)))?;
+        options.validate().map_err(|e| JsValue::from_str(&format!("Invalid visibility options: {}", e)))?;
+        visibility::configure(&self.state, options);
+        Ok(())
+    }
+
+    // Tell the client its page was shown or hidden, for clients without a document such
+    // as one in a Web Worker
+    #[wasm_bindgen]
+    pub fn set_page_visible(&self, visible: bool) {
+        visibility::changed(&self.state, !visible);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_page_hidden(&self) -> bool {
+        self.state.borrow().visibility.hidden
+    }
+
+    #[wasm_bindgen]
+    pub fn disconnect(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.idle.suspended = false;
+        state.reconnect.armed = false;
+        if let Some(transport) = state.transport.take() {
+            log_info!("Disconnecting WASM WebSocket");
+            transport.close();
+            state.fail_pending("Client disconnected");
+        }
+    }
+
+    // Stop accepting new queries, wait up to `timeout_ms` for in-flight ones, reject
+    // whatever is left with a "Client closing" error, then close the connection.
+    // Resolves with `{ completed, rejected }`.
+    #[wasm_bindgen]
+    pub fn close_gracefully(&self, timeout_ms: f64) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let summary = connection::close_gracefully(&state, timeout_ms).await?;
+            to_js(&summary)
+        })
+    }
+
+    // Stop a running query on the backend, as a Postgres CancelRequest would; the
+    // query's own promise then rejects with SQLSTATE 57014. Resolves to whether
+    // the bridge found it running.
+    #[wasm_bindgen]
+    pub fn cancel_query(&self, query_id: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let cancelled = connection::cancel_query(&state, &query_id).await?;
+            Ok(JsValue::from_bool(cancelled))
+        })
+    }
+
+    // "queued", "sent", "executing", "completed" or "failed" for a `query()` by its id
+    // (the `queryId` option, or the id reported to `on_progress` and on errors);
+    // undefined once it is unknown. Settled queries are remembered for a while.
+    #[wasm_bindgen]
+    pub fn query_status(&self, id: &str) -> Option<String> {
+        self.state.borrow().queries.status(id).map(|status| status.as_str().to_string())
+    }
+
+    // Cancel one request by id. A queued query rejects with code CANCELLED instead of
+    // being sent; a running one is cancelled on the backend when the bridge supports it,
+    // otherwise it rejects locally while the bridge finishes it. Resolves to false when
+    // nothing was waiting under `id`.
+    #[wasm_bindgen]
+    pub fn cancel(&self, id: &str) -> Promise {
+        let state = self.state.clone();
+        let id = id.to_string();
+        future_to_promise(async move {
+            if state.borrow_mut().queries.cancel_queued(&id) {
+                return Ok(JsValue::TRUE);
+            }
+            let (waiting, supported) = {
+                let state = state.borrow();
+                (state.pending.contains_key(&id), state.protocol.negotiated.is_some_and(|n| n.capabilities.cancel))
+            };
+            if !waiting {
+                return Ok(JsValue::FALSE);
+            }
+            if supported && connection::cancel_query(&state, &id).await? {
+                return Ok(JsValue::TRUE);
+            }
+            let payload = serde_json::json!({ "message": "Query cancelled", "code": "CANCELLED" });
+            let cancelled = state.borrow_mut().fail_request(&id, BridgeError::from_error_payload(&payload, Some(id.clone())));
+            Ok(JsValue::from_bool(cancelled))
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn is_connected(&self) -> bool {
+        self.state.borrow().is_connected()
+    }
+
+    // Requests sent and still waiting for a response
+    #[wasm_bindgen]
+    pub fn pending_count(&self) -> usize {
+        self.state.borrow().pending.len()
+    }
+
+    // Ids of the requests still waiting for a response, longest-running first
+    #[wasm_bindgen]
+    pub fn pending_ids(&self) -> Vec<String> {
+        self.state.borrow().pending_requests(js_sys::Date::now()).into_iter().map(|request| request.id).collect()
+    }
+
+    // `[{ id, type, sql, elapsedMs }]` for each request still waiting for a response,
+    // longest-running first; compare `elapsedMs` against a threshold to spot stuck ones
+    #[wasm_bindgen]
+    pub fn pending_queries(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().pending_requests(js_sys::Date::now()))
+    }
+
+    // Allow at most `max` queries in flight at once; later ones wait in arrival order.
+    // Undefined removes the limit.
+    #[wasm_bindgen]
+    pub fn set_max_in_flight(&mut self, max: Option<usize>) {
+        self.state.borrow_mut().dispatch.set_limit(max);
+    }
+
+    // `{ inFlight, queued, queuedBackground, maxInFlight }` for the concurrency limit
+    #[wasm_bindgen]
+    pub fn dispatch_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().dispatch.stats())
+    }
+
+    // Limit queries sent per second. `options` accepts `{ queriesPerSecond, bytesPerSecond,
+    // burstSeconds, policy }`, where policy "queue" (default) waits for capacity and
+    // "reject" fails with RATE_LIMITED. Undefined removes the limit.
+    #[wasm_bindgen]
+    pub fn set_rate_limit(&mut self, #[wasm_bindgen(unchecked_optional_param_type = "RateLimitOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        let options: rate_limit::RateLimitOptions = if options.is_undefined() || options.is_null() {
+            rate_limit::RateLimitOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid rate limit options: {}", e)))?
+        };
+        self.state.borrow_mut().rate_limit.configure(options, js_sys::Date::now());
+        Ok(())
+    }
+
+    // The load the bridge reported with its latest result: `{ queued, poolWaiting,
+    // saturation }`, or null before any result or from bridges that don't report it
+    #[wasm_bindgen(unchecked_return_type = "BridgeLoad | null")]
+    pub fn bridge_load(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().backpressure.latest.map(|(load, _)| load))
+    }
+
+    // Delay queries while the bridge reports pressure. `options` accepts `{ saturation,
+    // maxDelayMs }`: above `saturation` (default 0.8) queries wait up to `maxDelayMs`
+    // (default 1000), and the full delay while sessions wait for a pooled connection.
+    // Null turns it off.
+    #[wasm_bindgen]
+    pub fn set_adaptive_throttle(&self, #[wasm_bindgen(unchecked_optional_param_type = "ThrottleOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        let throttle = if options.is_undefined() || options.is_null() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value::<backpressure::ThrottleOptions>(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid throttle options: {}", e)))?)
+        };
+        self.state.borrow_mut().backpressure.throttle = throttle;
+        Ok(())
+    }
+
+    // How queries the bridge answers with `throttled` are sent again. `options` accepts
+    // `{ maxRetries, maxWaitMs }` (defaults 3 and 30000); a query throttled more often,
+    // or told to wait longer, fails with `THROTTLED`. Null fails them all right away.
+    #[wasm_bindgen]
+    pub fn set_throttle_retry(&self, #[wasm_bindgen(unchecked_optional_param_type = "ThrottleRetry | null")] options: JsValue) -> Result<(), JsValue> {
+        let retry = if options.is_undefined() || options.is_null() {
+            throttled::ThrottleRetry { max_retries: 0, ..throttled::ThrottleRetry::default() }
+        } else {
+            serde_wasm_bindgen::from_value::<throttled::ThrottleRetry>(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid throttle retry options: {}", e)))?
+        };
+        retry.validate().map_err(|e| JsValue::from_str(&format!("Invalid throttle retry options: {}", e)))?;
+        self.state.borrow_mut().throttled.retry = retry;
+        Ok(())
+    }
+
+    // Called with `{ sql, retryAfterMs, reason, attempt, retrying }` whenever the bridge
+    // throttles a query, like `throttled` listeners. Null stops it.
+    #[wasm_bindgen]
+    pub fn on_throttled(&self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().throttled.hook = hook;
+    }
+
+    // Throttled responses received so far, retried or not
+    #[wasm_bindgen]
+    pub fn throttled_count(&self) -> f64 {
+        self.state.borrow().throttled.count as f64
+    }
+
+    // Resilience testing only: delay, drop or disconnect on received frames. `options`
+    // accepts `{ latencyMs, jitterMs, dropRate, disconnectRate, seed }`; null turns it off.
+    #[wasm_bindgen]
+    pub fn set_chaos(&self, #[wasm_bindgen(unchecked_optional_param_type = "ChaosOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        let chaos = if options.is_undefined() || options.is_null() {
+            None
+        } else {
+            let options: chaos::ChaosOptions = serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid chaos options: {}", e)))?;
+            options.validate().map_err(|e| JsValue::from_str(&format!("Invalid chaos options: {}", e)))?;
+            log_warn!("WASM chaos mode on: {:?}", options);
+            Some(chaos::Chaos::new(options, (js_sys::Math::random() * u32::MAX as f64) as u32))
+        };
+        self.state.borrow_mut().chaos = chaos;
+        Ok(())
+    }
+
+    // Open the circuit after `failure_threshold` consecutive abnormal closes (default 5);
+    // connection attempts then fail fast for `cooldown_ms` (default 30000) before a probe
+    #[wasm_bindgen]
+    pub fn set_circuit_breaker(&mut self, failure_threshold: u32, cooldown_ms: f64) {
+        self.state.borrow_mut().breaker.configure(failure_threshold, cooldown_ms);
+    }
+
+    // "closed", "open" or "half_open"
+    #[wasm_bindgen]
+    pub fn circuit_state(&self) -> String {
+        self.state.borrow().breaker.state().as_str().to_string()
+    }
+
+    // Called with `{ connected, breaker, consecutiveFailures, retryInMs }` whenever the
+    // connection opens or closes or the circuit breaker changes state
+    #[wasm_bindgen]
+    pub fn on_state_change(&mut self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().state_hook = hook;
+    }
+
+    // EventTarget-style subscription, so several components can listen without replacing
+    // each other's `on_*` hook. `kind` is one of open, close, error, notification,
+    // statechange or slowquery; listeners get `{ type, detail, timeStamp }`, with the
+    // payload the matching hook would receive (`{ code, reason }` for close, `{ message }`
+    // for error) as `detail`.
+    #[wasm_bindgen(js_name = addEventListener)]
+    pub fn add_event_listener(&self, kind: &str, listener: js_sys::Function) -> Result<(), JsValue> {
+        self.state.borrow_mut().events.add(kind, Listener(listener)).map_err(|e| JsValue::from_str(&e))
+    }
+
+    #[wasm_bindgen(js_name = removeEventListener)]
+    pub fn remove_event_listener(&self, kind: &str, listener: js_sys::Function) {
+        self.state.borrow_mut().events.remove(kind, &Listener(listener));
+    }
+
+    // Called with `{ source, code, reason, retryAfterMs }` when the bridge announces it is
+    // shutting down ("bridge") or a query fails because the database is restarting
+    // ("database", with the SQLSTATE as `code`)
+    #[wasm_bindgen]
+    pub fn on_server_restarting(&mut self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().restart.hook = hook;
+    }
+
+    // Called with `{ commandTag, objectType, schema, object }` for each DDL command the
+    // bridge's event triggers report. `options.invalidate` also drops the bridge's kept
+    // prepared statements and cached results that name the changed object. Null stops it.
+    #[wasm_bindgen]
+    pub fn on_schema_change(
+        &mut self,
+        hook: Option<js_sys::Function>,
+        #[wasm_bindgen(unchecked_optional_param_type = "SchemaChangeOptions | null")] options: JsValue,
+    ) -> Result<(), JsValue> {
+        let options: crate::schema_change::SchemaChangeOptions = if options.is_undefined() || options.is_null() {
+            Default::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid schema change options: {}", e)))?
+        };
+        let mut state = self.state.borrow_mut();
+        state.schema_change.invalidate = hook.is_some() && options.invalidate;
+        state.schema_change.hook = hook;
+        Ok(())
+    }
+
+    // Largest frame accepted from the bridge, announced when the connection opens;
+    // bigger messages arrive as chunks. Frames above it fail their request.
+    #[wasm_bindgen]
+    pub fn set_max_message_size(&mut self, bytes: usize) {
+        self.state.borrow_mut().framing.max_message_size = bytes;
+    }
+
+    // Fail results above `{ maxBytes, maxRows }` with a RESULT_TOO_LARGE error before they
+    // are decoded, rather than running the page out of memory; null lifts the budget
+    #[wasm_bindgen]
+    pub fn set_result_budget(&self, #[wasm_bindgen(unchecked_optional_param_type = "ResultBudget | null")] budget: JsValue) -> Result<(), JsValue> {
+        let budget: result_budget::ResultBudget = if budget.is_undefined() || budget.is_null() {
+            result_budget::ResultBudget::default()
+        } else {
+            serde_wasm_bindgen::from_value(budget).map_err(|e| JsValue::from_str(&format!("Invalid result budget: {}", e)))?
+        };
+        budget.validate().map_err(|e| JsValue::from_str(&format!("Invalid result budget: {}", e)))?;
+        let mut state = self.state.borrow_mut();
+        // Chunked results are given up on as soon as the pieces so far go over
+        let assembled = budget.max_bytes.map_or(DEFAULT_MAX_ASSEMBLED_SIZE, |max| max.min(DEFAULT_MAX_ASSEMBLED_SIZE));
+        state.framing.assembler = ChunkAssembler::new(assembled);
+        state.result_budget = budget;
+        Ok(())
+    }
+
+    // Messages of at least this many bytes are deflated when the bridge supports it
+    #[wasm_bindgen]
+    pub fn set_compression_threshold(&mut self, bytes: usize) {
+        self.state.borrow_mut().framing.compression_threshold = bytes;
+    }
+
+    // Parse frames of at least `bytes` (1 MiB by default) `batch_rows` rows at a time,
+    // yielding to the event loop between batches; undefined parses every frame at once
+    #[wasm_bindgen]
+    pub fn set_incremental_parsing(&mut self, bytes: Option<usize>, batch_rows: Option<usize>) {
+        let mut state = self.state.borrow_mut();
+        state.incremental.threshold = bytes;
+        if let Some(batch_rows) = batch_rows {
+            state.incremental.batch_rows = batch_rows.max(1);
+        }
+    }
+
+    // Negotiated `{version, capabilities}`, or null until the handshake completes.
+    // Version 0 means the bridge predates negotiation.
+    #[wasm_bindgen(unchecked_return_type = "Negotiated | null")]
+    pub fn protocol_info(&self) -> Result<JsValue, JsValue> {
+        match self.state.borrow().protocol.negotiated {
+            Some(negotiated) => to_js(&negotiated),
+            None => Ok(JsValue::NULL),
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn send_ping(&mut self, message: &str) -> Result<String, JsValue> {
+        if !self.is_connected() {
+            return Err(BridgeError::connection("WebSocket not connected").into());
+        }
+
+        let message_id = self.state.borrow_mut().send_ping(message)?;
+        log_debug!("WASM sent ping message: {}", message);
+        Ok(message_id)
+    }
+
+    // `options` accepts `{ settings }`, applied with SET LOCAL as in `query()`
+    #[wasm_bindgen]
+    pub fn send_query(
+        &mut self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "SendOptions | null")] options: JsValue,
+    ) -> Result<String, JsValue> {
+        // Parse parameters if provided
+        let params = if let Some(params_str) = params_json {
+            match serde_json::from_str::<Vec<serde_json::Value>>(&params_str) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    log_warn!("Failed to parse query parameters: {}", e);
+                    return Err(JsValue::from_str(&format!("Invalid parameters JSON: {}", e)));
+                }
+            }
+        } else {
+            None
+        };
+
+        let options: SendOptions = if options.is_undefined() || options.is_null() {
+            SendOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid query options: {}", e)))?
+        };
+        if !options.settings.is_empty() && !self.should_queue(sql) {
+            return self.dispatch_with_settings(sql, params, options.settings);
+        }
+        self.dispatch_query(sql, params)
+    }
+
+    // Send a query with parameters given as a JS array instead of a JSON string
+    #[wasm_bindgen]
+    pub fn send_query_params(&mut self, sql: &str, params: js_sys::Array) -> Result<String, JsValue> {
+        let params = null_policy::params_from_js(&params, self.strict_params)?;
+        self.dispatch_query(sql, Some(params))
+    }
+
+    #[wasm_bindgen]
+    pub fn set_null_policy(&mut self, policy: &str) -> Result<(), JsValue> {
+        self.null_policy = NullPolicy::parse(policy).map_err(|e| JsValue::from_str(&e))?;
+        log_debug!("WASM null policy set to '{}'", policy);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn null_policy(&self) -> String {
+        self.null_policy.as_str().to_string()
+    }
+
+    // Make equal short strings in `query()` rows one shared JS string rather than a copy per
+    // cell. `options` accepts `{ maxLength, columns, maxDistinct }` (defaults 64, every
+    // column, 10000). Setting it again starts from an empty pool; null turns it off.
+    #[wasm_bindgen]
+    pub fn set_string_interning(&self, #[wasm_bindgen(unchecked_optional_param_type = "InternOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        let options = if options.is_undefined() || options.is_null() {
+            None
+        } else {
+            let options: intern::InternOptions =
+                serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid string interning options: {}", e)))?;
+            options.validate().map_err(|e| JsValue::from_str(&format!("Invalid string interning options: {}", e)))?;
+            Some(options)
+        };
+        self.state.borrow_mut().interning.configure(options);
+        Ok(())
+    }
+
+    // Strings held and cells served from them since interning was set, or null when it's off
+    #[wasm_bindgen(unchecked_return_type = "InternStats | null")]
+    pub fn string_interning_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().interning.pool.as_ref().map(|pool| pool.stats()))
+    }
+
+    // When enabled, binding `undefined` as a parameter is an error instead of NULL
+    #[wasm_bindgen]
+    pub fn set_strict_params(&mut self, strict: bool) {
+        self.strict_params = strict;
+    }
+
+    // Drop a response whose id matches one of the last `window` responses (256 by
+    // default), e.g. one a resumed session delivers again; 0 turns this off
+    #[wasm_bindgen]
+    pub fn set_duplicate_window(&self, window: usize) {
+        self.state.borrow_mut().duplicates.set_window(window);
+    }
+
+    // Name messages with `generator(kind)` instead of `wasm_<kind>_<uuid>`; pass undefined
+    // to go back to UUIDs. Ids must be unique across tabs and page loads: a write query's
+    // id is also its idempotency key on the bridge.
+    #[wasm_bindgen]
+    pub fn set_id_generator(&self, generator: Option<js_sys::Function>) {
+        self.state.borrow_mut().ids = match generator {
+            Some(generator) => ids::IdGenerator::Custom(generator),
+            None => ids::IdGenerator::Uuid,
+        };
+    }
+
+    // Check every inbound message against the schema of its type: a known `type`, and the
+    // fields that type requires. Malformed messages never reach handlers; they fail the
+    // request they answer with PROTOCOL_VIOLATION and fire a `protocolviolation` event.
+    #[wasm_bindgen]
+    pub fn set_strict_protocol(&self, enabled: bool) {
+        self.state.borrow_mut().strict_protocol = enabled;
+    }
+
+    // Parse a raw server message into a JS object, applying the null policy to result rows
+    #[wasm_bindgen(unchecked_return_type = "WebSocketMessage")]
+    pub fn decode_message(&self, message: &str) -> Result<JsValue, JsValue> {
+        let mut message: WebSocketMessage = serde_json::from_str(message)
+            .map_err(|e| JsValue::from_str(&format!("Invalid message JSON: {}", e)))?;
+
+        if message.message_type == "result" {
+            if let Some(serde_json::Value::Array(rows)) = message.payload.get_mut("rows") {
+                self.null_policy.apply_to_rows(rows);
+            }
+        }
+
+        self.null_policy.to_js(&message)
+    }
+
+    fn dispatch_query(&mut self, sql: &str, params: Option<Vec<serde_json::Value>>) -> Result<String, JsValue> {
+        // Writes sent during a replay queue behind it to keep their order. The entry goes
+        // out under the returned id, so its result reaches the message handler like any
+        // other. Storing it can't be waited for here; `query` resolves once it is stored.
+        let replaying = {
+            let state = self.state.borrow();
+            state.outbox.enabled && state.outbox.replaying && outbox::is_write(sql)
+        };
+        if self.should_queue(sql) || replaying {
+            let message_id = self.state.borrow_mut().next_message_id("query");
+            let entry = outbox::queue(&self.state, sql, params, None, Some(message_id.clone()), false)?;
+            outbox::persist_later(&self.state, entry);
+            return Ok(message_id);
+        }
+        if !self.is_connected() {
+            return Err(BridgeError::connection("WebSocket not connected").into());
+        }
+
+        let mut state = self.state.borrow_mut();
+        state.check_statements(sql, false)?;
+        let message_id = state.next_message_id("query");
+
+        let query_message = WebSocketMessage {
+            message_type: "query".to_string(),
+            payload: state.query_payload(sql, params, None)?,
+            id: Some(message_id.clone()),
+        };
+
+        state.send_message(&query_message)?;
+        drop(state);
+        payload_size::report(&self.state);
+        log_debug!("WASM sent query: {}", sql);
+        Ok(message_id)
+    }
+
+    // Send a query wrapped in its own transaction with `settings` SET LOCAL. Like
+    // `send_query`, the result goes to the message handler under the returned id.
+    fn dispatch_with_settings(&mut self, sql: &str, params: Option<Vec<serde_json::Value>>, settings: local_settings::Settings) -> Result<String, JsValue> {
+        if !self.is_connected() {
+            return Err(BridgeError::connection("WebSocket not connected").into());
+        }
+        local_settings::set_local_statements(&settings).map_err(|e| JsValue::from_str(&format!("Invalid settings: {}", e)))?;
+        let message_id = self.state.borrow_mut().next_message_id("query");
+        let options = QueryOptions { settings, query_id: Some(message_id.clone()), ..QueryOptions::default() };
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = connection::execute_query_with(&state, &sql, params, options).await {
+                log_warn!("WASM query with settings failed: {:?}", e);
+            }
+        });
+        Ok(message_id)
+    }
+
+    // Execute a query and resolve with its decoded result payload.
+    // `options` accepts `{ retry, priority, format, cache }`: `retry: false` skips the retry
+    // policy, `priority: "background"` lets interactive queries go first under the concurrency
+    // limit, `format: "columnar"` resolves with typed column arrays instead of row objects, and
+    // `cache: { ttl, maxEntries }` answers repeats of a read from memory for `ttl` ms.
+    // SQL with more than one statement is rejected unless `multiStatement` is true.
+    // `statementTimeoutMs` runs this query under its own statement_timeout, and
+    // `settings: { work_mem: "256MB" }` SETs LOCAL configuration parameters for it alone.
+    // `idempotent: true` re-sends the query after a reconnect instead of failing it.
+    // `inLists: "any" | "expand"` rewrites `IN ($n)` when `$n` is bound to an array.
+    #[wasm_bindgen]
+    pub fn query(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "QueryOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let options: QueryOptions = if options.is_undefined() || options.is_null() {
+            QueryOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid query options: {}", e)))?
+        };
+        Ok(self.query_promise_with(sql.to_string(), params, options))
+    }
+
+    // `query()` with parameters given as a JS array. Uint8Array and ArrayBuffer values go
+    // to the bridge as bytea, Int32Array, BigInt64Array, Float32Array and Float64Array as
+    // int4[], int8[], float4[] and float8[], Date as timestamptz and BigInt as int8, all in
+    // binary instead of JSON text; other values are sent as JSON. Rejects with
+    // BINARY_UNSUPPORTED when the bridge didn't negotiate binary parameters.
+    #[wasm_bindgen]
+    pub fn query_binary(
+        &self,
+        sql: &str,
+        params: js_sys::Array,
+        #[wasm_bindgen(unchecked_optional_param_type = "QueryOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let (params, binary) = binary_params::params_from_js(&params, self.strict_params)?;
+        let mut options: QueryOptions = if options.is_undefined() || options.is_null() {
+            QueryOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid query options: {}", e)))?
+        };
+        options.binary_params = binary;
+        Ok(self.query_promise_with(sql.to_string(), Some(params), options))
+    }
+
+    // Retry reads that fail with a transient SQLSTATE. `policy` accepts
+    // `{ maxAttempts, baseDelayMs, maxDelayMs, retryableSqlStates }`; maxAttempts 1 disables it.
+    #[wasm_bindgen]
+    pub fn set_retry_policy(&mut self, #[wasm_bindgen(unchecked_optional_param_type = "RetryPolicy | null")] policy: JsValue) -> Result<(), JsValue> {
+        let policy: retry::RetryPolicy = if policy.is_undefined() || policy.is_null() {
+            retry::RetryPolicy::default()
+        } else {
+            serde_wasm_bindgen::from_value(policy)
+                .map_err(|e| JsValue::from_str(&format!("Invalid retry policy: {}", e)))?
+        };
+        self.state.borrow_mut().retry_policy = policy;
+        Ok(())
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "RetryPolicy")]
+    pub fn retry_policy(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().retry_policy)
+    }
+
+    // Execute a statement produced by a `QueryBuilder`
+    #[wasm_bindgen(unchecked_return_type = "Promise<QueryResult>")]
+    pub fn execute(&self, builder: &QueryBuilder) -> Result<Promise, JsValue> {
+        let built = builder.to_query().map_err(|e| JsValue::from_str(&e))?;
+        Ok(self.query_promise(built.sql, Some(built.params)))
+    }
+
+    // Run a write whose expected effect `patch` (`{ table, key, upsert, delete }`) is
+    // applied straight away to cached and live query results reading `table`. Rows the
+    // statement RETURNs replace the optimistic ones; if it fails the patch is undone.
+    #[wasm_bindgen]
+    pub fn mutate(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_param_type = "OptimisticPatch")] patch: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let patch: optimistic::OptimisticPatch = serde_wasm_bindgen::from_value(patch)
+            .map_err(|e| JsValue::from_str(&format!("Invalid optimistic patch: {}", e)))?;
+        patch.validate().map_err(|e| JsValue::from_str(&format!("Invalid optimistic patch: {}", e)))?;
+
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        let null_policy = self.null_policy;
+        Ok(future_to_promise(async move {
+            let mut result = optimistic::mutate(state, sql, params, patch).await?;
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows") {
+                null_policy.apply_to_rows(rows);
+            }
+            null_policy.to_js(&result)
+        }))
+    }
+
+    // Insert rows (arrays of values in column order) using chunked multi-row INSERTs;
+    // resolves with `{ statements, rowsInserted }`
+    #[wasm_bindgen]
+    pub fn insert_many(&self, table: &str, columns: Vec<String>, rows: JsValue) -> Result<Promise, JsValue> {
+        let rows: Vec<Vec<serde_json::Value>> = serde_wasm_bindgen::from_value(rows)
+            .map_err(|e| JsValue::from_str(&format!("Rows must be an array of value arrays: {}", e)))?;
+        let statements = bulk::build_insert_many(table, &columns, &rows, bulk::DEFAULT_ROWS_PER_STATEMENT)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        let state = self.state.clone();
+        let column_count = columns.len();
+        Ok(future_to_promise(async move {
+            let summary = bulk::insert_many(state, statements, column_count).await?;
+            to_js(&summary)
+        }))
+    }
+
+    // Update rows (objects of column values) matched on `key_columns`, in one statement per
+    // chunk rather than one per row. Resolves with `{ statements, rowsAffected, rows }`, where
+    // `rows` has `{ index, matched, error? }` for each input row.
+    #[wasm_bindgen]
+    pub fn update_many(&self, table: &str, rows: JsValue, key_columns: Vec<String>) -> Result<Promise, JsValue> {
+        let rows: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_wasm_bindgen::from_value(rows).map_err(|e| JsValue::from_str(&format!("Rows must be an array of objects: {}", e)))?;
+        let statements = bulk::build_update_many(table, &rows, &key_columns, bulk::DEFAULT_ROWS_PER_STATEMENT).map_err(|e| JsValue::from_str(&e))?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move { to_js(&bulk::write_keyed(state, statements).await) }))
+    }
+
+    // Delete the rows matching each key object (`{ id: 1 }`, or several columns for a
+    // composite key); resolves like `update_many`
+    #[wasm_bindgen]
+    pub fn delete_many(&self, table: &str, keys: JsValue) -> Result<Promise, JsValue> {
+        let keys: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_wasm_bindgen::from_value(keys).map_err(|e| JsValue::from_str(&format!("Keys must be an array of objects: {}", e)))?;
+        let statements = bulk::build_delete_many(table, &keys, bulk::DEFAULT_ROWS_PER_STATEMENT).map_err(|e| JsValue::from_str(&e))?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move { to_js(&bulk::write_keyed(state, statements).await) }))
+    }
+
+    // Run `sql` against a temporary table holding `rows`, e.g. to join a long client-side
+    // ID list. The table (`{ name, columns: [{ name, type }] }`) is created, loaded and
+    // dropped inside one transaction; resolves like `query()`.
+    #[wasm_bindgen(unchecked_return_type = "Promise<QueryResult>")]
+    pub fn with_temp_table(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "TempTableSpec")] spec: JsValue,
+        rows: JsValue,
+        sql: &str,
+        params_json: Option<String>,
+    ) -> Result<Promise, JsValue> {
+        let spec: temp_table::TempTableSpec =
+            serde_wasm_bindgen::from_value(spec).map_err(|e| JsValue::from_str(&format!("Invalid temp table spec: {}", e)))?;
+        let rows: Vec<serde_json::Value> =
+            serde_wasm_bindgen::from_value(rows).map_err(|e| JsValue::from_str(&format!("Rows must be an array: {}", e)))?;
+        let params = parse_params_json(params_json)?;
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        let null_policy = self.null_policy;
+        Ok(future_to_promise(async move {
+            let mut result = temp_table::run(&state, spec, rows, &sql, params).await?;
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows") {
+                null_policy.apply_to_rows(rows);
+            }
+            null_policy.to_js(&result)
+        }))
+    }
+
+    // Execute a statement produced by `sql_template`
+    #[wasm_bindgen]
+    pub fn execute_template(&self, template: &SqlTemplate) -> Promise {
+        let query = template.query().clone();
+        self.query_promise(query.sql, Some(query.params))
+    }
+
+    // Run EXPLAIN (FORMAT JSON) and resolve with `{ plan, summary }`.
+    // `options` accepts `{ analyze, buffers }`.
+    #[wasm_bindgen]
+    pub fn explain(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "ExplainOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let options: explain::ExplainOptions = if options.is_undefined() || options.is_null() {
+            explain::ExplainOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid explain options: {}", e)))?
+        };
+
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let result = explain::explain(&state, &sql, params, options).await?;
+            to_js(&result)
+        }))
+    }
+
+    // Fetch one page of a query and resolve with `{ rows, hasNext, nextCursor }`.
+    // `options` accepts `{ pageSize, after, orderBy, descending }`; pass the previous
+    // page's `nextCursor` as `after` to continue.
+    #[wasm_bindgen]
+    pub fn paginate(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "PageOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?.unwrap_or_default();
+        let options: pagination::PageOptions = if options.is_undefined() || options.is_null() {
+            pagination::PageOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid pagination options: {}", e)))?
+        };
+
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let page = pagination::paginate(&state, &sql, params, options).await?;
+            to_js(&page)
+        }))
+    }
+
+    // Run `callback(tx)` inside a transaction: BEGIN, then COMMIT once the promise it
+    // returns resolves or ROLLBACK if it rejects. Queries made through `tx.query()` run in
+    // the transaction; the client's other queries wait until it has finished. `options`
+    // accepts `{ isolation, readOnly, deferrable }`.
+    #[wasm_bindgen]
+    pub fn with_transaction(
+        &self,
+        callback: js_sys::Function,
+        #[wasm_bindgen(unchecked_optional_param_type = "TransactionOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let options: transaction::TransactionOptions = if options.is_undefined() || options.is_null() {
+            transaction::TransactionOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid transaction options: {}", e)))?
+        };
+        Ok(future_to_promise(transaction::with_transaction(self.state.clone(), callback, options, self.null_policy)))
+    }
+
+    // "idle", "in_transaction" while a `with_transaction` is open, or "failed" once one of
+    // its statements has failed and only ROLLBACK remains
+    #[wasm_bindgen(unchecked_return_type = "\"idle\" | \"in_transaction\" | \"failed\"")]
+    pub fn transaction_status(&self) -> String {
+        self.state.borrow().transactions.status().as_str().to_string()
+    }
+
+    // The next page of a query run with `pageSize`, given the `continuation` token of the
+    // previous page; `page_size` defaults to the query's. Resolves like `query()`.
+    #[wasm_bindgen(unchecked_return_type = "Promise<QueryResult>")]
+    pub fn fetch_more(&self, token: String, page_size: Option<u32>) -> Promise {
+        let state = self.state.clone();
+        let null_policy = self.null_policy;
+        future_to_promise(async move {
+            let mut result = continuation::fetch_more(&state, &token, page_size).await?;
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows") {
+                null_policy.apply_to_rows(rows);
+            }
+            null_policy.to_js(&result)
+        })
+    }
+
+    // Stop a paged query before its last page, freeing the bridge's cursor; resolves with
+    // false if it had already finished
+    #[wasm_bindgen(unchecked_return_type = "Promise<boolean>")]
+    pub fn close_continuation(&self, token: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move { Ok(JsValue::from_bool(continuation::close(&state, &token).await?)) })
+    }
+
+    // Open a server-side cursor over `sql` and resolve with a handle whose `fetch_next()`
+    // returns `{ rows, done }`, `batch_size` rows at a time (100 by default). Call
+    // `close()` when abandoning it early; it closes itself once exhausted.
+    #[wasm_bindgen]
+    pub fn open_cursor(&self, sql: &str, params_json: Option<String>, batch_size: Option<u32>) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        let null_policy = self.null_policy;
+        Ok(future_to_promise(async move {
+            let cursor = cursor::open(state, &sql, params, batch_size, null_policy).await?;
+            Ok(JsValue::from(cursor))
+        }))
+    }
+
+    // Async iterable over the rows of `sql`, `batch_size` at a time (100 by default):
+    // `for await (const rows of client.stream(sql)) { ... }`. Runs on a server-side
+    // cursor declared on the first iteration and closed when the loop ends.
+    #[wasm_bindgen(unchecked_return_type = "AsyncIterable<any[]>")]
+    pub fn stream(&self, sql: &str, params_json: Option<String>, batch_size: Option<u32>) -> Result<JsValue, JsValue> {
+        let params = parse_params_json(params_json)?;
+        cursor::stream(self.state.clone(), sql, params, batch_size, self.null_policy)
+    }
+
+    // Write the rows of `sql` into a WritableStream, such as a File System Access file
+    // handle, as JSON lines or CSV, one cursor batch at a time. `options` accepts
+    // `{ format, batchSize, csv }`. Resolves with `{ rows, batches, bytes }` once the
+    // stream is closed; a failure aborts the stream.
+    #[wasm_bindgen]
+    pub fn stream_to(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        stream: web_sys::WritableStream,
+        #[wasm_bindgen(unchecked_optional_param_type = "ExportOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let options: export::ExportOptions = if options.is_undefined() || options.is_null() {
+            export::ExportOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid export options: {}", e)))?
+        };
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let summary = export::stream_to(state, sql, params, stream, options).await?;
+            to_js(&summary)
+        }))
+    }
+
+    // `stream_to` into file `name` in the Origin Private File System, so a Worker can
+    // export more rows than fit in memory. Resolves with `{ rows, batches, bytes, file }`;
+    // `opfs_file(file)` then gives the `File` to offer as a download.
+    #[wasm_bindgen]
+    pub fn export_to_opfs(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        name: String,
+        #[wasm_bindgen(unchecked_optional_param_type = "ExportOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        opfs::check_name(&name).map_err(|e| JsValue::from_str(&e))?;
+        let options: export::ExportOptions = if options.is_undefined() || options.is_null() {
+            export::ExportOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid export options: {}", e)))?
+        };
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let export = opfs::export(state, sql, params, name, options).await?;
+            to_js(&export)
+        }))
+    }
+
+    // Wait for session-level advisory lock `key`, a safe integer, BigInt or name, and hold
+    // it until `advisory_unlock(key)` or the bridge session ends
+    #[wasm_bindgen]
+    pub fn advisory_lock(&self, key: JsValue) -> Result<Promise, JsValue> {
+        let key = advisory::LockKey::from_js(&key)?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            advisory::lock(&state, key).await?;
+            Ok(JsValue::UNDEFINED)
+        }))
+    }
+
+    // Take advisory lock `key` if it is free; resolves with whether it was taken
+    #[wasm_bindgen(unchecked_return_type = "Promise<boolean>")]
+    pub fn try_advisory_lock(&self, key: JsValue) -> Result<Promise, JsValue> {
+        let key = advisory::LockKey::from_js(&key)?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move { Ok(JsValue::from_bool(advisory::try_lock(&state, key).await?)) }))
+    }
+
+    // Release one hold on session-level lock `key`; resolves with false if it wasn't held
+    #[wasm_bindgen(unchecked_return_type = "Promise<boolean>")]
+    pub fn advisory_unlock(&self, key: JsValue) -> Result<Promise, JsValue> {
+        let key = advisory::LockKey::from_js(&key)?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move { Ok(JsValue::from_bool(advisory::unlock(&state, key).await?)) }))
+    }
+
+    // Keys of the session-level advisory locks the client holds
+    #[wasm_bindgen]
+    pub fn held_advisory_locks(&self) -> Vec<String> {
+        self.state.borrow().advisory_locks.keys()
+    }
+
+    // Read large object `oid` into a Blob, `chunk_size` bytes per query (256 KiB by default)
+    #[wasm_bindgen]
+    pub fn lo_read(&self, oid: u32, chunk_size: Option<u32>) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move { Ok(large_object::read_blob(&state, oid, chunk_size).await?.into()) })
+    }
+
+    // Large object `oid` as a ReadableStream of Uint8Array chunks, fetched as they are read
+    #[wasm_bindgen]
+    pub fn lo_stream(&self, oid: u32, chunk_size: Option<u32>) -> Result<web_sys::ReadableStream, JsValue> {
+        large_object::read_stream(&self.state, oid, chunk_size)
+    }
+
+    // Write a Uint8Array, ArrayBuffer or Blob into large object `oid` from its start, or
+    // into a new one when `oid` is undefined; resolves with the oid
+    #[wasm_bindgen]
+    pub fn lo_write(&self, oid: Option<u32>, data: JsValue, chunk_size: Option<u32>) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let bytes = large_object::bytes_from(data).await?;
+            let oid = large_object::write(&state, oid, &bytes, chunk_size).await?;
+            Ok(JsValue::from_f64(oid as f64))
+        })
+    }
+
+    // Record results whose executionTime is at least `threshold_ms`; pass undefined to disable
+    #[wasm_bindgen]
+    pub fn set_slow_query_threshold(&mut self, threshold_ms: Option<f64>) {
+        self.state.borrow_mut().slow_queries.set_threshold(threshold_ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_slow_query_capacity(&mut self, capacity: usize) {
+        self.state.borrow_mut().slow_queries.set_capacity(capacity);
+    }
+
+    #[wasm_bindgen(unchecked_return_type = "SlowQuery[]")]
+    pub fn get_slow_queries(&self) -> Result<JsValue, JsValue> {
+        let entries = self.state.borrow().slow_queries.entries();
+        to_js(&entries)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_slow_queries(&mut self) {
+        self.state.borrow_mut().slow_queries.clear();
+    }
+
+    // Record every message sent and received into a ring buffer of `capacity`
+    // entries (default 500); turning recording off keeps what was captured
+    #[wasm_bindgen]
+    pub fn set_traffic_recording(&mut self, enabled: bool, capacity: Option<usize>) {
+        let mut state = self.state.borrow_mut();
+        if let Some(capacity) = capacity {
+            state.recorder.set_capacity(capacity);
+        }
+        state.recorder.set_enabled(enabled);
+    }
+
+    // Whether recorded messages hide bound parameter values (on by default)
+    #[wasm_bindgen]
+    pub fn set_traffic_redaction(&mut self, enabled: bool) {
+        self.state.borrow_mut().recorder.set_redaction(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn get_traffic(&self) -> Result<JsValue, JsValue> {
+        let entries = self.state.borrow().recorder.entries();
+        to_js(&entries)
+    }
+
+    // The recorded session as a JSON document, suitable for attaching to a bug report
+    #[wasm_bindgen]
+    pub fn export_traffic(&self) -> String {
+        self.state.borrow().recorder.export()
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_traffic(&mut self) {
+        self.state.borrow_mut().recorder.clear();
+    }
+
+    // One JSON document to attach to a support ticket: environment, the config with
+    // secrets masked, connection status and stats, metrics, the slow query log and the
+    // recorded traffic with parameter values and tokens hidden. Traffic is only there
+    // while `set_traffic_recording` is or was on.
+    #[wasm_bindgen]
+    pub fn export_diagnostics(&self) -> String {
+        let bundle = diagnostics::bundle(&self.state.borrow());
+        serde_json::to_string_pretty(&bundle).unwrap_or_default()
+    }
+
+    // "record" captures each query and its response, starting a fresh recording;
+    // "replay" answers queries from the loaded fixture; "off" talks to the bridge
+    #[wasm_bindgen]
+    pub fn set_fixture_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        let mode = replay::TapeMode::parse(mode).map_err(|e| JsValue::from_str(&e))?;
+        self.state.borrow_mut().query_tape.set_mode(mode);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn fixture_mode(&self) -> String {
+        self.state.borrow().query_tape.mode().as_str().to_string()
+    }
+
+    // The recorded query/response pairs as a fixture file
+    #[wasm_bindgen]
+    pub fn export_query_fixtures(&self) -> String {
+        self.state.borrow().query_tape.export()
+    }
+
+    // Load a fixture file and switch to replay; returns the number of recorded queries
+    #[wasm_bindgen]
+    pub fn load_query_fixtures(&mut self, fixture_json: &str) -> Result<usize, JsValue> {
+        self.state.borrow_mut().query_tape.load(fixture_json).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Called with each slow query entry as it is recorded
+    #[wasm_bindgen]
+    pub fn on_slow_query(&mut self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().slow_query_hook = hook;
+    }
+
+    // Keep an append-only trail of executed statements: hash, tag, timestamp, duration and
+    // outcome. `options` accepts `{ tag, includeSql, maxEntries }`; null turns it off.
+    #[wasm_bindgen]
+    pub fn enable_audit_log(&self, #[wasm_bindgen(unchecked_optional_param_type = "AuditOptions")] options: JsValue) -> Result<(), JsValue> {
+        if options.is_null() {
+            self.state.borrow_mut().audit.disable();
+            return Ok(());
+        }
+        let options: audit::AuditOptions = if options.is_undefined() {
+            audit::AuditOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid audit options: {}", e)))?
+        };
+        self.state.borrow_mut().audit.enable(options);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn audit_log(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().audit.entries())
+    }
+
+    // The retained audit entries as JSON Lines, oldest first
+    #[wasm_bindgen]
+    pub fn export_audit_log(&self) -> String {
+        self.state.borrow().audit.export()
+    }
+
+    // Called with each audit entry as it is appended, e.g. to ship it to a server
+    #[wasm_bindgen]
+    pub fn on_audit_entry(&mut self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().audit_hook = hook;
+    }
+
+    // Called as `fn(queryId, {rows, elapsedMs})` while a long query is still reading
+    // rows; the bridge reports roughly every half second
+    #[wasm_bindgen]
+    pub fn on_progress(&mut self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().progress_hook = hook;
+    }
+
+    // Register `fn(phase, message)` run in order on outgoing ("request") and
+    // incoming ("response") messages; it may return a replacement message
+    #[wasm_bindgen]
+    pub fn use_interceptor(&mut self, interceptor: js_sys::Function) {
+        self.state.borrow_mut().interceptors.add(interceptor);
+    }
+
+    #[wasm_bindgen]
+    pub fn remove_interceptor(&mut self, interceptor: &js_sys::Function) -> bool {
+        self.state.borrow_mut().interceptors.remove(interceptor)
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_interceptors(&mut self) {
+        self.state.borrow_mut().interceptors.clear();
+    }
+
+    // W3C traceparent attached to every subsequent query; pass undefined to clear
+    #[wasm_bindgen]
+    pub fn set_traceparent(&mut self, traceparent: Option<String>) -> Result<(), JsValue> {
+        if let Some(value) = &traceparent {
+            tracing::validate_traceparent(value).map_err(|e| JsValue::from_str(&e))?;
+        }
+        self.state.borrow_mut().tracing.traceparent = traceparent;
+        Ok(())
+    }
+
+    // Also append the traceparent to the SQL as a sqlcommenter comment
+    #[wasm_bindgen]
+    pub fn set_sqlcommenter(&mut self, enabled: bool) {
+        self.state.borrow_mut().tracing.sqlcommenter = enabled;
+    }
+
+    // Counters, error classes, latency percentiles/histogram and byte totals
+    #[wasm_bindgen(unchecked_return_type = "MetricsSnapshot")]
+    pub fn metrics(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().metrics.snapshot())
+    }
+
+    // Transport counters for status pages: bytes and frames each way, messages by type,
+    // reconnects, uptime of the current connection and the latest `send_ping` round trip
+    #[wasm_bindgen(unchecked_return_type = "ConnectionStatsSnapshot")]
+    pub fn connection_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().connection_stats.snapshot(js_sys::Date::now()))
+    }
+
+    // Sizes of outbound messages and of bound parameter sets: count, total, max and
+    // average, overall and per message type
+    #[wasm_bindgen(unchecked_return_type = "PayloadStats")]
+    pub fn payload_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().payload_sizes.snapshot())
+    }
+
+    // Fire `largepayload` for any message over `bytes` once serialized (1 MiB by
+    // default); pass undefined to stop warning
+    #[wasm_bindgen]
+    pub fn set_payload_warning_threshold(&self, bytes: Option<usize>) {
+        self.state.borrow_mut().payload_sizes.set_warn_bytes(bytes);
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_payload_stats(&self) {
+        self.state.borrow_mut().payload_sizes.reset();
+    }
+
+    // Client metrics in the Prometheus/OpenMetrics text exposition format
+    #[wasm_bindgen]
+    pub fn metrics_prometheus(&self) -> String {
+        prometheus::render_client_metrics(&self.state.borrow().metrics.snapshot())
+    }
+
+    // Ask the bridge for its pool statistics; resolves with the exposition text
+    // for the pool followed by the client metrics
+    #[wasm_bindgen]
+    pub fn server_metrics_prometheus(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let response = connection::request(&state, "pool_stats", "pool_stats", serde_json::Value::Null).await?;
+            let stats: prometheus::PoolStats = serde_json::from_value(response.payload)
+                .map_err(|e| JsValue::from_str(&format!("Unexpected pool_stats response: {}", e)))?;
+            let mut text = prometheus::render_pool_stats(&stats);
+            text.push_str(&prometheus::render_client_metrics(&state.borrow().metrics.snapshot()));
+            Ok(JsValue::from_str(&text))
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_metrics(&mut self) {
+        self.state.borrow_mut().metrics.reset();
+    }
+
+    // Calls, errors, rows and timings per statement shape, most total time first
+    #[wasm_bindgen(unchecked_return_type = "StatementStats[]")]
+    pub fn query_stats(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().query_stats.snapshot())
+    }
+
+    #[wasm_bindgen]
+    pub fn reset_query_stats(&mut self) {
+        self.state.borrow_mut().query_stats.reset();
+    }
+
+    // `{ mode, statementCache, maxStatements, minIdle, maxIdle }` the bridge granted for the `pool` config
+    // field on this connection; undefined when none was asked for or the bridge ignored it
+    #[wasm_bindgen]
+    pub fn pool_settings(&self) -> Result<JsValue, JsValue> {
+        match self.state.borrow().protocol.pool {
+            Some(pool) => to_js(&pool),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    // Keep one backend for this session until `unpin_session()`, even when the `pool` mode
+    // hands it back between queries. Needed for temp tables, session-level advisory locks
+    // and `SET` that should outlast a query. Resolves with `{ pinned, backendPid }`.
+    #[wasm_bindgen(unchecked_return_type = "Promise<PinStatus>")]
+    pub fn pin_session(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let response = connection::request(&state, "pin", message_type::PIN, serde_json::Value::Null).await?;
+            state.borrow_mut().pinned = true;
+            to_js(&response.payload)
+        })
+    }
+
+    // Let the session's pool mode decide again; a pooled session gives its backend back
+    // once no transaction or cursor needs it
+    #[wasm_bindgen(unchecked_return_type = "Promise<PinStatus>")]
+    pub fn unpin_session(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            state.borrow_mut().pinned = false;
+            let response = connection::request(&state, "unpin", message_type::UNPIN, serde_json::Value::Null).await?;
+            to_js(&response.payload)
+        })
+    }
+
+    // Bridge-wide stats for operators: connected clients with their backend PIDs, pool
+    // utilization and queries per second. `token` is the bridge's BRIDGE_ADMIN_TOKEN.
+    #[wasm_bindgen(unchecked_return_type = "Promise<AdminStats>")]
+    pub fn admin_stats(&self, token: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let payload = serde_json::to_value(AdminPayload { token }).unwrap_or_default();
+            let response = connection::request(&state, "admin", message_type::ADMIN_STATS, payload).await?;
+            to_js(&response.payload)
+        })
+    }
+
+    // Backends logged in as the bridge's role from pg_stat_activity, longest-running query
+    // first: `[{ pid, state, query, waitEventType, waitEvent, durationMs, ... }]`.
+    // `options` accepts `{ includeIdle, includeSelf }`.
+    #[wasm_bindgen]
+    pub fn server_activity(&self, #[wasm_bindgen(unchecked_optional_param_type = "ActivityOptions | null")] options: JsValue) -> Result<Promise, JsValue> {
+        let options: activity::ActivityOptions = if options.is_undefined() || options.is_null() {
+            activity::ActivityOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid activity options: {}", e)))?
+        };
+
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let activity = activity::server_activity(&state, options).await?;
+            to_js(&activity)
+        }))
+    }
+
+    // Prepared statements the bridge keeps for this session's repeated SQL; resolves with
+    // `[{ handle, sql, uses }]`, least recently used first
+    #[wasm_bindgen]
+    pub fn prepared_statements(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let response = connection::request(&state, "prepared", message_type::PREPARED_STATEMENTS, serde_json::Value::Null).await?;
+            to_js(&response.payload)
+        })
+    }
+
+    // The parameter types and result columns of `sql`, found by preparing it on the bridge
+    // without running it: `{ params: [{ typeOid, typeName }], columns: [{ name, typeOid,
+    // typeName }] }`. `types` hints parameter types as in `query()`'s `types` option.
+    // Column names follow the client's `columnCase`.
+    #[wasm_bindgen(unchecked_return_type = "Promise<StatementDescription>")]
+    pub fn describe(&self, sql: String, #[wasm_bindgen(unchecked_optional_param_type = "(string | null)[] | null")] types: JsValue) -> Result<Promise, JsValue> {
+        let types: Option<Vec<Option<String>>> = if types.is_undefined() || types.is_null() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(types).map_err(|e| JsValue::from_str(&format!("Invalid parameter types: {}", e)))?)
+        };
+        let sql = match self.placeholders {
+            placeholders::PlaceholderStyle::Dollar => sql,
+            placeholders::PlaceholderStyle::Question => placeholders::translate(&sql).map_err(|reason| JsValue::from_str(&reason))?,
+        };
+        let payload = serde_json::to_value(DescribePayload { sql, types, keep: false })
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize describe: {}", e)))?;
+        let state = self.state.clone();
+        let camel = self.column_case == column_case::ColumnCase::Camel;
+        Ok(future_to_promise(async move {
+            let response = connection::request(&state, "describe", message_type::DESCRIBE, payload).await?;
+            let mut description: StatementDescription = serde_json::from_value(response.payload)
+                .map_err(|e| JsValue::from_str(&format!("Invalid describe response: {}", e)))?;
+            if camel {
+                for column in &mut description.columns {
+                    column.name = column_case::to_camel(&column.name);
+                }
+            }
+            to_js(&description)
+        }))
+    }
+
+    // Close one kept prepared statement, e.g. after a schema change made its plan stale.
+    // Its SQL is prepared again on its next run. Resolves with whether it existed.
+    #[wasm_bindgen]
+    pub fn deallocate(&self, handle: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let deallocated = deallocate(&state, Some(handle)).await?;
+            Ok(JsValue::from_bool(deallocated > 0))
+        })
+    }
+
+    // Close every kept prepared statement; resolves with how many there were
+    #[wasm_bindgen]
+    pub fn deallocate_all(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let deallocated = deallocate(&state, None).await?;
+            Ok(JsValue::from_f64(deallocated as f64))
+        })
+    }
+
+    // Queue writes while the socket is down; resolves with `{ queued, idempotencyKey }`
+    #[wasm_bindgen]
+    pub fn enable_outbox(&mut self, persistent: bool) -> Promise {
+        let state = self.state.clone();
+        {
+            let mut state = state.borrow_mut();
+            state.outbox.enabled = true;
+            state.outbox.persistent = persistent;
+        }
+        future_to_promise(async move {
+            let queued = if persistent { outbox::load_persisted(&state).await? } else { state.borrow().outbox.len() };
+            Ok(JsValue::from_f64(queued as f64))
+        })
+    }
+
+    // Stop queuing new writes; entries already queued are kept
+    #[wasm_bindgen]
+    pub fn disable_outbox(&mut self) {
+        self.state.borrow_mut().outbox.enabled = false;
+    }
+
+    #[wasm_bindgen]
+    pub fn outbox_size(&self) -> usize {
+        self.state.borrow().outbox.len()
+    }
+
+    // Queued entries as `{ idempotencyKey, sql, params, queuedAt }` objects
+    #[wasm_bindgen]
+    pub fn get_outbox(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().outbox.entries())
+    }
+
+    // Drop every queued write, including persisted ones
+    #[wasm_bindgen]
+    pub fn clear_outbox(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let persistent = {
+                let mut state = state.borrow_mut();
+                state.outbox.clear();
+                state.outbox.persistent
+            };
+            if persistent {
+                outbox::clear_persisted().await?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Replay queued writes now; resolves with how many were sent
+    #[wasm_bindgen]
+    pub fn flush_outbox(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let replayed = outbox::replay(state).await?;
+            Ok(JsValue::from_f64(replayed as f64))
+        })
+    }
+
+    // Results younger than the TTL are served from IndexedDB without a round trip
+    #[wasm_bindgen]
+    pub fn set_result_cache_ttl(&mut self, ttl_ms: f64) {
+        self.result_cache_ttl_ms = ttl_ms.max(0.0);
+    }
+
+    // Query through the persistent result cache. Cached rows resolve immediately with
+    // `cacheStatus` "hit" or "stale"; stale entries are refreshed in the background and
+    // the fresh result is passed to `on_revalidate`.
+    #[wasm_bindgen]
+    pub fn cached_query(&self, sql: &str, params_json: Option<String>, on_revalidate: Option<js_sys::Function>) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        Ok(future_to_promise(result_cache::cached_query(
+            self.state.clone(),
+            sql.to_string(),
+            params,
+            self.result_cache_ttl_ms,
+            self.null_policy,
+            on_revalidate,
+        )))
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_result_cache(&self) -> Promise {
+        future_to_promise(async move {
+            result_cache::clear().await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Drop in-memory cached results whose SQL contains `pattern` (case-insensitive), or all
+    // of them when it is undefined; returns how many were dropped
+    #[wasm_bindgen]
+    pub fn invalidate(&self, pattern: Option<String>) -> usize {
+        self.state.borrow_mut().query_cache.invalidate(pattern.as_deref())
+    }
+
+    // LISTEN on `channel` and drop in-memory cached results that read any of `tables`
+    // whenever a NOTIFY arrives on it. `options` accepts `{ refresh }`; with refresh the
+    // dropped queries run again in the background and are cached afresh. Registering a
+    // channel again replaces its tables.
+    #[wasm_bindgen]
+    pub fn invalidate_on_notify(
+        &self,
+        channel: String,
+        tables: Vec<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "InvalidationOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let options: cache_invalidation::InvalidationOptions = if options.is_undefined() || options.is_null() {
+            cache_invalidation::InvalidationOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid invalidation options: {}", e)))?
+        };
+        let rule = cache_invalidation::InvalidationRule { tables, refresh: options.refresh };
+        self.state.borrow_mut().cache_invalidation.set(channel.clone(), rule);
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            live::ensure_listening(&state, std::slice::from_ref(&channel)).await?;
+            Ok(JsValue::UNDEFINED)
+        }))
+    }
+
+    // Stop invalidating on `channel`, UNLISTENing unless something else uses it; resolves
+    // false if it wasn't registered
+    #[wasm_bindgen]
+    pub fn stop_invalidate_on_notify(&self, channel: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let removed = state.borrow_mut().cache_invalidation.remove(&channel);
+            if removed {
+                live::release_channels(&state, &[channel]).await?;
+            }
+            Ok(JsValue::from_bool(removed))
+        })
+    }
+
+    // Live query: run `sql` now and again whenever a NOTIFY arrives on one of
+    // `channels`, passing each fresh result to `callback`. Resolves with a watch id.
+    #[wasm_bindgen]
+    pub fn watch_query(&self, sql: &str, params_json: Option<String>, channels: Vec<String>, callback: js_sys::Function) -> Result<Promise, JsValue> {
+        self.watch(sql, params_json, channels, Vec::new(), callback)
+    }
+
+    // Called with `{ watchId, error }` when a live query's refresh fails or its callback
+    // throws; without it they are logged
+    #[wasm_bindgen]
+    pub fn on_watch_error(&self, callback: Option<js_sys::Function>) {
+        self.state.borrow_mut().watches.error_hook = callback;
+    }
+
+    // Like `watch_query`, but rows are matched by `key_columns` and the callback gets
+    // `{ added, removed, changed, rowCount }` with only what changed since the last run
+    #[wasm_bindgen]
+    pub fn watch_query_diff(&self, sql: &str, params_json: Option<String>, channels: Vec<String>, key_columns: Vec<String>, callback: js_sys::Function) -> Result<Promise, JsValue> {
+        if key_columns.is_empty() {
+            return Err(JsValue::from_str("watch_query_diff requires at least one key column"));
+        }
+        self.watch(sql, params_json, channels, key_columns, callback)
+    }
+
+    // Call `callback` with each NOTIFY on `channel` as `{ channel, payload }`, LISTENing
+    // first if needed. `options.batchMs` delivers at most once per window instead, as an
+    // array of the notifications received in it; `options.debounceMs` delivers only the
+    // latest once the channel has been quiet that long. Resolves with a subscription id.
+    #[wasm_bindgen]
+    pub fn on_notification(
+        &self,
+        channel: &str,
+        callback: js_sys::Function,
+        #[wasm_bindgen(unchecked_optional_param_type = "NotificationOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let options: notifications::NotificationOptions = if options.is_undefined() || options.is_null() {
+            notifications::NotificationOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid notification options: {}", e)))?
+        };
+        options.validate().map_err(|e| JsValue::from_str(&e))?;
+        let (id, held) = {
+            let mut state = self.state.borrow_mut();
+            (state.notifications.add(channel, callback, options), state.subscriptions.claim(channel))
+        };
+        let state = self.state.clone();
+        let channel = channel.to_string();
+        Ok(future_to_promise(async move {
+            if let Err(e) = live::ensure_listening(&state, std::slice::from_ref(&channel)).await {
+                state.borrow_mut().notifications.remove(id);
+                return Err(e);
+            }
+            // Notifications a restored channel received before this callback existed
+            for notification in &held {
+                notifications::deliver(&state, notification);
+            }
+            Ok(JsValue::from_f64(id as f64))
+        }))
+    }
+
+    // `{ [name]: id }` for the config's `subscriptions`, each id as `on_notification`
+    // returns it, for `off_notification`
+    #[wasm_bindgen(unchecked_return_type = "Record<string, number>")]
+    pub fn named_subscriptions(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().named_subscriptions.ids())
+    }
+
+    // `{ [channel]: "pending" | "active" | "lost" }` for every channel the client LISTENs on
+    // or is trying to; changes also fire `listenstate` events
+    #[wasm_bindgen(unchecked_return_type = "Record<string, ListenState>")]
+    pub fn listen_states(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().listen_states.snapshot())
+    }
+
+    // `{ [channel]: count }` for every LISTENed channel: how many notification callbacks,
+    // live queries and cache rules use it. A channel at 0 is lingering before its UNLISTEN.
+    #[wasm_bindgen(unchecked_return_type = "Record<string, number>")]
+    pub fn channel_listeners(&self) -> Result<JsValue, JsValue> {
+        to_js(&crate::unlisten::ref_counts(&self.state.borrow()))
+    }
+
+    // Remove a notification callback, UNLISTENing its channel if nothing else uses it,
+    // after `unlistenLingerMs` when set. A batch still waiting is dropped.
+    #[wasm_bindgen]
+    pub fn off_notification(&self, id: u32) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let channel = state.borrow_mut().notifications.remove(id);
+            match channel {
+                Some(channel) => {
+                    live::release_channels(&state, &[channel]).await?;
+                    Ok(JsValue::TRUE)
+                }
+                None => Ok(JsValue::FALSE),
+            }
+        })
+    }
+
+    // Type the payloads of `channel`: `{ schema, decode }` JSON-parses each one, checks
+    // it against a JSON Schema and passes it through `decode(payload)`, either being
+    // optional. Payloads that fail go to `on_notification_error` instead of callbacks.
+    #[wasm_bindgen]
+    pub fn set_channel_type(&self, channel: &str, options: JsValue) -> Result<(), JsValue> {
+        let field = |name: &str| js_sys::Reflect::get(&options, &JsValue::from_str(name)).ok().filter(|v| !v.is_undefined() && !v.is_null());
+        let schema = field("schema")
+            .map(serde_wasm_bindgen::from_value::<serde_json::Value>)
+            .transpose()
+            .map_err(|e| JsValue::from_str(&format!("Invalid channel schema: {}", e)))?;
+        if schema.as_ref().is_some_and(|schema| !schema.is_object()) {
+            return Err(JsValue::from_str("Invalid channel schema: expected an object"));
+        }
+        let decode = field("decode")
+            .map(|decode| decode.dyn_into::<js_sys::Function>())
+            .transpose()
+            .map_err(|_| JsValue::from_str("Invalid channel decoder: expected a function"))?;
+        let channel_type = notifications::ChannelType { schema, decode };
+        self.state.borrow_mut().notifications.types.insert(channel.to_string(), channel_type);
+        Ok(())
+    }
+
+    // Deliver `channel`'s payloads as raw strings again
+    #[wasm_bindgen]
+    pub fn clear_channel_type(&self, channel: &str) -> bool {
+        self.state.borrow_mut().notifications.types.remove(channel).is_some()
+    }
+
+    // Called with `{ channel, payload, error }` for each payload a typed channel
+    // rejected; without it they are logged and dropped
+    #[wasm_bindgen]
+    pub fn on_notification_error(&self, callback: Option<js_sys::Function>) {
+        self.state.borrow_mut().notifications.error_hook = callback;
+    }
+
+    // Called with each NOTIFY and row change this client receives, numbered in arrival
+    // order as a `FanoutEvent`, for the leader tab to broadcast to the tabs sharing its
+    // connection. Each tab passes them through a `TabFanoutFilter`. Null stops it.
+    #[wasm_bindgen]
+    pub fn set_tab_fanout(&self, hook: Option<js_sys::Function>) {
+        let mut state = self.state.borrow_mut();
+        if hook.is_some() && state.tab_fanout.source.is_empty() {
+            state.tab_fanout.source = crate::ids::random_uuid();
+        }
+        state.tab_fanout.hook = hook;
+    }
+
+    // Save the notification channels and cache invalidation rules to IndexedDB as they
+    // change, so a restarted worker or discarded tab gets them back. Enabling restores
+    // what was saved and resolves with the restored channels, which stay LISTENed and
+    // hold their notifications until `on_notification` subscribes to them again.
+    // Disabling deletes the saved copy.
+    #[wasm_bindgen]
+    pub fn persist_subscriptions(&self, enabled: bool) -> Promise {
+        let state = self.state.clone();
+        state.borrow_mut().subscriptions.enabled = enabled;
+        future_to_promise(async move {
+            if !enabled {
+                subscription_store::clear_saved().await?;
+                return Ok(js_sys::Array::new().into());
+            }
+            let channels = subscription_store::restore(&state).await?;
+            subscription_store::changed(&state);
+            Ok(channels.into_iter().map(|channel| JsValue::from_str(&channel)).collect::<js_sys::Array>().into())
+        })
+    }
+
+    // Stop a live query, UNLISTENing channels nothing else watches
+    #[wasm_bindgen]
+    pub fn unwatch(&self, watch_id: u32) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let removed = state.borrow_mut().watches.remove(watch_id);
+            match removed {
+                Some(watch) => {
+                    live::release_channels(&state, &watch.channels).await?;
+                    Ok(JsValue::TRUE)
+                }
+                None => Ok(JsValue::FALSE),
+            }
+        })
+    }
+
+    fn watch(&self, sql: &str, params_json: Option<String>, channels: Vec<String>, key_columns: Vec<String>, callback: js_sys::Function) -> Result<Promise, JsValue> {
+        if channels.is_empty() {
+            return Err(JsValue::from_str("Live queries require at least one channel"));
+        }
+        let params = parse_params_json(params_json)?;
+        let state = self.state.clone();
+        let id = state.borrow_mut().watches.add(live::Watch {
+            sql: sql.to_string(),
+            params,
+            channels: channels.clone(),
+            callback,
+            null_policy: self.null_policy,
+            run: live::RunState::default(),
+            diff: (!key_columns.is_empty()).then(|| live::DiffState::new(key_columns)),
+            last: None,
+        });
+        Ok(future_to_promise(async move {
+            if let Err(e) = live::ensure_listening(&state, &channels).await {
+                state.borrow_mut().watches.remove(id);
+                return Err(e);
+            }
+            live::run(state, id).await;
+            Ok(JsValue::from_f64(id as f64))
+        }))
+    }
+
+    // Statements run in order after every connect and reconnect, before queries waiting
+    // for the connection go out; e.g. `SET statement_timeout = '5s'`. Used from the next
+    // connect; a failing statement is logged and the rest still run.
+    #[wasm_bindgen]
+    pub fn set_on_connect_sql(&self, statements: Vec<String>) {
+        self.state.borrow_mut().on_connect_sql = statements;
+    }
+
+    // Cancel statements on the backend after `ms` milliseconds (0 for no limit), or go back
+    // to the server default with null. Kept across reconnects; a query's
+    // `statementTimeoutMs` option overrides it for that query.
+    #[wasm_bindgen]
+    pub fn set_statement_timeout(&self, ms: Option<u32>) -> Promise {
+        let value = ms.map(|ms| format!("{}ms", ms));
+        self.set_session_setting(session_state::STATEMENT_TIMEOUT_SETTING.to_string(), value)
+    }
+
+    // Set a session setting (GUC) with set_config(), or RESET it when `value` is null.
+    // Settings are re-applied automatically after a reconnect.
+    #[wasm_bindgen]
+    pub fn set_session_setting(&self, name: String, value: Option<String>) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            session_state::apply_setting(&state, &name, value).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Reject anything but reads, EXPLAIN and transaction control before it is sent, and
+    // set `default_transaction_read_only` on the backend session (kept across reconnects)
+    #[wasm_bindgen]
+    pub fn set_read_only(&self, enabled: bool) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            read_only::set(&state, enabled).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    #[wasm_bindgen]
+    pub fn is_read_only(&self) -> bool {
+        self.state.borrow().read_only
+    }
+
+    // Let `query()` run `;`-separated statements without `multiStatement: true` per call
+    #[wasm_bindgen]
+    pub fn allow_multi_statements(&self, enabled: bool) {
+        self.state.borrow_mut().multi_statement = enabled;
+    }
+
+    // Run `validate` on `query()` SQL before sending it, rejecting syntax errors with
+    // SYNTAX_ERROR instead of spending a round trip on them
+    #[wasm_bindgen]
+    pub fn set_sql_validation(&self, enabled: bool) {
+        self.state.borrow_mut().validate_sql = enabled;
+    }
+
+    // Append `LIMIT limit + 1` to `query()` reads that have no LIMIT of their own, then
+    // return at most `limit` rows with `truncated` set; null turns the guard off
+    #[wasm_bindgen]
+    pub fn set_limit_guard(&self, limit: Option<u32>) {
+        self.state.borrow_mut().limit_guard = limit;
+    }
+
+    // Plan each `query()` statement with EXPLAIN first and reject it with
+    // COST_LIMIT_EXCEEDED when the estimates pass `{ maxCost, maxRows }`; null turns the
+    // guard off
+    #[wasm_bindgen]
+    pub fn set_cost_guard(&self, #[wasm_bindgen(unchecked_optional_param_type = "CostGuard | null")] guard: JsValue) -> Result<(), JsValue> {
+        let guard: Option<cost_guard::CostGuard> = if guard.is_undefined() || guard.is_null() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(guard).map_err(|e| JsValue::from_str(&format!("Invalid cost guard: {}", e)))?)
+        };
+        self.state.borrow_mut().cost_guard = guard;
+        Ok(())
+    }
+
+    // Register a row transform that queries apply with `{ transform: name }`. `map`, if
+    // given, is called as `map(row, index)` with each converted row after the declarative
+    // steps, and its return value replaces the row.
+    #[wasm_bindgen]
+    pub fn register_row_transform(
+        &mut self,
+        name: &str,
+        #[wasm_bindgen(unchecked_param_type = "RowTransform | null")] transform: JsValue,
+        map: Option<js_sys::Function>,
+    ) -> Result<(), JsValue> {
+        let transform: row_transform::RowTransform = if transform.is_undefined() || transform.is_null() {
+            Default::default()
+        } else {
+            serde_wasm_bindgen::from_value(transform).map_err(|e| JsValue::from_str(&format!("Invalid row transform: {}", e)))?
+        };
+        self.row_transforms.insert(name.to_string(), row_transform::Registered { transform, map });
+        Ok(())
+    }
+
+    // Forget a registered row transform; returns whether it existed
+    #[wasm_bindgen]
+    pub fn unregister_row_transform(&mut self, name: &str) -> bool {
+        self.row_transforms.remove(name).is_some()
+    }
+
+    // Encrypt every frame after `hello` with a base64 key of at least 32 bytes shared with
+    // the bridge out of band; null turns encryption off. Takes effect at the next connect,
+    // and a bridge without the key is then refused.
+    #[wasm_bindgen]
+    pub fn set_encryption_key(&self, key: Option<String>) -> Result<(), JsValue> {
+        let key = key.map(|key| FrameKey::from_base64(&key)).transpose().map_err(|e| JsValue::from_str(&e))?;
+        self.state.borrow_mut().encryption_key = key;
+        Ok(())
+    }
+
+    // Keep the bridge's session token in sessionStorage so a reload reattaches to the
+    // same backend session within the bridge's resume window: session settings and LISTEN
+    // channels survive, open transactions don't. The token grants the session to whoever
+    // holds it, so the new connection still authenticates. Takes effect at the next connect.
+    #[wasm_bindgen]
+    pub fn enable_session_resumption(&self, enabled: bool) {
+        let mut state = self.state.borrow_mut();
+        if enabled {
+            let key = format!("pg-bridge-session:{}", self.url);
+            let token = resume::load(&key);
+            state.resumption.enable(key, token);
+        } else if let Some(key) = state.resumption.disable() {
+            resume::forget(&key);
+        }
+    }
+
+    // Whether the current connection reattached to a session parked by the bridge
+    #[wasm_bindgen]
+    pub fn session_resumed(&self) -> bool {
+        self.state.borrow().resumption.resumed
+    }
+
+    // Register allow/deny rules checked before each query is sent:
+    // `{ allowKinds, denyKinds, allowPatterns, denyPatterns }`, or null to clear them.
+    // The bridge learns the policy at the next handshake, so set it before `connect()`.
+    #[wasm_bindgen]
+    pub fn set_statement_policy(&self, #[wasm_bindgen(unchecked_optional_param_type = "StatementPolicy | null")] policy: JsValue) -> Result<(), JsValue> {
+        let policy: Option<StatementPolicy> = if policy.is_undefined() || policy.is_null() {
+            None
+        } else {
+            serde_wasm_bindgen::from_value(policy)
+                .map_err(|e| JsValue::from_str(&format!("Invalid statement policy: {}", e)))?
+        };
+        self.state.borrow_mut().policy = policy.filter(|policy| !policy.is_empty());
+        Ok(())
+    }
+
+    // Set the session TimeZone to an IANA name, or "browser" for the browser's own zone;
+    // null goes back to the server default. Restored after every reconnect.
+    #[wasm_bindgen]
+    pub fn set_time_zone(&self, zone: Option<String>) -> Result<Promise, JsValue> {
+        let zone = match zone {
+            Some(zone) => Some(time_zone::resolve(&zone).ok_or_else(|| JsValue::from_str("The browser's time zone is not available here"))?),
+            None => None,
+        };
+        Ok(self.set_session_setting(session_state::TIME_ZONE_SETTING.to_string(), zone))
+    }
+
+    // The session TimeZone, or null when the server default applies
+    #[wasm_bindgen]
+    pub fn time_zone(&self) -> Option<String> {
+        self.state.borrow().session.settings().get(session_state::TIME_ZONE_SETTING).cloned()
+    }
+
+    // A timestamptz (e.g. as results return it, in UTC) as wall-clock time in the session
+    // zone with its offset, e.g. "2024-03-01T13:00:00.000+01:00". Without a session zone
+    // the browser's is used.
+    #[wasm_bindgen]
+    pub fn to_session_time(&self, value: &str) -> Result<String, JsValue> {
+        time_zone::to_zone(value, &self.conversion_zone()?).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // A timestamp as UTC ISO 8601, for parameters. One without an offset is taken as
+    // wall-clock time in the session zone.
+    #[wasm_bindgen]
+    pub fn to_utc(&self, value: &str) -> Result<String, JsValue> {
+        time_zone::to_utc(value, &self.conversion_zone()?).map_err(|e| JsValue::from_str(&e))
+    }
+
+    fn conversion_zone(&self) -> Result<String, JsValue> {
+        self.time_zone()
+            .or_else(time_zone::browser_time_zone)
+            .ok_or_else(|| JsValue::from_str("No session or browser time zone to convert with"))
+    }
+
+    // Settings applied through `set_session_setting`/`set_rls_context`, by name
+    #[wasm_bindgen]
+    pub fn session_settings(&self) -> Result<JsValue, JsValue> {
+        to_js(self.state.borrow().session.settings())
+    }
+
+    // Set the claims row-level security policies see as `request.jwt.claims`;
+    // null clears them. Restored after every reconnect like other session settings.
+    #[wasm_bindgen]
+    pub fn set_rls_context(&self, claims: JsValue) -> Result<Promise, JsValue> {
+        let claims = if claims.is_undefined() || claims.is_null() {
+            None
+        } else {
+            let claims: serde_json::Value = serde_wasm_bindgen::from_value(claims)
+                .map_err(|e| JsValue::from_str(&format!("Invalid RLS context: {}", e)))?;
+            Some(claims.to_string())
+        };
+        Ok(self.set_session_setting(session_state::RLS_CONTEXT_SETTING.to_string(), claims))
+    }
+
+    // Stream row changes on `tables` from the bridge's logical replication slot.
+    // `callback` receives `{ action, schema, table, new, old }`; resolves with a subscription id.
+    #[wasm_bindgen]
+    pub fn subscribe_changes(&self, tables: Vec<String>, callback: js_sys::Function) -> Result<Promise, JsValue> {
+        if tables.is_empty() {
+            return Err(JsValue::from_str("subscribe_changes requires at least one table"));
+        }
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let subscription = cdc::subscribe(&state, tables, callback).await?;
+            Ok(JsValue::from_str(&subscription))
+        }))
+    }
+
+    #[wasm_bindgen]
+    pub fn unsubscribe_changes(&self, subscription: String) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            let removed = cdc::unsubscribe(&state, &subscription).await?;
+            Ok(JsValue::from_bool(removed))
+        })
+    }
+
+    // Keep `tables` in sync both ways: writes made with `sync_write` go to the IndexedDB
+    // outbox and are pushed in the background, while changes to `tables` arrive through
+    // change data capture and are passed to `on_change`. `options` accepts
+    // `{ intervalMs }`; resolves with the first status.
+    #[wasm_bindgen]
+    pub fn start_sync(
+        &self,
+        tables: Vec<String>,
+        on_change: Option<js_sys::Function>,
+        #[wasm_bindgen(unchecked_optional_param_type = "SyncOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let options: sync::SyncOptions = if options.is_undefined() || options.is_null() {
+            sync::SyncOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options)
+                .map_err(|e| JsValue::from_str(&format!("Invalid sync options: {}", e)))?
+        };
+        options.validate().map_err(|e| JsValue::from_str(&format!("Invalid sync options: {}", e)))?;
+
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let status = sync::start(state, tables, on_change, options).await?;
+            to_js(&status)
+        }))
+    }
+
+    // Stop syncing; writes still queued stay in the outbox for the next start
+    #[wasm_bindgen]
+    pub fn stop_sync(&self) -> Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            sync::stop(&state).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Queue a write for the sync loop, which sends it right away when connected;
+    // resolves with its idempotency key once stored. `conflict` accepts
+    // `{ table, key, versionColumn, baseVersion, row }` to check the server's row first.
+    #[wasm_bindgen(unchecked_return_type = "Promise<string>")]
+    pub fn sync_write(
+        &self,
+        sql: &str,
+        params_json: Option<String>,
+        #[wasm_bindgen(unchecked_optional_param_type = "ConflictCheck | null")] conflict: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let params = parse_params_json(params_json)?;
+        let conflict: Option<conflict::ConflictCheck> = if conflict.is_undefined() || conflict.is_null() {
+            None
+        } else {
+            let check: conflict::ConflictCheck = serde_wasm_bindgen::from_value(conflict)
+                .map_err(|e| JsValue::from_str(&format!("Invalid conflict check: {}", e)))?;
+            check.validate().map_err(|e| JsValue::from_str(&format!("Invalid conflict check: {}", e)))?;
+            Some(check)
+        };
+        if !self.state.borrow().sync.running() {
+            return Err(JsValue::from_str("Sync is not running; call start_sync first"));
+        }
+        let state = self.state.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let key = sync::write(&state, &sql, params, conflict).await?;
+            Ok(JsValue::from_str(&key))
+        }))
+    }
+
+    // Settle `table`'s sync conflicts with "last-write-wins" (the default), "server-wins" or
+    // a merge function; null goes back to the default
+    #[wasm_bindgen]
+    pub fn set_conflict_resolver(&self, table: &str, resolver: JsValue) -> Result<(), JsValue> {
+        let mut state = self.state.borrow_mut();
+        if resolver.is_undefined() || resolver.is_null() {
+            state.sync.resolvers.remove(table);
+            return Ok(());
+        }
+        let resolver = conflict::Resolver::parse(resolver)
+            .map_err(|e| JsValue::from_str(&format!("Invalid conflict resolver: {}", e)))?;
+        state.sync.resolvers.insert(table.to_string(), resolver);
+        Ok(())
+    }
+
+    // `{ phase, pending, lastPushAt, lastPullAt, pulled, conflicts, error }`; `phase` is "stopped",
+    // "offline", "pushing", "synced" or "error"
+    #[wasm_bindgen]
+    pub fn sync_status(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.state.borrow().sync.status)
+    }
+
+    // Called with the sync status whenever it changes
+    #[wasm_bindgen]
+    pub fn on_sync_status(&self, hook: Option<js_sys::Function>) {
+        self.state.borrow_mut().sync.hook = hook;
+    }
+
+    pub(crate) fn with_config(config: WasmClientConfig) -> WasmWebSocketClient {
+        log_info!("Creating WASM WebSocket client for URL: {}", config.url);
+        let mut state = ClientState { timeouts: config.timeouts, config: Some(config.clone()), ..ClientState::default() };
+        // Replayed on every connect like settings made with `set_session_setting`
+        if let Some(search_path) = config.search_path_setting() {
+            state.session.set("search_path", Some(search_path));
+        }
+        if let Some(timeout_ms) = config.statement_timeout_ms {
+            state.session.set(session_state::STATEMENT_TIMEOUT_SETTING, Some(format!("{}ms", timeout_ms)));
+        }
+        if let Some(tenant_id) = &config.tenant_id {
+            state.session.set(session_state::TENANT_SETTING, Some(tenant_id.clone()));
+        }
+        if let Some(zone) = &config.time_zone {
+            match time_zone::resolve(zone) {
+                Some(zone) => state.session.set(session_state::TIME_ZONE_SETTING, Some(zone)),
+                None => log_warn!("WASM could not resolve the browser's time zone; keeping the server default"),
+            }
+        }
+        state.tenant_id = config.tenant_id;
+        state.auth_token = config.auth;
+        state.on_connect_sql = config.on_connect_sql;
+        state.reconnect.policy = config.reconnect;
+        state.pool_request = config.pool;
+        state.listen_states.relisten_ms = config.relisten_ms;
+        state.unlisten.disabled = config.auto_unlisten == Some(false);
+        state.unlisten.linger_ms = config.unlisten_linger_ms;
+        named_subscriptions::register(&mut state, &config.subscriptions);
+        WasmWebSocketClient {
+            url: config.url,
+            state: Rc::new(RefCell::new(state)),
+            null_policy: NullPolicy::default(),
+            strict_params: false,
+            migrations: MigrationSet::default(),
+            result_cache_ttl_ms: 60_000.0,
+            websocket_impl: None,
+            result_format: config.encoding,
+            placeholders: config.placeholders,
+            column_case: config.column_case,
+            row_transforms: row_transform::Registry::default(),
+        }
+    }
+
+    fn should_queue(&self, sql: &str) -> bool {
+        let state = self.state.borrow();
+        state.outbox.enabled
+            && !state.is_connected()
+            && !state.idle.suspended
+            && !state.query_tape.is_replaying()
+            && outbox::is_write(sql)
+    }
+
+    fn query_promise(&self, sql: String, params: Option<Vec<serde_json::Value>>) -> Promise {
+        self.query_promise_with(sql, params, QueryOptions::default())
+    }
+
+    fn query_promise_with(&self, sql: String, mut params: Option<Vec<serde_json::Value>>, mut options: QueryOptions) -> Promise {
+        let sql = match self.placeholders {
+            placeholders::PlaceholderStyle::Dollar => sql,
+            placeholders::PlaceholderStyle::Question => match placeholders::translate(&sql) {
+                Ok(sql) => sql,
+                Err(reason) => {
+                    let payload = serde_json::json!({ "message": reason, "code": "INVALID_PLACEHOLDERS" });
+                    return Promise::reject(&BridgeError::from_error_payload(&payload, None).into());
+                }
+            },
+        };
+        let sql = match (options.in_lists, params.take()) {
+            (Some(style), Some(list)) => match in_list::expand(&sql, list, style) {
+                Ok((query, origins)) => {
+                    // A type given for an expanded array applies to each of its elements
+                    if let Some(types) = options.types.as_mut() {
+                        *types = origins
+                            .iter()
+                            .map(|(origin, element)| {
+                                let ty = types.get(*origin).cloned().flatten();
+                                ty.map(|ty| if *element { ty.trim_end_matches("[]").to_string() } else { ty })
+                            })
+                            .collect();
+                    }
+                    params = Some(query.params);
+                    query.sql
+                }
+                Err(reason) => {
+                    let payload = serde_json::json!({ "message": reason, "code": "INVALID_PLACEHOLDERS" });
+                    return Promise::reject(&BridgeError::from_error_payload(&payload, None).into());
+                }
+            },
+            (_, list) => {
+                params = list;
+                sql
+            }
+        };
+        if self.state.borrow().validate_sql {
+            if let Some(error) = sql_validate::validate(&sql).error {
+                let message = format!("{} (line {}, column {})", error.message, error.line, error.column);
+                let payload = serde_json::json!({ "message": message, "code": "SYNTAX_ERROR", "detail": format!("position {}", error.position) });
+                return Promise::reject(&BridgeError::from_error_payload(&payload, None).into());
+            }
+        }
+        if options.binary_params.is_none() && self.should_queue(&sql) {
+            let state = self.state.clone();
+            return future_to_promise(async move {
+                let key = outbox::enqueue(&state, &sql, params, None, options.multi_statement).await?;
+                to_js(&serde_json::json!({ "queued": true, "idempotencyKey": key }))
+            });
+        }
+        let camel = options.column_case.unwrap_or(self.column_case) == column_case::ColumnCase::Camel;
+        if let Some(params) = params.as_mut().filter(|_| camel) {
+            column_case::snake_params(params);
+        }
+        let transform = match row_transform::resolve(&self.row_transforms, options.transform.take()) {
+            Ok(transform) => transform,
+            Err(e) => return Promise::reject(&e),
+        };
+        let id = {
+            let mut state = self.state.borrow_mut();
+            let id = options.query_id.take().unwrap_or_else(|| state.next_message_id("query"));
+            if !state.queries.queue(&id) {
+                let payload = serde_json::json!({ "message": format!("Query id '{}' is already in use", id), "code": "DUPLICATE_QUERY_ID" });
+                return Promise::reject(&BridgeError::from_error_payload(&payload, Some(id)).into());
+            }
+            id
+        };
+        options.query_id = Some(id.clone());
+        let state = self.state.clone();
+        let null_policy = self.null_policy;
+        let result_format = self.result_format;
+        let format = options.format;
+        let raw = options.raw;
+        let guard = self.state.borrow().limit_guard.and_then(|limit| Some((limit_guard::apply(&sql, limit)?, limit)));
+        let cost_guard = self.state.borrow().cost_guard;
+        let kind = statement_kind::classify(&sql);
+        future_to_promise(async move {
+            let sql = guard.as_ref().map_or(sql.as_str(), |(guarded, _)| guarded.as_str());
+            let outcome = async {
+                session_state::wait_until_restored(&state).await?;
+                if let Some(cost_guard) = cost_guard {
+                    cost_guard::preflight(&state, cost_guard, sql, params.clone(), &options).await?;
+                }
+                match options.cache {
+                    Some(cache) if query_cache::applies(sql, &options) => query_cache::cached(&state, sql, params, options, cache).await,
+                    _ => connection::execute_query_with(&state, sql, params, options).await,
+                }
+            }
+            .await;
+            let status = if outcome.is_ok() { QueryStatus::Completed } else { QueryStatus::Failed };
+            state.borrow_mut().queries.advance(&id, status);
+            let mut result = outcome?;
+            if let Some((_, limit)) = guard {
+                limit_guard::truncate(&mut result, limit);
+            }
+            if let (Some(kind), Some(object)) = (kind, result.as_object_mut()) {
+                object.insert("statementKind".to_string(), serde_json::json!(kind));
+            }
+            if raw {
+                return raw::to_js(&result);
+            }
+            if let (Some(transform), Some(serde_json::Value::Array(rows))) = (&transform, result.get_mut("rows")) {
+                transform.transform.apply(rows);
+            }
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows").filter(|_| camel) {
+                column_case::camel_rows(rows);
+            }
+            if format.unwrap_or(result_format) == columnar::ResultFormat::Columnar {
+                return columnar::to_js(&result);
+            }
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows") {
+                null_policy.apply_to_rows(rows);
+            }
+            let output = intern::result_to_js(result, &mut state.borrow_mut().interning, null_policy)?;
+            if let Some(map) = transform.as_ref().and_then(|transform| transform.map.as_ref()) {
+                row_transform::map_rows(&output, map)?;
+            }
+            Ok(output)
+        })
+    }
+
+    // Register a migration script; versions are applied in ascending order
+    #[wasm_bindgen]
+    pub fn register_migration(&mut self, version: i64, name: &str, up_sql: &str, down_sql: Option<String>) -> Result<(), JsValue> {
+        self.migrations
+            .register(Migration {
+                version,
+                name: name.to_string(),
+                up: up_sql.to_string(),
+                down: down_sql,
+            })
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    // Apply all pending migrations; resolves with the versions that were applied
+    #[wasm_bindgen]
+    pub fn migrate_up(&self) -> Promise {
+        let state = self.state.clone();
+        let set = self.migrations.clone();
+        future_to_promise(async move {
+            let applied = migrations::migrate_up(state, set).await?;
+            Ok(applied.into_iter().map(|v| JsValue::from_f64(v as f64)).collect::<js_sys::Array>().into())
+        })
+    }
+
+    // Revert the most recently applied migration; resolves with its version or null
+    #[wasm_bindgen]
+    pub fn migrate_down(&self) -> Promise {
+        let state = self.state.clone();
+        let set = self.migrations.clone();
+        future_to_promise(async move {
+            let reverted = migrations::migrate_down(state, set).await?;
+            Ok(reverted.map_or(JsValue::NULL, |v| JsValue::from_f64(v as f64)))
+        })
+    }
+
+    // Insert a table -> rows fixture document in one transaction; resolves with the row count
+    #[wasm_bindgen]
+    pub fn load_fixtures(&self, fixtures_json: &str, truncate: bool) -> Result<Promise, JsValue> {
+        let fixtures = fixtures::parse_fixtures(fixtures_json).map_err(|e| JsValue::from_str(&e))?;
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let rows = fixtures::load_fixtures(state, fixtures, truncate).await?;
+            Ok(JsValue::from_f64(rows as f64))
+        }))
+    }
+
+    // Call `schema.name(args)` as `SELECT * FROM`, passing `args` in order (an array) or by
+    // name (an object), each cast to its declared type. Resolves with the rows of a
+    // set-returning function, an object of the OUT parameters when there are several, or
+    // else the return value.
+    #[wasm_bindgen]
+    pub fn call_function(
+        &self,
+        schema: &str,
+        name: &str,
+        #[wasm_bindgen(unchecked_optional_param_type = "unknown[] | Record<string, unknown> | null")] args: JsValue,
+    ) -> Result<Promise, JsValue> {
+        self.call_routine(schema, name, false, args)
+    }
+
+    // `CALL schema.name(args)`, with NULL passed for OUT parameters. Resolves with an
+    // object of the OUT parameters, or null when the procedure has none.
+    #[wasm_bindgen]
+    pub fn call_procedure(
+        &self,
+        schema: &str,
+        name: &str,
+        #[wasm_bindgen(unchecked_optional_param_type = "unknown[] | Record<string, unknown> | null")] args: JsValue,
+    ) -> Result<Promise, JsValue> {
+        self.call_routine(schema, name, true, args)
+    }
+
+    fn call_routine(&self, schema: &str, name: &str, procedure: bool, args: JsValue) -> Result<Promise, JsValue> {
+        let args = if args.is_undefined() || args.is_null() {
+            routines::CallArgs::Positional(Vec::new())
+        } else {
+            match serde_wasm_bindgen::from_value(args).map_err(|e| JsValue::from_str(&format!("Invalid arguments: {}", e)))? {
+                serde_json::Value::Array(values) => routines::CallArgs::Positional(values),
+                serde_json::Value::Object(values) => routines::CallArgs::Named(values),
+                _ => return Err(JsValue::from_str("Invalid arguments: expected an array or an object")),
+            }
+        };
+        let state = self.state.clone();
+        let (schema, name) = (schema.to_string(), name.to_string());
+        Ok(future_to_promise(async move {
+            let value = routines::call(&state, &schema, &name, procedure, args).await?;
+            to_js(&value)
+        }))
+    }
+
+    // Every overload of `schema.name` as `{ procedure, returnsSet, returnType, args,
+    // defaults }`, each argument `{ name, type, mode }`
+    #[wasm_bindgen]
+    pub fn describe_routine(&self, schema: &str, name: &str) -> Promise {
+        let state = self.state.clone();
+        let (schema, name) = (schema.to_string(), name.to_string());
+        future_to_promise(async move {
+            let routines = routines::describe(&state, &schema, &name).await?;
+            to_js(&routines)
+        })
+    }
+
+    // Introspect the schema and emit row types; `language` is "typescript" or "rust"
+    #[wasm_bindgen]
+    pub fn generate_types(&self, language: &str) -> Result<Promise, JsValue> {
+        let generate: fn(&[codegen::TableInfo]) -> String = match language {
+            "typescript" | "ts" => codegen::generate_typescript,
+            "rust" => codegen::generate_rust,
+            other => return Err(JsValue::from_str(&format!("Unsupported codegen language '{}'", other))),
+        };
+        let state = self.state.clone();
+        Ok(future_to_promise(async move {
+            let tables = codegen::introspect(&state).await?;
+            Ok(JsValue::from_str(&generate(&tables)))
+        }))
+    }
+
+    #[wasm_bindgen]
+    pub fn set_message_handler(&mut self, handler: js_sys::Function) -> Result<(), JsValue> {
+        let mut state = self.state.borrow_mut();
+        if state.transport.is_none() {
+            return Err(BridgeError::connection("WebSocket not initialized").into());
+        }
+        state.message_handler = Some(handler);
+        Ok(())
+    }
+}
+
+#[wasm_bindgen]
+pub fn create_websocket_client(url: &str) -> WasmWebSocketClient {
+    log_info!("Creating WASM WebSocket client instance");
+    WasmWebSocketClient::with_config(WasmClientConfig::from_url(url))
+}
+
+// Utility function for initialization
+#[wasm_bindgen(start)]
+pub fn main() {
+    log_info!("WASM module with WebSocket support initialized successfully!");
+}
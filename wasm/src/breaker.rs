@@ -0,0 +1,124 @@
+use serde::Serialize;
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_COOLDOWN_MS: f64 = 30_000.0;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    // Connecting normally
+    Closed,
+    // Too many consecutive failures; connection attempts fail fast until the cool-down ends
+    Open,
+    // Cool-down over; the next attempt probes whether the bridge is back
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+// Stops reconnect storms against a dying bridge. Times are milliseconds since the
+// epoch, passed in so the logic stays independent of the JS clock.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown_ms: f64,
+    failures: u32,
+    state: BreakerState,
+    opened_at: f64,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker {
+            threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown_ms: DEFAULT_COOLDOWN_MS,
+            failures: 0,
+            state: BreakerState::Closed,
+            opened_at: 0.0,
+        }
+    }
+}
+
+impl CircuitBreaker {
+    pub fn configure(&mut self, threshold: u32, cooldown_ms: f64) {
+        self.threshold = threshold.max(1);
+        self.cooldown_ms = cooldown_ms.max(0.0);
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    // Milliseconds until an open circuit lets a probe through
+    pub fn retry_in(&self, now: f64) -> Option<f64> {
+        match self.state {
+            BreakerState::Open => Some((self.opened_at + self.cooldown_ms - now).max(0.0)),
+            _ => None,
+        }
+    }
+
+    // Whether a connection attempt may go ahead; moves an open circuit whose
+    // cool-down has passed to half-open
+    pub fn admit(&mut self, now: f64) -> bool {
+        if self.state == BreakerState::Open {
+            if now < self.opened_at + self.cooldown_ms {
+                return false;
+            }
+            self.state = BreakerState::HalfOpen;
+        }
+        true
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+        self.state = BreakerState::Closed;
+    }
+
+    // A failed probe reopens the circuit straight away
+    pub fn record_failure(&mut self, now: f64) {
+        self.failures += 1;
+        if self.state == BreakerState::HalfOpen || self.failures >= self.threshold {
+            self.state = BreakerState::Open;
+            self.opened_at = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold_and_probes_after_cooldown() {
+        let mut breaker = CircuitBreaker::default();
+        breaker.configure(3, 1000.0);
+        breaker.record_failure(0.0);
+        breaker.record_failure(10.0);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record_failure(20.0);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.admit(500.0));
+        assert_eq!(breaker.retry_in(500.0), Some(520.0));
+
+        assert!(breaker.admit(1020.0));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        breaker.record_failure(1030.0);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        assert!(breaker.admit(2030.0));
+        breaker.record_success();
+        assert_eq!((breaker.state(), breaker.failures()), (BreakerState::Closed, 0));
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+use crate::query_builder::BuiltQuery;
+use crate::sql::{quote_ident, quote_qualified};
+
+// Postgres caps bind parameters per statement at 65535
+pub const MAX_BIND_PARAMS: usize = 65535;
+
+// Upper bound on rows per statement even when the parameter limit would allow more
+pub const DEFAULT_ROWS_PER_STATEMENT: usize = 1000;
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BulkInsertSummary {
+    pub statements: usize,
+    #[serde(rename = "rowsInserted")]
+    pub rows_inserted: usize,
+}
+
+// Split rows into multi-row INSERT statements that stay under the parameter limit
+pub fn build_insert_many(table: &str, columns: &[String], rows: &[Vec<Value>], max_rows: usize) -> Result<Vec<BuiltQuery>, String> {
+    if columns.is_empty() {
+        return Err("insert_many requires at least one column".to_string());
+    }
+    if let Some(index) = rows.iter().position(|row| row.len() != columns.len()) {
+        return Err(format!(
+            "Row {} has {} values but {} columns were given",
+            index,
+            rows[index].len(),
+            columns.len()
+        ));
+    }
+
+    let rows_per_statement = (MAX_BIND_PARAMS / columns.len()).min(max_rows.max(1)).max(1);
+    let column_list = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let prefix = format!("INSERT INTO {} ({}) VALUES ", quote_qualified(table), column_list);
+
+    Ok(rows
+        .chunks(rows_per_statement)
+        .map(|chunk| {
+            let mut params = Vec::with_capacity(chunk.len() * columns.len());
+            let tuples = chunk
+                .iter()
+                .map(|row| {
+                    let placeholders = row
+                        .iter()
+                        .map(|value| {
+                            params.push(value.clone());
+                            format!("${}", params.len())
+                        })
+                        .collect::<Vec<_>>();
+                    format!("({})", placeholders.join(", "))
+                })
+                .collect::<Vec<_>>();
+            BuiltQuery {
+                sql: format!("{}{}", prefix, tuples.join(", ")),
+                params,
+            }
+        })
+        .collect())
+}
+
+// Execute the chunked statements in order and total up what was inserted
+pub(crate) async fn insert_many(state: SharedState, statements: Vec<BuiltQuery>, columns: usize) -> Result<BulkInsertSummary, JsValue> {
+    let mut summary = BulkInsertSummary::default();
+    for statement in statements {
+        let rows = statement.params.len() / columns;
+        execute_query(&state, &statement.sql, Some(statement.params)).await?;
+        summary.statements += 1;
+        summary.rows_inserted += rows;
+    }
+    Ok(summary)
+}
+
+// How one input row of `update_many` or `delete_many` fared: `matched` when its key found
+// a table row, or the error its statement failed with
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BulkRowResult {
+    pub index: usize,
+    pub matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct BulkWriteSummary {
+    pub statements: usize,
+    #[serde(rename = "rowsAffected")]
+    pub rows_affected: usize,
+    pub rows: Vec<BulkRowResult>,
+}
+
+// One keyed statement: its SQL, the rows it binds as a single jsonb parameter, and the
+// index of its first row in the caller's list
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyedStatement {
+    pub query: BuiltQuery,
+    pub first_row: usize,
+    pub rows: usize,
+}
+
+// The column names every row must share, or which row doesn't
+fn shared_columns(rows: &[Map<String, Value>], what: &str) -> Result<BTreeSet<String>, String> {
+    let columns: BTreeSet<String> = rows.first().map(|row| row.keys().cloned().collect()).unwrap_or_default();
+    if let Some(index) = rows.iter().position(|row| row.len() != columns.len() || !row.keys().all(|key| columns.contains(key))) {
+        return Err(format!("{} {} doesn't have the same columns as the first", what, index));
+    }
+    Ok(columns)
+}
+
+// Rows go in as one jsonb array, read back as the table's row type, so every value is cast
+// to its column's type without the client knowing it. The row source is numbered so
+// RETURNING can say which input rows matched.
+fn keyed(table: &str, rows: &[Map<String, Value>], max_rows: usize, build: impl Fn(&str) -> String) -> Vec<KeyedStatement> {
+    let source = format!("jsonb_populate_recordset(NULL::{}, $1::jsonb) WITH ORDINALITY AS keyed", quote_qualified(table));
+    let sql = build(&source);
+    rows.chunks(max_rows.max(1))
+        .enumerate()
+        .map(|(i, chunk)| KeyedStatement {
+            query: BuiltQuery { sql: sql.clone(), params: vec![Value::Array(chunk.iter().cloned().map(Value::Object).collect())] },
+            first_row: i * max_rows.max(1),
+            rows: chunk.len(),
+        })
+        .collect()
+}
+
+fn key_match(key_columns: &[String]) -> String {
+    key_columns
+        .iter()
+        .map(|column| format!("target.{} = keyed.{}", quote_ident(column), quote_ident(column)))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+// UPDATE ... FROM the rows, matched on `key_columns`; every other column in the rows is set
+pub fn build_update_many(table: &str, rows: &[Map<String, Value>], key_columns: &[String], max_rows: usize) -> Result<Vec<KeyedStatement>, String> {
+    if key_columns.is_empty() {
+        return Err("update_many requires at least one key column".to_string());
+    }
+    let columns = shared_columns(rows, "Row")?;
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+    if let Some(missing) = key_columns.iter().find(|column| !columns.contains(*column)) {
+        return Err(format!("Rows have no value for key column '{}'", missing));
+    }
+    let assignments: Vec<String> = columns
+        .iter()
+        .filter(|column| !key_columns.contains(column))
+        .map(|column| format!("{} = keyed.{}", quote_ident(column), quote_ident(column)))
+        .collect();
+    if assignments.is_empty() {
+        return Err("update_many rows have no columns to set besides the key".to_string());
+    }
+    Ok(keyed(table, rows, max_rows, |source| {
+        format!(
+            "UPDATE {} AS target SET {} FROM {} WHERE {} RETURNING keyed.ordinality::int AS ordinality",
+            quote_qualified(table),
+            assignments.join(", "),
+            source,
+            key_match(key_columns)
+        )
+    }))
+}
+
+// DELETE ... USING the keys, matched on the columns they name
+pub fn build_delete_many(table: &str, keys: &[Map<String, Value>], max_rows: usize) -> Result<Vec<KeyedStatement>, String> {
+    let key_columns: Vec<String> = shared_columns(keys, "Key")?.into_iter().collect();
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    if key_columns.is_empty() {
+        return Err("delete_many keys must name at least one column".to_string());
+    }
+    Ok(keyed(table, keys, max_rows, |source| {
+        format!("DELETE FROM {} AS target USING {} WHERE {} RETURNING keyed.ordinality::int AS ordinality", quote_qualified(table), source, key_match(&key_columns))
+    }))
+}
+
+// Mark which of a statement's `rows` the RETURNed ordinalities matched. They are 1-based
+// within the statement, and a key can match several table rows. A bridge that sends
+// integers as strings is read as well.
+fn record_matches(summary: &mut BulkWriteSummary, rows: std::ops::Range<usize>, returned: &[Value]) {
+    summary.rows_affected += returned.len();
+    let ordinality = |row: &Value| match row.get("ordinality")? {
+        Value::String(text) => text.parse::<usize>().ok(),
+        value => value.as_u64().map(|n| n as usize),
+    };
+    let matched: BTreeSet<usize> = returned.iter().filter_map(ordinality).collect();
+    let first_row = rows.start;
+    summary.rows.extend(rows.map(|index| BulkRowResult { index, matched: matched.contains(&(index - first_row + 1)), error: None }));
+}
+
+// Run the keyed statements in order. A failing statement marks its rows with the error and
+// the rest still run, since earlier ones have already committed.
+pub(crate) async fn write_keyed(state: SharedState, statements: Vec<KeyedStatement>) -> BulkWriteSummary {
+    let mut summary = BulkWriteSummary::default();
+    for statement in statements {
+        let rows = statement.first_row..statement.first_row + statement.rows;
+        summary.statements += 1;
+        match execute_query(&state, &statement.query.sql, Some(statement.query.params)).await {
+            Ok(result) => {
+                let returned = result.get("rows").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+                record_matches(&mut summary, rows, returned);
+            }
+            Err(e) => {
+                let error = crate::errors::message_of(&e);
+                summary.rows.extend(rows.map(|index| BulkRowResult { index, matched: false, error: Some(error.clone()) }));
+            }
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn columns(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("c{}", i)).collect()
+    }
+
+    #[test]
+    fn test_build_insert_many_chunks_rows() {
+        let rows = vec![vec![json!(1), json!("a")], vec![json!(2), json!("b")], vec![json!(3), json!("c")]];
+        let statements = build_insert_many("items", &columns(2), &rows, 2).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].sql, "INSERT INTO \"items\" (\"c0\", \"c1\") VALUES ($1, $2), ($3, $4)");
+        assert_eq!(statements[1].sql, "INSERT INTO \"items\" (\"c0\", \"c1\") VALUES ($1, $2)");
+        assert_eq!(statements[1].params, vec![json!(3), json!("c")]);
+    }
+
+    #[test]
+    fn test_build_insert_many_respects_param_limit() {
+        let cols = columns(100);
+        let rows = vec![vec![json!(0); 100]; 700];
+        let statements = build_insert_many("wide", &cols, &rows, DEFAULT_ROWS_PER_STATEMENT).unwrap();
+        assert!(statements.iter().all(|s| s.params.len() <= MAX_BIND_PARAMS));
+        assert_eq!(statements[0].params.len(), 655 * 100);
+        assert_eq!(statements.len(), 2);
+
+        assert!(build_insert_many("t", &cols, &[vec![json!(1)]], 10).is_err());
+    }
+
+    #[test]
+    fn test_build_keyed_update_and_delete() {
+        let rows: Vec<Map<String, Value>> = serde_json::from_value(json!([{ "id": 1, "name": "a", "qty": 2 }, { "id": 2, "name": "b", "qty": 3 }, { "qty": 4, "name": "c", "id": 3 }])).unwrap();
+        let statements = build_update_many("shop.items", &rows, &["id".to_string()], 2).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0].query.sql,
+            "UPDATE \"shop\".\"items\" AS target SET \"name\" = keyed.\"name\", \"qty\" = keyed.\"qty\" FROM jsonb_populate_recordset(NULL::\"shop\".\"items\", $1::jsonb) WITH ORDINALITY AS keyed WHERE target.\"id\" = keyed.\"id\" RETURNING keyed.ordinality::int AS ordinality"
+        );
+        assert_eq!(statements[1].query.params, vec![json!([{ "id": 3, "name": "c", "qty": 4 }])]);
+        assert_eq!((statements[1].first_row, statements[1].rows), (2, 1));
+        assert!(build_update_many("items", &rows, &["sku".to_string()], 10).is_err());
+        assert!(build_update_many("items", &rows, &["id".to_string(), "name".to_string(), "qty".to_string()], 10).is_err());
+
+        let keys: Vec<Map<String, Value>> = serde_json::from_value(json!([{ "id": 1 }, { "sku": "x" }])).unwrap();
+        assert_eq!(build_delete_many("items", &keys, 10).unwrap_err(), "Key 1 doesn't have the same columns as the first");
+        let statements = build_delete_many("items", &keys[..1], 10).unwrap();
+        assert_eq!(
+            statements[0].query.sql,
+            "DELETE FROM \"items\" AS target USING jsonb_populate_recordset(NULL::\"items\", $1::jsonb) WITH ORDINALITY AS keyed WHERE target.\"id\" = keyed.\"id\" RETURNING keyed.ordinality::int AS ordinality"
+        );
+    }
+
+    #[test]
+    fn test_record_matches_reads_string_ordinality() {
+        let mut summary = BulkWriteSummary::default();
+        // Rows 4 to 6 of the caller's list; the node bridge sends int8 as a string
+        record_matches(&mut summary, 4..7, &[json!({ "ordinality": "1" }), json!({ "ordinality": 3 }), json!({ "ordinality": "3" })]);
+        assert_eq!(summary.rows_affected, 3);
+        let matched: Vec<(usize, bool)> = summary.rows.iter().map(|row| (row.index, row.matched)).collect();
+        assert_eq!(matched, vec![(4, true), (5, false), (6, true)]);
+    }
+}
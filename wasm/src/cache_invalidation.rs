@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, QueryOptions, SharedState};
+use crate::dispatch::Priority;
+use crate::query_cache::Dropped;
+use crate::sql_validate::{lex, Lex};
+
+// Options accepted by `invalidate_on_notify`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvalidationOptions {
+    // Run dropped queries again in the background and cache their fresh results
+    pub refresh: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct InvalidationRule {
+    pub tables: Vec<String>,
+    pub refresh: bool,
+}
+
+// The tables whose cached queries a NOTIFY on each channel makes stale
+#[derive(Debug, Default)]
+pub(crate) struct CacheInvalidation {
+    rules: HashMap<String, InvalidationRule>,
+}
+
+impl CacheInvalidation {
+    pub fn set(&mut self, channel: String, rule: InvalidationRule) {
+        self.rules.insert(channel, rule);
+    }
+
+    pub fn remove(&mut self, channel: &str) -> bool {
+        self.rules.remove(channel).is_some()
+    }
+
+    pub fn is_used(&self, channel: &str) -> bool {
+        self.rules.contains_key(channel)
+    }
+
+    pub fn rule(&self, channel: &str) -> Option<InvalidationRule> {
+        self.rules.get(channel).cloned()
+    }
+
+    pub fn rules(&self) -> Vec<(String, InvalidationRule)> {
+        self.rules.iter().map(|(channel, rule)| (channel.clone(), rule.clone())).collect()
+    }
+
+    pub fn tables(&self) -> Vec<String> {
+        self.rules.values().flat_map(|rule| rule.tables.iter().cloned()).collect()
+    }
+}
+
+// Whether `sql` names any of `tables`, bare, schema-qualified or quoted. Names match
+// case-insensitively, so a quoted table may drop a few entries too many; mentions in
+// literals and comments don't count.
+pub fn references(sql: &str, tables: &[String]) -> bool {
+    let chars: Vec<char> = sql.chars().collect();
+    let Ok(lexemes) = lex(&chars, false) else {
+        // Can't tell what it reads, so treat it as stale
+        return true;
+    };
+    let names: Vec<&str> = tables.iter().map(|table| table.rsplit('.').next().unwrap_or(table)).collect();
+    lexemes.iter().any(|(lexeme, _)| match lexeme {
+        Lex::Word(word, _) => names.iter().any(|name| name.eq_ignore_ascii_case(word)),
+        Lex::Value(text) if text.len() > 1 && text.starts_with('"') => {
+            let quoted = text[1..text.len() - 1].replace("\"\"", "\"");
+            names.iter().any(|name| name.eq_ignore_ascii_case(&quoted))
+        }
+        _ => false,
+    })
+}
+
+// Run a dropped query again and cache its result for the TTL it had
+async fn refresh(state: SharedState, dropped: Dropped) {
+    let options = QueryOptions { priority: Priority::Background, ..QueryOptions::default() };
+    match connection::execute_query_with(&state, &dropped.sql, dropped.params.clone(), options).await {
+        Ok(payload) => state.borrow_mut().query_cache.insert(&dropped.sql, &dropped.params, payload, dropped.ttl_ms, js_sys::Date::now()),
+        Err(e) => log_warn!("WASM failed to refresh a cached query after a NOTIFY: {:?}", e),
+    }
+}
+
+// A NOTIFY arrived: drop cached queries reading the channel's tables, and refresh them
+// if the rule asks to
+pub(crate) fn notify(state: &SharedState, channel: &str) {
+    let Some(rule) = state.borrow().cache_invalidation.rule(channel) else {
+        return;
+    };
+    let dropped = state.borrow_mut().query_cache.take_matching(|sql| references(sql, &rule.tables));
+    if dropped.is_empty() {
+        return;
+    }
+    log_debug!("WASM NOTIFY on '{}' invalidated {} cached queries", channel, dropped.len());
+    if rule.refresh {
+        for dropped in dropped {
+            wasm_bindgen_futures::spawn_local(refresh(state.clone(), dropped));
+        }
+    }
+}
+
+// NOTIFYs sent while the connection was down are lost, so nothing cached for a mapped
+// table can be trusted after a reconnect
+pub(crate) fn reconnected(state: &SharedState) {
+    let tables = state.borrow().cache_invalidation.tables();
+    if tables.is_empty() {
+        return;
+    }
+    let dropped = state.borrow_mut().query_cache.take_matching(|sql| references(sql, &tables));
+    if !dropped.is_empty() {
+        log_info!("WASM dropped {} cached queries that may have missed a NOTIFY", dropped.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_tables_by_name() {
+        let tables = vec!["public.orders".to_string(), "LineItems".to_string()];
+        assert!(references("SELECT * FROM Orders WHERE id = $1", &tables));
+        assert!(references("SELECT count(*) FROM public.lineitems", &tables));
+        assert!(references("SELECT * FROM \"LineItems\" JOIN x USING (id)", &tables));
+        assert!(!references("SELECT 'orders' AS label -- orders\nFROM customers", &tables));
+        assert!(!references("SELECT * FROM orders_archive", &tables));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+
+// One row change decoded from the bridge's logical replication stream
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChangeEvent {
+    pub action: &'static str,
+    pub schema: String,
+    pub table: String,
+    // Row image after the change; None for deletes
+    pub new: Option<Map<String, Value>>,
+    // Replica identity before the change (primary key columns by default); None for inserts
+    pub old: Option<Map<String, Value>>,
+}
+
+fn row_image(columns: Option<&Value>) -> Option<Map<String, Value>> {
+    let columns = columns?.as_array()?;
+    Some(
+        columns
+            .iter()
+            .filter_map(|c| Some((c.get("name")?.as_str()?.to_string(), c.get("value").cloned().unwrap_or(Value::Null))))
+            .collect(),
+    )
+}
+
+// Parse a wal2json format-version 2 record; transaction markers and messages yield None
+pub fn parse_wal2json(record: &Value) -> Option<ChangeEvent> {
+    let action = match record.get("action")?.as_str()? {
+        "I" => "insert",
+        "U" => "update",
+        "D" => "delete",
+        "T" => "truncate",
+        _ => return None,
+    };
+    let text = |key: &str| record.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    Some(ChangeEvent {
+        action,
+        schema: text("schema"),
+        table: text("table"),
+        new: if action == "delete" { None } else { row_image(record.get("columns")) },
+        old: row_image(record.get("identity")),
+    })
+}
+
+// Change subscriptions keyed by the id the bridge assigned
+#[derive(Default)]
+pub(crate) struct ChangeSubscriptions {
+    callbacks: HashMap<String, js_sys::Function>,
+}
+
+impl ChangeSubscriptions {
+    pub fn callback(&self, subscription: &str) -> Option<js_sys::Function> {
+        self.callbacks.get(subscription).cloned()
+    }
+
+    pub fn has_subscriptions(&self) -> bool {
+        !self.callbacks.is_empty()
+    }
+}
+
+pub(crate) async fn subscribe(state: &SharedState, tables: Vec<String>, callback: js_sys::Function) -> Result<String, JsValue> {
+    let response = connection::request(state, "cdc", "subscribe_changes", json!({ "tables": tables })).await?;
+    let subscription = response
+        .payload
+        .get("subscription")
+        .and_then(|s| s.as_str())
+        .ok_or_else(|| JsValue::from_str("Bridge did not return a subscription id"))?
+        .to_string();
+    state.borrow_mut().changes.callbacks.insert(subscription.clone(), callback);
+    Ok(subscription)
+}
+
+pub(crate) async fn unsubscribe(state: &SharedState, subscription: &str) -> Result<bool, JsValue> {
+    if state.borrow_mut().changes.callbacks.remove(subscription).is_none() {
+        return Ok(false);
+    }
+    connection::request(state, "cdc", "unsubscribe_changes", json!({ "subscription": subscription })).await?;
+    Ok(true)
+}
+
+// Deliver a `change` frame's payload to its subscription callback
+pub(crate) fn deliver(state: &SharedState, payload: &Value) {
+    let callback = payload
+        .get("subscription")
+        .and_then(|s| s.as_str())
+        .and_then(|s| state.borrow().changes.callback(s));
+    let (Some(callback), Some(event)) = (callback, payload.get("change").and_then(parse_wal2json)) else {
+        return;
+    };
+    match crate::to_js(&event) {
+        Ok(event) => {
+            let _ = callback.call1(&JsValue::NULL, &event);
+        }
+        Err(e) => log_warn!("WASM failed to convert change event: {:?}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_update() {
+        let record = json!({
+            "action": "U",
+            "schema": "public",
+            "table": "users",
+            "columns": [{"name": "id", "type": "integer", "value": 1}, {"name": "name", "type": "text", "value": "b"}],
+            "identity": [{"name": "id", "type": "integer", "value": 1}]
+        });
+        let event = parse_wal2json(&record).unwrap();
+        assert_eq!(event.action, "update");
+        assert_eq!(event.table, "users");
+        assert_eq!(event.new.unwrap().get("name"), Some(&json!("b")));
+        assert_eq!(event.old.unwrap().get("id"), Some(&json!(1)));
+    }
+
+    #[test]
+    fn test_parse_delete_and_markers() {
+        let record = json!({
+            "action": "D",
+            "schema": "public",
+            "table": "users",
+            "identity": [{"name": "id", "type": "integer", "value": 7}]
+        });
+        let event = parse_wal2json(&record).unwrap();
+        assert_eq!(event.new, None);
+        assert_eq!(event.old.unwrap().get("id"), Some(&json!(7)));
+
+        assert_eq!(parse_wal2json(&json!({"action": "B"})), None);
+        assert_eq!(parse_wal2json(&json!({"action": "C"})), None);
+    }
+}
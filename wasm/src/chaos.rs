@@ -0,0 +1,145 @@
+use serde::Deserialize;
+use tsify::Tsify;
+
+use crate::connection::SharedState;
+
+// Faults injected into the frames the client receives, for testing retry, backoff and
+// UI behavior against a flaky bridge. Never enable this in production.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChaosOptions {
+    // Added to every frame before it is processed
+    #[serde(rename = "latencyMs")]
+    pub latency_ms: f64,
+    // Up to this much more, at random per frame
+    #[serde(rename = "jitterMs")]
+    pub jitter_ms: f64,
+    // Fraction of frames silently discarded
+    #[serde(rename = "dropRate")]
+    pub drop_rate: f64,
+    // Chance per frame that the connection is closed instead
+    #[serde(rename = "disconnectRate")]
+    pub disconnect_rate: f64,
+    // Makes the sequence of faults repeatable
+    pub seed: Option<u32>,
+}
+
+impl ChaosOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, ms) in [("latencyMs", self.latency_ms), ("jitterMs", self.jitter_ms)] {
+            if !(ms.is_finite() && ms >= 0.0) {
+                return Err(format!("{} must be a non-negative number", name));
+            }
+        }
+        for (name, rate) in [("dropRate", self.drop_rate), ("disconnectRate", self.disconnect_rate)] {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("{} must be between 0 and 1", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// What happens to one received frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fate {
+    // Process it after this many milliseconds
+    Deliver(f64),
+    Drop,
+    Disconnect,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chaos {
+    options: ChaosOptions,
+    rng: u32,
+    // When the latest delayed frame is due, so delays never reorder frames
+    last_due: f64,
+}
+
+impl Chaos {
+    pub fn new(options: ChaosOptions, seed: u32) -> Chaos {
+        let rng = options.seed.unwrap_or(seed).max(1);
+        Chaos { options, rng, last_due: 0.0 }
+    }
+
+    // xorshift32, in [0, 1)
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f64 / (u32::MAX as f64 + 1.0)
+    }
+
+    pub fn decide(&mut self, now: f64) -> Fate {
+        if self.random() < self.options.disconnect_rate {
+            return Fate::Disconnect;
+        }
+        if self.random() < self.options.drop_rate {
+            return Fate::Drop;
+        }
+        let delay = self.options.latency_ms + self.options.jitter_ms * self.random();
+        let due = (now + delay).max(self.last_due);
+        self.last_due = due;
+        Fate::Deliver(due - now)
+    }
+}
+
+// Pass a received frame on through `process`, unless chaos mode drops or delays it or
+// closes the connection instead
+pub(crate) fn receive(state: &SharedState, text: &str, process: impl FnOnce(&SharedState, &str) + 'static) {
+    let fate = {
+        let mut state = state.borrow_mut();
+        match state.chaos.as_mut() {
+            Some(chaos) => chaos.decide(js_sys::Date::now()),
+            None => Fate::Deliver(0.0),
+        }
+    };
+    match fate {
+        Fate::Deliver(delay) if delay <= 0.0 => process(state, text),
+        Fate::Deliver(delay) => {
+            let state = state.clone();
+            let text = text.to_string();
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = crate::retry::sleep(delay).await;
+                process(&state, &text);
+            });
+        }
+        Fate::Drop => log_warn!("WASM chaos: dropped a {} byte frame", text.len()),
+        Fate::Disconnect => {
+            log_warn!("WASM chaos: closing the connection");
+            crate::transport::drop_connection(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fates_follow_rates_and_keep_order() {
+        let options = ChaosOptions { latency_ms: 50.0, jitter_ms: 100.0, drop_rate: 0.25, seed: Some(7), ..ChaosOptions::default() };
+        let mut chaos = Chaos::new(options.clone(), 1);
+        let mut due = Vec::new();
+        let mut drops = 0;
+        for i in 0..1000 {
+            let now = i as f64;
+            match chaos.decide(now) {
+                Fate::Deliver(delay) => {
+                    assert!(delay >= 0.0);
+                    due.push(now + delay);
+                }
+                Fate::Drop => drops += 1,
+                Fate::Disconnect => panic!("disconnectRate is 0"),
+            }
+        }
+        assert!((200..300).contains(&drops), "{} drops", drops);
+        assert!(due.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // The same seed replays the same faults
+        let (mut first, mut second) = (Chaos::new(options.clone(), 1), Chaos::new(options, 99));
+        assert!((0..100).all(|i| first.decide(i as f64) == second.decide(i as f64)));
+        assert!(ChaosOptions { drop_rate: 1.5, ..ChaosOptions::default() }.validate().is_err());
+    }
+}
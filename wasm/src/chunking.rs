@@ -0,0 +1,123 @@
+use bridge_protocol::chunking::{split_frame, ChunkAssembler, DEFAULT_MAX_MESSAGE_SIZE};
+use bridge_protocol::compression::DEFAULT_COMPRESSION_THRESHOLD;
+use bridge_protocol::{ChunkPayload, FrameError, WebSocketMessage};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{ClientState, SharedState};
+use crate::errors::BridgeError;
+
+// Frame size limits, compression settings and reassembly of chunked messages
+pub(crate) struct Framing {
+    // Largest frame accepted from the bridge, announced in `hello`
+    pub max_message_size: usize,
+    pub compression_threshold: usize,
+    pub assembler: ChunkAssembler,
+}
+
+impl Default for Framing {
+    fn default() -> Framing {
+        Framing {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            compression_threshold: DEFAULT_COMPRESSION_THRESHOLD,
+            assembler: ChunkAssembler::default(),
+        }
+    }
+}
+
+fn size_error(message: String, id: Option<String>) -> BridgeError {
+    BridgeError::from_frame_error(&FrameError::TooLarge(message), id)
+}
+
+// Frames to put on the wire for one serialized message: the message itself, or
+// `chunk` pieces when it exceeds the limit the bridge announced
+pub(crate) fn split_outgoing(state: &mut ClientState, id: Option<&str>, frame: String) -> Result<Vec<String>, JsValue> {
+    let Some(negotiated) = state.protocol.negotiated else {
+        return Ok(vec![frame]);
+    };
+    match negotiated.peer_max_message_size {
+        Some(limit) if frame.len() > limit => {
+            if !negotiated.capabilities.chunking {
+                let message = format!(
+                    "Message of {} bytes exceeds the bridge's {} byte limit and the bridge cannot reassemble chunks",
+                    frame.len(),
+                    limit
+                );
+                return Err(size_error(message, id.map(String::from)).into());
+            }
+            let group = match id {
+                Some(id) => id.to_string(),
+                None => state.next_message_id("chunk"),
+            };
+            let chunks = split_frame(&frame, limit, &group).map_err(|e| BridgeError::from_frame_error(&e, id.map(String::from)))?;
+            log_debug!("WASM split {} byte message into {} chunks", frame.len(), chunks.len());
+            Ok(chunks.iter().map(WebSocketMessage::to_json).collect())
+        }
+        _ => Ok(vec![frame]),
+    }
+}
+
+// `split_frame` always serializes `type` first, so chunks are recognisable without a parse
+fn is_chunk(text: &str) -> bool {
+    text.starts_with(r#"{"type":"chunk""#)
+}
+
+// Entry point for every raw frame from the transport: enforce the size limit, join
+// chunks, then dispatch complete messages
+pub(crate) fn receive_frame(state: &SharedState, text: &str) {
+    let limit = state.borrow().framing.max_message_size;
+    if text.len() > limit {
+        let id = serde_json::from_str::<WebSocketMessage>(text).ok().and_then(|m| m.id);
+        let message = format!("Received a {} byte frame, above the {} byte limit", text.len(), limit);
+        log_error!("WASM {}", message);
+        if let Some(id) = id {
+            state.borrow_mut().fail_request(&id, size_error(message, Some(id.clone())));
+        }
+        return;
+    }
+    if !is_chunk(text) {
+        crate::encryption::receive(state, text);
+        return;
+    }
+
+    let chunk = match serde_json::from_str::<WebSocketMessage>(text).map(|m| serde_json::from_value::<ChunkPayload>(m.payload)) {
+        Ok(Ok(chunk)) => chunk,
+        _ => return crate::resync::recover(state, text, "Malformed chunk"),
+    };
+    let message_id = chunk.message_id.clone();
+    let assembled = state.borrow_mut().framing.assembler.accept(chunk, js_sys::Date::now() as u64);
+    match assembled {
+        Ok(Some(frame)) => crate::encryption::receive(state, &frame),
+        Ok(None) => {}
+        Err(e) => {
+            log_error!("WASM failed to reassemble message {}: {}", message_id, e);
+            let error = match (&e, state.borrow().result_budget.max_bytes) {
+                (FrameError::TooLarge(_), Some(max)) => {
+                    crate::result_budget::too_large(&format!("the message is over the {} byte budget", max), Some(message_id.clone()))
+                }
+                _ => BridgeError::from_frame_error(&e, Some(message_id.clone())),
+            };
+            state.borrow_mut().fail_request(&message_id, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_protocol::{Capabilities, Negotiated};
+
+    #[test]
+    fn test_split_outgoing_respects_negotiated_limit() {
+        let mut state = ClientState::default();
+        let frame = "x".repeat(500);
+        assert_eq!(split_outgoing(&mut state, Some("q1"), frame.clone()).unwrap().len(), 1);
+
+        state.protocol.negotiated = Some(Negotiated {
+            version: 1,
+            capabilities: Capabilities { chunking: true, ..Capabilities::default() },
+            peer_max_message_size: Some(200),
+        });
+        let frames = split_outgoing(&mut state, Some("q1"), frame).unwrap();
+        assert!(frames.len() > 2 && frames.iter().all(|f| is_chunk(f) && f.len() <= 200));
+    }
+}
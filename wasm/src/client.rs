@@ -0,0 +1,229 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use bridge_protocol::chunking::DEFAULT_MAX_MESSAGE_SIZE;
+use bridge_protocol::{message_type, Capabilities, HelloPayload, Negotiated, QueryPayload, QueryResult, WebSocketMessage};
+use serde_json::Value;
+
+use crate::errors::BridgeError;
+use crate::transport::Transport;
+
+// The answer to one request, filled in by `Client::receive`
+#[derive(Default)]
+struct Slot {
+    response: Option<Result<WebSocketMessage, BridgeError>>,
+    waker: Option<Waker>,
+}
+
+struct Response {
+    slot: Rc<RefCell<Slot>>,
+}
+
+impl Future for Response {
+    type Output = Result<WebSocketMessage, BridgeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.response.take() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// A bridge client for plain Rust: no wasm-bindgen types, errors are `BridgeError`. The
+// caller owns the transport's event loop and hands every received frame to `receive`.
+// It speaks the base protocol only, so the bridge never compresses, chunks or encrypts
+// what it sends back.
+pub struct Client<T: Transport> {
+    transport: T,
+    next_id: Cell<u64>,
+    pending: RefCell<HashMap<String, Rc<RefCell<Slot>>>>,
+    negotiated: Cell<Option<Negotiated>>,
+    tenant_id: Option<String>,
+}
+
+impl<T: Transport> Client<T> {
+    pub fn new(transport: T) -> Client<T> {
+        Client { transport, next_id: Cell::new(0), pending: RefCell::default(), negotiated: Cell::new(None), tenant_id: None }
+    }
+
+    // Sent with every query for the bridge to check against the key's tenant
+    pub fn with_tenant(mut self, tenant_id: &str) -> Client<T> {
+        self.tenant_id = Some(tenant_id.to_string());
+        self
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    // What `hello` agreed on; `None` before it completes
+    pub fn negotiated(&self) -> Option<Negotiated> {
+        self.negotiated.get()
+    }
+
+    // Exchange `hello`. A bridge that predates it answers with an error, which leaves
+    // version 0 and no capabilities.
+    pub async fn hello(&self) -> Result<Negotiated, BridgeError> {
+        let ours = HelloPayload::new(0, Capabilities::default(), DEFAULT_MAX_MESSAGE_SIZE);
+        let response = self.send(message_type::HELLO, bridge_protocol::to_value(&ours))?.await?;
+        let negotiated = crate::handshake::negotiate(&ours, &response.message_type, &response.payload)
+            .map_err(|message| BridgeError::connection(&message))?;
+        self.negotiated.set(Some(negotiated));
+        Ok(negotiated)
+    }
+
+    pub async fn authenticate(&self, token: &str) -> Result<(), BridgeError> {
+        self.request(message_type::AUTH, serde_json::json!({ "token": token })).await.map(|_| ())
+    }
+
+    pub async fn query(&self, sql: &str, params: Vec<Value>) -> Result<QueryResult, BridgeError> {
+        let payload = QueryPayload {
+            sql: sql.to_string(),
+            params: Some(params),
+            traceparent: None,
+            idempotency_key: None,
+            statement_timeout_ms: None,
+            tenant_id: self.tenant_id.clone(),
+            raw: false,
+            cache: None,
+            page_size: None,
+            binary_params: None,
+            tags: None,
+            types: None,
+        };
+        let response = self.request(message_type::QUERY, bridge_protocol::to_value(&payload)).await?;
+        serde_json::from_value(response.payload).map_err(|e| BridgeError::connection(&format!("Invalid query result from bridge: {}", e)))
+    }
+
+    // Send any request and wait for its answer; an `error` answer becomes the error
+    pub async fn request(&self, kind: &str, payload: Value) -> Result<WebSocketMessage, BridgeError> {
+        let response = self.send(kind, payload)?.await?;
+        if response.message_type == message_type::ERROR {
+            return Err(BridgeError::from_error_payload(&response.payload, response.id));
+        }
+        Ok(response)
+    }
+
+    fn send(&self, kind: &str, payload: Value) -> Result<Response, BridgeError> {
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        let id = format!("{}_{}", kind, id);
+        let message = WebSocketMessage::new(kind, payload, Some(id.clone()));
+        self.transport.send(&message.to_json())?;
+        let slot = Rc::new(RefCell::new(Slot::default()));
+        self.pending.borrow_mut().insert(id, slot.clone());
+        Ok(Response { slot })
+    }
+
+    // Hand over a frame from the transport. Answers complete their request; anything
+    // else, such as a notification, is returned for the caller to handle.
+    pub fn receive(&self, frame: &str) -> Result<Option<WebSocketMessage>, BridgeError> {
+        let message: WebSocketMessage =
+            serde_json::from_str(frame).map_err(|e| BridgeError::connection(&format!("Malformed frame from bridge: {}", e)))?;
+        let slot = message.id.as_deref().and_then(|id| self.pending.borrow_mut().remove(id));
+        match slot {
+            Some(slot) => {
+                let mut slot = slot.borrow_mut();
+                slot.response = Some(Ok(message));
+                if let Some(waker) = slot.waker.take() {
+                    waker.wake();
+                }
+                Ok(None)
+            }
+            None => Ok(Some(message)),
+        }
+    }
+
+    // The transport closed: every request still waiting fails with `ConnectionLost`
+    pub fn closed(&self, reason: &str) {
+        let error = BridgeError::connection_lost(reason);
+        for (id, slot) in self.pending.borrow_mut().drain() {
+            let mut slot = slot.borrow_mut();
+            slot.response = Some(Err(error.clone().with_query_id(&id)));
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Recorded {
+        sent: RefCell<Vec<WebSocketMessage>>,
+    }
+
+    impl Transport for Rc<Recorded> {
+        fn send(&self, text: &str) -> Result<(), BridgeError> {
+            self.sent.borrow_mut().push(serde_json::from_str(text).unwrap());
+            Ok(())
+        }
+
+        fn close(&self) {}
+
+        fn is_open(&self) -> bool {
+            true
+        }
+    }
+
+    fn poll<F: Future>(future: &mut Pin<Box<F>>) -> Poll<F::Output> {
+        future.as_mut().poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    // Answer the last request sent with a message of `kind`
+    fn answer(client: &Client<Rc<Recorded>>, kind: &str, payload: Value) -> Result<Option<WebSocketMessage>, BridgeError> {
+        let id = client.transport().sent.borrow().last().unwrap().id.clone();
+        client.receive(&WebSocketMessage::new(kind, payload, id).to_json())
+    }
+
+    #[test]
+    fn test_client_runs_queries_without_js() {
+        let client = Client::new(Rc::new(Recorded::default())).with_tenant("acme");
+
+        let mut hello = Box::pin(client.hello());
+        assert!(poll(&mut hello).is_pending());
+        let server = HelloPayload::new(1, Capabilities { compression: true, ..Capabilities::default() }, 0);
+        answer(&client, message_type::HELLO, bridge_protocol::to_value(&server)).unwrap();
+        let Poll::Ready(Ok(negotiated)) = poll(&mut hello) else { panic!("hello did not complete") };
+        assert_eq!(negotiated.version, 1);
+        assert!(!negotiated.capabilities.compression);
+
+        let mut query = Box::pin(client.query("SELECT $1::int AS n", vec![json!(1)]));
+        assert!(poll(&mut query).is_pending());
+        let sent = client.transport().sent.borrow().last().unwrap().clone();
+        assert_eq!(sent.payload["tenantId"], "acme");
+        let result = json!({ "sql": "SELECT $1::int AS n", "params": [1], "rows": [{ "n": 1 }], "rowCount": 1, "executionTime": 2.0, "timestamp": "now" });
+        answer(&client, message_type::RESULT, result).unwrap();
+        let Poll::Ready(Ok(result)) = poll(&mut query) else { panic!("query did not complete") };
+        assert_eq!(result.rows, vec![json!({ "n": 1 })]);
+
+        let mut failing = Box::pin(client.query("SELECT nope", Vec::new()));
+        assert!(poll(&mut failing).is_pending());
+        answer(&client, message_type::ERROR, json!({ "message": "column does not exist", "code": "DATABASE_ERROR", "sqlState": "42703" })).unwrap();
+        let Poll::Ready(Err(error)) = poll(&mut failing) else { panic!("query did not fail") };
+        assert_eq!(error.sql_state(), Some("42703"));
+
+        let notification = json!({ "channel": "orders", "payload": "1" });
+        let unsolicited = client.receive(&WebSocketMessage::new(message_type::NOTIFICATION, notification, None).to_json()).unwrap();
+        assert_eq!(unsolicited.map(|m| m.message_type).as_deref(), Some(message_type::NOTIFICATION));
+
+        let mut lost = Box::pin(client.query("SELECT 1", Vec::new()));
+        assert!(poll(&mut lost).is_pending());
+        client.closed("socket closed");
+        let Poll::Ready(Err(error)) = poll(&mut lost) else { panic!("query did not fail") };
+        assert!(matches!(error, BridgeError::ConnectionLost { .. }));
+    }
+}
@@ -0,0 +1,105 @@
+use bridge_protocol::{close_code, close_reason};
+use serde::Serialize;
+use tsify::Tsify;
+
+use crate::errors::BridgeError;
+
+// Why the connection closed, from its close code or the reason the bridge gave
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "camelCase")]
+pub enum CloseKind {
+    // Closed on purpose by the client or the bridge
+    Normal,
+    // The session's credentials ran out
+    AuthExpired,
+    // The bridge is stopping or restarting
+    Shutdown,
+    // A peer broke the protocol, so repeating the same requests won't help
+    ProtocolViolation,
+    // The bridge closed a connection that had been idle
+    IdleTimeout,
+    // Dropped without a reason, e.g. by the network
+    Abnormal,
+}
+
+// What an application is expected to do about a close
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum CloseAction {
+    None,
+    // Sign in again, then reconnect with the new credentials
+    Relogin,
+    // Nothing: the client reconnects on its own or with the next query
+    Retry,
+    // Report it; reconnecting would fail the same way
+    Surface,
+}
+
+impl CloseKind {
+    pub fn classify(code: u16, reason: &str) -> CloseKind {
+        let token = reason.split(':').next().unwrap_or_default().trim();
+        match (code, token) {
+            (close_code::AUTH_EXPIRED, _) | (_, close_reason::AUTH_EXPIRED) => CloseKind::AuthExpired,
+            (close_code::SHUTDOWN, _) | (_, close_reason::SHUTDOWN) => CloseKind::Shutdown,
+            (close_code::PROTOCOL_VIOLATION, _) | (_, close_reason::PROTOCOL_VIOLATION) => CloseKind::ProtocolViolation,
+            (close_code::IDLE_TIMEOUT, _) | (_, close_reason::IDLE_TIMEOUT) => CloseKind::IdleTimeout,
+            (1000 | 1005, _) => CloseKind::Normal,
+            // Going away
+            (1001, _) => CloseKind::Shutdown,
+            // Protocol error, unsupported or invalid data, policy violation, message too big
+            (1002 | 1003 | 1007 | 1008 | 1009, _) => CloseKind::ProtocolViolation,
+            _ => CloseKind::Abnormal,
+        }
+    }
+
+    pub fn action(self) -> CloseAction {
+        match self {
+            CloseKind::Normal => CloseAction::None,
+            CloseKind::AuthExpired => CloseAction::Relogin,
+            CloseKind::Shutdown | CloseKind::IdleTimeout | CloseKind::Abnormal => CloseAction::Retry,
+            CloseKind::ProtocolViolation => CloseAction::Surface,
+        }
+    }
+
+    // Whether the reconnect policy reopens the connection
+    pub fn reconnects(self) -> bool {
+        matches!(self, CloseKind::Shutdown | CloseKind::Abnormal)
+    }
+
+    // Whether the close counts against the circuit breaker
+    pub fn is_failure(self) -> bool {
+        self == CloseKind::Abnormal
+    }
+
+    // What requests cut short by the close fail with
+    pub fn error(self, reason: &str) -> BridgeError {
+        let reason = if reason.is_empty() { "no reason given" } else { reason };
+        match self {
+            CloseKind::Normal | CloseKind::Abnormal => BridgeError::connection_lost("WebSocket closed before a response arrived"),
+            CloseKind::AuthExpired => BridgeError::closed("AUTH_EXPIRED", &format!("Authentication expired: {}", reason)),
+            CloseKind::Shutdown => BridgeError::closed("SERVER_RESTARTING", &format!("Server is restarting: {}", reason)),
+            CloseKind::ProtocolViolation => BridgeError::closed("PROTOCOL_VIOLATION", &format!("Connection closed for a protocol violation: {}", reason)),
+            CloseKind::IdleTimeout => BridgeError::closed("IDLE_TIMEOUT", &format!("Connection closed for inactivity: {}", reason)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_codes_and_reasons() {
+        assert_eq!(CloseKind::classify(1000, ""), CloseKind::Normal);
+        assert_eq!(CloseKind::classify(1006, ""), CloseKind::Abnormal);
+        assert_eq!(CloseKind::classify(close_code::SHUTDOWN, "shutdown: The bridge is shutting down"), CloseKind::Shutdown);
+        // A bridge limited to standard codes still says why in the reason
+        assert_eq!(CloseKind::classify(1008, "auth_expired: token expired"), CloseKind::AuthExpired);
+        assert_eq!(CloseKind::classify(1008, "not allowed"), CloseKind::ProtocolViolation);
+        assert_eq!(CloseKind::classify(close_code::IDLE_TIMEOUT, "").action(), CloseAction::Retry);
+
+        let error = CloseKind::AuthExpired.error("token expired");
+        assert_eq!((error.name(), error.code()), ("BridgeConnectionError", Some("AUTH_EXPIRED")));
+        assert_eq!(CloseKind::Abnormal.error("").code(), Some("CONNECTION_LOST"));
+    }
+}
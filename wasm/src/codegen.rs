@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+
+// Column listing used by schema introspection and code generation
+pub const INTROSPECTION_SQL: &str = "SELECT table_schema, table_name, column_name, udt_name, \
+    is_nullable = 'YES' AS is_nullable, ordinal_position \
+    FROM information_schema.columns \
+    WHERE table_schema NOT IN ('pg_catalog', 'information_schema') \
+    ORDER BY table_schema, table_name, ordinal_position";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColumnInfo {
+    pub table_schema: String,
+    pub table_name: String,
+    pub column_name: String,
+    pub udt_name: String,
+    pub is_nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub schema: String,
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+}
+
+// Group introspected columns by table, keeping the query's ordering
+pub fn group_tables(columns: Vec<ColumnInfo>) -> Vec<TableInfo> {
+    let mut tables: Vec<TableInfo> = Vec::new();
+    for column in columns {
+        match tables.last_mut() {
+            Some(table) if table.schema == column.table_schema && table.name == column.table_name => {
+                table.columns.push(column);
+            }
+            _ => tables.push(TableInfo {
+                schema: column.table_schema.clone(),
+                name: column.table_name.clone(),
+                columns: vec![column],
+            }),
+        }
+    }
+    tables
+}
+
+// Words a TS property is quoted for, though most would parse bare
+const TS_RESERVED: &[&str] = &[
+    "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do", "else", "enum", "export",
+    "extends", "false", "finally", "for", "function", "if", "import", "in", "instanceof", "new", "null", "return", "super",
+    "switch", "this", "throw", "true", "try", "type", "typeof", "var", "void", "while", "with",
+];
+
+// Rust keywords, written as raw identifiers
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl",
+    "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+fn pascal_case(name: &str) -> String {
+    let name: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    match name.chars().next() {
+        Some(first) if first.is_ascii_alphabetic() => name,
+        _ => format!("T{}", name),
+    }
+}
+
+// `base`, or `base2`, `base3`... when it is already taken
+fn unique(base: String, taken: &mut Vec<String>, separator: &str) -> String {
+    let mut name = base.clone();
+    let mut n = 1;
+    while taken.contains(&name) {
+        n += 1;
+        name = format!("{}{}{}", base, separator, n);
+    }
+    taken.push(name.clone());
+    name
+}
+
+// A TS property name: bare when it is a plain identifier, quoted otherwise
+fn ts_property(name: &str) -> String {
+    let mut chars = name.chars();
+    let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if identifier && !TS_RESERVED.contains(&name) {
+        name.to_string()
+    } else {
+        serde_json::Value::String(name.to_string()).to_string()
+    }
+}
+
+// A snake_case Rust field name for a column
+fn rust_field(name: &str) -> String {
+    let mut field = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && !field.is_empty() && !field.ends_with('_') {
+            field.push('_');
+        }
+        match c {
+            c if c.is_ascii_alphanumeric() => field.push(c.to_ascii_lowercase()),
+            _ if !field.ends_with('_') => field.push('_'),
+            _ => {}
+        }
+    }
+    let field = field.trim_matches('_').to_string();
+    match field.as_str() {
+        "" => "column".to_string(),
+        "self" | "super" | "crate" => format!("{}_", field),
+        _ if field.starts_with(|c: char| c.is_ascii_digit()) => format!("_{}", field),
+        _ => field,
+    }
+}
+
+fn ts_type(udt_name: &str) -> String {
+    if let Some(element) = udt_name.strip_prefix('_') {
+        return format!("{}[]", ts_type(element));
+    }
+    match udt_name {
+        "int2" | "int4" | "float4" | "float8" | "oid" => "number",
+        // 64-bit and arbitrary precision values arrive as strings to avoid precision loss
+        "int8" | "numeric" | "money" => "string",
+        "bool" => "boolean",
+        "json" | "jsonb" => "unknown",
+        _ => "string",
+    }
+    .to_string()
+}
+
+fn rust_type(udt_name: &str) -> String {
+    if let Some(element) = udt_name.strip_prefix('_') {
+        return format!("Vec<{}>", rust_type(element));
+    }
+    match udt_name {
+        "int2" => "i16",
+        "int4" => "i32",
+        // Sent as a string, like numeric; see `ts_type`
+        "int8" => "String",
+        "float4" => "f32",
+        "float8" => "f64",
+        "oid" => "u32",
+        "bool" => "bool",
+        "json" | "jsonb" => "serde_json::Value",
+        _ => "String",
+    }
+    .to_string()
+}
+
+fn type_name(table: &TableInfo) -> String {
+    if table.schema == "public" {
+        pascal_case(&table.name)
+    } else {
+        pascal_case(&format!("{}_{}", table.schema, table.name))
+    }
+}
+
+// One type name per table; tables whose names collide get a number after the first
+fn type_names(tables: &[TableInfo]) -> Vec<String> {
+    let mut taken = Vec::new();
+    tables.iter().map(|table| unique(type_name(table), &mut taken, "")).collect()
+}
+
+pub fn generate_typescript(tables: &[TableInfo]) -> String {
+    let mut out = String::from("// Generated from the database schema. Do not edit by hand.\n");
+    for (table, name) in tables.iter().zip(type_names(tables)) {
+        out.push_str(&format!("\nexport interface {} {{\n", name));
+        for column in &table.columns {
+            let nullable = if column.is_nullable { " | null" } else { "" };
+            out.push_str(&format!(
+                "  {}: {}{};\n",
+                ts_property(&column.column_name),
+                ts_type(&column.udt_name),
+                nullable
+            ));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+pub fn generate_rust(tables: &[TableInfo]) -> String {
+    let mut out = String::from("// Generated from the database schema. Do not edit by hand.\n");
+    out.push_str("use serde::{Deserialize, Serialize};\n");
+    for (table, name) in tables.iter().zip(type_names(tables)) {
+        out.push_str("\n#[derive(Serialize, Deserialize, Debug, Clone)]\n");
+        out.push_str(&format!("pub struct {} {{\n", name));
+        let mut fields = Vec::new();
+        for column in &table.columns {
+            let base = rust_type(&column.udt_name);
+            let ty = if column.is_nullable { format!("Option<{}>", base) } else { base };
+            let field = unique(rust_field(&column.column_name), &mut fields, "_");
+            if field != column.column_name {
+                out.push_str(&format!("    #[serde(rename = {})]\n", serde_json::Value::String(column.column_name.clone())));
+            }
+            let field = if RUST_KEYWORDS.contains(&field.as_str()) { format!("r#{}", field) } else { field };
+            out.push_str(&format!("    pub {}: {},\n", field, ty));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+// Run the introspection query through the bridge
+pub(crate) async fn introspect(state: &SharedState) -> Result<Vec<TableInfo>, JsValue> {
+    let result = execute_query(state, INTROSPECTION_SQL, None).await?;
+    let rows = result.get("rows").cloned().unwrap_or(serde_json::Value::Array(Vec::new()));
+    let columns: Vec<ColumnInfo> = serde_json::from_value(rows)
+        .map_err(|e| JsValue::from_str(&format!("Unexpected introspection result: {}", e)))?;
+    Ok(group_tables(columns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(table: &str, name: &str, udt: &str, nullable: bool) -> ColumnInfo {
+        ColumnInfo {
+            table_schema: "public".to_string(),
+            table_name: table.to_string(),
+            column_name: name.to_string(),
+            udt_name: udt.to_string(),
+            is_nullable: nullable,
+        }
+    }
+
+    #[test]
+    fn test_generate_typescript() {
+        let tables = group_tables(vec![
+            column("user_accounts", "id", "int4", false),
+            column("user_accounts", "tags", "_text", true),
+        ]);
+        assert_eq!(
+            generate_typescript(&tables),
+            "// Generated from the database schema. Do not edit by hand.\n\n\
+             export interface UserAccounts {\n  id: number;\n  tags: string[] | null;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_rust() {
+        let tables = group_tables(vec![
+            column("posts", "id", "int8", false),
+            column("posts", "meta", "jsonb", true),
+            column("users", "id", "int4", false),
+        ]);
+        assert_eq!(tables.len(), 2);
+        let code = generate_rust(&tables);
+        assert!(code.contains("pub struct Posts {\n    pub id: String,\n    pub meta: Option<serde_json::Value>,\n}"));
+        assert!(code.contains("pub struct Users {"));
+    }
+
+    #[test]
+    fn test_awkward_names_stay_valid() {
+        let mut columns = vec![
+            column("user_info", "type", "text", false),
+            column("user_info", "order-id", "int4", false),
+            column("user_info", "CamelCase", "int4", false),
+            column("user_info", "camel_case", "int4", false),
+        ];
+        columns.push(ColumnInfo { table_schema: "user".to_string(), ..column("info", "id", "int4", false) });
+        let tables = group_tables(columns);
+
+        let ts = generate_typescript(&tables);
+        assert!(ts.contains("export interface UserInfo {\n  \"type\": string;\n  \"order-id\": number;\n  CamelCase: number;\n  camel_case: number;\n}"));
+        assert!(ts.contains("export interface UserInfo2 {"));
+
+        let rust = generate_rust(&tables);
+        assert!(rust.contains("    pub r#type: String,\n"));
+        assert!(rust.contains("    #[serde(rename = \"order-id\")]\n    pub order_id: i32,\n"));
+        assert!(rust.contains("    #[serde(rename = \"CamelCase\")]\n    pub camel_case: i32,\n"));
+        assert!(rust.contains("    #[serde(rename = \"camel_case\")]\n    pub camel_case_2: i32,\n"));
+        assert!(rust.contains("pub struct UserInfo2 {"));
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tsify::Tsify;
+
+// How `query()` names result columns for JS
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnCase {
+    // Column names as Postgres returns them
+    #[default]
+    Preserve,
+    // `created_at` becomes `createdAt`, and object parameters go the other way
+    Camel,
+}
+
+// `created_at` -> `createdAt`. Leading underscores are kept, and so is a name with no
+// underscore in it.
+pub fn to_camel(name: &str) -> String {
+    let trimmed = name.trim_start_matches('_');
+    let mut camel = name[..name.len() - trimmed.len()].to_string();
+    for (i, part) in trimmed.split('_').filter(|part| !part.is_empty()).enumerate() {
+        let mut chars = part.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                camel.extend(first.to_uppercase());
+                camel.push_str(chars.as_str());
+            }
+            _ => camel.push_str(part),
+        }
+    }
+    camel
+}
+
+// `createdAt` -> `created_at`, with a run of capitals read as one word: `userID` ->
+// `user_id`, `HTTPStatus` -> `http_status`
+pub fn to_snake(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && next_is_lower) {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn rename_keys(map: Map<String, Value>, rename: fn(&str) -> String) -> Map<String, Value> {
+    map.into_iter().map(|(key, value)| (rename(&key), value)).collect()
+}
+
+// Rename each row's columns to camelCase
+pub fn camel_rows(rows: &mut [Value]) {
+    for row in rows.iter_mut() {
+        if let Value::Object(map) = row {
+            *map = rename_keys(std::mem::take(map), to_camel);
+        }
+    }
+}
+
+// Rename the keys of object parameters to snake_case, in nested objects and arrays too,
+// so a row read with camelCase can be written back
+pub fn snake_params(params: &mut [Value]) {
+    fn snake_value(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                *map = rename_keys(std::mem::take(map), to_snake);
+                map.values_mut().for_each(snake_value);
+            }
+            Value::Array(items) => items.iter_mut().for_each(snake_value),
+            _ => {}
+        }
+    }
+    params.iter_mut().for_each(snake_value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_converts_both_ways() {
+        assert_eq!(to_camel("created_at"), "createdAt");
+        assert_eq!(to_camel("_row_id"), "_rowId");
+        assert_eq!(to_camel("address_line_2"), "addressLine2");
+        assert_eq!(to_camel("count"), "count");
+        assert_eq!(to_snake("createdAt"), "created_at");
+        assert_eq!(to_snake("userID"), "user_id");
+        assert_eq!(to_snake("HTTPStatus"), "http_status");
+        assert_eq!(to_snake("addressLine2"), "address_line2");
+
+        let mut rows = vec![json!({ "user_id": 1, "profile": { "display_name": "x" } })];
+        camel_rows(&mut rows);
+        assert_eq!(rows[0], json!({ "userId": 1, "profile": { "display_name": "x" } }));
+        let mut params = vec![json!(1), json!({ "displayName": "x", "tags": [{ "tagId": 2 }] })];
+        snake_params(&mut params);
+        assert_eq!(params[1], json!({ "display_name": "x", "tags": [{ "tag_id": 2 }] }));
+    }
+}
@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::csv::column_names;
+
+// Shape `query()` resolves with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultFormat {
+    // One object per row
+    #[default]
+    Rows,
+    // One array per column; numbers and booleans in typed arrays
+    Columnar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnData {
+    // NULLs are 0 in `values` and 0 in `validity`
+    Float64 { values: Vec<f64>, validity: Vec<u8> },
+    Bool { values: Vec<u8>, validity: Vec<u8> },
+    // Strings, or anything mixed or nested, kept as JSON values with NULLs in place
+    Utf8(Vec<Value>),
+    Json(Vec<Value>),
+}
+
+impl ColumnData {
+    fn type_name(&self) -> &'static str {
+        match self {
+            ColumnData::Float64 { .. } => "float64",
+            ColumnData::Bool { .. } => "bool",
+            ColumnData::Utf8(_) => "utf8",
+            ColumnData::Json(_) => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub null_count: usize,
+    pub data: ColumnData,
+}
+
+// Pick the narrowest representation that holds every non-null value of the column
+fn build_column(name: &str, rows: &[Value]) -> Column {
+    let cells: Vec<&Value> = rows.iter().map(|row| row.get(name).unwrap_or(&Value::Null)).collect();
+    let present = || cells.iter().filter(|v| !v.is_null());
+    let null_count = cells.iter().filter(|v| v.is_null()).count();
+    let validity = || cells.iter().map(|v| u8::from(!v.is_null())).collect::<Vec<u8>>();
+
+    let data = if present().next().is_none() {
+        ColumnData::Json(cells.iter().map(|v| (*v).clone()).collect())
+    } else if present().all(|v| v.is_number()) {
+        let values = cells.iter().map(|v| v.as_f64().unwrap_or(0.0)).collect();
+        ColumnData::Float64 { values, validity: validity() }
+    } else if present().all(|v| v.is_boolean()) {
+        let values = cells.iter().map(|v| u8::from(v.as_bool().unwrap_or(false))).collect();
+        ColumnData::Bool { values, validity: validity() }
+    } else if present().all(|v| v.is_string()) {
+        ColumnData::Utf8(cells.iter().map(|v| (*v).clone()).collect())
+    } else {
+        ColumnData::Json(cells.iter().map(|v| (*v).clone()).collect())
+    };
+    Column { name: name.to_string(), null_count, data }
+}
+
+pub fn to_columns(rows: &[Value]) -> Vec<Column> {
+    column_names(rows).iter().map(|name| build_column(name, rows)).collect()
+}
+
+fn set(target: &js_sys::Object, key: &str, value: &JsValue) -> Result<(), JsValue> {
+    js_sys::Reflect::set(target, &JsValue::from_str(key), value).map(|_| ())
+}
+
+// The result payload with `rows` replaced by `columns`:
+// `[{ name, type, nullCount, values, validity }]`
+pub(crate) fn to_js(result: &Value) -> Result<JsValue, JsValue> {
+    let rows = result.get("rows").and_then(|rows| rows.as_array()).map(|rows| rows.as_slice()).unwrap_or_default();
+    let mut rest = result.clone();
+    if let Some(object) = rest.as_object_mut() {
+        object.remove("rows");
+    }
+    let output: js_sys::Object = crate::to_js(&rest)?.unchecked_into();
+
+    let columns = js_sys::Array::new();
+    for column in to_columns(rows) {
+        let entry = js_sys::Object::new();
+        set(&entry, "name", &JsValue::from_str(&column.name))?;
+        set(&entry, "type", &JsValue::from_str(column.data.type_name()))?;
+        set(&entry, "nullCount", &JsValue::from_f64(column.null_count as f64))?;
+        match &column.data {
+            ColumnData::Float64 { values, validity } => {
+                set(&entry, "values", &js_sys::Float64Array::from(values.as_slice()))?;
+                set(&entry, "validity", &js_sys::Uint8Array::from(validity.as_slice()))?;
+            }
+            ColumnData::Bool { values, validity } => {
+                set(&entry, "values", &js_sys::Uint8Array::from(values.as_slice()))?;
+                set(&entry, "validity", &js_sys::Uint8Array::from(validity.as_slice()))?;
+            }
+            ColumnData::Utf8(values) | ColumnData::Json(values) => {
+                set(&entry, "values", &crate::to_js(values)?)?;
+                set(&entry, "validity", &JsValue::NULL)?;
+            }
+        }
+        columns.push(&entry);
+    }
+    set(&output, "columns", &columns)?;
+    set(&output, "rowCount", &JsValue::from_f64(rows.len() as f64))?;
+    Ok(output.into())
+}
+
+// A cell of a row object, as `column_as_f64array` and `column_as_i32array` read it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Null,
+    Number(f64),
+    // NUMERIC and BIGINT columns arrive as strings
+    Text(String),
+    Other,
+}
+
+impl Cell {
+    fn from_js(value: &JsValue) -> Cell {
+        if value.is_null() || value.is_undefined() {
+            Cell::Null
+        } else if let Some(number) = value.as_f64() {
+            Cell::Number(number)
+        } else if let Some(text) = value.as_string() {
+            Cell::Text(text)
+        } else {
+            Cell::Other
+        }
+    }
+
+    // NULL becomes NaN, which charting libraries treat as a gap
+    pub fn to_f64(&self) -> Result<f64, String> {
+        match self {
+            Cell::Null => Ok(f64::NAN),
+            Cell::Number(number) => Ok(*number),
+            Cell::Text(text) => text.trim().parse().map_err(|_| format!("'{}' is not a number", text)),
+            Cell::Other => Err("value is not a number".to_string()),
+        }
+    }
+
+    // Int32Array has no room for NULL, so a NULL is an error
+    pub fn to_i32(&self) -> Result<i32, String> {
+        match self {
+            Cell::Null => Err("value is NULL; column_as_f64array reads NULL as NaN".to_string()),
+            Cell::Number(number) if number.fract() == 0.0 && *number >= i32::MIN as f64 && *number <= i32::MAX as f64 => Ok(*number as i32),
+            Cell::Number(number) => Err(format!("{} does not fit an Int32Array", number)),
+            Cell::Text(text) => text.trim().parse().map_err(|_| format!("'{}' does not fit an Int32Array", text)),
+            Cell::Other => Err("value is not a number".to_string()),
+        }
+    }
+}
+
+// Read one column out of a `query()` result, or an array of row objects, converting each
+// cell with `convert`
+fn extract<T>(result: &JsValue, column: &str, convert: impl Fn(&Cell) -> Result<T, String>) -> Result<Vec<T>, JsValue> {
+    let rows = if js_sys::Array::is_array(result) { result.clone() } else { js_sys::Reflect::get(result, &JsValue::from_str("rows"))? };
+    let rows: js_sys::Array = rows.dyn_into().map_err(|_| JsValue::from_str("Expected a query result or an array of rows"))?;
+    let key = JsValue::from_str(column);
+    if let Some(first) = rows.iter().next() {
+        if !first.is_object() || !js_sys::Reflect::has(&first, &key)? {
+            return Err(JsValue::from_str(&format!("Result has no column '{}'", column)));
+        }
+    }
+    rows.iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let cell = Cell::from_js(&js_sys::Reflect::get(&row, &key)?);
+            convert(&cell).map_err(|e| JsValue::from_str(&format!("Row {} of column '{}': {}", i, column, e)))
+        })
+        .collect()
+}
+
+// Copy a numeric column into a Float64Array in one pass, for charting libraries
+#[wasm_bindgen]
+pub fn column_as_f64array(result: &JsValue, column: &str) -> Result<js_sys::Float64Array, JsValue> {
+    let values = extract(result, column, Cell::to_f64)?;
+    Ok(js_sys::Float64Array::from(values.as_slice()))
+}
+
+// Like `column_as_f64array`, for integer columns without NULLs
+#[wasm_bindgen]
+pub fn column_as_i32array(result: &JsValue, column: &str) -> Result<js_sys::Int32Array, JsValue> {
+    let values = extract(result, column, Cell::to_i32)?;
+    Ok(js_sys::Int32Array::from(values.as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_column_types() {
+        let rows = vec![
+            json!({"id": 1, "ok": true, "name": "a", "meta": {"x": 1}, "gone": null}),
+            json!({"id": null, "ok": false, "name": null, "meta": "text", "gone": null}),
+        ];
+        let columns = to_columns(&rows);
+        let types: Vec<&str> = columns.iter().map(|c| c.data.type_name()).collect();
+        assert_eq!(types, vec!["float64", "bool", "utf8", "json", "json"]);
+        assert_eq!(columns[0].data, ColumnData::Float64 { values: vec![1.0, 0.0], validity: vec![1, 0] });
+        assert_eq!(columns[1].data, ColumnData::Bool { values: vec![1, 0], validity: vec![1, 1] });
+        assert_eq!(columns[2].null_count, 1);
+        assert_eq!(columns[4].null_count, 2);
+    }
+
+    #[test]
+    fn test_cell_conversions() {
+        assert_eq!(Cell::Number(2.5).to_f64(), Ok(2.5));
+        assert_eq!(Cell::Text("12345.678".to_string()).to_f64(), Ok(12345.678));
+        assert!(Cell::Null.to_f64().unwrap().is_nan());
+        assert!(Cell::Other.to_f64().is_err());
+
+        assert_eq!(Cell::Number(-7.0).to_i32(), Ok(-7));
+        assert_eq!(Cell::Text("42".to_string()).to_i32(), Ok(42));
+        assert!(Cell::Number(1.5).to_i32().is_err());
+        assert!(Cell::Number(3e9).to_i32().is_err());
+        assert!(Cell::Text("9000000000".to_string()).to_i32().is_err());
+        assert!(Cell::Null.to_i32().is_err());
+    }
+}
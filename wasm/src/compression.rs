@@ -0,0 +1,77 @@
+use bridge_protocol::chunking::DEFAULT_MAX_ASSEMBLED_SIZE;
+use bridge_protocol::compression::{compress_frame, decompress_frame, CompressedPayload};
+use bridge_protocol::{FrameError, WebSocketMessage};
+
+use crate::connection::{self, ClientState, SharedState};
+use crate::errors::BridgeError;
+
+// Deflate a serialized message when both sides negotiated compression and it is at
+// least `compression_threshold` bytes; otherwise return it unchanged
+pub(crate) fn compress_outgoing(state: &ClientState, id: Option<&str>, frame: String) -> String {
+    let enabled = state.protocol.negotiated.is_some_and(|n| n.capabilities.compression);
+    if !enabled || frame.len() < state.framing.compression_threshold {
+        return frame;
+    }
+    match compress_frame(&frame, id.map(String::from)) {
+        Some(message) => {
+            let compressed = message.to_json();
+            log_debug!("WASM compressed {} byte message to {} bytes", frame.len(), compressed.len());
+            compressed
+        }
+        None => frame,
+    }
+}
+
+// `compress_frame` always serializes `type` first
+fn is_compressed(text: &str) -> bool {
+    text.starts_with(r#"{"type":"compressed""#)
+}
+
+// Dispatch a complete frame, inflating it first if it arrived compressed
+pub(crate) fn receive(state: &SharedState, text: &str) {
+    if !is_compressed(text) {
+        connection::dispatch_incoming(state, text);
+        return;
+    }
+    let message = match serde_json::from_str::<WebSocketMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            log_error!("WASM received a malformed compressed message: {}", e);
+            return;
+        }
+    };
+    let inflated = serde_json::from_value::<CompressedPayload>(message.payload)
+        .map_err(|e| FrameError::Compression(format!("Invalid compressed payload: {}", e)))
+        .and_then(|payload| decompress_frame(&payload, DEFAULT_MAX_ASSEMBLED_SIZE));
+    match inflated {
+        Ok(frame) => connection::dispatch_incoming(state, &frame),
+        Err(e) => {
+            log_error!("WASM failed to decompress message: {}", e);
+            if let Some(id) = message.id {
+                let error = BridgeError::from_frame_error(&e, Some(id.clone()));
+                state.borrow_mut().fail_request(&id, error);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_protocol::{Capabilities, Negotiated};
+
+    #[test]
+    fn test_compress_outgoing_only_when_negotiated() {
+        let mut state = ClientState::default();
+        let frame = r#"{"type":"query","payload":{"sql":"SELECT 1"},"id":"q1"}"#.repeat(1000);
+        assert_eq!(compress_outgoing(&state, Some("q1"), frame.clone()), frame);
+
+        state.protocol.negotiated = Some(Negotiated {
+            version: 1,
+            capabilities: Capabilities { compression: true, ..Capabilities::default() },
+            peer_max_message_size: None,
+        });
+        let compressed = compress_outgoing(&state, Some("q1"), frame.clone());
+        assert!(is_compressed(&compressed) && compressed.len() < frame.len());
+    }
+}
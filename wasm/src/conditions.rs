@@ -0,0 +1,247 @@
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::query_builder::BuiltQuery;
+use crate::sql::quote_ident;
+
+pub const COMPARISON_OPERATORS: [&str; 6] = ["=", "<>", "<", "<=", ">", ">="];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    // No-op filter, produced by optional inputs that were left unset
+    Empty,
+    Compare(String, &'static str, Value),
+    InList(String, Vec<Value>),
+    Like(String, Value, bool),
+    IsNull(String, bool),
+    Not(Box<Predicate>),
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>),
+}
+
+impl Predicate {
+    fn render(&self, params: &mut Vec<Value>) -> Option<String> {
+        match self {
+            Predicate::Empty => None,
+            Predicate::Compare(column, "=", Value::Null) => Some(format!("{} IS NULL", quote_ident(column))),
+            Predicate::Compare(column, "<>", Value::Null) => Some(format!("{} IS NOT NULL", quote_ident(column))),
+            Predicate::Compare(column, operator, value) => {
+                params.push(value.clone());
+                Some(format!("{} {} ${}", quote_ident(column), operator, params.len()))
+            }
+            Predicate::InList(_, values) if values.is_empty() => Some("FALSE".to_string()),
+            Predicate::InList(column, values) => {
+                let placeholders = values
+                    .iter()
+                    .map(|value| {
+                        params.push(value.clone());
+                        format!("${}", params.len())
+                    })
+                    .collect::<Vec<_>>();
+                Some(format!("{} IN ({})", quote_ident(column), placeholders.join(", ")))
+            }
+            Predicate::Like(column, pattern, case_insensitive) => {
+                params.push(pattern.clone());
+                let keyword = if *case_insensitive { "ILIKE" } else { "LIKE" };
+                Some(format!("{} {} ${}", quote_ident(column), keyword, params.len()))
+            }
+            Predicate::IsNull(column, true) => Some(format!("{} IS NULL", quote_ident(column))),
+            Predicate::IsNull(column, false) => Some(format!("{} IS NOT NULL", quote_ident(column))),
+            // An empty condition matches every row, so its negation matches none
+            Predicate::Not(inner) => Some(inner.render(params).map_or_else(|| "FALSE".to_string(), |sql| format!("NOT ({})", sql))),
+            Predicate::All(parts) => render_group(parts, " AND ", params),
+            // Matches every row once any alternative does: empty, with no values added
+            Predicate::Any(parts) => {
+                let mut own = params.clone();
+                let rendered = parts.iter().map(|p| p.render(&mut own)).collect::<Option<Vec<String>>>()?;
+                *params = own;
+                match rendered.len() {
+                    1 => rendered.into_iter().next(),
+                    _ => Some(format!("({})", rendered.join(" OR "))),
+                }
+            }
+        }
+    }
+}
+
+fn render_group(parts: &[Predicate], separator: &str, params: &mut Vec<Value>) -> Option<String> {
+    let rendered: Vec<String> = parts.iter().filter_map(|p| p.render(params)).collect();
+    match rendered.len() {
+        0 => None,
+        1 => rendered.into_iter().next(),
+        _ => Some(format!("({})", rendered.join(separator))),
+    }
+}
+
+// Composable, parameterized WHERE predicate. Conditions built from `undefined`
+// values are empty and drop out of the final SQL, so optional UI filters can
+// be chained unconditionally. An empty condition matches every row: it vanishes
+// from `and`, makes an `or` match everything, and its `not` matches nothing.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    predicate: Predicate,
+}
+
+fn optional_value(value: JsValue) -> Result<Option<Value>, JsValue> {
+    if value.is_undefined() {
+        return Ok(None);
+    }
+    serde_wasm_bindgen::from_value(value)
+        .map(Some)
+        .map_err(|e| JsValue::from_str(&format!("Invalid condition value: {}", e)))
+}
+
+#[wasm_bindgen]
+impl Condition {
+    // An empty condition that matches everything
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Condition {
+        Condition { predicate: Predicate::Empty }
+    }
+
+    pub fn eq(column: &str, value: JsValue) -> Result<Condition, JsValue> {
+        Ok(Condition::compare_value(column, "=", optional_value(value)?))
+    }
+
+    pub fn compare(column: &str, operator: &str, value: JsValue) -> Result<Condition, JsValue> {
+        let operator = COMPARISON_OPERATORS
+            .iter()
+            .find(|op| **op == operator)
+            .ok_or_else(|| JsValue::from_str(&format!("Unsupported operator '{}'", operator)))?;
+        Ok(Condition::compare_value(column, operator, optional_value(value)?))
+    }
+
+    pub fn in_list(column: &str, values: JsValue) -> Result<Condition, JsValue> {
+        match optional_value(values)? {
+            None => Ok(Condition::new()),
+            Some(Value::Array(values)) => Ok(Condition::in_values(column, values)),
+            Some(_) => Err(JsValue::from_str("in_list expects an array of values")),
+        }
+    }
+
+    pub fn like(column: &str, pattern: Option<String>) -> Condition {
+        Condition::like_value(column, pattern, false)
+    }
+
+    pub fn ilike(column: &str, pattern: Option<String>) -> Condition {
+        Condition::like_value(column, pattern, true)
+    }
+
+    pub fn is_null(column: &str) -> Condition {
+        Condition { predicate: Predicate::IsNull(column.to_string(), true) }
+    }
+
+    pub fn is_not_null(column: &str) -> Condition {
+        Condition { predicate: Predicate::IsNull(column.to_string(), false) }
+    }
+
+    pub fn and(&self, other: &Condition) -> Condition {
+        Condition {
+            predicate: Predicate::All(vec![self.predicate.clone(), other.predicate.clone()]),
+        }
+    }
+
+    pub fn or(&self, other: &Condition) -> Condition {
+        Condition {
+            predicate: Predicate::Any(vec![self.predicate.clone(), other.predicate.clone()]),
+        }
+    }
+
+    pub fn not(&self) -> Condition {
+        Condition { predicate: Predicate::Not(Box::new(self.predicate.clone())) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_query(0).is_none()
+    }
+
+    // Returns `{ sql, params }` with placeholders numbered from $1
+    pub fn build(&self) -> Result<JsValue, JsValue> {
+        let built = self.to_query(0).unwrap_or(BuiltQuery { sql: "TRUE".to_string(), params: Vec::new() });
+        crate::to_js(&built)
+    }
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Condition::new()
+    }
+}
+
+impl Condition {
+    pub fn compare_value(column: &str, operator: &'static str, value: Option<Value>) -> Condition {
+        match value {
+            Some(value) => Condition { predicate: Predicate::Compare(column.to_string(), operator, value) },
+            None => Condition::new(),
+        }
+    }
+
+    pub fn in_values(column: &str, values: Vec<Value>) -> Condition {
+        Condition { predicate: Predicate::InList(column.to_string(), values) }
+    }
+
+    pub fn like_value(column: &str, pattern: Option<String>, case_insensitive: bool) -> Condition {
+        match pattern {
+            Some(pattern) => Condition {
+                predicate: Predicate::Like(column.to_string(), Value::String(pattern), case_insensitive),
+            },
+            None => Condition::new(),
+        }
+    }
+
+    // Render the predicate, appending its values to `params`; None when empty
+    pub fn render(&self, params: &mut Vec<Value>) -> Option<String> {
+        self.predicate.render(params)
+    }
+
+    // Standalone rendering with placeholders numbered after `offset` existing parameters
+    pub fn to_query(&self, offset: usize) -> Option<BuiltQuery> {
+        let mut params = vec![Value::Null; offset];
+        let sql = self.render(&mut params)?;
+        Some(BuiltQuery { sql, params: params.split_off(offset) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_compose_and_or() {
+        let condition = Condition::compare_value("status", "=", Some(json!("active")))
+            .and(&Condition::in_values("role", vec![json!("admin"), json!("owner")]).or(&Condition::is_null("role")));
+        let query = condition.to_query(0).unwrap();
+        assert_eq!(query.sql, "(\"status\" = $1 AND (\"role\" IN ($2, $3) OR \"role\" IS NULL))");
+        assert_eq!(query.params, vec![json!("active"), json!("admin"), json!("owner")]);
+    }
+
+    #[test]
+    fn test_unset_filters_drop_out() {
+        let condition = Condition::compare_value("status", "=", None)
+            .and(&Condition::like_value("name", Some("a%".to_string()), true))
+            .and(&Condition::like_value("email", None, false));
+        let query = condition.to_query(2).unwrap();
+        assert_eq!(query.sql, "\"name\" ILIKE $3");
+        assert_eq!(query.params, vec![json!("a%")]);
+
+        assert!(Condition::new().and(&Condition::new()).is_empty());
+        assert_eq!(Condition::in_values("id", Vec::new()).to_query(0).unwrap().sql, "FALSE");
+    }
+
+    #[test]
+    fn test_empty_condition_matches_everything() {
+        let status = Condition::compare_value("status", "=", Some(json!("active")));
+        let unset = Condition::compare_value("role", "=", None);
+        assert!(status.or(&unset).is_empty());
+        assert!(unset.or(&status).is_empty());
+        assert_eq!(unset.not().to_query(0).unwrap().sql, "FALSE");
+
+        // Values of a dropped alternative don't leak into the parameters
+        let condition = Condition::compare_value("id", "=", Some(json!(1))).and(&status.or(&unset));
+        let query = condition.to_query(0).unwrap();
+        assert_eq!(query.sql, "\"id\" = $1");
+        assert_eq!(query.params, vec![json!(1)]);
+    }
+}
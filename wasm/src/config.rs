@@ -0,0 +1,229 @@
+use bridge_protocol::PoolRequest;
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::column_case::ColumnCase;
+use crate::columnar::ResultFormat;
+use crate::logging::LogLevel;
+use crate::named_subscriptions::SubscriptionDefinition;
+use crate::placeholders::PlaceholderStyle;
+use crate::reconnect::ReconnectPolicy;
+use crate::sql::quote_ident;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    // Give up on an attempt that hasn't opened by then
+    #[serde(rename = "connectMs")]
+    pub connect_ms: Option<f64>,
+    // Fail a request that gets no response by then; the bridge may still finish it
+    #[serde(rename = "requestMs")]
+    pub request_ms: Option<f64>,
+}
+
+// Everything the constructor accepts besides a bare URL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct WasmClientConfig {
+    pub url: String,
+    // Sent in an `auth` message after every handshake
+    #[serde(default)]
+    pub auth: Option<String>,
+    #[serde(default)]
+    pub timeouts: Timeouts,
+    // Reconnect after unexpected closes; off unless given
+    #[serde(default)]
+    pub reconnect: Option<ReconnectPolicy>,
+    // Default shape of `query()` results
+    #[serde(default)]
+    pub encoding: ResultFormat,
+    // Placeholder syntax of `query()` SQL; "question" rewrites `?` to `$n` before sending
+    #[serde(default)]
+    pub placeholders: PlaceholderStyle,
+    // "camel" turns `snake_case` result columns into `camelCase` and object parameters back
+    #[serde(default, rename = "columnCase")]
+    pub column_case: ColumnCase,
+    // Applies to the whole module, like `set_log_level`
+    #[serde(default, rename = "logLevel")]
+    pub log_level: Option<String>,
+    // Schemas set as the session's search_path on every connect
+    #[serde(default, rename = "searchPath")]
+    pub search_path: Option<Vec<String>>,
+    // Session default for statement_timeout; 0 means no limit
+    #[serde(default, rename = "statementTimeoutMs")]
+    pub statement_timeout_ms: Option<u32>,
+    // Statements run in order after every connect, before queries waiting on it go out
+    #[serde(default, rename = "onConnectSql")]
+    pub on_connect_sql: Vec<String>,
+    // Pool behavior asked of the bridge in `hello`; it grants what its limits allow
+    #[serde(default)]
+    pub pool: Option<PoolRequest>,
+    // Set as `app.tenant_id` on every connect and sent with every query
+    #[serde(default, rename = "tenantId")]
+    pub tenant_id: Option<String>,
+    // LISTEN again this long after the bridge reports its LISTEN connection lost; off
+    // unless given
+    #[serde(default, rename = "relistenMs")]
+    pub relisten_ms: Option<f64>,
+    // UNLISTEN a channel once nothing uses it; on unless false
+    #[serde(default, rename = "autoUnlisten")]
+    pub auto_unlisten: Option<bool>,
+    // How long an unused channel stays LISTENed first, in case a handler comes back
+    #[serde(default, rename = "unlistenLingerMs")]
+    pub unlisten_linger_ms: Option<f64>,
+    // Session TimeZone set on every connect: an IANA name, or "browser" for the one the
+    // browser resolves
+    #[serde(default, rename = "timeZone")]
+    pub time_zone: Option<String>,
+    // Notification subscriptions registered up front and LISTENed on every connect
+    #[serde(default)]
+    pub subscriptions: Vec<SubscriptionDefinition>,
+}
+
+fn positive(field: &str, value: Option<f64>) -> Result<(), String> {
+    match value {
+        Some(ms) if !(ms.is_finite() && ms > 0.0) => Err(format!("{} must be a positive number of milliseconds", field)),
+        _ => Ok(()),
+    }
+}
+
+impl WasmClientConfig {
+    pub fn from_url(url: &str) -> WasmClientConfig {
+        WasmClientConfig {
+            url: url.to_string(),
+            auth: None,
+            timeouts: Timeouts::default(),
+            reconnect: None,
+            encoding: ResultFormat::default(),
+            placeholders: PlaceholderStyle::default(),
+            column_case: ColumnCase::default(),
+            log_level: None,
+            search_path: None,
+            statement_timeout_ms: None,
+            on_connect_sql: Vec::new(),
+            pool: None,
+            tenant_id: None,
+            relisten_ms: None,
+            auto_unlisten: None,
+            unlisten_linger_ms: None,
+            time_zone: None,
+            subscriptions: Vec::new(),
+        }
+    }
+
+    // A URL string or a config object, validated
+    pub fn from_js(value: JsValue) -> Result<WasmClientConfig, String> {
+        let config = match value.as_string() {
+            Some(url) => WasmClientConfig::from_url(&url),
+            None => {
+                // Handlers and decoders are functions, which serde can't carry
+                let (value, functions) = crate::named_subscriptions::take_functions(&value);
+                let mut config: WasmClientConfig = serde_wasm_bindgen::from_value(value).map_err(|e| format!("Invalid client config: {}", e))?;
+                for (definition, mut functions) in config.subscriptions.iter_mut().zip(functions) {
+                    definition.handler = functions.remove("handler");
+                    definition.decode = functions.remove("decode");
+                }
+                config
+            }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Check each field, naming the one at fault
+    pub fn validate(&self) -> Result<(), String> {
+        let invalid = |name: &str, message: String| format!("Invalid client config field '{}': {}", name, message);
+        if !["ws://", "wss://", "http://", "https://"].iter().any(|scheme| self.url.starts_with(scheme)) {
+            return Err(invalid("url", format!("expected a ws://, wss://, http:// or https:// URL, got '{}'", self.url)));
+        }
+        if self.auth.as_deref().is_some_and(str::is_empty) {
+            return Err(invalid("auth", "must not be empty".to_string()));
+        }
+        if self.tenant_id.as_deref().is_some_and(|tenant| tenant.trim().is_empty()) {
+            return Err(invalid("tenantId", "must not be empty".to_string()));
+        }
+        if self.time_zone.as_deref().is_some_and(|zone| zone.trim().is_empty()) {
+            return Err(invalid("timeZone", "must not be empty".to_string()));
+        }
+        positive("connectMs", self.timeouts.connect_ms)
+            .and_then(|_| positive("requestMs", self.timeouts.request_ms))
+            .map_err(|e| invalid("timeouts", e))?;
+        positive("relistenMs", self.relisten_ms).map_err(|e| invalid("relistenMs", e))?;
+        positive("unlistenLingerMs", self.unlisten_linger_ms).map_err(|e| invalid("unlistenLingerMs", e))?;
+        if let Some(reconnect) = &self.reconnect {
+            reconnect.validate().map_err(|e| invalid("reconnect", e))?;
+        }
+        if let Some(level) = &self.log_level {
+            LogLevel::parse(level).map_err(|e| invalid("logLevel", e))?;
+        }
+        if let Some(schemas) = &self.search_path {
+            if schemas.is_empty() || schemas.iter().any(|s| s.is_empty()) {
+                return Err(invalid("searchPath", "expected a non-empty list of schema names".to_string()));
+            }
+        }
+        if let Some(index) = self.on_connect_sql.iter().position(|sql| sql.trim().is_empty()) {
+            return Err(invalid("onConnectSql", format!("statement {} is empty", index)));
+        }
+        crate::named_subscriptions::validate(&self.subscriptions).map_err(|e| invalid("subscriptions", e))?;
+        Ok(())
+    }
+
+    // The `search_path` value, schemas quoted so any name is taken literally
+    pub fn search_path_setting(&self) -> Option<String> {
+        let schemas = self.search_path.as_ref()?;
+        Some(schemas.iter().map(|s| quote_ident(s)).collect::<Vec<_>>().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: serde_json::Value) -> Result<WasmClientConfig, String> {
+        let config: WasmClientConfig = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_full_config() {
+        let config = parse(json!({
+            "url": "wss://db.example.com/bridge",
+            "auth": "key",
+            "timeouts": { "connectMs": 5000, "requestMs": 30000 },
+            "reconnect": { "maxAttempts": 5 },
+            "encoding": "columnar",
+            "logLevel": "warn",
+            "searchPath": ["app", "public"],
+            "statementTimeoutMs": 5000,
+            "onConnectSql": ["SET application_name = 'app'"],
+            "pool": { "mode": "dedicated", "statementCache": false },
+            "tenantId": "acme"
+        }))
+        .unwrap();
+        assert_eq!(config.timeouts.request_ms, Some(30000.0));
+        assert_eq!(config.reconnect.as_ref().unwrap().max_attempts, 5);
+        assert_eq!(config.encoding, ResultFormat::Columnar);
+        assert_eq!(config.statement_timeout_ms, Some(5000));
+        assert_eq!(config.tenant_id.as_deref(), Some("acme"));
+        assert_eq!(config.pool.and_then(|pool| pool.mode), Some(bridge_protocol::PoolMode::Dedicated));
+        assert_eq!(config.search_path_setting().as_deref(), Some(r#""app", "public""#));
+    }
+
+    #[test]
+    fn test_errors_name_the_field() {
+        let error = |value| parse(value).unwrap_err();
+        assert!(error(json!({"url": "db.example.com"})).contains("'url'"));
+        assert!(error(json!({"url": "ws://db", "timeouts": {"requestMs": 0}})).contains("requestMs"));
+        assert!(error(json!({"url": "ws://db", "logLevel": "loud"})).contains("'logLevel'"));
+        assert!(error(json!({"url": "ws://db", "searchPath": []})).contains("'searchPath'"));
+        assert!(error(json!({"url": "ws://db", "onConnectSql": ["SET x = 1", " "]})).contains("statement 1 is empty"));
+        assert!(error(json!({"url": "ws://db", "timeout": 5})).contains("unknown field `timeout`"));
+        assert!(error(json!({"url": "ws://db", "tenantId": " "})).contains("'tenantId'"));
+        assert!(error(json!({"url": "ws://db", "relistenMs": -1})).contains("'relistenMs'"));
+        assert!(error(json!({"url": "ws://db", "timeZone": ""})).contains("'timeZone'"));
+        assert!(error(json!({"url": "ws://db", "subscriptions": [{"name": "a", "channel": ""}]})).contains("'subscriptions'"));
+    }
+}
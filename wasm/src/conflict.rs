@@ -0,0 +1,197 @@
+use js_sys::Promise;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+use crate::conditions::Condition;
+use crate::connection::{execute_query, SharedState};
+use crate::outbox::OutboxEntry;
+use crate::query_builder::{BuiltQuery, QueryBuilder};
+
+// What a sync write carries so its push can tell whether the row changed on the server
+// since the local edit was made
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct ConflictCheck {
+    pub table: String,
+    // Key columns and values of the written row
+    #[tsify(type = "Record<string, Value>")]
+    pub key: Map<String, Value>,
+    // Column every write to the row bumps, such as updated_at or a version number
+    #[serde(rename = "versionColumn")]
+    pub version_column: String,
+    // Its value when the local edit was made; null for a row created locally
+    #[serde(rename = "baseVersion", default)]
+    pub base_version: Value,
+    // The row as the client wants it, for merge resolvers
+    #[serde(default)]
+    #[tsify(type = "Record<string, Value>")]
+    pub row: Map<String, Value>,
+}
+
+impl ConflictCheck {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.table.trim().is_empty() {
+            return Err("table must not be empty".to_string());
+        }
+        if self.key.is_empty() {
+            return Err("key must name at least one column".to_string());
+        }
+        if self.version_column.trim().is_empty() {
+            return Err("versionColumn must not be empty".to_string());
+        }
+        Ok(())
+    }
+
+    fn keyed(&self, builder: QueryBuilder) -> QueryBuilder {
+        self.key.iter().fold(builder, |builder, (column, value)| builder.where_value(column, "=", value.clone()))
+    }
+
+    pub fn select(&self) -> Result<BuiltQuery, String> {
+        self.keyed(QueryBuilder::new(&self.table)).to_query()
+    }
+
+    // Whether the server's row moved on from the version the local edit started from.
+    // A row that is gone doesn't conflict; the write decides what that means.
+    pub fn is_stale(&self, server: &Value) -> bool {
+        server.get(&self.version_column).unwrap_or(&Value::Null) != &self.base_version
+    }
+
+    // Write a merged row, unless the server row changes again in the meantime
+    pub fn merged_update(&self, merged: Map<String, Value>, server_version: &Value) -> Result<BuiltQuery, String> {
+        let values = merged.into_iter().filter(|(column, _)| !self.key.contains_key(column)).collect();
+        let builder = self.keyed(QueryBuilder::new(&self.table).update_values(values));
+        let guard = match server_version {
+            Value::Null => Condition::is_null(&self.version_column),
+            version => Condition::compare_value(&self.version_column, "=", Some(version.clone())),
+        };
+        builder.where_condition(&guard).to_query()
+    }
+}
+
+// How a table's conflicts are settled
+#[derive(Debug, Clone, Default)]
+pub enum Resolver {
+    // The later of the local edit and the server's version applies
+    #[default]
+    LastWriteWins,
+    // The server's row stays and the local write is dropped
+    ServerWins,
+    // `({ table, key, local, server, base }) => merged row`, or null to keep the server's
+    Merge(js_sys::Function),
+}
+
+impl Resolver {
+    pub fn parse(value: JsValue) -> Result<Resolver, String> {
+        if let Some(merge) = value.dyn_ref::<js_sys::Function>() {
+            return Ok(Resolver::Merge(merge.clone()));
+        }
+        match value.as_string().as_deref() {
+            Some("last-write-wins") => Ok(Resolver::LastWriteWins),
+            Some("server-wins") => Ok(Resolver::ServerWins),
+            _ => Err("expected \"last-write-wins\", \"server-wins\" or a merge function".to_string()),
+        }
+    }
+}
+
+// What a push does after checking for a conflict
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    // Send the queued write as it is
+    Push,
+    // Send this instead
+    Write(BuiltQuery),
+    // Send nothing; the server's row stands
+    Drop,
+}
+
+// Whether a local edit queued at `queued_at` is newer than the server's version, read as
+// epoch milliseconds. A version that doesn't read as a time never beats the local edit.
+pub fn local_is_newer(queued_at: f64, server_ms: Option<f64>) -> bool {
+    server_ms.is_none_or(|server_ms| server_ms.is_nan() || queued_at >= server_ms)
+}
+
+fn version_ms(version: &Value) -> Option<f64> {
+    match version {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => Some(js_sys::Date::parse(text)),
+        _ => None,
+    }
+}
+
+async fn merge(callback: &js_sys::Function, check: &ConflictCheck, server: &Value) -> Result<Resolution, JsValue> {
+    let conflict = json!({
+        "table": check.table,
+        "key": check.key,
+        "local": check.row,
+        "server": server,
+        "base": check.base_version,
+    });
+    let returned = callback.call1(&JsValue::NULL, &crate::to_js(&conflict)?)?;
+    let merged = JsFuture::from(Promise::resolve(&returned)).await?;
+    if merged.is_null() || merged.is_undefined() {
+        return Ok(Resolution::Drop);
+    }
+    let merged: Map<String, Value> = serde_wasm_bindgen::from_value(merged)
+        .map_err(|e| JsValue::from_str(&format!("Conflict resolver for {} must return a row: {}", check.table, e)))?;
+    let version = server.get(&check.version_column).unwrap_or(&Value::Null);
+    check.merged_update(merged, version).map(Resolution::Write).map_err(|e| JsValue::from_str(&e))
+}
+
+// Compare a queued write with the server's current row and settle any conflict with the
+// table's resolver
+pub(crate) async fn resolve(state: &SharedState, entry: &OutboxEntry, check: &ConflictCheck) -> Result<Resolution, JsValue> {
+    let select = check.select().map_err(|e| JsValue::from_str(&e))?;
+    let result = execute_query(state, &select.sql, Some(select.params)).await?;
+    let Some(server) = result.get("rows").and_then(|rows| rows.get(0)).filter(|row| check.is_stale(row)) else {
+        return Ok(Resolution::Push);
+    };
+    let resolver = state.borrow().sync.resolvers.get(&check.table).cloned().unwrap_or_default();
+    crate::sync::count_conflict(state);
+    log_info!("WASM sync write {} conflicts with a newer row in {}", entry.key, check.table);
+    match resolver {
+        Resolver::LastWriteWins => {
+            let server_ms = version_ms(server.get(&check.version_column).unwrap_or(&Value::Null));
+            Ok(if local_is_newer(entry.queued_at, server_ms) { Resolution::Push } else { Resolution::Drop })
+        }
+        Resolver::ServerWins => Ok(Resolution::Drop),
+        Resolver::Merge(callback) => merge(&callback, check, server).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(base: Value) -> ConflictCheck {
+        serde_json::from_value(json!({
+            "table": "todos", "key": {"id": 7}, "versionColumn": "version", "baseVersion": base,
+            "row": {"id": 7, "title": "local"}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_stale_rows_and_merged_update() {
+        let check = check(json!(3));
+        assert!(check.validate().is_ok());
+        assert!(!check.is_stale(&json!({"id": 7, "version": 3})));
+        assert!(check.is_stale(&json!({"id": 7, "version": 4})));
+        assert_eq!(check.select().unwrap().sql, "SELECT * FROM \"todos\" WHERE \"id\" = $1");
+
+        let merged = json!({"id": 7, "title": "merged"}).as_object().cloned().unwrap();
+        let update = check.merged_update(merged, &json!(4)).unwrap();
+        assert_eq!(update.sql, "UPDATE \"todos\" SET \"title\" = $1 WHERE \"id\" = $2 AND \"version\" = $3");
+        assert_eq!(update.params, vec![json!("merged"), json!(7), json!(4)]);
+    }
+
+    #[test]
+    fn test_last_write_wins() {
+        assert!(local_is_newer(2_000.0, Some(1_000.0)));
+        assert!(!local_is_newer(1_000.0, Some(2_000.0)));
+        assert!(local_is_newer(1_000.0, None));
+        assert!(local_is_newer(1_000.0, Some(f64::NAN)));
+    }
+}
@@ -0,0 +1,1314 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use bridge_protocol::encryption::FrameKey;
+use bridge_protocol::{message_type, StatementPolicy};
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::advisory::AdvisoryLocks;
+use crate::audit::AuditLog;
+use crate::breaker::{BreakerState, CircuitBreaker};
+use crate::cdc::ChangeSubscriptions;
+use crate::chunking::Framing;
+use crate::config::Timeouts;
+use crate::columnar::ResultFormat;
+use crate::dispatch::{DispatchQueue, Priority};
+use crate::duplicates::DuplicateFilter;
+use crate::errors::BridgeError;
+use crate::fingerprint::QueryStats;
+use crate::handshake::ProtocolState;
+use crate::ids::IdGenerator;
+use crate::idle::IdleState;
+use crate::incremental::IncrementalParse;
+use crate::interceptors::{InterceptorChain, Phase};
+use crate::live::WatchRegistry;
+use crate::metrics::QueryMetrics;
+use crate::notifications::NotificationSubscriptions;
+use crate::chaos::Chaos;
+use crate::connection_stats::ConnectionStats;
+use crate::events::EventListeners;
+use crate::outbox::Outbox;
+use crate::payload_size::PayloadSizes;
+use crate::query_cache::{CacheOptions, QueryCache};
+use crate::query_status::{QueryStatus, QueryTracker};
+use crate::rate_limit::RateLimiter;
+use crate::recorder::{Direction, TrafficRecorder};
+use crate::reconnect::ReconnectState;
+use crate::replay::QueryTape;
+use crate::restart::RestartState;
+use crate::result_budget::ResultBudget;
+use crate::resume::Resumption;
+use crate::retry::RetryPolicy;
+use crate::session_state::SessionState;
+use crate::slow_log::SlowQueryLog;
+use crate::sync::SyncState;
+use crate::tracing::TraceConfig;
+use crate::transaction::TransactionGate;
+use crate::tab_fanout::FanoutKind;
+use crate::transport::Transport;
+use crate::{QueryPayload, WebSocketMessage};
+
+// Shared connection state, reachable from both the client and its event callbacks
+#[derive(Default)]
+pub(crate) struct ClientState {
+    pub transport: Option<Box<dyn Transport>>,
+    pub ids: IdGenerator,
+    pub pending: HashMap<String, Rc<RefCell<ResponseSlot>>>,
+    pub message_handler: Option<js_sys::Function>,
+    pub slow_queries: SlowQueryLog,
+    pub slow_query_hook: Option<js_sys::Function>,
+    pub progress_hook: Option<js_sys::Function>,
+    pub metrics: QueryMetrics,
+    pub connection_stats: ConnectionStats,
+    pub payload_sizes: PayloadSizes,
+    // Ids of recent responses, to drop any the bridge delivers twice
+    pub duplicates: DuplicateFilter,
+    pub query_stats: QueryStats,
+    pub tracing: TraceConfig,
+    pub interceptors: InterceptorChain,
+    pub outbox: Outbox,
+    pub watches: WatchRegistry,
+    pub changes: ChangeSubscriptions,
+    pub protocol: ProtocolState,
+    pub framing: Framing,
+    pub recorder: TrafficRecorder,
+    pub query_tape: QueryTape,
+    pub retry_policy: RetryPolicy,
+    pub breaker: CircuitBreaker,
+    pub state_hook: Option<js_sys::Function>,
+    // `addEventListener` subscribers, alongside the single `on_*` hooks
+    pub events: EventListeners,
+    pub session: SessionState,
+    // Set while `close_gracefully` drains in-flight requests; new ones are refused
+    pub closing: bool,
+    pub idle: IdleState,
+    pub dispatch: DispatchQueue,
+    pub rate_limit: RateLimiter,
+    // The bridge's latest reported load and the adaptive throttle, if one is set
+    pub backpressure: crate::backpressure::BackpressureState,
+    // Retry options for, and the hook told of, queries the bridge throttles
+    pub throttled: crate::throttled::ThrottledState,
+    // Whether the page is hidden, and what the client does about it
+    pub visibility: crate::visibility::VisibilityState,
+    pub incremental: IncrementalParse,
+    // Options and the kept strings of `set_string_interning`
+    pub interning: crate::intern::InternState,
+    pub query_cache: QueryCache,
+    // Raw result columns by the `schemaId` the bridge sends in their place
+    pub result_schemas: crate::result_schema::SchemaCache,
+    // Tables whose cached queries a NOTIFY on each channel invalidates
+    pub cache_invalidation: crate::cache_invalidation::CacheInvalidation,
+    // Saved copy of the channels and cache rules above, for restarted workers
+    pub subscriptions: crate::subscription_store::SubscriptionStore,
+    // Reject statements that could write before they are sent
+    pub read_only: bool,
+    // Allow/deny rules checked before sending and declared to the bridge in `hello`
+    pub policy: Option<StatementPolicy>,
+    // Row cap appended to unbounded reads made through `query()`
+    pub limit_guard: Option<u32>,
+    // Planner estimate limits checked with EXPLAIN before `query()` runs a statement
+    pub cost_guard: Option<crate::cost_guard::CostGuard>,
+    // Lets every `query()` run stacked statements without opting in per call
+    pub multi_statement: bool,
+    // Check `query()` SQL with `validate` before sending it
+    pub validate_sql: bool,
+    // Check every inbound message against its type's schema; see `set_strict_protocol`
+    pub strict_protocol: bool,
+    pub audit: AuditLog,
+    pub audit_hook: Option<js_sys::Function>,
+    // Shared secret for end-to-end encryption; frames after `hello` are encrypted with it
+    pub encryption_key: Option<FrameKey>,
+    pub resumption: Resumption,
+    pub timeouts: Timeouts,
+    // As the client was constructed, for `export_diagnostics`
+    pub config: Option<crate::config::WasmClientConfig>,
+    // Sent in an `auth` message after each handshake
+    pub auth_token: Option<String>,
+    pub reconnect: ReconnectState,
+    // Session setup run after every connect
+    pub on_connect_sql: Vec<String>,
+    // Prepared by `prewarm` ahead of their first run
+    pub hot_statements: Vec<crate::prewarm::HotStatement>,
+    pub queries: QueryTracker,
+    pub notifications: NotificationSubscriptions,
+    // Subscriptions declared in the config, by name
+    pub named_subscriptions: crate::named_subscriptions::NamedSubscriptions,
+    // Pending, active or lost, per LISTEN channel
+    pub listen_states: crate::listen_state::ListenStates,
+    // Whether and when channels nothing uses are UNLISTENed
+    pub unlisten: crate::unlisten::UnlistenPolicy,
+    // `on_schema_change` and whether schema changes invalidate caches
+    pub schema_change: crate::schema_change::SchemaChangeState,
+    // Introspected signatures for `call_function` and `call_procedure`
+    pub routines: crate::routines::RoutineCache,
+    // Relays received NOTIFYs and changes to the other tabs sharing this connection
+    pub tab_fanout: crate::tab_fanout::TabFanout,
+    // Asked of the bridge in every `hello`
+    pub pool_request: Option<bridge_protocol::PoolRequest>,
+    // Set by `pin_session`; pinned again on a reconnect that doesn't resume the session
+    pub pinned: bool,
+    // Sent with every query for the bridge to check against the key's tenant
+    pub tenant_id: Option<String>,
+    pub restart: RestartState,
+    pub transactions: TransactionGate,
+    // Session-level advisory locks held through the client
+    pub advisory_locks: AdvisoryLocks,
+    pub sync: SyncState,
+    pub result_budget: ResultBudget,
+    // Faults injected into received frames; see `set_chaos`
+    pub chaos: Option<Chaos>,
+    // Closed by the client but standing for a drop, waiting for the close event
+    pub dropping: bool,
+}
+
+pub(crate) type SharedState = Rc<RefCell<ClientState>>;
+
+// SQL text plus optional bound parameters
+pub(crate) type Statement = (String, Option<Vec<serde_json::Value>>);
+
+// How often a graceful close checks whether in-flight requests have drained
+const DRAIN_POLL_MS: f64 = 25.0;
+
+// Per-query settings accepted by `query()`
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(default)]
+pub(crate) struct QueryOptions {
+    // Set to false to fail on the first transient error instead of applying the retry policy
+    pub retry: bool,
+    pub priority: Priority,
+    // Falls back to the client's `encoding`
+    pub format: Option<ResultFormat>,
+    // Serve repeats from the in-memory cache for `cache.ttl` milliseconds
+    pub cache: Option<CacheOptions>,
+    // Let the bridge answer from its own result cache; ignored by bridges without one
+    #[serde(rename = "bridgeCache")]
+    pub bridge_cache: Option<bridge_protocol::CacheControl>,
+    // Return this many rows and a `continuation` token for `fetch_more` when more remain;
+    // bridges without continuations return every row
+    #[serde(rename = "pageSize")]
+    pub page_size: Option<u32>,
+    // Allow several `;`-separated statements in this one query
+    #[serde(rename = "multiStatement")]
+    pub multi_statement: bool,
+    // Overrides the session's statement_timeout for this query only
+    #[serde(rename = "statementTimeoutMs")]
+    pub statement_timeout_ms: Option<u32>,
+    // Names the query for `query_status` and `cancel`, and becomes its message id
+    #[serde(rename = "queryId")]
+    pub query_id: Option<String>,
+    // The `with_transaction` this query belongs to; others wait for it to finish
+    #[serde(skip)]
+    pub transaction: Option<u32>,
+    // Parameters sent as packed bytes, by position; set by `query_binary`
+    #[serde(skip)]
+    pub binary_params: Option<crate::binary_params::BinaryParams>,
+    // Configuration parameters SET LOCAL for this query only
+    pub settings: crate::local_settings::Settings,
+    // Undecoded cells plus column OIDs and format codes, for callers that decode themselves
+    pub raw: bool,
+    // Safe to send again: if the connection drops before the answer, the query is re-sent
+    // once it is back instead of failing with CONNECTION_LOST
+    pub idempotent: bool,
+    // Labels such as `{ feature: "orders-grid" }`, sent with the query and kept in the
+    // audit and slow-query logs
+    pub tags: Option<bridge_protocol::QueryTags>,
+    // Postgres types for the parameters, by position, such as `["int8", null, "uuid[]"]`,
+    // for placeholders whose type can't be inferred
+    pub types: Option<Vec<Option<String>>>,
+    // Reshape the rows before they reach JS: a transform registered by name, or an
+    // inline one
+    pub transform: Option<crate::row_transform::TransformRef>,
+    // The bridge's own SQL, such as migration and on-connect scripts, which may stack
+    // statements without the caller opting in
+    #[serde(skip)]
+    pub internal: bool,
+    // Falls back to the client's `columnCase`
+    #[serde(rename = "columnCase")]
+    pub column_case: Option<crate::column_case::ColumnCase>,
+    // Rewrite `IN ($n)` when `$n` is an array: "any" for `= ANY($n)`, "expand" for one
+    // placeholder per element
+    #[serde(rename = "inLists")]
+    pub in_lists: Option<crate::in_list::InListStyle>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            retry: true,
+            priority: Priority::Interactive,
+            format: None,
+            cache: None,
+            bridge_cache: None,
+            page_size: None,
+            multi_statement: false,
+            statement_timeout_ms: None,
+            query_id: None,
+            transaction: None,
+            binary_params: None,
+            settings: Default::default(),
+            raw: false,
+            idempotent: false,
+            tags: None,
+            types: None,
+            transform: None,
+            column_case: None,
+            in_lists: None,
+            internal: false,
+        }
+    }
+}
+
+impl QueryOptions {
+    // Whether this query may carry several statements
+    pub fn allows_stacking(&self) -> bool {
+        self.multi_statement || self.internal
+    }
+}
+
+// Options accepted by `send_query()`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct SendOptions {
+    pub settings: crate::local_settings::Settings,
+}
+
+// One request still waiting for its response
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct PendingRequest {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    #[serde(rename = "elapsedMs")]
+    pub elapsed_ms: f64,
+}
+
+// Snapshot passed to the `on_state_change` hook
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct ConnectionStatus {
+    pub connected: bool,
+    pub breaker: BreakerState,
+    #[serde(rename = "consecutiveFailures")]
+    pub consecutive_failures: u32,
+    #[serde(rename = "retryInMs")]
+    pub retry_in_ms: Option<f64>,
+}
+
+// Holds the response for one in-flight request until its future is polled
+#[derive(Default)]
+pub(crate) struct ResponseSlot {
+    response: Option<Result<WebSocketMessage, BridgeError>>,
+    waker: Option<Waker>,
+    // What was sent and when, for `pending_requests`
+    message_type: String,
+    sql: Option<String>,
+    sent_at: f64,
+    // The message to send again if the connection drops first; only for idempotent queries
+    resend: Option<WebSocketMessage>,
+}
+
+impl ResponseSlot {
+    fn complete(&mut self, response: Result<WebSocketMessage, BridgeError>) {
+        self.response = Some(response);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct ResponseFuture {
+    slot: Rc<RefCell<ResponseSlot>>,
+}
+
+impl Future for ResponseFuture {
+    type Output = Result<WebSocketMessage, BridgeError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.response.take() {
+            Some(response) => Poll::Ready(response),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl ClientState {
+    pub fn is_connected(&self) -> bool {
+        self.transport.as_ref().is_some_and(|t| t.is_open())
+    }
+
+    pub fn status(&self) -> ConnectionStatus {
+        ConnectionStatus {
+            connected: self.is_connected(),
+            breaker: self.breaker.state(),
+            consecutive_failures: self.breaker.failures(),
+            retry_in_ms: self.breaker.retry_in(js_sys::Date::now()),
+        }
+    }
+
+    pub fn next_message_id(&mut self, kind: &str) -> String {
+        let pending = &self.pending;
+        self.ids.next(kind, |id| pending.contains_key(id))
+    }
+
+    // Send a `ping`, whose answer the connection stats time
+    pub fn send_ping(&mut self, message: &str) -> Result<String, JsValue> {
+        let message_id = self.next_message_id("ping");
+        let ping_message = WebSocketMessage {
+            message_type: "ping".to_string(),
+            payload: serde_json::Value::String(message.to_string()),
+            id: Some(message_id.clone()),
+        };
+        self.send_message(&ping_message)?;
+        self.connection_stats.ping_sent(&message_id, js_sys::Date::now());
+        Ok(message_id)
+    }
+
+    pub fn send_message(&mut self, message: &WebSocketMessage) -> Result<(), JsValue> {
+        if self.transport.is_none() {
+            return Err(BridgeError::connection("WebSocket not initialized").into());
+        }
+        let intercepted;
+        let message = if self.interceptors.is_empty() {
+            message
+        } else {
+            intercepted = self.interceptors.apply(Phase::Request, message.clone())?;
+            &intercepted
+        };
+        let keyed;
+        let message = match idempotency_keyed(message) {
+            Some(message) => {
+                keyed = message;
+                &keyed
+            }
+            None => message,
+        };
+        if message.message_type == message_type::QUERY {
+            let sql = message.payload.get("sql").and_then(|sql| sql.as_str()).unwrap_or_default();
+            let read_only = if self.read_only { crate::read_only::check(sql) } else { Ok(()) };
+            let checked = read_only.map_err(|reason| (reason, "READ_ONLY")).and_then(|()| match &self.policy {
+                Some(policy) => policy.check(sql).map_err(|reason| (reason, "POLICY_VIOLATION")),
+                None => Ok(()),
+            });
+            if let Err((reason, code)) = checked {
+                let payload = serde_json::json!({ "message": reason, "code": code });
+                return Err(BridgeError::from_error_payload(&payload, message.id.clone()).into());
+            }
+        }
+        let message_json = serde_json::to_string(message).map_err(|e| {
+            JsValue::from_str(&format!("Failed to serialize message: {}", e))
+        })?;
+
+        let size = message_json.len();
+        self.payload_sizes.observe(message, size);
+        if let Some(id) = &message.id {
+            self.duplicates.forget(id);
+        }
+        let frame = crate::compression::compress_outgoing(self, message.id.as_deref(), message_json);
+        let frame = crate::encryption::encrypt_outgoing(self, message.id.as_deref(), frame)?;
+        let frames = crate::chunking::split_outgoing(self, message.id.as_deref(), frame)?;
+        if let Some(transport) = &self.transport {
+            for frame in &frames {
+                transport.send(frame)?;
+            }
+        }
+        self.metrics.record_sent(size, message.message_type == "query");
+        self.connection_stats.record_sent(&message.message_type, &frames);
+        if self.recorder.is_enabled() {
+            let recorded = serde_json::to_value(message).unwrap_or_default();
+            self.recorder.record(Direction::Outbound, recorded, size, js_sys::Date::now());
+        }
+        log_trace!("WASM sent WebSocket message: {}", crate::logging::redact(message));
+        Ok(())
+    }
+
+    // Refuse stacked statements unless `allowed` for this query or by
+    // `allow_multi_statements`. Checked wherever a query is sent or queued.
+    pub fn check_statements(&self, sql: &str, allowed: bool) -> Result<(), BridgeError> {
+        if allowed || self.multi_statement {
+            return Ok(());
+        }
+        crate::sql::single_statement(sql).map_err(|reason| {
+            BridgeError::from_error_payload(&serde_json::json!({ "message": reason, "code": "MULTIPLE_STATEMENTS" }), None)
+        })
+    }
+
+    // Build a query payload with the client's tracing context applied
+    pub fn query_payload(
+        &self,
+        sql: &str,
+        params: Option<Vec<serde_json::Value>>,
+        statement_timeout_ms: Option<u32>,
+    ) -> Result<serde_json::Value, JsValue> {
+        serde_json::to_value(QueryPayload {
+            sql: self.tracing.annotate(sql),
+            params,
+            traceparent: self.tracing.traceparent.clone(),
+            idempotency_key: None,
+            statement_timeout_ms,
+            tenant_id: self.tenant_id.clone(),
+            raw: false,
+            cache: None,
+            page_size: None,
+            binary_params: None,
+            tags: None,
+            types: None,
+        })
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize query: {}", e)))
+    }
+
+    // Fail one in-flight request; returns false if nothing was waiting on `id`
+    pub fn fail_request(&mut self, id: &str, error: BridgeError) -> bool {
+        match self.pending.remove(id) {
+            Some(slot) => {
+                slot.borrow_mut().complete(Err(error));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // In-flight requests as of `now`, longest-running first
+    pub fn pending_requests(&self, now: f64) -> Vec<PendingRequest> {
+        let mut requests: Vec<PendingRequest> = self
+            .pending
+            .iter()
+            .map(|(id, slot)| {
+                let slot = slot.borrow();
+                PendingRequest {
+                    id: id.clone(),
+                    message_type: slot.message_type.clone(),
+                    sql: slot.sql.clone(),
+                    elapsed_ms: (now - slot.sent_at).max(0.0),
+                }
+            })
+            .collect();
+        requests.sort_by(|a, b| b.elapsed_ms.total_cmp(&a.elapsed_ms).then_with(|| a.id.cmp(&b.id)));
+        requests
+    }
+
+    // Fail every in-flight request, e.g. when the socket closes underneath them
+    pub fn fail_pending(&mut self, reason: &str) {
+        self.fail_pending_with(&BridgeError::connection(reason));
+    }
+
+    pub fn fail_pending_with(&mut self, error: &BridgeError) {
+        for (id, slot) in self.pending.drain() {
+            slot.borrow_mut().complete(Err(error.clone().with_query_id(&id)));
+        }
+    }
+
+    // Whether a dropped connection will be reopened on its own
+    pub fn will_reconnect(&self) -> bool {
+        let policy = self.reconnect.policy.as_ref().filter(|policy| policy.enabled);
+        policy.is_some_and(|policy| self.reconnect.attempts < policy.max_attempts)
+            && self.reconnect.armed
+            && !self.closing
+            && !self.idle.suspended
+    }
+
+    // The connection dropped under in-flight requests. Idempotent queries stay pending to
+    // be re-sent when `reconnecting`; the rest fail with `error`. Returns how many stay.
+    pub fn interrupt_pending(&mut self, error: &BridgeError, reconnecting: bool) -> usize {
+        self.pending.retain(|id, slot| {
+            if reconnecting && slot.borrow().resend.is_some() {
+                return true;
+            }
+            slot.borrow_mut().complete(Err(error.clone().with_query_id(id)));
+            false
+        });
+        self.pending.len()
+    }
+
+    // Requests interrupted by a dropped connection, to send again on the new one
+    pub fn interrupted(&self) -> Vec<WebSocketMessage> {
+        let mut messages: Vec<(f64, WebSocketMessage)> = self
+            .pending
+            .values()
+            .filter_map(|slot| {
+                let slot = slot.borrow();
+                slot.resend.clone().map(|message| (slot.sent_at, message))
+            })
+            .collect();
+        messages.sort_by(|a, b| a.0.total_cmp(&b.0));
+        messages.into_iter().map(|(_, message)| message).collect()
+    }
+}
+
+// A write query goes out with its message id as idempotency key, so the bridge answers a
+// repeat of the same message from its cache instead of running the write twice. Keys set
+// by the caller, like the outbox's, are kept.
+pub(crate) fn idempotency_keyed(message: &WebSocketMessage) -> Option<WebSocketMessage> {
+    let id = message.id.as_ref()?;
+    if message.message_type != message_type::QUERY || message.payload.get("idempotencyKey").is_some() {
+        return None;
+    }
+    let sql = message.payload.get("sql").and_then(|sql| sql.as_str())?;
+    if !crate::outbox::is_write(sql) {
+        return None;
+    }
+    let mut keyed = message.clone();
+    keyed.payload["idempotencyKey"] = serde_json::Value::String(id.clone());
+    Some(keyed)
+}
+
+// Report the current status to the state-change hook, called without the state borrowed
+pub(crate) fn emit_state_change(state: &SharedState) {
+    let (hook, status) = {
+        let state = state.borrow();
+        (state.state_hook.clone(), state.status())
+    };
+    if let Some(hook) = hook {
+        if let Ok(status) = crate::to_js(&status) {
+            let _ = hook.call1(&JsValue::NULL, &status);
+        }
+    }
+    crate::events::emit(state, "statechange", &status);
+}
+
+// Gate a connection attempt on the circuit breaker, failing fast while it is open
+pub(crate) fn admit_connection(state: &SharedState) -> Result<(), JsValue> {
+    let now = js_sys::Date::now();
+    let (admitted, before, after, retry_in) = {
+        let mut state = state.borrow_mut();
+        let before = state.breaker.state();
+        let admitted = state.breaker.admit(now);
+        (admitted, before, state.breaker.state(), state.breaker.retry_in(now))
+    };
+    if before != after {
+        log_info!("WASM circuit breaker half-open, probing the bridge");
+        emit_state_change(state);
+    }
+    if admitted {
+        return Ok(());
+    }
+    let message = format!("Circuit breaker open after repeated connection failures; retry in {:.0}ms", retry_in.unwrap_or_default());
+    Err(BridgeError::connection(&message).into())
+}
+
+// Outcome of a graceful close: requests that finished while draining, and those
+// still in flight at the timeout
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DrainSummary {
+    pub completed: usize,
+    pub rejected: usize,
+}
+
+// Stop accepting requests, wait up to `timeout_ms` for in-flight ones to finish,
+// reject the rest, then close the transport
+pub(crate) async fn close_gracefully(state: &SharedState, timeout_ms: f64) -> Result<DrainSummary, JsValue> {
+    let in_flight = {
+        let mut state = state.borrow_mut();
+        state.closing = true;
+        state.reconnect.armed = false;
+        state.pending.len()
+    };
+    log_info!("WASM closing gracefully, draining {} in-flight requests", in_flight);
+    let deadline = js_sys::Date::now() + timeout_ms.max(0.0);
+    loop {
+        let remaining = deadline - js_sys::Date::now();
+        if state.borrow().pending.is_empty() || remaining <= 0.0 {
+            break;
+        }
+        crate::retry::sleep(remaining.min(DRAIN_POLL_MS)).await?;
+    }
+
+    let mut state = state.borrow_mut();
+    let rejected = state.pending.len();
+    if rejected > 0 {
+        log_warn!("WASM rejecting {} requests still in flight at close", rejected);
+        state.fail_pending("Client closing: request did not complete before the close timeout");
+    }
+    state.closing = false;
+    let transport = state.transport.take();
+    drop(state);
+    // Close outside the borrow: a JS transport may report `close` synchronously
+    if let Some(transport) = transport {
+        transport.close();
+    }
+    Ok(DrainSummary { completed: in_flight.saturating_sub(rejected), rejected })
+}
+
+// Runs once the transport is open: negotiate the protocol, restore the previous
+// session's settings and subscriptions, then replay writes queued while offline
+pub(crate) fn on_open(state: &SharedState) {
+    log_info!("WASM WebSocket connected successfully");
+    {
+        let mut state = state.borrow_mut();
+        state.framing.assembler.clear();
+        state.breaker.record_success();
+        state.connection_stats.opened(js_sys::Date::now());
+        state.session.restored = false;
+        state.reconnect.attempts = 0;
+    }
+    emit_state_change(state);
+    crate::events::emit(state, "open", &serde_json::Value::Null);
+    crate::idle::start_timer(state);
+    establish(state);
+}
+
+// Negotiate, authenticate, restore the session and replay the outbox in the background;
+// run on every open and again when the stream has to be resynchronized
+pub(crate) fn establish(state: &SharedState) {
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = crate::handshake::hello(&state).await {
+            log_error!("WASM protocol handshake failed: {:?}", e);
+            return;
+        }
+        let token = state.borrow().auth_token.clone();
+        if let Some(token) = token {
+            match request(&state, "auth", message_type::AUTH, serde_json::json!({ "token": token })).await {
+                Ok(reply) => {
+                    if let Some(token) = reply.payload.get("sessionToken").and_then(|t| t.as_str()) {
+                        let resumed = reply.payload.get("resumed").and_then(|r| r.as_bool()).unwrap_or(false);
+                        crate::handshake::settle_session(&mut state.borrow_mut(), Some(token.to_string()), resumed);
+                    }
+                }
+                Err(e) => {
+                    log_error!("WASM authentication failed: {:?}", e);
+                    // Fail queries right away instead of leaving them waiting on setup
+                    let mut state = state.borrow_mut();
+                    state.protocol.refused = Some("Authentication with the bridge failed".to_string());
+                    state.fail_pending("Authentication with the bridge failed");
+                    return;
+                }
+            }
+        }
+        // A new backend session: Postgres released the locks with the old one
+        if !state.borrow().resumption.resumed {
+            crate::advisory::lost(&state, "The bridge session ended");
+        }
+        crate::session_state::restore(&state).await;
+        resend_interrupted(&state);
+        if state.borrow().outbox.len() > 0 {
+            if let Err(e) = crate::outbox::replay(state).await {
+                log_warn!("WASM outbox replay failed: {:?}", e);
+            }
+        }
+    });
+}
+
+// Send idempotent queries the previous connection dropped, in their original order and
+// under their original ids, so their promises settle from the new connection
+fn resend_interrupted(state: &SharedState) {
+    let messages = state.borrow().interrupted();
+    if messages.is_empty() {
+        return;
+    }
+    log_info!("WASM re-sending {} queries interrupted by the reconnect", messages.len());
+    let mut state = state.borrow_mut();
+    for message in messages {
+        if let Err(e) = state.send_message(&message) {
+            let id = message.id.clone().unwrap_or_default();
+            log_warn!("WASM failed to re-send {}: {:?}", id, e);
+            let error = BridgeError::connection_lost("re-sending after the reconnect failed");
+            state.fail_request(&id, error.with_query_id(&id));
+        }
+    }
+}
+
+// Route an incoming frame to the request waiting on its id, then to the user handler
+pub(crate) fn dispatch_incoming(state: &SharedState, text: &str) {
+    let incremental = {
+        let mut state = state.borrow_mut();
+        state.metrics.record_received(text.len());
+        if state.recorder.is_enabled() {
+            // Keep frames that aren't valid JSON as plain strings
+            let recorded = serde_json::from_str(text).unwrap_or_else(|_| serde_json::Value::String(text.to_string()));
+            state.recorder.record(Direction::Inbound, recorded, text.len(), js_sys::Date::now());
+        }
+        if let Some((id, error)) = state.result_budget.check(text) {
+            log_warn!("WASM failing result {}: {:?}", id, error);
+            state.fail_request(&id, error);
+            return;
+        }
+        state.incremental.applies_to(text)
+    };
+    if incremental {
+        crate::incremental::dispatch(state, text.to_string());
+        return;
+    }
+    let strict = state.borrow().strict_protocol;
+    let parsed = if strict {
+        match crate::strict::check(text) {
+            Ok(message) => Some(message),
+            Err(violation) => {
+                let garbled = violation.message_type.is_none().then(|| violation.reason.clone());
+                crate::strict::reject(state, violation);
+                if let Some(reason) = garbled {
+                    crate::resync::recover(state, text, &reason);
+                }
+                return;
+            }
+        }
+    } else {
+        match serde_json::from_str::<WebSocketMessage>(text) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                crate::resync::recover(state, text, &format!("Not a bridge message: {}", e));
+                None
+            }
+        }
+    };
+    deliver(state, text.to_string(), parsed);
+}
+
+// Second half of `dispatch_incoming`, once the frame has been parsed
+pub(crate) fn deliver(state: &SharedState, mut text: String, parsed: Option<WebSocketMessage>) {
+    let mut slow_query = None;
+    let mut notification = None;
+    let mut change = None;
+    let mut listen_lost = None;
+    let mut progress = None;
+    let mut shutdown = None;
+    let mut schema_change = None;
+    let mut row_delta = None;
+
+    // A response already delivered once is dropped before it reaches any handler
+    if let Some(message) = &parsed {
+        let answers = matches!(message.message_type.as_str(), message_type::RESULT | message_type::ERROR);
+        if let Some(id) = message.id.as_deref().filter(|_| answers) {
+            let mut state = state.borrow_mut();
+            if !state.duplicates.admit(id) {
+                state.connection_stats.duplicate_dropped();
+                log_debug!("WASM dropped a duplicate response to {}", id);
+                return;
+            }
+        }
+    }
+
+    // Interceptors run without the state borrowed so they may call back into the client
+    let interceptors = state.borrow().interceptors.clone();
+    let parsed = match parsed {
+        Some(message) if !interceptors.is_empty() => match interceptors.apply(Phase::Response, message) {
+            Ok(message) => {
+                text = serde_json::to_string(&message).unwrap_or(text);
+                Some(message)
+            }
+            Err(e) => {
+                log_error!("WASM response interceptor failed: {:?}", e);
+                None
+            }
+        },
+        parsed => parsed,
+    };
+
+    let (handler, slow_query_hook, progress_hook) = {
+        let mut state = state.borrow_mut();
+        if let Some(message) = parsed {
+            state.connection_stats.record_received(&message.message_type, message.id.as_deref(), js_sys::Date::now());
+            match message.message_type.as_str() {
+                // Query results echo their SQL; other results (pong, welcome) don't
+                message_type::RESULT if message.payload.get("sql").is_some() => {
+                    state.metrics.record_result(&message.payload);
+                    state.query_stats.record_result(&message.payload);
+                    slow_query = state.slow_queries.observe(&message.payload);
+                }
+                message_type::ERROR => {
+                    state.metrics.record_error(&message.payload);
+                    state.query_stats.record_error(&message.payload);
+                }
+                message_type::NOTIFICATION => notification = Some(message.payload.clone()),
+                message_type::LISTEN_LOST => listen_lost = Some(message.payload.clone()),
+                message_type::CHANGE => change = Some(message.payload.clone()),
+                message_type::SHUTDOWN => shutdown = Some(message.payload.clone()),
+                message_type::SCHEMA_CHANGE => schema_change = Some(message.payload.clone()),
+                message_type::ROW_DELTA => row_delta = Some(message.payload.clone()),
+                message_type::PROGRESS => {
+                    if let Some(id) = &message.id {
+                        state.queries.advance(id, QueryStatus::Executing);
+                    }
+                    progress = Some((message.id.clone(), message.payload.clone()));
+                }
+                _ => {}
+            }
+            // Bridges that predate `hello` reject it with an error that carries no id
+            let id = message.id.clone().or_else(|| match message.message_type.as_str() {
+                message_type::ERROR => state.protocol.pending_hello.clone(),
+                _ => None,
+            });
+            // Progress shares the query's id but the query is still running
+            let slot = id.filter(|_| progress.is_none()).and_then(|id| state.pending.remove(&id));
+            if let Some(slot) = slot {
+                slot.borrow_mut().complete(Ok(message));
+            }
+        }
+        (state.message_handler.clone(), state.slow_query_hook.clone(), state.progress_hook.clone())
+    };
+
+    // Live queries take a row delta as it is; everything else sees its NOTIFY
+    let patched = row_delta.is_some();
+    if let Some(payload) = row_delta {
+        notification = crate::row_delta::received(state, &payload);
+    }
+    if let Some(notification) = notification {
+        if let Some(channel) = notification.get("channel").and_then(|c| c.as_str()) {
+            if !patched {
+                crate::live::notify(state, channel);
+            }
+            crate::cache_invalidation::notify(state, channel);
+        }
+        crate::notifications::deliver(state, &notification);
+        crate::events::emit(state, "notification", &notification);
+        let channel = notification.get("channel").and_then(|c| c.as_str()).map(String::from);
+        if let Some(channel) = channel {
+            crate::tab_fanout::publish(state, FanoutKind::Notification, channel, notification);
+        }
+    }
+    if let Some(change) = change {
+        crate::cdc::deliver(state, &change);
+        if let Some(event) = change.get("change").and_then(crate::cdc::parse_wal2json) {
+            let topic = format!("{}.{}", event.schema, event.table);
+            crate::tab_fanout::publish(state, FanoutKind::Change, topic, serde_json::to_value(&event).unwrap_or_default());
+        }
+    }
+    if let Some(payload) = listen_lost {
+        crate::listen_state::bridge_lost(state, &payload);
+    }
+    if let Some(payload) = schema_change {
+        crate::schema_change::received(state, &payload);
+    }
+    if let Some(entry) = slow_query {
+        if let (Some(hook), Ok(js_entry)) = (slow_query_hook, crate::to_js(&entry)) {
+            let _ = hook.call1(&JsValue::NULL, &js_entry);
+        }
+        crate::events::emit(state, "slowquery", &entry);
+    }
+    if let (Some((Some(id), info)), Some(hook)) = (progress, progress_hook) {
+        if let Ok(info) = crate::to_js(&info) {
+            let _ = hook.call2(&JsValue::NULL, &JsValue::from_str(&id), &info);
+        }
+    }
+    if let Some(handler) = handler {
+        let _ = handler.call1(&JsValue::NULL, &JsValue::from_str(&text));
+    }
+    if let Some(shutdown) = shutdown {
+        crate::restart::bridge_shutdown(state, &shutdown);
+    }
+}
+
+// Send a message and return its id plus a future for the response carrying that id
+pub(crate) fn send_request(state: &SharedState, kind: &str, message_type: &str, payload: serde_json::Value) -> Result<(String, ResponseFuture), JsValue> {
+    send_request_as(state, None, kind, message_type, payload, false)
+}
+
+// `send_request` under a message id chosen by the caller. A `resendable` request survives
+// a dropped connection and goes out again once it is back.
+pub(crate) fn send_request_as(
+    state: &SharedState,
+    id: Option<&str>,
+    kind: &str,
+    message_type: &str,
+    payload: serde_json::Value,
+    resendable: bool,
+) -> Result<(String, ResponseFuture), JsValue> {
+    let slot = ResponseSlot {
+        message_type: message_type.to_string(),
+        sql: payload.get("sql").and_then(|sql| sql.as_str()).map(str::to_string),
+        sent_at: js_sys::Date::now(),
+        ..ResponseSlot::default()
+    };
+    let slot = Rc::new(RefCell::new(slot));
+    let shared = state.clone();
+    let mut state = state.borrow_mut();
+    if let Some(reason) = &state.protocol.refused {
+        return Err(BridgeError::connection(reason).into());
+    }
+    if state.closing {
+        return Err(BridgeError::connection("Client closing: no new requests are accepted").into());
+    }
+    if !state.is_connected() {
+        return Err(BridgeError::connection("WebSocket not connected").into());
+    }
+
+    state.idle.last_activity = js_sys::Date::now();
+    let message_id = match id {
+        Some(id) => id.to_string(),
+        None => state.next_message_id(kind),
+    };
+    let message = WebSocketMessage {
+        message_type: message_type.to_string(),
+        payload,
+        id: Some(message_id.clone()),
+    };
+    state.send_message(&message)?;
+    if resendable {
+        slot.borrow_mut().resend = Some(message);
+    }
+    state.pending.insert(message_id.clone(), slot.clone());
+    let timeout_ms = state.timeouts.request_ms;
+    drop(state);
+    crate::payload_size::report(&shared);
+    if let Some(timeout_ms) = timeout_ms {
+        expire_request(shared, message_id.clone(), timeout_ms);
+    }
+    Ok((message_id, ResponseFuture { slot }))
+}
+
+// Fail a request still unanswered after `timeout_ms`
+fn expire_request(state: SharedState, id: String, timeout_ms: f64) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = crate::retry::sleep(timeout_ms).await;
+        let message = format!("No response from the bridge within {}ms", timeout_ms);
+        let payload = serde_json::json!({ "message": message, "code": "REQUEST_TIMEOUT" });
+        if state.borrow_mut().fail_request(&id, BridgeError::from_error_payload(&payload, Some(id.clone()))) {
+            log_warn!("WASM request {} timed out", id);
+        }
+    });
+}
+
+// Send a message and wait for the response carrying the same id
+pub(crate) async fn request(state: &SharedState, kind: &str, message_type: &str, payload: serde_json::Value) -> Result<WebSocketMessage, JsValue> {
+    request_as(state, None, kind, message_type, payload).await
+}
+
+// `request` under a message id chosen by the caller
+pub(crate) async fn request_as(
+    state: &SharedState,
+    id: Option<&str>,
+    kind: &str,
+    message_type: &str,
+    payload: serde_json::Value,
+) -> Result<WebSocketMessage, JsValue> {
+    crate::idle::resume(state).await?;
+    let (_, response) = send_request_as(state, id, kind, message_type, payload, false)?;
+    let response = response.await?;
+    if response.message_type == message_type::ERROR {
+        return Err(BridgeError::from_error_payload(&response.payload, response.id.clone()).into());
+    }
+    Ok(response)
+}
+
+// Ask the bridge to cancel a running query on the backend. Resolves to false when the
+// query isn't running there (already finished, or not started yet).
+pub(crate) async fn cancel_query(state: &SharedState, query_id: &str) -> Result<bool, JsValue> {
+    let supported = state.borrow().protocol.negotiated.is_some_and(|n| n.capabilities.cancel);
+    if !supported {
+        return Err(BridgeError::connection("The bridge does not support cancelling queries").into());
+    }
+    let (_, response) = send_request(state, "cancel", message_type::CANCEL, serde_json::json!({ "queryId": query_id }))?;
+    let response = response.await?;
+    if response.message_type == message_type::ERROR {
+        return Err(BridgeError::from_error_payload(&response.payload, response.id.clone()).into());
+    }
+    Ok(response.payload.get("cancelled").and_then(|c| c.as_bool()).unwrap_or(false))
+}
+
+// Execute a query and return the result payload
+pub(crate) async fn execute_query(state: &SharedState, sql: &str, params: Option<Vec<serde_json::Value>>) -> Result<serde_json::Value, JsValue> {
+    execute_query_with(state, sql, params, QueryOptions::default()).await
+}
+
+// Execute a query, retrying reads that fail with a retryable SQLSTATE
+pub(crate) async fn execute_query_with(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<serde_json::Value>>,
+    options: QueryOptions,
+) -> Result<serde_json::Value, JsValue> {
+    if !options.settings.is_empty() {
+        return Box::pin(crate::local_settings::run(state, sql, params, options)).await;
+    }
+    // Writes may have taken effect before failing, so only reads are retried
+    let retryable = options.retry && !crate::outbox::is_write(sql);
+    if !state.borrow().query_tape.is_replaying() {
+        crate::idle::resume(state).await?;
+    }
+    let mut attempt = 1;
+    loop {
+        let response = query_response(state, sql, params.clone(), options.clone()).await?;
+        if response.message_type != message_type::ERROR {
+            state.borrow_mut().restart.database = false;
+            return Ok(response.payload);
+        }
+        let restarting = crate::restart::observe_error(state, &response.payload, response.id.clone());
+        let policy = state.borrow().retry_policy.clone();
+        let sql_state = response.payload.get("sqlState").and_then(|s| s.as_str());
+        if retryable && policy.should_retry(attempt, sql_state) {
+            let delay = policy.delay_ms(attempt, js_sys::Math::random());
+            log_info!("WASM retrying query after SQLSTATE {} (attempt {}) in {:.0}ms", sql_state.unwrap_or_default(), attempt + 1, delay);
+            crate::retry::sleep(delay).await?;
+            attempt += 1;
+            continue;
+        }
+        let error = restarting.unwrap_or_else(|| BridgeError::from_error_payload(&response.payload, response.id.clone()));
+        return Err(error.into());
+    }
+}
+
+// One round trip for a query, once the rate and concurrency limits allow it. In
+// fixture replay mode the answer comes from the recording instead of the bridge.
+async fn query_response(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<serde_json::Value>>,
+    options: QueryOptions,
+) -> Result<WebSocketMessage, JsValue> {
+    let (replaying, recording) = {
+        let state = state.borrow();
+        state.check_statements(sql, options.allows_stacking())?;
+        (state.query_tape.is_replaying(), state.query_tape.is_recording())
+    };
+    let queued = js_sys::Date::now();
+    crate::transaction::admit(state, options.transaction).await;
+    if !replaying {
+        crate::rate_limit::admit(state, sql, params.as_deref()).await?;
+        crate::backpressure::throttle(state).await?;
+    }
+    let response = if replaying {
+        let _permit = admitted(state, &options).await;
+        state.borrow_mut().query_tape.replay(sql, params.as_deref())?
+    } else {
+        let recorded_params = if recording { params.clone() } else { None };
+        let audited_params = if state.borrow().audit.is_enabled() { Some(params.clone()) } else { None };
+        let started = js_sys::Date::now();
+        let mut params = params;
+        let mut attempt = 0;
+        // A throttled query is sent again once its retry-after has passed
+        let response = loop {
+            let resend = state.borrow().throttled.retry.max_retries > attempt;
+            let sent = if resend { params.clone() } else { params.take() };
+            let permit = admitted(state, &options).await;
+            let response = round_trip(state, sql, sent, &options).await;
+            drop(permit);
+            match response {
+                Ok(response) if response.message_type == message_type::THROTTLED => {
+                    attempt += 1;
+                    if let Err(e) = crate::throttled::honor(state, sql, &response, attempt).await {
+                        break Err(e);
+                    }
+                }
+                response => break response,
+            }
+        };
+        if let Some(params) = audited_params {
+            crate::audit::observe(state, sql, &params, options.tags.as_ref(), started, &response);
+        }
+        let mut response = response?;
+        if response.message_type == message_type::RESULT {
+            state.borrow_mut().backpressure.observe(&response.payload, js_sys::Date::now());
+            crate::query_timing::complete(&mut response.payload, started - queued, js_sys::Date::now() - started);
+        }
+        if recording {
+            state.borrow_mut().query_tape.record(sql, recorded_params, &response);
+        }
+        response
+    };
+    Ok(response)
+}
+
+// Wait for the transaction gate and a dispatch permit, right before a query is sent. The
+// gate is checked again once the permit is taken: a transaction may have sent BEGIN in
+// the meantime, and a query outside it must not run between its BEGIN and COMMIT.
+async fn admitted(state: &SharedState, options: &QueryOptions) -> crate::dispatch::Permit {
+    loop {
+        crate::transaction::admit(state, options.transaction).await;
+        let permit = crate::dispatch::acquire(state, options.priority).await;
+        if state.borrow().transactions.admits(options.transaction) {
+            return permit;
+        }
+        // Let the transaction's own statements have the permit
+        drop(permit);
+    }
+}
+
+// Send one query to the bridge and wait for its answer. A query cancelled while it
+// was queued fails here instead of being sent.
+async fn round_trip(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<serde_json::Value>>,
+    options: &QueryOptions,
+) -> Result<WebSocketMessage, JsValue> {
+    let query_id = options.query_id.as_deref();
+    if let Some(id) = query_id {
+        if state.borrow_mut().queries.take_cancelled(id) {
+            let payload = serde_json::json!({ "message": "Query cancelled before it was sent", "code": "CANCELLED" });
+            return Err(BridgeError::from_error_payload(&payload, Some(id.to_string())).into());
+        }
+    }
+    let mut payload = state.borrow().query_payload(sql, params, options.statement_timeout_ms)?;
+    if options.raw {
+        payload["raw"] = serde_json::Value::Bool(true);
+    }
+    let capabilities = state.borrow().protocol.negotiated.map(|n| n.capabilities).unwrap_or_default();
+    if let Some(cache) = options.bridge_cache.as_ref().filter(|_| capabilities.result_cache) {
+        payload["cache"] = bridge_protocol::to_value(cache);
+    }
+    if let Some(page_size) = options.page_size.filter(|_| capabilities.continuation) {
+        payload["pageSize"] = serde_json::json!(page_size);
+    }
+    if let Some(binary) = &options.binary_params {
+        if !capabilities.binary {
+            return Err(crate::binary_params::unsupported());
+        }
+        payload["binaryParams"] = bridge_protocol::to_value(binary);
+    }
+    if let Some(tags) = &options.tags {
+        payload["tags"] = bridge_protocol::to_value(tags);
+    }
+    if let Some(types) = &options.types {
+        payload["types"] = bridge_protocol::to_value(types);
+    }
+    // A statement of a transaction can't be replayed outside it on a new backend
+    let resendable = options.idempotent && options.transaction.is_none();
+    let (id, response) = send_request_as(state, query_id, "query", "query", payload, resendable)?;
+    state.borrow_mut().queries.advance(&id, QueryStatus::Sent);
+    let mut response = response.await?;
+    state.borrow_mut().result_schemas.resolve(&mut response.payload).map_err(|reason| crate::result_schema::unknown(reason, Some(id)))?;
+    Ok(response)
+}
+
+// Run one statement of a transaction without retries, keeping a failure's SQLSTATE.
+// Resolves with whether it returned any rows.
+async fn transaction_statement(state: &SharedState, sql: &str, params: Option<Vec<serde_json::Value>>) -> Result<bool, (Option<String>, JsValue)> {
+    let options = QueryOptions { internal: true, ..QueryOptions::default() };
+    let response = query_response(state, sql, params, options).await.map_err(|e| (None, e))?;
+    if response.message_type == message_type::ERROR {
+        let sql_state = response.payload.get("sqlState").and_then(|s| s.as_str()).map(String::from);
+        let restarting = crate::restart::observe_error(state, &response.payload, response.id.clone());
+        let error = restarting.unwrap_or_else(|| BridgeError::from_error_payload(&response.payload, response.id.clone()));
+        return Err((sql_state, error.into()));
+    }
+    Ok(response.payload.get("rows").and_then(|rows| rows.as_array()).is_some_and(|rows| !rows.is_empty()))
+}
+
+async fn transaction_attempt(state: &SharedState, guard: &[Statement], statements: &[Statement]) -> Result<bool, (Option<String>, JsValue)> {
+    transaction_statement(state, "BEGIN", None).await?;
+    let ran = async {
+        let mut skip = false;
+        for (sql, params) in guard {
+            skip = transaction_statement(state, sql, params.clone()).await?;
+        }
+        if skip {
+            return Ok(false);
+        }
+        for (sql, params) in statements {
+            transaction_statement(state, sql, params.clone()).await?;
+        }
+        Ok(true)
+    }
+    .await;
+    match ran {
+        Ok(true) => transaction_statement(state, "COMMIT", None).await.map(|_| true),
+        Ok(false) => transaction_statement(state, "ROLLBACK", None).await.map(|_| false),
+        Err(e) => {
+            let _ = transaction_statement(state, "ROLLBACK", None).await;
+            Err(e)
+        }
+    }
+}
+
+// Run statements between BEGIN and COMMIT, rolling back on the first failure. A
+// serialization failure or deadlock re-runs the whole transaction with backoff.
+pub(crate) async fn run_in_transaction(state: &SharedState, statements: Vec<Statement>) -> Result<(), JsValue> {
+    run_in_transaction_unless(state, Vec::new(), statements).await.map(|_| ())
+}
+
+// `run_in_transaction`, with `guard` run first in the same transaction. When the last
+// guard statement returns a row, it rolls back without running `statements`. Resolves
+// with whether they ran.
+pub(crate) async fn run_in_transaction_unless(state: &SharedState, guard: Vec<Statement>, statements: Vec<Statement>) -> Result<bool, JsValue> {
+    let policy = state.borrow().retry_policy.clone();
+    crate::retry::rerun_transaction(&policy, || transaction_attempt(state, &guard, &statements), |attempt, sql_state| transaction_pause(&policy, attempt, sql_state)).await
+}
+
+// Back off before re-running a transaction that hit a serialization failure or deadlock
+pub(crate) async fn transaction_pause(policy: &RetryPolicy, attempt: u32, sql_state: String) -> Result<(), JsValue> {
+    let delay = policy.delay_ms(attempt, js_sys::Math::random());
+    log_info!("WASM re-running transaction after SQLSTATE {} (attempt {}) in {:.0}ms", sql_state, attempt + 1, delay);
+    crate::retry::sleep(delay).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_statements() {
+        let mut state = ClientState::default();
+        let stacked = "SELECT 1; DROP TABLE users";
+        assert_eq!(state.check_statements(stacked, false).unwrap_err().code(), Some("MULTIPLE_STATEMENTS"));
+        assert!(state.check_statements(stacked, true).is_ok());
+        assert!(state.check_statements("SELECT ';'", false).is_ok());
+        state.multi_statement = true;
+        assert!(state.check_statements(stacked, false).is_ok());
+    }
+
+    #[test]
+    fn test_stacked_statements_rejected_through_explain_and_cached_query() {
+        let state = ClientState::default();
+        let stacked = "SELECT 1; DROP TABLE t";
+        // `explain` sends the caller's SQL wrapped in EXPLAIN and `cached_query` sends it as
+        // is, both with default options, so `query_response` checks them like `query()`
+        let explained = crate::explain::explain_sql(stacked, crate::explain::ExplainOptions::default());
+        for sql in [explained.as_str(), stacked] {
+            let error = state.check_statements(sql, QueryOptions::default().allows_stacking()).unwrap_err();
+            assert_eq!(error.code(), Some("MULTIPLE_STATEMENTS"));
+        }
+        let internal = QueryOptions { internal: true, ..QueryOptions::default() };
+        assert!(state.check_statements(stacked, internal.allows_stacking()).is_ok());
+    }
+
+    #[test]
+    fn test_pending_requests_longest_running_first() {
+        let mut state = ClientState::default();
+        let slot = |message_type: &str, sql: Option<&str>, sent_at: f64| {
+            let slot = ResponseSlot { message_type: message_type.to_string(), sql: sql.map(str::to_string), sent_at, ..ResponseSlot::default() };
+            Rc::new(RefCell::new(slot))
+        };
+        state.pending.insert("wasm_query_2".to_string(), slot("query", Some("SELECT 2"), 1_500.0));
+        state.pending.insert("wasm_query_1".to_string(), slot("query", Some("SELECT 1"), 1_000.0));
+        state.pending.insert("wasm_ping_3".to_string(), slot("ping", None, 1_900.0));
+
+        let pending = state.pending_requests(2_000.0);
+        let ids: Vec<&str> = pending.iter().map(|request| request.id.as_str()).collect();
+        assert_eq!(ids, vec!["wasm_query_1", "wasm_query_2", "wasm_ping_3"]);
+        assert_eq!(pending[0].elapsed_ms, 1_000.0);
+        assert_eq!(pending[0].sql.as_deref(), Some("SELECT 1"));
+    }
+
+    #[test]
+    fn test_interrupt_keeps_idempotent_queries() {
+        let mut state = ClientState::default();
+        let message = |id: &str| WebSocketMessage { message_type: "query".to_string(), payload: serde_json::json!({}), id: Some(id.to_string()) };
+        let slot = |resend: Option<WebSocketMessage>, sent_at: f64| Rc::new(RefCell::new(ResponseSlot { resend, sent_at, ..ResponseSlot::default() }));
+        let plain = slot(None, 1.0);
+        state.pending.insert("q1".to_string(), plain.clone());
+        state.pending.insert("q3".to_string(), slot(Some(message("q3")), 3.0));
+        state.pending.insert("q2".to_string(), slot(Some(message("q2")), 2.0));
+
+        let error = BridgeError::connection_lost("WebSocket closed");
+        assert_eq!(state.interrupt_pending(&error, true), 2);
+        let failed = plain.borrow_mut().response.take().unwrap().unwrap_err();
+        assert_eq!((failed.code(), failed.query_id()), (Some("CONNECTION_LOST"), Some("q1")));
+        let resent: Vec<Option<String>> = state.interrupted().into_iter().map(|m| m.id).collect();
+        assert_eq!(resent, vec![Some("q2".to_string()), Some("q3".to_string())]);
+
+        assert_eq!(state.interrupt_pending(&error, false), 0);
+    }
+
+    #[test]
+    fn test_write_ids_become_idempotency_keys() {
+        let query = |payload: serde_json::Value| WebSocketMessage {
+            message_type: "query".to_string(),
+            payload,
+            id: Some("wasm_query_1".to_string()),
+        };
+        let keyed = idempotency_keyed(&query(serde_json::json!({ "sql": "INSERT INTO t VALUES (1)" }))).unwrap();
+        assert_eq!(keyed.payload["idempotencyKey"], "wasm_query_1");
+        assert!(idempotency_keyed(&query(serde_json::json!({ "sql": "SELECT 1" }))).is_none());
+        assert!(idempotency_keyed(&query(serde_json::json!({ "sql": "DELETE FROM t", "idempotencyKey": "idem_1" }))).is_none());
+    }
+}
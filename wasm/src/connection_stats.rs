@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tsify::Tsify;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct ConnectionStatsSnapshot {
+    pub connected: bool,
+    // Wire totals, after compression, encryption and chunking
+    #[serde(rename = "bytesSent")]
+    pub bytes_sent: u64,
+    #[serde(rename = "bytesReceived")]
+    pub bytes_received: u64,
+    #[serde(rename = "framesSent")]
+    pub frames_sent: u64,
+    #[serde(rename = "framesReceived")]
+    pub frames_received: u64,
+    // Messages by `type`, counted once however many frames carried them
+    #[serde(rename = "messagesSent")]
+    pub messages_sent: BTreeMap<String, u64>,
+    #[serde(rename = "messagesReceived")]
+    pub messages_received: BTreeMap<String, u64>,
+    // Opens after the first one
+    pub reconnects: u64,
+    // Time since the current connection opened; null while disconnected
+    #[serde(rename = "uptimeMs")]
+    pub uptime_ms: Option<f64>,
+    // Round trip of the latest answered `ping`
+    #[serde(rename = "lastRttMs")]
+    pub last_rtt_ms: Option<f64>,
+    #[serde(rename = "lastHeartbeatAt")]
+    pub last_heartbeat_at: Option<f64>,
+    // Times the stream fell out of step and was resynchronized
+    pub resyncs: u64,
+    // Responses the bridge delivered twice, dropped the second time
+    #[serde(rename = "duplicatesDropped")]
+    pub duplicates_dropped: u64,
+}
+
+// Per-client transport counters for `connection_stats()`; unlike `metrics()` these
+// cover every frame, not just queries, and survive reconnects
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    bytes_sent: u64,
+    bytes_received: u64,
+    frames_sent: u64,
+    frames_received: u64,
+    messages_sent: BTreeMap<String, u64>,
+    messages_received: BTreeMap<String, u64>,
+    opens: u64,
+    connected_at: Option<f64>,
+    // Id and send time of the latest ping still waiting for its answer
+    ping: Option<(String, f64)>,
+    last_rtt_ms: Option<f64>,
+    last_heartbeat_at: Option<f64>,
+    resyncs: u64,
+    duplicates_dropped: u64,
+}
+
+impl ConnectionStats {
+    pub fn opened(&mut self, now: f64) {
+        self.opens += 1;
+        self.connected_at = Some(now);
+    }
+
+    pub fn closed(&mut self) {
+        self.connected_at = None;
+        self.ping = None;
+    }
+
+    // One outbound message and the frames it went out as
+    pub fn record_sent(&mut self, message_type: &str, frames: &[String]) {
+        self.frames_sent += frames.len() as u64;
+        self.bytes_sent += frames.iter().map(|frame| frame.len() as u64).sum::<u64>();
+        *self.messages_sent.entry(message_type.to_string()).or_insert(0) += 1;
+    }
+
+    // A heartbeat went out; its answer sets the round trip
+    pub fn ping_sent(&mut self, id: &str, now: f64) {
+        self.ping = Some((id.to_string(), now));
+    }
+
+    pub fn resynced(&mut self) {
+        self.resyncs += 1;
+    }
+
+    pub fn duplicate_dropped(&mut self) {
+        self.duplicates_dropped += 1;
+    }
+
+    pub fn record_frame(&mut self, bytes: usize) {
+        self.frames_received += 1;
+        self.bytes_received += bytes as u64;
+    }
+
+    pub fn record_received(&mut self, message_type: &str, id: Option<&str>, now: f64) {
+        *self.messages_received.entry(message_type.to_string()).or_insert(0) += 1;
+        let answers_ping = self.ping.as_ref().is_some_and(|(ping, _)| Some(ping.as_str()) == id);
+        if answers_ping {
+            if let Some((_, sent_at)) = self.ping.take() {
+                self.last_rtt_ms = Some(now - sent_at);
+                self.last_heartbeat_at = Some(now);
+            }
+        }
+    }
+
+    pub fn snapshot(&self, now: f64) -> ConnectionStatsSnapshot {
+        ConnectionStatsSnapshot {
+            connected: self.connected_at.is_some(),
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+            frames_sent: self.frames_sent,
+            frames_received: self.frames_received,
+            messages_sent: self.messages_sent.clone(),
+            messages_received: self.messages_received.clone(),
+            reconnects: self.opens.saturating_sub(1),
+            uptime_ms: self.connected_at.map(|at| now - at),
+            last_rtt_ms: self.last_rtt_ms,
+            last_heartbeat_at: self.last_heartbeat_at,
+            resyncs: self.resyncs,
+            duplicates_dropped: self.duplicates_dropped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_frames_messages_and_ping_rtt() {
+        let mut stats = ConnectionStats::default();
+        stats.opened(1_000.0);
+        stats.record_sent("query", &["{\"a\":1}".to_string(), "{}".to_string()]);
+        stats.record_sent("ping", &["{}".to_string()]);
+        stats.ping_sent("p1", 1_100.0);
+        stats.record_frame(40);
+        stats.record_received("result", Some("q1"), 1_120.0);
+        stats.record_frame(10);
+        stats.record_received("result", Some("p1"), 1_130.0);
+
+        let snapshot = stats.snapshot(1_500.0);
+        assert_eq!((snapshot.frames_sent, snapshot.bytes_sent), (3, 11));
+        assert_eq!((snapshot.frames_received, snapshot.bytes_received), (2, 50));
+        assert_eq!(snapshot.messages_sent.get("query"), Some(&1));
+        assert_eq!(snapshot.messages_received.get("result"), Some(&2));
+        assert_eq!((snapshot.last_rtt_ms, snapshot.last_heartbeat_at), (Some(30.0), Some(1_130.0)));
+        assert_eq!(snapshot.uptime_ms, Some(500.0));
+
+        stats.closed();
+        stats.opened(2_000.0);
+        let snapshot = stats.snapshot(2_250.0);
+        assert_eq!((snapshot.reconnects, snapshot.uptime_ms), (1, Some(250.0)));
+    }
+}
@@ -0,0 +1,33 @@
+use bridge_protocol::{message_type, FetchMorePayload};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+use crate::errors::BridgeError;
+
+fn payload(token: &str, page_size: Option<u32>, close: bool) -> Value {
+    bridge_protocol::to_value(&FetchMorePayload { token: token.to_string(), page_size, close })
+}
+
+fn check_supported(state: &SharedState) -> Result<(), JsValue> {
+    if state.borrow().protocol.negotiated.is_some_and(|n| n.capabilities.continuation) {
+        Ok(())
+    } else {
+        Err(BridgeError::connection("The bridge does not support continuation tokens").into())
+    }
+}
+
+// The next page of a paged query, with a new `continuation` while rows may remain
+pub(crate) async fn fetch_more(state: &SharedState, token: &str, page_size: Option<u32>) -> Result<Value, JsValue> {
+    check_supported(state)?;
+    let mut response = connection::request(state, "fetch_more", message_type::FETCH_MORE, payload(token, page_size, false)).await?;
+    state.borrow_mut().result_schemas.resolve(&mut response.payload).map_err(|reason| crate::result_schema::unknown(reason, response.id.clone()))?;
+    Ok(response.payload)
+}
+
+// Abandon a paged query early, closing its cursor on the bridge; false if it had finished
+pub(crate) async fn close(state: &SharedState, token: &str) -> Result<bool, JsValue> {
+    check_supported(state)?;
+    let response = connection::request(state, "fetch_more", message_type::FETCH_MORE, payload(token, None, true)).await?;
+    Ok(response.payload.get("closed").and_then(|closed| closed.as_bool()).unwrap_or(false))
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query_with, QueryOptions, SharedState};
+use crate::errors::BridgeError;
+use crate::explain::{explain_sql, parse_explain, ExplainOptions, PlanSummary};
+use crate::sql::{tokenize, Token};
+
+// Words that start a statement EXPLAIN accepts; anything else runs without a preflight
+const EXPLAINABLE: [&str; 8] = ["select", "with", "values", "table", "insert", "update", "delete", "merge"];
+
+// Planner estimates above which `query()` refuses to run a statement
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Tsify)]
+pub struct CostGuard {
+    #[serde(default, rename = "maxCost")]
+    pub max_cost: Option<f64>,
+    #[serde(default, rename = "maxRows")]
+    pub max_rows: Option<f64>,
+}
+
+// Detail of a COST_LIMIT_EXCEEDED error: the estimates next to the limits they broke
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    #[serde(rename = "totalCost")]
+    pub total_cost: f64,
+    #[serde(rename = "planRows")]
+    pub plan_rows: f64,
+    #[serde(rename = "maxCost")]
+    pub max_cost: Option<f64>,
+    #[serde(rename = "maxRows")]
+    pub max_rows: Option<f64>,
+}
+
+pub fn is_explainable(sql: &str) -> bool {
+    let first = tokenize(sql).into_iter().find_map(|token| match token {
+        Token::Word(word) => Some(word),
+        _ => None,
+    });
+    first.is_some_and(|word| EXPLAINABLE.contains(&word.as_str()))
+}
+
+impl CostGuard {
+    // Why the plan may not run, or None when it is within both limits
+    pub fn violation(&self, summary: &PlanSummary) -> Option<String> {
+        if let Some(max) = self.max_cost.filter(|max| summary.total_cost > *max) {
+            return Some(format!("Estimated cost {} exceeds the limit of {}", summary.total_cost, max));
+        }
+        if let Some(max) = self.max_rows.filter(|max| summary.plan_rows > *max) {
+            return Some(format!("Estimated {} rows exceeds the limit of {}", summary.plan_rows, max));
+        }
+        None
+    }
+
+    fn estimate(&self, summary: &PlanSummary) -> CostEstimate {
+        CostEstimate { total_cost: summary.total_cost, plan_rows: summary.plan_rows, max_cost: self.max_cost, max_rows: self.max_rows }
+    }
+}
+
+// Plan `sql` with a plain EXPLAIN, which doesn't run it, and reject it with
+// COST_LIMIT_EXCEEDED when the estimates are over the guard's limits. Runs in the query's
+// transaction so it sees the same tables.
+pub(crate) async fn preflight(state: &SharedState, guard: CostGuard, sql: &str, params: Option<Vec<Value>>, query: &QueryOptions) -> Result<(), JsValue> {
+    if !is_explainable(sql) {
+        return Ok(());
+    }
+    let options = QueryOptions { transaction: query.transaction, binary_params: query.binary_params.clone(), types: query.types.clone(), ..QueryOptions::default() };
+    let result = execute_query_with(state, &explain_sql(sql, ExplainOptions::default()), params, options).await?;
+    let summary = parse_explain(&result).map_err(|e| JsValue::from_str(&e))?.summary;
+    match guard.violation(&summary) {
+        Some(message) => {
+            log_warn!("WASM cost guard rejected a query: {}", message);
+            let detail = serde_json::to_string(&guard.estimate(&summary)).unwrap_or_default();
+            let payload = json!({ "message": message, "code": "COST_LIMIT_EXCEEDED", "detail": detail });
+            Err(BridgeError::from_error_payload(&payload, None).into())
+        }
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_and_explainable_statements() {
+        assert!(is_explainable("-- report\nSELECT * FROM a, b"));
+        assert!(is_explainable("WITH t AS (SELECT 1) DELETE FROM f"));
+        assert!(!is_explainable("CREATE INDEX ON facts (id)"));
+        assert!(!is_explainable("VACUUM facts"));
+
+        let summary = PlanSummary { total_cost: 5_000.0, plan_rows: 40.0, ..PlanSummary::default() };
+        assert!(CostGuard::default().violation(&summary).is_none());
+        let guard = CostGuard { max_cost: Some(1_000.0), max_rows: None };
+        assert_eq!(guard.violation(&summary).unwrap(), "Estimated cost 5000 exceeds the limit of 1000");
+        let guard = CostGuard { max_cost: Some(10_000.0), max_rows: Some(10.0) };
+        assert_eq!(guard.violation(&summary).unwrap(), "Estimated 40 rows exceeds the limit of 10");
+        assert!(CostGuard { max_rows: Some(40.0), ..guard }.violation(&summary).is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+// Options accepted by `result_to_csv`
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(default)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // Written for SQL NULL; strings equal to it are quoted so the two stay distinguishable
+    #[serde(rename = "nullValue")]
+    pub null_value: String,
+    pub header: bool,
+    // Column order; defaults to the order columns first appear in the rows
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions { delimiter: ',', null_value: String::new(), header: true, columns: None }
+    }
+}
+
+// Keys of the row objects in the order they first appear
+pub(crate) fn column_names(rows: &[Value]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for row in rows {
+        if let Some(object) = row.as_object() {
+            for key in object.keys() {
+                if !names.contains(key) {
+                    names.push(key.clone());
+                }
+            }
+        }
+    }
+    names
+}
+
+fn push_field(out: &mut String, field: &str, force_quotes: bool, delimiter: char) {
+    let needs_quotes = force_quotes || field.contains([delimiter, '"', '\r', '\n']);
+    if needs_quotes {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+// RFC 4180 CSV with CRLF line endings. Nested JSON values are written as JSON text.
+pub fn rows_to_csv(rows: &[Value], options: &CsvOptions) -> String {
+    let columns = options.columns.clone().unwrap_or_else(|| column_names(rows));
+    let delimiter = options.delimiter;
+    let mut out = String::new();
+    if options.header {
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.push(delimiter);
+            }
+            push_field(&mut out, column, false, delimiter);
+        }
+        out.push_str("\r\n");
+    }
+    for row in rows {
+        for (i, column) in columns.iter().enumerate() {
+            if i > 0 {
+                out.push(delimiter);
+            }
+            match row.get(column) {
+                None | Some(Value::Null) => push_field(&mut out, &options.null_value, false, delimiter),
+                Some(Value::String(text)) => push_field(&mut out, text, *text == options.null_value, delimiter),
+                Some(other) => push_field(&mut out, &other.to_string(), false, delimiter),
+            }
+        }
+        out.push_str("\r\n");
+    }
+    out
+}
+
+// Convert a query result (or a bare array of rows) to CSV.
+// `options` accepts `{ delimiter, nullValue, header, columns }`.
+#[wasm_bindgen]
+pub fn result_to_csv(result: JsValue, #[wasm_bindgen(unchecked_optional_param_type = "CsvOptions | null")] options: JsValue) -> Result<String, JsValue> {
+    let options: CsvOptions = if options.is_undefined() || options.is_null() {
+        CsvOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid CSV options: {}", e)))?
+    };
+    let result: Value =
+        serde_wasm_bindgen::from_value(result).map_err(|e| JsValue::from_str(&format!("Invalid query result: {}", e)))?;
+    let rows = match &result {
+        Value::Array(rows) => rows.as_slice(),
+        _ => result.get("rows").and_then(|rows| rows.as_array()).map(|rows| rows.as_slice()).unwrap_or_default(),
+    };
+    Ok(rows_to_csv(rows, &options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_quoting_and_nulls() {
+        let rows = vec![
+            json!({"id": 1, "name": "Smith, \"Jo\"", "tags": ["a"]}),
+            json!({"id": 2, "name": "", "note": "two\nlines"}),
+            json!({"id": null, "name": null}),
+        ];
+        let csv = rows_to_csv(&rows, &CsvOptions::default());
+        assert_eq!(
+            csv,
+            "id,name,tags,note\r\n1,\"Smith, \"\"Jo\"\"\",\"[\"\"a\"\"]\",\r\n2,\"\",,\"two\nlines\"\r\n,,,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let options: CsvOptions =
+            serde_json::from_str(r#"{"delimiter": ";", "nullValue": "NULL", "header": false, "columns": ["b", "a"]}"#).unwrap();
+        let csv = rows_to_csv(&[json!({"a": "x;y", "b": null})], &options);
+        assert_eq!(csv, "NULL;\"x;y\"\r\n");
+    }
+}
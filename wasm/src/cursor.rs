@@ -0,0 +1,290 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use js_sys::Promise;
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::connection::{execute_query, SharedState};
+use crate::null_policy::NullPolicy;
+use crate::sql::{quote_ident, single_statement};
+
+pub const DEFAULT_BATCH_SIZE: u32 = 100;
+
+// Names cursors uniquely for the module's lifetime
+static CURSOR_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Batch {
+    pub rows: Vec<Value>,
+    // No rows are left; the cursor has been closed
+    pub done: bool,
+}
+
+// SQL for a server-side cursor over one query
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorSql {
+    name: String,
+    batch_size: u32,
+}
+
+impl CursorSql {
+    pub fn new(name: &str, batch_size: Option<u32>) -> Result<CursorSql, String> {
+        if batch_size == Some(0) {
+            return Err("Cursor batch size must be at least 1".to_string());
+        }
+        Ok(CursorSql { name: quote_ident(name), batch_size: batch_size.unwrap_or(DEFAULT_BATCH_SIZE) })
+    }
+
+    // WITH HOLD keeps the cursor past the implicit transaction DECLARE runs in, so it
+    // doesn't pin a transaction open between fetches; the bridge's backend holds the rows
+    pub fn declare(&self, sql: &str) -> Result<String, String> {
+        single_statement(sql)?;
+        let inner = sql.trim().trim_end_matches(';');
+        if inner.is_empty() {
+            return Err("Cursor query is empty".to_string());
+        }
+        Ok(format!("DECLARE {} NO SCROLL CURSOR WITH HOLD FOR {}", self.name, inner))
+    }
+
+    pub fn fetch(&self) -> String {
+        format!("FETCH FORWARD {} FROM {}", self.batch_size, self.name)
+    }
+
+    pub fn close(&self) -> String {
+        format!("CLOSE {}", self.name)
+    }
+
+    // A short batch means the query has no rows left
+    pub fn exhausted(&self, rows: usize) -> bool {
+        rows < self.batch_size as usize
+    }
+}
+
+struct CursorState {
+    client: SharedState,
+    sql: CursorSql,
+    null_policy: NullPolicy,
+    closed: bool,
+}
+
+// A server-side cursor opened by `open_cursor`. Lives on the bridge's backend
+// connection, so it is lost if that connection is (a resumed session keeps it).
+#[wasm_bindgen]
+pub struct QueryCursor {
+    inner: Rc<RefCell<CursorState>>,
+}
+
+async fn close_cursor(inner: &Rc<RefCell<CursorState>>) -> Result<(), JsValue> {
+    let (client, sql) = {
+        let mut cursor = inner.borrow_mut();
+        if cursor.closed {
+            return Ok(());
+        }
+        cursor.closed = true;
+        (cursor.client.clone(), cursor.sql.close())
+    };
+    execute_query(&client, &sql, None).await.map(|_| ())
+}
+
+// FETCH the next batch, closing the cursor once one comes back short
+async fn fetch_batch(inner: &Rc<RefCell<CursorState>>) -> Result<Batch, JsValue> {
+    let (client, sql, null_policy) = {
+        let cursor = inner.borrow();
+        if cursor.closed {
+            return Ok(Batch { rows: Vec::new(), done: true });
+        }
+        (cursor.client.clone(), cursor.sql.clone(), cursor.null_policy)
+    };
+    let result = execute_query(&client, &sql.fetch(), None).await?;
+    let mut rows = result.get("rows").and_then(|rows| rows.as_array()).cloned().unwrap_or_default();
+    let done = sql.exhausted(rows.len());
+    if done {
+        close_cursor(inner).await?;
+    }
+    null_policy.apply_to_rows(&mut rows);
+    Ok(Batch { rows, done })
+}
+
+impl QueryCursor {
+    pub(crate) async fn next_batch(&self) -> Result<Batch, JsValue> {
+        fetch_batch(&self.inner).await
+    }
+
+    pub(crate) async fn close_now(&self) -> Result<(), JsValue> {
+        close_cursor(&self.inner).await
+    }
+}
+
+#[wasm_bindgen]
+impl QueryCursor {
+    // Resolve with `{ rows, done }` holding the next batch. The cursor closes itself once
+    // a batch comes back short; later calls resolve with no rows.
+    #[wasm_bindgen]
+    pub fn fetch_next(&self) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            let null_policy = inner.borrow().null_policy;
+            null_policy.to_js(&fetch_batch(&inner).await?)
+        })
+    }
+
+    // Close the cursor on the bridge; safe to call more than once
+    #[wasm_bindgen]
+    pub fn close(&self) -> Promise {
+        let inner = self.inner.clone();
+        future_to_promise(async move {
+            close_cursor(&inner).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn closed(&self) -> bool {
+        self.inner.borrow().closed
+    }
+}
+
+// DECLARE a cursor for `sql` and hand back its handle
+pub(crate) async fn open(
+    client: SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    batch_size: Option<u32>,
+    null_policy: NullPolicy,
+) -> Result<QueryCursor, JsValue> {
+    let name = format!("bridge_cursor_{}", CURSOR_COUNTER.fetch_add(1, Ordering::Relaxed) + 1);
+    let cursor_sql = CursorSql::new(&name, batch_size).map_err(|e| JsValue::from_str(&e))?;
+    let declare = cursor_sql.declare(sql).map_err(|e| JsValue::from_str(&e))?;
+    execute_query(&client, &declare, params).await?;
+    let state = CursorState { client, sql: cursor_sql, null_policy, closed: false };
+    Ok(QueryCursor { inner: Rc::new(RefCell::new(state)) })
+}
+
+#[wasm_bindgen(inline_js = "export function make_async_iterable(target) { target[Symbol.asyncIterator] = function () { return this; }; }")]
+extern "C" {
+    fn make_async_iterable(target: &JsValue);
+}
+
+// One step of the async iterator protocol: a batch of rows, or the end
+#[derive(Serialize)]
+struct IteratorStep {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Vec<Value>>,
+    done: bool,
+}
+
+enum StreamState {
+    // Declared on the first `next()`, so `stream()` can return synchronously
+    Unopened { sql: String, params: Option<Vec<Value>>, batch_size: Option<u32> },
+    Open(Rc<RefCell<CursorState>>),
+    Finished,
+}
+
+// What `stream()` returns: an async iterable over batches of rows, backed by a cursor
+#[wasm_bindgen]
+pub struct QueryStream {
+    client: SharedState,
+    null_policy: NullPolicy,
+    state: Rc<RefCell<StreamState>>,
+}
+
+async fn next_rows(client: SharedState, null_policy: NullPolicy, state: &Rc<RefCell<StreamState>>) -> Result<Option<Vec<Value>>, JsValue> {
+    let unopened = match &*state.borrow() {
+        StreamState::Finished => return Ok(None),
+        StreamState::Open(_) => None,
+        StreamState::Unopened { sql, params, batch_size } => Some((sql.clone(), params.clone(), *batch_size)),
+    };
+    if let Some((sql, params, batch_size)) = unopened {
+        let cursor = open(client, &sql, params, batch_size, null_policy).await?;
+        *state.borrow_mut() = StreamState::Open(cursor.inner);
+    }
+    let inner = match &*state.borrow() {
+        StreamState::Open(inner) => inner.clone(),
+        _ => return Ok(None),
+    };
+    let Batch { rows, done } = fetch_batch(&inner).await?;
+    if done {
+        *state.borrow_mut() = StreamState::Finished;
+    }
+    // The short batch that ends the query is still yielded, unless it is empty
+    Ok(Some(rows).filter(|rows| !rows.is_empty() || !done))
+}
+
+#[wasm_bindgen]
+impl QueryStream {
+    // Resolve with `{ value, done }`: the next batch of rows, or `done` once the query
+    // has none left. A failure closes the cursor before rejecting.
+    #[wasm_bindgen]
+    pub fn next(&self) -> Promise {
+        let (client, null_policy, state) = (self.client.clone(), self.null_policy, self.state.clone());
+        future_to_promise(async move {
+            let rows = match next_rows(client, null_policy, &state).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = finish(&state).await;
+                    return Err(e);
+                }
+            };
+            null_policy.to_js(&IteratorStep { done: rows.is_none(), value: rows })
+        })
+    }
+
+    // Called by `for await` when the loop exits early; closes the cursor
+    #[wasm_bindgen(js_name = "return")]
+    pub fn stop(&self) -> Promise {
+        let (null_policy, state) = (self.null_policy, self.state.clone());
+        future_to_promise(async move {
+            finish(&state).await?;
+            null_policy.to_js(&IteratorStep { value: None, done: true })
+        })
+    }
+}
+
+async fn finish(state: &Rc<RefCell<StreamState>>) -> Result<(), JsValue> {
+    let previous = std::mem::replace(&mut *state.borrow_mut(), StreamState::Finished);
+    match previous {
+        StreamState::Open(inner) => close_cursor(&inner).await,
+        _ => Ok(()),
+    }
+}
+
+// Stream `sql` through a cursor as an async iterable of row batches
+pub(crate) fn stream(
+    client: SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    batch_size: Option<u32>,
+    null_policy: NullPolicy,
+) -> Result<JsValue, JsValue> {
+    // Fail on bad arguments now rather than on the first `next()`
+    CursorSql::new("check", batch_size).and_then(|cursor| cursor.declare(sql)).map_err(|e| JsValue::from_str(&e))?;
+    let state = StreamState::Unopened { sql: sql.to_string(), params, batch_size };
+    let stream = JsValue::from(QueryStream { client, null_policy, state: Rc::new(RefCell::new(state)) });
+    make_async_iterable(&stream);
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_sql() {
+        let cursor = CursorSql::new("bridge_cursor_1", Some(2)).unwrap();
+        assert_eq!(
+            cursor.declare("SELECT * FROM events WHERE kind = $1;").unwrap(),
+            r#"DECLARE "bridge_cursor_1" NO SCROLL CURSOR WITH HOLD FOR SELECT * FROM events WHERE kind = $1"#
+        );
+        assert_eq!(cursor.fetch(), r#"FETCH FORWARD 2 FROM "bridge_cursor_1""#);
+        assert_eq!(cursor.close(), r#"CLOSE "bridge_cursor_1""#);
+        assert!(!cursor.exhausted(2));
+        assert!(cursor.exhausted(1));
+
+        assert!(cursor.declare("SELECT 1; DROP TABLE events").is_err());
+        assert!(CursorSql::new("c", Some(0)).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+// Example exports, only built with the `demo` feature so they stay out of production
+// binaries. Test builds include them too, so their tests run without the feature.
+use wasm_bindgen::prelude::*;
+
+use crate::config::WasmClientConfig;
+use crate::bindings::WasmWebSocketClient;
+
+// Basic arithmetic functions
+#[wasm_bindgen]
+pub fn add(a: i32, b: i32) -> i32 {
+    log_debug!("Adding {} + {} = {}", a, b, a + b);
+    a + b
+}
+
+#[wasm_bindgen]
+pub fn subtract(a: i32, b: i32) -> i32 {
+    log_debug!("Subtracting {} - {} = {}", a, b, a - b);
+    a - b
+}
+
+#[wasm_bindgen]
+pub fn multiply(a: i32, b: i32) -> i32 {
+    log_debug!("Multiplying {} * {} = {}", a, b, a * b);
+    a * b
+}
+
+#[wasm_bindgen]
+pub fn divide(a: i32, b: i32) -> Result<i32, String> {
+    if b == 0 {
+        log_warn!("Error: Division by zero attempted");
+        Err("Division by zero".to_string())
+    } else {
+        let result = a / b;
+        log_debug!("Dividing {} / {} = {}", a, b, result);
+        Ok(result)
+    }
+}
+
+// String processing functions
+#[wasm_bindgen]
+pub fn reverse_string(input: &str) -> String {
+    let reversed: String = input.chars().rev().collect();
+    log_debug!("Reversing '{}' to '{}'", input, reversed);
+    reversed
+}
+
+#[wasm_bindgen]
+pub fn to_uppercase(input: &str) -> String {
+    let upper = input.to_uppercase();
+    log_debug!("Converting '{}' to uppercase: '{}'", input, upper);
+    upper
+}
+
+#[wasm_bindgen]
+pub fn count_words(input: &str) -> usize {
+    let count = input.split_whitespace().count();
+    log_debug!("Counting words in '{}': {} words", input, count);
+    count
+}
+
+#[wasm_bindgen]
+pub fn process_string(input: &str) -> String {
+    let processed = format!("Processed: {} (length: {})", input, input.len());
+    log_debug!("Processing string: '{}'", processed);
+    processed
+}
+
+// Memory management demonstration
+#[wasm_bindgen]
+pub fn create_array(size: usize) -> Vec<i32> {
+    log_debug!("Creating array of size {}", size);
+    let mut arr = Vec::with_capacity(size);
+    for i in 0..size {
+        arr.push(i as i32);
+    }
+    log_debug!("Array created successfully");
+    arr
+}
+
+#[wasm_bindgen]
+pub fn sum_array(arr: &[i32]) -> i32 {
+    let sum: i32 = arr.iter().sum();
+    log_debug!("Summing array of {} elements: {}", arr.len(), sum);
+    sum
+}
+
+// Error handling demonstration
+#[wasm_bindgen]
+pub fn safe_parse_int(input: &str) -> Result<i32, String> {
+    match input.parse::<i32>() {
+        Ok(num) => {
+            log_debug!("Successfully parsed '{}' to {}", input, num);
+            Ok(num)
+        }
+        Err(_) => {
+            log_debug!("Failed to parse '{}' as integer", input);
+            Err(format!("Cannot parse '{}' as integer", input))
+        }
+    }
+}
+
+// Convenience functions for database operations through WebSocket
+#[allow(unused_variables)]
+#[wasm_bindgen]
+pub fn wasm_query_database(
+    websocket_url: &str,
+    sql: &str,
+    params_json: Option<String>,
+) -> Result<WasmWebSocketClient, JsValue> {
+    log_info!("WASM creating database query client");
+    
+    let mut client = WasmWebSocketClient::with_config(WasmClientConfig::from_url(websocket_url));
+    client.connect()?;
+    
+    log_info!("WASM WebSocket client created and connected");
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        assert_eq!(add(2, 3), 5);
+        assert_eq!(add(-1, 1), 0);
+    }
+
+    #[test]
+    fn test_subtract() {
+        assert_eq!(subtract(5, 3), 2);
+        assert_eq!(subtract(0, 5), -5);
+    }
+
+    #[test]
+    fn test_multiply() {
+        assert_eq!(multiply(3, 4), 12);
+        assert_eq!(multiply(-2, 3), -6);
+    }
+
+    #[test]
+    fn test_divide() {
+        assert_eq!(divide(10, 2), Ok(5));
+        assert_eq!(divide(7, 3), Ok(2));
+        assert!(divide(5, 0).is_err());
+    }
+
+    #[test]
+    fn test_reverse_string() {
+        assert_eq!(reverse_string("hello"), "olleh");
+        assert_eq!(reverse_string(""), "");
+    }
+
+    #[test]
+    fn test_to_uppercase() {
+        assert_eq!(to_uppercase("hello"), "HELLO");
+        assert_eq!(to_uppercase("World"), "WORLD");
+    }
+
+    #[test]
+    fn test_count_words() {
+        assert_eq!(count_words("hello world"), 2);
+        assert_eq!(count_words(""), 0);
+        assert_eq!(count_words("single"), 1);
+    }
+
+    #[test]
+    fn test_sum_array() {
+        assert_eq!(sum_array(&[1, 2, 3, 4]), 10);
+        assert_eq!(sum_array(&[]), 0);
+    }
+
+    #[test]
+    fn test_safe_parse_int() {
+        assert_eq!(safe_parse_int("123"), Ok(123));
+        assert_eq!(safe_parse_int("-456"), Ok(-456));
+        assert!(safe_parse_int("abc").is_err());
+        assert!(safe_parse_int("12.34").is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+// Rows keyed by their primary-key values, as seen on the previous run
+pub type RowIndex = HashMap<String, Value>;
+
+// Delta between two runs of the same query
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct RowDiff {
+    pub added: Vec<Value>,
+    pub removed: Vec<Value>,
+    pub changed: Vec<Value>,
+    #[serde(rename = "rowCount")]
+    pub row_count: usize,
+}
+
+impl RowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Stable identity for a row: its key column values serialized as a JSON array
+pub fn row_key(row: &Value, key_columns: &[String]) -> Option<String> {
+    let values = key_columns
+        .iter()
+        .map(|c| row.get(c).cloned())
+        .collect::<Option<Vec<_>>>()?;
+    Some(Value::Array(values).to_string())
+}
+
+// Compare fresh rows against the previous index. Returns the delta (in result order
+// for added/changed rows) and the index for the next comparison.
+pub fn diff_rows(previous: &RowIndex, rows: &[Value], key_columns: &[String]) -> Result<(RowDiff, RowIndex), String> {
+    let mut diff = RowDiff { row_count: rows.len(), ..RowDiff::default() };
+    let mut index = RowIndex::with_capacity(rows.len());
+
+    for row in rows {
+        let key = row_key(row, key_columns)
+            .ok_or_else(|| format!("Row is missing key column(s) {}", key_columns.join(", ")))?;
+        match previous.get(&key) {
+            None => diff.added.push(row.clone()),
+            Some(old) if old != row => diff.changed.push(row.clone()),
+            Some(_) => {}
+        }
+        index.insert(key, row.clone());
+    }
+    diff.removed = previous
+        .iter()
+        .filter(|(key, _)| !index.contains_key(*key))
+        .map(|(_, row)| row.clone())
+        .collect();
+
+    Ok((diff, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keys() -> Vec<String> {
+        vec!["id".to_string()]
+    }
+
+    #[test]
+    fn test_diff_added_changed_removed() {
+        let first = vec![json!({"id": 1, "name": "a"}), json!({"id": 2, "name": "b"})];
+        let (diff, index) = diff_rows(&RowIndex::new(), &first, &keys()).unwrap();
+        assert_eq!(diff.added.len(), 2);
+
+        let second = vec![json!({"id": 2, "name": "B"}), json!({"id": 3, "name": "c"})];
+        let (diff, _) = diff_rows(&index, &second, &keys()).unwrap();
+        assert_eq!(diff.added, vec![json!({"id": 3, "name": "c"})]);
+        assert_eq!(diff.changed, vec![json!({"id": 2, "name": "B"})]);
+        assert_eq!(diff.removed, vec![json!({"id": 1, "name": "a"})]);
+        assert_eq!(diff.row_count, 2);
+    }
+
+    #[test]
+    fn test_unchanged_and_missing_key() {
+        let rows = vec![json!({"id": 1, "name": "a"})];
+        let (_, index) = diff_rows(&RowIndex::new(), &rows, &keys()).unwrap();
+        let (diff, _) = diff_rows(&index, &rows, &keys()).unwrap();
+        assert!(diff.is_empty());
+
+        assert!(diff_rows(&index, &[json!({"name": "x"})], &keys()).is_err());
+    }
+}
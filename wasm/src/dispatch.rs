@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+
+use crate::connection::SharedState;
+
+// Which lane a query waits in when the concurrency limit is reached
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    // User-facing queries, served before any background work
+    #[default]
+    Interactive,
+    // Refreshes, prefetches and analytics that can wait
+    Background,
+}
+
+// A query waiting for an in-flight slot
+#[derive(Default)]
+pub(crate) struct Ticket {
+    granted: bool,
+    waker: Option<Waker>,
+}
+
+impl Ticket {
+    fn grant(&mut self) {
+        self.granted = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchStats {
+    #[serde(rename = "inFlight")]
+    pub in_flight: usize,
+    pub queued: usize,
+    #[serde(rename = "queuedBackground")]
+    pub queued_background: usize,
+    #[serde(rename = "maxInFlight")]
+    pub max_in_flight: Option<usize>,
+}
+
+// Caps how many queries are in flight at once; the rest wait in arrival order,
+// interactive ones ahead of background ones
+#[derive(Default)]
+pub(crate) struct DispatchQueue {
+    max_in_flight: Option<usize>,
+    in_flight: usize,
+    interactive: VecDeque<Rc<RefCell<Ticket>>>,
+    background: VecDeque<Rc<RefCell<Ticket>>>,
+}
+
+impl DispatchQueue {
+    // None removes the cap; raising it lets waiting queries through straight away
+    pub fn set_limit(&mut self, max_in_flight: Option<usize>) {
+        self.max_in_flight = max_in_flight.map(|max| max.max(1));
+        self.grant_waiting();
+    }
+
+    pub fn stats(&self) -> DispatchStats {
+        DispatchStats {
+            in_flight: self.in_flight,
+            queued: self.interactive.len() + self.background.len(),
+            queued_background: self.background.len(),
+            max_in_flight: self.max_in_flight,
+        }
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.max_in_flight.is_none_or(|max| self.in_flight < max)
+    }
+
+    // Take a slot now, or queue a ticket that is granted once one frees up
+    pub fn enter(&mut self, priority: Priority) -> Option<Rc<RefCell<Ticket>>> {
+        // Slots are handed out as soon as they free up, so capacity means nobody waits
+        if self.has_capacity() {
+            self.in_flight += 1;
+            return None;
+        }
+        let ticket = Rc::new(RefCell::new(Ticket::default()));
+        match priority {
+            Priority::Interactive => self.interactive.push_back(ticket.clone()),
+            Priority::Background => self.background.push_back(ticket.clone()),
+        }
+        Some(ticket)
+    }
+
+    pub fn leave(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.grant_waiting();
+    }
+
+    fn grant_waiting(&mut self) {
+        while self.has_capacity() {
+            let Some(ticket) = self.interactive.pop_front().or_else(|| self.background.pop_front()) else {
+                break;
+            };
+            self.in_flight += 1;
+            ticket.borrow_mut().grant();
+        }
+    }
+}
+
+struct TicketFuture {
+    ticket: Rc<RefCell<Ticket>>,
+}
+
+impl Future for TicketFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut ticket = self.ticket.borrow_mut();
+        if ticket.granted {
+            return Poll::Ready(());
+        }
+        ticket.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+// An in-flight slot, given back when dropped
+pub(crate) struct Permit {
+    state: SharedState,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.state.borrow_mut().dispatch.leave();
+    }
+}
+
+// Wait for an in-flight slot
+pub(crate) async fn acquire(state: &SharedState, priority: Priority) -> Permit {
+    let ticket = state.borrow_mut().dispatch.enter(priority);
+    if let Some(ticket) = ticket {
+        log_debug!("WASM query queued behind the concurrency limit");
+        TicketFuture { ticket }.await;
+    }
+    Permit { state: state.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_beyond_limit() {
+        let mut queue = DispatchQueue::default();
+        queue.set_limit(Some(1));
+        assert!(queue.enter(Priority::Interactive).is_none());
+        let first = queue.enter(Priority::Interactive).unwrap();
+        let second = queue.enter(Priority::Interactive).unwrap();
+        assert_eq!(queue.stats().queued, 2);
+
+        queue.leave();
+        assert!(first.borrow().granted && !second.borrow().granted);
+        queue.set_limit(None);
+        assert!(second.borrow().granted);
+        assert_eq!(queue.stats().in_flight, 2);
+    }
+
+    #[test]
+    fn test_interactive_lane_served_first() {
+        let mut queue = DispatchQueue::default();
+        queue.set_limit(Some(1));
+        queue.enter(Priority::Background);
+        let refresh = queue.enter(Priority::Background).unwrap();
+        let click = queue.enter(Priority::Interactive).unwrap();
+        assert_eq!(queue.stats(), DispatchStats { in_flight: 1, queued: 2, queued_background: 1, max_in_flight: Some(1) });
+
+        queue.leave();
+        assert!(click.borrow().granted && !refresh.borrow().granted);
+        queue.leave();
+        assert!(refresh.borrow().granted);
+    }
+}
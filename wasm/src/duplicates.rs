@@ -0,0 +1,82 @@
+use std::collections::{HashSet, VecDeque};
+
+pub const DEFAULT_DUPLICATE_WINDOW: usize = 256;
+
+// Ids of the last `window` responses, so a response the bridge delivers twice (after a
+// resumed session or a re-send) is dropped instead of reaching handlers again
+#[derive(Debug, Clone)]
+pub struct DuplicateFilter {
+    window: usize,
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl Default for DuplicateFilter {
+    fn default() -> Self {
+        DuplicateFilter::new(DEFAULT_DUPLICATE_WINDOW)
+    }
+}
+
+impl DuplicateFilter {
+    pub fn new(window: usize) -> DuplicateFilter {
+        DuplicateFilter { window, order: VecDeque::new(), seen: HashSet::new() }
+    }
+
+    // 0 turns the filter off
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.order.len() > self.window {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+
+    // Record a response id; false when it was already seen and the response is a duplicate
+    pub fn admit(&mut self, id: &str) -> bool {
+        if self.window == 0 {
+            return true;
+        }
+        if self.seen.contains(id) {
+            return false;
+        }
+        self.seen.insert(id.to_string());
+        self.order.push_back(id.to_string());
+        self.evict();
+        true
+    }
+
+    // A new request is going out under `id`, so its answer is not a duplicate of an older
+    // one; matters for ids chosen by the caller or re-sent after a reconnect
+    pub fn forget(&mut self, id: &str) {
+        if self.seen.remove(id) {
+            self.order.retain(|seen| seen != id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drops_repeats_within_the_window() {
+        let mut filter = DuplicateFilter::new(2);
+        assert!(filter.admit("q1"));
+        assert!(!filter.admit("q1"));
+        assert!(filter.admit("q2"));
+        assert!(filter.admit("q3"));
+        // q1 has left the window
+        assert!(filter.admit("q1"));
+
+        filter.forget("q1");
+        assert!(filter.admit("q1"));
+
+        filter.set_window(0);
+        assert!(filter.admit("q1") && filter.admit("q1"));
+    }
+}
@@ -0,0 +1,66 @@
+use bridge_protocol::encryption::{encode_salt, EncryptedPayload, SALT_SIZE};
+use bridge_protocol::{FrameError, WebSocketMessage};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{ClientState, SharedState};
+use crate::errors::BridgeError;
+
+// Sent in `hello`; the bridge adds its own, so every connection gets fresh keys
+pub(crate) fn random_salt() -> Result<String, JsValue> {
+    let salt: [u8; SALT_SIZE] = crate::ids::random_bytes().map_err(|_| JsValue::from_str("Encryption requires crypto.getRandomValues"))?;
+    Ok(encode_salt(&salt))
+}
+
+// Encrypt a serialized message once both sides agreed on keys; `hello` itself goes out
+// before that and stays in the clear
+pub(crate) fn encrypt_outgoing(state: &mut ClientState, id: Option<&str>, frame: String) -> Result<String, JsValue> {
+    match state.protocol.cipher.as_mut() {
+        Some(cipher) => Ok(cipher.seal(&frame, id.map(String::from)).to_json()),
+        None => Ok(frame),
+    }
+}
+
+// `FrameCipher::seal` always serializes `type` first
+fn is_encrypted(text: &str) -> bool {
+    text.starts_with(r#"{"type":"encrypted""#)
+}
+
+// Decrypt a complete frame before it is inflated and dispatched. With a key set, frames
+// in the clear are only accepted until the handshake completes, and each encrypted
+// frame only once.
+pub(crate) fn receive(state: &SharedState, text: &str) {
+    let (keyed, opening) = {
+        let state = state.borrow();
+        (state.encryption_key.is_some(), state.protocol.cipher.is_some())
+    };
+    let encrypted = is_encrypted(text);
+    if !keyed || !(encrypted || opening) {
+        crate::compression::receive(state, text);
+        return;
+    }
+    let message = match serde_json::from_str::<WebSocketMessage>(text) {
+        Ok(message) => message,
+        Err(e) => return crate::resync::recover(state, text, &format!("Not a bridge message: {}", e)),
+    };
+    let id = message.id.clone();
+    let opened = if encrypted {
+        serde_json::from_value::<EncryptedPayload>(message.payload)
+            .map_err(|e| FrameError::Encryption(format!("Invalid encrypted payload: {}", e)))
+            .and_then(|payload| match state.borrow_mut().protocol.cipher.as_mut() {
+                Some(cipher) => cipher.open(&payload, id.as_deref()),
+                None => Err(FrameError::Encryption("Received an encrypted message before the handshake".to_string())),
+            })
+    } else {
+        Err(FrameError::Encryption("Rejected an unencrypted message from the bridge".to_string()))
+    };
+    match opened {
+        Ok(frame) => crate::compression::receive(state, &frame),
+        Err(e) => {
+            log_error!("WASM {}", e);
+            if let Some(id) = id {
+                let error = BridgeError::from_frame_error(&e, Some(id.clone()));
+                state.borrow_mut().fail_request(&id, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use bridge_protocol::FrameError;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+// Structured errors surfaced to JS as `Error` objects with a distinguishing `name`
+// (BridgeConnectionError, BridgeQueryError, BridgeIntegrityError) plus `code`,
+// `detail`, `sqlState` and `queryId`
+#[derive(Debug, Clone, PartialEq)]
+pub enum BridgeError {
+    Connection {
+        message: String,
+        query_id: Option<String>,
+    },
+    Query {
+        message: String,
+        code: Option<String>,
+        detail: Option<String>,
+        sql_state: Option<String>,
+        query_id: Option<String>,
+    },
+    // A message failed its checksum: it was corrupted somewhere between the peers
+    Integrity {
+        message: String,
+        query_id: Option<String>,
+    },
+    // The connection dropped after the request was sent; it may or may not have run
+    ConnectionLost {
+        message: String,
+        query_id: Option<String>,
+    },
+    // The bridge closed the connection for a reason it named, such as expired auth
+    Closed {
+        message: String,
+        code: &'static str,
+        query_id: Option<String>,
+    },
+}
+
+impl BridgeError {
+    pub fn connection(message: &str) -> BridgeError {
+        BridgeError::Connection {
+            message: message.to_string(),
+            query_id: None,
+        }
+    }
+
+    pub fn connection_lost(reason: &str) -> BridgeError {
+        BridgeError::ConnectionLost {
+            message: format!("Connection lost mid-query: {}", reason),
+            query_id: None,
+        }
+    }
+
+    pub fn closed(code: &'static str, message: &str) -> BridgeError {
+        BridgeError::Closed {
+            message: message.to_string(),
+            code,
+            query_id: None,
+        }
+    }
+
+    // Build a query error from a bridge `error` message payload
+    pub fn from_error_payload(payload: &Value, query_id: Option<String>) -> BridgeError {
+        let text = |key: &str| payload.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+        if text("code").as_deref() == Some("INTEGRITY_ERROR") {
+            return BridgeError::Integrity {
+                message: text("message").unwrap_or_else(|| "Message failed its integrity check".to_string()),
+                query_id,
+            };
+        }
+        BridgeError::Query {
+            message: text("message").unwrap_or_else(|| "Unknown error".to_string()),
+            code: text("code"),
+            detail: text("detail"),
+            sql_state: text("sqlState"),
+            query_id,
+        }
+    }
+
+    // Error for a chunked or compressed message that couldn't be decoded
+    pub fn from_frame_error(error: &FrameError, query_id: Option<String>) -> BridgeError {
+        match error {
+            FrameError::Integrity(message) => BridgeError::Integrity { message: message.clone(), query_id },
+            other => BridgeError::Query {
+                message: other.message().to_string(),
+                code: Some(other.code().to_string()),
+                detail: None,
+                sql_state: None,
+                query_id,
+            },
+        }
+    }
+
+    pub fn with_query_id(mut self, id: &str) -> BridgeError {
+        match &mut self {
+            BridgeError::Connection { query_id, .. }
+            | BridgeError::Query { query_id, .. }
+            | BridgeError::Integrity { query_id, .. }
+            | BridgeError::ConnectionLost { query_id, .. }
+            | BridgeError::Closed { query_id, .. } => {
+                *query_id = Some(id.to_string());
+            }
+        }
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BridgeError::Connection { .. } | BridgeError::ConnectionLost { .. } | BridgeError::Closed { .. } => "BridgeConnectionError",
+            BridgeError::Query { .. } => "BridgeQueryError",
+            BridgeError::Integrity { .. } => "BridgeIntegrityError",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            BridgeError::Connection { message, .. }
+            | BridgeError::Query { message, .. }
+            | BridgeError::Integrity { message, .. }
+            | BridgeError::ConnectionLost { message, .. }
+            | BridgeError::Closed { message, .. } => message,
+        }
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            BridgeError::Connection { .. } => Some("CONNECTION_ERROR"),
+            BridgeError::Query { code, .. } => code.as_deref(),
+            BridgeError::Integrity { .. } => Some("INTEGRITY_ERROR"),
+            BridgeError::ConnectionLost { .. } => Some("CONNECTION_LOST"),
+            BridgeError::Closed { code, .. } => Some(code),
+        }
+    }
+
+    pub fn query_id(&self) -> Option<&str> {
+        match self {
+            BridgeError::Connection { query_id, .. }
+            | BridgeError::Query { query_id, .. }
+            | BridgeError::Integrity { query_id, .. }
+            | BridgeError::ConnectionLost { query_id, .. }
+            | BridgeError::Closed { query_id, .. } => query_id.as_deref(),
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            BridgeError::Connection { .. } | BridgeError::Integrity { .. } | BridgeError::ConnectionLost { .. } | BridgeError::Closed { .. } => None,
+            BridgeError::Query { detail, .. } => detail.as_deref(),
+        }
+    }
+
+    pub fn sql_state(&self) -> Option<&str> {
+        match self {
+            BridgeError::Query { sql_state, .. } => sql_state.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.code() {
+            Some(code) => write!(f, "{}: {} ({})", self.name(), self.message(), code),
+            None => write!(f, "{}: {}", self.name(), self.message()),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+fn set_property(target: &JsValue, key: &str, value: Option<&str>) {
+    let value = value.map_or(JsValue::UNDEFINED, JsValue::from_str);
+    let _ = js_sys::Reflect::set(target, &JsValue::from_str(key), &value);
+}
+
+impl From<BridgeError> for JsValue {
+    fn from(error: BridgeError) -> JsValue {
+        let js_error = js_sys::Error::new(error.message());
+        js_error.set_name(error.name());
+        let value: JsValue = js_error.into();
+        set_property(&value, "code", error.code());
+        set_property(&value, "detail", error.detail());
+        set_property(&value, "sqlState", error.sql_state());
+        set_property(&value, "queryId", error.query_id());
+        value
+    }
+}
+
+// The `sqlState` of a thrown query error
+pub(crate) fn sql_state_of(error: &JsValue) -> Option<String> {
+    js_sys::Reflect::get(error, &JsValue::from_str("sqlState")).ok().and_then(|state| state.as_string())
+}
+
+// The `message` of a thrown error, or the thrown value itself as text
+pub(crate) fn message_of(error: &JsValue) -> String {
+    js_sys::Reflect::get(error, &JsValue::from_str("message"))
+        .ok()
+        .and_then(|message| message.as_string())
+        .or_else(|| error.as_string())
+        .unwrap_or_else(|| format!("{:?}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_query_error_from_payload() {
+        let payload = json!({"message": "relation does not exist", "code": "DATABASE_ERROR", "detail": "x", "sqlState": "42P01"});
+        let error = BridgeError::from_error_payload(&payload, Some("q1".to_string()));
+        assert_eq!(error.name(), "BridgeQueryError");
+        assert_eq!(error.message(), "relation does not exist");
+        assert_eq!(error.code(), Some("DATABASE_ERROR"));
+        assert_eq!(error.detail(), Some("x"));
+        assert_eq!(error.sql_state(), Some("42P01"));
+        assert_eq!(error.query_id(), Some("q1"));
+    }
+
+    #[test]
+    fn test_integrity_errors() {
+        let error = BridgeError::from_frame_error(&FrameError::Integrity("Checksum mismatch".to_string()), Some("q3".to_string()));
+        assert_eq!(error.name(), "BridgeIntegrityError");
+        assert_eq!(error.code(), Some("INTEGRITY_ERROR"));
+
+        let payload = json!({"message": "Checksum mismatch", "code": "INTEGRITY_ERROR"});
+        assert_eq!(BridgeError::from_error_payload(&payload, None).name(), "BridgeIntegrityError");
+        let too_large = BridgeError::from_frame_error(&FrameError::TooLarge("big".to_string()), None);
+        assert_eq!(too_large.code(), Some("MESSAGE_TOO_LARGE"));
+    }
+
+    #[test]
+    fn test_connection_error() {
+        let error = BridgeError::connection("WebSocket closed").with_query_id("q2");
+        assert_eq!(error.name(), "BridgeConnectionError");
+        assert_eq!(error.code(), Some("CONNECTION_ERROR"));
+        assert_eq!(error.query_id(), Some("q2"));
+
+        let lost = BridgeError::connection_lost("WebSocket closed").with_query_id("q4");
+        assert_eq!((lost.name(), lost.code()), ("BridgeConnectionError", Some("CONNECTION_LOST")));
+        assert_eq!(lost.message(), "Connection lost mid-query: WebSocket closed");
+    }
+}
@@ -0,0 +1,123 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+
+// Events `addEventListener` accepts
+pub const EVENT_TYPES: [&str; 14] = [
+    "open",
+    "close",
+    "error",
+    "notification",
+    "statechange",
+    "slowquery",
+    "protocolviolation",
+    "resync",
+    "largepayload",
+    "advisorylockslost",
+    "listenstate",
+    "schemachange",
+    "throttled",
+    "visibility",
+];
+
+// A JS listener, compared by identity like EventTarget does
+#[derive(Debug, Clone)]
+pub(crate) struct Listener(pub js_sys::Function);
+
+impl PartialEq for Listener {
+    fn eq(&self, other: &Listener) -> bool {
+        js_sys::Object::is(&self.0, &other.0)
+    }
+}
+
+// Listeners per event type, in the order they were added. As with EventTarget, adding
+// the same listener for the same type twice has no effect.
+#[derive(Debug, Clone)]
+pub(crate) struct EventListeners<L = Listener> {
+    listeners: Vec<(&'static str, L)>,
+}
+
+impl<L> Default for EventListeners<L> {
+    fn default() -> Self {
+        EventListeners { listeners: Vec::new() }
+    }
+}
+
+impl<L: PartialEq + Clone> EventListeners<L> {
+    pub fn add(&mut self, kind: &str, listener: L) -> Result<(), String> {
+        let kind = event_type(kind)?;
+        if !self.listeners.iter().any(|(k, l)| *k == kind && *l == listener) {
+            self.listeners.push((kind, listener));
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, kind: &str, listener: &L) -> bool {
+        let before = self.listeners.len();
+        self.listeners.retain(|(k, l)| !(*k == kind && l == listener));
+        self.listeners.len() != before
+    }
+
+    // Snapshot for one dispatch, so listeners may add or remove others while it runs
+    pub fn for_type(&self, kind: &str) -> Vec<L> {
+        self.listeners.iter().filter(|(k, _)| *k == kind).map(|(_, l)| l.clone()).collect()
+    }
+}
+
+fn event_type(kind: &str) -> Result<&'static str, String> {
+    EVENT_TYPES
+        .iter()
+        .find(|known| **known == kind)
+        .copied()
+        .ok_or_else(|| format!("Unknown event type '{}'; expected one of {}", kind, EVENT_TYPES.join(", ")))
+}
+
+#[derive(Serialize)]
+struct Event<'a, T: Serialize> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    detail: &'a T,
+    #[serde(rename = "timeStamp")]
+    time_stamp: f64,
+}
+
+// Call each listener for `kind` with `{ type, detail, timeStamp }`, without the state
+// borrowed so listeners may call back into the client. A throwing listener doesn't keep
+// the others from running.
+pub(crate) fn emit<T: Serialize>(state: &SharedState, kind: &str, detail: &T) {
+    let listeners = state.borrow().events.for_type(kind);
+    if listeners.is_empty() {
+        return;
+    }
+    let event = Event { kind, detail, time_stamp: js_sys::Date::now() };
+    let Ok(event) = crate::to_js(&event) else {
+        return;
+    };
+    for Listener(listener) in listeners {
+        if let Err(e) = listener.call1(&JsValue::NULL, &event) {
+            log_error!("WASM '{}' listener threw: {:?}", kind, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listeners_are_independent() {
+        let mut events: EventListeners<u32> = EventListeners::default();
+        events.add("notification", 1).unwrap();
+        events.add("notification", 2).unwrap();
+        events.add("notification", 1).unwrap();
+        events.add("close", 1).unwrap();
+        assert_eq!(events.for_type("notification"), vec![1, 2]);
+
+        assert!(events.remove("notification", &1));
+        assert!(!events.remove("notification", &1));
+        assert_eq!(events.for_type("notification"), vec![2]);
+        assert_eq!(events.for_type("close"), vec![1]);
+        assert!(events.add("notifcation", 3).unwrap_err().contains("Unknown event type 'notifcation'"));
+    }
+}
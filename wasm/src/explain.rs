@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Tsify)]
+pub struct ExplainOptions {
+    #[serde(default)]
+    pub analyze: bool,
+    #[serde(default)]
+    pub buffers: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PlanSummary {
+    #[serde(rename = "totalCost")]
+    pub total_cost: f64,
+    #[serde(rename = "planRows")]
+    pub plan_rows: f64,
+    #[serde(rename = "actualRows")]
+    pub actual_rows: Option<f64>,
+    #[serde(rename = "planningTime")]
+    pub planning_time: Option<f64>,
+    #[serde(rename = "executionTime")]
+    pub execution_time: Option<f64>,
+    #[serde(rename = "nodeCount")]
+    pub node_count: usize,
+    #[serde(rename = "nodeTypes")]
+    pub node_types: Vec<String>,
+    #[serde(rename = "sharedHitBlocks")]
+    pub shared_hit_blocks: Option<f64>,
+    #[serde(rename = "sharedReadBlocks")]
+    pub shared_read_blocks: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExplainResult {
+    pub plan: Value,
+    pub summary: PlanSummary,
+}
+
+pub fn explain_sql(sql: &str, options: ExplainOptions) -> String {
+    let mut flags = vec!["FORMAT JSON"];
+    if options.analyze {
+        flags.push("ANALYZE");
+    }
+    if options.buffers {
+        flags.push("BUFFERS");
+    }
+    format!("EXPLAIN ({}) {}", flags.join(", "), sql)
+}
+
+fn walk(node: &Value, summary: &mut PlanSummary) {
+    summary.node_count += 1;
+    if let Some(node_type) = node.get("Node Type").and_then(|t| t.as_str()) {
+        if !summary.node_types.iter().any(|t| t == node_type) {
+            summary.node_types.push(node_type.to_string());
+        }
+    }
+    if let Some(children) = node.get("Plans").and_then(|p| p.as_array()) {
+        for child in children {
+            walk(child, summary);
+        }
+    }
+}
+
+// Extract the plan document from an EXPLAIN (FORMAT JSON) result payload
+pub fn parse_explain(result: &Value) -> Result<ExplainResult, String> {
+    let cell = result
+        .get("rows")
+        .and_then(|rows| rows.get(0))
+        .and_then(|row| row.get("QUERY PLAN"))
+        .ok_or("EXPLAIN result did not contain a QUERY PLAN column")?;
+
+    // Bridges may hand the json column back already parsed or as text
+    let document = match cell {
+        Value::String(text) => serde_json::from_str(text).map_err(|e| format!("Invalid plan JSON: {}", e))?,
+        other => other.clone(),
+    };
+    let top = document.get(0).cloned().ok_or("EXPLAIN plan was empty")?;
+    let plan = top.get("Plan").cloned().ok_or("EXPLAIN output is missing the Plan node")?;
+
+    let number = |value: &Value, key: &str| value.get(key).and_then(|v| v.as_f64());
+    let mut summary = PlanSummary {
+        total_cost: number(&plan, "Total Cost").unwrap_or(0.0),
+        plan_rows: number(&plan, "Plan Rows").unwrap_or(0.0),
+        actual_rows: number(&plan, "Actual Rows"),
+        planning_time: number(&top, "Planning Time"),
+        execution_time: number(&top, "Execution Time"),
+        shared_hit_blocks: number(&plan, "Shared Hit Blocks"),
+        shared_read_blocks: number(&plan, "Shared Read Blocks"),
+        ..PlanSummary::default()
+    };
+    walk(&plan, &mut summary);
+
+    Ok(ExplainResult { plan, summary })
+}
+
+pub(crate) async fn explain(state: &SharedState, sql: &str, params: Option<Vec<Value>>, options: ExplainOptions) -> Result<ExplainResult, JsValue> {
+    let result = execute_query(state, &explain_sql(sql, options), params).await?;
+    parse_explain(&result).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_explain_sql_flags() {
+        assert_eq!(explain_sql("SELECT 1", ExplainOptions::default()), "EXPLAIN (FORMAT JSON) SELECT 1");
+        let options = ExplainOptions { analyze: true, buffers: true };
+        assert_eq!(explain_sql("SELECT 1", options), "EXPLAIN (FORMAT JSON, ANALYZE, BUFFERS) SELECT 1");
+    }
+
+    #[test]
+    fn test_parse_explain_summary() {
+        let plan = json!([{
+            "Plan": {
+                "Node Type": "Hash Join", "Total Cost": 42.5, "Plan Rows": 10, "Actual Rows": 8,
+                "Plans": [{"Node Type": "Seq Scan"}, {"Node Type": "Hash", "Plans": [{"Node Type": "Seq Scan"}]}]
+            },
+            "Planning Time": 0.2,
+            "Execution Time": 1.5
+        }]);
+        let result = json!({"rows": [{"QUERY PLAN": plan.to_string()}]});
+        let parsed = parse_explain(&result).unwrap();
+        assert_eq!(parsed.summary.total_cost, 42.5);
+        assert_eq!(parsed.summary.node_count, 4);
+        assert_eq!(parsed.summary.node_types, vec!["Hash Join", "Seq Scan", "Hash"]);
+        assert_eq!(parsed.summary.actual_rows, Some(8.0));
+        assert_eq!(parsed.summary.execution_time, Some(1.5));
+
+        assert!(parse_explain(&json!({"rows": []})).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{WritableStream, WritableStreamDefaultWriter};
+
+use crate::connection::SharedState;
+use crate::csv::{column_names, rows_to_csv, CsvOptions};
+use crate::null_policy::NullPolicy;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    // One JSON object per line
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+// Options accepted by `stream_to`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    // Rows fetched and written at a time; defaults to the cursor batch size
+    #[serde(rename = "batchSize")]
+    pub batch_size: Option<u32>,
+    // `{ delimiter, nullValue, header, columns }` for CSV
+    pub csv: CsvOptions,
+}
+
+// What `stream_to` resolves with
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub rows: u64,
+    pub batches: u64,
+    pub bytes: u64,
+}
+
+// Encodes batches so that written one after another they form a single document: CSV
+// gets one header, and the columns of the first batch unless they were given
+pub struct BatchEncoder {
+    format: ExportFormat,
+    csv: CsvOptions,
+}
+
+impl BatchEncoder {
+    pub fn new(options: &ExportOptions) -> BatchEncoder {
+        BatchEncoder { format: options.format, csv: options.csv.clone() }
+    }
+
+    pub fn encode(&mut self, rows: &[Value]) -> String {
+        match self.format {
+            ExportFormat::Jsonl => rows.iter().map(|row| format!("{}\n", row)).collect(),
+            ExportFormat::Csv => {
+                if self.csv.columns.is_none() {
+                    self.csv.columns = Some(column_names(rows));
+                }
+                let text = rows_to_csv(rows, &self.csv);
+                self.csv.header = false;
+                text
+            }
+        }
+    }
+}
+
+async fn write_rows(
+    state: SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    options: &ExportOptions,
+    writer: &WritableStreamDefaultWriter,
+) -> Result<ExportSummary, JsValue> {
+    let cursor = crate::cursor::open(state, sql, params, options.batch_size, NullPolicy::Null).await?;
+    let mut encoder = BatchEncoder::new(options);
+    let mut summary = ExportSummary { rows: 0, batches: 0, bytes: 0 };
+    loop {
+        let batch = match cursor.next_batch().await {
+            Ok(batch) => batch,
+            Err(e) => {
+                let _ = cursor.close_now().await;
+                return Err(e);
+            }
+        };
+        if !batch.rows.is_empty() {
+            let text = encoder.encode(&batch.rows);
+            // Wait for the sink: a slow file or network stream holds the next fetch back
+            JsFuture::from(writer.ready()).await?;
+            if let Err(e) = JsFuture::from(writer.write_with_chunk(&js_sys::Uint8Array::from(text.as_bytes()))).await {
+                let _ = cursor.close_now().await;
+                return Err(e);
+            }
+            summary.rows += batch.rows.len() as u64;
+            summary.batches += 1;
+            summary.bytes += text.len() as u64;
+        }
+        if batch.done {
+            return Ok(summary);
+        }
+    }
+}
+
+// Fetch `sql` through a cursor and write each batch, UTF-8 encoded, to `stream`. The
+// stream is closed once every row is written, or aborted with the error on a failure.
+pub(crate) async fn stream_to(
+    state: SharedState,
+    sql: String,
+    params: Option<Vec<Value>>,
+    stream: WritableStream,
+    options: ExportOptions,
+) -> Result<ExportSummary, JsValue> {
+    let writer = stream.get_writer()?;
+    match write_rows(state, &sql, params, &options, &writer).await {
+        Ok(summary) => {
+            JsFuture::from(writer.close()).await?;
+            log_debug!("WASM exported {} rows ({} bytes)", summary.rows, summary.bytes);
+            Ok(summary)
+        }
+        Err(e) => {
+            let _ = JsFuture::from(writer.abort_with_reason(&e)).await;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batches_form_one_document() {
+        let mut jsonl = BatchEncoder::new(&ExportOptions::default());
+        assert_eq!(jsonl.encode(&[json!({"id": 1}), json!({"id": 2})]), "{\"id\":1}\n{\"id\":2}\n");
+
+        let options: ExportOptions = serde_json::from_str(r#"{"format": "csv", "csv": {"delimiter": ";"}}"#).unwrap();
+        let mut csv = BatchEncoder::new(&options);
+        assert_eq!(csv.encode(&[json!({"id": 1, "name": "a"})]), "id;name\r\n1;a\r\n");
+        assert_eq!(csv.encode(&[json!({"name": "b", "id": 2, "extra": true})]), "2;b\r\n");
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+use tsify::Tsify;
+
+use crate::hashing::hash_hex;
+use crate::sql_format::KEYWORDS;
+use crate::sql_validate::{lex, Lex};
+
+// Distinct statement shapes tracked; past this the least-called shape makes room
+pub const MAX_TRACKED_STATEMENTS: usize = 1000;
+
+// The shape of `sql`: literals replaced by `?`, whitespace and comments dropped, and
+// unquoted names lowercased. A run of literals in a list counts as one, so `IN (1, 2)`
+// and `IN (1, 2, 3)` share a shape. Parameters like `$1` are kept.
+pub fn normalize(sql: &str) -> String {
+    let chars: Vec<char> = sql.chars().collect();
+    let Ok(lexemes) = lex(&chars, false) else {
+        // Unlexable SQL still gets a stable shape; the server will reject it anyway
+        return sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    };
+    let mut parts: Vec<String> = Vec::new();
+    for (lexeme, _) in lexemes {
+        let part = match lexeme {
+            Lex::Word(lower, _) => lower,
+            Lex::Value(text) if text.starts_with('"') || (text.starts_with('$') && text[1..].starts_with(|c: char| c.is_ascii_digit())) => text,
+            Lex::Value(_) => {
+                if parts.len() >= 2 && parts[parts.len() - 1] == "," && parts[parts.len() - 2] == "?" {
+                    parts.pop();
+                    continue;
+                }
+                "?".to_string()
+            }
+            Lex::Punct(c) => c.to_string(),
+            Lex::Comment(_) => continue,
+        };
+        parts.push(part);
+    }
+    while parts.last().is_some_and(|part| part == ";") {
+        parts.pop();
+    }
+
+    let mut out = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        let glued = i == 0
+            || matches!(part.as_str(), "," | ")" | "." | ";")
+            || matches!(parts[i - 1].as_str(), "(" | ".")
+            // A function call
+            || (part == "(" && parts[i - 1].starts_with(|c: char| c.is_alphabetic() || c == '_') && !KEYWORDS.contains(&parts[i - 1].as_str()));
+        if !glued {
+            out.push(' ');
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+// Stable id for a statement shape, like pg_stat_statements' queryid
+pub fn fingerprint(sql: &str) -> String {
+    hash_hex(normalize(sql).as_bytes())
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct StatementStats {
+    pub fingerprint: String,
+    // The normalized statement
+    pub query: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub rows: u64,
+    #[serde(rename = "totalTime")]
+    pub total_time: f64,
+    #[serde(rename = "minTime")]
+    pub min_time: f64,
+    #[serde(rename = "maxTime")]
+    pub max_time: f64,
+    #[serde(rename = "meanTime")]
+    pub mean_time: f64,
+}
+
+// Calls, errors, rows and execution time per statement shape
+#[derive(Debug, Clone, Default)]
+pub struct QueryStats {
+    statements: HashMap<String, StatementStats>,
+}
+
+impl QueryStats {
+    fn entry(&mut self, sql: &str) -> &mut StatementStats {
+        let query = normalize(sql);
+        let fingerprint = hash_hex(query.as_bytes());
+        if !self.statements.contains_key(&fingerprint) && self.statements.len() >= MAX_TRACKED_STATEMENTS {
+            let least = self.statements.values().min_by_key(|s| s.calls + s.errors).map(|s| s.fingerprint.clone());
+            if let Some(least) = least {
+                self.statements.remove(&least);
+            }
+        }
+        self.statements.entry(fingerprint.clone()).or_insert_with(|| StatementStats {
+            fingerprint,
+            query,
+            calls: 0,
+            errors: 0,
+            rows: 0,
+            total_time: 0.0,
+            min_time: 0.0,
+            max_time: 0.0,
+            mean_time: 0.0,
+        })
+    }
+
+    // Count a query result, which echoes its SQL
+    pub fn record_result(&mut self, payload: &Value) {
+        let Some(sql) = payload.get("sql").and_then(|s| s.as_str()) else {
+            return;
+        };
+        let ms = payload.get("executionTime").and_then(|t| t.as_f64()).unwrap_or(0.0);
+        let rows = payload.get("rowCount").and_then(|r| r.as_u64()).unwrap_or(0);
+        let stats = self.entry(sql);
+        stats.min_time = if stats.calls == 0 { ms } else { stats.min_time.min(ms) };
+        stats.max_time = stats.max_time.max(ms);
+        stats.calls += 1;
+        stats.rows += rows;
+        stats.total_time += ms;
+        stats.mean_time = stats.total_time / stats.calls as f64;
+    }
+
+    // Count a database error; errors without the failed SQL aren't attributed
+    pub fn record_error(&mut self, payload: &Value) {
+        if let Some(sql) = payload.get("sql").and_then(|s| s.as_str()) {
+            self.entry(sql).errors += 1;
+        }
+    }
+
+    // Most total time first
+    pub fn snapshot(&self) -> Vec<StatementStats> {
+        let mut statements: Vec<StatementStats> = self.statements.values().cloned().collect();
+        statements.sort_by(|a, b| b.total_time.total_cmp(&a.total_time).then_with(|| a.fingerprint.cmp(&b.fingerprint)));
+        statements
+    }
+
+    pub fn reset(&mut self) {
+        self.statements.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_groups_by_shape() {
+        assert_eq!(
+            normalize("SELECT *  FROM users\n WHERE id IN (1, 2, 3) AND name = 'bob' -- lookup\n;"),
+            "select * from users where id in (?) and name = ?"
+        );
+        assert_eq!(fingerprint("select * from users where id in (4) and NAME = $$x$$"), fingerprint("SELECT * FROM users WHERE id IN (1,2) AND name = 'y'"));
+        assert_ne!(fingerprint("SELECT * FROM \"Users\" WHERE id = $1"), fingerprint("SELECT * FROM users WHERE id = $1"));
+        assert_eq!(normalize("select f(a.b, $2)"), "select f(a.b, $2)");
+    }
+
+    #[test]
+    fn test_stats_accumulate_per_shape() {
+        let mut stats = QueryStats::default();
+        stats.record_result(&json!({"sql": "SELECT * FROM t WHERE id = 1", "executionTime": 4.0, "rowCount": 1}));
+        stats.record_result(&json!({"sql": "select * from t where id = 2", "executionTime": 2.0, "rowCount": 0}));
+        stats.record_result(&json!({"sql": "SELECT 1", "executionTime": 1.0, "rowCount": 1}));
+        stats.record_error(&json!({"sql": "SELECT * FROM t WHERE id = 'x'", "code": "DATABASE_ERROR"}));
+        stats.record_error(&json!({"code": "TIMEOUT"}));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        let lookup = &snapshot[0];
+        assert_eq!(lookup.query, "select * from t where id = ?");
+        assert_eq!((lookup.calls, lookup.errors, lookup.rows), (2, 1, 1));
+        assert_eq!((lookup.total_time, lookup.min_time, lookup.max_time, lookup.mean_time), (6.0, 2.0, 4.0, 3.0));
+        stats.reset();
+        assert!(stats.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{run_in_transaction, SharedState};
+use crate::sql::{quote_ident, quote_qualified};
+
+// Rows inserted per statement when loading fixtures
+const FIXTURE_BATCH_SIZE: usize = 500;
+
+// Most bind parameters Postgres takes in one statement
+const MAX_BIND_PARAMS: usize = 65535;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixtureTable {
+    pub table: String,
+    pub rows: Vec<Map<String, Value>>,
+}
+
+// Accepts either `{"table": [rows]}` or `[{"table": "...", "rows": [rows]}]`, preserving order
+pub fn parse_fixtures(json: &str) -> Result<Vec<FixtureTable>, String> {
+    let document: Value = serde_json::from_str(json).map_err(|e| format!("Invalid fixtures JSON: {}", e))?;
+
+    let entries: Vec<(String, Value)> = match document {
+        Value::Object(tables) => tables.into_iter().collect(),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| {
+                let table = item
+                    .get("table")
+                    .and_then(|t| t.as_str())
+                    .ok_or("Fixture entry is missing a \"table\" name")?
+                    .to_string();
+                let rows = item.get("rows").cloned().unwrap_or(Value::Array(Vec::new()));
+                Ok((table, rows))
+            })
+            .collect::<Result<_, String>>()?,
+        _ => return Err("Fixtures must be an object or an array".to_string()),
+    };
+
+    entries
+        .into_iter()
+        .map(|(table, rows)| {
+            let rows = match rows {
+                Value::Array(rows) => rows,
+                _ => return Err(format!("Rows for table '{}' must be an array", table)),
+            };
+            let rows = rows
+                .into_iter()
+                .map(|row| match row {
+                    Value::Object(map) => Ok(map),
+                    _ => Err(format!("Every row for table '{}' must be an object", table)),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(FixtureTable { table, rows })
+        })
+        .collect()
+}
+
+// Build multi-row INSERT statements; columns missing from a row use DEFAULT. Batches
+// shrink for wide tables to stay under the bind parameter limit.
+pub fn build_inserts(fixture: &FixtureTable, batch_size: usize) -> Vec<(String, Vec<Value>)> {
+    let mut columns: Vec<&String> = Vec::new();
+    for row in &fixture.rows {
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+    // Only empty rows: nothing to list, every column takes its default
+    if columns.is_empty() {
+        let sql = format!("INSERT INTO {} DEFAULT VALUES", quote_qualified(&fixture.table));
+        return fixture.rows.iter().map(|_| (sql.clone(), Vec::new())).collect();
+    }
+
+    let column_list = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let batch_size = batch_size.min(MAX_BIND_PARAMS / columns.len()).max(1);
+    fixture
+        .rows
+        .chunks(batch_size)
+        .map(|batch| {
+            let mut params = Vec::new();
+            let tuples: Vec<String> = batch
+                .iter()
+                .map(|row| {
+                    let cells: Vec<String> = columns
+                        .iter()
+                        .map(|column| match row.get(*column) {
+                            Some(value) => {
+                                params.push(value.clone());
+                                format!("${}", params.len())
+                            }
+                            None => "DEFAULT".to_string(),
+                        })
+                        .collect();
+                    format!("({})", cells.join(", "))
+                })
+                .collect();
+            let sql = format!(
+                "INSERT INTO {} ({}) VALUES {}",
+                quote_qualified(&fixture.table),
+                column_list,
+                tuples.join(", ")
+            );
+            (sql, params)
+        })
+        .collect()
+}
+
+// Load all fixture tables in one transaction, optionally truncating them first
+pub(crate) async fn load_fixtures(state: SharedState, fixtures: Vec<FixtureTable>, truncate: bool) -> Result<usize, JsValue> {
+    let mut statements = Vec::new();
+    if truncate && !fixtures.is_empty() {
+        let tables = fixtures.iter().map(|f| quote_qualified(&f.table)).collect::<Vec<_>>().join(", ");
+        statements.push((format!("TRUNCATE {} RESTART IDENTITY CASCADE", tables), None));
+    }
+
+    let mut row_count = 0;
+    for fixture in &fixtures {
+        row_count += fixture.rows.len();
+        for (sql, params) in build_inserts(fixture, FIXTURE_BATCH_SIZE) {
+            statements.push((sql, Some(params)));
+        }
+    }
+
+    log_info!("WASM loading {} fixture rows into {} tables", row_count, fixtures.len());
+    run_in_transaction(&state, statements).await?;
+    Ok(row_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fixtures_preserves_order() {
+        let tables = parse_fixtures(r#"{"users": [{"id": 1}], "posts": []}"#).unwrap();
+        assert_eq!(tables[0].table, "users");
+        assert_eq!(tables[1].table, "posts");
+
+        let tables = parse_fixtures(r#"[{"table": "posts", "rows": [{"id": 1}]}]"#).unwrap();
+        assert_eq!(tables[0].rows.len(), 1);
+        assert!(parse_fixtures(r#"{"users": [1]}"#).is_err());
+    }
+
+    #[test]
+    fn test_build_inserts_batches_and_defaults() {
+        let fixture = parse_fixtures(r#"{"users": [{"id": 1, "name": "a"}, {"id": 2}, {"id": 3}]}"#)
+            .unwrap()
+            .remove(0);
+        let statements = build_inserts(&fixture, 2);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            statements[0].0,
+            "INSERT INTO \"users\" (\"id\", \"name\") VALUES ($1, $2), ($3, DEFAULT)"
+        );
+        assert_eq!(statements[0].1.len(), 3);
+        assert_eq!(statements[1].0, "INSERT INTO \"users\" (\"id\", \"name\") VALUES ($1, DEFAULT)");
+    }
+
+    #[test]
+    fn test_build_inserts_within_bind_limit() {
+        let row: Map<String, Value> = (0..200).map(|i| (format!("c{}", i), Value::from(i))).collect();
+        let fixture = FixtureTable { table: "wide".to_string(), rows: vec![row; 500] };
+        let statements = build_inserts(&fixture, FIXTURE_BATCH_SIZE);
+        assert_eq!(statements.len(), 2);
+        assert!(statements.iter().all(|(_, params)| params.len() <= MAX_BIND_PARAMS));
+        assert_eq!(statements[0].1.len(), 327 * 200);
+
+        let fixture = parse_fixtures(r#"{"events": [{}, {}]}"#).unwrap().remove(0);
+        let statements = build_inserts(&fixture, FIXTURE_BATCH_SIZE);
+        assert_eq!(statements, vec![("INSERT INTO \"events\" DEFAULT VALUES".to_string(), Vec::new()); 2]);
+    }
+}
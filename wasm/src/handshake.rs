@@ -0,0 +1,195 @@
+use bridge_protocol::encryption::{FrameCipher, FrameKey, Side};
+use bridge_protocol::{message_type, Capabilities, HelloPayload, Negotiated, PoolRequest};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, ClientState, SharedState};
+use crate::errors::BridgeError;
+
+// What the client offers in `hello`; features join this list as they land
+pub const CLIENT_CAPABILITIES: Capabilities = Capabilities {
+    binary: true,
+    streaming: false,
+    compression: true,
+    notifications: true,
+    chunking: true,
+    progress: true,
+    cancel: true,
+    encryption: false,
+    resume: false,
+    result_cache: false,
+    continuation: true,
+    schema_cache: true,
+    schema_events: true,
+    row_deltas: true,
+    throttling: true,
+};
+
+// Outcome of the `hello` exchange for the current connection
+#[derive(Default)]
+pub(crate) struct ProtocolState {
+    // Id of the in-flight `hello`, so an id-less rejection can be matched to it
+    pub pending_hello: Option<String>,
+    pub negotiated: Option<Negotiated>,
+    // Set when the bridge is incompatible; requests fail with this reason until reconnect
+    pub refused: Option<String>,
+    // Pool behavior the bridge granted, when the client asked for any
+    pub pool: Option<PoolRequest>,
+    // Base64 salt sent in `hello` when the client has an encryption key
+    pub salt: Option<String>,
+    // This connection's keys, set once the bridge answered with its own salt
+    pub cipher: Option<FrameCipher>,
+}
+
+// Encryption is only offered, and then required, when the client has a key; resumption
+// when it is enabled, along with the token of the session to reattach
+pub(crate) fn client_hello(state: &ClientState) -> HelloPayload {
+    let capabilities = Capabilities {
+        encryption: state.encryption_key.is_some(),
+        resume: state.resumption.is_enabled(),
+        ..CLIENT_CAPABILITIES
+    };
+    HelloPayload {
+        policy: state.policy.clone(),
+        session_token: state.resumption.token().map(String::from),
+        pool: state.pool_request,
+        encryption_salt: state.protocol.salt.clone(),
+        ..HelloPayload::new(0, capabilities, state.framing.max_message_size)
+    }
+}
+
+// Interpret the bridge's answer to `hello`. An error means the bridge predates the
+// handshake: fall back to version 0 with no optional features.
+// A client with an encryption key refuses bridges that can't encrypt, so stripping the
+// capability from the handshake can't downgrade it to plaintext.
+pub fn negotiate(ours: &HelloPayload, response_type: &str, payload: &serde_json::Value) -> Result<Negotiated, String> {
+    let negotiated = if response_type == message_type::ERROR {
+        match payload.get("code").and_then(|c| c.as_str()) {
+            Some("UNSUPPORTED_PROTOCOL") => {
+                return Err(payload
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("Bridge rejected the protocol version")
+                    .to_string())
+            }
+            _ => Negotiated::default(),
+        }
+    } else {
+        let server: HelloPayload = serde_json::from_value(payload.clone())
+            .map_err(|e| format!("Invalid hello response from bridge: {}", e))?;
+        ours.negotiate(&server)?
+    };
+    if ours.capabilities.encryption && !negotiated.capabilities.encryption {
+        return Err("Bridge does not support end-to-end encryption".to_string());
+    }
+    Ok(negotiated)
+}
+
+// Keys for this connection, from the salts both sides sent
+fn connection_cipher(key: Option<&FrameKey>, ours: &HelloPayload, payload: &serde_json::Value) -> Result<Option<FrameCipher>, String> {
+    let (Some(key), Some(salt)) = (key, &ours.encryption_salt) else {
+        return Ok(None);
+    };
+    let bridge_salt = payload.get("encryptionSalt").and_then(|salt| salt.as_str()).ok_or("Bridge sent no encryptionSalt")?;
+    key.connection(Side::Client, salt, bridge_salt).map(Some).map_err(|e| e.message().to_string())
+}
+
+// Take the bridge's word on the session: resumed, or new with the token that resumes it
+pub(crate) fn settle_session(state: &mut ClientState, token: Option<String>, resumed: bool) {
+    if let Some((key, token)) = state.resumption.accept(token, resumed) {
+        crate::resume::save(&key, &token);
+    }
+    if resumed {
+        log_info!("WASM resumed the previous bridge session");
+    } else {
+        state.result_schemas.clear();
+    }
+}
+
+// Exchange `hello` with the bridge; on an incompatible bridge the connection is
+// closed and pending requests fail with the reason
+pub(crate) async fn hello(state: &SharedState) -> Result<Negotiated, JsValue> {
+    let salt = match state.borrow().encryption_key {
+        Some(_) => Some(crate::encryption::random_salt()?),
+        None => None,
+    };
+    state.borrow_mut().protocol = ProtocolState { salt, ..ProtocolState::default() };
+    let ours = client_hello(&state.borrow());
+    let payload = serde_json::to_value(&ours)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize hello: {}", e)))?;
+    let (id, response) = connection::send_request(state, "hello", message_type::HELLO, payload)?;
+    state.borrow_mut().protocol.pending_hello = Some(id);
+    let response = response.await;
+
+    let mut state = state.borrow_mut();
+    state.protocol.pending_hello = None;
+    let response = response?;
+    let negotiated = negotiate(&ours, &response.message_type, &response.payload)
+        .and_then(|negotiated| Ok((negotiated, connection_cipher(state.encryption_key.as_ref(), &ours, &response.payload)?)));
+    match negotiated {
+        Ok((negotiated, cipher)) => {
+            state.protocol.cipher = cipher;
+            if negotiated.version == 0 {
+                log_warn!("WASM bridge predates protocol negotiation; optional features disabled");
+            } else {
+                log_info!("WASM negotiated protocol v{}: {:?}", negotiated.version, negotiated.capabilities);
+            }
+            state.protocol.negotiated = Some(negotiated);
+            state.protocol.pool = response.payload.get("pool").and_then(|pool| serde_json::from_value(pool.clone()).ok());
+            let token = response.payload.get("sessionToken").and_then(|t| t.as_str()).map(String::from);
+            let resumed = response.payload.get("resumed").and_then(|r| r.as_bool()).unwrap_or(false);
+            if negotiated.capabilities.resume && token.is_none() {
+                // The bridge resumes sessions once the client authenticates, and says so then
+                state.resumption.resumed = false;
+            } else {
+                settle_session(&mut state, token.filter(|_| negotiated.capabilities.resume), resumed);
+            }
+            Ok(negotiated)
+        }
+        Err(reason) => {
+            let reason = format!("Incompatible bridge: {}", reason);
+            log_error!("WASM {}", reason);
+            state.protocol.refused = Some(reason.clone());
+            state.fail_pending(&reason);
+            // Close outside the borrow: a JS transport may report `close` synchronously
+            let transport = state.transport.take();
+            drop(state);
+            if let Some(transport) = transport {
+                transport.close();
+            }
+            Err(BridgeError::connection(&reason).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_negotiate_downgrades_and_refuses() {
+        let ours = client_hello(&ClientState::default());
+        let legacy = negotiate(&ours, "error", &json!({"message": "Invalid message type \"hello\"", "code": "PARSE_ERROR"}));
+        assert_eq!(legacy.unwrap(), Negotiated::default());
+
+        let current = negotiate(&ours, "result", &json!({"version": 1, "minVersion": 0, "capabilities": {"notifications": true, "streaming": true}}));
+        let current = current.unwrap();
+        assert_eq!(current.version, 1);
+        assert!(current.capabilities.notifications && !current.capabilities.streaming);
+
+        assert!(negotiate(&ours, "result", &json!({"version": 4, "minVersion": 3})).is_err());
+        assert!(negotiate(&ours, "error", &json!({"message": "too old", "code": "UNSUPPORTED_PROTOCOL"})).is_err());
+
+        let encrypting = HelloPayload { capabilities: Capabilities { encryption: true, ..CLIENT_CAPABILITIES }, ..ours };
+        assert!(negotiate(&encrypting, "result", &json!({"version": 1, "capabilities": {"encryption": true}})).is_ok());
+        assert!(negotiate(&encrypting, "result", &json!({"version": 1, "capabilities": {}})).is_err());
+        assert!(negotiate(&encrypting, "error", &json!({"message": "Invalid message type \"hello\"", "code": "PARSE_ERROR"})).is_err());
+
+        let key = FrameKey::new(&[5u8; 32]).unwrap();
+        let salt = bridge_protocol::encryption::encode_salt(&[1u8; 16]);
+        let encrypting = HelloPayload { encryption_salt: Some(salt.clone()), ..encrypting };
+        assert!(connection_cipher(None, &encrypting, &json!({})).unwrap().is_none());
+        assert!(connection_cipher(Some(&key), &encrypting, &json!({})).is_err());
+        assert!(connection_cipher(Some(&key), &encrypting, &json!({ "encryptionSalt": salt })).unwrap().is_some());
+    }
+}
@@ -0,0 +1,126 @@
+// Small async wrapper over IndexedDB; values are stored as JSON strings keyed by string ids.
+// Builds without the `indexeddb` feature (Deno, edge runtimes) get stubs that always fail.
+pub(crate) const OUTBOX_STORE: &str = "outbox";
+pub(crate) const RESULTS_STORE: &str = "results";
+pub(crate) const SUBSCRIPTIONS_STORE: &str = "subscriptions";
+
+pub(crate) use imp::{clear, delete, get, get_all, put};
+
+#[cfg(feature = "indexeddb")]
+mod imp {
+    use wasm_bindgen::prelude::*;
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+    use web_sys::{IdbDatabase, IdbFactory, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+
+    const DB_NAME: &str = "wasm-postgres-bridge";
+    const DB_VERSION: u32 = 3;
+    const STORES: [&str; 3] = [super::OUTBOX_STORE, super::RESULTS_STORE, super::SUBSCRIPTIONS_STORE];
+
+    async fn await_request(request: &IdbRequest) -> Result<JsValue, JsValue> {
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            request.set_onsuccess(Some(&resolve));
+            request.set_onerror(Some(&reject));
+        });
+        JsFuture::from(promise).await?;
+        request.result()
+    }
+
+    async fn open() -> Result<IdbDatabase, JsValue> {
+        let factory: IdbFactory = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))?
+            .dyn_into()
+            .map_err(|_| JsValue::from_str("IndexedDB is not available in this environment"))?;
+        let request: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+        let upgrade_request = request.clone();
+        let onupgradeneeded = Closure::once(move |_: JsValue| {
+            if let Ok(db) = upgrade_request.result().and_then(|r| r.dyn_into::<IdbDatabase>()) {
+                let existing = db.object_store_names();
+                for store in STORES {
+                    if !existing.contains(store) {
+                        let _ = db.create_object_store(store);
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(onupgradeneeded.as_ref().unchecked_ref()));
+
+        let db = await_request(&request).await?;
+        drop(onupgradeneeded);
+        db.dyn_into::<IdbDatabase>()
+    }
+
+    pub(crate) async fn put(store: &str, key: &str, json: &str) -> Result<(), JsValue> {
+        let db = open().await?;
+        let object_store = db
+            .transaction_with_str_and_mode(store, IdbTransactionMode::Readwrite)?
+            .object_store(store)?;
+        await_request(&object_store.put_with_key(&JsValue::from_str(json), &JsValue::from_str(key))?).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn get(store: &str, key: &str) -> Result<Option<String>, JsValue> {
+        let db = open().await?;
+        let object_store = db
+            .transaction_with_str_and_mode(store, IdbTransactionMode::Readonly)?
+            .object_store(store)?;
+        Ok(await_request(&object_store.get(&JsValue::from_str(key))?).await?.as_string())
+    }
+
+    pub(crate) async fn delete(store: &str, key: &str) -> Result<(), JsValue> {
+        let db = open().await?;
+        let object_store = db
+            .transaction_with_str_and_mode(store, IdbTransactionMode::Readwrite)?
+            .object_store(store)?;
+        await_request(&object_store.delete(&JsValue::from_str(key))?).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn clear(store: &str) -> Result<(), JsValue> {
+        let db = open().await?;
+        let object_store = db
+            .transaction_with_str_and_mode(store, IdbTransactionMode::Readwrite)?
+            .object_store(store)?;
+        await_request(&object_store.clear()?).await?;
+        Ok(())
+    }
+
+    // All stored values, in key order
+    pub(crate) async fn get_all(store: &str) -> Result<Vec<String>, JsValue> {
+        let db = open().await?;
+        let object_store = db
+            .transaction_with_str_and_mode(store, IdbTransactionMode::Readonly)?
+            .object_store(store)?;
+        let values: js_sys::Array = await_request(&object_store.get_all()?).await?.dyn_into()?;
+        Ok(values.iter().filter_map(|v| v.as_string()).collect())
+    }
+}
+
+#[cfg(not(feature = "indexeddb"))]
+mod imp {
+    use wasm_bindgen::prelude::*;
+
+    fn unavailable() -> JsValue {
+        JsValue::from_str("IndexedDB support is not compiled in (build with the `indexeddb` feature)")
+    }
+
+    pub(crate) async fn put(_store: &str, _key: &str, _json: &str) -> Result<(), JsValue> {
+        Err(unavailable())
+    }
+
+    pub(crate) async fn get(_store: &str, _key: &str) -> Result<Option<String>, JsValue> {
+        Err(unavailable())
+    }
+
+    pub(crate) async fn delete(_store: &str, _key: &str) -> Result<(), JsValue> {
+        Err(unavailable())
+    }
+
+    pub(crate) async fn clear(_store: &str) -> Result<(), JsValue> {
+        Err(unavailable())
+    }
+
+    pub(crate) async fn get_all(_store: &str) -> Result<Vec<String>, JsValue> {
+        Err(unavailable())
+    }
+}
@@ -0,0 +1,171 @@
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+use crate::errors::BridgeError;
+
+// How long a query waits for a suspended connection to come back
+const RESUME_TIMEOUT_MS: f64 = 10_000.0;
+const RESUME_POLL_MS: f64 = 25.0;
+
+// Re-opens the transport the client last connected with
+pub(crate) type Reconnect = Rc<dyn Fn(&SharedState) -> Result<(), JsValue>>;
+
+// Closes the connection after a stretch without requests, so idle tabs don't hold a
+// Postgres backend, and reopens it for the next query
+#[derive(Default)]
+pub(crate) struct IdleState {
+    pub timeout_ms: Option<f64>,
+    pub last_activity: f64,
+    // Closed for inactivity; the next request reconnects
+    pub suspended: bool,
+    resuming: bool,
+    pub reconnect: Option<Reconnect>,
+    // Bumped per connection so timers from an earlier one stop
+    generation: u32,
+}
+
+impl IdleState {
+    // Milliseconds left before the connection counts as idle
+    pub fn remaining(&self, now: f64) -> Option<f64> {
+        self.timeout_ms.map(|timeout| timeout - (now - self.last_activity))
+    }
+}
+
+pub(crate) fn touch(state: &SharedState) {
+    state.borrow_mut().idle.last_activity = js_sys::Date::now();
+}
+
+// Watch the current connection for inactivity, replacing any earlier watcher
+pub(crate) fn start_timer(state: &SharedState) {
+    let generation = {
+        let mut state = state.borrow_mut();
+        if state.idle.timeout_ms.is_none() {
+            return;
+        }
+        state.idle.generation += 1;
+        state.idle.last_activity = js_sys::Date::now();
+        state.idle.generation
+    };
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        loop {
+            let remaining = {
+                let state = state.borrow();
+                if state.idle.generation != generation || !state.is_connected() {
+                    return;
+                }
+                match state.idle.remaining(js_sys::Date::now()) {
+                    Some(remaining) => remaining,
+                    None => return,
+                }
+            };
+            if remaining > 0.0 {
+                if crate::retry::sleep(remaining).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            if suspend(&state, false) {
+                return;
+            }
+            // Busy or subscribed: wait a full period before checking again
+            touch(&state);
+        }
+    });
+}
+
+// Close an idle connection unless it has requests in flight, delivers notifications
+// or changes (which only arrive while connected), or couldn't be reopened. A hidden
+// page's connection closes despite listeners; showing the page reopens it.
+pub(crate) fn suspend(state: &SharedState, hidden: bool) -> bool {
+    let transport = {
+        let mut state = state.borrow_mut();
+        let listening = state.watches.has_listeners() || state.changes.has_subscriptions();
+        if !state.pending.is_empty() || (listening && !hidden) || state.idle.reconnect.is_none() {
+            return false;
+        }
+        if hidden {
+            log_info!("WASM closing the connection of a hidden page");
+        } else {
+            log_info!("WASM closing idle connection; the next query reconnects");
+        }
+        state.idle.suspended = true;
+        state.protocol = Default::default();
+        state.session.restored = false;
+        state.transport.take()
+    };
+    if let Some(transport) = transport {
+        transport.close();
+    }
+    true
+}
+
+// Reconnect a suspended client and wait until the handshake and session restore
+// are done before a request goes out. Concurrent callers share one reconnect.
+pub(crate) async fn resume(state: &SharedState) -> Result<(), JsValue> {
+    let reconnect = {
+        let mut state = state.borrow_mut();
+        // Connected, or the restore's own queries running on a fresh connection
+        if state.is_connected() || !(state.idle.suspended || state.idle.resuming) {
+            return Ok(());
+        }
+        if state.idle.resuming {
+            None
+        } else {
+            let Some(reconnect) = state.idle.reconnect.clone() else {
+                return Err(BridgeError::connection("Connection was closed for inactivity and cannot be reopened").into());
+            };
+            state.idle.suspended = false;
+            state.idle.resuming = true;
+            Some(reconnect)
+        }
+    };
+    if let Some(reconnect) = reconnect {
+        log_info!("WASM resuming idle connection");
+        let reopened = connection::admit_connection(state).and_then(|_| reconnect(state));
+        if let Err(e) = reopened {
+            let mut state = state.borrow_mut();
+            state.idle.suspended = true;
+            state.idle.resuming = false;
+            return Err(e);
+        }
+    }
+
+    let deadline = js_sys::Date::now() + RESUME_TIMEOUT_MS;
+    let outcome = loop {
+        {
+            let state = state.borrow();
+            if let Some(reason) = &state.protocol.refused {
+                break Err(BridgeError::connection(reason).into());
+            }
+            if state.session.restored {
+                break Ok(());
+            }
+        }
+        if js_sys::Date::now() >= deadline {
+            break Err(BridgeError::connection("Timed out reopening the connection").into());
+        }
+        if let Err(e) = crate::retry::sleep(RESUME_POLL_MS).await {
+            break Err(e);
+        }
+    };
+    state.borrow_mut().idle.resuming = false;
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining() {
+        let mut idle = IdleState::default();
+        assert_eq!(idle.remaining(1000.0), None);
+        idle.timeout_ms = Some(500.0);
+        idle.last_activity = 1000.0;
+        assert_eq!(idle.remaining(1200.0), Some(300.0));
+        assert!(idle.remaining(1600.0).unwrap() < 0.0);
+    }
+}
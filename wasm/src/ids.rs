@@ -0,0 +1,69 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Random bytes from `crypto.getRandomValues`, available in browsers, workers, Node and Deno
+pub(crate) fn random_bytes<const N: usize>() -> Result<[u8; N], JsValue> {
+    let crypto = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))?;
+    let get_random_values: js_sys::Function = js_sys::Reflect::get(&crypto, &JsValue::from_str("getRandomValues"))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str("crypto.getRandomValues is not available"))?;
+    let bytes = js_sys::Uint8Array::new_with_length(N as u32);
+    get_random_values.call1(&crypto, &bytes)?;
+    let mut out = [0u8; N];
+    bytes.copy_to(&mut out);
+    Ok(out)
+}
+
+// RFC 4122 version 4 UUID from 16 random bytes
+pub fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+// Ids are unique across tabs and workers, not secret: without `crypto`, Math.random will do
+pub(crate) fn random_uuid() -> String {
+    let bytes = random_bytes::<16>().unwrap_or_else(|_| std::array::from_fn(|_| (js_sys::Math::random() * 256.0) as u8));
+    uuid_v4(bytes)
+}
+
+// Where message ids come from. A write query's id doubles as its idempotency key, so ids
+// must not repeat across tabs, workers or page loads.
+#[derive(Default)]
+pub(crate) enum IdGenerator {
+    // `wasm_<kind>_<uuid>`
+    #[default]
+    Uuid,
+    // `generator(kind)`, which must return a string
+    Custom(js_sys::Function),
+}
+
+impl IdGenerator {
+    // An id not already waiting for a response; a custom generator that throws, returns
+    // something other than a string or repeats a pending id falls back to a UUID
+    pub fn next(&self, kind: &str, in_use: impl Fn(&str) -> bool) -> String {
+        if let IdGenerator::Custom(generator) = self {
+            match generator.call1(&JsValue::NULL, &JsValue::from_str(kind)).map(|id| id.as_string()) {
+                Ok(Some(id)) if !id.is_empty() && !in_use(&id) => return id,
+                Ok(Some(id)) => log_warn!("WASM id generator returned '{}', which is empty or in use; using a UUID", id),
+                Ok(None) => log_warn!("WASM id generator did not return a string; using a UUID"),
+                Err(e) => log_warn!("WASM id generator threw: {:?}; using a UUID", e),
+            }
+        }
+        format!("wasm_{}_{}", kind, random_uuid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid_v4_sets_version_and_variant() {
+        assert_eq!(uuid_v4([0; 16]), "00000000-0000-4000-8000-000000000000");
+        let uuid = uuid_v4([0xff; 16]);
+        assert_eq!(uuid, "ffffffff-ffff-4fff-bfff-ffffffffffff");
+        assert_eq!(uuid.len(), 36);
+    }
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::query_builder::BuiltQuery;
+use crate::sql_validate::{lex, Lex};
+
+// How `IN ($n)` is rewritten when `$n` is bound to an array
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum InListStyle {
+    // `= ANY($n)` (`<> ALL($n)` for NOT IN), binding the array as one parameter: one
+    // statement shape whatever the length, so it prepares once
+    #[default]
+    Any,
+    // `IN ($n, $n+1, ...)`, one parameter per element, renumbering those after it. An
+    // empty array still becomes `= ANY($n)`, since `IN ()` isn't valid SQL.
+    Expand,
+}
+
+// An `[NOT] IN ( $n )` to rewrite: the characters it spans and the parameter, 0-based
+struct InList {
+    start: usize,
+    end: usize,
+    param: usize,
+    negated: bool,
+}
+
+fn placeholder(lexeme: &Lex) -> Option<usize> {
+    match lexeme {
+        Lex::Value(text) if text.starts_with('$') => text[1..].parse::<usize>().ok().filter(|n| *n > 0).map(|n| n - 1),
+        _ => None,
+    }
+}
+
+// Rewrite every `IN ($n)` whose parameter is an array in `params`. Arrays bound anywhere
+// else are left as they are. Returns the query and, for each parameter it binds, the
+// index of the parameter it came from and whether it is one element of that array.
+pub fn expand(sql: &str, params: Vec<Value>, style: InListStyle) -> Result<(BuiltQuery, Vec<(usize, bool)>), String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let lexemes = lex(&chars, false).map_err(|(message, _)| format!("Cannot expand IN lists: {}", message))?;
+    let is_array = |param: usize| params.get(param).is_some_and(Value::is_array);
+
+    let mut lists = Vec::new();
+    for (index, (lexeme, at)) in lexemes.iter().enumerate() {
+        let (Lex::Word(word, _), Some((Lex::Punct('('), _)), Some((inner, _)), Some((Lex::Punct(')'), close))) =
+            (lexeme, lexemes.get(index + 1), lexemes.get(index + 2), lexemes.get(index + 3))
+        else {
+            continue;
+        };
+        let Some(param) = placeholder(inner).filter(|param| word == "in" && is_array(*param)) else {
+            continue;
+        };
+        let negated = index > 0 && matches!(&lexemes[index - 1].0, Lex::Word(word, _) if word == "not");
+        let start = if negated { lexemes[index - 1].1 } else { *at };
+        lists.push(InList { start, end: close + 1, param, negated });
+    }
+
+    // Parameters bound element by element, and where each parameter now starts
+    let expanded: Vec<bool> = params
+        .iter()
+        .enumerate()
+        .map(|(index, param)| style == InListStyle::Expand && lists.iter().any(|list| list.param == index) && param.as_array().is_some_and(|items| !items.is_empty()))
+        .collect();
+    let mut origins = Vec::new();
+    let mut starts = Vec::with_capacity(params.len());
+    let mut new_params = Vec::new();
+    for (index, param) in params.into_iter().enumerate() {
+        starts.push(new_params.len() + 1);
+        match param {
+            Value::Array(items) if expanded[index] => {
+                origins.extend(std::iter::repeat_n((index, true), items.len()));
+                new_params.extend(items);
+            }
+            param => {
+                origins.push((index, false));
+                new_params.push(param);
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(sql.len());
+    let mut lists = lists.into_iter().peekable();
+    let mut placeholders = lexemes.iter().filter_map(|(lexeme, at)| placeholder(lexeme).map(|param| (*at, param, lexeme))).peekable();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(list) = lists.next_if(|list| list.start == i) {
+            let n = starts[list.param];
+            let rewritten = if expanded[list.param] {
+                let count = origins.iter().filter(|(origin, _)| *origin == list.param).count();
+                let marks: Vec<String> = (n..n + count).map(|n| format!("${}", n)).collect();
+                format!("{}IN ({})", if list.negated { "NOT " } else { "" }, marks.join(", "))
+            } else if list.negated {
+                format!("<> ALL(${})", n)
+            } else {
+                format!("= ANY(${})", n)
+            };
+            out.push_str(&rewritten);
+            while placeholders.next_if(|(at, _, _)| *at < list.end).is_some() {}
+            i = list.end;
+            continue;
+        }
+        if let Some((_, param, Lex::Value(text))) = placeholders.next_if(|(at, _, _)| *at == i) {
+            if expanded.get(param) == Some(&true) {
+                return Err(format!("${} is expanded in an IN list and can't also be used elsewhere", param + 1));
+            }
+            // A placeholder without a parameter is left for the bridge to reject
+            match starts.get(param) {
+                Some(n) => out.push_str(&format!("${}", n)),
+                None => out.push_str(text),
+            }
+            i += text.chars().count();
+            continue;
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    Ok((BuiltQuery { sql: out, params: new_params }, origins))
+}
+
+// `{ sql, params }` with each `IN ($n)` whose parameter is an array rewritten as `style`
+// ("any" by default, or "expand") says, so ids never have to be joined into the SQL
+#[wasm_bindgen(unchecked_return_type = "{ sql: string, params: Value[] }")]
+pub fn expand_in_lists(
+    sql: &str,
+    #[wasm_bindgen(unchecked_param_type = "Value[]")] params: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "InListStyle | null")] style: JsValue,
+) -> Result<JsValue, JsValue> {
+    let params: Vec<Value> = serde_wasm_bindgen::from_value(params).map_err(|e| JsValue::from_str(&format!("Invalid parameters: {}", e)))?;
+    let style: InListStyle = if style.is_undefined() || style.is_null() {
+        InListStyle::default()
+    } else {
+        serde_wasm_bindgen::from_value(style).map_err(|e| JsValue::from_str(&format!("Invalid IN list style: {}", e)))?
+    };
+    let (query, _) = expand(sql, params, style).map_err(|e| JsValue::from_str(&e))?;
+    crate::to_js(&query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rewrites_array_in_lists() {
+        let sql = "SELECT * FROM t WHERE id IN ($1) AND kind NOT IN ( $2 ) AND owner = $3 AND 'IN ($1)' <> ''";
+        let params = vec![json!([4, 5]), json!(["a"]), json!(9)];
+        let (any, origins) = expand(sql, params.clone(), InListStyle::Any).unwrap();
+        assert_eq!(any.sql, "SELECT * FROM t WHERE id = ANY($1) AND kind <> ALL($2) AND owner = $3 AND 'IN ($1)' <> ''");
+        assert_eq!(any.params, params);
+        assert_eq!(origins, vec![(0, false), (1, false), (2, false)]);
+
+        let (expanded, origins) = expand(sql, params, InListStyle::Expand).unwrap();
+        assert_eq!(expanded.sql, "SELECT * FROM t WHERE id IN ($1, $2) AND kind NOT IN ($3) AND owner = $4 AND 'IN ($1)' <> ''");
+        assert_eq!(expanded.params, vec![json!(4), json!(5), json!("a"), json!(9)]);
+        assert_eq!(origins, vec![(0, true), (0, true), (1, true), (2, false)]);
+
+        let (empty, _) = expand("SELECT 1 WHERE 2 IN ($1)", vec![json!([])], InListStyle::Expand).unwrap();
+        assert_eq!(empty.sql, "SELECT 1 WHERE 2 = ANY($1)");
+        // Arrays outside IN lists are ordinary array parameters
+        let (plain, _) = expand("SELECT $1::int[]", vec![json!([1])], InListStyle::Expand).unwrap();
+        assert_eq!(plain.sql, "SELECT $1::int[]");
+        assert!(expand("SELECT $1 WHERE 1 IN ($1)", vec![json!([1])], InListStyle::Expand).is_err());
+    }
+}
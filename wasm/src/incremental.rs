@@ -0,0 +1,184 @@
+use bridge_protocol::WebSocketMessage;
+use serde_json::Value;
+
+use crate::connection::{self, SharedState};
+
+// Frames at least this large are parsed a batch of rows at a time
+pub const DEFAULT_THRESHOLD: usize = 1024 * 1024;
+pub const DEFAULT_BATCH_ROWS: usize = 1000;
+
+pub(crate) struct IncrementalParse {
+    // None parses every frame in one go
+    pub threshold: Option<usize>,
+    pub batch_rows: usize,
+}
+
+impl Default for IncrementalParse {
+    fn default() -> Self {
+        IncrementalParse { threshold: Some(DEFAULT_THRESHOLD), batch_rows: DEFAULT_BATCH_ROWS }
+    }
+}
+
+impl IncrementalParse {
+    pub fn applies_to(&self, text: &str) -> bool {
+        self.threshold.is_some_and(|threshold| text.len() >= threshold)
+    }
+}
+
+// Where `payload.rows` sits in a frame: the array's byte range and each element's
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowSpans {
+    pub array: (usize, usize),
+    pub rows: Vec<(usize, usize)>,
+}
+
+// Index just past the string starting at `start` (which must be a quote)
+fn skip_string(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn skip_whitespace(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+// Element ranges of the array starting at `start`, and the index past its end
+fn split_array(bytes: &[u8], start: usize) -> Option<RowSpans> {
+    let mut rows = Vec::new();
+    let mut depth = 0usize;
+    let mut element = start + 1;
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                i = skip_string(bytes, i)?;
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b']' if depth > 0 => depth -= 1,
+            b',' if depth == 0 => {
+                rows.push((element, i));
+                element = i + 1;
+            }
+            b']' => {
+                if skip_whitespace(bytes, element) < i {
+                    rows.push((element, i));
+                }
+                return Some(RowSpans { array: (start, i + 1), rows });
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Find `payload.rows` without parsing the rest of the frame. This only tracks strings
+// and nesting, so it is far cheaper than deserializing.
+pub fn find_rows(text: &str) -> Option<RowSpans> {
+    let bytes = text.as_bytes();
+    let mut depth = 0usize;
+    let mut in_payload = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let end = skip_string(bytes, i)?;
+                let colon = skip_whitespace(bytes, end);
+                if bytes.get(colon) == Some(&b':') {
+                    let key = &text[i + 1..end - 1];
+                    let value = skip_whitespace(bytes, colon + 1);
+                    match (depth, key, bytes.get(value)) {
+                        (1, "payload", Some(b'{')) => in_payload = true,
+                        (2, "rows", Some(b'[')) if in_payload => return split_array(bytes, value),
+                        _ => {}
+                    }
+                }
+                i = end;
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 1 {
+                    in_payload = false;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Parse a frame, deserializing its rows `batch_rows` at a time and yielding to the
+// event loop in between so a huge result doesn't freeze the page
+pub async fn parse(text: &str, batch_rows: usize) -> Result<WebSocketMessage, String> {
+    let Some(spans) = find_rows(text) else {
+        return serde_json::from_str(text).map_err(|e| e.to_string());
+    };
+    let skeleton = format!("{}[]{}", &text[..spans.array.0], &text[spans.array.1..]);
+    let mut message: WebSocketMessage = serde_json::from_str(&skeleton).map_err(|e| e.to_string())?;
+
+    let mut rows = Vec::with_capacity(spans.rows.len());
+    for batch in spans.rows.chunks(batch_rows.max(1)) {
+        for (start, end) in batch {
+            rows.push(serde_json::from_str::<Value>(&text[*start..*end]).map_err(|e| e.to_string())?);
+        }
+        if rows.len() < spans.rows.len() {
+            // A macrotask, so rendering and input get a turn
+            crate::retry::sleep(0.0).await.map_err(|e| format!("{:?}", e))?;
+        }
+    }
+    if let Some(payload) = message.payload.as_object_mut() {
+        payload.insert("rows".to_string(), Value::Array(rows));
+    }
+    Ok(message)
+}
+
+// Deliver a large frame once it has been parsed in the background
+pub(crate) fn dispatch(state: &SharedState, text: String) {
+    let batch_rows = state.borrow().incremental.batch_rows;
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let started = js_sys::Date::now();
+        let parsed = match parse(&text, batch_rows).await {
+            Ok(message) => Some(message),
+            Err(e) => {
+                log_warn!("WASM failed to parse a {} byte frame: {}", text.len(), e);
+                None
+            }
+        };
+        log_debug!("WASM parsed a {} byte frame in {}ms", text.len(), js_sys::Date::now() - started);
+        connection::deliver(&state, text, parsed);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_rows() {
+        let text = r#"{"type":"result","payload":{"sql":"SELECT '\"rows\":[' AS s","rows":[{"a":[1,{"b":"],"}]}, {"a":2} ],"rowCount":2},"id":"q1"}"#;
+        let spans = find_rows(text).unwrap();
+        let rows: Vec<&str> = spans.rows.iter().map(|(s, e)| text[*s..*e].trim()).collect();
+        assert_eq!(rows, vec![r#"{"a":[1,{"b":"],"}]}"#, r#"{"a":2}"#]);
+        assert_eq!(&text[spans.array.0..spans.array.1], r#"[{"a":[1,{"b":"],"}]}, {"a":2} ]"#);
+
+        assert_eq!(find_rows(r#"{"type":"result","payload":{"rows":[]}}"#).unwrap().rows, vec![]);
+        // Only the payload's own rows count
+        assert!(find_rows(r#"{"type":"result","payload":{"meta":{"rows":[1]}}}"#).is_none());
+    }
+}
@@ -0,0 +1,63 @@
+use wasm_bindgen::prelude::*;
+
+use crate::WebSocketMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Request,
+    Response,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Request => "request",
+            Phase::Response => "response",
+        }
+    }
+}
+
+// Ordered list of JS interceptors. Each is called as `fn(phase, message)` and may
+// return a replacement message; returning undefined keeps the message as is.
+#[derive(Debug, Clone, Default)]
+pub struct InterceptorChain {
+    interceptors: Vec<js_sys::Function>,
+}
+
+impl InterceptorChain {
+    pub fn add(&mut self, interceptor: js_sys::Function) {
+        self.interceptors.push(interceptor);
+    }
+
+    pub fn remove(&mut self, interceptor: &js_sys::Function) -> bool {
+        let before = self.interceptors.len();
+        self.interceptors.retain(|f| !js_sys::Object::is(f, interceptor));
+        self.interceptors.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.interceptors.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interceptors.is_empty()
+    }
+
+    // Run the message through every interceptor in registration order. The
+    // message id is preserved so responses still correlate with their request.
+    pub fn apply(&self, phase: Phase, message: WebSocketMessage) -> Result<WebSocketMessage, JsValue> {
+        let id = message.id.clone();
+        let mut current = message;
+        for interceptor in &self.interceptors {
+            let value = crate::to_js(&current)?;
+            let returned = interceptor.call2(&JsValue::NULL, &JsValue::from_str(phase.as_str()), &value)?;
+            if !returned.is_undefined() {
+                current = serde_wasm_bindgen::from_value(returned).map_err(|e| {
+                    JsValue::from_str(&format!("Interceptor returned an invalid {} message: {}", phase.as_str(), e))
+                })?;
+            }
+        }
+        current.id = id;
+        Ok(current)
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::null_policy::NullPolicy;
+
+// Options accepted by `set_string_interning`
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct InternOptions {
+    // Longer strings (in UTF-8 bytes) are converted on their own; long text rarely repeats
+    #[serde(rename = "maxLength")]
+    pub max_length: usize,
+    // Only cells of these columns are interned, named as they reach JS. All when unset.
+    pub columns: Option<Vec<String>>,
+    // Distinct strings the client keeps. Once full, new strings are no longer added.
+    #[serde(rename = "maxDistinct")]
+    pub max_distinct: usize,
+}
+
+impl Default for InternOptions {
+    fn default() -> Self {
+        InternOptions { max_length: 64, columns: None, max_distinct: 10_000 }
+    }
+}
+
+impl InternOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_length == 0 || self.max_distinct == 0 {
+            return Err("maxLength and maxDistinct must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn applies_to(&self, column: &str, text: &str) -> bool {
+        text.len() <= self.max_length && self.columns.as_ref().is_none_or(|columns| columns.iter().any(|c| c == column))
+    }
+}
+
+// What `string_interning_stats` returns
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+pub struct InternStats {
+    // Distinct strings held
+    pub size: usize,
+    // Cells given a string already held
+    pub hits: u64,
+    // Cells converted on their own, the first of each distinct string included
+    pub misses: u64,
+}
+
+// Strings converted once and handed out again for every equal cell
+pub struct Interner<T> {
+    values: HashMap<String, T>,
+    limit: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<T: Clone> Interner<T> {
+    pub fn new(limit: usize) -> Self {
+        Interner { values: HashMap::new(), limit, hits: 0, misses: 0 }
+    }
+
+    pub fn get(&mut self, text: &str, make: impl FnOnce(&str) -> T) -> T {
+        if let Some(value) = self.values.get(text) {
+            self.hits += 1;
+            return value.clone();
+        }
+        self.misses += 1;
+        let value = make(text);
+        if self.values.len() < self.limit {
+            self.values.insert(text.to_string(), value.clone());
+        }
+        value
+    }
+
+    pub fn stats(&self) -> InternStats {
+        InternStats { size: self.values.len(), hits: self.hits, misses: self.misses }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct InternState {
+    pub options: Option<InternOptions>,
+    // Kept across queries, so statuses and codes that recur in every result share one string
+    pub pool: Option<Interner<JsValue>>,
+}
+
+impl InternState {
+    pub fn configure(&mut self, options: Option<InternOptions>) {
+        self.pool = options.as_ref().map(|options| Interner::new(options.max_distinct));
+        self.options = options;
+    }
+}
+
+// Build a result's row objects cell by cell, handing out one JS string for every equal
+// string cell instead of a copy each. The rest of the result converts as usual.
+pub(crate) fn result_to_js(mut result: Value, state: &mut InternState, null_policy: NullPolicy) -> Result<JsValue, JsValue> {
+    let (Some(options), Some(pool)) = (&state.options, &mut state.pool) else {
+        return null_policy.to_js(&result);
+    };
+    let Some(Value::Array(rows)) = result.as_object_mut().and_then(|object| object.remove("rows")) else {
+        return null_policy.to_js(&result);
+    };
+    let output = null_policy.to_js(&result)?;
+    let null = if null_policy == NullPolicy::Undefined { JsValue::UNDEFINED } else { JsValue::NULL };
+    // Column names repeat in every row whatever the options say
+    let mut keys: Interner<JsValue> = Interner::new(usize::MAX);
+    let array = js_sys::Array::new_with_length(rows.len() as u32);
+    for (i, row) in rows.iter().enumerate() {
+        let Value::Object(cells) = row else {
+            array.set(i as u32, null_policy.to_js(row)?);
+            continue;
+        };
+        let object = js_sys::Object::new();
+        for (column, cell) in cells {
+            let value = match cell {
+                Value::String(text) if options.applies_to(column, text) => pool.get(text, JsValue::from_str),
+                Value::String(text) => JsValue::from_str(text),
+                Value::Bool(flag) => JsValue::from_bool(*flag),
+                Value::Null => null.clone(),
+                other => null_policy.to_js(other)?,
+            };
+            js_sys::Reflect::set(&object, &keys.get(column, JsValue::from_str), &value)?;
+        }
+        array.set(i as u32, object.into());
+    }
+    js_sys::Reflect::set(&output, &JsValue::from_str("rows"), &array)?;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn share(text: &str) -> Rc<str> {
+        Rc::from(text)
+    }
+
+    #[test]
+    fn test_equal_strings_share_one_value() {
+        let options: InternOptions = serde_json::from_value(serde_json::json!({ "maxLength": 8, "columns": ["status"] })).unwrap();
+        assert_eq!(options.max_distinct, 10_000);
+        assert!(options.applies_to("status", "shipped"));
+        assert!(!options.applies_to("status", "backordered"));
+        assert!(!options.applies_to("note", "shipped"));
+        assert!(InternOptions { max_distinct: 0, ..InternOptions::default() }.validate().is_err());
+
+        let mut interner: Interner<Rc<str>> = Interner::new(2);
+        let first = interner.get("shipped", share);
+        let again = interner.get("shipped", share);
+        assert!(Rc::ptr_eq(&first, &again));
+        interner.get("pending", share);
+        // Full: a new string is converted but not kept
+        let extra = interner.get("lost", share);
+        assert!(!Rc::ptr_eq(&extra, &interner.get("lost", share)));
+        assert_eq!(interner.stats(), InternStats { size: 2, hits: 1, misses: 4 });
+    }
+}
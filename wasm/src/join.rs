@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::csv::column_names;
+
+// Which rows `join_results` keeps besides the matched pairs
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinKind {
+    #[default]
+    Inner,
+    // Every left row, with NULL right columns where nothing matched
+    Left,
+    Right,
+    Full,
+}
+
+// Options accepted by `join_results`
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct JoinOptions {
+    // Key columns of the left rows
+    pub on: Vec<String>,
+    // Key columns of the right rows, in the same order; defaults to `on`
+    #[serde(default, rename = "rightOn")]
+    pub right_on: Option<Vec<String>>,
+    #[serde(default)]
+    pub kind: JoinKind,
+    // Prepended to right columns whose name a left column already has. Without it the
+    // left value is kept.
+    #[serde(default, rename = "rightPrefix")]
+    pub right_prefix: Option<String>,
+}
+
+// Options accepted by `merge_results`
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct MergeOptions {
+    // Rows with equal key values are one row: a later result's copy replaces an earlier
+    // one in place. Without a key every row is kept.
+    pub key: Option<Vec<String>>,
+}
+
+// A row's key, or None when a key column is missing or NULL; like SQL, NULL matches nothing
+fn key_of(row: &Value, columns: &[String]) -> Option<String> {
+    if columns.iter().any(|c| row.get(c).is_none_or(Value::is_null)) {
+        return None;
+    }
+    crate::diff::row_key(row, columns)
+}
+
+// Hash join: index the right rows by key, then probe with each left row in order.
+// Right rows nothing matched follow for right and full joins.
+pub fn join_rows(left: &[Value], right: &[Value], options: &JoinOptions) -> Result<Vec<Value>, String> {
+    let right_on = options.right_on.as_ref().unwrap_or(&options.on);
+    if options.on.is_empty() || options.on.len() != right_on.len() {
+        return Err("join needs the same number of key columns on both sides".to_string());
+    }
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, row) in right.iter().enumerate() {
+        if let Some(key) = key_of(row, right_on) {
+            index.entry(key).or_default().push(i);
+        }
+    }
+    let left_columns = column_names(left);
+    // Where each right column lands in a joined row; the key columns are the left's
+    let placement: Vec<(String, Option<String>)> = column_names(right)
+        .into_iter()
+        .filter(|column| !right_on.contains(column))
+        .map(|column| {
+            let target = match (&options.right_prefix, left_columns.contains(&column)) {
+                (Some(prefix), true) => Some(format!("{}{}", prefix, column)),
+                (None, true) => None,
+                (_, false) => Some(column.clone()),
+            };
+            (column, target)
+        })
+        .collect();
+
+    let combine = |left: Option<&Value>, right: Option<&Value>| {
+        let mut row = match left {
+            Some(left) => left.as_object().cloned().unwrap_or_default(),
+            None => left_columns.iter().map(|c| (c.clone(), Value::Null)).collect::<Map<String, Value>>(),
+        };
+        if let (None, Some(right)) = (left, right) {
+            for (left_key, right_key) in options.on.iter().zip(right_on) {
+                row.insert(left_key.clone(), right.get(right_key).cloned().unwrap_or_default());
+            }
+        }
+        for (column, target) in &placement {
+            if let Some(target) = target {
+                let value = right.and_then(|r| r.get(column)).cloned().unwrap_or_default();
+                row.insert(target.clone(), value);
+            }
+        }
+        Value::Object(row)
+    };
+
+    let mut joined = Vec::new();
+    let mut matched = HashSet::new();
+    for row in left {
+        let matches = key_of(row, &options.on).and_then(|key| index.get(&key));
+        match matches {
+            Some(matches) => {
+                for &i in matches {
+                    matched.insert(i);
+                    joined.push(combine(Some(row), Some(&right[i])));
+                }
+            }
+            None if matches!(options.kind, JoinKind::Left | JoinKind::Full) => joined.push(combine(Some(row), None)),
+            None => {}
+        }
+    }
+    if matches!(options.kind, JoinKind::Right | JoinKind::Full) {
+        let unmatched = right.iter().enumerate().filter(|(i, _)| !matched.contains(i));
+        joined.extend(unmatched.map(|(_, row)| combine(None, Some(row))));
+    }
+    Ok(joined)
+}
+
+// Concatenate row sets in order, collapsing rows with equal keys onto the first position
+// with the last copy's values
+pub fn merge_rows(sets: Vec<Vec<Value>>, options: &MergeOptions) -> Result<Vec<Value>, String> {
+    let Some(key) = &options.key else {
+        return Ok(sets.into_iter().flatten().collect());
+    };
+    let mut merged: Vec<Value> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for row in sets.into_iter().flatten() {
+        let id = crate::diff::row_key(&row, key).ok_or_else(|| format!("Row is missing key column(s) {}", key.join(", ")))?;
+        match positions.get(&id) {
+            Some(&at) => merged[at] = row,
+            None => {
+                positions.insert(id, merged.len());
+                merged.push(row);
+            }
+        }
+    }
+    Ok(merged)
+}
+
+// The rows of a query result, or of a bare array of rows
+fn rows_of(result: JsValue) -> Result<Vec<Value>, JsValue> {
+    let result: Value = serde_wasm_bindgen::from_value(result).map_err(|e| JsValue::from_str(&format!("Invalid query result: {}", e)))?;
+    match result {
+        Value::Array(rows) => Ok(rows),
+        Value::Object(mut result) => match result.remove("rows") {
+            Some(Value::Array(rows)) => Ok(rows),
+            _ => Err(JsValue::from_str("Expected a query result with rows; columnar results can't be joined")),
+        },
+        _ => Err(JsValue::from_str("Expected a query result or an array of rows")),
+    }
+}
+
+fn result_of(rows: Vec<Value>) -> Result<JsValue, JsValue> {
+    crate::to_js(&json!({ "rowCount": rows.len(), "rows": rows }))
+}
+
+// Join two query results (or arrays of rows) on key columns in WASM, without asking the
+// server again. Returns `{ rows, rowCount }`.
+#[wasm_bindgen]
+pub fn join_results(left: JsValue, right: JsValue, #[wasm_bindgen(unchecked_param_type = "JoinOptions")] options: JsValue) -> Result<JsValue, JsValue> {
+    let options: JoinOptions = serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid join options: {}", e)))?;
+    let joined = join_rows(&rows_of(left)?, &rows_of(right)?, &options).map_err(|e| JsValue::from_str(&e))?;
+    result_of(joined)
+}
+
+// Combine query results (or arrays of rows) into one, such as pages or cached fragments
+// of the same query. `options.key` replaces rows seen earlier instead of repeating them.
+#[wasm_bindgen]
+pub fn merge_results(
+    #[wasm_bindgen(unchecked_param_type = "unknown[]")] results: js_sys::Array,
+    #[wasm_bindgen(unchecked_optional_param_type = "MergeOptions | null")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let options: MergeOptions = if options.is_undefined() || options.is_null() {
+        MergeOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid merge options: {}", e)))?
+    };
+    let sets = results.iter().map(rows_of).collect::<Result<Vec<_>, _>>()?;
+    let merged = merge_rows(sets, &options).map_err(|e| JsValue::from_str(&e))?;
+    result_of(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(kind: JoinKind) -> JoinOptions {
+        JoinOptions { on: vec!["id".to_string()], right_on: Some(vec!["user_id".to_string()]), kind, right_prefix: None }
+    }
+
+    #[test]
+    fn test_join_kinds_and_merge() {
+        let users = vec![json!({ "id": 1, "name": "ada" }), json!({ "id": 2, "name": "bo" }), json!({ "id": null, "name": "?" })];
+        let orders = vec![json!({ "user_id": 1, "total": 5 }), json!({ "user_id": 1, "total": 7 }), json!({ "user_id": 3, "total": 9 })];
+
+        let inner = join_rows(&users, &orders, &options(JoinKind::Inner)).unwrap();
+        assert_eq!(inner, vec![json!({ "id": 1, "name": "ada", "total": 5 }), json!({ "id": 1, "name": "ada", "total": 7 })]);
+        let left = join_rows(&users, &orders, &options(JoinKind::Left)).unwrap();
+        assert_eq!(left[2], json!({ "id": 2, "name": "bo", "total": null }));
+        // A NULL key matches nothing
+        assert_eq!(left[3], json!({ "id": null, "name": "?", "total": null }));
+        let full = join_rows(&users, &orders, &options(JoinKind::Full)).unwrap();
+        assert_eq!(full.last(), Some(&json!({ "id": 3, "name": null, "total": 9 })));
+
+        let prices = vec![json!({ "id": 1, "name": "list" })];
+        let prefixed = JoinOptions { on: vec!["id".to_string()], right_on: None, kind: JoinKind::Inner, right_prefix: Some("price_".to_string()) };
+        assert_eq!(join_rows(&users, &prices, &prefixed).unwrap(), vec![json!({ "id": 1, "name": "ada", "price_name": "list" })]);
+
+        let merged = merge_rows(vec![users.clone(), vec![json!({ "id": 2, "name": "Bo" })]], &MergeOptions { key: Some(vec!["id".to_string()]) }).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1], json!({ "id": 2, "name": "Bo" }));
+        assert_eq!(merge_rows(vec![users.clone(), users], &MergeOptions::default()).unwrap().len(), 6);
+    }
+}
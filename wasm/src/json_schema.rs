@@ -0,0 +1,98 @@
+use serde_json::Value;
+
+// Check `value` against the JSON Schema keywords notification payloads need: `type`,
+// `enum`, `required`, `properties`, `additionalProperties: false` and `items`. Other
+// keywords are ignored. Errors name the path of the offending value.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    check(schema, value, "payload")
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(|name| name.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(name, value)) {
+            return Err(format!("{}: expected {}, got {}", path, types.join(" or "), type_name(value)));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return Err(format!("{}: {} is not one of the allowed values", path, value));
+        }
+    }
+    if let Value::Object(fields) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            if let Some(missing) = required.iter().filter_map(|name| name.as_str()).find(|name| !fields.contains_key(*name)) {
+                return Err(format!("{}.{}: is required", path, missing));
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (name, field) in fields {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => check(field_schema, field, &format!("{}.{}", path, name))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{}.{}: is not allowed", path, name));
+                }
+                None => {}
+            }
+        }
+    }
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            check(item_schema, item, &format!("{}[{}]", path, index))?;
+        }
+    }
+    Ok(())
+}
+
+fn has_type(name: &str, value: &Value) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_reports_paths() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "op"],
+            "properties": {
+                "id": { "type": "integer" },
+                "op": { "enum": ["insert", "update", "delete"] },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "additionalProperties": false
+        });
+        assert!(validate(&schema, &json!({"id": 7, "op": "insert", "tags": ["a"]})).is_ok());
+        assert_eq!(validate(&schema, &json!({"op": "insert"})).unwrap_err(), "payload.id: is required");
+        assert_eq!(validate(&schema, &json!({"id": 1.5, "op": "insert"})).unwrap_err(), "payload.id: expected integer, got number");
+        assert!(validate(&schema, &json!({"id": 1, "op": "upsert"})).unwrap_err().starts_with("payload.op:"));
+        assert_eq!(validate(&schema, &json!({"id": 1, "op": "delete", "tags": [3]})).unwrap_err(), "payload.tags[0]: expected string, got number");
+        assert_eq!(validate(&schema, &json!({"id": 1, "op": "delete", "extra": true})).unwrap_err(), "payload.extra: is not allowed");
+        assert!(validate(&json!({"type": ["string", "null"]}), &Value::Null).is_ok());
+    }
+}
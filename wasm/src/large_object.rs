@@ -0,0 +1,148 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Blob, ReadableStream, ReadableStreamDefaultController};
+
+use crate::connection::{execute_query, SharedState};
+use crate::errors::BridgeError;
+
+// Bytes moved per query; base64 makes each frame about a third larger
+pub const DEFAULT_CHUNK_SIZE: u32 = 256 * 1024;
+
+// Large objects go through lo_get/lo_put, which work on any session without opening a
+// descriptor, so each chunk is an ordinary query that either bridge can run
+const READ_CHUNK_SQL: &str = "SELECT encode(lo_get($1::int8::oid, $2::int8, $3::int4), 'base64') AS data";
+const WRITE_CHUNK_SQL: &str = "SELECT lo_put($1::int8::oid, $2::int8, decode($3::text, 'base64'))";
+const CREATE_SQL: &str = "SELECT lo_create(0) AS oid";
+
+// `(offset, end)` of each chunk needed to write `len` bytes
+pub fn chunk_ranges(len: usize, chunk_size: usize) -> Vec<(usize, usize)> {
+    let chunk_size = chunk_size.max(1);
+    (0..len).step_by(chunk_size).map(|offset| (offset, (offset + chunk_size).min(len))).collect()
+}
+
+// Bytes of a chunk read with READ_CHUNK_SQL. Postgres wraps base64 output every 76
+// characters, so line breaks are dropped before decoding.
+pub fn decode_chunk(result: &Value) -> Result<Vec<u8>, String> {
+    let encoded = result
+        .get("rows")
+        .and_then(|rows| rows.get(0))
+        .and_then(|row| row.get("data"))
+        .and_then(|data| data.as_str())
+        .ok_or_else(|| "Large object chunk missing from the result".to_string())?;
+    let compact: String = encoded.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    STANDARD.decode(compact).map_err(|e| format!("Large object chunk is not valid base64: {}", e))
+}
+
+fn chunk_size(requested: Option<u32>) -> u32 {
+    requested.filter(|size| *size > 0).unwrap_or(DEFAULT_CHUNK_SIZE)
+}
+
+async fn read_chunk(state: &SharedState, oid: u32, offset: u64, len: u32) -> Result<Vec<u8>, JsValue> {
+    let result = execute_query(state, READ_CHUNK_SQL, Some(vec![json!(oid), json!(offset), json!(len)])).await?;
+    decode_chunk(&result).map_err(|e| BridgeError::connection(&e).into())
+}
+
+// The whole large object as a Blob
+pub(crate) async fn read_blob(state: &SharedState, oid: u32, chunk: Option<u32>) -> Result<Blob, JsValue> {
+    let chunk = chunk_size(chunk);
+    let parts = js_sys::Array::new();
+    let mut offset = 0u64;
+    loop {
+        let bytes = read_chunk(state, oid, offset, chunk).await?;
+        let done = bytes.len() < chunk as usize;
+        offset += bytes.len() as u64;
+        if !bytes.is_empty() {
+            parts.push(&js_sys::Uint8Array::from(bytes.as_slice()));
+        }
+        if done {
+            break;
+        }
+    }
+    log_debug!("WASM read large object {} ({} bytes)", oid, offset);
+    Blob::new_with_u8_array_sequence(&parts)
+}
+
+// A ReadableStream of Uint8Array chunks, each fetched when the consumer pulls
+pub(crate) fn read_stream(state: &SharedState, oid: u32, chunk: Option<u32>) -> Result<ReadableStream, JsValue> {
+    let chunk = chunk_size(chunk);
+    let offset = Rc::new(Cell::new(0u64));
+    let state = state.clone();
+    let pull = Closure::wrap(Box::new(move |controller: JsValue| {
+        let controller: ReadableStreamDefaultController = controller.unchecked_into();
+        let (state, offset) = (state.clone(), offset.clone());
+        future_to_promise(async move {
+            let bytes = read_chunk(&state, oid, offset.get(), chunk).await?;
+            offset.set(offset.get() + bytes.len() as u64);
+            if !bytes.is_empty() {
+                controller.enqueue_with_chunk(&js_sys::Uint8Array::from(bytes.as_slice()))?;
+            }
+            if bytes.len() < chunk as usize {
+                controller.close()?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
+    }) as Box<dyn FnMut(JsValue) -> js_sys::Promise>);
+    let source = js_sys::Object::new();
+    js_sys::Reflect::set(&source, &JsValue::from_str("pull"), pull.as_ref())?;
+    // The stream calls `pull` for as long as it lives
+    pull.forget();
+    ReadableStream::new_with_underlying_source(&source)
+}
+
+// Bytes from a Uint8Array, ArrayBuffer or Blob
+pub(crate) async fn bytes_from(data: JsValue) -> Result<Vec<u8>, JsValue> {
+    if let Some(blob) = data.dyn_ref::<Blob>() {
+        let buffer = JsFuture::from(blob.array_buffer()).await?;
+        return Ok(js_sys::Uint8Array::new(&buffer).to_vec());
+    }
+    if data.is_instance_of::<js_sys::Uint8Array>() || data.is_instance_of::<js_sys::ArrayBuffer>() {
+        return Ok(js_sys::Uint8Array::new(&data).to_vec());
+    }
+    Err(JsValue::from_str("Large object data must be a Uint8Array, ArrayBuffer or Blob"))
+}
+
+// Write `data` from the start of large object `oid`, creating one when `oid` is None.
+// Existing bytes past the end of `data` are kept. Returns the object's oid.
+pub(crate) async fn write(state: &SharedState, oid: Option<u32>, data: &[u8], chunk: Option<u32>) -> Result<u32, JsValue> {
+    let oid = match oid {
+        Some(oid) => oid,
+        None => {
+            let result = execute_query(state, CREATE_SQL, None).await?;
+            let oid = result.get("rows").and_then(|rows| rows.get(0)).and_then(|row| row.get("oid")).and_then(|oid| oid.as_u64());
+            oid.ok_or_else(|| BridgeError::connection("lo_create returned no oid"))? as u32
+        }
+    };
+    for (start, end) in chunk_ranges(data.len(), chunk_size(chunk) as usize) {
+        let params = vec![json!(oid), json!(start), json!(STANDARD.encode(&data[start..end]))];
+        execute_query(state, WRITE_CHUNK_SQL, Some(params)).await?;
+    }
+    log_debug!("WASM wrote large object {} ({} bytes)", oid, data.len());
+    Ok(oid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_ranges() {
+        assert_eq!(chunk_ranges(0, 4), vec![]);
+        assert_eq!(chunk_ranges(10, 4), vec![(0, 4), (4, 8), (8, 10)]);
+        assert_eq!(chunk_ranges(8, 4), vec![(0, 4), (4, 8)]);
+    }
+
+    #[test]
+    fn test_decode_chunk_ignores_line_breaks() {
+        let data = vec![7u8; 100];
+        let encoded = STANDARD.encode(&data);
+        let wrapped = format!("{}\n{}", &encoded[..76], &encoded[76..]);
+        assert_eq!(decode_chunk(&json!({"rows": [{"data": wrapped}]})).unwrap(), data);
+        assert!(decode_chunk(&json!({"rows": []})).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use serde_json::Value;
+
+use crate::sql::{tokenize, Token};
+
+// Words that start a statement returning rows the guard can bound
+const READS: [&str; 4] = ["select", "with", "values", "table"];
+
+const DATA_MODIFYING: [&str; 4] = ["insert", "update", "delete", "merge"];
+
+// Words at the statement's top level, outside any parentheses
+fn top_level_words(tokens: &[Token]) -> Vec<&str> {
+    let mut depth = 0usize;
+    let mut words = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Punct('(') => depth += 1,
+            Token::Punct(')') => depth = depth.saturating_sub(1),
+            Token::Word(word) if depth == 0 => words.push(word.as_str()),
+            _ => {}
+        }
+    }
+    words
+}
+
+// Whether the SQL changes data anywhere, including inside a CTE. `FOR UPDATE` and
+// `FOR NO KEY UPDATE` only lock rows.
+pub fn modifies_data(sql: &str) -> bool {
+    let tokens = tokenize(sql);
+    let words: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Word(word) => Some(word.as_str()),
+            _ => None,
+        })
+        .collect();
+    words.iter().enumerate().any(|(i, word)| {
+        DATA_MODIFYING.contains(word) && !(*word == "update" && i > 0 && matches!(words[i - 1], "for" | "key"))
+    })
+}
+
+// The SQL with `LIMIT limit + 1` appended when it is a single read with no LIMIT or
+// FETCH of its own; the extra row tells a complete result from a truncated one
+pub fn apply(sql: &str, limit: u32) -> Option<String> {
+    let trimmed = sql.trim_end().trim_end_matches(';').trim_end();
+    let tokens = tokenize(trimmed);
+    // A semicolon left over means more follows, even if only a comment (`SELECT 1; -- note`)
+    if tokens.contains(&Token::Punct(';')) {
+        return None;
+    }
+    let words = top_level_words(&tokens);
+    let bounded = words.iter().any(|word| matches!(*word, "limit" | "fetch" | "into"));
+    let writes = words.iter().any(|word| DATA_MODIFYING.contains(word));
+    if !READS.contains(words.first()?) || bounded || writes {
+        return None;
+    }
+    // On its own line so a trailing `--` comment can't swallow it
+    Some(format!("{}\nLIMIT {}", trimmed, u64::from(limit) + 1))
+}
+
+// Cut a guarded result back to `limit` rows and flag whether anything was dropped
+pub fn truncate(result: &mut Value, limit: u32) {
+    let limit = limit as usize;
+    let Some(object) = result.as_object_mut() else {
+        return;
+    };
+    let truncated = match object.get_mut("rows") {
+        Some(Value::Array(rows)) if rows.len() > limit => {
+            rows.truncate(limit);
+            true
+        }
+        _ => false,
+    };
+    if truncated {
+        object.insert("rowCount".to_string(), Value::from(limit));
+    }
+    object.insert("truncated".to_string(), Value::Bool(truncated));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(apply("SELECT * FROM facts -- all\n;", 100).unwrap(), "SELECT * FROM facts -- all\nLIMIT 101");
+        assert!(apply("WITH t AS (SELECT * FROM f LIMIT 5) SELECT * FROM t", 10).is_some());
+        assert!(apply("SELECT * FROM facts ORDER BY id LIMIT 5", 10).is_none());
+        assert!(apply("SELECT * FROM facts FETCH FIRST 5 ROWS ONLY", 10).is_none());
+        assert!(apply("SELECT 1; SELECT 2", 10).is_none());
+        assert!(apply("SELECT 1; -- done", 10).is_none());
+        assert!(apply("WITH gone AS (DELETE FROM f RETURNING *) SELECT * FROM gone", 10).is_some());
+        assert!(apply("WITH t AS (SELECT 1) DELETE FROM f", 10).is_none());
+        assert!(apply("UPDATE facts SET x = 1", 10).is_none());
+    }
+
+    #[test]
+    fn test_modifies_data() {
+        assert!(modifies_data("WITH gone AS (DELETE FROM f RETURNING *) SELECT * FROM gone"));
+        assert!(modifies_data("with t as (select 1) insert into f select * from t"));
+        assert!(!modifies_data("SELECT * FROM f FOR UPDATE"));
+        assert!(!modifies_data("SELECT * FROM f FOR NO KEY UPDATE SKIP LOCKED"));
+        assert!(!modifies_data("SELECT 'delete' AS \"update\""));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let mut result = json!({"rows": [{"id": 1}, {"id": 2}, {"id": 3}], "rowCount": 3});
+        truncate(&mut result, 2);
+        assert_eq!(result, json!({"rows": [{"id": 1}, {"id": 2}], "rowCount": 2, "truncated": true}));
+        truncate(&mut result, 2);
+        assert_eq!(result["truncated"], json!(false));
+    }
+}
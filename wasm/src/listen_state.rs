@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+
+// Where a LISTEN channel stands, for realtime-connection indicators
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenState {
+    // LISTEN sent, not acknowledged yet
+    Pending,
+    // The bridge confirmed the LISTEN; notifications are arriving
+    Active,
+    // The connection or the bridge's LISTEN connection went away; notifications sent
+    // since may be missed
+    Lost,
+}
+
+// Detail of the `listenstate` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ListenStateChange {
+    pub channel: String,
+    pub state: ListenState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// State of every channel the client LISTENs on or is trying to
+#[derive(Debug, Default)]
+pub(crate) struct ListenStates {
+    states: BTreeMap<String, ListenState>,
+    // LISTEN again this long after the bridge reports its LISTEN connection lost
+    pub relisten_ms: Option<f64>,
+}
+
+impl ListenStates {
+    // False if the channel was already in that state
+    pub fn set(&mut self, channel: &str, state: ListenState) -> bool {
+        self.states.insert(channel.to_string(), state) != Some(state)
+    }
+
+    pub fn remove(&mut self, channel: &str) {
+        self.states.remove(channel);
+    }
+
+    // Mark every channel lost, returning the ones that weren't already
+    pub fn lose_all(&mut self) -> Vec<String> {
+        self.states
+            .iter_mut()
+            .filter(|(_, state)| **state != ListenState::Lost)
+            .map(|(channel, state)| {
+                *state = ListenState::Lost;
+                channel.clone()
+            })
+            .collect()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, ListenState> {
+        self.states.clone()
+    }
+}
+
+fn emit(state: &SharedState, channel: String, listen_state: ListenState, reason: Option<&str>) {
+    let change = ListenStateChange { channel, state: listen_state, reason: reason.map(String::from) };
+    crate::events::emit(state, "listenstate", &change);
+}
+
+// Move one channel to `listen_state`, firing `listenstate` if that changed it
+pub(crate) fn update(state: &SharedState, channel: &str, listen_state: ListenState, reason: Option<&str>) {
+    if state.borrow_mut().listen_states.set(channel, listen_state) {
+        emit(state, channel.to_string(), listen_state, reason);
+    }
+}
+
+// Every LISTEN is gone with the connection or session that held it
+pub(crate) fn lost(state: &SharedState, reason: &str) {
+    let channels = state.borrow_mut().listen_states.lose_all();
+    for channel in channels {
+        emit(state, channel, ListenState::Lost, Some(reason));
+    }
+}
+
+// The bridge's LISTEN connection failed while ours stayed up. The channels have to be
+// LISTENed again, which happens on the next reconnect or subscription, or after
+// `relistenMs` when configured.
+pub(crate) fn bridge_lost(state: &SharedState, payload: &serde_json::Value) {
+    let reason = payload.get("reason").and_then(|r| r.as_str()).unwrap_or("The bridge lost its LISTEN connection");
+    log_warn!("WASM bridge lost its LISTEN connection: {}", reason);
+    let (channels, relisten_ms) = {
+        let mut state = state.borrow_mut();
+        (state.watches.reset_listening(), state.listen_states.relisten_ms)
+    };
+    lost(state, reason);
+    let Some(delay) = relisten_ms.filter(|_| !channels.is_empty()) else {
+        return;
+    };
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        if crate::retry::sleep(delay).await.is_err() {
+            return;
+        }
+        match crate::live::ensure_listening(&state, &channels).await {
+            Ok(()) => log_info!("WASM LISTENed again on {} channels", channels.len()),
+            Err(e) => log_warn!("WASM failed to LISTEN again after the bridge lost its connection: {:?}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loses_only_what_was_not_lost() {
+        let mut states = ListenStates::default();
+        assert!(states.set("orders", ListenState::Pending));
+        assert!(states.set("orders", ListenState::Active));
+        assert!(!states.set("orders", ListenState::Active));
+        states.set("stock", ListenState::Lost);
+        assert_eq!(states.lose_all(), vec!["orders".to_string()]);
+        assert!(states.lose_all().is_empty());
+        states.remove("stock");
+        assert_eq!(states.snapshot(), BTreeMap::from([("orders".to_string(), ListenState::Lost)]));
+        assert_eq!(serde_json::to_value(ListenState::Active).unwrap(), "active");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, ClientState, SharedState};
+use crate::diff::{self, RowIndex};
+use crate::listen_state::{self, ListenState};
+use crate::dispatch::Priority;
+use crate::null_policy::NullPolicy;
+
+// Coalesces change notifications: at most one run in flight, plus one follow-up
+// when changes arrive while it is running
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunState {
+    running: bool,
+    dirty: bool,
+}
+
+impl RunState {
+    // True if the caller should start a run now
+    pub fn begin(&mut self) -> bool {
+        if self.running {
+            self.dirty = true;
+            return false;
+        }
+        self.running = true;
+        true
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    // True if another change arrived during the run and it must go again
+    pub fn finish(&mut self) -> bool {
+        if self.dirty {
+            self.dirty = false;
+            return true;
+        }
+        self.running = false;
+        false
+    }
+}
+
+pub(crate) struct Watch {
+    pub sql: String,
+    pub params: Option<Vec<Value>>,
+    pub channels: Vec<String>,
+    pub callback: js_sys::Function,
+    pub null_policy: NullPolicy,
+    pub run: RunState,
+    // Set for diffing watches, which deliver only the delta since the previous run
+    pub diff: Option<DiffState>,
+    // The result last delivered, as the bridge returned it
+    pub last: Option<Value>,
+}
+
+pub(crate) struct DiffState {
+    pub key_columns: Vec<String>,
+    pub index: Option<RowIndex>,
+}
+
+impl DiffState {
+    pub fn new(key_columns: Vec<String>) -> DiffState {
+        DiffState { key_columns, index: None }
+    }
+}
+
+// Live queries keyed by watch id, plus the channels this client is LISTENing on
+#[derive(Default)]
+pub(crate) struct WatchRegistry {
+    watches: HashMap<u32, Watch>,
+    listening: HashSet<String>,
+    next_id: u32,
+    // Hears about failed refreshes and callbacks that threw
+    pub error_hook: Option<js_sys::Function>,
+}
+
+impl WatchRegistry {
+    pub fn add(&mut self, watch: Watch) -> u32 {
+        self.next_id += 1;
+        self.watches.insert(self.next_id, watch);
+        self.next_id
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Watch> {
+        self.watches.remove(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Watch> {
+        self.watches.get_mut(&id)
+    }
+
+    // Watches that depend on a notification channel
+    pub fn affected(&self, channel: &str) -> Vec<u32> {
+        self.watches
+            .iter()
+            .filter(|(_, w)| w.channels.iter().any(|c| c == channel))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn has_listeners(&self) -> bool {
+        !self.listening.is_empty()
+    }
+
+    pub fn ids(&self) -> Vec<u32> {
+        self.watches.keys().copied().collect()
+    }
+
+    // Forget which channels are LISTENed, e.g. because the connection that held them
+    // is gone; returns them so they can be subscribed again
+    pub fn reset_listening(&mut self) -> Vec<String> {
+        self.listening.drain().collect()
+    }
+
+    pub fn listening(&self) -> Vec<String> {
+        self.listening.iter().cloned().collect()
+    }
+
+    pub fn is_used(&self, channel: &str) -> bool {
+        self.watches.values().any(|w| w.channels.iter().any(|c| c == channel))
+    }
+
+    pub fn count(&self, channel: &str) -> usize {
+        self.watches.values().filter(|w| w.channels.iter().any(|c| c == channel)).count()
+    }
+
+    pub fn channels(&self) -> Vec<String> {
+        self.watches.values().flat_map(|w| w.channels.iter().cloned()).collect()
+    }
+
+    // The last result of each watch whose SQL passes `matches`
+    pub fn last_results(&self, matches: impl Fn(&str) -> bool) -> Vec<(u32, Value)> {
+        self.watches
+            .iter()
+            .filter(|(_, w)| matches(&w.sql))
+            .filter_map(|(id, w)| Some((*id, w.last.clone()?)))
+            .collect()
+    }
+}
+
+// LISTEN on any channel this client isn't already subscribed to
+pub(crate) async fn ensure_listening(state: &SharedState, channels: &[String]) -> Result<(), JsValue> {
+    for channel in channels {
+        if state.borrow().watches.listening.contains(channel) {
+            continue;
+        }
+        listen_state::update(state, channel, ListenState::Pending, None);
+        if let Err(e) = connection::request(state, "listen", "listen", json!({ "channel": channel })).await {
+            listen_state::update(state, channel, ListenState::Lost, Some("The bridge did not confirm the LISTEN"));
+            return Err(e);
+        }
+        state.borrow_mut().watches.listening.insert(channel.clone());
+        listen_state::update(state, channel, ListenState::Active, None);
+    }
+    crate::subscription_store::changed(state);
+    Ok(())
+}
+
+// UNLISTEN channels no remaining watch, notification callback or cache rule depends on,
+// right away or once `unlistenLingerMs` has passed without one coming back
+pub(crate) async fn release_channels(state: &SharedState, channels: &[String]) -> Result<(), JsValue> {
+    for channel in channels {
+        let linger = {
+            let mut state = state.borrow_mut();
+            if state.unlisten.disabled || !is_released(&state, channel) {
+                continue;
+            }
+            state.unlisten.linger_ms.map(|ms| (ms, state.unlisten.release(channel)))
+        };
+        match linger {
+            Some((ms, generation)) => {
+                let state = state.clone();
+                let channel = channel.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    let _ = crate::retry::sleep(ms).await;
+                    let released = {
+                        let state = state.borrow();
+                        state.unlisten.is_current(&channel, generation) && is_released(&state, &channel)
+                    };
+                    if released {
+                        if let Err(e) = unlisten(&state, &channel).await {
+                            log_warn!("WASM failed to UNLISTEN {}: {:?}", channel, e);
+                        }
+                        crate::subscription_store::changed(&state);
+                    }
+                });
+            }
+            None => unlisten(state, channel).await?,
+        }
+    }
+    crate::subscription_store::changed(state);
+    Ok(())
+}
+
+fn is_released(state: &ClientState, channel: &str) -> bool {
+    state.watches.listening.contains(channel) && crate::unlisten::ref_count(state, channel) == 0
+}
+
+async fn unlisten(state: &SharedState, channel: &str) -> Result<(), JsValue> {
+    state.borrow_mut().watches.listening.remove(channel);
+    state.borrow_mut().listen_states.remove(channel);
+    connection::request(state, "unlisten", "unlisten", json!({ "channel": channel })).await?;
+    Ok(())
+}
+
+// Re-run a watched query and hand the fresh result to its callback
+pub(crate) async fn run(state: SharedState, id: u32) {
+    let start = state.borrow_mut().watches.get_mut(id).is_some_and(|watch| watch.run.begin());
+    if !start {
+        return;
+    }
+
+    loop {
+        let (sql, params) = match state.borrow().watches.watches.get(&id) {
+            Some(watch) => (watch.sql.clone(), watch.params.clone()),
+            None => return,
+        };
+        // Refreshes yield to interactive queries under the concurrency limit
+        let options = connection::QueryOptions { priority: Priority::Background, ..Default::default() };
+        let outcome = connection::execute_query_with(&state, &sql, params, options).await;
+
+        let again = match state.borrow_mut().watches.get_mut(id) {
+            Some(watch) => watch.run.finish(),
+            None => return,
+        };
+        match outcome {
+            Ok(result) => present(&state, id, result),
+            Err(e) => fail(&state, id, crate::errors::message_of(&e)),
+        }
+        if !again {
+            return;
+        }
+    }
+}
+
+// Hand a result to a watch's callback: whole, or as the delta for diffing watches
+pub(crate) fn present(state: &SharedState, id: u32, result: Value) {
+    let (callback, null_policy, delivery) = {
+        let mut state = state.borrow_mut();
+        let Some(watch) = state.watches.get_mut(id) else {
+            return;
+        };
+        watch.last = Some(result.clone());
+        let delivery = match &mut watch.diff {
+            Some(diff_state) => delta(diff_state, &result),
+            None => Ok(Some(result)),
+        };
+        (watch.callback.clone(), watch.null_policy, delivery)
+    };
+    match delivery {
+        Ok(Some(mut result)) => {
+            for key in ["rows", "added", "removed", "changed"] {
+                if let Some(Value::Array(rows)) = result.get_mut(key) {
+                    null_policy.apply_to_rows(rows);
+                }
+            }
+            if let Ok(result) = null_policy.to_js(&result) {
+                if let Err(e) = callback.call1(&JsValue::NULL, &result) {
+                    fail(state, id, format!("callback threw: {}", crate::errors::message_of(&e)));
+                }
+            }
+        }
+        Ok(None) => {}
+        Err(e) => fail(state, id, e),
+    }
+}
+
+// Report a watch's failure to the `on_watch_error` hook, or log it without one
+fn fail(state: &SharedState, id: u32, error: String) {
+    let hook = state.borrow().watches.error_hook.clone();
+    let Some(hook) = hook else {
+        log_warn!("WASM live query {} failed: {}", id, error);
+        return;
+    };
+    let report = json!({ "watchId": id, "error": error });
+    if let Ok(report) = crate::to_js(&report) {
+        let _ = hook.call1(&JsValue::NULL, &report);
+    }
+}
+
+// Diff a fresh result against the previous run; None when nothing changed.
+// The first run always delivers, with every row reported as added.
+fn delta(diff_state: &mut DiffState, result: &Value) -> Result<Option<Value>, String> {
+    let rows = result.get("rows").and_then(|r| r.as_array()).map(Vec::as_slice).unwrap_or_default();
+    let first_run = diff_state.index.is_none();
+    let empty = RowIndex::new();
+    let previous = diff_state.index.as_ref().unwrap_or(&empty);
+    let (diff, index) = diff::diff_rows(previous, rows, &diff_state.key_columns)?;
+    diff_state.index = Some(index);
+    if diff.is_empty() && !first_run {
+        return Ok(None);
+    }
+    serde_json::to_value(&diff).map(Some).map_err(|e| e.to_string())
+}
+
+// Schedule re-runs for every watch depending on `channel`
+pub(crate) fn notify(state: &SharedState, channel: &str) {
+    let ids = state.borrow().watches.affected(channel);
+    for id in ids {
+        // A hidden page's refreshes may wait until it's shown
+        if state.borrow_mut().visibility.defer_refresh(id) {
+            continue;
+        }
+        wasm_bindgen_futures::spawn_local(run(state.clone(), id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_state_coalesces() {
+        let mut run = RunState::default();
+        assert!(run.begin());
+        assert!(!run.begin());
+        assert!(!run.begin());
+        assert!(run.finish());
+        assert!(!run.finish());
+        assert!(run.begin());
+    }
+
+    #[test]
+    fn test_idle_finish_stops() {
+        let mut run = RunState::default();
+        assert!(run.begin());
+        assert!(!run.finish());
+        assert_eq!(run, RunState::default());
+    }
+}
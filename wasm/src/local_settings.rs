@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query_with, QueryOptions, SharedState};
+use crate::sql::{quote_literal, quote_qualified};
+
+// Configuration parameters set for one query, such as `{ work_mem: "256MB" }`
+pub type Settings = BTreeMap<String, Value>;
+
+// A parameter name, possibly with a dotted prefix like `app.tenant_id`
+fn valid_name(name: &str) -> bool {
+    name.split('.').all(|part| {
+        part.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+// Options for the BEGIN, SET LOCAL and COMMIT statements around the query
+fn scoped(transaction: Option<u32>) -> QueryOptions {
+    QueryOptions { retry: false, transaction, ..QueryOptions::default() }
+}
+
+// One `SET LOCAL` per setting, in name order
+pub fn set_local_statements(settings: &Settings) -> Result<Vec<String>, String> {
+    settings
+        .iter()
+        .map(|(name, value)| {
+            if !valid_name(name) {
+                return Err(format!("'{}' is not a configuration parameter name", name));
+            }
+            let value = match value {
+                Value::String(text) => text.clone(),
+                Value::Number(number) => number.to_string(),
+                Value::Bool(flag) => flag.to_string(),
+                _ => return Err(format!("{} must be a string, number or boolean", name)),
+            };
+            if value.contains('\0') {
+                return Err(format!("{} cannot contain NUL characters", name));
+            }
+            Ok(format!("SET LOCAL {} = {}", quote_qualified(name), quote_literal(&value)))
+        })
+        .collect()
+}
+
+// Run a query with `options.settings` applied by SET LOCAL, so they end with the
+// transaction. Outside `with_transaction` the query gets a transaction of its own.
+pub(crate) async fn run(state: &SharedState, sql: &str, params: Option<Vec<Value>>, mut options: QueryOptions) -> Result<Value, JsValue> {
+    let statements = set_local_statements(&options.settings).map_err(|e| JsValue::from_str(&format!("Invalid settings: {}", e)))?;
+    options.settings.clear();
+    // A retry would land in the aborted transaction
+    options.retry = false;
+    if options.transaction.is_some() {
+        for set in &statements {
+            execute_query_with(state, set, None, scoped(options.transaction)).await?;
+        }
+        return execute_query_with(state, sql, params, options).await;
+    }
+
+    let id = crate::transaction::open(state).await;
+    options.transaction = Some(id);
+    let outcome = async {
+        execute_query_with(state, "BEGIN", None, scoped(Some(id))).await?;
+        let result = async {
+            for set in &statements {
+                execute_query_with(state, set, None, scoped(Some(id))).await?;
+            }
+            execute_query_with(state, sql, params, options).await
+        }
+        .await;
+        match result {
+            Ok(result) => execute_query_with(state, "COMMIT", None, scoped(Some(id))).await.map(|_| result),
+            Err(e) => {
+                if let Err(rollback) = execute_query_with(state, "ROLLBACK", None, scoped(Some(id))).await {
+                    log_warn!("WASM rollback after a failed query with settings failed: {:?}", rollback);
+                }
+                Err(e)
+            }
+        }
+    }
+    .await;
+    crate::transaction::close(state, id);
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_set_local_statements() {
+        let settings: Settings = serde_json::from_value(json!({"work_mem": "256MB", "statement_timeout": 5000, "app.tenant": "o'brien"})).unwrap();
+        assert_eq!(
+            set_local_statements(&settings).unwrap(),
+            vec![
+                "SET LOCAL \"app\".\"tenant\" = 'o''brien'",
+                "SET LOCAL \"statement_timeout\" = '5000'",
+                "SET LOCAL \"work_mem\" = '256MB'",
+            ]
+        );
+        let bad: Settings = serde_json::from_value(json!({"work_mem; DROP TABLE x": "1"})).unwrap();
+        assert!(set_local_statements(&bad).is_err());
+        let nested: Settings = serde_json::from_value(json!({"work_mem": {"a": 1}})).unwrap();
+        assert!(set_local_statements(&nested).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use wasm_bindgen::prelude::*;
+
+use crate::WebSocketMessage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    pub fn parse(name: &str) -> Result<LogLevel, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            other => Err(format!(
+                "Unknown log level '{}'. Valid levels: off, error, warn, info, debug, trace",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+
+    fn from_u8(value: u8) -> LogLevel {
+        match value {
+            0 => LogLevel::Off,
+            1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            4 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Warn as u8);
+static REDACT_PARAMS: AtomicBool = AtomicBool::new(true);
+
+pub fn log_level() -> LogLevel {
+    LogLevel::from_u8(LOG_LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level <= log_level()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn console_info(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn console_debug(s: &str);
+}
+
+pub fn write(level: LogLevel, message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    match level {
+        LogLevel::Error => console_error(message),
+        LogLevel::Warn => console_warn(message),
+        LogLevel::Info => console_info(message),
+        LogLevel::Debug | LogLevel::Trace => console_debug(message),
+        LogLevel::Off => {}
+    }
+    // Native builds (unit tests) have no console binding
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = (level, message);
+}
+
+// Render a message for logging, hiding bound parameter values when redaction is on
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+pub fn redact(message: &WebSocketMessage) -> String {
+    if !REDACT_PARAMS.load(Ordering::Relaxed) {
+        return serde_json::to_string(message).unwrap_or_default();
+    }
+    let mut redacted = message.clone();
+    redact_params(&mut redacted.payload);
+    serde_json::to_string(&redacted).unwrap_or_default()
+}
+
+// Replace a payload's bound parameter values with their count
+pub fn redact_params(payload: &mut serde_json::Value) {
+    if let Some(params) = payload.get_mut("params") {
+        if let Some(count) = params.as_array().map(|p| p.len()) {
+            *params = serde_json::Value::String(format!("[{} params redacted]", count));
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn set_log_level(level: &str) -> Result<(), JsValue> {
+    let level = LogLevel::parse(level).map_err(|e| JsValue::from_str(&e))?;
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+#[wasm_bindgen(js_name = get_log_level)]
+pub fn get_log_level() -> String {
+    log_level().as_str().to_string()
+}
+
+// When enabled (the default), logged messages show parameter counts instead of values
+#[wasm_bindgen]
+pub fn set_log_redaction(enabled: bool) {
+    REDACT_PARAMS.store(enabled, Ordering::Relaxed);
+}
+
+macro_rules! log_at {
+    ($level:expr, $($t:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::write($level, &format!($($t)*));
+        }
+    };
+}
+
+macro_rules! log_error {
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Error, $($t)*))
+}
+
+macro_rules! log_warn {
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Warn, $($t)*))
+}
+
+macro_rules! log_info {
+    ($($t:tt)*) => (log_at!($crate::logging::LogLevel::Info, $($t)*))
+}
+
+// Debug and trace logging is compiled out of release builds
+macro_rules! log_debug {
+    ($($t:tt)*) => {
+        #[cfg(debug_assertions)]
+        log_at!($crate::logging::LogLevel::Debug, $($t)*);
+    };
+}
+
+macro_rules! log_trace {
+    ($($t:tt)*) => {
+        #[cfg(debug_assertions)]
+        log_at!($crate::logging::LogLevel::Trace, $($t)*);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_levels() {
+        assert_eq!(LogLevel::parse("DEBUG"), Ok(LogLevel::Debug));
+        assert!(LogLevel::parse("verbose").is_err());
+        assert!(LogLevel::Error < LogLevel::Trace);
+    }
+
+    #[test]
+    fn test_redact_params() {
+        let message = WebSocketMessage {
+            message_type: "query".to_string(),
+            payload: json!({"sql": "SELECT $1", "params": ["secret"]}),
+            id: None,
+        };
+        let logged = redact(&message);
+        assert!(!logged.contains("secret"));
+        assert!(logged.contains("[1 params redacted]"));
+    }
+}
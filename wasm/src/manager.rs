@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::config::WasmClientConfig;
+use crate::bindings::WasmWebSocketClient;
+
+struct Entry {
+    config: WasmClientConfig,
+    // Called with the new client before it connects, to apply its settings
+    configure: Option<js_sys::Function>,
+    // The JS-owned client, once `get` has created it
+    client: Option<JsValue>,
+}
+
+// Named clients ("primary", "analytics", ...) with their own URLs and settings, each
+// created and connected the first time it is asked for
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct ConnectionManager {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl ConnectionManager {
+    fn insert(&mut self, name: &str, config: WasmClientConfig, configure: Option<js_sys::Function>) -> Result<(), String> {
+        if self.entries.contains_key(name) {
+            return Err(format!("Connection '{}' is already registered", name));
+        }
+        self.entries.insert(name.to_string(), Entry { config, configure, client: None });
+        Ok(())
+    }
+
+    fn entry(&mut self, name: &str) -> Result<&mut Entry, String> {
+        self.entries.get_mut(name).ok_or_else(|| format!("Unknown connection '{}'", name))
+    }
+}
+
+// Call a method of a client owned by JS; the manager only holds it as a JsValue
+fn call_method(client: &JsValue, method: &str) -> Result<JsValue, JsValue> {
+    let function: js_sys::Function = js_sys::Reflect::get(client, &JsValue::from_str(method))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str(&format!("Client has no method '{}'", method)))?;
+    function.call0(client)
+}
+
+#[wasm_bindgen]
+impl ConnectionManager {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> ConnectionManager {
+        ConnectionManager::default()
+    }
+
+    // Add a named connection from a URL or a client config object. `configure(client)`,
+    // when given, runs once the client is created and before it connects.
+    #[wasm_bindgen]
+    pub fn register(
+        &mut self,
+        name: &str,
+        #[wasm_bindgen(unchecked_param_type = "string | WasmClientConfig")] config: JsValue,
+        configure: Option<js_sys::Function>,
+    ) -> Result<(), JsValue> {
+        let config = WasmClientConfig::from_js(config).map_err(|e| JsValue::from_str(&e))?;
+        self.insert(name, config, configure).map_err(|e| JsValue::from_str(&e))
+    }
+
+    // The client registered as `name`, created and connected on first use; later calls
+    // return the same instance
+    #[wasm_bindgen]
+    pub fn get(&mut self, name: &str) -> Result<JsValue, JsValue> {
+        let entry = self.entry(name).map_err(|e| JsValue::from_str(&e))?;
+        if let Some(client) = &entry.client {
+            return Ok(client.clone());
+        }
+        let client = JsValue::from(WasmWebSocketClient::with_config(entry.config.clone()));
+        if let Some(configure) = &entry.configure {
+            configure.call1(&JsValue::NULL, &client)?;
+        }
+        call_method(&client, "connect")?;
+        entry.client = Some(client.clone());
+        Ok(client)
+    }
+
+    // Registered names, in order
+    #[wasm_bindgen]
+    pub fn names(&self) -> Vec<String> {
+        self.entries.keys().cloned().collect()
+    }
+
+    // Whether `get(name)` has created the client yet
+    #[wasm_bindgen]
+    pub fn is_open(&self, name: &str) -> bool {
+        self.entries.get(name).is_some_and(|entry| entry.client.is_some())
+    }
+
+    // Disconnect and forget a connection; returns false for an unknown name
+    #[wasm_bindgen]
+    pub fn remove(&mut self, name: &str) -> Result<bool, JsValue> {
+        match self.entries.remove(name) {
+            Some(Entry { client: Some(client), .. }) => call_method(&client, "disconnect").map(|_| true),
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    // Disconnect every client created so far; they reconnect lazily on the next `get`
+    #[wasm_bindgen]
+    pub fn disconnect_all(&mut self) -> Result<(), JsValue> {
+        for entry in self.entries.values_mut() {
+            if let Some(client) = entry.client.take() {
+                call_method(&client, "disconnect")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_lookup() {
+        let mut manager = ConnectionManager::new();
+        let config = WasmClientConfig::from_url;
+        manager.insert("primary", config("ws://db:8080"), None).unwrap();
+        manager.insert("analytics", config("ws://replica:8080"), None).unwrap();
+        assert!(manager.insert("primary", config("ws://other"), None).is_err());
+        assert_eq!(manager.names(), vec!["analytics", "primary"]);
+        assert_eq!(manager.entry("analytics").unwrap().config.url, "ws://replica:8080");
+        assert!(manager.entry("reporting").is_err());
+        assert!(!manager.is_open("primary"));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::Serialize;
+use serde_json::Value;
+use tsify::Tsify;
+
+// Histogram bucket upper bounds in milliseconds
+pub const LATENCY_BUCKETS_MS: [f64; 12] = [1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0];
+
+// Recent samples kept for percentile estimates
+const LATENCY_WINDOW: usize = 1024;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct LatencyBucket {
+    pub le: f64,
+    pub count: u64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct MetricsSnapshot {
+    #[serde(rename = "queriesSent")]
+    pub queries_sent: u64,
+    #[serde(rename = "queriesSucceeded")]
+    pub queries_succeeded: u64,
+    #[serde(rename = "queriesFailed")]
+    pub queries_failed: u64,
+    #[serde(rename = "errorsByClass")]
+    pub errors_by_class: BTreeMap<String, u64>,
+    #[serde(rename = "bytesSent")]
+    pub bytes_sent: u64,
+    #[serde(rename = "bytesReceived")]
+    pub bytes_received: u64,
+    pub p50: Option<f64>,
+    pub p95: Option<f64>,
+    pub p99: Option<f64>,
+    // Cumulative counts, Prometheus style
+    pub histogram: Vec<LatencyBucket>,
+    #[serde(rename = "latencySum")]
+    pub latency_sum: f64,
+    #[serde(rename = "latencyCount")]
+    pub latency_count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryMetrics {
+    queries_sent: u64,
+    queries_succeeded: u64,
+    queries_failed: u64,
+    errors_by_class: BTreeMap<String, u64>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    bucket_counts: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    latency_sum: f64,
+    latency_count: u64,
+    recent: VecDeque<f64>,
+}
+
+impl Default for QueryMetrics {
+    fn default() -> Self {
+        QueryMetrics {
+            queries_sent: 0,
+            queries_succeeded: 0,
+            queries_failed: 0,
+            errors_by_class: BTreeMap::new(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            bucket_counts: [0; LATENCY_BUCKETS_MS.len() + 1],
+            latency_sum: 0.0,
+            latency_count: 0,
+            recent: VecDeque::with_capacity(LATENCY_WINDOW),
+        }
+    }
+}
+
+// Nearest-rank percentile over a sorted slice
+fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl QueryMetrics {
+    pub fn record_sent(&mut self, bytes: usize, is_query: bool) {
+        self.bytes_sent += bytes as u64;
+        if is_query {
+            self.queries_sent += 1;
+        }
+    }
+
+    pub fn record_received(&mut self, bytes: usize) {
+        self.bytes_received += bytes as u64;
+    }
+
+    pub fn record_result(&mut self, payload: &Value) {
+        self.queries_succeeded += 1;
+        if let Some(ms) = payload.get("executionTime").and_then(|t| t.as_f64()) {
+            self.record_latency(ms);
+        }
+    }
+
+    pub fn record_error(&mut self, payload: &Value) {
+        self.queries_failed += 1;
+        let class = payload
+            .get("code")
+            .and_then(|c| c.as_str())
+            .unwrap_or("UNKNOWN")
+            .to_string();
+        *self.errors_by_class.entry(class).or_insert(0) += 1;
+    }
+
+    pub fn record_latency(&mut self, ms: f64) {
+        let bucket = LATENCY_BUCKETS_MS.iter().position(|le| ms <= *le).unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.bucket_counts[bucket] += 1;
+        self.latency_sum += ms;
+        self.latency_count += 1;
+        if self.recent.len() == LATENCY_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ms);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let mut sorted: Vec<f64> = self.recent.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mut cumulative = 0;
+        let mut histogram: Vec<LatencyBucket> = LATENCY_BUCKETS_MS
+            .iter()
+            .zip(self.bucket_counts.iter())
+            .map(|(le, count)| {
+                cumulative += count;
+                LatencyBucket { le: *le, count: cumulative }
+            })
+            .collect();
+        histogram.push(LatencyBucket {
+            le: f64::INFINITY,
+            count: self.latency_count,
+        });
+
+        MetricsSnapshot {
+            queries_sent: self.queries_sent,
+            queries_succeeded: self.queries_succeeded,
+            queries_failed: self.queries_failed,
+            errors_by_class: self.errors_by_class.clone(),
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+            p50: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            histogram,
+            latency_sum: self.latency_sum,
+            latency_count: self.latency_count,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = QueryMetrics::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_percentiles_and_histogram() {
+        let mut metrics = QueryMetrics::default();
+        for ms in 1..=100 {
+            metrics.record_latency(ms as f64);
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.p50, Some(50.0));
+        assert_eq!(snapshot.p95, Some(95.0));
+        assert_eq!(snapshot.p99, Some(99.0));
+        assert_eq!(snapshot.histogram[0].count, 1);
+        assert_eq!(snapshot.histogram[5].count, 100);
+        assert_eq!(snapshot.histogram.last().unwrap().count, 100);
+        assert_eq!(snapshot.latency_sum, 5050.0);
+    }
+
+    #[test]
+    fn test_counters() {
+        let mut metrics = QueryMetrics::default();
+        metrics.record_sent(10, true);
+        metrics.record_sent(5, false);
+        metrics.record_received(7);
+        metrics.record_result(&json!({"executionTime": 3}));
+        metrics.record_error(&json!({"code": "DATABASE_ERROR"}));
+        metrics.record_error(&json!({}));
+        let snapshot = metrics.snapshot();
+        assert_eq!((snapshot.queries_sent, snapshot.bytes_sent, snapshot.bytes_received), (1, 15, 7));
+        assert_eq!(snapshot.queries_succeeded, 1);
+        assert_eq!(snapshot.errors_by_class.get("DATABASE_ERROR"), Some(&1));
+        assert_eq!(snapshot.errors_by_class.get("UNKNOWN"), Some(&1));
+        assert_eq!(metrics.snapshot().p50, Some(3.0));
+    }
+}
@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, run_in_transaction_unless, SharedState, Statement};
+
+// Key for the advisory lock serializing concurrent migration runs
+const MIGRATION_LOCK_KEY: i64 = 0x6272_6964_6765; // "bridge"
+
+const CREATE_MIGRATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS _bridge_migrations (\
+    version BIGINT PRIMARY KEY, \
+    name TEXT NOT NULL, \
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT now())";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub up: String,
+    pub down: Option<String>,
+}
+
+// Ordered set of registered migrations
+#[derive(Debug, Clone, Default)]
+pub struct MigrationSet {
+    migrations: Vec<Migration>,
+}
+
+impl MigrationSet {
+    pub fn register(&mut self, migration: Migration) -> Result<(), String> {
+        if self.migrations.iter().any(|m| m.version == migration.version) {
+            return Err(format!("Migration version {} is already registered", migration.version));
+        }
+        self.migrations.push(migration);
+        self.migrations.sort_by_key(|m| m.version);
+        Ok(())
+    }
+
+    // Migrations not yet recorded as applied, in ascending version order
+    pub fn pending(&self, applied: &[i64]) -> Vec<Migration> {
+        self.migrations
+            .iter()
+            .filter(|m| !applied.contains(&m.version))
+            .cloned()
+            .collect()
+    }
+
+    // The most recently applied migration that is still registered
+    pub fn latest_applied(&self, applied: &[i64]) -> Option<Migration> {
+        self.migrations
+            .iter()
+            .rev()
+            .find(|m| applied.contains(&m.version))
+            .cloned()
+    }
+}
+
+async fn applied_versions(state: &SharedState) -> Result<Vec<i64>, JsValue> {
+    let result = execute_query(state, "SELECT version FROM _bridge_migrations ORDER BY version", None).await?;
+    let rows = result.get("rows").and_then(|r| r.as_array()).cloned().unwrap_or_default();
+    Ok(rows
+        .iter()
+        .filter_map(|row| row.get("version"))
+        .filter_map(|v| v.as_i64().or_else(|| v.as_str().and_then(|s| s.parse().ok())))
+        .collect())
+}
+
+fn lock_statement() -> Statement {
+    ("SELECT pg_advisory_xact_lock($1)".to_string(), Some(vec![MIGRATION_LOCK_KEY.into()]))
+}
+
+// Checked again under the lock: another client may have got there first
+fn applied_statement(version: i64) -> Statement {
+    ("SELECT 1 FROM _bridge_migrations WHERE version = $1".to_string(), Some(vec![version.into()]))
+}
+
+fn reverted_statement(version: i64) -> Statement {
+    ("SELECT 1 WHERE NOT EXISTS (SELECT 1 FROM _bridge_migrations WHERE version = $1)".to_string(), Some(vec![version.into()]))
+}
+
+// Apply every pending migration, each in its own locked transaction. Every statement of a
+// transaction has to reach the same backend, so this needs the Rust bridge, or a bridge
+// that keeps one connection per client.
+pub(crate) async fn migrate_up(state: SharedState, set: MigrationSet) -> Result<Vec<i64>, JsValue> {
+    execute_query(&state, CREATE_MIGRATIONS_TABLE, None).await?;
+    let applied = applied_versions(&state).await?;
+
+    let mut newly_applied = Vec::new();
+    for migration in set.pending(&applied) {
+        log_info!("WASM applying migration {} ({})", migration.version, migration.name);
+        let ran = run_in_transaction_unless(&state, vec![lock_statement(), applied_statement(migration.version)], vec![
+            (migration.up.clone(), None),
+            (
+                "INSERT INTO _bridge_migrations (version, name) VALUES ($1, $2)".to_string(),
+                Some(vec![migration.version.into(), migration.name.clone().into()]),
+            ),
+        ])
+        .await?;
+        if ran {
+            newly_applied.push(migration.version);
+        } else {
+            log_info!("WASM migration {} was applied by another client meanwhile", migration.version);
+        }
+    }
+    Ok(newly_applied)
+}
+
+// Revert the latest applied migration, returning its version if one was reverted
+pub(crate) async fn migrate_down(state: SharedState, set: MigrationSet) -> Result<Option<i64>, JsValue> {
+    execute_query(&state, CREATE_MIGRATIONS_TABLE, None).await?;
+    let applied = applied_versions(&state).await?;
+
+    let migration = match set.latest_applied(&applied) {
+        Some(m) => m,
+        None => return Ok(None),
+    };
+    let down = migration.down.clone().ok_or_else(|| {
+        JsValue::from_str(&format!("Migration {} has no down script", migration.version))
+    })?;
+
+    log_info!("WASM reverting migration {} ({})", migration.version, migration.name);
+    let ran = run_in_transaction_unless(&state, vec![lock_statement(), reverted_statement(migration.version)], vec![
+        (down, None),
+        (
+            "DELETE FROM _bridge_migrations WHERE version = $1".to_string(),
+            Some(vec![migration.version.into()]),
+        ),
+    ])
+    .await?;
+    if !ran {
+        log_info!("WASM migration {} was reverted by another client meanwhile", migration.version);
+    }
+    Ok(ran.then_some(migration.version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migration(version: i64) -> Migration {
+        Migration {
+            version,
+            name: format!("m{}", version),
+            up: "SELECT 1".to_string(),
+            down: None,
+        }
+    }
+
+    #[test]
+    fn test_register_orders_and_rejects_duplicates() {
+        let mut set = MigrationSet::default();
+        set.register(migration(3)).unwrap();
+        set.register(migration(1)).unwrap();
+        assert!(set.register(migration(3)).is_err());
+
+        let versions: Vec<i64> = set.pending(&[]).iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_pending_and_latest_applied() {
+        let mut set = MigrationSet::default();
+        for v in [1, 2, 3] {
+            set.register(migration(v)).unwrap();
+        }
+        let pending: Vec<i64> = set.pending(&[1, 2]).iter().map(|m| m.version).collect();
+        assert_eq!(pending, vec![3]);
+        assert_eq!(set.latest_applied(&[1, 2]).map(|m| m.version), Some(2));
+        assert_eq!(set.latest_applied(&[]), None);
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::connection::SharedState;
+use crate::notifications::{ChannelType, NotificationOptions};
+
+// Fields of a definition that hold functions; see `take_functions`
+const FUNCTION_FIELDS: [&str; 2] = ["handler", "decode"];
+
+// What a subscription's handler gets after a reconnect, which notifications may have
+// been missed across
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayPolicy {
+    // Nothing
+    #[default]
+    None,
+    // The last notification it received before the connection dropped, again
+    Last,
+    // `{ channel, payload: null, resync: true }`, to reload what it tracks
+    Resync,
+}
+
+// Functions only ever come from the config object, never through serde: `from_js` takes
+// them out first and they serialize as null
+mod function_field {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(_: &Option<js_sys::Function>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_none()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<js_sys::Function>, D::Error> {
+        serde::de::IgnoredAny::deserialize(deserializer).map(|_| None)
+    }
+}
+
+// A subscription declared in the client config's `subscriptions`, registered when the
+// client is constructed and LISTENed on every connect
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionDefinition {
+    pub name: String,
+    pub channel: String,
+    // As in `set_channel_type`: the payload is JSON-parsed and checked against `schema`
+    // when either is given, then passed through `decode`
+    #[serde(default)]
+    #[tsify(type = "Value", optional)]
+    pub schema: Option<Value>,
+    #[serde(default, with = "function_field")]
+    #[tsify(type = "(payload: any) => any", optional)]
+    pub decode: Option<js_sys::Function>,
+    #[serde(default, with = "function_field")]
+    #[tsify(type = "(notification: any) => void")]
+    pub handler: Option<js_sys::Function>,
+    // As in `on_notification`'s options
+    #[serde(default, rename = "batchMs")]
+    pub batch_ms: Option<f64>,
+    #[serde(default, rename = "debounceMs")]
+    pub debounce_ms: Option<f64>,
+    #[serde(default)]
+    pub replay: ReplayPolicy,
+}
+
+impl SubscriptionDefinition {
+    pub fn options(&self) -> NotificationOptions {
+        NotificationOptions { batch_ms: self.batch_ms, debounce_ms: self.debounce_ms }
+    }
+
+    pub fn channel_type(&self) -> Option<ChannelType> {
+        if self.schema.is_none() && self.decode.is_none() {
+            return None;
+        }
+        Some(ChannelType { schema: self.schema.clone(), decode: self.decode.clone() })
+    }
+}
+
+// Check a config's definitions, naming the one at fault
+pub fn validate(definitions: &[SubscriptionDefinition]) -> Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    for definition in definitions {
+        if definition.name.trim().is_empty() {
+            return Err("every subscription needs a name".to_string());
+        }
+        let invalid = |message: &str| Err(format!("subscription '{}' {}", definition.name, message));
+        if !names.insert(definition.name.as_str()) {
+            return invalid("is defined twice");
+        }
+        if definition.channel.trim().is_empty() {
+            return invalid("needs a channel");
+        }
+        if definition.schema.as_ref().is_some_and(|schema| !schema.is_object()) {
+            return invalid("has a schema that is not an object");
+        }
+        if let Err(e) = definition.options().validate() {
+            return Err(format!("subscription '{}': {}", definition.name, e));
+        }
+        if definition.handler.is_none() {
+            return invalid("needs a handler function");
+        }
+    }
+    Ok(())
+}
+
+// A shallow copy of a config object whose `subscriptions` entries no longer hold the
+// functions serde can't carry, and those functions for each entry in order
+pub fn take_functions(config: &JsValue) -> (JsValue, Vec<HashMap<&'static str, js_sys::Function>>) {
+    let Some(object) = config.dyn_ref::<js_sys::Object>() else {
+        return (config.clone(), Vec::new());
+    };
+    let key = JsValue::from_str("subscriptions");
+    let Some(entries) = js_sys::Reflect::get(object, &key).ok().and_then(|v| v.dyn_into::<js_sys::Array>().ok()) else {
+        return (config.clone(), Vec::new());
+    };
+    let mut functions = Vec::new();
+    let stripped = js_sys::Array::new();
+    for entry in entries.iter() {
+        let mut found = HashMap::new();
+        if let Some(entry_object) = entry.dyn_ref::<js_sys::Object>() {
+            let copy = js_sys::Object::assign(&js_sys::Object::new(), entry_object);
+            for field in FUNCTION_FIELDS {
+                let name = JsValue::from_str(field);
+                if let Some(function) = js_sys::Reflect::get(&copy, &name).ok().and_then(|v| v.dyn_into::<js_sys::Function>().ok()) {
+                    let _ = js_sys::Reflect::delete_property(&copy, &name);
+                    found.insert(field, function);
+                }
+            }
+            stripped.push(&copy);
+        } else {
+            stripped.push(&entry);
+        }
+        functions.push(found);
+    }
+    let copy = js_sys::Object::assign(&js_sys::Object::new(), object);
+    let _ = js_sys::Reflect::set(&copy, &key, &stripped);
+    (copy.into(), functions)
+}
+
+struct NamedSubscription {
+    // The `on_notification` subscription it was registered as
+    id: u32,
+    channel: String,
+    replay: ReplayPolicy,
+    last: Option<Value>,
+}
+
+#[derive(Default)]
+pub(crate) struct NamedSubscriptions {
+    subscriptions: BTreeMap<String, NamedSubscription>,
+    // Set once the first connection is set up; later ones are reconnects
+    connected: bool,
+}
+
+impl NamedSubscriptions {
+    pub fn add(&mut self, name: &str, id: u32, channel: &str, replay: ReplayPolicy) {
+        let subscription = NamedSubscription { id, channel: channel.to_string(), replay, last: None };
+        self.subscriptions.insert(name.to_string(), subscription);
+    }
+
+    pub fn ids(&self) -> BTreeMap<String, u32> {
+        self.subscriptions.iter().map(|(name, s)| (name.clone(), s.id)).collect()
+    }
+
+    // Keep the notification for subscriptions on `channel` that replay their last one
+    pub fn received(&mut self, channel: &str, notification: &Value) {
+        for subscription in self.subscriptions.values_mut().filter(|s| s.channel == channel && s.replay == ReplayPolicy::Last) {
+            subscription.last = Some(notification.clone());
+        }
+    }
+
+    // Channels of those still registered, which every connect LISTENs on
+    pub fn channels(&self, is_registered: impl Fn(u32) -> bool) -> Vec<String> {
+        let mut channels: Vec<String> = self.subscriptions.values().filter(|s| is_registered(s.id)).map(|s| s.channel.clone()).collect();
+        channels.sort();
+        channels.dedup();
+        channels
+    }
+}
+
+// Register every definition in a config with the client's notification callbacks
+pub(crate) fn register(state: &mut crate::connection::ClientState, definitions: &[SubscriptionDefinition]) {
+    for definition in definitions {
+        let Some(handler) = definition.handler.clone() else {
+            continue;
+        };
+        if let Some(channel_type) = definition.channel_type() {
+            state.notifications.types.insert(definition.channel.clone(), channel_type);
+        }
+        let id = state.notifications.add(&definition.channel, handler, definition.options());
+        state.named_subscriptions.add(&definition.name, id, &definition.channel, definition.replay);
+    }
+}
+
+// After a connection's channels are LISTENed again, replay to each subscription what
+// its policy asks for. The first connect has nothing to catch up on.
+pub(crate) fn reconnected(state: &SharedState) {
+    let replays: Vec<(u32, String, ReplayPolicy, Option<Value>)> = {
+        let mut state = state.borrow_mut();
+        if !std::mem::replace(&mut state.named_subscriptions.connected, true) {
+            return;
+        }
+        state.named_subscriptions.subscriptions.values().map(|s| (s.id, s.channel.clone(), s.replay, s.last.clone())).collect()
+    };
+    for (id, channel, replay, last) in replays {
+        match (replay, last) {
+            (ReplayPolicy::Last, Some(notification)) => crate::notifications::redeliver(state, id, &notification),
+            (ReplayPolicy::Resync, _) => {
+                let callback = state.borrow().notifications.callback(id);
+                if let Some(callback) = callback {
+                    let signal = json!({ "channel": channel, "payload": null, "resync": true });
+                    if let Ok(signal) = crate::to_js(&signal) {
+                        let _ = callback.call1(&JsValue::NULL, &signal);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_definitions_are_validated_and_replay_the_last_notification() {
+        let definitions: Vec<SubscriptionDefinition> = serde_json::from_value(json!([
+            { "name": "orders", "channel": "orders", "schema": { "type": "object" }, "debounceMs": 100, "replay": "last" }
+        ]))
+        .unwrap();
+        assert_eq!(definitions[0].replay, ReplayPolicy::Last);
+        assert_eq!(definitions[0].options().debounce_ms, Some(100.0));
+        // A handler only comes from a JS config object
+        assert_eq!(validate(&definitions).unwrap_err(), "subscription 'orders' needs a handler function");
+        let no_channel = SubscriptionDefinition { channel: String::new(), ..definitions[0].clone() };
+        assert_eq!(validate(&[no_channel]).unwrap_err(), "subscription 'orders' needs a channel");
+        assert!(serde_json::from_value::<SubscriptionDefinition>(json!({ "name": "a", "channel": "a", "replay": "all" })).is_err());
+
+        let mut named = NamedSubscriptions::default();
+        named.add("orders", 1, "orders", ReplayPolicy::Last);
+        named.add("audit", 2, "audit", ReplayPolicy::None);
+        named.received("orders", &json!({ "channel": "orders", "payload": "1" }));
+        named.received("audit", &json!({ "channel": "audit", "payload": "2" }));
+        assert_eq!(named.subscriptions["orders"].last, Some(json!({ "channel": "orders", "payload": "1" })));
+        assert_eq!(named.subscriptions["audit"].last, None);
+        assert_eq!(named.channels(|id| id != 2), vec!["orders".to_string()]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+
+// Options accepted by `on_notification`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationOptions {
+    // Deliver at most once per window, as an array of everything received in it
+    #[serde(rename = "batchMs")]
+    pub batch_ms: Option<f64>,
+    // Deliver only the latest notification, once none has arrived for this long
+    #[serde(rename = "debounceMs")]
+    pub debounce_ms: Option<f64>,
+}
+
+impl NotificationOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("batchMs", self.batch_ms), ("debounceMs", self.debounce_ms)] {
+            if value.is_some_and(|ms| !(ms.is_finite() && ms > 0.0)) {
+                return Err(format!("{} must be a positive number of milliseconds", name));
+            }
+        }
+        if self.batch_ms.is_some() && self.debounce_ms.is_some() {
+            return Err("batchMs and debounceMs can't be combined".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Delivery<T> {
+    // Unbatched: hand this notification over right away
+    Now(T),
+    // First of a window: flush the batch after this many ms
+    Flush(f64),
+    // Joins the batch already waiting
+    Buffered,
+    // Debounced: deliver this one after this many ms unless push `generation` is
+    // superseded first
+    Settle(f64, u32),
+}
+
+#[derive(Debug)]
+pub struct Batch<T> {
+    batch_ms: Option<f64>,
+    debounce_ms: Option<f64>,
+    buffer: Vec<T>,
+    generation: u32,
+}
+
+impl<T> Batch<T> {
+    pub fn new(options: NotificationOptions) -> Batch<T> {
+        Batch { batch_ms: options.batch_ms, debounce_ms: options.debounce_ms, buffer: Vec::new(), generation: 0 }
+    }
+
+    pub fn push(&mut self, notification: T) -> Delivery<T> {
+        if let Some(debounce_ms) = self.debounce_ms {
+            self.generation = self.generation.wrapping_add(1);
+            self.buffer = vec![notification];
+            return Delivery::Settle(debounce_ms, self.generation);
+        }
+        let Some(batch_ms) = self.batch_ms else {
+            return Delivery::Now(notification);
+        };
+        self.buffer.push(notification);
+        if self.buffer.len() == 1 {
+            Delivery::Flush(batch_ms)
+        } else {
+            Delivery::Buffered
+        }
+    }
+
+    pub fn flush(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    // The debounced notification, if nothing newer arrived since push `generation`
+    pub fn settle(&mut self, generation: u32) -> Option<T> {
+        if generation != self.generation {
+            return None;
+        }
+        self.buffer.pop()
+    }
+}
+
+struct Subscription {
+    channel: String,
+    callback: js_sys::Function,
+    batch: Batch<JsValue>,
+}
+
+// How a typed channel's payloads are parsed: as JSON, checked against `schema` and then
+// passed through `decode`
+#[derive(Clone)]
+pub(crate) struct ChannelType {
+    pub schema: Option<Value>,
+    pub decode: Option<js_sys::Function>,
+}
+
+// Callbacks registered with `on_notification`, keyed by subscription id
+#[derive(Default)]
+pub(crate) struct NotificationSubscriptions {
+    subscriptions: HashMap<u32, Subscription>,
+    next_id: u32,
+    pub types: HashMap<String, ChannelType>,
+    // Hears about payloads a typed channel rejected
+    pub error_hook: Option<js_sys::Function>,
+}
+
+impl NotificationSubscriptions {
+    pub fn add(&mut self, channel: &str, callback: js_sys::Function, options: NotificationOptions) -> u32 {
+        self.next_id += 1;
+        let subscription = Subscription { channel: channel.to_string(), callback, batch: Batch::new(options) };
+        self.subscriptions.insert(self.next_id, subscription);
+        self.next_id
+    }
+
+    // Returns the channel the subscription was on
+    pub fn remove(&mut self, id: u32) -> Option<String> {
+        self.subscriptions.remove(&id).map(|subscription| subscription.channel)
+    }
+
+    pub fn callback(&self, id: u32) -> Option<js_sys::Function> {
+        self.subscriptions.get(&id).map(|s| s.callback.clone())
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.subscriptions.contains_key(&id)
+    }
+
+    pub fn is_used(&self, channel: &str) -> bool {
+        self.subscriptions.values().any(|s| s.channel == channel)
+    }
+
+    pub fn count(&self, channel: &str) -> usize {
+        self.subscriptions.values().filter(|s| s.channel == channel).count()
+    }
+
+    pub fn channels(&self) -> Vec<String> {
+        self.subscriptions.values().map(|s| s.channel.clone()).collect()
+    }
+}
+
+// JSON-parse and validate a typed channel's raw payload
+pub fn parse_payload(schema: Option<&Value>, raw: &str) -> Result<Value, String> {
+    let payload: Value = serde_json::from_str(raw).map_err(|e| format!("payload is not valid JSON: {}", e))?;
+    match schema {
+        Some(schema) => crate::json_schema::validate(schema, &payload).map(|()| payload),
+        None => Ok(payload),
+    }
+}
+
+// The `{ channel, payload }` object handed to callbacks, its payload typed when the
+// channel has a type
+fn typed(state: &SharedState, channel: &str, notification: &Value) -> Result<JsValue, String> {
+    let channel_type = state.borrow().notifications.types.get(channel).cloned();
+    let Some(channel_type) = channel_type else {
+        return crate::to_js(notification).map_err(|e| format!("{:?}", e));
+    };
+    let raw = notification.get("payload").and_then(|p| p.as_str()).unwrap_or_default();
+    let payload = parse_payload(channel_type.schema.as_ref(), raw)?;
+    let mut payload = crate::to_js(&payload).map_err(|e| format!("{:?}", e))?;
+    if let Some(decode) = &channel_type.decode {
+        payload = decode.call1(&JsValue::NULL, &payload).map_err(|e| format!("decoder threw: {}", e.as_string().unwrap_or_else(|| format!("{:?}", e))))?;
+    }
+    let object = js_sys::Object::new();
+    let _ = js_sys::Reflect::set(&object, &JsValue::from_str("channel"), &JsValue::from_str(channel));
+    let _ = js_sys::Reflect::set(&object, &JsValue::from_str("payload"), &payload);
+    Ok(object.into())
+}
+
+// Report a payload a typed channel rejected; it reaches no callback
+fn reject(state: &SharedState, notification: &Value, error: String) {
+    let hook = state.borrow().notifications.error_hook.clone();
+    let channel = notification.get("channel").cloned().unwrap_or_default();
+    let Some(hook) = hook else {
+        log_warn!("WASM dropped notification on {}: {}", channel, error);
+        return;
+    };
+    let report = serde_json::json!({ "channel": channel, "payload": notification.get("payload"), "error": error });
+    if let Ok(report) = crate::to_js(&report) {
+        let _ = hook.call1(&JsValue::NULL, &report);
+    }
+}
+
+// Pass a `notification` message's payload to the callbacks on its channel, or to
+// their batches
+pub(crate) fn deliver(state: &SharedState, notification: &Value) {
+    let Some(channel) = notification.get("channel").and_then(|c| c.as_str()) else {
+        return;
+    };
+    if !state.borrow().notifications.is_used(channel) {
+        // A channel restored from the saved registry keeps its notifications for the
+        // callback the app registers again
+        state.borrow_mut().subscriptions.hold(channel, notification);
+        return;
+    }
+    state.borrow_mut().named_subscriptions.received(channel, notification);
+    dispatch(state, channel, notification, None);
+}
+
+// Pass a notification received earlier to subscription `id` alone
+pub(crate) fn redeliver(state: &SharedState, id: u32, notification: &Value) {
+    if let Some(channel) = notification.get("channel").and_then(|c| c.as_str()) {
+        dispatch(state, channel, notification, Some(id));
+    }
+}
+
+fn dispatch(state: &SharedState, channel: &str, notification: &Value, only: Option<u32>) {
+    let notification = match typed(state, channel, notification) {
+        Ok(typed) => typed,
+        Err(error) => return reject(state, notification, error),
+    };
+    let mut immediate = Vec::new();
+    let mut flushes = Vec::new();
+    let mut settles = Vec::new();
+    {
+        let mut state = state.borrow_mut();
+        let targets = state.notifications.subscriptions.iter_mut().filter(|(id, s)| s.channel == channel && only.is_none_or(|only| only == **id));
+        for (id, subscription) in targets {
+            match subscription.batch.push(notification.clone()) {
+                Delivery::Now(notification) => immediate.push((subscription.callback.clone(), notification)),
+                Delivery::Flush(delay) => flushes.push((*id, delay)),
+                Delivery::Settle(delay, generation) => settles.push((*id, delay, generation)),
+                Delivery::Buffered => {}
+            }
+        }
+    }
+    for (callback, notification) in immediate {
+        let _ = callback.call1(&JsValue::NULL, &notification);
+    }
+    for (id, delay) in flushes {
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = crate::retry::sleep(delay).await;
+            let (callback, batch) = {
+                let mut state = state.borrow_mut();
+                let Some(subscription) = state.notifications.subscriptions.get_mut(&id) else {
+                    return;
+                };
+                (subscription.callback.clone(), subscription.batch.flush())
+            };
+            let batch: js_sys::Array = batch.into_iter().collect();
+            let _ = callback.call1(&JsValue::NULL, &batch);
+        });
+    }
+    for (id, delay, generation) in settles {
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = crate::retry::sleep(delay).await;
+            let settled = {
+                let mut state = state.borrow_mut();
+                let Some(subscription) = state.notifications.subscriptions.get_mut(&id) else {
+                    return;
+                };
+                subscription.batch.settle(generation).map(|notification| (subscription.callback.clone(), notification))
+            };
+            if let Some((callback, notification)) = settled {
+                let _ = callback.call1(&JsValue::NULL, &notification);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batch_coalesces_within_window() {
+        let mut unbatched = Batch::new(NotificationOptions::default());
+        assert_eq!(unbatched.push(json!(1)), Delivery::Now(json!(1)));
+
+        let mut batch = Batch::new(NotificationOptions { batch_ms: Some(100.0), ..NotificationOptions::default() });
+        assert_eq!(batch.push(json!(1)), Delivery::Flush(100.0));
+        assert_eq!(batch.push(json!(2)), Delivery::Buffered);
+        assert_eq!(batch.push(json!(3)), Delivery::Buffered);
+        assert_eq!(batch.flush(), vec![json!(1), json!(2), json!(3)]);
+        // The next notification opens a new window
+        assert_eq!(batch.push(json!(4)), Delivery::Flush(100.0));
+
+        let mut debounced = Batch::new(NotificationOptions { debounce_ms: Some(50.0), ..NotificationOptions::default() });
+        assert_eq!(debounced.push(json!(1)), Delivery::Settle(50.0, 1));
+        assert_eq!(debounced.push(json!(2)), Delivery::Settle(50.0, 2));
+        assert_eq!(debounced.settle(1), None);
+        assert_eq!(debounced.settle(2), Some(json!(2)));
+
+        assert!(NotificationOptions { batch_ms: Some(0.0), ..NotificationOptions::default() }.validate().is_err());
+        assert!(NotificationOptions { batch_ms: Some(10.0), debounce_ms: Some(10.0) }.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_payload() {
+        let schema = json!({ "type": "object", "required": ["id"] });
+        assert_eq!(parse_payload(Some(&schema), r#"{"id": 3}"#).unwrap(), json!({"id": 3}));
+        assert_eq!(parse_payload(Some(&schema), "{}").unwrap_err(), "payload.id: is required");
+        assert!(parse_payload(None, "row 3 changed").unwrap_err().starts_with("payload is not valid JSON"));
+    }
+}
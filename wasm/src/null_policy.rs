@@ -0,0 +1,102 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+// How NULL cells in result rows are presented to JavaScript
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NullPolicy {
+    #[default]
+    Null,
+    Undefined,
+    Omit,
+}
+
+impl NullPolicy {
+    pub fn parse(name: &str) -> Result<NullPolicy, String> {
+        match name {
+            "null" => Ok(NullPolicy::Null),
+            "undefined" => Ok(NullPolicy::Undefined),
+            "omit" => Ok(NullPolicy::Omit),
+            other => Err(format!(
+                "Unknown null policy '{}'. Valid policies: null, undefined, omit",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NullPolicy::Null => "null",
+            NullPolicy::Undefined => "undefined",
+            NullPolicy::Omit => "omit",
+        }
+    }
+
+    // Remove NULL-valued keys from row objects when the policy asks for it
+    pub fn apply_to_rows(&self, rows: &mut [serde_json::Value]) {
+        if *self != NullPolicy::Omit {
+            return;
+        }
+        for row in rows.iter_mut() {
+            if let serde_json::Value::Object(map) = row {
+                map.retain(|_, value| !value.is_null());
+            }
+        }
+    }
+
+    // Convert a value to JS, mapping JSON null according to the policy
+    pub fn to_js<T: Serialize>(&self, value: &T) -> Result<JsValue, JsValue> {
+        let serializer = match self {
+            NullPolicy::Undefined => {
+                serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true)
+            }
+            NullPolicy::Null | NullPolicy::Omit => serde_wasm_bindgen::Serializer::json_compatible(),
+        };
+        value
+            .serialize(&serializer)
+            .map_err(|e| JsValue::from_str(&format!("Failed to convert value: {}", e)))
+    }
+}
+
+// Convert a JS parameter array into JSON values, rejecting `undefined` in strict mode
+pub fn params_from_js(params: &js_sys::Array, strict: bool) -> Result<Vec<serde_json::Value>, JsValue> {
+    params.iter().enumerate().map(|(index, param)| param_from_js(index, param, strict)).collect()
+}
+
+// One parameter of `params_from_js`, at zero-based `index`
+pub fn param_from_js(index: usize, param: JsValue, strict: bool) -> Result<serde_json::Value, JsValue> {
+    if param.is_undefined() {
+        if strict {
+            return Err(JsValue::from_str(&format!(
+                "Parameter ${} is undefined (strict parameter mode is enabled)",
+                index + 1
+            )));
+        }
+        return Ok(serde_json::Value::Null);
+    }
+    serde_wasm_bindgen::from_value(param).map_err(|e| JsValue::from_str(&format!("Invalid parameter ${}: {}", index + 1, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(NullPolicy::parse("null"), Ok(NullPolicy::Null));
+        assert_eq!(NullPolicy::parse("undefined"), Ok(NullPolicy::Undefined));
+        assert_eq!(NullPolicy::parse("omit"), Ok(NullPolicy::Omit));
+        assert!(NullPolicy::parse("NULL").is_err());
+    }
+
+    #[test]
+    fn test_omit_removes_null_keys() {
+        let mut rows = vec![json!({"id": 1, "name": null}), json!({"id": 2, "name": "a"})];
+        NullPolicy::Omit.apply_to_rows(&mut rows);
+        assert_eq!(rows, vec![json!({"id": 1}), json!({"id": 2, "name": "a"})]);
+
+        let mut rows = vec![json!({"id": 1, "name": null})];
+        NullPolicy::Null.apply_to_rows(&mut rows);
+        assert_eq!(rows, vec![json!({"id": 1, "name": null})]);
+    }
+}
@@ -0,0 +1,109 @@
+use js_sys::Promise;
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::connection::SharedState;
+use crate::export::{ExportOptions, ExportSummary};
+
+// What `export_to_opfs` resolves with
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OpfsExport {
+    #[serde(flatten)]
+    pub summary: ExportSummary,
+    pub file: String,
+}
+
+// OPFS names are single path components
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!("'{}' is not a valid OPFS file name", name));
+    }
+    Ok(())
+}
+
+// Call `target.method(...args)` and wait for the promise it returns
+async fn call(target: &JsValue, method: &str, args: &[JsValue]) -> Result<JsValue, JsValue> {
+    let function: js_sys::Function = js_sys::Reflect::get(target, &JsValue::from_str(method))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str(&format!("{} is not available here", method)))?;
+    let args: js_sys::Array = args.iter().collect();
+    let promise: Promise = function.apply(target, &args)?.dyn_into()?;
+    JsFuture::from(promise).await
+}
+
+// The origin's private directory, from `navigator.storage.getDirectory()`
+async fn root() -> Result<JsValue, JsValue> {
+    let storage = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("navigator"))
+        .and_then(|navigator| js_sys::Reflect::get(&navigator, &JsValue::from_str("storage")))
+        .ok()
+        .filter(|storage| !storage.is_undefined())
+        .ok_or_else(|| JsValue::from_str("The Origin Private File System is not available here"))?;
+    call(&storage, "getDirectory", &[]).await
+}
+
+async fn file_handle(name: &str, create: bool) -> Result<JsValue, JsValue> {
+    check_name(name).map_err(|e| JsValue::from_str(&e))?;
+    let options = js_sys::Object::new();
+    js_sys::Reflect::set(&options, &JsValue::from_str("create"), &JsValue::from_bool(create))?;
+    call(&root().await?, "getFileHandle", &[JsValue::from_str(name), options.into()]).await
+}
+
+async fn remove(name: &str) -> Result<bool, JsValue> {
+    check_name(name).map_err(|e| JsValue::from_str(&e))?;
+    match call(&root().await?, "removeEntry", &[JsValue::from_str(name)]).await {
+        Ok(_) => Ok(true),
+        Err(e) if js_sys::Reflect::get(&e, &JsValue::from_str("name")).ok().and_then(|n| n.as_string()).as_deref() == Some("NotFoundError") => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Stream the rows of `sql` into OPFS file `name`, replacing what it held. The file only
+// takes the new contents once every row is written; a failed export removes it.
+pub(crate) async fn export(state: SharedState, sql: String, params: Option<Vec<Value>>, name: String, options: ExportOptions) -> Result<OpfsExport, JsValue> {
+    let handle = file_handle(&name, true).await?;
+    let stream: web_sys::WritableStream = call(&handle, "createWritable", &[]).await?.dyn_into()?;
+    match crate::export::stream_to(state, sql, params, stream, options).await {
+        Ok(summary) => Ok(OpfsExport { summary, file: name }),
+        Err(e) => {
+            if let Err(removed) = remove(&name).await {
+                log_warn!("WASM failed to remove OPFS file '{}' after a failed export: {:?}", name, removed);
+            }
+            Err(e)
+        }
+    }
+}
+
+// The OPFS file `name` as a `File`, e.g. for `URL.createObjectURL` to offer it as a
+// download. Works on the main thread for a file a Worker exported.
+#[wasm_bindgen(unchecked_return_type = "Promise<File>")]
+pub fn opfs_file(name: String) -> Promise {
+    future_to_promise(async move {
+        let handle = file_handle(&name, false).await?;
+        call(&handle, "getFile", &[]).await
+    })
+}
+
+// Delete the OPFS file `name`; resolves to whether there was one
+#[wasm_bindgen(unchecked_return_type = "Promise<boolean>")]
+pub fn remove_opfs_file(name: String) -> Promise {
+    future_to_promise(async move { remove(&name).await.map(JsValue::from_bool) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_are_single_components() {
+        assert!(check_name("events-2024.csv").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("..").is_err());
+        assert!(check_name("exports/events.csv").is_err());
+
+        let export = OpfsExport { summary: ExportSummary { rows: 2, batches: 1, bytes: 10 }, file: "a.jsonl".to_string() };
+        assert_eq!(serde_json::to_value(export).unwrap(), serde_json::json!({ "rows": 2, "batches": 1, "bytes": 10, "file": "a.jsonl" }));
+    }
+}
@@ -0,0 +1,168 @@
+use bridge_protocol::sql::{tokenize, Token};
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+use crate::diff::row_key;
+
+// The local change `mutate` expects its statement to make to one table's rows
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct OptimisticPatch {
+    pub table: String,
+    // Columns identifying a row
+    pub key: Vec<String>,
+    // Rows to insert, or to merge into the row with the same key
+    #[serde(default)]
+    pub upsert: Vec<Value>,
+    // Rows (or just their keys) to remove
+    #[serde(default)]
+    pub delete: Vec<Value>,
+}
+
+impl OptimisticPatch {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.table.trim().is_empty() {
+            return Err("table must not be empty".to_string());
+        }
+        if self.key.is_empty() {
+            return Err("key must name at least one column".to_string());
+        }
+        if self.upsert.iter().chain(&self.delete).any(|row| row_key(row, &self.key).is_none()) {
+            return Err(format!("every patched row needs the key column(s) {}", self.key.join(", ")));
+        }
+        Ok(())
+    }
+
+    // Whether `sql` reads the patched table, by its unqualified name
+    pub fn touches(&self, sql: &str) -> bool {
+        let table = self.table.rsplit('.').next().unwrap_or_default().to_lowercase();
+        tokenize(sql).iter().any(|token| matches!(token, Token::Word(word) if *word == table))
+    }
+
+    // Patch a result payload's rows in place; false when nothing changed
+    pub fn apply(&self, result: &mut Value) -> bool {
+        let Some(Value::Array(rows)) = result.get_mut("rows") else {
+            return false;
+        };
+        let before = rows.clone();
+        let deleted: Vec<String> = self.delete.iter().filter_map(|row| row_key(row, &self.key)).collect();
+        rows.retain(|row| row_key(row, &self.key).is_none_or(|key| !deleted.contains(&key)));
+        for patch in &self.upsert {
+            let key = row_key(patch, &self.key);
+            match rows.iter_mut().find(|row| row_key(row, &self.key) == key) {
+                Some(Value::Object(row)) => {
+                    if let Value::Object(fields) = patch {
+                        row.extend(fields.clone());
+                    }
+                }
+                Some(row) => *row = patch.clone(),
+                None => rows.push(patch.clone()),
+            }
+        }
+        let (changed, count) = (*rows != before, rows.len());
+        if let Some(row_count) = result.get_mut("rowCount") {
+            *row_count = Value::from(count);
+        }
+        changed
+    }
+}
+
+// What the patch replaced, to put back if the statement fails
+struct Snapshot {
+    cache: Vec<(String, Value)>,
+    watches: Vec<(u32, Value)>,
+}
+
+// Apply `patch` to cached results and live query results reading its table, delivering
+// the patched results to the live queries' callbacks
+fn patch_local(state: &SharedState, patch: &OptimisticPatch) -> Snapshot {
+    let (cache, patched) = {
+        let mut state = state.borrow_mut();
+        let cache = state.query_cache.patch(|sql| patch.touches(sql), |payload| patch.apply(payload));
+        let patched: Vec<(u32, Value, Value)> = state
+            .watches
+            .last_results(|sql| patch.touches(sql))
+            .into_iter()
+            .filter_map(|(id, last)| {
+                let mut next = last.clone();
+                patch.apply(&mut next).then_some((id, last, next))
+            })
+            .collect();
+        (cache, patched)
+    };
+    let mut watches = Vec::new();
+    for (id, last, next) in patched {
+        crate::live::present(state, id, next);
+        watches.push((id, last));
+    }
+    Snapshot { cache, watches }
+}
+
+fn roll_back(state: &SharedState, snapshot: Snapshot) {
+    state.borrow_mut().query_cache.restore(snapshot.cache);
+    for (id, last) in snapshot.watches {
+        crate::live::present(state, id, last);
+    }
+}
+
+// Run `sql` with its expected effect applied locally first. Rows it RETURNs replace the
+// optimistic ones; a failure rolls the patch back. Patched live queries re-run either way.
+pub(crate) async fn mutate(state: SharedState, sql: String, params: Option<Vec<Value>>, patch: OptimisticPatch) -> Result<Value, JsValue> {
+    let snapshot = patch_local(&state, &patch);
+    let watches: Vec<u32> = snapshot.watches.iter().map(|(id, _)| *id).collect();
+    let outcome = execute_query(&state, &sql, params).await;
+    match &outcome {
+        Ok(result) => {
+            let returned = result.get("rows").and_then(|rows| rows.as_array()).cloned().unwrap_or_default();
+            if !returned.is_empty() && returned.iter().all(|row| row_key(row, &patch.key).is_some()) {
+                let server = OptimisticPatch { upsert: returned, delete: Vec::new(), ..patch.clone() };
+                patch_local(&state, &server);
+            }
+        }
+        Err(e) => {
+            log_info!("WASM rolling back optimistic patch of {}: {:?}", patch.table, e);
+            roll_back(&state, snapshot);
+        }
+    }
+    for id in watches {
+        wasm_bindgen_futures::spawn_local(crate::live::run(state.clone(), id));
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn patch(upsert: Vec<Value>, delete: Vec<Value>) -> OptimisticPatch {
+        OptimisticPatch { table: "public.todos".to_string(), key: vec!["id".to_string()], upsert, delete }
+    }
+
+    #[test]
+    fn test_apply_upserts_and_deletes() {
+        let mut result = json!({"rows": [{"id": 1, "title": "a", "done": false}, {"id": 2, "title": "b", "done": false}], "rowCount": 2});
+        let change = patch(vec![json!({"id": 1, "done": true}), json!({"id": 3, "title": "c", "done": false})], vec![json!({"id": 2})]);
+        assert!(change.validate().is_ok());
+        assert!(change.apply(&mut result));
+        assert_eq!(
+            result,
+            json!({"rows": [{"id": 1, "title": "a", "done": true}, {"id": 3, "title": "c", "done": false}], "rowCount": 2})
+        );
+        // Applying it again changes nothing
+        assert!(!change.apply(&mut result));
+        assert!(patch(vec![json!({"title": "no key"})], Vec::new()).validate().is_err());
+    }
+
+    #[test]
+    fn test_touches_by_table_name() {
+        let change = patch(Vec::new(), Vec::new());
+        assert!(change.touches("SELECT * FROM todos WHERE done = false"));
+        assert!(change.touches("select t.* from public.TODOS t"));
+        assert!(!change.touches("SELECT * FROM todos_archive"));
+        assert!(!change.touches("SELECT 'todos' AS label"));
+    }
+}
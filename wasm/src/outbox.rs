@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::conflict::{self, ConflictCheck, Resolution};
+use crate::connection::{self, SharedState};
+use crate::idb;
+
+// A write issued while offline, replayed with its idempotency key once reconnected
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+    #[serde(rename = "idempotencyKey")]
+    pub key: String,
+    pub sql: String,
+    pub params: Option<Vec<Value>>,
+    #[serde(rename = "queuedAt")]
+    pub queued_at: f64,
+    // The request id it is replayed under, when `send_query` returned one to the caller
+    #[serde(rename = "messageId", default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    // Sync writes that check the server's row before they are pushed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<ConflictCheck>,
+}
+
+// Opt-in queue of offline writes, optionally mirrored to IndexedDB
+#[derive(Debug, Default)]
+pub(crate) struct Outbox {
+    pub enabled: bool,
+    pub persistent: bool,
+    entries: VecDeque<OutboxEntry>,
+    counter: u64,
+    pub replaying: bool,
+}
+
+// Statements that only read are never queued; a WITH query counts as a write when one of
+// its parts changes data
+pub fn is_write(sql: &str) -> bool {
+    let keyword = sql
+        .trim_start()
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ';')
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    !matches!(keyword.as_str(), "" | "select" | "with" | "show" | "explain" | "values" | "table") || crate::limit_guard::modifies_data(sql)
+}
+
+impl Outbox {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> Vec<OutboxEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    // Keys sort in queue order: millisecond timestamp, then a per-client sequence number
+    pub fn next_key(&mut self, now: f64, random: u32) -> String {
+        self.counter += 1;
+        format!("idem_{:013}_{:020}_{:08x}", now as u64, self.counter, random)
+    }
+
+    pub fn push(&mut self, entry: OutboxEntry) {
+        self.entries.push_back(entry);
+    }
+
+    // Merge entries restored from storage, keeping key order and skipping duplicates
+    pub fn restore(&mut self, restored: Vec<OutboxEntry>) {
+        for entry in restored {
+            if !self.entries.iter().any(|e| e.key == entry.key) {
+                self.entries.push_back(entry);
+            }
+        }
+        self.entries.make_contiguous().sort_by(|a, b| a.key.cmp(&b.key));
+    }
+
+    pub fn front(&self) -> Option<OutboxEntry> {
+        self.entries.front().cloned()
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.retain(|e| e.key != key);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// Queue a write in memory; a persistent outbox still has to store it. Stacked
+// statements are refused here, as on a live send, unless `multi_statement`.
+pub(crate) fn queue(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    conflict: Option<ConflictCheck>,
+    message_id: Option<String>,
+    multi_statement: bool,
+) -> Result<OutboxEntry, JsValue> {
+    let mut state = state.borrow_mut();
+    state.check_statements(sql, multi_statement)?;
+    let now = js_sys::Date::now();
+    let key = state.outbox.next_key(now, (js_sys::Math::random() * u32::MAX as f64) as u32);
+    let entry = OutboxEntry { key, sql: sql.to_string(), params, queued_at: now, conflict, message_id };
+    state.outbox.push(entry.clone());
+    log_debug!("WASM queued offline write {}", entry.key);
+    Ok(entry)
+}
+
+// Queue a write while disconnected and resolve with its idempotency key once it is
+// stored. A write that can't be stored is taken off the queue again.
+pub(crate) async fn enqueue(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    conflict: Option<ConflictCheck>,
+    multi_statement: bool,
+) -> Result<String, JsValue> {
+    let entry = queue(state, sql, params, conflict, None, multi_statement)?;
+    if state.borrow().outbox.persistent {
+        if let Err(e) = persist(&entry).await {
+            state.borrow_mut().outbox.remove(&entry.key);
+            return Err(e);
+        }
+    }
+    Ok(entry.key)
+}
+
+// Store an entry queued by `queue` without waiting for it
+pub(crate) fn persist_later(state: &SharedState, entry: OutboxEntry) {
+    if !state.borrow().outbox.persistent {
+        return;
+    }
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = persist(&entry).await {
+            log_warn!("WASM failed to persist outbox entry {}: {:?}", entry.key, e);
+        }
+    });
+}
+
+async fn persist(entry: &OutboxEntry) -> Result<(), JsValue> {
+    let json = serde_json::to_string(entry)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize outbox entry: {}", e)))?;
+    idb::put(idb::OUTBOX_STORE, &entry.key, &json).await
+}
+
+// Load entries left over from a previous session; resolves with the queue length
+pub(crate) async fn load_persisted(state: &SharedState) -> Result<usize, JsValue> {
+    let restored = idb::get_all(idb::OUTBOX_STORE)
+        .await?
+        .iter()
+        .filter_map(|json| serde_json::from_str::<OutboxEntry>(json).ok())
+        .collect();
+    let mut state = state.borrow_mut();
+    state.outbox.restore(restored);
+    Ok(state.outbox.len())
+}
+
+pub(crate) async fn clear_persisted() -> Result<(), JsValue> {
+    idb::clear(idb::OUTBOX_STORE).await
+}
+
+// Send one entry with its idempotency key, settling any conflict with the server's row first
+async fn push(state: &SharedState, entry: &OutboxEntry) -> Result<(), JsValue> {
+    let (sql, params) = match &entry.conflict {
+        None => (entry.sql.clone(), entry.params.clone()),
+        Some(check) => match conflict::resolve(state, entry, check).await? {
+            Resolution::Push => (entry.sql.clone(), entry.params.clone()),
+            Resolution::Write(merged) => (merged.sql, Some(merged.params)),
+            Resolution::Drop => {
+                log_info!("WASM dropping outbox entry {} in favour of the server's row", entry.key);
+                return Ok(());
+            }
+        },
+    };
+    let mut payload = state.borrow().query_payload(&sql, params, None)?;
+    payload["idempotencyKey"] = Value::String(entry.key.clone());
+    connection::request_as(state, entry.message_id.as_deref(), "query", "query", payload).await.map(|_| ())
+}
+
+// Replay queued writes in order. Stops if the connection drops again; a write the
+// server rejects is dropped so it cannot block the rest of the queue.
+pub(crate) async fn replay(state: SharedState) -> Result<usize, JsValue> {
+    {
+        let mut state = state.borrow_mut();
+        if state.outbox.replaying {
+            return Ok(0);
+        }
+        state.outbox.replaying = true;
+    }
+
+    let mut replayed = 0;
+    let result = loop {
+        let (entry, persistent) = {
+            let state = state.borrow();
+            if !state.is_connected() {
+                break Ok(replayed);
+            }
+            match state.outbox.front() {
+                Some(entry) => (entry, state.outbox.persistent),
+                None => break Ok(replayed),
+            }
+        };
+
+        match push(&state, &entry).await {
+            Ok(_) => replayed += 1,
+            Err(e) if !state.borrow().is_connected() => {
+                log_info!("WASM outbox replay paused, connection lost: {:?}", e);
+                break Ok(replayed);
+            }
+            Err(e) => log_warn!("WASM dropping outbox entry {} rejected by server: {:?}", entry.key, e),
+        }
+
+        state.borrow_mut().outbox.remove(&entry.key);
+        if persistent {
+            if let Err(e) = idb::delete(idb::OUTBOX_STORE, &entry.key).await {
+                log_warn!("WASM failed to remove persisted outbox entry {}: {:?}", entry.key, e);
+            }
+        }
+    };
+
+    state.borrow_mut().outbox.replaying = false;
+    if let Ok(count) = result {
+        if count > 0 {
+            log_info!("WASM replayed {} offline writes", count);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str) -> OutboxEntry {
+        OutboxEntry { key: key.to_string(), sql: "INSERT INTO t VALUES (1)".to_string(), params: None, queued_at: 0.0, conflict: None, message_id: None }
+    }
+
+    #[test]
+    fn test_is_write() {
+        assert!(is_write("INSERT INTO t VALUES (1)"));
+        assert!(is_write("  update t set a = 1"));
+        assert!(is_write("DELETE FROM t"));
+        assert!(!is_write("SELECT 1"));
+        assert!(!is_write("with x as (select 1) select * from x"));
+        assert!(is_write("WITH gone AS (DELETE FROM t RETURNING id) SELECT count(*) FROM gone"));
+        assert!(!is_write("SELECT * FROM t FOR UPDATE"));
+        assert!(!is_write("(SELECT 1)"));
+        assert!(!is_write("   "));
+    }
+
+    #[test]
+    fn test_keys_order_and_restore() {
+        let mut outbox = Outbox::default();
+        let first = outbox.next_key(1_700_000_000_000.0, 0xffff_ffff);
+        let second = outbox.next_key(1_700_000_000_000.0, 0);
+        assert!(first < second);
+        outbox.counter = 999_999;
+        assert!(outbox.next_key(1_700_000_000_000.0, 0) < outbox.next_key(1_700_000_000_000.0, 0));
+
+        outbox.push(entry(&second));
+        outbox.restore(vec![entry(&first), entry(&second)]);
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.front().unwrap().key, first);
+
+        outbox.remove(&first);
+        assert_eq!(outbox.entries(), vec![entry(&second)]);
+    }
+}
@@ -0,0 +1,178 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+use crate::hashing::hash_hex;
+use crate::sql::quote_ident;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+// Options accepted by `paginate`
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Tsify)]
+#[serde(default)]
+pub struct PageOptions {
+    #[serde(rename = "pageSize")]
+    pub page_size: Option<u32>,
+    // `nextCursor` of the previous page
+    pub after: Option<String>,
+    // Keyset columns; together they must be unique and non-null. Empty falls back to OFFSET.
+    #[serde(rename = "orderBy")]
+    pub order_by: Vec<String>,
+    pub descending: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Page {
+    pub rows: Vec<Value>,
+    #[serde(rename = "hasNext")]
+    pub has_next: bool,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
+}
+
+// What a cursor token holds: the query it was issued for and where the next page starts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Cursor {
+    query: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    key: Option<Vec<Value>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    offset: Option<u64>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(token: &str) -> Result<Cursor, String> {
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| "Invalid page cursor".to_string())?;
+        serde_json::from_slice(&bytes).map_err(|_| "Invalid page cursor".to_string())
+    }
+}
+
+// Identifies the query, parameters and ordering, so a cursor can't be used with another
+fn fingerprint(sql: &str, params: &[Value], options: &PageOptions) -> String {
+    let text = json!([sql, params, options.order_by, options.descending]).to_string();
+    hash_hex(text.as_bytes())
+}
+
+// A query paged with `options`, ready to run and turn into a `Page`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageQuery {
+    pub sql: String,
+    pub params: Vec<Value>,
+    page_size: u32,
+    fingerprint: String,
+    offset: u64,
+}
+
+impl PageQuery {
+    // Wrap `sql` as a subquery and add the keyset condition (or OFFSET), ordering and a
+    // LIMIT one past the page size so the extra row reveals whether there is a next page
+    pub fn new(sql: &str, params: Vec<Value>, options: &PageOptions) -> Result<PageQuery, String> {
+        let page_size = options.page_size.filter(|size| *size > 0).unwrap_or(DEFAULT_PAGE_SIZE);
+        let fingerprint = fingerprint(sql, &params, options);
+        let cursor = options.after.as_deref().map(Cursor::decode).transpose()?;
+        if cursor.as_ref().is_some_and(|cursor| cursor.query != fingerprint) {
+            return Err("Page cursor was issued for a different query, parameters or ordering".to_string());
+        }
+
+        let inner = sql.trim().trim_end_matches(';');
+        let mut params = params;
+        let mut paged = format!("SELECT * FROM ({}) AS page", inner);
+        let mut offset = 0;
+        if options.order_by.is_empty() {
+            offset = cursor.and_then(|cursor| cursor.offset).unwrap_or(0);
+            paged.push_str(&format!(" LIMIT {} OFFSET {}", page_size + 1, offset));
+        } else {
+            let columns: Vec<String> = options.order_by.iter().map(|c| quote_ident(c)).collect();
+            if let Some(key) = cursor.and_then(|cursor| cursor.key) {
+                if key.len() != columns.len() {
+                    return Err("Page cursor does not match the orderBy columns".to_string());
+                }
+                let placeholders: Vec<String> = key.iter().enumerate().map(|(i, _)| format!("${}", params.len() + i + 1)).collect();
+                let operator = if options.descending { "<" } else { ">" };
+                paged.push_str(&format!(" WHERE ({}) {} ({})", columns.join(", "), operator, placeholders.join(", ")));
+                params.extend(key);
+            }
+            let direction = if options.descending { " DESC" } else { "" };
+            let order: Vec<String> = columns.iter().map(|c| format!("{}{}", c, direction)).collect();
+            paged.push_str(&format!(" ORDER BY {} LIMIT {}", order.join(", "), page_size + 1));
+        }
+        Ok(PageQuery { sql: paged, params, page_size, fingerprint, offset })
+    }
+
+    // Trim the look-ahead row and issue the cursor for the page after this one
+    pub fn finish(&self, mut rows: Vec<Value>, options: &PageOptions) -> Result<Page, String> {
+        let has_next = rows.len() > self.page_size as usize;
+        rows.truncate(self.page_size as usize);
+        let next_cursor = match rows.last() {
+            Some(last) if has_next => {
+                let mut cursor = Cursor { query: self.fingerprint.clone(), key: None, offset: None };
+                if options.order_by.is_empty() {
+                    cursor.offset = Some(self.offset + rows.len() as u64);
+                } else {
+                    let key = options
+                        .order_by
+                        .iter()
+                        .map(|column| last.get(column).cloned().ok_or_else(|| format!("Rows have no orderBy column \"{}\"", column)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    cursor.key = Some(key);
+                }
+                Some(cursor.encode())
+            }
+            _ => None,
+        };
+        Ok(Page { rows, has_next, next_cursor })
+    }
+}
+
+// Run one page of `sql`
+pub(crate) async fn paginate(state: &SharedState, sql: &str, params: Vec<Value>, options: PageOptions) -> Result<Page, JsValue> {
+    let query = PageQuery::new(sql, params, &options).map_err(|e| JsValue::from_str(&e))?;
+    let result = execute_query(state, &query.sql, Some(query.params.clone())).await?;
+    let rows = result.get("rows").and_then(|rows| rows.as_array()).cloned().unwrap_or_default();
+    query.finish(rows, &options).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyset_pages() {
+        let options = PageOptions { page_size: Some(2), order_by: vec!["created_at".into(), "id".into()], ..Default::default() };
+        let first = PageQuery::new("SELECT * FROM posts WHERE author = $1;", vec![json!(7)], &options).unwrap();
+        assert_eq!(first.sql, r#"SELECT * FROM (SELECT * FROM posts WHERE author = $1) AS page ORDER BY "created_at", "id" LIMIT 3"#);
+
+        let rows = vec![json!({"id": 1, "created_at": "a"}), json!({"id": 2, "created_at": "b"}), json!({"id": 3, "created_at": "c"})];
+        let page = first.finish(rows, &options).unwrap();
+        assert!(page.has_next);
+        assert_eq!(page.rows.len(), 2);
+
+        let next = PageOptions { after: page.next_cursor, ..options.clone() };
+        let second = PageQuery::new("SELECT * FROM posts WHERE author = $1;", vec![json!(7)], &next).unwrap();
+        assert!(second.sql.contains(r#"WHERE ("created_at", "id") > ($2, $3) ORDER BY"#));
+        assert_eq!(second.params, vec![json!(7), json!("b"), json!(2)]);
+
+        // The cursor only fits the query it came from
+        assert!(PageQuery::new("SELECT * FROM posts WHERE author = $1", vec![json!(8)], &next).is_err());
+    }
+
+    #[test]
+    fn test_offset_fallback() {
+        let options = PageOptions { page_size: Some(2), ..Default::default() };
+        let first = PageQuery::new("SELECT 1", Vec::new(), &options).unwrap();
+        let page = first.finish(vec![json!({}), json!({}), json!({})], &options).unwrap();
+        let next = PageOptions { after: page.next_cursor, ..options.clone() };
+        assert_eq!(PageQuery::new("SELECT 1", Vec::new(), &next).unwrap().sql, "SELECT * FROM (SELECT 1) AS page LIMIT 3 OFFSET 2");
+
+        let last = first.finish(vec![json!({})], &options).unwrap();
+        assert!(!last.has_next && last.next_cursor.is_none());
+    }
+}
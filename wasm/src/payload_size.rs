@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use bridge_protocol::{message_type, WebSocketMessage};
+use serde::Serialize;
+use serde_json::Value;
+use tsify::Tsify;
+
+use crate::connection::SharedState;
+
+// Messages larger than this fire a `largepayload` event unless configured otherwise
+pub const DEFAULT_WARN_BYTES: usize = 1024 * 1024;
+
+// How much of the SQL a warning quotes
+const SQL_EXCERPT: usize = 200;
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Tsify)]
+pub struct SizeStats {
+    pub count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: u64,
+    #[serde(rename = "avgBytes")]
+    pub avg_bytes: f64,
+}
+
+impl SizeStats {
+    fn record(&mut self, bytes: usize) {
+        self.count += 1;
+        self.total_bytes += bytes as u64;
+        self.max_bytes = self.max_bytes.max(bytes as u64);
+        self.avg_bytes = self.total_bytes as f64 / self.count as f64;
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct PayloadStats {
+    // Every outbound message, serialized, before compression and encryption
+    pub messages: SizeStats,
+    // Bound parameter sets of queries that had any
+    pub params: SizeStats,
+    #[serde(rename = "byType")]
+    pub by_type: BTreeMap<String, SizeStats>,
+    // Messages that crossed the warning threshold
+    pub warnings: u64,
+    #[serde(rename = "warnBytes")]
+    pub warn_bytes: Option<usize>,
+}
+
+// Detail of the `largepayload` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PayloadWarning {
+    pub id: Option<String>,
+    #[serde(rename = "messageType")]
+    pub message_type: String,
+    pub bytes: usize,
+    #[serde(rename = "paramsBytes")]
+    pub params_bytes: Option<usize>,
+    // Position and size of the biggest bound parameter, usually the culprit
+    #[serde(rename = "largestParam")]
+    pub largest_param: Option<(usize, usize)>,
+    pub sql: Option<String>,
+    pub threshold: usize,
+}
+
+// Outbound payload sizes, and warnings waiting to be emitted once the state is released
+#[derive(Debug, Clone)]
+pub struct PayloadSizes {
+    warn_bytes: Option<usize>,
+    messages: SizeStats,
+    params: SizeStats,
+    by_type: BTreeMap<String, SizeStats>,
+    warnings: u64,
+    unreported: Vec<PayloadWarning>,
+}
+
+impl Default for PayloadSizes {
+    fn default() -> Self {
+        PayloadSizes {
+            warn_bytes: Some(DEFAULT_WARN_BYTES),
+            messages: SizeStats::default(),
+            params: SizeStats::default(),
+            by_type: BTreeMap::new(),
+            warnings: 0,
+            unreported: Vec::new(),
+        }
+    }
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_string(value).map(|s| s.len()).unwrap_or_default()
+}
+
+impl PayloadSizes {
+    pub fn set_warn_bytes(&mut self, bytes: Option<usize>) {
+        self.warn_bytes = bytes;
+    }
+
+    // Account for one message of `bytes` serialized bytes, queueing a warning when it is
+    // over the threshold
+    pub fn observe(&mut self, message: &WebSocketMessage, bytes: usize) {
+        self.messages.record(bytes);
+        self.by_type.entry(message.message_type.clone()).or_default().record(bytes);
+        let params = match message.payload.get("params") {
+            Some(Value::Array(params)) if message.message_type == message_type::QUERY => Some(params),
+            _ => None,
+        };
+        let params_bytes = params.map(|params| serialized_len(&Value::Array(params.clone())));
+        if let Some(params_bytes) = params_bytes {
+            self.params.record(params_bytes);
+        }
+
+        let Some(threshold) = self.warn_bytes.filter(|threshold| bytes > *threshold) else {
+            return;
+        };
+        self.warnings += 1;
+        let largest_param = params.and_then(|params| {
+            params.iter().map(serialized_len).enumerate().max_by_key(|(index, size)| (*size, std::cmp::Reverse(*index)))
+        });
+        let sql = message.payload.get("sql").and_then(|sql| sql.as_str()).map(|sql| sql.chars().take(SQL_EXCERPT).collect());
+        self.unreported.push(PayloadWarning {
+            id: message.id.clone(),
+            message_type: message.message_type.clone(),
+            bytes,
+            params_bytes,
+            largest_param,
+            sql,
+            threshold,
+        });
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<PayloadWarning> {
+        std::mem::take(&mut self.unreported)
+    }
+
+    pub fn snapshot(&self) -> PayloadStats {
+        PayloadStats {
+            messages: self.messages.clone(),
+            params: self.params.clone(),
+            by_type: self.by_type.clone(),
+            warnings: self.warnings,
+            warn_bytes: self.warn_bytes,
+        }
+    }
+
+    // Clear the counters, keeping the threshold
+    pub fn reset(&mut self) {
+        *self = PayloadSizes { warn_bytes: self.warn_bytes, ..PayloadSizes::default() };
+    }
+}
+
+// Fire `largepayload` for messages sent since the last call; runs once the caller has
+// released the state, since `send_message` holds it
+pub(crate) fn report(state: &SharedState) {
+    let warnings = state.borrow_mut().payload_sizes.take_warnings();
+    for warning in warnings {
+        log_warn!("WASM sent a {} byte {} message, above the {} byte warning threshold", warning.bytes, warning.message_type, warning.threshold);
+        crate::events::emit(state, "largepayload", &warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(message_type: &str, payload: Value) -> WebSocketMessage {
+        WebSocketMessage { message_type: message_type.to_string(), payload, id: Some("m1".to_string()) }
+    }
+
+    #[test]
+    fn test_sizes_and_warnings() {
+        let mut sizes = PayloadSizes::default();
+        sizes.set_warn_bytes(Some(100));
+        sizes.observe(&message("ping", json!({})), 30);
+        sizes.observe(&message("query", json!({"sql": "SELECT $1", "params": [1]})), 50);
+        assert!(sizes.take_warnings().is_empty());
+
+        let blob = "x".repeat(80);
+        sizes.observe(&message("query", json!({"sql": "INSERT INTO t VALUES ($1, $2)", "params": [1, blob]})), 160);
+        let warnings = sizes.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].bytes, warnings[0].params_bytes, warnings[0].largest_param), (160, Some(86), Some((1, 82))));
+        assert!(sizes.take_warnings().is_empty());
+
+        let stats = sizes.snapshot();
+        assert_eq!((stats.messages.count, stats.messages.max_bytes, stats.messages.avg_bytes), (3, 160, 80.0));
+        assert_eq!((stats.params.count, stats.params.max_bytes), (2, 86));
+        assert_eq!(stats.by_type["query"].total_bytes, 210);
+        assert_eq!(stats.warnings, 1);
+
+        sizes.reset();
+        assert_eq!((sizes.snapshot().messages.count, sizes.snapshot().warn_bytes), (0, Some(100)));
+    }
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::sql_validate::{lex, Lex};
+
+// How `query()` SQL marks its parameters
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaceholderStyle {
+    // Postgres' own `$1`, `$2`, ...; sent unchanged
+    #[default]
+    Dollar,
+    // `?` in order, as MySQL and SQLite drivers write them
+    Question,
+}
+
+// Rewrite `?` placeholders to `$1..$n` in order. Literals, quoted identifiers, comments and
+// dollar-quoted bodies are left alone, and `??` stands for a literal `?`, which is how the
+// jsonb `?`, `?|` and `?&` operators are written.
+pub fn translate(sql: &str) -> Result<String, String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let lexemes = lex(&chars, false).map_err(|(message, _)| format!("Cannot translate placeholders: {}", message))?;
+    // Character index of each `?`, and whether it becomes a parameter or stays a `?`
+    let mut marks = Vec::new();
+    let mut dollar_params = false;
+    let mut index = 0;
+    while index < lexemes.len() {
+        match &lexemes[index] {
+            (Lex::Punct('?'), at) => {
+                let doubled = matches!(lexemes.get(index + 1), Some((Lex::Punct('?'), next)) if *next == at + 1);
+                marks.push((*at, !doubled));
+                if doubled {
+                    index += 1;
+                }
+            }
+            (Lex::Value(value), _) if value.starts_with('$') && value[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                dollar_params = true;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    if dollar_params && marks.iter().any(|(_, param)| *param) {
+        return Err("SQL mixes ? and $n placeholders".to_string());
+    }
+
+    let mut translated = String::with_capacity(sql.len());
+    let mut next = 1;
+    let mut marks = marks.into_iter().peekable();
+    let mut i = 0;
+    while i < chars.len() {
+        match marks.next_if(|(at, _)| *at == i) {
+            Some((_, true)) => {
+                translated.push_str(&format!("${}", next));
+                next += 1;
+            }
+            Some((_, false)) => {
+                translated.push('?');
+                // Skip the second `?` of the pair
+                i += 1;
+            }
+            None => translated.push(chars[i]),
+        }
+        i += 1;
+    }
+    Ok(translated)
+}
+
+#[wasm_bindgen(js_name = translate_placeholders)]
+pub fn translate_js(sql: &str) -> Result<String, JsValue> {
+    translate(sql).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_skips_quoted_sections() {
+        assert_eq!(translate("SELECT * FROM t WHERE a = ? AND b IN (?, ?)").unwrap(), "SELECT * FROM t WHERE a = $1 AND b IN ($2, $3)");
+        assert_eq!(
+            translate("SELECT '?', \"x?\", $$ ? $$, $fn$ ? $fn$ -- ?\n/* ? */, ?").unwrap(),
+            "SELECT '?', \"x?\", $$ ? $$, $fn$ ? $fn$ -- ?\n/* ? */, $1"
+        );
+        assert_eq!(translate("SELECT doc ?? 'key', doc ??| ?").unwrap(), "SELECT doc ? 'key', doc ?| $1");
+        assert_eq!(translate("SELECT ? ?").unwrap(), "SELECT $1 $2");
+        assert_eq!(translate("SELECT $1").unwrap(), "SELECT $1");
+        assert!(translate("SELECT $1, ?").is_err());
+        assert!(translate("SELECT 'open, ?").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use bridge_protocol::{message_type, DescribePayload};
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+use crate::errors::BridgeError;
+
+// How long `prewarm` waits for the connection when the config sets no connect timeout
+const DEFAULT_READY_MS: f64 = 10_000.0;
+const POLL_MS: f64 = 25.0;
+
+// A statement `prewarm` prepares ahead of its first run
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct HotStatement {
+    pub sql: String,
+    // As in `query()`'s `types` option
+    #[serde(default)]
+    pub types: Option<Vec<Option<String>>>,
+}
+
+impl HotStatement {
+    pub fn describe_payload(&self) -> DescribePayload {
+        DescribePayload { sql: self.sql.clone(), types: self.types.clone(), keep: true }
+    }
+}
+
+// What `prewarm` resolves with
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct PrewarmSummary {
+    // From the call until the connection was open, authenticated and set up
+    #[serde(rename = "readyMs")]
+    pub ready_ms: f64,
+    pub prepared: usize,
+    // SQL of hot statements the bridge failed to prepare
+    pub failed: Vec<String>,
+}
+
+// Wait until the connection is open and its setup (handshake, auth, session settings,
+// setup SQL) has finished
+async fn wait_until_ready(state: &SharedState, timeout_ms: f64) -> Result<(), JsValue> {
+    let deadline = js_sys::Date::now() + timeout_ms;
+    loop {
+        {
+            let state = state.borrow();
+            if let Some(reason) = &state.protocol.refused {
+                return Err(BridgeError::connection(reason).into());
+            }
+            if state.is_connected() && state.session.restored {
+                return Ok(());
+            }
+        }
+        if js_sys::Date::now() >= deadline {
+            return Err(BridgeError::connection("Timed out waiting for the connection to be ready").into());
+        }
+        crate::retry::sleep(POLL_MS).await?;
+    }
+}
+
+// Once the connection `connect` (or an idle resume) opened is ready, prepare the hot
+// statements so the first real queries skip that round trip
+pub(crate) async fn prewarm(state: &SharedState) -> Result<PrewarmSummary, JsValue> {
+    let started = js_sys::Date::now();
+    crate::idle::resume(state).await?;
+    let timeout_ms = state.borrow().timeouts.connect_ms.unwrap_or(DEFAULT_READY_MS);
+    wait_until_ready(state, timeout_ms).await?;
+    let mut summary = PrewarmSummary { ready_ms: js_sys::Date::now() - started, ..PrewarmSummary::default() };
+
+    let statements = state.borrow().hot_statements.clone();
+    for statement in statements {
+        let payload = bridge_protocol::to_value(&statement.describe_payload());
+        match connection::request(state, "describe", message_type::DESCRIBE, payload).await {
+            Ok(_) => summary.prepared += 1,
+            Err(e) => {
+                log_warn!("WASM failed to prepare hot statement {}: {:?}", statement.sql, e);
+                summary.failed.push(statement.sql);
+            }
+        }
+    }
+    log_info!("WASM prewarmed in {:.0}ms with {} statements prepared", summary.ready_ms, summary.prepared);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hot_statements_are_kept() {
+        let statement: HotStatement = serde_json::from_str(r#"{"sql": "SELECT * FROM orders WHERE id = $1", "types": ["int8"]}"#).unwrap();
+        assert_eq!(
+            bridge_protocol::to_value(&statement.describe_payload()),
+            serde_json::json!({ "sql": "SELECT * FROM orders WHERE id = $1", "types": ["int8"], "keep": true })
+        );
+        let summary = PrewarmSummary { ready_ms: 42.0, prepared: 1, failed: vec!["SELECT nope".to_string()] };
+        assert_eq!(serde_json::to_value(summary).unwrap(), serde_json::json!({ "readyMs": 42.0, "prepared": 1, "failed": ["SELECT nope"] }));
+    }
+}
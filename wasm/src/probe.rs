@@ -0,0 +1,152 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use bridge_protocol::message_type;
+use js_sys::Promise;
+use serde::Serialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+use crate::config::WasmClientConfig;
+use crate::connection::{self, ClientState, SharedState};
+
+// Each stage's limit unless `timeouts.connectMs` / `timeouts.requestMs` say otherwise
+pub const PROBE_TIMEOUT_MS: f64 = 10_000.0;
+const PROBE_POLL_MS: f64 = 10.0;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeStage {
+    Connect,
+    Handshake,
+    Auth,
+    Query,
+}
+
+// What `probe` found. Stages that didn't run, because an earlier one failed or there was
+// no `auth` to send, have no timing.
+#[derive(Serialize, Debug, Clone, PartialEq, Default, Tsify)]
+pub struct ProbeResult {
+    pub ok: bool,
+    // The stage that failed
+    #[serde(rename = "failedStage", skip_serializing_if = "Option::is_none")]
+    pub failed_stage: Option<ProbeStage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(rename = "connectMs")]
+    pub connect_ms: Option<f64>,
+    #[serde(rename = "handshakeMs")]
+    pub handshake_ms: Option<f64>,
+    #[serde(rename = "authMs")]
+    pub auth_ms: Option<f64>,
+    #[serde(rename = "queryMs")]
+    pub query_ms: Option<f64>,
+    #[serde(rename = "totalMs")]
+    pub total_ms: f64,
+    // Negotiated protocol version; 0 for a bridge that predates `hello`
+    #[serde(rename = "protocolVersion")]
+    pub protocol_version: Option<u32>,
+}
+
+impl ProbeResult {
+    fn fail(&mut self, stage: ProbeStage, error: String) {
+        self.failed_stage = Some(stage);
+        self.error = Some(error);
+    }
+}
+
+// Check every `PROBE_POLL_MS` until `done` holds; false once `timeout_ms` runs out
+async fn wait_for(state: &SharedState, timeout_ms: f64, done: impl Fn(&ClientState) -> bool) -> Result<bool, JsValue> {
+    let deadline = js_sys::Date::now() + timeout_ms;
+    while !done(&state.borrow()) {
+        if js_sys::Date::now() >= deadline {
+            return Ok(false);
+        }
+        crate::retry::sleep(PROBE_POLL_MS).await?;
+    }
+    Ok(true)
+}
+
+async fn run(state: &SharedState, config: &WasmClientConfig, result: &mut ProbeResult) -> Result<(), (ProbeStage, JsValue)> {
+    let connect_timeout = config.timeouts.connect_ms.unwrap_or(PROBE_TIMEOUT_MS);
+    let request_timeout = config.timeouts.request_ms.unwrap_or(PROBE_TIMEOUT_MS);
+
+    let started = js_sys::Date::now();
+    let ws = crate::transport::open_socket(&config.url, None).map_err(|e| (ProbeStage::Connect, e))?;
+    let events = crate::transport::TransportEvents::new(state);
+    state.borrow_mut().transport = Some(Box::new(crate::transport::WebSocketTransport::attach(ws, events)));
+    // A close before opening counts as a failure on the breaker
+    let opened = wait_for(state, connect_timeout, |state| state.is_connected() || state.breaker.failures() > 0).await;
+    if !opened.map_err(|e| (ProbeStage::Connect, e))? || !state.borrow().is_connected() {
+        return Err((ProbeStage::Connect, JsValue::from_str("The connection did not open")));
+    }
+    result.connect_ms = Some(js_sys::Date::now() - started);
+
+    // Opening starts the `hello` exchange on its own
+    let started = js_sys::Date::now();
+    let negotiated = wait_for(state, request_timeout, |state| state.protocol.negotiated.is_some() || state.protocol.refused.is_some()).await;
+    if !negotiated.map_err(|e| (ProbeStage::Handshake, e))? {
+        return Err((ProbeStage::Handshake, JsValue::from_str("No hello response from the bridge")));
+    }
+    if let Some(reason) = state.borrow().protocol.refused.clone() {
+        return Err((ProbeStage::Handshake, JsValue::from_str(&reason)));
+    }
+    result.handshake_ms = Some(js_sys::Date::now() - started);
+    result.protocol_version = state.borrow().protocol.negotiated.map(|negotiated| negotiated.version);
+
+    if let Some(token) = &config.auth {
+        let started = js_sys::Date::now();
+        connection::request(state, "auth", message_type::AUTH, serde_json::json!({ "token": token }))
+            .await
+            .map_err(|e| (ProbeStage::Auth, e))?;
+        result.auth_ms = Some(js_sys::Date::now() - started);
+    }
+
+    let started = js_sys::Date::now();
+    connection::execute_query(state, "SELECT 1", None).await.map_err(|e| (ProbeStage::Query, e))?;
+    result.query_ms = Some(js_sys::Date::now() - started);
+    Ok(())
+}
+
+// Connect to a bridge, exchange `hello`, authenticate when the config has `auth` and run
+// `SELECT 1`, timing each step, then disconnect. Accepts a URL or a client config.
+// Resolves with a `ProbeResult` whether or not the bridge passed; rejects only on an
+// invalid config. Meant for smoke tests after a deployment.
+#[wasm_bindgen(unchecked_return_type = "Promise<ProbeResult>")]
+pub fn probe(#[wasm_bindgen(unchecked_param_type = "string | WasmClientConfig")] config: JsValue) -> Result<Promise, JsValue> {
+    let config = WasmClientConfig::from_js(config).map_err(|e| JsValue::from_str(&e))?;
+    // A bare client: no reconnects and no session setup, and auth is sent by the probe
+    let state: SharedState = Rc::new(RefCell::new(ClientState { timeouts: config.timeouts, ..ClientState::default() }));
+    Ok(future_to_promise(async move {
+        let started = js_sys::Date::now();
+        let mut result = ProbeResult::default();
+        match run(&state, &config, &mut result).await {
+            Ok(()) => result.ok = true,
+            Err((stage, error)) => result.fail(stage, crate::errors::message_of(&error)),
+        }
+        result.total_ms = js_sys::Date::now() - started;
+        let transport = state.borrow_mut().transport.take();
+        if let Some(transport) = transport {
+            transport.close();
+        }
+        state.borrow_mut().fail_pending("Probe finished");
+        crate::to_js(&result)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_shape() {
+        let mut result = ProbeResult { connect_ms: Some(12.0), total_ms: 30.0, ..ProbeResult::default() };
+        result.fail(ProbeStage::Handshake, "refused".to_string());
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["failedStage"], "handshake");
+        assert_eq!(value["connectMs"], 12.0);
+        assert!(value["authMs"].is_null());
+        assert_eq!(value["ok"], false);
+    }
+}
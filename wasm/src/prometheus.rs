@@ -0,0 +1,106 @@
+use std::fmt::Write;
+
+use serde::Deserialize;
+
+use crate::metrics::MetricsSnapshot;
+
+// Pool statistics reported by the bridge in response to a `pool_stats` message
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PoolStats {
+    #[serde(rename = "totalCount", default)]
+    pub total_count: u64,
+    #[serde(rename = "idleCount", default)]
+    pub idle_count: u64,
+    #[serde(rename = "waitingCount", default)]
+    pub waiting_count: u64,
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_number(value: f64) -> String {
+    if value.is_infinite() {
+        "+Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+// Render client metrics in the Prometheus text exposition format (durations in seconds)
+pub fn render_client_metrics(snapshot: &MetricsSnapshot) -> String {
+    let mut out = String::new();
+
+    let counters = [
+        ("bridge_client_queries_sent_total", "Queries sent to the bridge.", snapshot.queries_sent),
+        ("bridge_client_queries_succeeded_total", "Queries that returned a result.", snapshot.queries_succeeded),
+        ("bridge_client_bytes_sent_total", "Bytes written to the socket.", snapshot.bytes_sent),
+        ("bridge_client_bytes_received_total", "Bytes read from the socket.", snapshot.bytes_received),
+    ];
+    for (name, help, value) in counters {
+        header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{} {}", name, value);
+    }
+
+    header(&mut out, "bridge_client_query_errors_total", "counter", "Query errors by error class.");
+    for (class, count) in &snapshot.errors_by_class {
+        let _ = writeln!(out, "bridge_client_query_errors_total{{class=\"{}\"}} {}", escape_label(class), count);
+    }
+
+    let name = "bridge_client_query_duration_seconds";
+    header(&mut out, name, "histogram", "Query execution time reported by the bridge.");
+    for bucket in &snapshot.histogram {
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, format_number(bucket.le / 1000.0), bucket.count);
+    }
+    let _ = writeln!(out, "{}_sum {}", name, snapshot.latency_sum / 1000.0);
+    let _ = writeln!(out, "{}_count {}", name, snapshot.latency_count);
+
+    out
+}
+
+pub fn render_pool_stats(stats: &PoolStats) -> String {
+    let mut out = String::new();
+    let name = "bridge_pool_connections";
+    header(&mut out, name, "gauge", "Bridge database pool connections by state.");
+    let _ = writeln!(out, "{}{{state=\"total\"}} {}", name, stats.total_count);
+    let _ = writeln!(out, "{}{{state=\"idle\"}} {}", name, stats.idle_count);
+    header(&mut out, "bridge_pool_waiting_clients", "gauge", "Requests waiting for a pool connection.");
+    let _ = writeln!(out, "bridge_pool_waiting_clients {}", stats.waiting_count);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::QueryMetrics;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_client_metrics() {
+        let mut metrics = QueryMetrics::default();
+        metrics.record_sent(12, true);
+        metrics.record_result(&json!({"executionTime": 4}));
+        metrics.record_error(&json!({"code": "DATABASE_ERROR"}));
+        let text = render_client_metrics(&metrics.snapshot());
+
+        assert!(text.contains("# TYPE bridge_client_queries_sent_total counter\nbridge_client_queries_sent_total 1\n"));
+        assert!(text.contains("bridge_client_query_errors_total{class=\"DATABASE_ERROR\"} 1\n"));
+        assert!(text.contains("bridge_client_query_duration_seconds_bucket{le=\"0.005\"} 1\n"));
+        assert!(text.contains("bridge_client_query_duration_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("bridge_client_query_duration_seconds_sum 0.004\n"));
+    }
+
+    #[test]
+    fn test_render_pool_stats() {
+        let stats = PoolStats { total_count: 10, idle_count: 7, waiting_count: 0 };
+        let text = render_pool_stats(&stats);
+        assert!(text.contains("bridge_pool_connections{state=\"idle\"} 7\n"));
+        assert!(text.contains("bridge_pool_waiting_clients 0\n"));
+        assert_eq!(escape_label("a\"b"), "a\\\"b");
+    }
+}
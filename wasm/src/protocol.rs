@@ -0,0 +1,10 @@
+// Wire format shared with the bridge, for Rust callers. Everything here is plain Rust:
+// the JS-facing client in `bindings` is built on top of it.
+
+pub use bridge_protocol::{
+    chunking, compression, integrity, message_type, CacheControl, CacheStatus, Capabilities, ChangePayload, ErrorPayload, HelloPayload,
+    Negotiated, NotificationPayload, PoolMode, PoolRequest, ProgressPayload, QueryPayload, QueryResult, RawColumn,
+    ShutdownPayload, StatementPolicy, WebSocketMessage, PROTOCOL_VERSION, to_value,
+};
+
+pub use crate::handshake::{negotiate, CLIENT_CAPABILITIES};
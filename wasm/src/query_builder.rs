@@ -0,0 +1,292 @@
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::conditions::{Condition, COMPARISON_OPERATORS};
+use crate::sql::{quote_ident, quote_qualified};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Select,
+    Insert(Vec<(String, Value)>),
+    Update(Vec<(String, Value)>),
+    Delete,
+}
+
+// SQL text together with the parameters it binds
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BuiltQuery {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+// Fluent builder producing parameterized SQL for common CRUD statements
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBuilder {
+    table: String,
+    action: Action,
+    columns: Vec<String>,
+    conditions: Vec<Condition>,
+    order_by: Vec<(String, bool)>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    returning: Vec<String>,
+}
+
+#[wasm_bindgen]
+pub fn table(name: &str) -> QueryBuilder {
+    QueryBuilder::new(name)
+}
+
+fn object_entries(value: JsValue) -> Result<Vec<(String, Value)>, JsValue> {
+    match serde_wasm_bindgen::from_value::<Value>(value) {
+        Ok(Value::Object(map)) => Ok(map.into_iter().collect()),
+        Ok(_) => Err(JsValue::from_str("Expected an object of column values")),
+        Err(e) => Err(JsValue::from_str(&format!("Invalid column values: {}", e))),
+    }
+}
+
+fn js_to_value(value: JsValue) -> Result<Value, JsValue> {
+    if value.is_undefined() {
+        return Ok(Value::Null);
+    }
+    serde_wasm_bindgen::from_value(value).map_err(|e| JsValue::from_str(&format!("Invalid value: {}", e)))
+}
+
+#[wasm_bindgen]
+impl QueryBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(table: &str) -> QueryBuilder {
+        QueryBuilder {
+            table: table.to_string(),
+            action: Action::Select,
+            columns: Vec::new(),
+            conditions: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            returning: Vec::new(),
+        }
+    }
+
+    pub fn select(mut self, columns: Vec<String>) -> QueryBuilder {
+        self.action = Action::Select;
+        self.columns = columns;
+        self
+    }
+
+    pub fn insert(self, values: JsValue) -> Result<QueryBuilder, JsValue> {
+        Ok(self.insert_values(object_entries(values)?))
+    }
+
+    pub fn update(self, values: JsValue) -> Result<QueryBuilder, JsValue> {
+        Ok(self.update_values(object_entries(values)?))
+    }
+
+    pub fn delete(mut self) -> QueryBuilder {
+        self.action = Action::Delete;
+        self
+    }
+
+    pub fn where_eq(self, column: &str, value: JsValue) -> Result<QueryBuilder, JsValue> {
+        Ok(self.where_value(column, "=", js_to_value(value)?))
+    }
+
+    // Compare with one of =, <>, <, <=, >, >=
+    pub fn where_op(self, column: &str, operator: &str, value: JsValue) -> Result<QueryBuilder, JsValue> {
+        let operator = COMPARISON_OPERATORS
+            .iter()
+            .find(|op| **op == operator)
+            .ok_or_else(|| JsValue::from_str(&format!("Unsupported operator '{}'", operator)))?;
+        Ok(self.where_value(column, operator, js_to_value(value)?))
+    }
+
+    // AND a composed condition into the WHERE clause
+    pub fn where_condition(mut self, condition: &Condition) -> QueryBuilder {
+        self.conditions.push(condition.clone());
+        self
+    }
+
+    pub fn order_by(mut self, column: &str, descending: bool) -> QueryBuilder {
+        self.order_by.push((column.to_string(), descending));
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> QueryBuilder {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: u32) -> QueryBuilder {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn returning(mut self, columns: Vec<String>) -> QueryBuilder {
+        self.returning = columns;
+        self
+    }
+
+    // Returns `{ sql, params }`
+    pub fn build(&self) -> Result<JsValue, JsValue> {
+        let built = self.to_query().map_err(|e| JsValue::from_str(&e))?;
+        crate::to_js(&built)
+    }
+
+    pub fn to_sql(&self) -> Result<String, JsValue> {
+        self.to_query().map(|q| q.sql).map_err(|e| JsValue::from_str(&e))
+    }
+}
+
+impl QueryBuilder {
+    pub fn insert_values(mut self, values: Vec<(String, Value)>) -> QueryBuilder {
+        self.action = Action::Insert(values);
+        self
+    }
+
+    pub fn update_values(mut self, values: Vec<(String, Value)>) -> QueryBuilder {
+        self.action = Action::Update(values);
+        self
+    }
+
+    pub fn where_value(mut self, column: &str, operator: &'static str, value: Value) -> QueryBuilder {
+        self.conditions.push(Condition::compare_value(column, operator, Some(value)));
+        self
+    }
+
+    pub fn to_query(&self) -> Result<BuiltQuery, String> {
+        let mut params = Vec::new();
+        let table = quote_qualified(&self.table);
+
+        let mut sql = match &self.action {
+            Action::Select => {
+                let columns = if self.columns.is_empty() {
+                    "*".to_string()
+                } else {
+                    self.columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ")
+                };
+                format!("SELECT {} FROM {}", columns, table)
+            }
+            Action::Insert(values) => {
+                if values.is_empty() {
+                    return Err("INSERT requires at least one column value".to_string());
+                }
+                let columns = values.iter().map(|(c, _)| quote_ident(c)).collect::<Vec<_>>();
+                let placeholders = values
+                    .iter()
+                    .map(|(_, v)| {
+                        params.push(v.clone());
+                        format!("${}", params.len())
+                    })
+                    .collect::<Vec<_>>();
+                format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders.join(", "))
+            }
+            Action::Update(values) => {
+                if values.is_empty() {
+                    return Err("UPDATE requires at least one column value".to_string());
+                }
+                let assignments = values
+                    .iter()
+                    .map(|(c, v)| {
+                        params.push(v.clone());
+                        format!("{} = ${}", quote_ident(c), params.len())
+                    })
+                    .collect::<Vec<_>>();
+                format!("UPDATE {} SET {}", table, assignments.join(", "))
+            }
+            Action::Delete => format!("DELETE FROM {}", table),
+        };
+
+        let predicates = self
+            .conditions
+            .iter()
+            .filter_map(|c| c.render(&mut params))
+            .collect::<Vec<_>>();
+        if !predicates.is_empty() {
+            if matches!(self.action, Action::Insert(_)) {
+                return Err("INSERT does not accept WHERE conditions".to_string());
+            }
+            sql.push_str(&format!(" WHERE {}", predicates.join(" AND ")));
+        }
+
+        if self.action == Action::Select {
+            if !self.order_by.is_empty() {
+                let order = self
+                    .order_by
+                    .iter()
+                    .map(|(c, desc)| format!("{} {}", quote_ident(c), if *desc { "DESC" } else { "ASC" }))
+                    .collect::<Vec<_>>();
+                sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+            }
+            if let Some(limit) = self.limit {
+                sql.push_str(&format!(" LIMIT {}", limit));
+            }
+            if let Some(offset) = self.offset {
+                sql.push_str(&format!(" OFFSET {}", offset));
+            }
+        } else if !self.returning.is_empty() {
+            let columns = self.returning.iter().map(|c| quote_ident(c)).collect::<Vec<_>>();
+            sql.push_str(&format!(" RETURNING {}", columns.join(", ")));
+        }
+
+        Ok(BuiltQuery { sql, params })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_with_conditions() {
+        let query = QueryBuilder::new("users")
+            .select(vec!["id".to_string(), "name".to_string()])
+            .where_value("status", "=", json!("active"))
+            .where_value("deleted_at", "=", Value::Null)
+            .order_by("id", true)
+            .limit(20)
+            .to_query()
+            .unwrap();
+        assert_eq!(
+            query.sql,
+            "SELECT \"id\", \"name\" FROM \"users\" WHERE \"status\" = $1 AND \"deleted_at\" IS NULL ORDER BY \"id\" DESC LIMIT 20"
+        );
+        assert_eq!(query.params, vec![json!("active")]);
+    }
+
+    #[test]
+    fn test_insert_update_delete() {
+        let insert = QueryBuilder::new("users")
+            .insert_values(vec![("name".to_string(), json!("a")), ("age".to_string(), json!(3))])
+            .returning(vec!["id".to_string()])
+            .to_query()
+            .unwrap();
+        assert_eq!(insert.sql, "INSERT INTO \"users\" (\"name\", \"age\") VALUES ($1, $2) RETURNING \"id\"");
+
+        let update = QueryBuilder::new("users")
+            .update_values(vec![("name".to_string(), json!("b"))])
+            .where_value("id", "=", json!(1))
+            .to_query()
+            .unwrap();
+        assert_eq!(update.sql, "UPDATE \"users\" SET \"name\" = $1 WHERE \"id\" = $2");
+        assert_eq!(update.params, vec![json!("b"), json!(1)]);
+
+        let delete = QueryBuilder::new("users").delete().where_value("id", ">", json!(5)).to_query().unwrap();
+        assert_eq!(delete.sql, "DELETE FROM \"users\" WHERE \"id\" > $1");
+
+        assert!(QueryBuilder::new("users").insert_values(Vec::new()).to_query().is_err());
+    }
+
+    #[test]
+    fn test_where_condition_numbers_after_values() {
+        let condition = Condition::in_values("id", vec![json!(1), json!(2)]).or(&Condition::is_null("id"));
+        let query = QueryBuilder::new("users")
+            .update_values(vec![("active".to_string(), json!(false))])
+            .where_condition(&condition)
+            .to_query()
+            .unwrap();
+        assert_eq!(query.sql, "UPDATE \"users\" SET \"active\" = $1 WHERE (\"id\" IN ($2, $3) OR \"id\" IS NULL)");
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, QueryOptions, SharedState};
+
+pub const DEFAULT_MAX_ENTRIES: usize = 100;
+
+// `cache` option of `query()`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Tsify)]
+pub struct CacheOptions {
+    // Milliseconds a result stays fresh
+    pub ttl: f64,
+    // Resizes the client's cache when given
+    #[serde(rename = "maxEntries", default)]
+    pub max_entries: Option<usize>,
+}
+
+// Collapse whitespace and drop a trailing semicolon, leaving quoted text alone, so
+// formatting differences don't split the cache
+pub fn normalize_sql(sql: &str) -> String {
+    let mut out = String::with_capacity(sql.len());
+    let mut quote: Option<char> = None;
+    let mut pending_space = false;
+    for c in sql.trim().trim_end_matches(';').trim_end().chars() {
+        match quote {
+            Some(q) => {
+                out.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c.is_whitespace() => pending_space = true,
+            None => {
+                if pending_space && !out.is_empty() {
+                    out.push(' ');
+                }
+                pending_space = false;
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                out.push(c);
+            }
+        }
+    }
+    out
+}
+
+fn cache_key(sql: &str, params: &Option<Vec<Value>>) -> String {
+    let params = params.as_ref().map_or("null".to_string(), |p| Value::from(p.clone()).to_string());
+    format!("{}\u{0}{}", sql, params)
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    sql: String,
+    // As given, to run again; `sql` is normalized
+    query: String,
+    params: Option<Vec<Value>>,
+    payload: Value,
+    ttl_ms: f64,
+    expires_at: f64,
+    last_used: u64,
+}
+
+// A query dropped from the cache, with what it takes to run and cache it again
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dropped {
+    pub sql: String,
+    pub params: Option<Vec<Value>>,
+    pub ttl_ms: f64,
+}
+
+// Recent query results held in memory, evicting the least recently used entry
+#[derive(Debug)]
+pub struct QueryCache {
+    entries: HashMap<String, Entry>,
+    max_entries: usize,
+    clock: u64,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        QueryCache { entries: HashMap::new(), max_entries: DEFAULT_MAX_ENTRIES, clock: 0 }
+    }
+}
+
+impl QueryCache {
+    pub fn set_max_entries(&mut self, max_entries: usize) {
+        self.max_entries = max_entries.max(1);
+        self.evict();
+    }
+
+    pub fn get(&mut self, sql: &str, params: &Option<Vec<Value>>, now: f64) -> Option<Value> {
+        let key = cache_key(&normalize_sql(sql), params);
+        let fresh = self.entries.get(&key).is_some_and(|entry| entry.expires_at > now);
+        if !fresh {
+            self.entries.remove(&key);
+            return None;
+        }
+        self.clock += 1;
+        let entry = self.entries.get_mut(&key)?;
+        entry.last_used = self.clock;
+        Some(entry.payload.clone())
+    }
+
+    pub fn insert(&mut self, query: &str, params: &Option<Vec<Value>>, payload: Value, ttl_ms: f64, now: f64) {
+        let sql = normalize_sql(query);
+        self.clock += 1;
+        let entry = Entry {
+            sql: sql.clone(),
+            query: query.to_string(),
+            params: params.clone(),
+            payload,
+            ttl_ms,
+            expires_at: now + ttl_ms,
+            last_used: self.clock,
+        };
+        self.entries.insert(cache_key(&sql, params), entry);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.entries.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    // Run `patch` over the payload of every fresh entry whose SQL passes `matches`;
+    // returns the payloads it changed, keyed for `restore`
+    pub fn patch(&mut self, matches: impl Fn(&str) -> bool, patch: impl Fn(&mut Value) -> bool) -> Vec<(String, Value)> {
+        let mut previous = Vec::new();
+        for (key, entry) in self.entries.iter_mut().filter(|(_, entry)| matches(&entry.sql)) {
+            let before = entry.payload.clone();
+            if patch(&mut entry.payload) {
+                previous.push((key.clone(), before));
+            }
+        }
+        previous
+    }
+
+    // Put back payloads `patch` replaced, for entries that are still cached
+    pub fn restore(&mut self, previous: Vec<(String, Value)>) {
+        for (key, payload) in previous {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                entry.payload = payload;
+            }
+        }
+    }
+
+    // Drop entries whose SQL contains `pattern` (case-insensitively), or every entry
+    // when there is no pattern; returns how many were dropped
+    pub fn invalidate(&mut self, pattern: Option<&str>) -> usize {
+        let before = self.entries.len();
+        match pattern.map(str::to_lowercase).filter(|p| !p.is_empty()) {
+            Some(pattern) => self.entries.retain(|_, entry| !entry.sql.to_lowercase().contains(&pattern)),
+            None => self.entries.clear(),
+        }
+        before - self.entries.len()
+    }
+
+    // Drop entries whose SQL passes `matches`, returning the queries that were dropped
+    pub fn take_matching(&mut self, matches: impl Fn(&str) -> bool) -> Vec<Dropped> {
+        let keys: Vec<String> = self.entries.iter().filter(|(_, entry)| matches(&entry.sql)).map(|(key, _)| key.clone()).collect();
+        keys.iter()
+            .filter_map(|key| self.entries.remove(key))
+            .map(|entry| Dropped { sql: entry.query, params: entry.params, ttl_ms: entry.ttl_ms })
+            .collect()
+    }
+}
+
+// Serve a fresh cached result, or run the query and cache what it returns
+// Whether `query()` may answer from the cache. Writes and binary parameters never do, nor
+// do paged queries: their result is one page plus a continuation token for a cursor.
+pub(crate) fn applies(sql: &str, options: &QueryOptions) -> bool {
+    options.cache.is_some() && !crate::outbox::is_write(sql) && options.binary_params.is_none() && options.page_size.is_none()
+}
+
+pub(crate) async fn cached(
+    state: &SharedState,
+    sql: &str,
+    params: Option<Vec<Value>>,
+    options: QueryOptions,
+    cache: CacheOptions,
+) -> Result<Value, JsValue> {
+    {
+        let mut state = state.borrow_mut();
+        if let Some(max_entries) = cache.max_entries {
+            state.query_cache.set_max_entries(max_entries);
+        }
+        if let Some(payload) = state.query_cache.get(sql, &params, js_sys::Date::now()) {
+            log_debug!("WASM query cache hit: {}", sql);
+            return Ok(payload);
+        }
+    }
+    let payload = connection::execute_query_with(state, sql, params.clone(), options).await?;
+    state.borrow_mut().query_cache.insert(sql, &params, payload.clone(), cache.ttl, js_sys::Date::now());
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_normalize_sql() {
+        assert_eq!(normalize_sql("  SELECT *\n  FROM   countries ;"), "SELECT * FROM countries");
+        assert_eq!(normalize_sql("SELECT 'a   b'  AS x"), "SELECT 'a   b' AS x");
+    }
+
+    #[test]
+    fn test_ttl_lru_and_invalidate() {
+        let mut cache = QueryCache::default();
+        cache.set_max_entries(2);
+        cache.insert("SELECT * FROM countries", &None, json!(1), 1000.0, 0.0);
+        cache.insert("SELECT * FROM currencies", &None, json!(2), 1000.0, 0.0);
+        assert_eq!(cache.get("SELECT *  FROM countries;", &None, 500.0), Some(json!(1)));
+        // currencies is now the least recently used
+        cache.insert("SELECT * FROM users WHERE id = $1", &Some(vec![json!(7)]), json!(3), 1000.0, 0.0);
+        assert_eq!(cache.get("SELECT * FROM currencies", &None, 500.0), None);
+        assert_eq!(cache.get("SELECT * FROM users WHERE id = $1", &Some(vec![json!(8)]), 500.0), None);
+        assert_eq!(cache.get("SELECT * FROM countries", &None, 1500.0), None);
+
+        assert_eq!(cache.invalidate(Some("USERS")), 1);
+        assert!(cache.entries.is_empty());
+    }
+
+    #[test]
+    fn test_paged_queries_skip_the_cache() {
+        let cache = Some(CacheOptions { ttl: 1000.0, max_entries: None });
+        let sql = "SELECT * FROM countries";
+        assert!(applies(sql, &QueryOptions { cache, ..QueryOptions::default() }));
+        assert!(!applies(sql, &QueryOptions { cache, page_size: Some(10), ..QueryOptions::default() }));
+        assert!(!applies("DELETE FROM countries", &QueryOptions { cache, ..QueryOptions::default() }));
+        assert!(!applies(sql, &QueryOptions::default()));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+// Finished queries remembered for `query_status`, oldest forgotten first
+const FINISHED_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryStatus {
+    // Waiting on session restore, the rate limit or a dispatch slot
+    Queued,
+    Sent,
+    // The bridge has reported progress
+    Executing,
+    Completed,
+    Failed,
+}
+
+impl QueryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryStatus::Queued => "queued",
+            QueryStatus::Sent => "sent",
+            QueryStatus::Executing => "executing",
+            QueryStatus::Completed => "completed",
+            QueryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(self, QueryStatus::Completed | QueryStatus::Failed)
+    }
+}
+
+// Status of each `query()` by id, from the call until shortly after it settles
+#[derive(Debug, Default)]
+pub struct QueryTracker {
+    active: HashMap<String, QueryStatus>,
+    finished: VecDeque<(String, QueryStatus)>,
+    // Queued queries cancelled before they were sent; they fail instead of sending
+    cancelled: HashSet<String>,
+}
+
+impl QueryTracker {
+    pub fn status(&self, id: &str) -> Option<QueryStatus> {
+        self.active
+            .get(id)
+            .copied()
+            .or_else(|| self.finished.iter().rev().find(|(finished, _)| finished == id).map(|(_, status)| *status))
+    }
+
+    // Start tracking `id`; false if a query with that id is still running
+    pub fn queue(&mut self, id: &str) -> bool {
+        if self.active.contains_key(id) {
+            return false;
+        }
+        self.finished.retain(|(finished, _)| finished != id);
+        self.active.insert(id.to_string(), QueryStatus::Queued);
+        true
+    }
+
+    // Move a running query along; does nothing for ids that aren't tracked
+    pub fn advance(&mut self, id: &str, status: QueryStatus) {
+        if status.is_finished() {
+            if self.active.remove(id).is_some() {
+                self.cancelled.remove(id);
+                self.finished.push_back((id.to_string(), status));
+                while self.finished.len() > FINISHED_CAPACITY {
+                    self.finished.pop_front();
+                }
+            }
+        } else if let Some(current) = self.active.get_mut(id) {
+            *current = status;
+        }
+    }
+
+    // Mark a queued query so it fails instead of being sent; false if it isn't queued
+    pub fn cancel_queued(&mut self, id: &str) -> bool {
+        if self.active.get(id) != Some(&QueryStatus::Queued) {
+            return false;
+        }
+        self.cancelled.insert(id.to_string())
+    }
+
+    pub fn take_cancelled(&mut self, id: &str) -> bool {
+        self.cancelled.remove(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_and_cancel() {
+        let mut tracker = QueryTracker::default();
+        assert!(tracker.queue("orders"));
+        assert!(!tracker.queue("orders"));
+        assert!(tracker.cancel_queued("orders"));
+        assert!(tracker.take_cancelled("orders"));
+        tracker.advance("orders", QueryStatus::Failed);
+        assert_eq!(tracker.status("orders"), Some(QueryStatus::Failed));
+
+        tracker.queue("users");
+        tracker.advance("users", QueryStatus::Sent);
+        tracker.advance("users", QueryStatus::Executing);
+        assert!(!tracker.cancel_queued("users"));
+        assert_eq!(tracker.status("users").map(QueryStatus::as_str), Some("executing"));
+        tracker.advance("users", QueryStatus::Completed);
+        assert_eq!(tracker.status("users"), Some(QueryStatus::Completed));
+        // The id can be reused once the query settled
+        assert!(tracker.queue("users"));
+        assert_eq!(tracker.status("unknown"), None);
+    }
+}
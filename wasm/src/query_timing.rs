@@ -0,0 +1,36 @@
+use bridge_protocol::QueryTiming;
+use serde_json::Value;
+
+// Complete a result's `timing` with the client's share. Bridges that don't time their
+// work leave networkMs out, since the round trip then includes the bridge.
+pub fn complete(result: &mut Value, queue_ms: f64, round_trip_ms: f64) {
+    let Some(result) = result.as_object_mut() else {
+        return;
+    };
+    let mut timing: QueryTiming = result.get("timing").and_then(|timing| serde_json::from_value(timing.clone()).ok()).unwrap_or_default();
+    timing.queue_ms = Some(queue_ms);
+    timing.round_trip_ms = Some(round_trip_ms);
+    timing.network_ms = timing.bridge_ms.map(|bridge_ms| (round_trip_ms - bridge_ms).max(0.0));
+    timing.total_ms = Some(queue_ms + round_trip_ms);
+    result.insert("timing".to_string(), bridge_protocol::to_value(&timing));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_splits_the_round_trip() {
+        let mut result = json!({ "rows": [], "timing": { "bridgeMs": 7.5, "databaseMs": 6.0 } });
+        complete(&mut result, 2.0, 12.0);
+        assert_eq!(
+            result["timing"],
+            json!({ "bridgeMs": 7.5, "databaseMs": 6.0, "queueMs": 2.0, "roundTripMs": 12.0, "networkMs": 4.5, "totalMs": 14.0 })
+        );
+
+        let mut untimed = json!({ "rows": [] });
+        complete(&mut untimed, 0.0, 3.0);
+        assert_eq!(untimed["timing"], json!({ "queueMs": 0.0, "roundTripMs": 3.0, "totalMs": 3.0 }));
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// What happens to a query that arrives while the limit is exhausted
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    // Wait until the buckets refill
+    #[default]
+    Queue,
+    // Fail straight away with RATE_LIMITED
+    Reject,
+}
+
+// Options accepted by `set_rate_limit`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default)]
+pub struct RateLimitOptions {
+    #[serde(rename = "queriesPerSecond")]
+    pub queries_per_second: Option<f64>,
+    #[serde(rename = "bytesPerSecond")]
+    pub bytes_per_second: Option<f64>,
+    // Seconds of traffic that may be spent in one burst; defaults to 1
+    #[serde(rename = "burstSeconds")]
+    pub burst_seconds: Option<f64>,
+    pub policy: OverflowPolicy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: f64,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, capacity: f64, now: f64) -> TokenBucket {
+        TokenBucket { rate, capacity, tokens: capacity, updated: now }
+    }
+
+    fn refill(&mut self, now: f64) {
+        let elapsed = (now - self.updated).max(0.0) / 1000.0;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    // Milliseconds until `amount` tokens are available; anything above the capacity
+    // only needs a full bucket, so oversized requests still get through eventually
+    fn wait_for(&mut self, amount: f64, now: f64) -> f64 {
+        self.refill(now);
+        let needed = amount.min(self.capacity) - self.tokens;
+        if needed <= 0.0 {
+            0.0
+        } else {
+            needed / self.rate * 1000.0
+        }
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.tokens -= amount.min(self.capacity);
+    }
+}
+
+// Token buckets for query count and bytes sent; a query passes only if both allow it
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    queries: Option<TokenBucket>,
+    bytes: Option<TokenBucket>,
+    policy: OverflowPolicy,
+}
+
+impl RateLimiter {
+    pub fn configure(&mut self, options: RateLimitOptions, now: f64) {
+        let burst = options.burst_seconds.unwrap_or(1.0).max(0.001);
+        let bucket = |rate: Option<f64>| rate.filter(|r| *r > 0.0).map(|r| TokenBucket::new(r, (r * burst).max(1.0), now));
+        self.queries = bucket(options.queries_per_second);
+        self.bytes = bucket(options.bytes_per_second);
+        self.policy = options.policy;
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    // Take the tokens for one query of `bytes`, or return how long to wait first
+    pub fn try_acquire(&mut self, bytes: usize, now: f64) -> Result<(), f64> {
+        let query_wait = self.queries.as_mut().map_or(0.0, |b| b.wait_for(1.0, now));
+        let byte_wait = self.bytes.as_mut().map_or(0.0, |b| b.wait_for(bytes as f64, now));
+        let wait = query_wait.max(byte_wait);
+        if wait > 0.0 {
+            return Err(wait);
+        }
+        if let Some(bucket) = &mut self.queries {
+            bucket.take(1.0);
+        }
+        if let Some(bucket) = &mut self.bytes {
+            bucket.take(bytes as f64);
+        }
+        Ok(())
+    }
+}
+
+// Wait for (or, under the reject policy, refuse) a query the rate limit doesn't allow yet
+pub(crate) async fn admit(state: &SharedState, sql: &str, params: Option<&[Value]>) -> Result<(), JsValue> {
+    let bytes = sql.len() + params.map_or(0, |p| Value::Array(p.to_vec()).to_string().len());
+    loop {
+        let outcome = state.borrow_mut().rate_limit.try_acquire(bytes, js_sys::Date::now());
+        let wait = match outcome {
+            Ok(()) => return Ok(()),
+            Err(wait) => wait,
+        };
+        if state.borrow().rate_limit.policy() == OverflowPolicy::Reject {
+            let payload = serde_json::json!({
+                "message": format!("Client rate limit exceeded; retry in {:.0}ms", wait),
+                "code": "RATE_LIMITED",
+            });
+            return Err(BridgeError::from_error_payload(&payload, None).into());
+        }
+        crate::retry::sleep(wait).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_bucket_refills() {
+        let mut limiter = RateLimiter::default();
+        assert_eq!(limiter.try_acquire(10, 0.0), Ok(()));
+        limiter.configure(RateLimitOptions { queries_per_second: Some(2.0), ..Default::default() }, 0.0);
+        assert_eq!(limiter.try_acquire(10, 0.0), Ok(()));
+        assert_eq!(limiter.try_acquire(10, 0.0), Ok(()));
+        assert_eq!(limiter.try_acquire(10, 0.0), Err(500.0));
+        assert_eq!(limiter.try_acquire(10, 500.0), Ok(()));
+    }
+
+    #[test]
+    fn test_byte_bucket_and_oversized_queries() {
+        let mut limiter = RateLimiter::default();
+        let options: RateLimitOptions = serde_json::from_str(r#"{"bytesPerSecond": 100, "policy": "reject"}"#).unwrap();
+        limiter.configure(options, 0.0);
+        assert_eq!(limiter.policy(), OverflowPolicy::Reject);
+        assert_eq!(limiter.try_acquire(60, 0.0), Ok(()));
+        assert_eq!(limiter.try_acquire(60, 0.0), Err(200.0));
+        // Larger than the bucket: allowed once it is full again
+        assert_eq!(limiter.try_acquire(5000, 600.0), Ok(()));
+    }
+}
@@ -0,0 +1,62 @@
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+// Bytes of a cell the bridge hex encoded like bytea, with or without the `\x` prefix
+pub fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.strip_prefix("\\x").unwrap_or(text);
+    if !digits.is_ascii() {
+        return Err(format!("'{}' is not hex", text));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!("odd number of hex digits in '{}'", text));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| format!("'{}' is not hex", text)))
+        .collect()
+}
+
+// The result payload of a raw query, with each row an array of Uint8Array cells (null
+// for SQL NULL) in `columns` order: `{ columns: [{ name, typeOid, format }], rows, ... }`
+pub(crate) fn to_js(result: &Value) -> Result<JsValue, JsValue> {
+    let rows = result.get("rows").and_then(|rows| rows.as_array()).map(|rows| rows.as_slice()).unwrap_or_default();
+    let mut rest = result.clone();
+    if let Some(object) = rest.as_object_mut() {
+        object.remove("rows");
+    }
+    let output: js_sys::Object = crate::to_js(&rest)?.unchecked_into();
+
+    let decoded = js_sys::Array::new();
+    for row in rows {
+        let cells = js_sys::Array::new();
+        for cell in row.as_array().map(|cells| cells.as_slice()).unwrap_or_default() {
+            match cell.as_str() {
+                Some(text) => {
+                    let bytes = decode_hex(text).map_err(|e| JsValue::from_str(&format!("Invalid raw cell: {}", e)))?;
+                    cells.push(&js_sys::Uint8Array::from(bytes.as_slice()));
+                }
+                None => {
+                    cells.push(&JsValue::NULL);
+                }
+            }
+        }
+        decoded.push(&cells);
+    }
+    js_sys::Reflect::set(&output, &JsValue::from_str("rows"), &decoded)?;
+    Ok(output.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_hex() {
+        // An int4 42 in the binary format
+        assert_eq!(decode_hex("\\x0000002a").unwrap(), vec![0, 0, 0, 42]);
+        assert_eq!(decode_hex("").unwrap(), Vec::<u8>::new());
+        assert!(decode_hex("\\xabc").is_err());
+        assert!(decode_hex("zz").is_err());
+        assert!(decode_hex("é1").is_err());
+    }
+}
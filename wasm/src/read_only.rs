@@ -0,0 +1,111 @@
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+use crate::session_state;
+use crate::sql::{statements, Token};
+
+// Makes every transaction on the backend session read-only unless it asks otherwise
+pub const READ_ONLY_SETTING: &str = "default_transaction_read_only";
+
+// Words that start the statement under an EXPLAIN
+const EXPLAINABLE: [&str; 10] = ["select", "with", "values", "table", "insert", "update", "delete", "merge", "create", "execute"];
+
+const DATA_MODIFYING: [&str; 4] = ["insert", "update", "delete", "merge"];
+
+fn words(tokens: &[Token]) -> impl Iterator<Item = &str> {
+    tokens.iter().filter_map(|token| match token {
+        Token::Word(word) => Some(word.as_str()),
+        _ => None,
+    })
+}
+
+fn check_statement(tokens: &[Token]) -> Result<(), String> {
+    let Some(first) = words(tokens).next() else {
+        return Ok(());
+    };
+    let has = |word: &str| words(tokens).any(|w| w == word);
+    let allowed = match first {
+        // SELECT ... INTO creates a table
+        "select" | "values" | "table" => !has("into"),
+        "show" => true,
+        // Data-modifying CTEs write even though the statement starts with WITH
+        "with" => !DATA_MODIFYING.iter().any(|word| has(word)) && !has("into"),
+        // Only EXPLAIN ANALYZE runs the statement
+        "explain" if has("analyze") || has("analyse") => {
+            let explained = tokens.iter().skip(1).position(|t| matches!(t, Token::Word(w) if EXPLAINABLE.contains(&w.as_str())));
+            return match explained {
+                Some(index) => check_statement(&tokens[index + 1..]),
+                None => Err("Read-only mode: EXPLAIN ANALYZE of this statement is not allowed".to_string()),
+            };
+        }
+        "explain" => true,
+        // A cursor is as read-only as the query it is declared for
+        "declare" => {
+            let query = tokens.iter().position(|t| matches!(t, Token::Word(w) if w == "for"));
+            return match query {
+                Some(index) => check_statement(&tokens[index + 1..]),
+                None => Err("Read-only mode: DECLARE without a query is not allowed".to_string()),
+            };
+        }
+        "fetch" | "move" | "close" => true,
+        "begin" | "start" | "commit" | "rollback" | "end" | "abort" | "savepoint" | "release" => !has("write"),
+        _ => false,
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(format!("Read-only mode: {} statements are not allowed", first.to_uppercase()))
+    }
+}
+
+// Reject SQL that could modify data: only reads, EXPLAIN and transaction control pass
+pub fn check(sql: &str) -> Result<(), String> {
+    statements(sql).iter().try_for_each(|statement| check_statement(statement))
+}
+
+// Turn read-only mode on or off, both locally and for the backend session. The setting
+// joins the session state, so it is re-applied after every reconnect.
+pub(crate) async fn set(state: &SharedState, enabled: bool) -> Result<(), JsValue> {
+    let value = enabled.then(|| "on".to_string());
+    // The local check changes first so the RESET that turns the mode off can go out
+    let connected = {
+        let mut state = state.borrow_mut();
+        state.read_only = enabled;
+        state.is_connected()
+    };
+    if connected {
+        session_state::apply_setting(state, READ_ONLY_SETTING, value).await
+    } else {
+        state.borrow_mut().session.set(READ_ONLY_SETTING, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_pass() {
+        assert!(check("SELECT * FROM users WHERE note = 'DELETE me'").is_ok());
+        assert!(check("WITH recent AS (SELECT * FROM orders) SELECT count(*) FROM recent").is_ok());
+        assert!(check("EXPLAIN DELETE FROM users").is_ok());
+        assert!(check("EXPLAIN (ANALYZE, BUFFERS) SELECT 1").is_ok());
+        assert!(check("BEGIN; SELECT 1; COMMIT").is_ok());
+        assert!(check("SHOW search_path").is_ok());
+        assert!(check("DECLARE c NO SCROLL CURSOR WITH HOLD FOR SELECT * FROM users").is_ok());
+        assert!(check("FETCH FORWARD 100 FROM c").is_ok());
+    }
+
+    #[test]
+    fn test_writes_rejected() {
+        assert!(check("UPDATE users SET name = 'x'").is_err());
+        assert!(check("SELECT 1; DROP TABLE users").is_err());
+        assert!(check("WITH gone AS (DELETE FROM users RETURNING *) SELECT * FROM gone").is_err());
+        assert!(check("EXPLAIN ANALYZE INSERT INTO users VALUES (1)").is_err());
+        assert!(check("SELECT * INTO backup FROM users").is_err());
+        assert!(check("BEGIN READ WRITE").is_err());
+        assert!(check("DECLARE c CURSOR FOR WITH gone AS (DELETE FROM users RETURNING *) SELECT * FROM gone").is_err());
+        assert_eq!(check("set default_transaction_read_only = off").unwrap_err(), "Read-only mode: SET statements are not allowed");
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// How the client reopens a connection that dropped unexpectedly
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectPolicy {
+    pub enabled: bool,
+    // Consecutive attempts before giving up; a successful open starts the count again
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: u32,
+    #[serde(rename = "baseDelayMs")]
+    pub base_delay_ms: f64,
+    #[serde(rename = "maxDelayMs")]
+    pub max_delay_ms: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy { enabled: true, max_attempts: 10, base_delay_ms: 500.0, max_delay_ms: 30_000.0 }
+    }
+}
+
+impl ReconnectPolicy {
+    // Exponential backoff with jitter over the upper half, as for query retries
+    pub fn delay_ms(&self, attempt: u32, jitter: f64) -> f64 {
+        let exponential = self.base_delay_ms * 2f64.powi(attempt.saturating_sub(1) as i32);
+        exponential.min(self.max_delay_ms) * (0.5 + jitter / 2.0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("maxAttempts must be at least 1".to_string());
+        }
+        if !(self.base_delay_ms.is_finite() && self.base_delay_ms >= 0.0) {
+            return Err("baseDelayMs must be a non-negative number".to_string());
+        }
+        if !(self.max_delay_ms.is_finite() && self.max_delay_ms >= self.base_delay_ms) {
+            return Err("maxDelayMs must be a number no smaller than baseDelayMs".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ReconnectState {
+    pub policy: Option<ReconnectPolicy>,
+    pub attempts: u32,
+    // Set while the app wants a connection: from a connect call until `disconnect`
+    pub armed: bool,
+    // A reconnect is waiting on its backoff timer
+    scheduled: bool,
+    // Bumped per connection attempt so a connect timeout only closes its own attempt
+    pub generation: u32,
+}
+
+// Reopen the connection after its backoff, unless it was closed on purpose: by
+// `disconnect`, a graceful close or the idle timeout
+pub(crate) fn schedule(state: &SharedState) {
+    schedule_after(state, None);
+}
+
+// `schedule`, waiting at least `min_delay_ms`, e.g. for a restarting bridge
+pub(crate) fn schedule_after(state: &SharedState, min_delay_ms: Option<f64>) {
+    let (delay, attempt) = {
+        let mut state = state.borrow_mut();
+        let Some(policy) = state.reconnect.policy.clone().filter(|p| p.enabled) else {
+            return;
+        };
+        if !state.reconnect.armed || state.reconnect.scheduled || state.closing || state.idle.suspended {
+            return;
+        }
+        if state.reconnect.attempts >= policy.max_attempts {
+            log_warn!("WASM giving up after {} reconnect attempts", state.reconnect.attempts);
+            // Idempotent queries kept for re-sending have nowhere to go
+            state.fail_pending_with(&BridgeError::connection_lost("gave up reconnecting"));
+            return;
+        }
+        state.reconnect.attempts += 1;
+        state.reconnect.scheduled = true;
+        let delay = policy.delay_ms(state.reconnect.attempts, js_sys::Math::random()).max(min_delay_ms.unwrap_or(0.0));
+        (delay, state.reconnect.attempts)
+    };
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = crate::retry::sleep(delay).await;
+        let open = {
+            let mut state = state.borrow_mut();
+            state.reconnect.scheduled = false;
+            if state.is_connected() || !state.reconnect.armed {
+                return;
+            }
+            state.idle.reconnect.clone()
+        };
+        let Some(open) = open else { return };
+        log_info!("WASM reconnecting (attempt {})", attempt);
+        if let Err(e) = crate::connection::admit_connection(&state).and_then(|_| open(&state)) {
+            log_warn!("WASM reconnect attempt {} failed: {:?}", attempt, e);
+            schedule(&state);
+        }
+    });
+}
+
+// Abandon an attempt that hasn't opened within `timeout_ms` and schedule the next one
+pub(crate) fn watch_connect(state: &SharedState, timeout_ms: f64) {
+    let generation = {
+        let mut state = state.borrow_mut();
+        state.reconnect.generation += 1;
+        state.reconnect.generation
+    };
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let _ = crate::retry::sleep(timeout_ms).await;
+        let transport = {
+            let mut state = state.borrow_mut();
+            if state.reconnect.generation != generation || state.is_connected() {
+                return;
+            }
+            state.transport.take()
+        };
+        if let Some(transport) = transport {
+            log_warn!("WASM connection did not open within {}ms", timeout_ms);
+            transport.close();
+            let reconnecting = state.borrow().will_reconnect();
+            state.borrow_mut().interrupt_pending(&BridgeError::connection_lost("connection timed out"), reconnecting);
+            schedule(&state);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_backoff_and_validation() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.delay_ms(1, 1.0), 500.0);
+        assert_eq!(policy.delay_ms(20, 1.0), 30_000.0);
+        assert!(policy.validate().is_ok());
+        assert!(ReconnectPolicy { max_attempts: 0, ..policy.clone() }.validate().is_err());
+        assert!(ReconnectPolicy { max_delay_ms: 100.0, ..policy }.validate().is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::logging::redact_params;
+
+pub const DEFAULT_RECORDER_CAPACITY: usize = 500;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TrafficEntry {
+    pub direction: Direction,
+    // Milliseconds since the epoch
+    pub timestamp: f64,
+    // Serialized size of the message, before compression or chunking
+    pub size: usize,
+    pub message: Value,
+}
+
+#[derive(Serialize)]
+struct TrafficExport<'a> {
+    capacity: usize,
+    dropped: u64,
+    entries: &'a VecDeque<TrafficEntry>,
+}
+
+// Ring buffer of every message sent and received while recording is on, for
+// attaching a session trace to bug reports
+#[derive(Debug, Clone)]
+pub struct TrafficRecorder {
+    enabled: bool,
+    redact: bool,
+    capacity: usize,
+    // Entries pushed out of the buffer since the last clear
+    dropped: u64,
+    entries: VecDeque<TrafficEntry>,
+}
+
+impl Default for TrafficRecorder {
+    fn default() -> Self {
+        TrafficRecorder {
+            enabled: false,
+            redact: true,
+            capacity: DEFAULT_RECORDER_CAPACITY,
+            dropped: 0,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl TrafficRecorder {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // When on (the default), recorded messages keep parameter counts instead of values
+    pub fn set_redaction(&mut self, redact: bool) {
+        self.redact = redact;
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    pub fn record(&mut self, direction: Direction, mut message: Value, size: usize, timestamp: f64) {
+        if !self.enabled {
+            return;
+        }
+        if self.redact {
+            if let Some(payload) = message.get_mut("payload") {
+                redact_params(payload);
+            }
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(TrafficEntry { direction, timestamp, size, message });
+    }
+
+    pub fn entries(&self) -> Vec<TrafficEntry> {
+        self.entries.iter().cloned().collect()
+    }
+
+    // The buffer as a JSON document
+    pub fn export(&self) -> String {
+        let export = TrafficExport { capacity: self.capacity, dropped: self.dropped, entries: &self.entries };
+        serde_json::to_string_pretty(&export).unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dropped = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(n: i64) -> Value {
+        json!({"type": "query", "payload": {"sql": "SELECT $1", "params": [n]}, "id": format!("q{}", n)})
+    }
+
+    #[test]
+    fn test_records_only_when_enabled_and_redacts() {
+        let mut recorder = TrafficRecorder::default();
+        recorder.record(Direction::Outbound, query(1), 10, 0.0);
+        assert!(recorder.entries().is_empty());
+
+        recorder.set_enabled(true);
+        recorder.record(Direction::Outbound, query(2), 10, 1.0);
+        recorder.set_redaction(false);
+        recorder.record(Direction::Inbound, query(3), 12, 2.0);
+        let entries = recorder.entries();
+        assert_eq!(entries[0].message["payload"]["params"], json!("[1 params redacted]"));
+        assert_eq!(entries[1].message["payload"]["params"], json!([3]));
+        assert_eq!(entries[1].direction, Direction::Inbound);
+    }
+
+    #[test]
+    fn test_ring_buffer_export() {
+        let mut recorder = TrafficRecorder::default();
+        recorder.set_enabled(true);
+        recorder.set_capacity(2);
+        for n in 1..=3 {
+            recorder.record(Direction::Outbound, query(n), 10, n as f64);
+        }
+        let export: Value = serde_json::from_str(&recorder.export()).unwrap();
+        assert_eq!(export["dropped"], json!(1));
+        assert_eq!(export["entries"][0]["message"]["id"], json!("q2"));
+        assert_eq!(export["entries"][1]["direction"], json!("outbound"));
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::BridgeError;
+use crate::WebSocketMessage;
+
+const FIXTURE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TapeMode {
+    #[default]
+    Off,
+    // Capture each query and the bridge's response
+    Record,
+    // Answer queries from the loaded fixture without touching the network
+    Replay,
+}
+
+impl TapeMode {
+    pub fn parse(name: &str) -> Result<TapeMode, String> {
+        match name {
+            "off" => Ok(TapeMode::Off),
+            "record" => Ok(TapeMode::Record),
+            "replay" => Ok(TapeMode::Replay),
+            other => Err(format!("Unknown fixture mode '{}'. Valid modes: off, record, replay", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TapeMode::Off => "off",
+            TapeMode::Record => "record",
+            TapeMode::Replay => "replay",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedQuery {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<Value>,
+    // The bridge's `result` or `error` message, without its id
+    #[serde(rename = "type")]
+    pub response_type: String,
+    pub response: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FixtureFile {
+    version: u32,
+    queries: Vec<RecordedQuery>,
+}
+
+fn key(sql: &str, params: &[Value]) -> String {
+    format!("{}\u{0}{}", sql, Value::Array(params.to_vec()))
+}
+
+// Query/response pairs captured from a real bridge and replayed in the same order,
+// matched on sql plus params
+#[derive(Default)]
+pub struct QueryTape {
+    mode: TapeMode,
+    queries: Vec<RecordedQuery>,
+    // Recordings per sql+params key, and how many of them have been replayed
+    index: HashMap<String, (Vec<usize>, usize)>,
+}
+
+impl QueryTape {
+    pub fn mode(&self) -> TapeMode {
+        self.mode
+    }
+
+    // Entering record mode starts a fresh recording; replay needs a loaded fixture
+    pub fn set_mode(&mut self, mode: TapeMode) {
+        if mode == TapeMode::Record {
+            self.queries.clear();
+        }
+        self.mode = mode;
+        self.reindex();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mode == TapeMode::Record
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.mode == TapeMode::Replay
+    }
+
+    pub fn record(&mut self, sql: &str, params: Option<Vec<Value>>, response: &WebSocketMessage) {
+        if !self.is_recording() {
+            return;
+        }
+        self.queries.push(RecordedQuery {
+            sql: sql.to_string(),
+            params: params.unwrap_or_default(),
+            response_type: response.message_type.clone(),
+            response: response.payload.clone(),
+        });
+    }
+
+    // The next recorded response for this query; repeats the last one once a
+    // query has been replayed more often than it was recorded
+    pub fn replay(&mut self, sql: &str, params: Option<&[Value]>) -> Result<WebSocketMessage, BridgeError> {
+        let Some((positions, replayed)) = self.index.get_mut(&key(sql, params.unwrap_or_default())) else {
+            return Err(BridgeError::from_error_payload(
+                &serde_json::json!({
+                    "message": format!("No recorded response for query: {}", sql),
+                    "code": "FIXTURE_MISS",
+                }),
+                None,
+            ));
+        };
+        let recorded = &self.queries[positions[(*replayed).min(positions.len() - 1)]];
+        *replayed += 1;
+        Ok(WebSocketMessage::new(&recorded.response_type, recorded.response.clone(), None))
+    }
+
+    pub fn export(&self) -> String {
+        let file = FixtureFile { version: FIXTURE_VERSION, queries: self.queries.clone() };
+        serde_json::to_string_pretty(&file).unwrap_or_default()
+    }
+
+    // Load a fixture exported by `export` and switch to replay
+    pub fn load(&mut self, json: &str) -> Result<usize, String> {
+        let file: FixtureFile = serde_json::from_str(json).map_err(|e| format!("Invalid query fixture: {}", e))?;
+        if file.version > FIXTURE_VERSION {
+            return Err(format!("Unsupported query fixture version {}", file.version));
+        }
+        self.queries = file.queries;
+        self.mode = TapeMode::Replay;
+        self.reindex();
+        Ok(self.queries.len())
+    }
+
+    fn reindex(&mut self) {
+        self.index.clear();
+        for (position, query) in self.queries.iter().enumerate() {
+            self.index.entry(key(&query.sql, &query.params)).or_default().0.push(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(n: i64) -> WebSocketMessage {
+        WebSocketMessage::result(Some("q".to_string()), json!({ "rows": [{ "n": n }] }))
+    }
+
+    #[test]
+    fn test_record_export_and_replay_in_order() {
+        let mut tape = QueryTape::default();
+        tape.set_mode(TapeMode::Record);
+        tape.record("SELECT $1", Some(vec![json!(1)]), &result(1));
+        tape.record("SELECT $1", Some(vec![json!(1)]), &result(2));
+        tape.record("SELECT $1", Some(vec![json!(2)]), &result(3));
+
+        let mut replay = QueryTape::default();
+        assert_eq!(replay.load(&tape.export()).unwrap(), 3);
+        let rows = |m: WebSocketMessage| m.payload["rows"][0]["n"].clone();
+        let params = [json!(1)];
+        assert_eq!(rows(replay.replay("SELECT $1", Some(&params)).unwrap()), json!(1));
+        assert_eq!(rows(replay.replay("SELECT $1", Some(&params)).unwrap()), json!(2));
+        assert_eq!(rows(replay.replay("SELECT $1", Some(&params)).unwrap()), json!(2));
+        assert_eq!(rows(replay.replay("SELECT $1", Some(&[json!(2)])).unwrap()), json!(3));
+    }
+
+    #[test]
+    fn test_replay_miss_and_modes() {
+        let mut tape = QueryTape::default();
+        tape.record("SELECT 1", None, &result(1));
+        assert!(tape.export().contains("\"queries\": []"));
+        let miss = tape.replay("SELECT 1", None).unwrap_err();
+        assert_eq!(miss.code(), Some("FIXTURE_MISS"));
+        assert_eq!(TapeMode::parse("replay"), Ok(TapeMode::Replay));
+        assert!(TapeMode::parse("rewind").is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use bridge_protocol::{is_restart_sql_state, ShutdownPayload};
+use serde::Serialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// What `on_server_restarting` hears: a bridge announcing its shutdown, or the database
+// failing a query because it is going down or not back yet
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RestartNotice {
+    // "bridge" or "database"
+    pub source: &'static str,
+    // The SQLSTATE, for database restarts
+    pub code: Option<String>,
+    pub reason: String,
+    #[serde(rename = "retryAfterMs")]
+    pub retry_after_ms: Option<f64>,
+}
+
+impl RestartNotice {
+    pub fn from_shutdown(payload: &Value) -> RestartNotice {
+        let shutdown: Option<ShutdownPayload> = serde_json::from_value(payload.clone()).ok();
+        RestartNotice {
+            source: "bridge",
+            code: None,
+            reason: shutdown.as_ref().map_or_else(|| "The bridge is shutting down".to_string(), |s| s.reason.clone()),
+            retry_after_ms: shutdown.and_then(|s| s.retry_after_ms).map(|ms| ms as f64),
+        }
+    }
+
+    // A restart notice for error payloads carrying an admin-shutdown SQLSTATE
+    pub fn from_error(payload: &Value) -> Option<RestartNotice> {
+        let code = payload.get("sqlState").and_then(|code| code.as_str()).filter(|code| is_restart_sql_state(code))?;
+        Some(RestartNotice {
+            source: "database",
+            code: Some(code.to_string()),
+            reason: payload.get("message").and_then(|m| m.as_str()).unwrap_or("The database is restarting").to_string(),
+            retry_after_ms: None,
+        })
+    }
+
+    // What requests cut short by the restart fail with
+    pub fn error(&self, query_id: Option<String>) -> BridgeError {
+        let message = format!("Server is restarting: {}", self.reason);
+        BridgeError::from_error_payload(&json!({ "message": message, "code": "SERVER_RESTARTING", "detail": self.code }), query_id)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RestartState {
+    pub hook: Option<js_sys::Function>,
+    // From the bridge's shutdown notice until its connection closes
+    pub bridge: Option<RestartNotice>,
+    // A database restart was reported and no query has succeeded since; repeats of it
+    // aren't announced again
+    pub database: bool,
+}
+
+// Tell the hook, called without the state borrowed
+fn announce(state: &SharedState, notice: &RestartNotice) {
+    log_warn!("WASM {} restarting: {}", notice.source, notice.reason);
+    let hook = state.borrow().restart.hook.clone();
+    if let (Some(hook), Ok(notice)) = (hook, crate::to_js(notice)) {
+        let _ = hook.call1(&JsValue::NULL, &notice);
+    }
+}
+
+// The bridge is going away: fail what it will never answer and close our end. The close
+// then reconnects after the bridge's suggested delay.
+pub(crate) fn bridge_shutdown(state: &SharedState, payload: &Value) {
+    let notice = RestartNotice::from_shutdown(payload);
+    announce(state, &notice);
+    let transport = {
+        let mut state = state.borrow_mut();
+        let reconnecting = state.will_reconnect();
+        state.interrupt_pending(&notice.error(None), reconnecting);
+        state.restart.bridge = Some(notice);
+        state.transport.take()
+    };
+    if let Some(transport) = transport {
+        transport.close();
+    }
+}
+
+// Check a query's error payload for a database restart, announcing the first one. Returns
+// the error the query should fail with instead of the generic one.
+pub(crate) fn observe_error(state: &SharedState, payload: &Value, query_id: Option<String>) -> Option<BridgeError> {
+    let notice = RestartNotice::from_error(payload)?;
+    let first = !std::mem::replace(&mut state.borrow_mut().restart.database, true);
+    if first {
+        announce(state, &notice);
+    }
+    Some(notice.error(query_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notices() {
+        let bridge = RestartNotice::from_shutdown(&json!({"reason": "Deploying", "retryAfterMs": 5000}));
+        assert_eq!(bridge.source, "bridge");
+        assert_eq!(bridge.retry_after_ms, Some(5000.0));
+
+        let error = json!({"message": "terminating connection due to administrator command", "code": "DATABASE_ERROR", "sqlState": "57P01"});
+        let database = RestartNotice::from_error(&error).unwrap();
+        assert_eq!(database.code.as_deref(), Some("57P01"));
+        assert_eq!(database.error(Some("q1".to_string())).code(), Some("SERVER_RESTARTING"));
+        assert_eq!(RestartNotice::from_error(&json!({"sqlState": "40001"})), None);
+    }
+}
@@ -0,0 +1,106 @@
+use bridge_protocol::message_type;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::json;
+use tsify::Tsify;
+
+use crate::errors::BridgeError;
+
+// Limits on a single decoded result, set with `set_result_budget`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResultBudget {
+    // Size of the result frame, once reassembled and decompressed
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: Option<usize>,
+    #[serde(rename = "maxRows")]
+    pub max_rows: Option<usize>,
+}
+
+// Just enough of a frame to hold it against the budget. The rows are skipped without
+// being built: a Vec of IgnoredAny only counts them.
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    message_type: String,
+    id: Option<String>,
+    #[serde(default)]
+    payload: Rows,
+}
+
+#[derive(Deserialize, Default)]
+struct Rows {
+    #[serde(default)]
+    rows: Vec<IgnoredAny>,
+}
+
+impl ResultBudget {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_bytes == Some(0) || self.max_rows == Some(0) {
+            return Err("maxBytes and maxRows must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_rows.is_none()
+    }
+
+    // What a result of this size goes over, if anything
+    pub fn exceeded(&self, bytes: usize, rows: usize) -> Option<String> {
+        if let Some(max) = self.max_bytes.filter(|max| bytes > *max) {
+            return Some(format!("{} bytes is over the {} byte budget", bytes, max));
+        }
+        if let Some(max) = self.max_rows.filter(|max| rows > *max) {
+            return Some(format!("{} rows is over the {} row budget", rows, max));
+        }
+        None
+    }
+
+    // The request to fail, and its error, when `text` is a result over budget. Runs before
+    // the frame is decoded, so an oversized result is never built.
+    pub fn check(&self, text: &str) -> Option<(String, BridgeError)> {
+        if self.is_unlimited() {
+            return None;
+        }
+        let envelope = serde_json::from_str::<Envelope>(text).ok().filter(|e| e.message_type == message_type::RESULT)?;
+        let reason = self.exceeded(text.len(), envelope.payload.rows.len())?;
+        let id = envelope.id?;
+        Some((id.clone(), too_large(&reason, Some(id))))
+    }
+}
+
+pub fn too_large(reason: &str, query_id: Option<String>) -> BridgeError {
+    let message = format!(
+        "Result too large: {}. Fetch it in pages with paginate() or stream it with open_cursor()",
+        reason
+    );
+    BridgeError::from_error_payload(&json!({ "message": message, "code": "RESULT_TOO_LARGE" }), query_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_counts_rows_without_decoding() {
+        let budget = ResultBudget { max_bytes: None, max_rows: Some(2) };
+        let small = r#"{"type":"result","id":"q1","payload":{"rows":[{"a":1},{"a":2}],"rowCount":2}}"#;
+        let large = r#"{"type":"result","id":"q2","payload":{"rows":[{"a":1},{"a":2},{"a":3}],"rowCount":3}}"#;
+        assert!(budget.check(small).is_none());
+        let (id, error) = budget.check(large).unwrap();
+        assert_eq!(id, "q2");
+        assert_eq!(error.code(), Some("RESULT_TOO_LARGE"));
+        // Only results are held to the budget
+        assert!(budget.check(r#"{"type":"notification","payload":{"rows":[1,2,3]}}"#).is_none());
+    }
+
+    #[test]
+    fn test_byte_budget() {
+        let budget = ResultBudget { max_bytes: Some(64), max_rows: None };
+        let frame = format!(r#"{{"type":"result","id":"q1","payload":{{"rows":["{}"]}}}}"#, "x".repeat(100));
+        assert_eq!(budget.check(&frame).map(|(id, _)| id).as_deref(), Some("q1"));
+        assert!(ResultBudget::default().is_unlimited());
+        assert!(ResultBudget { max_bytes: Some(0), max_rows: None }.validate().is_err());
+    }
+}
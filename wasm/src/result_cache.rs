@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+use crate::hashing::hash_hex;
+use crate::idb;
+use crate::null_policy::NullPolicy;
+
+// A query result persisted in IndexedDB. SQL and params are stored alongside the
+// payload so a hash collision is detected instead of served.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedResult {
+    pub sql: String,
+    pub params: Option<Vec<Value>>,
+    pub payload: Value,
+    #[serde(rename = "storedAt")]
+    pub stored_at: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Stale,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "hit",
+            CacheStatus::Stale => "stale",
+            CacheStatus::Miss => "miss",
+        }
+    }
+}
+
+pub fn cache_key(sql: &str, params: &Option<Vec<Value>>) -> String {
+    let params = params.as_ref().map_or("null".to_string(), |p| Value::from(p.clone()).to_string());
+    format!("q_{}", hash_hex(format!("{}\u{0}{}", sql, params).as_bytes()))
+}
+
+impl CachedResult {
+    pub fn matches(&self, sql: &str, params: &Option<Vec<Value>>) -> bool {
+        self.sql == sql && &self.params == params
+    }
+
+    pub fn status(&self, now: f64, ttl_ms: f64) -> CacheStatus {
+        if now - self.stored_at <= ttl_ms {
+            CacheStatus::Hit
+        } else {
+            CacheStatus::Stale
+        }
+    }
+}
+
+async fn lookup(key: &str, sql: &str, params: &Option<Vec<Value>>) -> Option<CachedResult> {
+    match idb::get(idb::RESULTS_STORE, key).await {
+        Ok(Some(json)) => serde_json::from_str::<CachedResult>(&json).ok().filter(|c| c.matches(sql, params)),
+        Ok(None) => None,
+        Err(e) => {
+            log_warn!("WASM result cache read failed: {:?}", e);
+            None
+        }
+    }
+}
+
+// Execute the query and store its result; cache write failures are only logged
+async fn fetch_and_store(state: &SharedState, key: &str, sql: &str, params: Option<Vec<Value>>) -> Result<Value, JsValue> {
+    let payload = connection::execute_query(state, sql, params.clone()).await?;
+    let entry = CachedResult {
+        sql: sql.to_string(),
+        params,
+        payload: payload.clone(),
+        stored_at: js_sys::Date::now(),
+    };
+    match serde_json::to_string(&entry) {
+        Ok(json) => {
+            if let Err(e) = idb::put(idb::RESULTS_STORE, key, &json).await {
+                log_warn!("WASM result cache write failed: {:?}", e);
+            }
+        }
+        Err(e) => log_warn!("WASM result cache entry not serializable: {}", e),
+    }
+    Ok(payload)
+}
+
+fn present(mut payload: Value, status: CacheStatus, null_policy: NullPolicy) -> Result<JsValue, JsValue> {
+    if let Some(Value::Array(rows)) = payload.get_mut("rows") {
+        null_policy.apply_to_rows(rows);
+    }
+    if let Value::Object(map) = &mut payload {
+        map.insert("cacheStatus".to_string(), Value::String(status.as_str().to_string()));
+    }
+    null_policy.to_js(&payload)
+}
+
+// Stale-while-revalidate: fresh entries resolve from the cache, stale entries resolve
+// from the cache while a background refresh runs and reports to `on_revalidate`
+pub(crate) async fn cached_query(
+    state: SharedState,
+    sql: String,
+    params: Option<Vec<Value>>,
+    ttl_ms: f64,
+    null_policy: NullPolicy,
+    on_revalidate: Option<js_sys::Function>,
+) -> Result<JsValue, JsValue> {
+    let key = cache_key(&sql, &params);
+    let cached = match lookup(&key, &sql, &params).await {
+        Some(cached) => cached,
+        None => {
+            let payload = fetch_and_store(&state, &key, &sql, params).await?;
+            return present(payload, CacheStatus::Miss, null_policy);
+        }
+    };
+
+    let status = cached.status(js_sys::Date::now(), ttl_ms);
+    if status == CacheStatus::Stale {
+        wasm_bindgen_futures::spawn_local(async move {
+            match fetch_and_store(&state, &key, &sql, params).await {
+                Ok(payload) => {
+                    if let (Some(callback), Ok(fresh)) = (on_revalidate, present(payload, CacheStatus::Miss, null_policy)) {
+                        let _ = callback.call1(&JsValue::NULL, &fresh);
+                    }
+                }
+                Err(e) => log_warn!("WASM background revalidation failed: {:?}", e),
+            }
+        });
+    }
+    present(cached.payload, status, null_policy)
+}
+
+pub(crate) async fn clear() -> Result<(), JsValue> {
+    idb::clear(idb::RESULTS_STORE).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_key_depends_on_params() {
+        let a = cache_key("SELECT $1", &Some(vec![json!(1)]));
+        let b = cache_key("SELECT $1", &Some(vec![json!("1")]));
+        assert_ne!(a, b);
+        assert_eq!(a, cache_key("SELECT $1", &Some(vec![json!(1)])));
+        assert_ne!(cache_key("SELECT 1", &None), cache_key("SELECT 1", &Some(Vec::new())));
+    }
+
+    #[test]
+    fn test_status_by_age() {
+        let cached = CachedResult {
+            sql: "SELECT 1".to_string(),
+            params: None,
+            payload: json!({"rows": []}),
+            stored_at: 1_000.0,
+        };
+        assert_eq!(cached.status(1_500.0, 1_000.0), CacheStatus::Hit);
+        assert_eq!(cached.status(2_500.0, 1_000.0), CacheStatus::Stale);
+        assert!(cached.matches("SELECT 1", &None));
+        assert!(!cached.matches("SELECT 2", &None));
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::errors::BridgeError;
+
+// Raw result `columns` the bridge has sent on this session, by `schemaId`. A result with
+// a shape sent before carries only the id, and gets its columns back from here.
+#[derive(Debug, Default)]
+pub(crate) struct SchemaCache {
+    columns: HashMap<String, Value>,
+}
+
+impl SchemaCache {
+    // A fresh bridge session numbers shapes from scratch
+    pub fn clear(&mut self) {
+        self.columns.clear();
+    }
+
+    // Remember the columns a result came with, or fill them in from its id
+    pub fn resolve(&mut self, result: &mut Value) -> Result<(), String> {
+        let Some(id) = result.get("schemaId").and_then(Value::as_str).map(String::from) else {
+            return Ok(());
+        };
+        match result.get("columns") {
+            Some(columns) => {
+                self.columns.insert(id, columns.clone());
+            }
+            None => match self.columns.get(&id) {
+                Some(columns) => result["columns"] = columns.clone(),
+                None => return Err(format!("The bridge referred to result shape '{}', which this connection was never sent", id)),
+            },
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn unknown(reason: String, id: Option<String>) -> JsValue {
+    BridgeError::from_error_payload(&json!({ "message": reason, "code": "UNKNOWN_SCHEMA" }), id).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fills_in_known_shapes() {
+        let mut cache = SchemaCache::default();
+        let columns = json!([{ "name": "id", "typeOid": 23, "format": 1 }]);
+        let mut first = json!({ "rows": [], "columns": columns, "schemaId": "schema_1" });
+        cache.resolve(&mut first).unwrap();
+        let mut again = json!({ "rows": [], "schemaId": "schema_1" });
+        cache.resolve(&mut again).unwrap();
+        assert_eq!(again["columns"], columns);
+
+        let mut plain = json!({ "rows": [] });
+        cache.resolve(&mut plain).unwrap();
+        assert!(plain.get("columns").is_none());
+        cache.clear();
+        assert!(cache.resolve(&mut json!({ "schemaId": "schema_1" })).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Token the bridge issued for this client's session, kept in sessionStorage so a
+// reloaded page can reattach to the same backend
+#[derive(Debug, Default)]
+pub struct Resumption {
+    // Where the token is stored; None while resumption is off
+    storage_key: Option<String>,
+    token: Option<String>,
+    // Whether the current connection picked up a parked session
+    pub resumed: bool,
+}
+
+impl Resumption {
+    pub fn enable(&mut self, storage_key: String, token: Option<String>) {
+        self.storage_key = Some(storage_key);
+        self.token = token;
+    }
+
+    // Returns the storage key the token should be removed from
+    pub fn disable(&mut self) -> Option<String> {
+        self.token = None;
+        self.resumed = false;
+        self.storage_key.take()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.storage_key.is_some()
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    // Take the bridge's answer to `hello`; returns the key and token to persist when the
+    // token changed
+    pub fn accept(&mut self, token: Option<String>, resumed: bool) -> Option<(String, String)> {
+        self.resumed = resumed;
+        let key = self.storage_key.clone()?;
+        let token = token?;
+        if self.token.as_deref() == Some(token.as_str()) {
+            return None;
+        }
+        self.token = Some(token.clone());
+        Some((key, token))
+    }
+}
+
+// Missing in workers, Node and Deno; the token then only lives as long as the client
+fn session_storage() -> Option<JsValue> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("sessionStorage"))
+        .ok()
+        .filter(|storage| !storage.is_undefined() && !storage.is_null())
+}
+
+fn call(method: &str, args: &[&str]) -> Option<JsValue> {
+    let storage = session_storage()?;
+    let function: js_sys::Function = js_sys::Reflect::get(&storage, &JsValue::from_str(method)).ok()?.dyn_into().ok()?;
+    let args: js_sys::Array = args.iter().map(|arg| JsValue::from_str(arg)).collect();
+    function.apply(&storage, &args).ok()
+}
+
+pub(crate) fn load(key: &str) -> Option<String> {
+    call("getItem", &[key]).and_then(|token| token.as_string())
+}
+
+pub(crate) fn save(key: &str, token: &str) {
+    call("setItem", &[key, token]);
+}
+
+pub(crate) fn forget(key: &str) {
+    call("removeItem", &[key]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_persists_new_tokens_only() {
+        let mut resumption = Resumption::default();
+        assert_eq!(resumption.accept(Some("abc".to_string()), false), None);
+
+        resumption.enable("pg-bridge-session:ws://db".to_string(), Some("abc".to_string()));
+        assert_eq!(resumption.accept(Some("abc".to_string()), true), None);
+        assert!(resumption.resumed);
+        let saved = resumption.accept(Some("def".to_string()), false);
+        assert_eq!(saved, Some(("pg-bridge-session:ws://db".to_string(), "def".to_string())));
+        assert_eq!(resumption.token(), Some("def"));
+
+        assert_eq!(resumption.disable().as_deref(), Some("pg-bridge-session:ws://db"));
+        assert_eq!(resumption.token(), None);
+    }
+}
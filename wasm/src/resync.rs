@@ -0,0 +1,86 @@
+use serde::Serialize;
+use serde_json::json;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// How much of the garbled frame a resync quotes
+const FRAME_EXCERPT: usize = 200;
+
+// Detail of the `resync` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Resync {
+    pub reason: String,
+    // Requests failed because their answer may have been the garbled frame
+    pub failed: Vec<String>,
+    // Whether the stream was out of step and the client reconnected to exchange `hello`
+    // again
+    pub renegotiated: bool,
+    pub frame: String,
+}
+
+// The top-level `id` of a frame that is JSON but not a bridge message. Text that isn't
+// JSON at all can't be attributed: an `"id"` found in it may belong to a row.
+pub fn frame_id(text: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    value.get("id").and_then(|id| id.as_str()).map(String::from)
+}
+
+// Requests a malformed frame leaves unanswerable: the one it names, or every pending
+// request when it names none, since any of them may have been waiting on it
+pub fn unanswerable(frame_id: Option<&str>, pending: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut ids: Vec<String> = match frame_id {
+        Some(id) => pending.into_iter().filter(|pending| pending == id).collect(),
+        None => pending.into_iter().collect(),
+    };
+    ids.sort();
+    ids
+}
+
+// Recover from a frame that couldn't be parsed instead of leaving its request hanging.
+// A frame that still names its request only fails that request. One that doesn't means
+// the stream is out of step: partial chunks are dropped, every pending request fails
+// with PROTOCOL_DESYNC and the client reconnects, since the bridge takes one `hello` per
+// connection.
+pub(crate) fn recover(state: &SharedState, text: &str, reason: &str) {
+    let id = frame_id(text);
+    let renegotiate = id.is_none();
+    let failed = {
+        let mut state = state.borrow_mut();
+        let failed = unanswerable(id.as_deref(), state.pending.keys().cloned().collect::<Vec<_>>());
+        let message = format!("Protocol desynchronized: {}", reason);
+        for id in &failed {
+            let error = BridgeError::from_error_payload(&json!({ "message": message, "code": "PROTOCOL_DESYNC" }), Some(id.clone()));
+            state.fail_request(id, error);
+        }
+        if renegotiate {
+            state.framing.assembler.clear();
+            state.connection_stats.resynced();
+        }
+        failed
+    };
+    let renegotiated = renegotiate && state.borrow().is_connected();
+    log_warn!("WASM malformed frame ({}); failed {} requests{}", reason, failed.len(), if renegotiated { ", resynchronizing" } else { "" });
+    if renegotiated {
+        crate::transport::drop_connection(state);
+    }
+    let resync = Resync { reason: reason.to_string(), failed, renegotiated, frame: text.chars().take(FRAME_EXCERPT).collect() };
+    crate::events::emit(state, "resync", &resync);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attributes_frames_to_requests() {
+        assert_eq!(frame_id(r#"{"type":7,"id":"q1"}"#).as_deref(), Some("q1"));
+        assert_eq!(frame_id(r#"{"type":"result","payload":{"rows":[{"id":"#), None);
+        assert_eq!(frame_id("garbage"), None);
+
+        let pending = || vec!["q2".to_string(), "q1".to_string(), "p3".to_string()];
+        assert_eq!(unanswerable(Some("q1"), pending()), vec!["q1"]);
+        assert!(unanswerable(Some("late"), pending()).is_empty());
+        assert_eq!(unanswerable(None, pending()), vec!["p3", "q1", "q2"]);
+    }
+}
@@ -0,0 +1,181 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+// Serialization failure, deadlock detected, admin shutdown
+const DEFAULT_RETRYABLE_SQL_STATES: [&str; 3] = ["40001", "40P01", "57P01"];
+
+// Failures after which re-running the whole transaction can succeed
+const TRANSACTION_CONFLICT_SQL_STATES: [&str; 2] = ["40001", "40P01"];
+
+fn default_retryable() -> Vec<String> {
+    DEFAULT_RETRYABLE_SQL_STATES.iter().map(|s| s.to_string()).collect()
+}
+
+// How read queries and whole transactions that fail with a transient SQLSTATE are retried
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Total attempts including the first; 1 disables retries
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: u32,
+    #[serde(rename = "baseDelayMs")]
+    pub base_delay_ms: f64,
+    #[serde(rename = "maxDelayMs")]
+    pub max_delay_ms: f64,
+    #[serde(rename = "retryableSqlStates")]
+    pub retryable_sql_states: Vec<String>,
+    // Total runs of a transaction that hits a serialization failure or deadlock
+    #[serde(rename = "transactionAttempts")]
+    pub transaction_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 100.0,
+            max_delay_ms: 2000.0,
+            retryable_sql_states: default_retryable(),
+            transaction_attempts: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Whether a failure on attempt `attempt` (1-based) with `sql_state` gets another try
+    pub fn should_retry(&self, attempt: u32, sql_state: Option<&str>) -> bool {
+        attempt < self.max_attempts
+            && sql_state.is_some_and(|code| self.retryable_sql_states.iter().any(|s| s == code))
+    }
+
+    // Whether a transaction that failed on run `attempt` with `sql_state` is re-run
+    pub fn should_retry_transaction(&self, attempt: u32, sql_state: Option<&str>) -> bool {
+        attempt < self.transaction_attempts && sql_state.is_some_and(|code| TRANSACTION_CONFLICT_SQL_STATES.contains(&code))
+    }
+
+    // Exponential backoff capped at `max_delay_ms`; `jitter` in [0, 1) spreads the
+    // wait over its upper half so clients failing together don't retry together
+    pub fn delay_ms(&self, attempt: u32, jitter: f64) -> f64 {
+        let exponential = self.base_delay_ms * 2f64.powi(attempt.saturating_sub(1) as i32);
+        exponential.min(self.max_delay_ms) * (0.5 + jitter / 2.0)
+    }
+}
+
+// Resolve after `ms` milliseconds using the host's setTimeout
+pub(crate) async fn sleep(ms: f64) -> Result<(), JsValue> {
+    let set_timeout: js_sys::Function = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))?.dyn_into()?;
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        let _ = set_timeout.call2(&JsValue::NULL, &resolve, &JsValue::from_f64(ms));
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await?;
+    Ok(())
+}
+
+// Run `attempt` until it succeeds, or fails in a way `policy` doesn't re-run a
+// transaction for. A failure carries the SQLSTATE it ended with; `pause` waits before
+// the next run, given the run that failed and its SQLSTATE.
+pub(crate) async fn rerun_transaction<T, E, A, P>(
+    policy: &RetryPolicy,
+    mut attempt: impl FnMut() -> A,
+    mut pause: impl FnMut(u32, String) -> P,
+) -> Result<T, E>
+where
+    A: Future<Output = Result<T, (Option<String>, E)>>,
+    P: Future<Output = Result<(), E>>,
+{
+    let mut run = 1;
+    loop {
+        let (sql_state, error) = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(failure) => failure,
+        };
+        if !policy.should_retry_transaction(run, sql_state.as_deref()) {
+            return Err(error);
+        }
+        pause(run, sql_state.unwrap_or_default()).await?;
+        run += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::task::{Context, Poll, Waker};
+
+    // Drive a future that never waits
+    fn finish<F: Future>(future: F) -> F::Output {
+        match std::pin::pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future waited"),
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(1, Some("40001")));
+        assert!(policy.should_retry(2, Some("40P01")));
+        assert!(!policy.should_retry(3, Some("40001")));
+        assert!(!policy.should_retry(1, Some("23505")));
+        assert!(!policy.should_retry(1, None));
+
+        let disabled: RetryPolicy = serde_json::from_str(r#"{"maxAttempts": 1}"#).unwrap();
+        assert!(!disabled.should_retry(1, Some("40001")));
+        assert_eq!(disabled.retryable_sql_states, default_retryable());
+    }
+
+    #[test]
+    fn test_should_retry_transaction() {
+        let policy = RetryPolicy { transaction_attempts: 2, ..RetryPolicy::default() };
+        assert!(policy.should_retry_transaction(1, Some("40P01")));
+        assert!(!policy.should_retry_transaction(2, Some("40001")));
+        // The session is gone after an admin shutdown, so the transaction can't be re-run in place
+        assert!(!policy.should_retry_transaction(1, Some("57P01")));
+    }
+
+    #[test]
+    fn test_rerun_transaction_until_it_commits() {
+        let policy = RetryPolicy::default();
+        let runs = Cell::new(0);
+        let pauses = Cell::new(0);
+        let conflict_twice = || {
+            runs.set(runs.get() + 1);
+            let outcome = if runs.get() < 3 { Err((Some("40001".to_string()), "conflict")) } else { Ok(runs.get()) };
+            std::future::ready(outcome)
+        };
+        let pause = |_, _| {
+            pauses.set(pauses.get() + 1);
+            std::future::ready(Ok(()))
+        };
+        assert_eq!(finish(rerun_transaction(&policy, conflict_twice, pause)), Ok(3));
+        assert_eq!(pauses.get(), 2);
+
+        // Other failures, and conflicts past `transactionAttempts`, end it
+        runs.set(0);
+        let runs = &runs;
+        let failing = |sql_state: &'static str| {
+            move || {
+                runs.set(runs.get() + 1);
+                std::future::ready(Err::<u32, _>((Some(sql_state.to_string()), sql_state)))
+            }
+        };
+        assert_eq!(finish(rerun_transaction(&policy, failing("23505"), pause)), Err("23505"));
+        assert_eq!(runs.get(), 1);
+        runs.set(0);
+        assert_eq!(finish(rerun_transaction(&policy, failing("40P01"), pause)), Err("40P01"));
+        assert_eq!(runs.get(), 3);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy { base_delay_ms: 100.0, max_delay_ms: 300.0, ..RetryPolicy::default() };
+        assert_eq!(policy.delay_ms(1, 1.0), 100.0);
+        assert_eq!(policy.delay_ms(2, 1.0), 200.0);
+        assert_eq!(policy.delay_ms(5, 1.0), 300.0);
+        assert_eq!(policy.delay_ms(2, 0.0), 100.0);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{execute_query, SharedState};
+use crate::sql::quote_ident;
+
+// Every overload of one function or procedure, with argument types as SQL spells them
+pub const ROUTINE_SQL: &str = "SELECT p.prokind::text AS kind, p.proretset AS returns_set, \
+    format_type(p.prorettype, NULL) AS return_type, \
+    coalesce(p.proargnames, ARRAY[]::text[]) AS arg_names, \
+    coalesce(p.proargmodes::text[], ARRAY[]::text[]) AS arg_modes, \
+    ARRAY(SELECT format_type(t, NULL) FROM unnest(coalesce(p.proallargtypes, p.proargtypes::oid[])) WITH ORDINALITY AS a(t, n) ORDER BY n) AS arg_types, \
+    p.pronargdefaults AS defaults \
+    FROM pg_proc p JOIN pg_namespace n ON n.oid = p.pronamespace \
+    WHERE n.nspname = $1 AND p.proname = $2 \
+    ORDER BY p.oid";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgMode {
+    In,
+    Out,
+    InOut,
+    Variadic,
+    // A column of `RETURNS TABLE (...)`
+    Table,
+}
+
+impl ArgMode {
+    fn parse(mode: &str) -> ArgMode {
+        match mode {
+            "o" => ArgMode::Out,
+            "b" => ArgMode::InOut,
+            "v" => ArgMode::Variadic,
+            "t" => ArgMode::Table,
+            _ => ArgMode::In,
+        }
+    }
+
+    pub fn is_input(self) -> bool {
+        matches!(self, ArgMode::In | ArgMode::InOut | ArgMode::Variadic)
+    }
+
+    pub fn is_output(self) -> bool {
+        matches!(self, ArgMode::Out | ArgMode::InOut | ArgMode::Table)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RoutineArg {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub mode: ArgMode,
+}
+
+// One overload, as `describe_routine` reports it
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Routine {
+    pub procedure: bool,
+    #[serde(rename = "returnsSet")]
+    pub returns_set: bool,
+    #[serde(rename = "returnType")]
+    pub return_type: String,
+    pub args: Vec<RoutineArg>,
+    // How many trailing inputs have defaults and may be left out
+    pub defaults: usize,
+}
+
+#[derive(Deserialize)]
+struct RoutineRow {
+    kind: String,
+    returns_set: bool,
+    return_type: String,
+    arg_names: Vec<String>,
+    arg_modes: Vec<String>,
+    arg_types: Vec<String>,
+    defaults: usize,
+}
+
+impl From<RoutineRow> for Routine {
+    fn from(row: RoutineRow) -> Routine {
+        let args = row
+            .arg_types
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| RoutineArg {
+                name: row.arg_names.get(i).filter(|name| !name.is_empty()).cloned(),
+                ty,
+                mode: row.arg_modes.get(i).map_or(ArgMode::In, |mode| ArgMode::parse(mode)),
+            })
+            .collect();
+        Routine { procedure: row.kind == "p", returns_set: row.returns_set, return_type: row.return_type, args, defaults: row.defaults }
+    }
+}
+
+// Arguments of a call: in order, or by name
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallArgs {
+    Positional(Vec<Value>),
+    Named(Map<String, Value>),
+}
+
+impl Routine {
+    fn inputs(&self) -> impl Iterator<Item = &RoutineArg> {
+        self.args.iter().filter(|arg| arg.mode.is_input())
+    }
+
+    fn outputs(&self) -> usize {
+        self.args.iter().filter(|arg| arg.mode.is_output()).count()
+    }
+
+    // Whether `args` can be passed to this overload
+    pub fn accepts(&self, args: &CallArgs) -> bool {
+        let inputs: Vec<&RoutineArg> = self.inputs().collect();
+        let required = inputs.len().saturating_sub(self.defaults);
+        match args {
+            CallArgs::Positional(values) => (required..=inputs.len()).contains(&values.len()),
+            CallArgs::Named(values) => {
+                values.keys().all(|key| inputs.iter().any(|arg| arg.name.as_deref() == Some(key.as_str())))
+                    && inputs[..required].iter().all(|arg| arg.name.as_ref().is_some_and(|name| values.contains_key(name)))
+            }
+        }
+    }
+
+    // `name(argtype, ...)`, for error messages
+    pub fn signature(&self, name: &str) -> String {
+        let inputs: Vec<&str> = self.inputs().map(|arg| arg.ty.as_str()).collect();
+        format!("{}({})", name, inputs.join(", "))
+    }
+
+    // The `SELECT * FROM` or `CALL` statement and its parameters. Every argument is cast
+    // to its declared type, which also picks this overload over the others.
+    pub fn call_sql(&self, schema: &str, name: &str, args: &CallArgs) -> Result<(String, Vec<Value>), String> {
+        if !self.accepts(args) {
+            return Err(format!("{} does not take these arguments", self.signature(name)));
+        }
+        let mut params = Vec::new();
+        let mut bind = |arg: &RoutineArg, value: &Value| {
+            params.push(value.clone());
+            let variadic = if arg.mode == ArgMode::Variadic { "VARIADIC " } else { "" };
+            format!("{}${}::{}", variadic, params.len(), arg.ty)
+        };
+        let mut list = Vec::new();
+        match args {
+            CallArgs::Positional(values) => {
+                let mut values = values.iter();
+                let mut omitted = false;
+                for arg in &self.args {
+                    if arg.mode.is_input() {
+                        match values.next() {
+                            Some(value) => list.push(bind(arg, value)),
+                            None => omitted = true,
+                        }
+                    } else if self.procedure && arg.mode == ArgMode::Out && !omitted {
+                        // Procedures take a placeholder for each OUT parameter
+                        list.push(format!("NULL::{}", arg.ty));
+                    }
+                }
+            }
+            CallArgs::Named(values) => {
+                for arg in &self.args {
+                    let Some(arg_name) = &arg.name else {
+                        continue;
+                    };
+                    if arg.mode.is_input() {
+                        if let Some(value) = values.get(arg_name) {
+                            let bound = bind(arg, value);
+                            list.push(format!("{} => {}", quote_ident(arg_name), bound));
+                        }
+                    } else if self.procedure && arg.mode == ArgMode::Out {
+                        list.push(format!("{} => NULL::{}", quote_ident(arg_name), arg.ty));
+                    }
+                }
+            }
+        }
+        let target = format!("{}.{}({})", quote_ident(schema), quote_ident(name), list.join(", "));
+        let sql = if self.procedure { format!("CALL {}", target) } else { format!("SELECT * FROM {}", target) };
+        Ok((sql, params))
+    }
+
+    // What a call resolves with: the rows of a set-returning function, the row of
+    // OUT parameters when there are several, or else the one value
+    pub fn shape(&self, mut rows: Vec<Value>) -> Value {
+        if self.returns_set {
+            return Value::Array(rows);
+        }
+        let Some(row) = (!rows.is_empty()).then(|| rows.swap_remove(0)) else {
+            return Value::Null;
+        };
+        if self.procedure || self.outputs() > 1 {
+            return row;
+        }
+        match row {
+            Value::Object(columns) if columns.len() == 1 => columns.into_iter().next().map(|(_, value)| value).unwrap_or_default(),
+            row => row,
+        }
+    }
+}
+
+// Signatures by "schema.name", so each routine is introspected once per session
+#[derive(Default)]
+pub(crate) struct RoutineCache {
+    routines: HashMap<String, Vec<Routine>>,
+}
+
+impl RoutineCache {
+    // After a schema change, a signature may no longer be what the database has
+    pub fn clear(&mut self) {
+        self.routines.clear();
+    }
+}
+
+// Every overload of `schema.name`, from the cache or pg_proc
+pub(crate) async fn describe(state: &SharedState, schema: &str, name: &str) -> Result<Vec<Routine>, JsValue> {
+    let key = format!("{}.{}", schema, name);
+    if let Some(routines) = state.borrow().routines.routines.get(&key) {
+        return Ok(routines.clone());
+    }
+    let result = execute_query(state, ROUTINE_SQL, Some(vec![Value::from(schema), Value::from(name)])).await?;
+    let rows = result.get("rows").cloned().unwrap_or(Value::Array(Vec::new()));
+    let rows: Vec<RoutineRow> = serde_json::from_value(rows).map_err(|e| JsValue::from_str(&format!("Unexpected routine introspection result: {}", e)))?;
+    let routines: Vec<Routine> = rows.into_iter().map(Routine::from).collect();
+    state.borrow_mut().routines.routines.insert(key, routines.clone());
+    Ok(routines)
+}
+
+// Call the overload of `schema.name` that `args` fits
+pub(crate) async fn call(state: &SharedState, schema: &str, name: &str, procedure: bool, args: CallArgs) -> Result<Value, JsValue> {
+    let kind = if procedure { "procedure" } else { "function" };
+    let routines: Vec<Routine> = describe(state, schema, name).await?.into_iter().filter(|r| r.procedure == procedure).collect();
+    if routines.is_empty() {
+        return Err(JsValue::from_str(&format!("No {} {}.{}", kind, schema, name)));
+    }
+    let fitting: Vec<&Routine> = routines.iter().filter(|r| r.accepts(&args)).collect();
+    let routine = match fitting.as_slice() {
+        [routine] => *routine,
+        [] => {
+            let signatures: Vec<String> = routines.iter().map(|r| r.signature(name)).collect();
+            return Err(JsValue::from_str(&format!("No overload of {}.{} takes these arguments; it has {}", schema, name, signatures.join(", "))));
+        }
+        _ => {
+            let signatures: Vec<String> = fitting.iter().map(|r| r.signature(name)).collect();
+            return Err(JsValue::from_str(&format!("Ambiguous call to {}.{}: {} all fit; pass arguments by name", schema, name, signatures.join(", "))));
+        }
+    };
+    let (sql, params) = routine.call_sql(schema, name, &args).map_err(|e| JsValue::from_str(&e))?;
+    let result = execute_query(state, &sql, Some(params)).await?;
+    let rows = result.get("rows").and_then(Value::as_array).cloned().unwrap_or_default();
+    Ok(routine.shape(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn routine(kind: &str, names: &[&str], modes: &[&str], types: &[&str], defaults: usize, returns_set: bool) -> Routine {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        let row = RoutineRow {
+            kind: kind.to_string(),
+            returns_set,
+            return_type: "record".to_string(),
+            arg_names: strings(names),
+            arg_modes: strings(modes),
+            arg_types: strings(types),
+            defaults,
+        };
+        Routine::from(row)
+    }
+
+    #[test]
+    fn test_builds_calls_and_shapes_results() {
+        let search = routine("f", &["term", "max_rows", "id", "title"], &["i", "i", "t", "t"], &["text", "integer", "bigint", "text"], 1, true);
+        let (sql, params) = search.call_sql("app", "search", &CallArgs::Positional(vec![json!("rust")])).unwrap();
+        assert_eq!(sql, r#"SELECT * FROM "app"."search"($1::text)"#);
+        assert_eq!(params, vec![json!("rust")]);
+        let named = CallArgs::Named(serde_json::from_value(json!({ "max_rows": 5, "term": "rust" })).unwrap());
+        let (sql, _) = search.call_sql("app", "search", &named).unwrap();
+        assert_eq!(sql, r#"SELECT * FROM "app"."search"("term" => $1::text, "max_rows" => $2::integer)"#);
+        assert!(!search.accepts(&CallArgs::Positional(vec![])));
+        assert!(!search.accepts(&CallArgs::Named(serde_json::from_value(json!({ "nope": 1 })).unwrap())));
+        assert_eq!(search.shape(vec![json!({ "id": 1 })]), json!([{ "id": 1 }]));
+
+        let transfer = routine("p", &["src", "dst", "balance"], &["i", "i", "o"], &["bigint", "bigint", "numeric"], 0, false);
+        let (sql, _) = transfer.call_sql("public", "transfer", &CallArgs::Positional(vec![json!(1), json!(2)])).unwrap();
+        assert_eq!(sql, r#"CALL "public"."transfer"($1::bigint, $2::bigint, NULL::numeric)"#);
+        assert_eq!(transfer.shape(vec![json!({ "balance": "10.00" })]), json!({ "balance": "10.00" }));
+
+        let scalar = routine("f", &[], &[], &["integer"], 0, false);
+        assert_eq!(scalar.shape(vec![json!({ "double_it": 4 })]), json!(4));
+        assert_eq!(scalar.signature("double_it"), "double_it(integer)");
+    }
+}
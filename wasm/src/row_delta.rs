@@ -0,0 +1,125 @@
+use bridge_protocol::{PatchOp, PatchOperation, RowDelta, RowDeltaPayload};
+use serde_json::{json, Value};
+
+use crate::connection::SharedState;
+use crate::optimistic::OptimisticPatch;
+
+// Position `token` names in an array of `len` items; `end` allows one past the last
+fn array_index(token: &str, len: usize, end: bool) -> Result<usize, String> {
+    if end && token == "-" {
+        return Ok(len);
+    }
+    let index: usize = token.parse().map_err(|_| format!("'{}' is not an array index", token))?;
+    let limit = if end { len + 1 } else { len };
+    if index >= limit {
+        return Err(format!("index {} is out of bounds", index));
+    }
+    Ok(index)
+}
+
+fn apply_operation(document: &mut Value, operation: &PatchOperation) -> Result<(), String> {
+    let (parent, token) = operation.path.rsplit_once('/').ok_or_else(|| format!("'{}' is not a JSON pointer", operation.path))?;
+    let token = token.replace("~1", "/").replace("~0", "~");
+    let value = || operation.value.clone().ok_or_else(|| format!("{:?} at {} needs a value", operation.op, operation.path));
+    let target = document.pointer_mut(parent).ok_or_else(|| format!("{} does not exist", operation.path))?;
+    match (operation.op, target) {
+        (PatchOp::Remove, Value::Object(map)) => map.remove(&token).map(drop).ok_or_else(|| format!("{} does not exist", operation.path)),
+        (PatchOp::Remove, Value::Array(items)) => {
+            items.remove(array_index(&token, items.len(), false)?);
+            Ok(())
+        }
+        (PatchOp::Replace, Value::Object(map)) if !map.contains_key(&token) => Err(format!("{} does not exist", operation.path)),
+        (PatchOp::Add | PatchOp::Replace, Value::Object(map)) => {
+            map.insert(token, value()?);
+            Ok(())
+        }
+        (PatchOp::Add, Value::Array(items)) => {
+            let index = array_index(&token, items.len(), true)?;
+            items.insert(index, value()?);
+            Ok(())
+        }
+        (PatchOp::Replace, Value::Array(items)) => {
+            let index = array_index(&token, items.len(), false)?;
+            items[index] = value()?;
+            Ok(())
+        }
+        _ => Err(format!("{} is not inside an object or array", operation.path)),
+    }
+}
+
+// Apply a delta to a result payload. Upserts and deletes match rows on `key`, so they
+// need the key columns of a diffing watch; patch paths are relative to the payload.
+pub fn apply(result: &mut Value, delta: &RowDelta, key: Option<&[String]>) -> Result<(), String> {
+    if !delta.upsert.is_empty() || !delta.delete.is_empty() {
+        let key = key.ok_or("upserts and deletes need the watch's key columns")?;
+        let patch = OptimisticPatch { table: String::new(), key: key.to_vec(), upsert: delta.upsert.clone(), delete: delta.delete.clone() };
+        if patch.upsert.iter().chain(&patch.delete).any(|row| crate::diff::row_key(row, key).is_none()) {
+            return Err(format!("every delta row needs the key column(s) {}", key.join(", ")));
+        }
+        patch.apply(result);
+    }
+    for operation in &delta.patch {
+        apply_operation(result, operation)?;
+    }
+    let count = result.get("rows").and_then(Value::as_array).map(Vec::len);
+    if let (Some(count), Some(row_count)) = (count, result.get_mut("rowCount")) {
+        *row_count = Value::from(count);
+    }
+    Ok(())
+}
+
+// A `row_delta` message: patch the last result of every live query on the channel and
+// deliver it, re-running those the delta can't be applied to. Returns the NOTIFY as a
+// notification for everything else listening on the channel.
+pub(crate) fn received(state: &SharedState, payload: &Value) -> Option<Value> {
+    let RowDeltaPayload { channel, delta } = serde_json::from_value(payload.clone()).ok()?;
+    let ids = state.borrow().watches.affected(&channel);
+    for id in ids {
+        let patched = {
+            let mut state = state.borrow_mut();
+            let Some(watch) = state.watches.get_mut(id) else {
+                continue;
+            };
+            // A run in flight may already miss the delta; let it go again instead
+            match watch.last.clone().filter(|_| !watch.run.is_running()) {
+                Some(mut next) => {
+                    let key = watch.diff.as_ref().map(|diff| diff.key_columns.as_slice());
+                    apply(&mut next, &delta, key).map(|()| next)
+                }
+                None => Err("no result to patch yet".to_string()),
+            }
+        };
+        match patched {
+            Ok(next) => crate::live::present(state, id, next),
+            Err(e) => {
+                log_debug!("WASM re-running live query {} instead of applying a row delta: {}", id, e);
+                wasm_bindgen_futures::spawn_local(crate::live::run(state.clone(), id));
+            }
+        }
+    }
+    let payload = serde_json::to_string(&delta).unwrap_or_default();
+    Some(json!({ "channel": channel, "payload": payload }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_applies_rows_and_patches() {
+        let mut result = json!({ "rows": [{ "id": 1, "n": "a" }, { "id": 2, "n": "b" }], "rowCount": 2 });
+        let key = vec!["id".to_string()];
+        let delta = RowDelta::parse(r#"{"upsert": [{"id": 3, "n": "c"}], "delete": [{"id": 1}], "patch": [{"op": "replace", "path": "/rows/0/n", "value": "B"}]}"#).unwrap();
+        apply(&mut result, &delta, Some(&key)).unwrap();
+        assert_eq!(result, json!({ "rows": [{ "id": 2, "n": "B" }, { "id": 3, "n": "c" }], "rowCount": 2 }));
+
+        let append = RowDelta::parse(r#"{"patch": [{"op": "add", "path": "/rows/-", "value": {"id": 4}}]}"#).unwrap();
+        apply(&mut result, &append, None).unwrap();
+        assert_eq!(result["rowCount"], 3);
+
+        // Keyed changes can't be placed without key columns, nor paths that don't exist
+        assert!(apply(&mut result, &delta, None).is_err());
+        let missing = RowDelta::parse(r#"{"patch": [{"op": "remove", "path": "/rows/9"}]}"#).unwrap();
+        assert!(apply(&mut result, &missing, None).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::errors::BridgeError;
+
+// A field computed from a row's columns
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Derived {
+    // The columns as text, joined by `separator`; nulls are skipped
+    Concat {
+        columns: Vec<String>,
+        #[serde(default)]
+        separator: String,
+    },
+    // The sum of the numeric columns; null if any of them is null
+    Sum { columns: Vec<String> },
+    // The first column that isn't null
+    Coalesce { columns: Vec<String> },
+}
+
+// Reshapes each result row in WASM before it is handed to JS. Fields are computed from
+// the original columns first, then `omit` drops columns and `rename` renames them.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct RowTransform {
+    // Old column name to new
+    pub rename: BTreeMap<String, String>,
+    pub omit: Vec<String>,
+    pub computed: BTreeMap<String, Derived>,
+}
+
+// A query's `transform`: the name of a registered transform, or one given inline
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(untagged)]
+pub enum TransformRef {
+    Named(String),
+    Inline(RowTransform),
+}
+
+// A transform registered with `register_row_transform`, which may also call a JS
+// function with each row as it is converted
+#[derive(Debug, Clone, Default)]
+pub struct Registered {
+    pub transform: RowTransform,
+    pub map: Option<js_sys::Function>,
+}
+
+pub type Registry = HashMap<String, Registered>;
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl Derived {
+    fn value(&self, row: &Map<String, Value>) -> Value {
+        let null = Value::Null;
+        match self {
+            Derived::Concat { columns, separator } => {
+                let parts: Vec<String> = columns.iter().filter_map(|c| row.get(c)).filter(|v| !v.is_null()).map(as_text).collect();
+                Value::String(parts.join(separator))
+            }
+            Derived::Sum { columns } => {
+                // Numeric columns arrive as strings to keep their precision
+                let numbers: Option<Vec<f64>> = columns
+                    .iter()
+                    .map(|c| match row.get(c).unwrap_or(&null) {
+                        Value::Number(n) => n.as_f64(),
+                        Value::String(s) => s.trim().parse().ok(),
+                        _ => None,
+                    })
+                    .collect();
+                numbers.map_or(Value::Null, |numbers| json!(numbers.iter().sum::<f64>()))
+            }
+            Derived::Coalesce { columns } => columns.iter().filter_map(|c| row.get(c)).find(|v| !v.is_null()).cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl RowTransform {
+    pub fn apply(&self, rows: &mut [Value]) {
+        for row in rows.iter_mut() {
+            if let Value::Object(map) = row {
+                self.apply_to_row(map);
+            }
+        }
+    }
+
+    fn apply_to_row(&self, row: &mut Map<String, Value>) {
+        let computed: Vec<(String, Value)> = self.computed.iter().map(|(name, derived)| (name.clone(), derived.value(row))).collect();
+        row.extend(computed);
+        for column in &self.omit {
+            row.remove(column);
+        }
+        for (from, to) in &self.rename {
+            if let Some(value) = row.remove(from) {
+                row.insert(to.clone(), value);
+            }
+        }
+    }
+}
+
+// The transform a query asked for, looked up in the registry when it is named
+pub fn resolve(registry: &Registry, transform: Option<TransformRef>) -> Result<Option<Registered>, JsValue> {
+    match transform {
+        None => Ok(None),
+        Some(TransformRef::Inline(transform)) => Ok(Some(Registered { transform, map: None })),
+        Some(TransformRef::Named(name)) => match registry.get(&name) {
+            Some(registered) => Ok(Some(registered.clone())),
+            None => {
+                let payload = json!({ "message": format!("No row transform is registered as '{}'", name), "code": "UNKNOWN_TRANSFORM" });
+                Err(BridgeError::from_error_payload(&payload, None).into())
+            }
+        },
+    }
+}
+
+// Replace each row of a converted result with what `map(row, index)` returns
+pub fn map_rows(result: &JsValue, map: &js_sys::Function) -> Result<(), JsValue> {
+    let rows = js_sys::Reflect::get(result, &JsValue::from_str("rows"))?;
+    let Some(rows) = rows.dyn_ref::<js_sys::Array>() else {
+        return Ok(());
+    };
+    for index in 0..rows.length() {
+        let mapped = map.call2(&JsValue::NULL, &rows.get(index), &JsValue::from(index))?;
+        rows.set(index, mapped);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computes_then_omits_and_renames() {
+        let transform: RowTransform = serde_json::from_value(json!({
+            "rename": { "first_name": "firstName" },
+            "omit": ["last_name", "price"],
+            "computed": {
+                "fullName": { "concat": { "columns": ["first_name", "middle", "last_name"], "separator": " " } },
+                "total": { "sum": { "columns": ["price", "tax"] } },
+                "nickname": { "coalesce": { "columns": ["nick", "first_name"] } },
+            },
+        }))
+        .unwrap();
+        let mut rows = vec![json!({ "first_name": "Ada", "middle": null, "last_name": "Lovelace", "nick": null, "price": "10.5", "tax": 2 })];
+        transform.apply(&mut rows);
+        assert_eq!(
+            rows[0],
+            json!({ "firstName": "Ada", "middle": null, "nick": null, "tax": 2, "fullName": "Ada Lovelace", "total": 12.5, "nickname": "Ada" })
+        );
+
+        let named: TransformRef = serde_json::from_value(json!("people")).unwrap();
+        assert_eq!(named, TransformRef::Named("people".to_string()));
+        assert!(serde_json::from_value::<RowTransform>(json!({ "renames": {} })).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use wasm_bindgen::prelude::*;
+
+// JavaScript host the module is running in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Browser,
+    Worker,
+    Node,
+    Deno,
+    Edge,
+    Unknown,
+}
+
+impl Runtime {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Runtime::Browser => "browser",
+            Runtime::Worker => "worker",
+            Runtime::Node => "node",
+            Runtime::Deno => "deno",
+            Runtime::Edge => "edge",
+            Runtime::Unknown => "unknown",
+        }
+    }
+}
+
+fn global_has(name: &str) -> bool {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str(name))
+        .map(|value| !value.is_undefined())
+        .unwrap_or(false)
+}
+
+fn is_node() -> bool {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("process"))
+        .and_then(|process| js_sys::Reflect::get(&process, &JsValue::from_str("versions")))
+        .and_then(|versions| js_sys::Reflect::get(&versions, &JsValue::from_str("node")))
+        .map(|node| node.is_string())
+        .unwrap_or(false)
+}
+
+// Deno is checked first: it also defines `window` and, in compat mode, `process`.
+// `WebSocketPair` only exists in Cloudflare Workers (workerd).
+pub fn detect() -> Runtime {
+    if global_has("Deno") {
+        Runtime::Deno
+    } else if global_has("WebSocketPair") {
+        Runtime::Edge
+    } else if is_node() {
+        Runtime::Node
+    } else if global_has("document") {
+        Runtime::Browser
+    } else if global_has("importScripts") {
+        Runtime::Worker
+    } else {
+        Runtime::Unknown
+    }
+}
+
+// Name of the detected host: "browser", "worker", "node", "deno", "edge" or "unknown"
+#[wasm_bindgen]
+pub fn detect_runtime() -> String {
+    detect().as_str().to_string()
+}
+
+// Whether persistent storage (offline outbox, result cache) is available here
+#[wasm_bindgen]
+pub fn supports_indexeddb() -> bool {
+    cfg!(feature = "indexeddb") && global_has("indexedDB")
+}
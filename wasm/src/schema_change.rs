@@ -0,0 +1,79 @@
+use bridge_protocol::{message_type, DeallocatePayload, SchemaChange};
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+
+// Options accepted by `on_schema_change`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaChangeOptions {
+    // Drop what the change may have made stale: the bridge's kept prepared statements
+    // and cached results whose SQL names the changed object
+    pub invalidate: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct SchemaChangeState {
+    pub hook: Option<js_sys::Function>,
+    pub invalidate: bool,
+}
+
+// The name SQL would use for the changed object: "public.orders" is "orders", and
+// "public.touch(integer)" is "touch". None when the whole query cache should go.
+pub fn object_name(change: &SchemaChange) -> Option<String> {
+    let object = change.object.as_deref()?;
+    let object = object.split('(').next().unwrap_or(object);
+    let name = object.rsplit('.').next().unwrap_or(object).trim_matches('"');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// A `schema_change` message from the bridge: invalidate if asked to, then tell the hook
+// and `schemachange` listeners
+pub(crate) fn received(state: &SharedState, payload: &Value) {
+    let Ok(change) = serde_json::from_value::<SchemaChange>(payload.clone()) else {
+        return;
+    };
+    log_info!("WASM schema changed: {} {}", change.command_tag, change.object.as_deref().unwrap_or_default());
+    state.borrow_mut().routines.clear();
+    let (hook, invalidate) = {
+        let state = state.borrow();
+        (state.schema_change.hook.clone(), state.schema_change.invalidate)
+    };
+    if invalidate {
+        let dropped = state.borrow_mut().query_cache.invalidate(object_name(&change).as_deref());
+        log_debug!("WASM dropped {} cached results after a schema change", dropped);
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let payload = serde_json::to_value(DeallocatePayload { handle: None }).unwrap_or_default();
+            if let Err(e) = connection::request(&state, "deallocate", message_type::DEALLOCATE, payload).await {
+                log_warn!("WASM failed to deallocate statements after a schema change: {:?}", e);
+            }
+        });
+    }
+    if let (Some(hook), Ok(change)) = (hook, crate::to_js(&change)) {
+        let _ = hook.call1(&JsValue::NULL, &change);
+    }
+    crate::events::emit(state, "schemachange", &change);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_name() {
+        let change = |object: Option<&str>| SchemaChange {
+            command_tag: "ALTER TABLE".to_string(),
+            object_type: None,
+            schema: None,
+            object: object.map(String::from),
+        };
+        assert_eq!(object_name(&change(Some("public.orders"))).as_deref(), Some("orders"));
+        assert_eq!(object_name(&change(Some("public.touch(integer)"))).as_deref(), Some("touch"));
+        assert_eq!(object_name(&change(Some("\"Sales\".\"Orders\""))).as_deref(), Some("Orders"));
+        assert_eq!(object_name(&change(None)), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::connection::{self, SharedState};
+use crate::errors::BridgeError;
+use crate::sql::quote_qualified;
+
+// Setting row-level security policies read the caller's claims from, following the
+// PostgREST convention: `current_setting('request.jwt.claims', true)::json`
+pub const RLS_CONTEXT_SETTING: &str = "request.jwt.claims";
+
+pub const STATEMENT_TIMEOUT_SETTING: &str = "statement_timeout";
+
+pub const TIME_ZONE_SETTING: &str = "TimeZone";
+
+// Setting tenant-scoped policies and views read the `tenantId` config field from
+pub const TENANT_SETTING: &str = "app.tenant_id";
+
+// How long a query waits for a fresh connection's setup to finish
+const SETUP_TIMEOUT_MS: f64 = 10_000.0;
+const SETUP_POLL_MS: f64 = 25.0;
+
+// Session settings (GUCs) applied through the client. The bridge's database session
+// ends with the connection, so these are replayed after every reconnect.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SessionState {
+    settings: BTreeMap<String, String>,
+    // Whether the current connection has been brought back to that state
+    pub restored: bool,
+}
+
+impl SessionState {
+    pub fn set(&mut self, name: &str, value: Option<String>) {
+        match value {
+            Some(value) => self.settings.insert(name.to_string(), value),
+            None => self.settings.remove(name),
+        };
+    }
+
+    pub fn settings(&self) -> &BTreeMap<String, String> {
+        &self.settings
+    }
+
+    // One statement re-applying every setting, or None when there are none
+    pub fn restore_statement(&self) -> Option<(String, Vec<Value>)> {
+        if self.settings.is_empty() {
+            return None;
+        }
+        let mut params = Vec::new();
+        let calls: Vec<String> = self
+            .settings
+            .iter()
+            .map(|(name, value)| {
+                params.push(json!(name));
+                params.push(json!(value));
+                format!("set_config(${}, ${}, false)", params.len() - 1, params.len())
+            })
+            .collect();
+        Some((format!("SELECT {}", calls.join(", ")), params))
+    }
+}
+
+// Apply a setting for the rest of the session, or RESET it when `value` is None
+pub(crate) async fn apply_setting(state: &SharedState, name: &str, value: Option<String>) -> Result<(), JsValue> {
+    match &value {
+        Some(value) => {
+            connection::execute_query(state, "SELECT set_config($1, $2, false)", Some(vec![json!(name), json!(value)])).await?;
+        }
+        None => {
+            connection::execute_query(state, &format!("RESET {}", quote_qualified(name)), None).await?;
+        }
+    }
+    state.borrow_mut().session.set(name, value);
+    Ok(())
+}
+
+// Re-establish what the previous connection had set up: the configured setup SQL,
+// session settings, LISTEN channels, and fresh results for live queries that may have
+// missed notifications. A resumed bridge session still has all but the last.
+pub(crate) async fn restore(state: &SharedState) {
+    let resumed = state.borrow().resumption.resumed;
+    // Pin first, so the setup SQL below lands on the backend the session keeps
+    let pinned = state.borrow().pinned && !resumed;
+    if pinned {
+        if let Err(e) = connection::request(state, "pin", bridge_protocol::message_type::PIN, Value::Null).await {
+            log_warn!("WASM failed to pin the session again: {:?}", e);
+        }
+    }
+    let setup = if resumed { Vec::new() } else { state.borrow().on_connect_sql.clone() };
+    for sql in &setup {
+        let options = connection::QueryOptions { internal: true, ..Default::default() };
+        if let Err(e) = connection::execute_query_with(state, sql, None, options).await {
+            log_warn!("WASM on-connect statement failed: {:?}", e);
+        }
+    }
+    let statement = state.borrow().session.restore_statement().filter(|_| !resumed);
+    if let Some((sql, params)) = statement {
+        match connection::execute_query(state, &sql, Some(params)).await {
+            Ok(_) => log_info!("WASM restored {} session settings", state.borrow().session.settings().len()),
+            Err(e) => log_warn!("WASM failed to restore session settings: {:?}", e),
+        }
+    }
+
+    let mut channels = if resumed { Vec::new() } else { state.borrow_mut().watches.reset_listening() };
+    // The resumed session kept its LISTENs
+    if resumed {
+        let listening = state.borrow().watches.listening();
+        for channel in listening {
+            crate::listen_state::update(state, &channel, crate::listen_state::ListenState::Active, None);
+        }
+    }
+    let named = {
+        let state = state.borrow();
+        state.named_subscriptions.channels(|id| state.notifications.contains(id))
+    };
+    for channel in state.borrow().subscriptions.held_channels().into_iter().chain(named) {
+        if !channels.contains(&channel) {
+            channels.push(channel);
+        }
+    }
+    if !resumed {
+        crate::cache_invalidation::reconnected(state);
+    }
+    if !channels.is_empty() {
+        match crate::live::ensure_listening(state, &channels).await {
+            Ok(()) => log_info!("WASM re-subscribed to {} notification channels", channels.len()),
+            Err(e) => log_warn!("WASM failed to re-subscribe notification channels: {:?}", e),
+        }
+    }
+    if !resumed {
+        crate::named_subscriptions::reconnected(state);
+    }
+    let watches = state.borrow().watches.ids();
+    for id in watches {
+        wasm_bindgen_futures::spawn_local(crate::live::run(state.clone(), id));
+    }
+    state.borrow_mut().session.restored = true;
+}
+
+// Hold a query issued while a fresh connection is still being set up, so setup SQL and
+// restored settings apply to it, or while the outbox is replaying, so it runs after the
+// writes queued before it
+pub(crate) async fn wait_until_restored(state: &SharedState) -> Result<(), JsValue> {
+    let deadline = js_sys::Date::now() + SETUP_TIMEOUT_MS;
+    loop {
+        {
+            let state = state.borrow();
+            if let Some(reason) = &state.protocol.refused {
+                return Err(BridgeError::connection(reason).into());
+            }
+            if (state.session.restored && !state.outbox.replaying) || !state.is_connected() {
+                return Ok(());
+            }
+        }
+        if js_sys::Date::now() >= deadline {
+            return Err(BridgeError::connection("Timed out waiting for the connection to be set up").into());
+        }
+        crate::retry::sleep(SETUP_POLL_MS).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_statement() {
+        let mut session = SessionState::default();
+        assert_eq!(session.restore_statement(), None);
+        session.set("app.tenant", Some("7".to_string()));
+        session.set(RLS_CONTEXT_SETTING, Some(r#"{"sub":"u1"}"#.to_string()));
+        session.set("app.removed", Some("x".to_string()));
+        session.set("app.removed", None);
+
+        let (sql, params) = session.restore_statement().unwrap();
+        assert_eq!(sql, "SELECT set_config($1, $2, false), set_config($3, $4, false)");
+        assert_eq!(params, vec![json!("app.tenant"), json!("7"), json!("request.jwt.claims"), json!(r#"{"sub":"u1"}"#)]);
+    }
+}
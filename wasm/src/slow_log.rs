@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+
+use bridge_protocol::QueryTags;
+use serde::Serialize;
+use serde_json::Value;
+use tsify::Tsify;
+
+use crate::fingerprint::fingerprint;
+use crate::hashing::hash_hex;
+
+pub const DEFAULT_SLOW_LOG_CAPACITY: usize = 100;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Tsify)]
+pub struct SlowQuery {
+    pub sql: String,
+    // Groups the entry with `query_stats()`
+    pub fingerprint: String,
+    #[serde(rename = "paramsHash")]
+    pub params_hash: String,
+    #[serde(rename = "executionTime")]
+    pub execution_time: f64,
+    pub timestamp: String,
+    // The query's `tags`, as the bridge echoed them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<QueryTags>,
+}
+
+// Ring buffer of queries slower than the configured threshold
+#[derive(Debug, Clone)]
+pub struct SlowQueryLog {
+    threshold_ms: Option<f64>,
+    capacity: usize,
+    entries: VecDeque<SlowQuery>,
+}
+
+impl Default for SlowQueryLog {
+    fn default() -> Self {
+        SlowQueryLog {
+            threshold_ms: None,
+            capacity: DEFAULT_SLOW_LOG_CAPACITY,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl SlowQueryLog {
+    pub fn set_threshold(&mut self, threshold_ms: Option<f64>) {
+        self.threshold_ms = threshold_ms;
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    // Record a result payload if it crossed the threshold, returning the new entry
+    pub fn observe(&mut self, result: &Value) -> Option<SlowQuery> {
+        let threshold = self.threshold_ms?;
+        let execution_time = result.get("executionTime")?.as_f64()?;
+        if execution_time < threshold {
+            return None;
+        }
+
+        let params = result.get("params").cloned().unwrap_or(Value::Array(Vec::new()));
+        let sql = result.get("sql").and_then(|s| s.as_str()).unwrap_or_default().to_string();
+        let entry = SlowQuery {
+            fingerprint: fingerprint(&sql),
+            sql,
+            params_hash: hash_hex(params.to_string().as_bytes()),
+            execution_time,
+            timestamp: result.get("timestamp").and_then(|t| t.as_str()).unwrap_or_default().to_string(),
+            tags: result.get("tags").and_then(|tags| serde_json::from_value(tags.clone()).ok()),
+        };
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry.clone());
+        Some(entry)
+    }
+
+    pub fn entries(&self) -> Vec<SlowQuery> {
+        self.entries.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(ms: f64) -> Value {
+        json!({"sql": "SELECT 1", "params": [1], "executionTime": ms, "timestamp": "t"})
+    }
+
+    #[test]
+    fn test_disabled_until_threshold_set() {
+        let mut log = SlowQueryLog::default();
+        assert!(log.observe(&result(5000.0)).is_none());
+        log.set_threshold(Some(100.0));
+        assert!(log.observe(&result(99.0)).is_none());
+        let entry = log.observe(&result(150.0)).unwrap();
+        assert_eq!(entry.sql, "SELECT 1");
+        assert_eq!(entry.fingerprint, fingerprint("select 2"));
+        assert_eq!(entry.params_hash, hash_hex(b"[1]"));
+        assert_eq!(entry.tags, None);
+        let mut tagged = result(150.0);
+        tagged["tags"] = json!({"feature": "orders-grid"});
+        let entry = log.observe(&tagged).unwrap();
+        assert_eq!(entry.tags.unwrap()["feature"], "orders-grid");
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut log = SlowQueryLog::default();
+        log.set_threshold(Some(0.0));
+        log.set_capacity(2);
+        for ms in [1.0, 2.0, 3.0] {
+            log.observe(&result(ms));
+        }
+        let times: Vec<f64> = log.entries().iter().map(|e| e.execution_time).collect();
+        assert_eq!(times, vec![2.0, 3.0]);
+    }
+}
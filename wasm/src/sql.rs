@@ -0,0 +1,89 @@
+use wasm_bindgen::prelude::*;
+
+// The statement lexer is shared with the bridge, which enforces the same policies
+pub use bridge_protocol::sql::{statements, tokenize, Token};
+
+// Quote an identifier following Postgres rules, doubling embedded quotes
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// Quote a possibly schema-qualified name such as `public.users`
+pub fn quote_qualified(name: &str) -> String {
+    name.split('.').map(quote_ident).collect::<Vec<_>>().join(".")
+}
+
+// Quote a string literal the way Postgres' quote_literal() does: embedded
+// quotes are doubled, and values containing backslashes use the E'' form
+pub fn quote_literal(value: &str) -> String {
+    let escaped = value.replace('\'', "''");
+    if escaped.contains('\\') {
+        format!("E'{}'", escaped.replace('\\', "\\\\"))
+    } else {
+        format!("'{}'", escaped)
+    }
+}
+
+fn reject_nul(value: &str, what: &str) -> Result<(), String> {
+    if value.contains('\0') {
+        Err(format!("{} cannot contain NUL characters", what))
+    } else {
+        Ok(())
+    }
+}
+
+#[wasm_bindgen(js_name = quote_ident)]
+pub fn quote_ident_checked(name: &str) -> Result<String, JsValue> {
+    reject_nul(name, "Identifier").map_err(|e| JsValue::from_str(&e))?;
+    if name.is_empty() {
+        return Err(JsValue::from_str("Identifier cannot be empty"));
+    }
+    Ok(quote_ident(name))
+}
+
+// Stacked statements are a common injection vector, so `query()` refuses them unless the
+// caller opts in
+pub fn single_statement(sql: &str) -> Result<(), String> {
+    match statements(sql).len() {
+        0 | 1 => Ok(()),
+        count => Err(format!(
+            "SQL contains {} statements; pass `multiStatement: true` or call allow_multi_statements(true) to run them",
+            count
+        )),
+    }
+}
+
+#[wasm_bindgen(js_name = quote_literal)]
+pub fn quote_literal_checked(value: &str) -> Result<String, JsValue> {
+    reject_nul(value, "Literal").map_err(|e| JsValue::from_str(&e))?;
+    Ok(quote_literal(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("users"), "\"users\"");
+        assert_eq!(quote_ident("we\"ird"), "\"we\"\"ird\"");
+        assert_eq!(quote_ident("zoë"), "\"zoë\"");
+        assert_eq!(quote_qualified("public.users"), "\"public\".\"users\"");
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quote_literal("plain"), "'plain'");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+        assert_eq!(quote_literal("a\\b'c"), "E'a\\\\b''c'");
+        assert_eq!(quote_literal("日本"), "'日本'");
+        assert!(reject_nul("a\0b", "Literal").is_err());
+    }
+
+    #[test]
+    fn test_single_statement() {
+        assert!(single_statement("SELECT ';' AS semi; -- trailing; comment").is_ok());
+        assert!(single_statement("DO $$ BEGIN PERFORM 1; END $$").is_ok());
+        assert!(single_statement("SELECT 1; DROP TABLE users").is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use serde::Deserialize;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::sql_validate::{lex, locate, Lex};
+
+// Options accepted by `format_sql`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatOptions {
+    #[serde(rename = "indentWidth")]
+    pub indent_width: usize,
+    #[serde(rename = "keywordCase")]
+    pub keyword_case: KeywordCase,
+    // Clauses longer than this are broken up, one item or condition per line
+    #[serde(rename = "maxLineLength")]
+    pub max_line_length: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { indent_width: 2, keyword_case: KeywordCase::Upper, max_line_length: 80 }
+    }
+}
+
+impl FormatOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.indent_width > 16 {
+            return Err("indentWidth must be at most 16".to_string());
+        }
+        if self.max_line_length < 20 {
+            return Err("maxLineLength must be at least 20".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum KeywordCase {
+    Upper,
+    Lower,
+    // Keep keywords as written
+    Preserve,
+}
+
+pub(crate) const KEYWORDS: &[&str] = &[
+    "all", "alter", "and", "any", "as", "asc", "begin", "between", "by", "case", "cast", "check", "coalesce", "commit",
+    "conflict", "constraint", "create", "cross", "default", "delete", "desc", "distinct", "do", "drop", "else", "end",
+    "except", "exists", "false", "filter", "first", "for", "foreign", "from", "full", "group", "having", "ilike", "in",
+    "index", "inner", "insert", "intersect", "into", "is", "join", "key", "last", "lateral", "left", "like", "limit",
+    "not", "nothing", "null", "nulls", "offset", "on", "or", "order", "outer", "over", "partition", "primary",
+    "recursive", "references", "returning", "right", "rollback", "select", "set", "table", "then", "true", "union",
+    "unique", "update", "using", "values", "view", "when", "where", "window", "with",
+];
+
+// Keywords written like function calls, without a space before their parenthesis
+const CALL_KEYWORDS: &[&str] = &["any", "cast", "coalesce", "exists", "filter", "over"];
+
+// Keywords that start a clause on its own line; longer forms come first
+const CLAUSES: &[&[&str]] = &[
+    &["with", "recursive"],
+    &["with"],
+    &["select"],
+    &["insert", "into"],
+    &["update"],
+    &["delete", "from"],
+    &["from"],
+    &["left", "outer", "join"],
+    &["left", "join"],
+    &["right", "outer", "join"],
+    &["right", "join"],
+    &["full", "outer", "join"],
+    &["full", "join"],
+    &["inner", "join"],
+    &["cross", "join"],
+    &["join"],
+    &["where"],
+    &["group", "by"],
+    &["having"],
+    &["window"],
+    &["order", "by"],
+    &["limit"],
+    &["offset"],
+    &["values"],
+    &["set"],
+    &["on", "conflict"],
+    &["returning"],
+    &["union", "all"],
+    &["union"],
+    &["intersect"],
+    &["except"],
+];
+
+// Clauses whose body is a comma-separated list, broken one item per line
+const LIST_CLAUSES: &[&str] = &["select", "group", "order", "values", "set", "returning", "with", "window"];
+// Clauses whose body is a condition, broken before each AND and OR
+const CONDITION_CLAUSES: &[&str] = &["where", "having", "join"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    // Lowercased, and as it should be written
+    Word(String, String),
+    Text(String),
+    Op(String),
+    Open,
+    Close,
+    Comma,
+    Dot,
+    Semicolon,
+    LineComment(String),
+}
+
+const OPERATOR_CHARS: &str = "+-*/<>=~!@#%^&|`?:";
+
+fn tokens(sql: &str, options: &FormatOptions) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let lexemes = lex(&chars, true).map_err(|(message, index)| {
+        let (line, column) = locate(&chars, index);
+        format!("Cannot format SQL: {} at line {}, column {}", message, line, column)
+    })?;
+    let mut tokens = Vec::new();
+    let mut operator_end = None;
+    for (lexeme, start) in lexemes {
+        let token = match lexeme {
+            Lex::Word(lower, text) => {
+                let text = match options.keyword_case {
+                    KeywordCase::Upper if KEYWORDS.contains(&lower.as_str()) => lower.to_uppercase(),
+                    KeywordCase::Lower if KEYWORDS.contains(&lower.as_str()) => lower.clone(),
+                    _ => text,
+                };
+                Tok::Word(lower, text)
+            }
+            Lex::Value(text) => Tok::Text(text),
+            Lex::Comment(text) if text.starts_with("--") => Tok::LineComment(text),
+            Lex::Comment(text) => Tok::Text(text),
+            Lex::Punct('(') => Tok::Open,
+            Lex::Punct(')') => Tok::Close,
+            Lex::Punct(',') => Tok::Comma,
+            Lex::Punct('.') => Tok::Dot,
+            Lex::Punct(';') => Tok::Semicolon,
+            Lex::Punct(c) if OPERATOR_CHARS.contains(c) => {
+                // Adjacent operator characters make up one operator, like `<=` or `::`
+                if operator_end == Some(start) {
+                    if let Some(Tok::Op(op)) = tokens.last_mut() {
+                        op.push(c);
+                        operator_end = Some(start + 1);
+                        continue;
+                    }
+                }
+                operator_end = Some(start + 1);
+                Tok::Op(c.to_string())
+            }
+            Lex::Punct(c) => Tok::Op(c.to_string()),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+// The clause starting at `tokens[0]`, as the number of words it takes
+fn clause_at(tokens: &[Tok]) -> Option<usize> {
+    CLAUSES.iter().find_map(|words| {
+        let matched = words.iter().enumerate().all(|(i, word)| matches!(tokens.get(i), Some(Tok::Word(w, _)) if w == word));
+        matched.then_some(words.len())
+    })
+}
+
+// Index of the parenthesis closing the one at `open`
+fn closing(tokens: &[Tok], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Tok::Open => depth += 1,
+            Tok::Close => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+// Split `tokens` before every top-level token `split` matches, keeping separators
+// with the part they start, or dropping them when `keep` is false
+fn split_top(tokens: &[Tok], split: impl Fn(&[Tok]) -> bool, keep: bool) -> Vec<&[Tok]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for i in 0..tokens.len() {
+        match tokens[i] {
+            Tok::Open => depth += 1,
+            Tok::Close => depth -= 1,
+            _ if depth == 0 && i > start && split(&tokens[i..]) => {
+                parts.push(&tokens[start..i]);
+                start = if keep { i } else { i + 1 };
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+// Whether the + or - at `index` is a sign rather than an operator
+fn is_sign(tokens: &[Tok], index: usize) -> bool {
+    match index.checked_sub(1).map(|i| &tokens[i]) {
+        None | Some(Tok::Open | Tok::Comma | Tok::Op(_)) => true,
+        Some(Tok::Word(w, _)) => KEYWORDS.contains(&w.as_str()),
+        _ => false,
+    }
+}
+
+struct Formatter {
+    options: FormatOptions,
+}
+
+impl Formatter {
+    fn indent(&self, level: usize) -> String {
+        " ".repeat(level * self.options.indent_width)
+    }
+
+    // Tokens on one line, except for subqueries and line comments
+    fn inline(&self, tokens: &[Tok], level: usize) -> String {
+        let mut out = String::new();
+        let mut previous: Option<&Tok> = None;
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let space = match (previous, token) {
+                (None, _) | (_, Tok::Comma | Tok::Close | Tok::Dot | Tok::Semicolon) => false,
+                (Some(Tok::Open | Tok::Dot), _) => false,
+                (Some(Tok::Op(op)), _) if op == "::" => false,
+                (_, Tok::Op(op)) if op == "::" => false,
+                (Some(Tok::Op(op)), _) if (op == "-" || op == "+") && is_sign(tokens, i - 1) => false,
+                (Some(Tok::Word(w, _)), Tok::Open) => KEYWORDS.contains(&w.as_str()) && !CALL_KEYWORDS.contains(&w.as_str()),
+                _ => true,
+            };
+            if space {
+                out.push(' ');
+            }
+            match token {
+                Tok::Open => {
+                    let close = closing(tokens, i);
+                    let inner = &tokens[i + 1..close.min(tokens.len())];
+                    if matches!(inner.first(), Some(Tok::Word(w, _)) if w == "select" || w == "with") {
+                        out.push_str("(\n");
+                        out.push_str(&self.statement(inner, level + 1));
+                        out.push('\n');
+                        out.push_str(&self.indent(level));
+                    } else {
+                        out.push('(');
+                        out.push_str(&self.inline(inner, level));
+                    }
+                    if close < tokens.len() {
+                        out.push(')');
+                    }
+                    previous = Some(&Tok::Close);
+                    i = close + 1;
+                    continue;
+                }
+                Tok::Word(_, text) | Tok::Text(text) | Tok::Op(text) => out.push_str(text),
+                Tok::LineComment(text) => {
+                    out.push_str(text);
+                    if i + 1 < tokens.len() {
+                        out.push('\n');
+                        out.push_str(&self.indent(level));
+                    }
+                    previous = None;
+                    i += 1;
+                    continue;
+                }
+                Tok::Close => out.push(')'),
+                Tok::Comma => out.push(','),
+                Tok::Dot => out.push('.'),
+                Tok::Semicolon => out.push(';'),
+            }
+            previous = Some(token);
+            i += 1;
+        }
+        out
+    }
+
+    fn fits(&self, line: &str, level: usize) -> bool {
+        !line.contains('\n') && level * self.options.indent_width + line.chars().count() <= self.options.max_line_length
+    }
+
+    fn clause(&self, keyword: &[Tok], body: &[Tok], level: usize) -> String {
+        let head = self.inline(keyword, level);
+        let indent = self.indent(level);
+        if body.is_empty() {
+            return format!("{}{}", indent, head);
+        }
+        let flat = self.inline(body, level);
+        let one_line = format!("{} {}", head, flat);
+        if self.fits(&one_line, level) {
+            return format!("{}{}", indent, one_line);
+        }
+
+        let inner = self.indent(level + 1);
+        // Every JOIN variant breaks like its ON condition
+        let kind = match (&keyword[0], keyword.last()) {
+            (_, Some(Tok::Word(w, _))) if w == "join" => "join",
+            (Tok::Word(w, _), _) => w.as_str(),
+            _ => "",
+        };
+        let lines: Vec<String> = if LIST_CLAUSES.contains(&kind) {
+            let items = split_top(body, |rest| rest[0] == Tok::Comma, false);
+            let count = items.len();
+            items
+                .into_iter()
+                .enumerate()
+                .map(|(n, item)| format!("{}{}{}", inner, self.inline(item, level + 1), if n + 1 < count { "," } else { "" }))
+                .collect()
+        } else if CONDITION_CLAUSES.contains(&kind) {
+            let is_connective = |rest: &[Tok]| matches!(&rest[0], Tok::Word(w, _) if w == "and" || w == "or");
+            split_top(body, is_connective, true).into_iter().map(|part| format!("{}{}", inner, self.inline(part, level + 1))).collect()
+        } else {
+            vec![format!("{}{}", inner, self.inline(body, level + 1))]
+        };
+        format!("{}{}\n{}", indent, head, lines.join("\n"))
+    }
+
+    // One statement, a clause per line
+    fn statement(&self, tokens: &[Tok], level: usize) -> String {
+        let mut clauses: Vec<(&[Tok], &[Tok])> = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        let mut keyword_len = 0;
+        let mut i = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                Tok::Open => depth += 1,
+                Tok::Close => depth -= 1,
+                _ if depth == 0 => {
+                    // WITH and UPDATE only start a clause at the head of a statement
+                    let head_only = matches!(&tokens[i], Tok::Word(w, _) if w == "with" || w == "update") && i > 0;
+                    let after_conflict = i > 0 && matches!(&tokens[i - 1], Tok::Word(w, _) if w == "do");
+                    if let Some(len) = clause_at(&tokens[i..]).filter(|_| !head_only || after_conflict) {
+                        if i > start || keyword_len > 0 {
+                            clauses.push((&tokens[start..start + keyword_len], &tokens[start + keyword_len..i]));
+                        }
+                        start = i;
+                        keyword_len = len;
+                        i += len;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        clauses.push((&tokens[start..start + keyword_len], &tokens[start + keyword_len..]));
+
+        clauses
+            .into_iter()
+            .filter(|(keyword, body)| !keyword.is_empty() || !body.is_empty())
+            .map(|(keyword, body)| match keyword.is_empty() {
+                true => format!("{}{}", self.indent(level), self.inline(body, level)),
+                false => self.clause(keyword, body, level),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// Reformat SQL a clause per line, breaking clauses that run past `maxLineLength` into one
+// item or condition per line. Literals, quoted names and comments are kept as written.
+pub fn format_sql(sql: &str, options: FormatOptions) -> Result<String, String> {
+    let tokens = tokens(sql, &options)?;
+    let formatter = Formatter { options };
+    let statements: Vec<String> = split_top(&tokens, |rest| rest[0] == Tok::Semicolon, false)
+        .into_iter()
+        .filter(|statement| !statement.is_empty())
+        .map(|statement| formatter.statement(statement, 0))
+        .collect();
+    let terminated = tokens.last() == Some(&Tok::Semicolon);
+    let mut out = statements.join(";\n\n");
+    if terminated {
+        out.push(';');
+    }
+    Ok(out)
+}
+
+#[wasm_bindgen(js_name = format_sql)]
+pub fn format_sql_js(sql: &str, #[wasm_bindgen(unchecked_optional_param_type = "FormatOptions | null")] options: JsValue) -> Result<String, JsValue> {
+    let options: FormatOptions = if options.is_undefined() || options.is_null() {
+        FormatOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid format options: {}", e)))?
+    };
+    options.validate().map_err(|e| JsValue::from_str(&format!("Invalid format options: {}", e)))?;
+    format_sql(sql, options).map_err(|e| JsValue::from_str(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_clauses_stay_on_one_line() {
+        let sql = "select id, name::text from users u where u.id = $1 and coalesce(u.age, -1) >= 18 order by name desc;";
+        assert_eq!(
+            format_sql(sql, FormatOptions::default()).unwrap(),
+            "SELECT id, name::text\nFROM users u\nWHERE u.id = $1 AND COALESCE(u.age, -1) >= 18\nORDER BY name DESC;"
+        );
+    }
+
+    #[test]
+    fn test_long_clauses_break_and_subqueries_indent() {
+        let options = FormatOptions { indent_width: 4, keyword_case: KeywordCase::Lower, max_line_length: 30 };
+        let sql = "SELECT first_name, last_name, email FROM people WHERE id IN (SELECT person_id FROM members) AND active -- only live ones\n";
+        assert_eq!(
+            format_sql(sql, options).unwrap(),
+            "select\n    first_name,\n    last_name,\n    email\nfrom people\nwhere\n    id in (\n        select person_id\n        from members\n    )\n    and active -- only live ones"
+        );
+        assert!(format_sql("SELECT 'oops", FormatOptions::default()).unwrap_err().contains("line 1, column 8"));
+    }
+}
@@ -0,0 +1,288 @@
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+// Words a Postgres statement can start with
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "abort", "alter", "analyze", "begin", "call", "checkpoint", "close", "cluster", "comment", "commit", "copy",
+    "create", "deallocate", "declare", "delete", "discard", "do", "drop", "end", "execute", "explain", "fetch",
+    "grant", "import", "insert", "listen", "load", "lock", "merge", "move", "notify", "prepare", "refresh",
+    "reindex", "release", "reset", "revoke", "rollback", "savepoint", "security", "select", "set", "show", "start",
+    "table", "truncate", "unlisten", "update", "vacuum", "values", "with",
+];
+
+// Words a comma can't come right before
+const CLAUSE_KEYWORDS: &[&str] = &["from", "where", "group", "having", "order", "limit", "offset", "returning", "values"];
+
+// Where validation found a problem, as Postgres reports it: a 1-based character position,
+// plus the line and column it falls on
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub position: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StatementInfo {
+    // Lowercased, e.g. "select" or "insert"; a WITH query takes the kind of its main statement
+    pub kind: String,
+    pub position: usize,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub valid: bool,
+    // Of the first statement
+    pub kind: Option<String>,
+    pub statements: Vec<StatementInfo>,
+    pub error: Option<SyntaxError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Lex {
+    // Lowercased, and as written
+    Word(String, String),
+    Punct(char),
+    // A literal, quoted identifier, number or parameter, as written
+    Value(String),
+    // Only kept for the formatter
+    Comment(String),
+}
+
+// A token and the character index it starts at
+pub(crate) type Lexeme = (Lex, usize);
+
+fn skip_quoted(chars: &[char], start: usize, quote: char, backslash_escapes: bool) -> Option<usize> {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if backslash_escapes && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == quote {
+            if chars.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+            return Some(i + 1);
+        }
+        i += 1;
+    }
+    None
+}
+
+// Tokens with their positions, or the first unterminated literal or comment
+pub(crate) fn lex(chars: &[char], keep_comments: bool) -> Result<Vec<Lexeme>, (String, usize)> {
+    let text = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let mut lexemes = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            if keep_comments {
+                lexemes.push((Lex::Comment(text(start, i)), start));
+            }
+        } else if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            loop {
+                if i >= chars.len() {
+                    return Err(("unterminated /* comment".to_string(), start));
+                }
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            if keep_comments {
+                lexemes.push((Lex::Comment(text(start, i)), start));
+            }
+        } else if c == '\'' || ((c == 'e' || c == 'E') && next == Some('\'')) {
+            let open = if c == '\'' { i } else { i + 1 };
+            i = skip_quoted(chars, open, '\'', c != '\'').ok_or(("unterminated quoted string".to_string(), start))?;
+            lexemes.push((Lex::Value(text(start, i)), start));
+        } else if c == '"' {
+            i = skip_quoted(chars, i, '"', false).ok_or(("unterminated quoted identifier".to_string(), start))?;
+            lexemes.push((Lex::Value(text(start, i)), start));
+        } else if c == '$' && next.is_some_and(|n| n.is_ascii_digit()) {
+            i += 1;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            lexemes.push((Lex::Value(text(start, i)), start));
+        } else if c == '$' {
+            let tag_end = (i + 1..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_'));
+            match tag_end.filter(|&j| chars[j] == '$') {
+                Some(j) => {
+                    let tag = &chars[i..=j];
+                    let close = (j + 1..chars.len()).find(|&k| chars[k..].starts_with(tag));
+                    i = close.ok_or(("unterminated dollar-quoted string".to_string(), start))? + tag.len();
+                    lexemes.push((Lex::Value(text(start, i)), start));
+                }
+                None => {
+                    lexemes.push((Lex::Punct(c), start));
+                    i += 1;
+                }
+            }
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_') {
+                i += 1;
+            }
+            lexemes.push((Lex::Value(text(start, i)), start));
+        } else if c.is_alphanumeric() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            let word = text(start, i);
+            lexemes.push((Lex::Word(word.to_lowercase(), word), start));
+        } else {
+            lexemes.push((Lex::Punct(c), start));
+            i += 1;
+        }
+    }
+    Ok(lexemes)
+}
+
+fn near(lexeme: Option<&Lexeme>, chars: &[char]) -> (String, usize) {
+    match lexeme {
+        Some((Lex::Word(_, text) | Lex::Value(text) | Lex::Comment(text), at)) => (format!("syntax error at or near \"{}\"", text), *at),
+        Some((Lex::Punct(c), at)) => (format!("syntax error at or near \"{}\"", c), *at),
+        None => ("syntax error at end of input".to_string(), chars.len()),
+    }
+}
+
+fn is_word(lexeme: Option<&Lexeme>, words: &[&str]) -> bool {
+    matches!(lexeme, Some((Lex::Word(word, _), _)) if words.contains(&word.as_str()))
+}
+
+// The kind of one statement, or what is wrong with it
+fn check_statement(statement: &[Lexeme], chars: &[char]) -> Result<String, (String, usize)> {
+    let first = &statement[0];
+    let mut kind = match &first.0 {
+        Lex::Word(word, _) if STATEMENT_KEYWORDS.contains(&word.as_str()) => word.clone(),
+        Lex::Punct('(') => "select".to_string(),
+        _ => return Err(near(Some(first), chars)),
+    };
+
+    let mut open = Vec::new();
+    let mut main = None;
+    for (index, lexeme) in statement.iter().enumerate() {
+        let following = statement.get(index + 1);
+        match &lexeme.0 {
+            Lex::Punct('(') => open.push(lexeme.1),
+            Lex::Punct(')') if open.pop().is_none() => return Err(near(Some(lexeme), chars)),
+            Lex::Punct(',') if following.is_none() || matches!(following, Some((Lex::Punct(',' | ')'), _))) => {
+                return Err(near(following, chars));
+            }
+            Lex::Punct(',') if is_word(following, CLAUSE_KEYWORDS) => return Err(near(following, chars)),
+            // The statement a WITH query's CTEs lead up to
+            Lex::Word(word, _)
+                if kind == "with"
+                    && main.is_none()
+                    && open.is_empty()
+                    && ["select", "insert", "update", "delete", "merge", "values", "table"].contains(&word.as_str()) =>
+            {
+                main = Some(word.clone());
+            }
+            _ => {}
+        }
+    }
+    if let Some(at) = open.pop() {
+        return Err(("unclosed parenthesis".to_string(), at));
+    }
+
+    let second = statement.get(1);
+    match kind.as_str() {
+        "insert" if !is_word(second, &["into"]) => return Err(near(second, chars)),
+        "delete" if !is_word(second, &["from"]) => return Err(near(second, chars)),
+        "update" if !statement.iter().any(|lexeme| matches!(&lexeme.0, Lex::Word(word, _) if word == "set")) => {
+            return Err(near(None, chars));
+        }
+        "select" | "with" | "insert" | "update" | "delete" if statement.len() == 1 => return Err(near(None, chars)),
+        _ => {}
+    }
+    if kind == "with" {
+        kind = main.ok_or_else(|| near(None, chars))?;
+    }
+    Ok(kind)
+}
+
+pub(crate) fn locate(chars: &[char], index: usize) -> (usize, usize) {
+    let before = &chars[..index.min(chars.len())];
+    let line = before.iter().filter(|c| **c == '\n').count() + 1;
+    let column = before.iter().rev().take_while(|c| **c != '\n').count() + 1;
+    (line, column)
+}
+
+// Check `sql` for the syntax errors a lexer can see, such as unterminated literals,
+// unbalanced parentheses, stray commas and unknown statements, without a round trip.
+// It is not a full parser: SQL that passes can still fail on the server.
+pub fn validate(sql: &str) -> Validation {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut statements = Vec::new();
+    let outcome = lex(&chars, false).and_then(|lexemes| {
+        for statement in lexemes.split(|lexeme| lexeme.0 == Lex::Punct(';')).filter(|s| !s.is_empty()) {
+            let kind = check_statement(statement, &chars)?;
+            statements.push(StatementInfo { kind, position: statement[0].1 + 1 });
+        }
+        Ok(())
+    });
+    let error = outcome.err().map(|(message, index)| {
+        let (line, column) = locate(&chars, index);
+        SyntaxError { message, position: index + 1, line, column }
+    });
+    Validation { valid: error.is_none(), kind: statements.first().map(|s| s.kind.clone()), statements, error }
+}
+
+// `{ valid, kind, statements: [{ kind, position }], error: { message, position, line, column } }`
+#[wasm_bindgen(js_name = validate)]
+pub fn validate_js(sql: &str) -> Result<JsValue, JsValue> {
+    crate::to_js(&validate(sql))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(sql: &str) -> (String, usize, usize) {
+        let error = validate(sql).error.expect("expected a syntax error");
+        (error.message, error.line, error.column)
+    }
+
+    #[test]
+    fn test_valid_statements_and_kinds() {
+        let result = validate("WITH recent AS (SELECT * FROM t WHERE a IN (1, 2))\nDELETE FROM t USING recent; select 'a;b', $$x$$");
+        assert!(result.valid, "{:?}", result.error);
+        assert_eq!(result.kind.as_deref(), Some("delete"));
+        let kinds: Vec<&str> = result.statements.iter().map(|s| s.kind.as_str()).collect();
+        assert_eq!(kinds, vec!["delete", "select"]);
+        assert_eq!(result.statements[1].position, 80);
+        assert!(validate("(SELECT 1) UNION (SELECT 2)").valid);
+    }
+
+    #[test]
+    fn test_errors_point_at_line_and_column() {
+        assert_eq!(error("SELECT a,\n  FROM t"), ("syntax error at or near \"FROM\"".to_string(), 2, 3));
+        assert_eq!(error("SELECT (1 + 2"), ("unclosed parenthesis".to_string(), 1, 8));
+        assert_eq!(error("SELECT 'abc"), ("unterminated quoted string".to_string(), 1, 8));
+        assert_eq!(error("SELEC 1"), ("syntax error at or near \"SELEC\"".to_string(), 1, 1));
+        assert_eq!(error("INSERT t VALUES (1)"), ("syntax error at or near \"t\"".to_string(), 1, 8));
+        assert_eq!(error("UPDATE t"), ("syntax error at end of input".to_string(), 1, 9));
+        assert_eq!(error("SELECT 1)"), ("syntax error at or near \")\"".to_string(), 1, 9));
+    }
+}
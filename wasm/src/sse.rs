@@ -0,0 +1,135 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+use crate::errors::BridgeError;
+use crate::transport::{Transport, TransportEvents};
+
+// EventSource.readyState values
+const SSE_OPEN: f64 = 1.0;
+const SSE_CLOSED: f64 = 2.0;
+
+// HTTP fallback for networks that block WebSocket upgrades: messages are POSTed to
+// `{base}/messages` and everything the bridge sends, including notifications and
+// change events, arrives on a Server-Sent Events stream from `{base}/events`.
+pub(crate) struct SseTransport {
+    source: JsValue,
+    messages_url: String,
+    events: TransportEvents,
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, JsValue> {
+    js_sys::Reflect::get(target, &JsValue::from_str(key))
+}
+
+fn set_handler(target: &JsValue, key: &str, handler: impl FnMut(JsValue) + 'static) -> Result<(), JsValue> {
+    let callback = Closure::wrap(Box::new(handler) as Box<dyn FnMut(JsValue)>);
+    js_sys::Reflect::set(target, &JsValue::from_str(key), callback.as_ref())?;
+    callback.forget();
+    Ok(())
+}
+
+// Endpoint URLs for a session; ws:// bases are accepted and mapped to http://
+pub fn session_urls(base: &str, session: &str) -> (String, String) {
+    let base = base.trim_end_matches('/');
+    let base = if let Some(rest) = base.strip_prefix("ws://") {
+        format!("http://{}", rest)
+    } else if let Some(rest) = base.strip_prefix("wss://") {
+        format!("https://{}", rest)
+    } else {
+        base.to_string()
+    };
+    (
+        format!("{}/events?session={}", base, session),
+        format!("{}/messages?session={}", base, session),
+    )
+}
+
+async fn post(url: String, body: String) -> Result<(), JsValue> {
+    let global = js_sys::global();
+    let fetch: js_sys::Function = get(&global, "fetch")?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str("fetch is not available in this runtime"))?;
+    let init = js_sys::Object::new();
+    js_sys::Reflect::set(&init, &JsValue::from_str("method"), &JsValue::from_str("POST"))?;
+    js_sys::Reflect::set(&init, &JsValue::from_str("body"), &JsValue::from_str(&body))?;
+    let response = JsFuture::from(js_sys::Promise::from(fetch.call2(&global, &JsValue::from_str(&url), &init)?)).await?;
+    if !get(&response, "ok")?.is_truthy() {
+        let status = get(&response, "status")?.as_f64().unwrap_or(0.0);
+        return Err(JsValue::from_str(&format!("Bridge rejected message (HTTP {})", status)));
+    }
+    Ok(())
+}
+
+impl SseTransport {
+    pub fn connect(base: &str, session: &str, events: TransportEvents) -> Result<SseTransport, JsValue> {
+        let ctor: js_sys::Function = get(&js_sys::global(), "EventSource")?
+            .dyn_into()
+            .map_err(|_| JsValue::from_str("EventSource is not available in this runtime"))?;
+        let (events_url, messages_url) = session_urls(base, session);
+        let source = js_sys::Reflect::construct(&ctor, &js_sys::Array::of1(&JsValue::from_str(&events_url)))?;
+
+        let on_open = events.clone();
+        set_handler(&source, "onopen", move |_| on_open.opened())?;
+
+        let on_message = events.clone();
+        set_handler(&source, "onmessage", move |event| {
+            if let Some(data) = get(&event, "data").ok().and_then(|d| d.as_string()) {
+                on_message.message(&data);
+            }
+        })?;
+
+        // EventSource retries on its own; only a closed source is a lost connection
+        let on_error = events.clone();
+        let error_source = source.clone();
+        set_handler(&source, "onerror", move |_| {
+            let state = get(&error_source, "readyState").ok().and_then(|s| s.as_f64());
+            if state == Some(SSE_CLOSED) {
+                on_error.closed(1006, "Event stream closed");
+            } else {
+                on_error.error("Event stream interrupted, reconnecting");
+            }
+        })?;
+
+        Ok(SseTransport { source, messages_url, events })
+    }
+}
+
+impl Transport for SseTransport {
+    fn send(&self, text: &str) -> Result<(), BridgeError> {
+        let url = self.messages_url.clone();
+        let body = text.to_string();
+        let events = self.events.clone();
+        spawn_local(async move {
+            if let Err(e) = post(url, body).await {
+                events.error(&format!("Failed to POST message: {:?}", e));
+            }
+        });
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Ok(close) = get(&self.source, "close").and_then(|c| c.dyn_into::<js_sys::Function>()) {
+            let _ = close.call0(&self.source);
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        get(&self.source, "readyState").ok().and_then(|s| s.as_f64()) == Some(SSE_OPEN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_urls() {
+        let (events, messages) = session_urls("ws://localhost:8080/", "abc");
+        assert_eq!(events, "http://localhost:8080/events?session=abc");
+        assert_eq!(messages, "http://localhost:8080/messages?session=abc");
+
+        let (events, _) = session_urls("https://bridge.example.com", "s1");
+        assert_eq!(events, "https://bridge.example.com/events?session=s1");
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Serialize;
+use tsify::Tsify;
+
+use crate::sql_validate::{lex, Lex};
+
+// What a statement does, so generic data layers know whether to read `rows`, `rowCount`
+// or neither
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementKind {
+    // SELECT, VALUES and TABLE: rows
+    Select,
+    // INSERT, UPDATE and DELETE: an affected count, and rows with RETURNING
+    Insert,
+    Update,
+    Delete,
+    // CREATE, ALTER, DROP, TRUNCATE, COMMENT, GRANT and REVOKE: nothing
+    Ddl,
+    // Everything else: transaction control, SET, COPY, CALL, LISTEN and the like. SHOW,
+    // EXPLAIN and FETCH still return rows.
+    Utility,
+}
+
+const DDL_KEYWORDS: &[&str] = &["create", "alter", "drop", "truncate", "comment", "grant", "revoke"];
+
+fn kind_of(word: &str) -> StatementKind {
+    match word {
+        "select" | "values" | "table" => StatementKind::Select,
+        "insert" => StatementKind::Insert,
+        // MERGE writes rows and reports a count like an UPDATE
+        "update" | "merge" => StatementKind::Update,
+        "delete" => StatementKind::Delete,
+        word if DDL_KEYWORDS.contains(&word) => StatementKind::Ddl,
+        _ => StatementKind::Utility,
+    }
+}
+
+// The kind of each `;`-separated statement in `sql`. A WITH query takes the kind of the
+// statement its CTEs lead up to; SQL that doesn't lex classifies as nothing.
+pub fn classify_all(sql: &str) -> Vec<StatementKind> {
+    let chars: Vec<char> = sql.chars().collect();
+    let Ok(lexemes) = lex(&chars, false) else {
+        return Vec::new();
+    };
+    lexemes
+        .split(|lexeme| lexeme.0 == Lex::Punct(';'))
+        .filter(|statement| !statement.is_empty())
+        .map(|statement| match &statement[0].0 {
+            Lex::Punct('(') => StatementKind::Select,
+            Lex::Word(word, _) if word == "with" => {
+                let mut depth = 0usize;
+                let main = statement.iter().find_map(|(lexeme, _)| match lexeme {
+                    Lex::Punct('(') => {
+                        depth += 1;
+                        None
+                    }
+                    Lex::Punct(')') => {
+                        depth = depth.saturating_sub(1);
+                        None
+                    }
+                    Lex::Word(word, _) if depth == 0 && ["select", "insert", "update", "delete", "merge", "values", "table"].contains(&word.as_str()) => {
+                        Some(kind_of(word))
+                    }
+                    _ => None,
+                });
+                main.unwrap_or(StatementKind::Select)
+            }
+            Lex::Word(word, _) => kind_of(word),
+            _ => StatementKind::Utility,
+        })
+        .collect()
+}
+
+// The kind a query's result is labelled with: the last statement's, when several are
+// stacked
+pub fn classify(sql: &str) -> Option<StatementKind> {
+    classify_all(sql).pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classifies_statements() {
+        assert_eq!(classify("select 1"), Some(StatementKind::Select));
+        assert_eq!(classify("(SELECT 1) UNION (SELECT 2)"), Some(StatementKind::Select));
+        assert_eq!(classify("WITH gone AS (DELETE FROM t RETURNING id) SELECT count(*) FROM gone"), Some(StatementKind::Select));
+        assert_eq!(classify("WITH ids AS (SELECT id FROM t) UPDATE t SET a = 1 FROM ids"), Some(StatementKind::Update));
+        assert_eq!(classify("-- 'select'\nINSERT INTO t VALUES (1)"), Some(StatementKind::Insert));
+        assert_eq!(classify("TRUNCATE t"), Some(StatementKind::Ddl));
+        assert_eq!(classify("SHOW search_path"), Some(StatementKind::Utility));
+        assert_eq!(classify_all("BEGIN; DELETE FROM t; COMMIT"), vec![StatementKind::Utility, StatementKind::Delete, StatementKind::Utility]);
+        assert_eq!(classify("SELECT 'unterminated"), None);
+        assert_eq!(classify(""), None);
+    }
+}
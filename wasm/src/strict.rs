@@ -0,0 +1,111 @@
+use bridge_protocol::{
+    message_type, ChangePayload, ErrorPayload, ListenLostPayload, NotificationPayload, ProgressPayload, QueryResult, RowDeltaPayload, SchemaChange,
+    ShutdownPayload, ThrottledPayload, WebSocketMessage,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// Message types a bridge sends, once chunking, compression and encryption are undone
+pub const INBOUND_TYPES: [&str; 10] = [
+    message_type::RESULT,
+    message_type::ERROR,
+    message_type::NOTIFICATION,
+    message_type::LISTEN_LOST,
+    message_type::CHANGE,
+    message_type::PROGRESS,
+    message_type::SHUTDOWN,
+    message_type::SCHEMA_CHANGE,
+    message_type::ROW_DELTA,
+    message_type::THROTTLED,
+];
+
+// How much of the offending frame a violation quotes
+const FRAME_EXCERPT: usize = 200;
+
+// Detail of the `protocolviolation` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProtocolViolation {
+    pub reason: String,
+    #[serde(rename = "messageType")]
+    pub message_type: Option<String>,
+    pub id: Option<String>,
+    pub frame: String,
+}
+
+fn payload<T: DeserializeOwned>(message: &WebSocketMessage) -> Result<(), String> {
+    serde_json::from_value::<T>(message.payload.clone())
+        .map(|_| ())
+        .map_err(|e| format!("Invalid {} payload: {}", message.message_type, e))
+}
+
+// Parse a frame, requiring a known message type and a payload of the shape that type
+// carries. Results are only checked when they are query results; other results (pong,
+// hello, acks) have no common shape.
+pub fn check(text: &str) -> Result<WebSocketMessage, ProtocolViolation> {
+    let violation = |reason: String, message: Option<&WebSocketMessage>| ProtocolViolation {
+        reason,
+        message_type: message.map(|m| m.message_type.clone()),
+        id: message.and_then(|m| m.id.clone()),
+        frame: text.chars().take(FRAME_EXCERPT).collect(),
+    };
+    let message: WebSocketMessage =
+        serde_json::from_str(text).map_err(|e| violation(format!("Not a bridge message: {}", e), None))?;
+    let checked = match message.message_type.as_str() {
+        message_type::RESULT if message.payload.get("sql").is_some() => payload::<QueryResult>(&message),
+        message_type::RESULT => Ok(()),
+        message_type::ERROR => payload::<ErrorPayload>(&message),
+        message_type::NOTIFICATION => payload::<NotificationPayload>(&message),
+        message_type::LISTEN_LOST => payload::<ListenLostPayload>(&message),
+        message_type::CHANGE => payload::<ChangePayload>(&message),
+        message_type::SHUTDOWN => payload::<ShutdownPayload>(&message),
+        message_type::SCHEMA_CHANGE => payload::<SchemaChange>(&message),
+        message_type::ROW_DELTA => payload::<RowDeltaPayload>(&message),
+        message_type::THROTTLED => payload::<ThrottledPayload>(&message),
+        message_type::PROGRESS if message.id.is_none() => Err("progress without the id of its query".to_string()),
+        message_type::PROGRESS => payload::<ProgressPayload>(&message),
+        other => Err(format!("Unknown message type '{}'; expected one of {}", other, INBOUND_TYPES.join(", "))),
+    };
+    match checked {
+        Ok(()) => Ok(message),
+        Err(reason) => Err(violation(reason, Some(&message))),
+    }
+}
+
+// Keep a malformed message from reaching any handler: fail the request it answers, if
+// any, and report it as a `protocolviolation` event
+pub(crate) fn reject(state: &SharedState, violation: ProtocolViolation) {
+    log_warn!("WASM protocol violation: {}", violation.reason);
+    if let Some(id) = &violation.id {
+        let error = BridgeError::from_error_payload(
+            &json!({ "message": format!("Protocol violation: {}", violation.reason), "code": "PROTOCOL_VIOLATION" }),
+            Some(id.clone()),
+        );
+        state.borrow_mut().fail_request(id, error);
+    }
+    crate::events::emit(state, "protocolviolation", &violation);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_requires_known_types_and_shapes() {
+        assert!(check(r#"{"type":"result","payload":{"message":"pong"},"id":"p1"}"#).is_ok());
+        assert!(check(r#"{"type":"notification","payload":{"channel":"jobs","payload":"7"}}"#).is_ok());
+
+        let violation = check(r#"{"type":"result","payload":{"sql":"SELECT 1","rows":"nope"},"id":"q1"}"#).unwrap_err();
+        assert!(violation.reason.starts_with("Invalid result payload"));
+        assert_eq!((violation.message_type.as_deref(), violation.id.as_deref()), (Some("result"), Some("q1")));
+
+        assert!(check(r#"{"type":"error","payload":{"code":"X"},"id":"q2"}"#).unwrap_err().reason.contains("message"));
+        assert!(check(r#"{"type":"progress","payload":{"rows":1,"elapsedMs":2}}"#).is_err());
+        assert!(check(r#"{"type":"surprise","payload":{}}"#).unwrap_err().reason.starts_with("Unknown message type 'surprise'"));
+        let garbage = check("not json").unwrap_err();
+        assert_eq!((garbage.message_type, garbage.frame.as_str()), (None, "not json"));
+    }
+}
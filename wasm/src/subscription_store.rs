@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::cache_invalidation::InvalidationRule;
+use crate::connection::SharedState;
+use crate::idb;
+
+const REGISTRY_KEY: &str = "registry";
+// Notifications kept per restored channel until a callback claims it
+pub const MAX_HELD_NOTIFICATIONS: usize = 100;
+
+// What a client was subscribed to, saved so a restarted worker or discarded tab can pick
+// up where it left off. Callbacks can't be saved: only the channels and the cache rules.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct SavedSubscriptions {
+    pub channels: BTreeSet<String>,
+    pub invalidation: BTreeMap<String, InvalidationRule>,
+}
+
+// Mirrors the subscription registry to IndexedDB while enabled. Channels restored from
+// it stay LISTENed, and their notifications are held, until the app subscribes to them
+// again.
+#[derive(Debug, Default)]
+pub(crate) struct SubscriptionStore {
+    pub enabled: bool,
+    held: HashMap<String, VecDeque<Value>>,
+}
+
+impl SubscriptionStore {
+    pub fn hold_channel(&mut self, channel: &str) {
+        self.held.entry(channel.to_string()).or_default();
+    }
+
+    pub fn held_channels(&self) -> Vec<String> {
+        self.held.keys().cloned().collect()
+    }
+
+    // Keep a notification for a restored channel nobody has subscribed to yet; false if
+    // the channel wasn't restored
+    pub fn hold(&mut self, channel: &str, notification: &Value) -> bool {
+        let Some(held) = self.held.get_mut(channel) else {
+            return false;
+        };
+        if held.len() == MAX_HELD_NOTIFICATIONS {
+            held.pop_front();
+        }
+        held.push_back(notification.clone());
+        true
+    }
+
+    // A registration now owns the channel: hand over what was held for it
+    pub fn claim(&mut self, channel: &str) -> Vec<Value> {
+        self.held.remove(channel).map(Vec::from).unwrap_or_default()
+    }
+}
+
+// The registry as it stands, restored channels nobody has claimed included
+fn snapshot(state: &SharedState) -> SavedSubscriptions {
+    let state = state.borrow();
+    let mut channels: BTreeSet<String> = state.notifications.channels().into_iter().collect();
+    channels.extend(state.watches.channels());
+    channels.extend(state.subscriptions.held_channels());
+    let invalidation: BTreeMap<String, InvalidationRule> = state.cache_invalidation.rules().into_iter().collect();
+    channels.extend(invalidation.keys().cloned());
+    SavedSubscriptions { channels, invalidation }
+}
+
+async fn save(state: &SharedState) -> Result<(), JsValue> {
+    let json = serde_json::to_string(&snapshot(state)).map_err(|e| JsValue::from_str(&format!("Failed to serialize subscriptions: {}", e)))?;
+    idb::put(idb::SUBSCRIPTIONS_STORE, REGISTRY_KEY, &json).await
+}
+
+// The registry changed: drop held notifications for channels something now uses, and
+// save it if enabled
+pub(crate) fn changed(state: &SharedState) {
+    {
+        let mut state = state.borrow_mut();
+        let claimed: Vec<String> = state
+            .subscriptions
+            .held_channels()
+            .into_iter()
+            .filter(|channel| state.notifications.is_used(channel) || state.watches.is_used(channel) || state.cache_invalidation.is_used(channel))
+            .collect();
+        for channel in claimed {
+            state.subscriptions.claim(&channel);
+        }
+        if !state.subscriptions.enabled {
+            return;
+        }
+    }
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = save(&state).await {
+            log_warn!("WASM failed to save the subscription registry: {:?}", e);
+        }
+    });
+}
+
+// Load the saved registry: cache rules come back as they were, and saved channels are
+// LISTENed again and held for the app to subscribe to. Resolves with those channels.
+pub(crate) async fn restore(state: &SharedState) -> Result<Vec<String>, JsValue> {
+    let saved = match idb::get(idb::SUBSCRIPTIONS_STORE, REGISTRY_KEY).await? {
+        Some(json) => serde_json::from_str::<SavedSubscriptions>(&json).unwrap_or_else(|e| {
+            log_warn!("WASM ignoring an unreadable saved subscription registry: {}", e);
+            SavedSubscriptions::default()
+        }),
+        None => SavedSubscriptions::default(),
+    };
+    let channels: Vec<String> = saved.channels.into_iter().collect();
+    {
+        let mut state = state.borrow_mut();
+        for (channel, rule) in saved.invalidation {
+            if !state.cache_invalidation.is_used(&channel) {
+                state.cache_invalidation.set(channel, rule);
+            }
+        }
+        for channel in &channels {
+            let used = state.notifications.is_used(channel) || state.watches.is_used(channel) || state.cache_invalidation.is_used(channel);
+            if !used {
+                state.subscriptions.hold_channel(channel);
+            }
+        }
+    }
+    // Offline, the next connect LISTENs them along with everything else
+    if state.borrow().is_connected() {
+        crate::live::ensure_listening(state, &channels).await?;
+    }
+    if !channels.is_empty() {
+        log_info!("WASM restored {} saved notification channels", channels.len());
+    }
+    Ok(channels)
+}
+
+pub(crate) async fn clear_saved() -> Result<(), JsValue> {
+    idb::delete(idb::SUBSCRIPTIONS_STORE, REGISTRY_KEY).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_holds_until_claimed() {
+        let mut store = SubscriptionStore::default();
+        assert!(!store.hold("orders", &json!({ "channel": "orders" })));
+        store.hold_channel("orders");
+        for n in 0..MAX_HELD_NOTIFICATIONS + 1 {
+            assert!(store.hold("orders", &json!({ "channel": "orders", "payload": n.to_string() })));
+        }
+        let held = store.claim("orders");
+        assert_eq!(held.len(), MAX_HELD_NOTIFICATIONS);
+        assert_eq!(held[0]["payload"], "1");
+        assert!(store.held_channels().is_empty());
+
+        let saved: SavedSubscriptions = serde_json::from_value(json!({ "channels": ["b", "a"] })).unwrap();
+        assert_eq!(saved.channels.into_iter().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::connection::SharedState;
+use crate::conflict::{ConflictCheck, Resolver};
+use crate::{cdc, outbox};
+
+// Options accepted by `start_sync`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncOptions {
+    // How often the loop pushes queued writes and checks the change subscription
+    #[serde(rename = "intervalMs")]
+    pub interval_ms: f64,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions { interval_ms: 5000.0 }
+    }
+}
+
+impl SyncOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.interval_ms.is_finite() && self.interval_ms >= 100.0) {
+            return Err("intervalMs must be at least 100".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncPhase {
+    #[default]
+    Stopped,
+    Offline,
+    Pushing,
+    // Nothing left to push and subscribed to changes
+    Synced,
+    Error,
+}
+
+// What `sync_status()` and the `on_sync_status` hook report
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct SyncStatus {
+    pub phase: SyncPhase,
+    // Writes still in the outbox
+    pub pending: usize,
+    #[serde(rename = "lastPushAt")]
+    pub last_push_at: Option<f64>,
+    #[serde(rename = "lastPullAt")]
+    pub last_pull_at: Option<f64>,
+    // Changes received since the sync started
+    pub pulled: u64,
+    // Pushed writes that met a newer server row
+    pub conflicts: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct SyncState {
+    pub status: SyncStatus,
+    pub hook: Option<js_sys::Function>,
+    // Per table; tables without one use last-write-wins
+    pub resolvers: HashMap<String, Resolver>,
+    tables: Vec<String>,
+    // Handed the change events pulled for `tables`
+    on_change: Option<js_sys::Function>,
+    // Feeds the subscription; reused when it has to be made again after a reconnect
+    listener: Option<js_sys::Function>,
+    subscription: Option<String>,
+    // Bumped by start and stop so a superseded loop ends
+    generation: u32,
+    // Last status the hook heard
+    reported: Option<SyncStatus>,
+    ticking: bool,
+}
+
+impl SyncState {
+    pub fn running(&self) -> bool {
+        self.status.phase != SyncPhase::Stopped
+    }
+
+    // The status if the hook hasn't heard it yet
+    fn unreported(&mut self) -> Option<SyncStatus> {
+        if self.reported.as_ref() == Some(&self.status) {
+            return None;
+        }
+        self.reported = Some(self.status.clone());
+        self.reported.clone()
+    }
+}
+
+fn update(state: &SharedState, change: impl FnOnce(&mut SyncStatus)) {
+    let (hook, status) = {
+        let mut state = state.borrow_mut();
+        let pending = state.outbox.len();
+        let sync = &mut state.sync;
+        change(&mut sync.status);
+        sync.status.pending = pending;
+        (sync.hook.clone(), sync.unreported())
+    };
+    if let (Some(hook), Some(status)) = (hook, status) {
+        if let Ok(status) = crate::to_js(&status) {
+            let _ = hook.call1(&JsValue::NULL, &status);
+        }
+    }
+}
+
+fn pulled(state: &SharedState, event: JsValue) {
+    let on_change = state.borrow().sync.on_change.clone();
+    update(state, |status| {
+        status.pulled += 1;
+        status.last_pull_at = Some(js_sys::Date::now());
+    });
+    if let Some(on_change) = on_change {
+        let _ = on_change.call1(&JsValue::NULL, &event);
+    }
+}
+
+pub(crate) fn count_conflict(state: &SharedState) {
+    update(state, |status| status.conflicts += 1);
+}
+
+fn failed(state: &SharedState, context: &str, error: JsValue) {
+    let message = format!("{}: {}", context, error.as_string().unwrap_or_else(|| format!("{:?}", error)));
+    update(state, |status| {
+        status.phase = SyncPhase::Error;
+        status.error = Some(message);
+    });
+}
+
+// One pass of the loop, unless another is under way
+async fn tick(state: &SharedState) {
+    if std::mem::replace(&mut state.borrow_mut().sync.ticking, true) {
+        return;
+    }
+    sync_once(state).await;
+    state.borrow_mut().sync.ticking = false;
+}
+
+// (Re)subscribe to changes, then push whatever is queued
+async fn sync_once(state: &SharedState) {
+    if !state.borrow().is_connected() {
+        // A reconnect gets a new bridge session without the old subscription
+        state.borrow_mut().sync.subscription = None;
+        update(state, |status| status.phase = SyncPhase::Offline);
+        return;
+    }
+    let (tables, listener, subscribed) = {
+        let sync = &state.borrow().sync;
+        (sync.tables.clone(), sync.listener.clone(), sync.subscription.is_some())
+    };
+    if let Some(listener) = listener.filter(|_| !subscribed && !tables.is_empty()) {
+        match cdc::subscribe(state, tables, listener).await {
+            Ok(subscription) => state.borrow_mut().sync.subscription = Some(subscription),
+            Err(e) => return failed(state, "Change subscription failed", e),
+        }
+    }
+    if state.borrow().outbox.len() > 0 {
+        update(state, |status| status.phase = SyncPhase::Pushing);
+        match outbox::replay(state.clone()).await {
+            Ok(0) => {}
+            Ok(_) => update(state, |status| status.last_push_at = Some(js_sys::Date::now())),
+            Err(e) => return failed(state, "Push failed", e),
+        }
+    }
+    // Replay stops early when the connection drops
+    let connected = state.borrow().is_connected();
+    update(state, |status| {
+        status.phase = if connected { SyncPhase::Synced } else { SyncPhase::Offline };
+        status.error = None;
+    });
+}
+
+// Turn on the persistent outbox, subscribe to `tables` and start the loop
+pub(crate) async fn start(state: SharedState, tables: Vec<String>, on_change: Option<js_sys::Function>, options: SyncOptions) -> Result<SyncStatus, JsValue> {
+    let generation = {
+        let mut state = state.borrow_mut();
+        state.outbox.enabled = true;
+        state.outbox.persistent = true;
+        state.sync.generation += 1;
+        state.sync.generation
+    };
+    if let Err(e) = outbox::load_persisted(&state).await {
+        log_warn!("WASM sync outbox is not persistent: {:?}", e);
+        state.borrow_mut().outbox.persistent = false;
+    }
+
+    let listener_state = state.clone();
+    let listener = Closure::<dyn FnMut(JsValue)>::new(move |event: JsValue| pulled(&listener_state, event));
+    {
+        let sync = &mut state.borrow_mut().sync;
+        sync.tables = tables;
+        sync.on_change = on_change;
+        sync.listener = Some(listener.into_js_value().unchecked_into());
+        sync.subscription = None;
+        sync.status = SyncStatus { phase: SyncPhase::Offline, ..SyncStatus::default() };
+    }
+    tick(&state).await;
+
+    let looping = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        loop {
+            let _ = crate::retry::sleep(options.interval_ms).await;
+            if looping.borrow().sync.generation != generation {
+                return;
+            }
+            tick(&looping).await;
+        }
+    });
+    Ok(state.borrow().sync.status.clone())
+}
+
+// Stop the loop and drop the change subscription; queued writes stay in the outbox
+pub(crate) async fn stop(state: &SharedState) -> Result<(), JsValue> {
+    let subscription = {
+        let sync = &mut state.borrow_mut().sync;
+        sync.generation += 1;
+        sync.listener = None;
+        sync.on_change = None;
+        sync.subscription.take()
+    };
+    update(state, |status| *status = SyncStatus::default());
+    if let Some(subscription) = subscription {
+        if state.borrow().is_connected() {
+            cdc::unsubscribe(state, &subscription).await?;
+        }
+    }
+    Ok(())
+}
+
+// Queue a write for the sync loop and push it straight away when connected
+pub(crate) async fn write(state: &SharedState, sql: &str, params: Option<Vec<serde_json::Value>>, conflict: Option<ConflictCheck>) -> Result<String, JsValue> {
+    let key = outbox::enqueue(state, sql, params, conflict, false).await?;
+    update(state, |_| {});
+    let state = state.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        if state.borrow().sync.running() {
+            tick(&state).await;
+        }
+    });
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_reported_once_per_change() {
+        let mut sync = SyncState::default();
+        assert!(sync.unreported().is_some());
+        assert!(sync.unreported().is_none());
+        sync.status.pending = 2;
+        assert_eq!(sync.unreported().map(|s| s.pending), Some(2));
+        assert!(sync.unreported().is_none());
+
+        assert!(SyncOptions::default().validate().is_ok());
+        assert!(SyncOptions { interval_ms: 10.0 }.validate().is_err());
+    }
+}
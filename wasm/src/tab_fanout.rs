@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+
+// Out-of-order events a tab holds while waiting for a missing one; past this the gap is
+// given up on
+pub const MAX_HELD: usize = 64;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "lowercase")]
+pub enum FanoutKind {
+    Notification,
+    Change,
+}
+
+// A NOTIFY or row change the leader tab relays to every tab sharing its connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Tsify)]
+pub struct FanoutEvent {
+    // The leader that numbered it; a new leader numbers from scratch
+    pub source: String,
+    pub seq: u64,
+    pub kind: FanoutKind,
+    // The NOTIFY channel, or `schema.table` for a change
+    pub topic: String,
+    // `{ channel, payload }`, or `{ action, schema, table, new, old }`
+    #[tsify(type = "Value")]
+    pub payload: Value,
+}
+
+// What one tab wants relayed. Entries match exactly or, ending in `*`, by prefix; a
+// table without a schema matches it in any schema. A list left out matches everything.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct FanoutFilter {
+    #[tsify(optional)]
+    pub channels: Option<Vec<String>>,
+    #[tsify(optional)]
+    pub tables: Option<Vec<String>>,
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+impl FanoutFilter {
+    pub fn accepts(&self, event: &FanoutEvent) -> bool {
+        match event.kind {
+            FanoutKind::Notification => self.channels.as_ref().is_none_or(|channels| channels.iter().any(|c| matches(c, &event.topic))),
+            FanoutKind::Change => self.tables.as_ref().is_none_or(|tables| {
+                let table = event.topic.split_once('.').map_or(event.topic.as_str(), |(_, table)| table);
+                tables.iter().any(|t| matches(t, &event.topic) || (!t.contains('.') && matches(t, table)))
+            }),
+        }
+    }
+}
+
+// Puts one leader's events back in the order it numbered them
+#[derive(Debug, Default)]
+pub(crate) struct FanoutOrder {
+    source: Option<String>,
+    next: u64,
+    held: BTreeMap<u64, FanoutEvent>,
+}
+
+impl FanoutOrder {
+    // The events `event` makes deliverable, in order. Repeats are dropped.
+    pub fn push(&mut self, event: FanoutEvent) -> Vec<FanoutEvent> {
+        if self.source.as_deref() != Some(event.source.as_str()) {
+            // The first event seen, or a new leader: start from wherever it is
+            self.source = Some(event.source.clone());
+            self.next = event.seq;
+            self.held.clear();
+        }
+        if event.seq < self.next {
+            return Vec::new();
+        }
+        self.held.insert(event.seq, event);
+        if self.held.len() > MAX_HELD {
+            if let Some(first) = self.held.keys().next() {
+                log_warn!("WASM tab fan-out skipped events {} to {} that never arrived", self.next, first - 1);
+                self.next = *first;
+            }
+        }
+        let mut ready = Vec::new();
+        while let Some(event) = self.held.remove(&self.next) {
+            ready.push(event);
+            self.next += 1;
+        }
+        ready
+    }
+}
+
+// Where the leader tab sends what it receives
+#[derive(Default)]
+pub(crate) struct TabFanout {
+    pub hook: Option<js_sys::Function>,
+    pub source: String,
+    next_seq: u64,
+}
+
+// Number a received NOTIFY or change and hand it to the fan-out hook, if one is set
+pub(crate) fn publish(state: &SharedState, kind: FanoutKind, topic: String, payload: Value) {
+    let (hook, event) = {
+        let mut state = state.borrow_mut();
+        let Some(hook) = state.tab_fanout.hook.clone() else {
+            return;
+        };
+        let fanout = &mut state.tab_fanout;
+        fanout.next_seq += 1;
+        (hook, FanoutEvent { source: fanout.source.clone(), seq: fanout.next_seq, kind, topic, payload })
+    };
+    match crate::to_js(&event) {
+        Ok(event) => {
+            let _ = hook.call1(&JsValue::NULL, &event);
+        }
+        Err(e) => log_warn!("WASM failed to convert a fan-out event: {:?}", e),
+    }
+}
+
+// Each tab's side of the fan-out: restores the leader's order and keeps only the events
+// the tab's filter accepts
+#[wasm_bindgen]
+pub struct TabFanoutFilter {
+    filter: FanoutFilter,
+    order: FanoutOrder,
+}
+
+fn parse_filter(filter: JsValue) -> Result<FanoutFilter, JsValue> {
+    if filter.is_undefined() || filter.is_null() {
+        return Ok(FanoutFilter::default());
+    }
+    serde_wasm_bindgen::from_value(filter).map_err(|e| JsValue::from_str(&format!("Invalid fan-out filter: {}", e)))
+}
+
+#[wasm_bindgen]
+impl TabFanoutFilter {
+    #[wasm_bindgen(constructor)]
+    pub fn new(#[wasm_bindgen(unchecked_optional_param_type = "FanoutFilter | null")] filter: JsValue) -> Result<TabFanoutFilter, JsValue> {
+        Ok(TabFanoutFilter { filter: parse_filter(filter)?, order: FanoutOrder::default() })
+    }
+
+    // Change what this tab receives; events already delivered stay delivered
+    #[wasm_bindgen]
+    pub fn set_filter(&mut self, #[wasm_bindgen(unchecked_optional_param_type = "FanoutFilter | null")] filter: JsValue) -> Result<(), JsValue> {
+        self.filter = parse_filter(filter)?;
+        Ok(())
+    }
+
+    // Take one relayed event; returns the events now ready for this tab, in order
+    #[wasm_bindgen(unchecked_return_type = "FanoutEvent[]")]
+    pub fn accept(&mut self, #[wasm_bindgen(unchecked_param_type = "FanoutEvent")] event: JsValue) -> Result<js_sys::Array, JsValue> {
+        let event: FanoutEvent = serde_wasm_bindgen::from_value(event).map_err(|e| JsValue::from_str(&format!("Invalid fan-out event: {}", e)))?;
+        let ready = self.order.push(event);
+        ready.iter().filter(|event| self.filter.accepts(event)).map(crate::to_js).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(source: &str, seq: u64, kind: FanoutKind, topic: &str) -> FanoutEvent {
+        FanoutEvent { source: source.to_string(), seq, kind, topic: topic.to_string(), payload: json!(null) }
+    }
+
+    #[test]
+    fn test_orders_and_filters_events() {
+        let mut order = FanoutOrder::default();
+        let seqs = |events: Vec<FanoutEvent>| events.iter().map(|e| e.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(order.push(event("a", 5, FanoutKind::Notification, "orders")))[..], [5]);
+        assert!(order.push(event("a", 7, FanoutKind::Notification, "orders")).is_empty());
+        assert_eq!(seqs(order.push(event("a", 6, FanoutKind::Notification, "orders")))[..], [6, 7]);
+        assert!(order.push(event("a", 6, FanoutKind::Notification, "orders")).is_empty());
+        // A new leader numbers from 1
+        assert_eq!(seqs(order.push(event("b", 1, FanoutKind::Notification, "orders")))[..], [1]);
+
+        let filter = FanoutFilter { channels: Some(vec!["orders*".to_string()]), tables: Some(vec!["users".to_string()]) };
+        assert!(filter.accepts(&event("a", 1, FanoutKind::Notification, "orders_eu")));
+        assert!(!filter.accepts(&event("a", 1, FanoutKind::Notification, "stock")));
+        assert!(filter.accepts(&event("a", 1, FanoutKind::Change, "auth.users")));
+        assert!(!filter.accepts(&event("a", 1, FanoutKind::Change, "public.users_archive")));
+        assert!(FanoutFilter::default().accepts(&event("a", 1, FanoutKind::Change, "public.orders")));
+    }
+}
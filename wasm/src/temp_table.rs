@@ -0,0 +1,138 @@
+use serde::Deserialize;
+use serde_json::Value;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::bulk::{build_insert_many, DEFAULT_ROWS_PER_STATEMENT};
+use crate::connection::{execute_query_with, QueryOptions, SharedState};
+use crate::query_builder::BuiltQuery;
+use crate::sql::quote_ident;
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Tsify)]
+pub struct TempColumn {
+    pub name: String,
+    // A Postgres type such as `bigint`, `text` or `numeric(12, 2)`
+    #[serde(rename = "type")]
+    pub column_type: String,
+}
+
+// The table `with_temp_table` creates for the length of one transaction
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Tsify)]
+pub struct TempTableSpec {
+    pub name: String,
+    pub columns: Vec<TempColumn>,
+}
+
+// Type names are spliced into the DDL, so they may only hold what a type name can: no
+// quotes, semicolons or comments
+fn valid_type(column_type: &str) -> bool {
+    !column_type.trim().is_empty()
+        && !column_type.contains("--")
+        && column_type.chars().all(|c| c.is_ascii_alphanumeric() || " _,.()[]".contains(c))
+}
+
+impl TempTableSpec {
+    // CREATE TEMP TABLE, dropped by the COMMIT or ROLLBACK that ends the transaction
+    pub fn create_sql(&self) -> Result<String, String> {
+        if self.name.is_empty() || self.name.contains('.') {
+            return Err("Temporary table names must be non-empty and unqualified".to_string());
+        }
+        if self.columns.is_empty() {
+            return Err("A temporary table needs at least one column".to_string());
+        }
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                if !valid_type(&column.column_type) {
+                    return Err(format!("'{}' is not a column type", column.column_type));
+                }
+                Ok(format!("{} {}", quote_ident(&column.name), column.column_type.trim()))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(format!("CREATE TEMP TABLE {} ({}) ON COMMIT DROP", quote_ident(&self.name), columns.join(", ")))
+    }
+
+    // Rows are arrays in column order; a one-column table also takes bare values, so an
+    // ID list can be passed as is
+    pub fn insert_statements(&self, rows: Vec<Value>) -> Result<Vec<BuiltQuery>, String> {
+        let single = self.columns.len() == 1;
+        let rows: Vec<Vec<Value>> = rows
+            .into_iter()
+            .map(|row| match row {
+                Value::Array(values) => values,
+                value if single => vec![value],
+                _ => Vec::new(),
+            })
+            .collect();
+        let columns: Vec<String> = self.columns.iter().map(|column| column.name.clone()).collect();
+        build_insert_many(&self.name, &columns, &rows, DEFAULT_ROWS_PER_STATEMENT)
+    }
+}
+
+// Options for each statement of the workflow: inside its transaction, without retries
+fn scoped(transaction: u32) -> QueryOptions {
+    QueryOptions { retry: false, transaction: Some(transaction), ..QueryOptions::default() }
+}
+
+// In one transaction: create the temp table, load `rows` into it in batched INSERTs,
+// ANALYZE it so the planner knows its size, then run `sql` and commit, which drops the
+// table. Resolves with the result of `sql`; any failure rolls the whole thing back.
+pub(crate) async fn run(state: &SharedState, spec: TempTableSpec, rows: Vec<Value>, sql: &str, params: Option<Vec<Value>>) -> Result<Value, JsValue> {
+    let create = spec.create_sql().map_err(|e| JsValue::from_str(&e))?;
+    let inserts = spec.insert_statements(rows).map_err(|e| JsValue::from_str(&e))?;
+    let id = crate::transaction::open(state).await;
+    let outcome = async {
+        execute_query_with(state, "BEGIN", None, scoped(id)).await?;
+        let result = async {
+            execute_query_with(state, &create, None, scoped(id)).await?;
+            for insert in inserts {
+                execute_query_with(state, &insert.sql, Some(insert.params), scoped(id)).await?;
+            }
+            execute_query_with(state, &format!("ANALYZE {}", quote_ident(&spec.name)), None, scoped(id)).await?;
+            execute_query_with(state, sql, params, scoped(id)).await
+        }
+        .await;
+        match result {
+            Ok(result) => execute_query_with(state, "COMMIT", None, scoped(id)).await.map(|_| result),
+            Err(e) => {
+                if let Err(rollback) = execute_query_with(state, "ROLLBACK", None, scoped(id)).await {
+                    log_warn!("WASM rollback after a failed temp table query failed: {:?}", rollback);
+                }
+                Err(e)
+            }
+        }
+    }
+    .await;
+    crate::transaction::close(state, id);
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_create_and_load_statements() {
+        let spec: TempTableSpec = serde_json::from_value(json!({
+            "name": "wanted",
+            "columns": [{ "name": "id", "type": "bigint" }],
+        }))
+        .unwrap();
+        assert_eq!(spec.create_sql().unwrap(), "CREATE TEMP TABLE \"wanted\" (\"id\" bigint) ON COMMIT DROP");
+        let inserts = spec.insert_statements(vec![json!(1), json!([2]), json!(3)]).unwrap();
+        assert_eq!(inserts[0].sql, "INSERT INTO \"wanted\" (\"id\") VALUES ($1), ($2), ($3)");
+
+        let pairs: TempTableSpec = serde_json::from_value(json!({
+            "name": "prices",
+            "columns": [{ "name": "sku", "type": "text" }, { "name": "price", "type": "numeric(12, 2)" }],
+        }))
+        .unwrap();
+        assert!(pairs.create_sql().is_ok());
+        assert!(pairs.insert_statements(vec![json!("a1")]).is_err());
+
+        let injected = TempTableSpec { columns: vec![TempColumn { name: "id".into(), column_type: "int); DROP TABLE users; --".into() }], ..spec };
+        assert!(injected.create_sql().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::query_builder::BuiltQuery;
+use crate::sql::{tokenize, Token};
+
+// SQL assembled from a tagged template, with every interpolation bound as a parameter.
+//
+// From JS: `const sql = (strings, ...values) => sql_template([...strings], values);`
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlTemplate {
+    query: BuiltQuery,
+}
+
+#[wasm_bindgen]
+impl SqlTemplate {
+    #[wasm_bindgen(getter)]
+    pub fn sql(&self) -> String {
+        self.query.sql.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn params(&self) -> Result<JsValue, JsValue> {
+        crate::to_js(&self.query.params)
+    }
+}
+
+impl SqlTemplate {
+    pub fn query(&self) -> &BuiltQuery {
+        &self.query
+    }
+}
+
+#[wasm_bindgen]
+pub fn sql_template(strings: Vec<String>, values: js_sys::Array) -> Result<SqlTemplate, JsValue> {
+    let values = values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            if value.is_undefined() {
+                return Err(JsValue::from_str(&format!("Interpolated value {} is undefined", index + 1)));
+            }
+            serde_wasm_bindgen::from_value(value)
+                .map_err(|e| JsValue::from_str(&format!("Invalid interpolated value {}: {}", index + 1, e)))
+        })
+        .collect::<Result<Vec<Value>, JsValue>>()?;
+    build_template(&strings, values).map_err(|e| JsValue::from_str(&e))
+}
+
+// `$n` placeholders the shared lexer finds outside literals, quoted identifiers and comments
+fn placeholders(sql: &str) -> usize {
+    tokenize(sql).iter().filter(|token| **token == Token::Param).count()
+}
+
+pub fn build_template(strings: &[String], values: Vec<Value>) -> Result<SqlTemplate, String> {
+    if strings.len() != values.len() + 1 {
+        return Err(format!(
+            "Template has {} string parts but {} values",
+            strings.len(),
+            values.len()
+        ));
+    }
+    if placeholders(&strings.join(" ")) > 0 {
+        return Err("Templates cannot contain positional placeholders like $1".to_string());
+    }
+
+    // Each value's placeholder must be one more the lexer sees; otherwise it landed in a
+    // string (dollar-quoted and E'' ones included), a comment or an identifier
+    let mut sql = String::new();
+    for (index, segment) in strings.iter().enumerate() {
+        sql.push_str(segment);
+        if index < values.len() {
+            sql.push_str(&format!("${}", index + 1));
+            if placeholders(&sql) != index + 1 {
+                return Err(format!(
+                    "Value {} is interpolated inside a quoted string, comment or identifier; it would not be bound as a parameter",
+                    index + 1
+                ));
+            }
+        }
+    }
+    if placeholders(&format!("{}\n$0", sql)) != values.len() + 1 {
+        return Err("Template has an unterminated quoted string or comment".to_string());
+    }
+
+    Ok(SqlTemplate {
+        query: BuiltQuery { sql, params: values },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parts(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_interpolations_become_parameters() {
+        let template = build_template(
+            &parts(&["SELECT * FROM t WHERE id = ", " AND name <> 'x''y' AND tag = ", ""]),
+            vec![json!(7), json!("a")],
+        )
+        .unwrap();
+        assert_eq!(template.query().sql, "SELECT * FROM t WHERE id = $1 AND name <> 'x''y' AND tag = $2");
+        assert_eq!(template.query().params, vec![json!(7), json!("a")]);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_templates() {
+        assert!(build_template(&parts(&["SELECT '", "'"]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT 1 -- ", ""]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT $1"]), Vec::new()).is_err());
+        assert!(build_template(&parts(&["SELECT ", ""]), Vec::new()).is_err());
+        // Dollar-quoted bodies and E'' strings, where a backslash escapes the quote
+        assert!(build_template(&parts(&["DO $$ BEGIN RAISE NOTICE '", "'; END $$"]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT $tag$ ", " $tag$"]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT E'it\\'s ", "'"]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT 1 /* ", ""]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT col", ""]), vec![json!(1)]).is_err());
+        assert!(build_template(&parts(&["SELECT $$ unterminated"]), Vec::new()).is_err());
+
+        let template = build_template(&parts(&["SELECT $$a$$, E'b\\'c', ", ""]), vec![json!(1)]).unwrap();
+        assert_eq!(template.query().sql, "SELECT $$a$$, E'b\\'c', $1");
+    }
+}
@@ -0,0 +1,103 @@
+use bridge_protocol::{ThrottledPayload, WebSocketMessage};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::connection::SharedState;
+use crate::errors::BridgeError;
+
+// Options accepted by `set_throttle_retry`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleRetry {
+    // Times one query is sent again after being throttled before it fails
+    #[serde(rename = "maxRetries")]
+    pub max_retries: u32,
+    // A longer retry-after fails the query right away instead of waiting it out
+    #[serde(rename = "maxWaitMs")]
+    pub max_wait_ms: f64,
+}
+
+impl Default for ThrottleRetry {
+    fn default() -> Self {
+        ThrottleRetry { max_retries: 3, max_wait_ms: 30_000.0 }
+    }
+}
+
+impl ThrottleRetry {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.max_wait_ms.is_finite() && self.max_wait_ms >= 0.0) {
+            return Err("maxWaitMs must be a number of milliseconds".to_string());
+        }
+        Ok(())
+    }
+
+    // Whether a query throttled `attempt` times (counting this one) waits `retry_after_ms`
+    // and goes again
+    pub fn allows(&self, attempt: u32, retry_after_ms: u64) -> bool {
+        attempt <= self.max_retries && retry_after_ms as f64 <= self.max_wait_ms
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ThrottledState {
+    pub retry: ThrottleRetry,
+    pub hook: Option<js_sys::Function>,
+    // Throttled responses received over the client's life
+    pub count: u64,
+}
+
+// Detail of the `throttled` event
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ThrottleEvent {
+    pub sql: String,
+    #[serde(rename = "retryAfterMs")]
+    pub retry_after_ms: u64,
+    pub reason: String,
+    // How many times this query has been throttled so far
+    pub attempt: u32,
+    // False when the query fails instead of waiting
+    pub retrying: bool,
+}
+
+// A `throttled` answer to a query: report it, then wait out its retry-after when the
+// retry options allow, or fail the query with `THROTTLED`
+pub(crate) async fn honor(state: &SharedState, sql: &str, response: &WebSocketMessage, attempt: u32) -> Result<(), JsValue> {
+    let payload: ThrottledPayload = serde_json::from_value(response.payload.clone())
+        .unwrap_or(ThrottledPayload { retry_after_ms: 0, reason: "Throttled by the bridge".to_string() });
+    let (retrying, hook) = {
+        let mut state = state.borrow_mut();
+        state.throttled.count += 1;
+        (state.throttled.retry.allows(attempt, payload.retry_after_ms), state.throttled.hook.clone())
+    };
+    let event = ThrottleEvent { sql: sql.to_string(), retry_after_ms: payload.retry_after_ms, reason: payload.reason.clone(), attempt, retrying };
+    if let (Some(hook), Ok(detail)) = (hook, crate::to_js(&event)) {
+        let _ = hook.call1(&JsValue::NULL, &detail);
+    }
+    crate::events::emit(state, "throttled", &event);
+    if !retrying {
+        let message = format!("{}; retry after {}ms", payload.reason, payload.retry_after_ms);
+        let error = json!({ "message": message, "code": "THROTTLED", "retryAfterMs": payload.retry_after_ms });
+        return Err(BridgeError::from_error_payload(&error, response.id.clone()).into());
+    }
+    log_debug!("WASM waiting {}ms to send a throttled query again", payload.retry_after_ms);
+    crate::retry::sleep(payload.retry_after_ms as f64).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retries_within_limits() {
+        let retry = ThrottleRetry::default();
+        assert!(retry.allows(1, 500));
+        assert!(retry.allows(3, 500));
+        assert!(!retry.allows(4, 500));
+        assert!(!retry.allows(1, 60_000));
+        let off = ThrottleRetry { max_retries: 0, ..ThrottleRetry::default() };
+        assert!(!off.allows(1, 0));
+        assert!(ThrottleRetry { max_wait_ms: f64::NAN, ..ThrottleRetry::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// Config value standing for the browser's own time zone
+pub const BROWSER: &str = "browser";
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn number(text: &str, what: &str) -> Result<i64, String> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("'{}' is not a valid {}", text, what));
+    }
+    text.parse().map_err(|_| format!("'{}' is not a valid {}", text, what))
+}
+
+// "+05:30", "-08", "+0530" or "Z" as minutes east of UTC
+fn parse_offset(text: &str) -> Result<i32, String> {
+    if text == "Z" || text == "z" {
+        return Ok(0);
+    }
+    let sign = match text.as_bytes().first() {
+        Some(b'+') => 1,
+        Some(b'-') => -1,
+        _ => return Err(format!("'{}' is not a UTC offset", text)),
+    };
+    let digits = text[1..].replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        2 => (number(&digits, "UTC offset")?, 0),
+        4 | 6 => (number(&digits[..2], "UTC offset")?, number(&digits[2..4], "UTC offset")?),
+        _ => return Err(format!("'{}' is not a UTC offset", text)),
+    };
+    Ok(sign * (hours * 60 + minutes) as i32)
+}
+
+// A timestamp as `YYYY-MM-DD[T ]HH:MM[:SS[.fff]]` with an optional offset, as Postgres
+// and `Date.toISOString()` write them. Returns the wall-clock time in milliseconds since
+// the epoch as if it were UTC, and the offset when the text has one.
+pub fn parse(text: &str) -> Result<(i64, Option<i32>), String> {
+    let invalid = || format!("'{}' is not a timestamp", text);
+    let text = text.trim();
+    let (date, rest) = text.split_at_checked(10).ok_or_else(invalid)?;
+    let mut parts = date.split('-');
+    let (year, month, day) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(year), Some(month), Some(day)) if year.len() == 4 && month.len() == 2 && day.len() == 2 => {
+            (number(year, "year")?, number(month, "month")?, number(day, "day")?)
+        }
+        _ => return Err(invalid()),
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let rest = rest.strip_prefix(['T', 't', ' ']).ok_or_else(invalid)?;
+    let zone_at = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+    let (time, zone) = rest.split_at(zone_at);
+    let (clock, fraction) = time.trim_end().split_once('.').unwrap_or((time.trim_end(), ""));
+    let mut fields = clock.split(':');
+    let hours = number(fields.next().unwrap_or_default(), "hour")?;
+    let minutes = number(fields.next().ok_or_else(invalid)?, "minute")?;
+    let seconds = fields.next().map_or(Ok(0), |s| number(s, "second"))?;
+    if fields.next().is_some() || hours > 24 || minutes > 59 || seconds > 60 {
+        return Err(invalid());
+    }
+    // Postgres keeps microseconds; these helpers work in milliseconds like `Date`
+    let millis = if fraction.is_empty() { 0 } else { number(&format!("{:0<3}", &fraction[..fraction.len().min(3)]), "fraction")? };
+    let offset = if zone.is_empty() { None } else { Some(parse_offset(zone.trim())?) };
+    let days = days_from_civil(year, month, day);
+    Ok(((days * 86_400 + hours * 3600 + minutes * 60 + seconds) * 1000 + millis, offset))
+}
+
+// `utc_ms` as the wall-clock time `offset_minutes` east of UTC, ISO 8601 with the
+// offset, or "Z" for UTC
+pub fn format(utc_ms: i64, offset_minutes: i32) -> String {
+    let local = utc_ms + i64::from(offset_minutes) * 60_000;
+    let (year, month, day) = civil_from_days(local.div_euclid(MS_PER_DAY));
+    let ms = local.rem_euclid(MS_PER_DAY);
+    let clock = format!("{:02}:{:02}:{:02}.{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+    let zone = match offset_minutes {
+        0 => "Z".to_string(),
+        offset => format!("{}{:02}:{:02}", if offset < 0 { '-' } else { '+' }, offset.abs() / 60, offset.abs() % 60),
+    };
+    format!("{:04}-{:02}-{:02}T{}{}", year, month, day, clock, zone)
+}
+
+fn intl_format(options: &js_sys::Object) -> Result<JsValue, JsValue> {
+    let intl = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("Intl"))?;
+    let constructor: js_sys::Function = js_sys::Reflect::get(&intl, &JsValue::from_str("DateTimeFormat"))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str("Intl.DateTimeFormat is not available here"))?;
+    js_sys::Reflect::construct(&constructor, &js_sys::Array::of2(&JsValue::from_str("en-US"), options))
+}
+
+// The browser's resolved IANA time zone, e.g. "Europe/Berlin"
+#[wasm_bindgen]
+pub fn browser_time_zone() -> Option<String> {
+    let format = intl_format(&js_sys::Object::new()).ok()?;
+    let resolved = js_sys::Reflect::get(&format, &JsValue::from_str("resolvedOptions")).ok()?.dyn_into::<js_sys::Function>().ok()?;
+    let options = resolved.call0(&format).ok()?;
+    js_sys::Reflect::get(&options, &JsValue::from_str("timeZone")).ok()?.as_string()
+}
+
+// A configured zone, with "browser" replaced by the browser's
+pub fn resolve(zone: &str) -> Option<String> {
+    if zone.eq_ignore_ascii_case(BROWSER) {
+        browser_time_zone()
+    } else {
+        Some(zone.to_string())
+    }
+}
+
+// Minutes `zone` is east of UTC at `utc_ms`, from its "GMT+05:30" style name
+fn offset_minutes(zone: &str, utc_ms: i64) -> Result<i32, String> {
+    let options = js_sys::Object::new();
+    let set = |key: &str, value: &str| js_sys::Reflect::set(&options, &JsValue::from_str(key), &JsValue::from_str(value));
+    set("timeZone", zone).and_then(|_| set("timeZoneName", "longOffset")).map_err(|_| "Failed to build time zone options".to_string())?;
+    let format = intl_format(&options).map_err(|_| format!("'{}' is not a known time zone", zone))?;
+    let parts = js_sys::Reflect::get(&format, &JsValue::from_str("formatToParts"))
+        .ok()
+        .and_then(|f| f.dyn_into::<js_sys::Function>().ok())
+        .and_then(|f| f.call1(&format, &js_sys::Date::new(&JsValue::from_f64(utc_ms as f64))).ok())
+        .map(|parts| js_sys::Array::from(&parts))
+        .ok_or_else(|| format!("Failed to format a time in '{}'", zone))?;
+    let name = parts
+        .iter()
+        .find(|part| js_sys::Reflect::get(part, &JsValue::from_str("type")).ok().and_then(|t| t.as_string()).as_deref() == Some("timeZoneName"))
+        .and_then(|part| js_sys::Reflect::get(&part, &JsValue::from_str("value")).ok()?.as_string())
+        .ok_or_else(|| format!("No UTC offset for '{}'", zone))?;
+    match name.strip_prefix("GMT").unwrap_or(&name) {
+        "" => Ok(0),
+        offset => parse_offset(offset),
+    }
+}
+
+// A timestamp with an offset, shown as the wall-clock time in `zone`
+pub fn to_zone(text: &str, zone: &str) -> Result<String, String> {
+    let (local, offset) = parse(text)?;
+    let utc = local - i64::from(offset.unwrap_or(0)) * 60_000;
+    Ok(format(utc, offset_minutes(zone, utc)?))
+}
+
+// A timestamp as UTC. One without an offset is taken as wall-clock time in `zone`.
+pub fn to_utc(text: &str, zone: &str) -> Result<String, String> {
+    let (local, offset) = parse(text)?;
+    let offset = match offset {
+        Some(offset) => offset,
+        // Guess with the offset at the same reading in UTC, then correct across a DST change
+        None => offset_minutes(zone, local - i64::from(offset_minutes(zone, local)?) * 60_000)?,
+    };
+    Ok(format(local - i64::from(offset) * 60_000, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(parse("1970-01-01T00:00:00Z"), Ok((0, Some(0))));
+        let (local, offset) = parse("2024-03-01 17:30:05.25+05:30").unwrap();
+        assert_eq!(offset, Some(330));
+        assert_eq!(format(local - 330 * 60_000, 0), "2024-03-01T12:00:05.250Z");
+        assert_eq!(format(local - 330 * 60_000, -480), "2024-03-01T04:00:05.250-08:00");
+        assert_eq!(parse("2024-03-01 12:00"), Ok((1_709_294_400_000, None)));
+        assert_eq!(format(-1, 0), "1969-12-31T23:59:59.999Z");
+        assert!(parse("2024-13-01 00:00:00").is_err());
+        assert!(parse("yesterday").is_err());
+    }
+}
@@ -0,0 +1,106 @@
+// W3C trace context propagation and sqlcommenter-style SQL annotation
+
+// Validate a `traceparent` header value: version-traceid-parentid-flags
+pub fn validate_traceparent(value: &str) -> Result<(), String> {
+    let parts: Vec<&str> = value.split('-').collect();
+    let lengths = [2, 32, 16, 2];
+    let well_formed = parts.len() == 4
+        && parts
+            .iter()
+            .zip(lengths.iter())
+            .all(|(part, len)| part.len() == *len && part.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+    if !well_formed {
+        return Err(format!("Invalid traceparent '{}'", value));
+    }
+    if parts[0] == "ff" || parts[1].chars().all(|c| c == '0') || parts[2].chars().all(|c| c == '0') {
+        return Err(format!("Invalid traceparent '{}'", value));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceConfig {
+    pub traceparent: Option<String>,
+    pub sqlcommenter: bool,
+}
+
+// Percent-encode a sqlcommenter value (RFC 3986 unreserved characters pass through)
+fn url_encode(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+// Append a `/*key='value',...*/` comment, keeping any trailing semicolon last. It goes on
+// its own line so a trailing `--` comment can't swallow it. Statements that already carry
+// a comment are left untouched, per the spec.
+pub fn append_sql_comment(sql: &str, tags: &[(&str, &str)]) -> String {
+    if tags.is_empty() || sql.contains("/*") {
+        return sql.to_string();
+    }
+
+    let mut tags: Vec<(&str, &str)> = tags.to_vec();
+    tags.sort_by(|a, b| a.0.cmp(b.0));
+    let comment = tags
+        .iter()
+        .map(|(key, value)| format!("{}='{}'", url_encode(key), url_encode(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let trimmed = sql.trim_end();
+    match trimmed.strip_suffix(';') {
+        Some(statement) => format!("{}\n/*{}*/;", statement.trim_end(), comment),
+        None => format!("{}\n/*{}*/", trimmed, comment),
+    }
+}
+
+impl TraceConfig {
+    // SQL as it should be sent, annotated when sqlcommenter is enabled
+    pub fn annotate(&self, sql: &str) -> String {
+        match (&self.traceparent, self.sqlcommenter) {
+            (Some(traceparent), true) => append_sql_comment(sql, &[("traceparent", traceparent)]),
+            _ => sql.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_validate_traceparent() {
+        assert!(validate_traceparent(TRACEPARENT).is_ok());
+        assert!(validate_traceparent("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_err());
+        assert!(validate_traceparent("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_err());
+        assert!(validate_traceparent("garbage").is_err());
+    }
+
+    #[test]
+    fn test_sqlcommenter_annotation() {
+        let config = TraceConfig { traceparent: Some(TRACEPARENT.to_string()), sqlcommenter: true };
+        assert_eq!(
+            config.annotate("SELECT 1;"),
+            format!("SELECT 1\n/*traceparent='{}'*/;", TRACEPARENT)
+        );
+        assert_eq!(config.annotate("SELECT /* hint */ 1"), "SELECT /* hint */ 1");
+        assert_eq!(append_sql_comment("SELECT 1", &[("route", "/a b"), ("app", "x")]), "SELECT 1\n/*app='x',route='%2Fa%20b'*/");
+
+        let disabled = TraceConfig { traceparent: Some(TRACEPARENT.to_string()), sqlcommenter: false };
+        assert_eq!(disabled.annotate("SELECT 1"), "SELECT 1");
+    }
+
+    #[test]
+    fn test_sqlcommenter_survives_trailing_line_comment() {
+        let sql = append_sql_comment("SELECT 1 -- latest only", &[("app", "x")]);
+        assert_eq!(sql, "SELECT 1 -- latest only\n/*app='x'*/");
+        assert_eq!(append_sql_comment("SELECT 1 -- note\n;", &[("app", "x")]), "SELECT 1 -- note\n/*app='x'*/;");
+    }
+}
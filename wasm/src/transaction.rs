@@ -0,0 +1,468 @@
+use std::cell::{Cell, RefCell};
+use std::future::poll_fn;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+use js_sys::Promise;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::advisory::{self, LockKey};
+use crate::connection::{execute_query_with, QueryOptions, SharedState};
+use crate::errors::BridgeError;
+use crate::null_policy::NullPolicy;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Tsify)]
+#[serde(rename_all = "kebab-case")]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+// Options accepted by `with_transaction`; anything left out takes the session default
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(default)]
+pub struct TransactionOptions {
+    pub isolation: Option<IsolationLevel>,
+    #[serde(rename = "readOnly")]
+    pub read_only: Option<bool>,
+    // Only takes effect on a serializable, read-only transaction
+    pub deferrable: Option<bool>,
+}
+
+impl TransactionOptions {
+    pub fn begin_sql(&self) -> String {
+        let mut sql = "BEGIN".to_string();
+        if let Some(isolation) = self.isolation {
+            sql.push_str(" ISOLATION LEVEL ");
+            sql.push_str(isolation.sql());
+        }
+        match self.read_only {
+            Some(true) => sql.push_str(" READ ONLY"),
+            Some(false) => sql.push_str(" READ WRITE"),
+            None => {}
+        }
+        match self.deferrable {
+            Some(true) => sql.push_str(" DEFERRABLE"),
+            Some(false) => sql.push_str(" NOT DEFERRABLE"),
+            None => {}
+        }
+        sql
+    }
+}
+
+// One statement of `Transaction.batch`
+#[derive(Deserialize, Debug, Clone, PartialEq, Tsify)]
+#[serde(deny_unknown_fields)]
+pub struct BatchStatement {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Option<Vec<Value>>,
+}
+
+// Options accepted by `Transaction.batch`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchOptions {
+    // Run each statement under its own savepoint, so a failure undoes only that statement
+    // and the rest of the batch and transaction carry on
+    pub savepoints: bool,
+}
+
+// How one statement of a savepoint batch went
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BatchOutcome {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<BatchFailure>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BatchFailure {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+impl BatchOutcome {
+    fn failed(error: &JsValue) -> BatchOutcome {
+        let code = js_sys::Reflect::get(error, &JsValue::from_str("code")).ok().and_then(|code| code.as_string());
+        BatchOutcome { ok: false, result: None, error: Some(BatchFailure { message: crate::errors::message_of(error), code }) }
+    }
+}
+
+// Savepoint guarding statement `index` of a batch
+pub fn savepoint_name(index: usize) -> String {
+    format!("bridge_batch_{}", index)
+}
+
+// Where the client's `with_transaction` stands, as `transaction_status()` reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Idle,
+    InTransaction,
+    Failed,
+}
+
+impl TransactionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransactionStatus::Idle => "idle",
+            TransactionStatus::InTransaction => "in_transaction",
+            TransactionStatus::Failed => "failed",
+        }
+    }
+}
+
+// Serializes `with_transaction` against every other query: while one is open only its
+// own queries are sent, and the rest wait until it commits or rolls back
+#[derive(Default)]
+pub(crate) struct TransactionGate {
+    holder: Option<u32>,
+    // A statement of the open transaction failed, so Postgres will only accept ROLLBACK
+    failed: bool,
+    next_id: u32,
+    waiters: Vec<Waker>,
+}
+
+impl TransactionGate {
+    // Whether a query from `owner` (None outside any transaction) may go ahead
+    pub fn admits(&self, owner: Option<u32>) -> bool {
+        self.holder.is_none() || (owner.is_some() && self.holder == owner)
+    }
+
+    // Take the gate for a new transaction, unless one is open
+    pub fn open(&mut self) -> Option<u32> {
+        if self.holder.is_some() {
+            return None;
+        }
+        self.next_id += 1;
+        self.holder = Some(self.next_id);
+        self.failed = false;
+        self.holder
+    }
+
+    pub fn fail(&mut self, id: u32) {
+        if self.holder == Some(id) {
+            self.failed = true;
+        }
+    }
+
+    pub fn status(&self) -> TransactionStatus {
+        match (self.holder, self.failed) {
+            (None, _) => TransactionStatus::Idle,
+            (Some(_), false) => TransactionStatus::InTransaction,
+            (Some(_), true) => TransactionStatus::Failed,
+        }
+    }
+
+    // Returns the waiters to wake, so they run without the state borrowed
+    pub fn close(&mut self, id: u32) -> Vec<Waker> {
+        if self.holder != Some(id) {
+            return Vec::new();
+        }
+        self.holder = None;
+        self.failed = false;
+        std::mem::take(&mut self.waiters)
+    }
+}
+
+// Wait until queries from `owner` may be sent
+pub(crate) async fn admit(state: &SharedState, owner: Option<u32>) {
+    poll_fn(|cx| {
+        let mut state = state.borrow_mut();
+        if state.transactions.admits(owner) {
+            return Poll::Ready(());
+        }
+        state.transactions.waiters.push(cx.waker().clone());
+        Poll::Pending
+    })
+    .await
+}
+
+// Wait for the gate and take it
+pub(crate) async fn open(state: &SharedState) -> u32 {
+    loop {
+        if let Some(id) = state.borrow_mut().transactions.open() {
+            return id;
+        }
+        admit(state, None).await;
+    }
+}
+
+pub(crate) fn close(state: &SharedState, id: u32) {
+    let waiters = state.borrow_mut().transactions.close(id);
+    for waker in waiters {
+        waker.wake();
+    }
+}
+
+struct TransactionState {
+    client: SharedState,
+    id: u32,
+    null_policy: NullPolicy,
+    open: Cell<bool>,
+    // SQLSTATE of the statement that failed the transaction, even when the callback caught it
+    sql_state: RefCell<Option<String>>,
+}
+
+impl TransactionState {
+    fn failed(&self) -> bool {
+        self.client.borrow().transactions.status() == TransactionStatus::Failed
+    }
+
+    fn fail(&self, error: &JsValue) {
+        self.client.borrow_mut().transactions.fail(self.id);
+        if let Some(sql_state) = crate::errors::sql_state_of(error) {
+            self.sql_state.replace(Some(sql_state));
+        }
+    }
+}
+
+// The client handed to a `with_transaction` callback. Its queries run inside the
+// transaction; it stops working once the callback settles.
+#[wasm_bindgen]
+pub struct Transaction {
+    inner: Rc<TransactionState>,
+}
+
+fn error(message: &str, code: &str) -> JsValue {
+    BridgeError::from_error_payload(&json!({ "message": message, "code": code }), None).into()
+}
+
+async fn run(inner: &TransactionState, sql: &str, params: Option<Vec<serde_json::Value>>) -> Result<serde_json::Value, JsValue> {
+    // A retry would land in the aborted transaction, so there are none
+    let options = QueryOptions { retry: false, transaction: Some(inner.id), ..QueryOptions::default() };
+    execute_query_with(&inner.client, sql, params, options).await
+}
+
+#[wasm_bindgen]
+impl Transaction {
+    // Run a query inside the transaction; resolves like `query()` without options
+    #[wasm_bindgen]
+    pub fn query(&self, sql: &str, params_json: Option<String>) -> Result<Promise, JsValue> {
+        let params = crate::parse_params_json(params_json)?;
+        if !self.inner.open.get() {
+            return Err(error("The transaction has already finished", "TRANSACTION_CLOSED"));
+        }
+        let inner = self.inner.clone();
+        let sql = sql.to_string();
+        Ok(future_to_promise(async move {
+            let mut result = run(&inner, &sql, params).await.inspect_err(|e| inner.fail(e))?;
+            if let Some(serde_json::Value::Array(rows)) = result.get_mut("rows") {
+                inner.null_policy.apply_to_rows(rows);
+            }
+            inner.null_policy.to_js(&result)
+        }))
+    }
+
+    // Run `statements` (`[{ sql, params }]`) in order. By default the first failure fails
+    // the transaction like a failing `query()`. With `{ savepoints: true }` each statement
+    // gets a savepoint: a failure rolls back just that statement, and the promise resolves
+    // with `[{ ok, result?, error? }]`, one entry per statement.
+    #[wasm_bindgen]
+    pub fn batch(
+        &self,
+        #[wasm_bindgen(unchecked_param_type = "BatchStatement[]")] statements: JsValue,
+        #[wasm_bindgen(unchecked_optional_param_type = "BatchOptions | null")] options: JsValue,
+    ) -> Result<Promise, JsValue> {
+        let statements: Vec<BatchStatement> =
+            serde_wasm_bindgen::from_value(statements).map_err(|e| JsValue::from_str(&format!("Invalid batch statements: {}", e)))?;
+        let options: BatchOptions = if options.is_undefined() || options.is_null() {
+            BatchOptions::default()
+        } else {
+            serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&format!("Invalid batch options: {}", e)))?
+        };
+        if !self.inner.open.get() {
+            return Err(error("The transaction has already finished", "TRANSACTION_CLOSED"));
+        }
+        let inner = self.inner.clone();
+        Ok(future_to_promise(async move {
+            let outcomes = batch(&inner, statements, options).await.inspect_err(|e| inner.fail(e))?;
+            inner.null_policy.to_js(&outcomes)
+        }))
+    }
+
+    // Wait for advisory lock `key` and hold it until the transaction commits or rolls back
+    #[wasm_bindgen]
+    pub fn advisory_lock(&self, key: JsValue) -> Result<Promise, JsValue> {
+        self.lock("pg_advisory_xact_lock", key, false)
+    }
+
+    // Take advisory lock `key` for the rest of the transaction if it is free; resolves with
+    // whether it was taken
+    #[wasm_bindgen(unchecked_return_type = "Promise<boolean>")]
+    pub fn try_advisory_lock(&self, key: JsValue) -> Result<Promise, JsValue> {
+        self.lock("pg_try_advisory_xact_lock", key, true)
+    }
+
+    // `reports`: resolve with whether the lock was taken, rather than once it is
+    fn lock(&self, function: &str, key: JsValue, reports: bool) -> Result<Promise, JsValue> {
+        let key = LockKey::from_js(&key)?;
+        if !self.inner.open.get() {
+            return Err(error("The transaction has already finished", "TRANSACTION_CLOSED"));
+        }
+        let inner = self.inner.clone();
+        let sql = key.sql(function);
+        Ok(future_to_promise(async move {
+            let result = run(&inner, &sql, Some(vec![key.param()])).await.inspect_err(|e| inner.fail(e))?;
+            Ok(if reports { JsValue::from_bool(advisory::locked(&result)) } else { JsValue::UNDEFINED })
+        }))
+    }
+}
+
+async fn batch(inner: &TransactionState, statements: Vec<BatchStatement>, options: BatchOptions) -> Result<Vec<BatchOutcome>, JsValue> {
+    let mut outcomes = Vec::with_capacity(statements.len());
+    for (index, statement) in statements.into_iter().enumerate() {
+        let savepoint = savepoint_name(index);
+        if options.savepoints {
+            run(inner, &format!("SAVEPOINT {}", savepoint), None).await?;
+        }
+        let outcome = match run(inner, &statement.sql, statement.params).await {
+            Ok(mut result) => {
+                if let Some(Value::Array(rows)) = result.get_mut("rows") {
+                    inner.null_policy.apply_to_rows(rows);
+                }
+                BatchOutcome { ok: true, result: Some(result), error: None }
+            }
+            Err(e) if options.savepoints => {
+                // Undo the statement; failing that, the transaction itself is aborted
+                run(inner, &format!("ROLLBACK TO SAVEPOINT {}", savepoint), None).await?;
+                BatchOutcome::failed(&e)
+            }
+            Err(e) => return Err(e),
+        };
+        if options.savepoints {
+            run(inner, &format!("RELEASE SAVEPOINT {}", savepoint), None).await?;
+        }
+        outcomes.push(outcome);
+    }
+    Ok(outcomes)
+}
+
+// BEGIN with `options`, hand `callback` a `Transaction` and COMMIT once the promise it
+// returns resolves, or ROLLBACK if it rejects or a statement failed along the way. A
+// serialization failure or deadlock rolls back and calls `callback` again with a new
+// `Transaction`, as many times as the retry policy's `transactionAttempts` allows.
+pub(crate) async fn with_transaction(
+    state: SharedState,
+    callback: js_sys::Function,
+    options: TransactionOptions,
+    null_policy: NullPolicy,
+) -> Result<JsValue, JsValue> {
+    let policy = state.borrow().retry_policy.clone();
+    crate::retry::rerun_transaction(
+        &policy,
+        || transaction_attempt(&state, &callback, options, null_policy),
+        |attempt, sql_state| crate::connection::transaction_pause(&policy, attempt, sql_state),
+    )
+    .await
+}
+
+// One run of `with_transaction`; a failure carries its SQLSTATE
+async fn transaction_attempt(
+    state: &SharedState,
+    callback: &js_sys::Function,
+    options: TransactionOptions,
+    null_policy: NullPolicy,
+) -> Result<JsValue, (Option<String>, JsValue)> {
+    let id = open(state).await;
+    let inner = Rc::new(TransactionState { client: state.clone(), id, null_policy, open: Cell::new(true), sql_state: RefCell::default() });
+    let outcome = async {
+        run(&inner, &options.begin_sql(), None).await?;
+        let transaction = Transaction { inner: inner.clone() };
+        let settled = match callback.call1(&JsValue::NULL, &transaction.into()) {
+            Ok(returned) => JsFuture::from(Promise::resolve(&returned)).await,
+            Err(thrown) => Err(thrown),
+        };
+        inner.open.set(false);
+        match settled {
+            Ok(_) if inner.failed() => {
+                run(&inner, "ROLLBACK", None).await?;
+                Err(error("A statement in the transaction failed, so it was rolled back", "TRANSACTION_ABORTED"))
+            }
+            Ok(value) => run(&inner, "COMMIT", None).await.map(|_| value),
+            Err(reason) => {
+                if let Err(e) = run(&inner, "ROLLBACK", None).await {
+                    log_warn!("WASM rollback after a rejected transaction failed: {:?}", e);
+                }
+                Err(reason)
+            }
+        }
+    }
+    .await;
+    inner.open.set(false);
+    close(state, id);
+    outcome.map_err(|error| (crate::errors::sql_state_of(&error).or_else(|| inner.sql_state.take()), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gate_admits_only_the_holder() {
+        let mut gate = TransactionGate::default();
+        assert!(gate.admits(None));
+        let id = gate.open().unwrap();
+        assert!(gate.open().is_none());
+        assert!(gate.admits(Some(id)));
+        assert!(!gate.admits(None));
+        assert!(!gate.admits(Some(id + 1)));
+
+        assert!(gate.close(id + 1).is_empty());
+        gate.close(id);
+        assert!(gate.admits(None));
+        assert_eq!(gate.open(), Some(id + 1));
+    }
+
+    #[test]
+    fn test_status_and_begin_options() {
+        let mut gate = TransactionGate::default();
+        assert_eq!(gate.status(), TransactionStatus::Idle);
+        let id = gate.open().unwrap();
+        assert_eq!(gate.status(), TransactionStatus::InTransaction);
+        gate.fail(id + 1);
+        assert_eq!(gate.status(), TransactionStatus::InTransaction);
+        gate.fail(id);
+        assert_eq!(gate.status().as_str(), "failed");
+        gate.close(id);
+        assert_eq!(gate.status(), TransactionStatus::Idle);
+
+        assert_eq!(TransactionOptions::default().begin_sql(), "BEGIN");
+        let options: TransactionOptions = serde_json::from_str(r#"{"isolation": "serializable", "readOnly": true, "deferrable": true}"#).unwrap();
+        assert_eq!(options.begin_sql(), "BEGIN ISOLATION LEVEL SERIALIZABLE READ ONLY DEFERRABLE");
+        let options: TransactionOptions = serde_json::from_str(r#"{"isolation": "repeatable-read", "readOnly": false}"#).unwrap();
+        assert_eq!(options.begin_sql(), "BEGIN ISOLATION LEVEL REPEATABLE READ READ WRITE");
+    }
+
+    #[test]
+    fn test_batch_outcomes() {
+        let statements: Vec<BatchStatement> = serde_json::from_str(r#"[{"sql": "INSERT INTO t VALUES ($1)", "params": [1]}, {"sql": "SELECT 1"}]"#).unwrap();
+        assert_eq!(statements[1].params, None);
+        let options: BatchOptions = serde_json::from_str(r#"{"savepoints": true}"#).unwrap();
+        assert!(options.savepoints);
+        assert_eq!(savepoint_name(2), "bridge_batch_2");
+
+        let failed = BatchOutcome { ok: false, result: None, error: Some(BatchFailure { message: "duplicate key".to_string(), code: None }) };
+        assert_eq!(serde_json::to_value(failed).unwrap(), json!({ "ok": false, "error": { "message": "duplicate key" } }));
+    }
+}
@@ -0,0 +1,446 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, CloseEvent, ErrorEvent, MessageEvent, WebSocket};
+
+use crate::close_kind::CloseKind;
+use crate::connection::{self, SharedState};
+use crate::errors::{message_of, BridgeError};
+
+// Close code reported for connections the client dropped on purpose: abnormal, so
+// reconnect and the circuit breaker react as they would to a real drop
+pub const DROPPED_CLOSE_CODE: u16 = 4000;
+
+// A text-frame connection to the bridge. WebSocket is the default implementation;
+// tests and other runtimes can supply their own without touching query logic.
+pub trait Transport {
+    fn send(&self, text: &str) -> Result<(), BridgeError>;
+    fn close(&self);
+    fn is_open(&self) -> bool;
+}
+
+// A received frame once decoded to text; binary frames that aren't UTF-8 keep a lossy
+// copy for the resync report
+type DecodedFrame = Result<String, (String, String)>;
+
+fn decode_bytes(bytes: Vec<u8>) -> DecodedFrame {
+    String::from_utf8(bytes).map_err(|e| {
+        let reason = format!("Binary frame is not valid UTF-8: {}", e.utf8_error());
+        (reason, String::from_utf8_lossy(e.as_bytes()).into_owned())
+    })
+}
+
+// Frames in arrival order. A Blob is read asynchronously, and the frames behind it wait
+// so handlers see them in the order they came off the wire.
+#[derive(Default)]
+pub(crate) struct InboundOrder {
+    // Sequence number of the front of `frames`
+    front: u64,
+    frames: VecDeque<Option<DecodedFrame>>,
+}
+
+impl InboundOrder {
+    // Hold a place for a frame that is still being read
+    pub fn reserve(&mut self) -> u64 {
+        self.frames.push_back(None);
+        self.front + self.frames.len() as u64 - 1
+    }
+
+    pub fn fill(&mut self, seq: u64, frame: DecodedFrame) {
+        if let Some(slot) = seq.checked_sub(self.front).and_then(|index| self.frames.get_mut(index as usize)) {
+            *slot = Some(frame);
+        }
+    }
+
+    // Frames whose turn has come
+    pub fn ready(&mut self) -> Vec<DecodedFrame> {
+        let mut ready = Vec::new();
+        while let Some(Some(_)) = self.frames.front() {
+            ready.extend(self.frames.pop_front().flatten());
+            self.front += 1;
+        }
+        ready
+    }
+}
+
+// Where every transport reports connection events
+#[derive(Clone)]
+pub(crate) struct TransportEvents {
+    state: SharedState,
+    inbound: Rc<RefCell<InboundOrder>>,
+}
+
+impl TransportEvents {
+    pub fn new(state: &SharedState) -> TransportEvents {
+        TransportEvents { state: state.clone(), inbound: Rc::default() }
+    }
+
+    pub fn opened(&self) {
+        connection::on_open(&self.state);
+    }
+
+    pub fn message(&self, text: &str) {
+        log_trace!("WASM received message ({} bytes)", text.len());
+        self.state.borrow_mut().connection_stats.record_frame(text.len());
+        crate::chaos::receive(&self.state, text, crate::chunking::receive_frame);
+    }
+
+    // A frame of any kind the runtime delivers: a string, an ArrayBuffer or byte view
+    // holding UTF-8 text, or a Blob, which is read before it is dispatched
+    pub fn data(&self, data: JsValue) {
+        let seq = self.inbound.borrow_mut().reserve();
+        if let Some(text) = data.as_string() {
+            self.inbound.borrow_mut().fill(seq, Ok(text));
+        } else if data.is_instance_of::<js_sys::ArrayBuffer>() || data.is_instance_of::<js_sys::Uint8Array>() {
+            self.inbound.borrow_mut().fill(seq, decode_bytes(js_sys::Uint8Array::new(&data).to_vec()));
+        } else if let Ok(blob) = data.dyn_into::<Blob>() {
+            let events = self.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let frame = match JsFuture::from(blob.array_buffer()).await {
+                    Ok(buffer) => decode_bytes(js_sys::Uint8Array::new(&buffer).to_vec()),
+                    Err(e) => Err((format!("Failed to read a Blob frame: {:?}", e), String::new())),
+                };
+                events.inbound.borrow_mut().fill(seq, frame);
+                events.flush();
+            });
+            return;
+        } else {
+            let reason = "Frame is neither text nor binary data".to_string();
+            self.inbound.borrow_mut().fill(seq, Err((reason, String::new())));
+        }
+        self.flush();
+    }
+
+    fn flush(&self) {
+        let ready = self.inbound.borrow_mut().ready();
+        for frame in ready {
+            match frame {
+                Ok(text) => self.message(&text),
+                Err((reason, text)) => {
+                    self.state.borrow_mut().connection_stats.record_frame(text.len());
+                    crate::resync::recover(&self.state, &text, &reason);
+                }
+            }
+        }
+    }
+
+    pub fn closed(&self, code: u16, reason: &str) {
+        // `drop_connection` closes the transport normally but stands for a drop
+        let code = if std::mem::take(&mut self.state.borrow_mut().dropping) { DROPPED_CLOSE_CODE } else { code };
+        let kind = CloseKind::classify(code, reason);
+        log_info!("WASM transport closed: code={}, reason={}, kind={:?}", code, reason, kind);
+        self.state.borrow_mut().connection_stats.closed();
+        let detail = serde_json::json!({ "code": code, "reason": reason, "kind": kind, "action": kind.action() });
+        crate::events::emit(&self.state, "close", &detail);
+        // Expected after a shutdown notice: not a failure, and reconnect once the bridge
+        // should be back
+        let restart = self.state.borrow_mut().restart.bridge.take();
+        if let Some(notice) = restart {
+            let mut state = self.state.borrow_mut();
+            let reconnecting = state.will_reconnect();
+            state.interrupt_pending(&notice.error(None), reconnecting);
+            drop(state);
+            connection::emit_state_change(&self.state);
+            crate::reconnect::schedule_after(&self.state, notice.retry_after_ms);
+            return;
+        }
+        let reconnecting = {
+            let mut state = self.state.borrow_mut();
+            // Only drops and shutdowns reconnect: expired auth or a protocol violation
+            // would fail again, and an idle close waits for the next query
+            let reconnecting = kind.reconnects() && state.will_reconnect();
+            let kept = state.interrupt_pending(&kind.error(reason), reconnecting);
+            if kept > 0 {
+                log_info!("WASM keeping {} idempotent queries to re-send after reconnecting", kept);
+            }
+            if kind == CloseKind::IdleTimeout && state.idle.reconnect.is_some() {
+                state.idle.suspended = true;
+                state.protocol = Default::default();
+                state.session.restored = false;
+            }
+            if kind.is_failure() {
+                state.breaker.record_failure(js_sys::Date::now());
+                if state.breaker.state() == crate::breaker::BreakerState::Open {
+                    log_warn!("WASM circuit breaker open after {} consecutive failures", state.breaker.failures());
+                }
+            }
+            reconnecting
+        };
+        crate::listen_state::lost(&self.state, "The connection closed");
+        // Reconnecting finds out whether the session, and its locks, were resumed
+        if !reconnecting {
+            crate::advisory::lost(&self.state, "The connection closed");
+        }
+        connection::emit_state_change(&self.state);
+        if kind.reconnects() {
+            crate::reconnect::schedule(&self.state);
+        }
+    }
+
+    pub fn error(&self, description: &str) {
+        log_error!("WASM transport error: {}", description);
+        crate::events::emit(&self.state, "error", &serde_json::json!({ "message": description }));
+    }
+}
+
+pub(crate) struct WebSocketTransport {
+    ws: WebSocket,
+}
+
+fn listen<E: JsCast>(ws: &WebSocket, event: &str, mut handler: impl FnMut(E) + 'static) {
+    let callback = Closure::wrap(Box::new(move |e: JsValue| handler(e.unchecked_into::<E>())) as Box<dyn FnMut(JsValue)>);
+    // addEventListener rather than on* properties: edge runtimes only support the former
+    let _ = ws.add_event_listener_with_callback(event, callback.as_ref().unchecked_ref());
+    callback.forget();
+}
+
+impl WebSocketTransport {
+    // Forward the socket's events to `events`
+    pub fn attach(ws: WebSocket, events: TransportEvents) -> WebSocketTransport {
+        ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+
+        let on_open = events.clone();
+        listen(&ws, "open", move |_: JsValue| on_open.opened());
+
+        let on_error = events.clone();
+        listen(&ws, "error", move |e: ErrorEvent| on_error.error(&format!("{:?}", e.message())));
+
+        let on_close = events.clone();
+        listen(&ws, "close", move |e: CloseEvent| on_close.closed(e.code(), &e.reason()));
+
+        listen(&ws, "message", move |e: MessageEvent| events.data(e.data()));
+
+        WebSocketTransport { ws }
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&self, text: &str) -> Result<(), BridgeError> {
+        self.ws.send_with_str(text).map_err(|e| BridgeError::connection(&message_of(&e)))
+    }
+
+    fn close(&self) {
+        let _ = self.ws.close();
+    }
+
+    fn is_open(&self) -> bool {
+        self.ws.ready_state() == WebSocket::OPEN
+    }
+}
+
+// Transport implemented in JavaScript: an object with `send(text)`, `close()` and `isOpen()`
+pub(crate) struct JsTransport {
+    object: JsValue,
+}
+
+fn call_method(object: &JsValue, name: &str, args: &js_sys::Array) -> Result<JsValue, JsValue> {
+    let method: js_sys::Function = js_sys::Reflect::get(object, &JsValue::from_str(name))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str(&format!("Transport is missing a `{}` method", name)))?;
+    method.apply(object, args)
+}
+
+impl JsTransport {
+    // Build a transport by calling `factory(url, events)`, where `events` exposes
+    // `open()`, `message(data)`, `close(code, reason)` and `error(message)`. `data` may be
+    // a string, an ArrayBuffer, a Uint8Array or a Blob.
+    pub fn create(factory: &js_sys::Function, url: &str, events: TransportEvents) -> Result<JsTransport, JsValue> {
+        let sink = js_sys::Object::new();
+
+        let on_open = events.clone();
+        let open = Closure::wrap(Box::new(move || on_open.opened()) as Box<dyn FnMut()>);
+        let on_message = events.clone();
+        let message = Closure::wrap(Box::new(move |data: JsValue| on_message.data(data)) as Box<dyn FnMut(JsValue)>);
+        let on_close = events.clone();
+        let close = Closure::wrap(Box::new(move |code: Option<u16>, reason: Option<String>| {
+            on_close.closed(code.unwrap_or(1000), reason.as_deref().unwrap_or(""))
+        }) as Box<dyn FnMut(Option<u16>, Option<String>)>);
+        let error = Closure::wrap(Box::new(move |description: JsValue| {
+            events.error(&description.as_string().unwrap_or_else(|| format!("{:?}", description)))
+        }) as Box<dyn FnMut(JsValue)>);
+
+        js_sys::Reflect::set(&sink, &JsValue::from_str("open"), open.as_ref())?;
+        js_sys::Reflect::set(&sink, &JsValue::from_str("message"), message.as_ref())?;
+        js_sys::Reflect::set(&sink, &JsValue::from_str("close"), close.as_ref())?;
+        js_sys::Reflect::set(&sink, &JsValue::from_str("error"), error.as_ref())?;
+        open.forget();
+        message.forget();
+        close.forget();
+        error.forget();
+
+        let object = factory.call2(&JsValue::NULL, &JsValue::from_str(url), &sink)?;
+        Ok(JsTransport { object })
+    }
+}
+
+impl Transport for JsTransport {
+    fn send(&self, text: &str) -> Result<(), BridgeError> {
+        call_method(&self.object, "send", &js_sys::Array::of1(&JsValue::from_str(text)))
+            .map(|_| ())
+            .map_err(|e| BridgeError::connection(&message_of(&e)))
+    }
+
+    fn close(&self) {
+        let _ = call_method(&self.object, "close", &js_sys::Array::new());
+    }
+
+    fn is_open(&self) -> bool {
+        call_method(&self.object, "isOpen", &js_sys::Array::new()).is_ok_and(|open| open.is_truthy())
+    }
+}
+
+// Open a socket with an injected constructor (e.g. `require('ws')` in Node) or the
+// runtime's global `WebSocket` (browsers, Deno, Node 22+). Anything implementing the
+// WHATWG WebSocket surface (onopen/onmessage/onclose, send, close, readyState) works.
+// Close the connection as if it had dropped, so the client reconnects with a fresh
+// `hello`: injected disconnects, and a stream that fell out of step
+pub(crate) fn drop_connection(state: &SharedState) {
+    let transport = {
+        let mut state = state.borrow_mut();
+        state.dropping = true;
+        state.transport.take()
+    };
+    if let Some(transport) = transport {
+        transport.close();
+    }
+}
+
+pub(crate) fn open_socket(url: &str, constructor: Option<&js_sys::Function>) -> Result<WebSocket, JsValue> {
+    let constructor = match constructor {
+        Some(constructor) => constructor.clone(),
+        None => global_websocket().ok_or_else(|| {
+            JsValue::from_str(
+                "No WebSocket implementation found; pass one to set_websocket_impl (e.g. require('ws') in Node)",
+            )
+        })?,
+    };
+    let args = js_sys::Array::of1(&JsValue::from_str(url));
+    let socket = js_sys::Reflect::construct(&constructor, &args)?;
+    Ok(socket.unchecked_into::<WebSocket>())
+}
+
+// Edge runtimes (Cloudflare Workers) open client sockets with a `fetch` upgrade request;
+// the socket in `response.webSocket` must be accepted before use
+pub(crate) async fn open_edge_socket(url: &str) -> Result<WebSocket, JsValue> {
+    let http_url = edge_fetch_url(url)?;
+    let global = js_sys::global();
+    let fetch: js_sys::Function = js_sys::Reflect::get(&global, &JsValue::from_str("fetch"))?
+        .dyn_into()
+        .map_err(|_| JsValue::from_str("fetch is not available in this runtime"))?;
+
+    let headers = js_sys::Object::new();
+    js_sys::Reflect::set(&headers, &JsValue::from_str("Upgrade"), &JsValue::from_str("websocket"))?;
+    let init = js_sys::Object::new();
+    js_sys::Reflect::set(&init, &JsValue::from_str("headers"), &headers)?;
+
+    let response = fetch.call2(&global, &JsValue::from_str(&http_url), &init)?;
+    let response = wasm_bindgen_futures::JsFuture::from(js_sys::Promise::from(response)).await?;
+    let socket = js_sys::Reflect::get(&response, &JsValue::from_str("webSocket"))?;
+    if socket.is_null() || socket.is_undefined() {
+        let status = js_sys::Reflect::get(&response, &JsValue::from_str("status"))?;
+        return Err(JsValue::from_str(&format!(
+            "Server did not accept the WebSocket upgrade (status {})",
+            status.as_f64().unwrap_or(0.0)
+        )));
+    }
+
+    let accept: js_sys::Function = js_sys::Reflect::get(&socket, &JsValue::from_str("accept"))?.dyn_into()?;
+    accept.call0(&socket)?;
+    Ok(socket.unchecked_into::<WebSocket>())
+}
+
+// fetch() speaks http(s), so ws:// and wss:// URLs are rewritten
+pub(crate) fn edge_fetch_url(url: &str) -> Result<String, JsValue> {
+    if let Some(rest) = url.strip_prefix("ws://") {
+        Ok(format!("http://{}", rest))
+    } else if let Some(rest) = url.strip_prefix("wss://") {
+        Ok(format!("https://{}", rest))
+    } else if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(url.to_string())
+    } else {
+        Err(JsValue::from_str(&format!("Unsupported WebSocket URL '{}'", url)))
+    }
+}
+
+fn global_websocket() -> Option<js_sys::Function> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("WebSocket"))
+        .ok()
+        .and_then(|ctor| ctor.dyn_into::<js_sys::Function>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::connection::ClientState;
+    use crate::WebSocketMessage;
+
+    struct MockTransport {
+        sent: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Transport for MockTransport {
+        fn send(&self, text: &str) -> Result<(), BridgeError> {
+            self.sent.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+
+        fn close(&self) {}
+
+        fn is_open(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_state_sends_through_injected_transport() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let mut state = ClientState {
+            transport: Some(Box::new(MockTransport { sent: sent.clone() })),
+            ..ClientState::default()
+        };
+        assert!(state.is_connected());
+
+        let message = WebSocketMessage {
+            message_type: "ping".to_string(),
+            payload: serde_json::json!({"message": "hi"}),
+            id: Some("p1".to_string()),
+        };
+        state.send_message(&message).unwrap();
+        assert_eq!(sent.borrow().as_slice(), [r#"{"type":"ping","payload":{"message":"hi"},"id":"p1"}"#]);
+    }
+
+    #[test]
+    fn test_inbound_order_waits_for_blobs() {
+        let mut order = InboundOrder::default();
+        let text = order.reserve();
+        order.fill(text, Ok("a".to_string()));
+        assert_eq!(order.ready(), vec![Ok("a".to_string())]);
+
+        let blob = order.reserve();
+        let after = order.reserve();
+        order.fill(after, Ok("c".to_string()));
+        assert!(order.ready().is_empty());
+        order.fill(blob, decode_bytes(b"b".to_vec()));
+        assert_eq!(order.ready(), vec![Ok("b".to_string()), Ok("c".to_string())]);
+
+        let garbled = order.reserve();
+        order.fill(garbled, decode_bytes(vec![b'{', 0xff]));
+        let (reason, lossy) = order.ready().remove(0).unwrap_err();
+        assert!(reason.starts_with("Binary frame is not valid UTF-8"));
+        assert_eq!(lossy, "{\u{fffd}");
+    }
+
+    #[test]
+    fn test_edge_fetch_url() {
+        assert_eq!(edge_fetch_url("ws://localhost:8080/db").unwrap(), "http://localhost:8080/db");
+        assert_eq!(edge_fetch_url("wss://bridge.example.com").unwrap(), "https://bridge.example.com");
+        assert_eq!(edge_fetch_url("https://bridge.example.com").unwrap(), "https://bridge.example.com");
+    }
+}
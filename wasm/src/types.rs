@@ -0,0 +1,30 @@
+// Client building blocks with no wasm-bindgen types in their Rust API: SQL building,
+// checking and fingerprinting, result limits, retry policy, metrics and errors.
+
+pub use crate::conditions::{Condition, COMPARISON_OPERATORS};
+pub use crate::errors::BridgeError;
+pub use crate::fingerprint::{fingerprint, normalize, QueryStats, StatementStats};
+pub use crate::local_settings::{set_local_statements, Settings};
+pub use crate::metrics::{LatencyBucket, MetricsSnapshot, QueryMetrics, LATENCY_BUCKETS_MS};
+pub use crate::query_builder::{BuiltQuery, QueryBuilder};
+pub use crate::result_budget::ResultBudget;
+pub use crate::retry::RetryPolicy;
+pub use crate::slow_log::{SlowQuery, SlowQueryLog};
+pub use crate::sql::{quote_ident, quote_literal, quote_qualified, single_statement};
+pub use crate::sql_format::{format_sql, FormatOptions, KeywordCase};
+pub use crate::sql_validate::{validate, StatementInfo, SyntaxError, Validation};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_building_blocks_compose_without_js() {
+        let query = QueryBuilder::new("orders").where_value("customer", "=", json!(42)).to_query().unwrap();
+        assert!(validate(&query.sql).valid);
+        assert_eq!(fingerprint(&query.sql), fingerprint("select * from \"orders\" where \"customer\" = $1"));
+        let error = BridgeError::from_error_payload(&json!({"message": "boom", "code": "DATABASE_ERROR"}), None);
+        assert_eq!(error.code(), Some("DATABASE_ERROR"));
+    }
+}
@@ -0,0 +1,158 @@
+use wasm_bindgen::prelude::*;
+
+// Declarations the derived types refer to but can't derive: JSON values, session settings,
+// query tags and the errors `BridgeError` becomes in JS. The wire and options types get
+// theirs from `#[derive(Tsify)]` in the protocol crate and the modules that define them.
+// Natively only the tests read it; the custom section exists on wasm targets alone.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+const SHARED_TYPES: &str = r#"
+export type Value = null | boolean | number | string | Value[] | { [key: string]: Value };
+
+export type Settings = Record<string, Value>;
+
+export type QueryTags = Record<string, string>;
+
+export interface BridgeError extends Error {
+    name: "BridgeConnectionError" | "BridgeQueryError" | "BridgeIntegrityError";
+    code?: string;
+    detail?: string;
+    sqlState?: string;
+    queryId?: string;
+}
+"#;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_SHARED_TYPES: &str = SHARED_TYPES;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tsify::Tsify;
+
+    use crate::protocol::*;
+
+    // Names of the types `decl` declares and those it mentions, string literals aside
+    fn names(decl: &str) -> (Vec<String>, Vec<String>) {
+        let code: String = decl.split('"').step_by(2).collect();
+        let words: Vec<&str> = code.split(|c: char| !c.is_alphanumeric() && c != '_').filter(|w| !w.is_empty()).collect();
+        let declared = words.windows(2).filter(|w| w[0] == "interface" || w[0] == "type").map(|w| w[1].to_string()).collect();
+        let mentioned = words.iter().filter(|w| w.starts_with(char::is_uppercase)).map(|w| w.to_string()).collect();
+        (declared, mentioned)
+    }
+
+    #[test]
+    fn test_every_referenced_type_is_declared() {
+        let decls = [
+            SHARED_TYPES,
+            WebSocketMessage::DECL,
+            HelloPayload::DECL,
+            Capabilities::DECL,
+            Negotiated::DECL,
+            PoolRequest::DECL,
+            PoolMode::DECL,
+            StatementPolicy::DECL,
+            QueryPayload::DECL,
+            QueryResult::DECL,
+            CacheControl::DECL,
+            CacheStatus::DECL,
+            bridge_protocol::QueryTiming::DECL,
+            bridge_protocol::BridgeLoad::DECL,
+            bridge_protocol::FetchMorePayload::DECL,
+            bridge_protocol::binary::BinaryParam::DECL,
+            bridge_protocol::binary::BinaryType::DECL,
+            RawColumn::DECL,
+            ErrorPayload::DECL,
+            NotificationPayload::DECL,
+            bridge_protocol::AdminStats::DECL,
+            bridge_protocol::AdminClient::DECL,
+            bridge_protocol::PoolStats::DECL,
+            bridge_protocol::PinStatus::DECL,
+            bridge_protocol::DescribedType::DECL,
+            bridge_protocol::DescribedColumn::DECL,
+            bridge_protocol::StatementDescription::DECL,
+            crate::config::WasmClientConfig::DECL,
+            crate::config::Timeouts::DECL,
+            crate::reconnect::ReconnectPolicy::DECL,
+            crate::columnar::ResultFormat::DECL,
+            crate::placeholders::PlaceholderStyle::DECL,
+            crate::in_list::InListStyle::DECL,
+            crate::column_case::ColumnCase::DECL,
+            crate::statement_kind::StatementKind::DECL,
+            crate::listen_state::ListenState::DECL,
+            crate::tab_fanout::FanoutKind::DECL,
+            crate::tab_fanout::FanoutEvent::DECL,
+            crate::tab_fanout::FanoutFilter::DECL,
+            crate::probe::ProbeStage::DECL,
+            crate::probe::ProbeResult::DECL,
+            crate::close_kind::CloseKind::DECL,
+            crate::close_kind::CloseAction::DECL,
+            crate::schema_change::SchemaChangeOptions::DECL,
+            bridge_protocol::SchemaChange::DECL,
+            bridge_protocol::RowDelta::DECL,
+            bridge_protocol::PatchOperation::DECL,
+            bridge_protocol::PatchOp::DECL,
+            bridge_protocol::RowDeltaPayload::DECL,
+            crate::connection::QueryOptions::DECL,
+            crate::dispatch::Priority::DECL,
+            crate::query_cache::CacheOptions::DECL,
+            crate::conflict::ConflictCheck::DECL,
+            crate::metrics::MetricsSnapshot::DECL,
+            crate::metrics::LatencyBucket::DECL,
+            crate::connection_stats::ConnectionStatsSnapshot::DECL,
+            crate::payload_size::PayloadStats::DECL,
+            crate::intern::InternOptions::DECL,
+            crate::intern::InternStats::DECL,
+            crate::payload_size::SizeStats::DECL,
+            crate::chaos::ChaosOptions::DECL,
+            crate::fingerprint::StatementStats::DECL,
+            crate::slow_log::SlowQuery::DECL,
+            crate::retry::RetryPolicy::DECL,
+            crate::result_budget::ResultBudget::DECL,
+            crate::rate_limit::RateLimitOptions::DECL,
+            crate::backpressure::ThrottleOptions::DECL,
+            crate::throttled::ThrottleRetry::DECL,
+            crate::visibility::VisibilityOptions::DECL,
+            crate::rate_limit::OverflowPolicy::DECL,
+            crate::connection::SendOptions::DECL,
+            crate::optimistic::OptimisticPatch::DECL,
+            crate::explain::ExplainOptions::DECL,
+            crate::cache_invalidation::InvalidationOptions::DECL,
+            crate::cost_guard::CostGuard::DECL,
+            crate::row_transform::Derived::DECL,
+            crate::row_transform::RowTransform::DECL,
+            crate::row_transform::TransformRef::DECL,
+            crate::pagination::PageOptions::DECL,
+            crate::audit::AuditOptions::DECL,
+            crate::activity::ActivityOptions::DECL,
+            crate::notifications::NotificationOptions::DECL,
+            crate::named_subscriptions::SubscriptionDefinition::DECL,
+            crate::named_subscriptions::ReplayPolicy::DECL,
+            crate::sync::SyncOptions::DECL,
+            crate::csv::CsvOptions::DECL,
+            crate::join::JoinOptions::DECL,
+            crate::join::JoinKind::DECL,
+            crate::join::MergeOptions::DECL,
+            crate::export::ExportOptions::DECL,
+            crate::export::ExportFormat::DECL,
+            crate::transaction::TransactionOptions::DECL,
+            crate::transaction::IsolationLevel::DECL,
+            crate::transaction::BatchStatement::DECL,
+            crate::transaction::BatchOptions::DECL,
+            crate::prewarm::HotStatement::DECL,
+            crate::temp_table::TempTableSpec::DECL,
+            crate::temp_table::TempColumn::DECL,
+            crate::sql_format::FormatOptions::DECL,
+            crate::sql_format::KeywordCase::DECL,
+        ];
+        let mut declared = vec!["Record".to_string(), "Error".to_string()];
+        let mut mentioned = Vec::new();
+        for decl in decls {
+            let (names, refs) = names(decl);
+            declared.extend(names);
+            mentioned.extend(refs);
+        }
+        let missing: Vec<&String> = mentioned.iter().filter(|name| !declared.contains(name)).collect();
+        assert!(missing.is_empty(), "undeclared types: {:?}", missing);
+        assert!(QueryResult::DECL.contains("rowCount: number;"));
+    }
+}
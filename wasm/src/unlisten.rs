@@ -0,0 +1,56 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::connection::ClientState;
+
+// When a channel nothing uses any more is UNLISTENed
+#[derive(Default)]
+pub(crate) struct UnlistenPolicy {
+    // Keep every channel LISTENed until the connection closes
+    pub disabled: bool,
+    // Wait this long first, so a handler added back in the meantime keeps the LISTEN
+    pub linger_ms: Option<f64>,
+    // Bumped each time a channel is released, so only its latest linger UNLISTENs
+    generations: HashMap<String, u32>,
+}
+
+impl UnlistenPolicy {
+    pub fn release(&mut self, channel: &str) -> u32 {
+        let generation = self.generations.entry(channel.to_string()).or_default();
+        *generation += 1;
+        *generation
+    }
+
+    pub fn is_current(&self, channel: &str, generation: u32) -> bool {
+        self.generations.get(channel) == Some(&generation)
+    }
+}
+
+// How many notification callbacks, live queries and cache rules use `channel`
+pub(crate) fn ref_count(state: &ClientState, channel: &str) -> usize {
+    state.notifications.count(channel) + state.watches.count(channel) + usize::from(state.cache_invalidation.is_used(channel))
+}
+
+// The reference count of every LISTENed channel, including those at 0 while they linger
+pub(crate) fn ref_counts(state: &ClientState) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for channel in state.watches.listening() {
+        let count = ref_count(state, &channel);
+        counts.insert(channel, count);
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_latest_release_is_current() {
+        let mut policy = UnlistenPolicy::default();
+        let first = policy.release("orders");
+        let second = policy.release("orders");
+        assert!(!policy.is_current("orders", first));
+        assert!(policy.is_current("orders", second));
+        assert!(!policy.is_current("users", second));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+use serde_json::json;
+use tsify::Tsify;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::connection::SharedState;
+
+// How often a hidden page whose connection couldn't close yet tries again
+const SUSPEND_RETRY_MS: f64 = 1_000.0;
+
+// Options accepted by `set_visibility_options`: what the client does while its page is
+// in a background tab
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default, Tsify)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisibilityOptions {
+    // Hold live-query refreshes while hidden; each one a change reached runs once when
+    // the page is shown again
+    #[serde(rename = "pauseLiveQueries")]
+    pub pause_live_queries: bool,
+    // Ping the bridge this often while the page is visible
+    #[serde(rename = "heartbeatMs")]
+    pub heartbeat_ms: Option<f64>,
+    // And this often while hidden; defaults to `heartbeatMs`
+    #[serde(rename = "hiddenHeartbeatMs")]
+    pub hidden_heartbeat_ms: Option<f64>,
+    // Close the connection once the page has been hidden this long, and reopen it when
+    // the page is shown
+    #[serde(rename = "suspendAfterMs")]
+    pub suspend_after_ms: Option<f64>,
+}
+
+impl VisibilityOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        for ms in [self.heartbeat_ms, self.hidden_heartbeat_ms].into_iter().flatten() {
+            if !(ms.is_finite() && ms > 0.0) {
+                return Err("heartbeatMs and hiddenHeartbeatMs must be positive".to_string());
+            }
+        }
+        if self.suspend_after_ms.is_some_and(|ms| !(ms.is_finite() && ms >= 0.0)) {
+            return Err("suspendAfterMs must be a number of milliseconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct VisibilityState {
+    pub options: VisibilityOptions,
+    pub hidden: bool,
+    // Live queries whose refresh waits for the page to be shown
+    stale: BTreeSet<u32>,
+    // Closed because the page was hidden
+    suspended: bool,
+    // The `visibilitychange` listener on the document, when there is one
+    listener: Option<js_sys::Function>,
+    // Bumped on every change so heartbeat and suspend timers from before stop
+    generation: u32,
+}
+
+impl VisibilityState {
+    // Milliseconds between heartbeats right now, if any
+    pub fn heartbeat_ms(&self) -> Option<f64> {
+        if self.hidden {
+            self.options.hidden_heartbeat_ms.or(self.options.heartbeat_ms)
+        } else {
+            self.options.heartbeat_ms
+        }
+    }
+
+    // Whether a live query's refresh should wait for the page; if so it's kept for then
+    pub fn defer_refresh(&mut self, id: u32) -> bool {
+        if !(self.hidden && self.options.pause_live_queries) {
+            return false;
+        }
+        self.stale.insert(id);
+        true
+    }
+
+    // Record a change; false when the page already was in that state
+    fn change(&mut self, hidden: bool) -> bool {
+        if self.hidden == hidden {
+            return false;
+        }
+        self.hidden = hidden;
+        self.generation += 1;
+        true
+    }
+}
+
+fn document() -> Option<web_sys::Document> {
+    web_sys::window().and_then(|window| window.document())
+}
+
+// Apply new options, following the document's `visibilitychange` where there is one.
+// In a worker the page's visibility comes from `set_page_visible` instead.
+pub(crate) fn configure(state: &SharedState, options: VisibilityOptions) {
+    let document = document();
+    let listening = {
+        let mut state = state.borrow_mut();
+        state.visibility.options = options;
+        state.visibility.generation += 1;
+        state.visibility.listener.is_some()
+    };
+    if let (Some(document), false) = (&document, listening) {
+        let listener_state = state.clone();
+        let listener = Closure::<dyn FnMut()>::new(move || {
+            if let Some(document) = self::document() {
+                changed(&listener_state, document.hidden());
+            }
+        });
+        let listener: js_sys::Function = listener.into_js_value().unchecked_into();
+        if document.add_event_listener_with_callback("visibilitychange", &listener).is_ok() {
+            state.borrow_mut().visibility.listener = Some(listener);
+        }
+    }
+    match document {
+        Some(document) if document.hidden() != state.borrow().visibility.hidden => changed(state, document.hidden()),
+        _ => start_timers(state),
+    }
+}
+
+// Back to always-visible behavior, without the document listener or timers
+pub(crate) fn reset(state: &SharedState) {
+    let listener = state.borrow_mut().visibility.listener.take();
+    if let (Some(listener), Some(document)) = (listener, document()) {
+        let _ = document.remove_event_listener_with_callback("visibilitychange", &listener);
+    }
+    state.borrow_mut().visibility.options = VisibilityOptions::default();
+    changed(state, false);
+    state.borrow_mut().visibility.generation += 1;
+}
+
+// The page was hidden or shown
+pub(crate) fn changed(state: &SharedState, hidden: bool) {
+    let (stale, resume) = {
+        let mut state = state.borrow_mut();
+        if !state.visibility.change(hidden) {
+            return;
+        }
+        if hidden {
+            (BTreeSet::new(), false)
+        } else {
+            let visibility = &mut state.visibility;
+            (std::mem::take(&mut visibility.stale), std::mem::take(&mut visibility.suspended))
+        }
+    };
+    log_debug!("WASM page is {}", if hidden { "hidden" } else { "visible" });
+    crate::events::emit(state, "visibility", &json!({ "hidden": hidden }));
+    if resume {
+        // The reopened connection runs every live query once it's set up
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = crate::idle::resume(&state).await {
+                log_warn!("WASM failed to reopen the connection of a shown page: {:?}", e);
+            }
+        });
+    } else {
+        for id in stale {
+            wasm_bindgen_futures::spawn_local(crate::live::run(state.clone(), id));
+        }
+    }
+    start_timers(state);
+}
+
+// Start the heartbeat, and the suspend countdown of a hidden page, for the current
+// generation
+fn start_timers(state: &SharedState) {
+    let (generation, heartbeat_ms, suspend_after_ms) = {
+        let state = state.borrow();
+        let visibility = &state.visibility;
+        (visibility.generation, visibility.heartbeat_ms(), visibility.options.suspend_after_ms.filter(|_| visibility.hidden))
+    };
+    let current = move |state: &SharedState| state.borrow().visibility.generation == generation;
+    if let Some(interval_ms) = heartbeat_ms {
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            loop {
+                if crate::retry::sleep(interval_ms).await.is_err() || !current(&state) {
+                    return;
+                }
+                let mut state = state.borrow_mut();
+                if state.is_connected() {
+                    if let Err(e) = state.send_ping("heartbeat") {
+                        log_warn!("WASM heartbeat failed: {:?}", e);
+                    }
+                }
+            }
+        });
+    }
+    if let Some(after_ms) = suspend_after_ms {
+        let state = state.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let mut wait_ms = after_ms;
+            loop {
+                if crate::retry::sleep(wait_ms).await.is_err() || !current(&state) {
+                    return;
+                }
+                if !state.borrow().is_connected() {
+                    return;
+                }
+                if crate::idle::suspend(&state, true) {
+                    state.borrow_mut().visibility.suspended = true;
+                    return;
+                }
+                // Requests in flight: close once they're done
+                wait_ms = SUSPEND_RETRY_MS;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden_page_slows_heartbeat_and_defers_refreshes() {
+        let options: VisibilityOptions = serde_json::from_value(json!({ "pauseLiveQueries": true, "heartbeatMs": 15000, "hiddenHeartbeatMs": 60000 })).unwrap();
+        assert!(options.validate().is_ok());
+        assert!(VisibilityOptions { heartbeat_ms: Some(0.0), ..options }.validate().is_err());
+
+        let mut visibility = VisibilityState { options, ..VisibilityState::default() };
+        assert_eq!(visibility.heartbeat_ms(), Some(15_000.0));
+        assert!(!visibility.defer_refresh(1));
+        assert!(visibility.change(true));
+        assert!(!visibility.change(true));
+        assert_eq!(visibility.heartbeat_ms(), Some(60_000.0));
+        assert!(visibility.defer_refresh(1));
+        assert!(visibility.defer_refresh(1));
+        assert_eq!(visibility.stale, BTreeSet::from([1]));
+
+        visibility.options.hidden_heartbeat_ms = None;
+        assert_eq!(visibility.heartbeat_ms(), Some(15_000.0));
+    }
+}
@@ -0,0 +1,159 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+use crate::errors::BridgeError;
+use crate::transport::{Transport, TransportEvents};
+
+// WebTransport (HTTP/3) session. Every outbound message is written on its own
+// unidirectional stream and the bridge answers each on a separate incoming stream,
+// so one large result never holds up the others.
+pub(crate) struct WebTransportTransport {
+    session: JsValue,
+    open: Rc<Cell<bool>>,
+    events: TransportEvents,
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, JsValue> {
+    js_sys::Reflect::get(target, &JsValue::from_str(key))
+}
+
+fn call0(target: &JsValue, method: &str) -> Result<JsValue, JsValue> {
+    get(target, method)?.dyn_into::<js_sys::Function>()?.call0(target)
+}
+
+async fn await_value(value: JsValue) -> Result<JsValue, JsValue> {
+    JsFuture::from(js_sys::Promise::resolve(&value)).await
+}
+
+pub fn is_supported() -> bool {
+    get(&js_sys::global(), "WebTransport").is_ok_and(|ctor| ctor.is_function())
+}
+
+// Join the chunks of one stream into a single UTF-8 message
+pub fn decode_chunks(chunks: Vec<Vec<u8>>) -> Result<String, String> {
+    String::from_utf8(chunks.concat()).map_err(|e| format!("Stream is not valid UTF-8: {}", e))
+}
+
+async fn read_stream(stream: JsValue) -> Result<String, JsValue> {
+    let reader = call0(&stream, "getReader")?;
+    let mut chunks = Vec::new();
+    loop {
+        let chunk = await_value(call0(&reader, "read")?).await?;
+        if get(&chunk, "done")?.is_truthy() {
+            break;
+        }
+        chunks.push(get(&chunk, "value")?.dyn_into::<js_sys::Uint8Array>()?.to_vec());
+    }
+    decode_chunks(chunks).map_err(|e| JsValue::from_str(&e))
+}
+
+// Each incoming unidirectional stream carries one complete server message
+async fn read_incoming(session: JsValue, events: TransportEvents) -> Result<(), JsValue> {
+    let reader = call0(&get(&session, "incomingUnidirectionalStreams")?, "getReader")?;
+    loop {
+        let next = await_value(call0(&reader, "read")?).await?;
+        if get(&next, "done")?.is_truthy() {
+            return Ok(());
+        }
+        let stream = get(&next, "value")?;
+        let events = events.clone();
+        spawn_local(async move {
+            match read_stream(stream).await {
+                Ok(text) => events.message(&text),
+                Err(e) => events.error(&format!("Failed to read WebTransport stream: {:?}", e)),
+            }
+        });
+    }
+}
+
+async fn write_message(session: JsValue, text: String) -> Result<(), JsValue> {
+    let stream = await_value(call0(&session, "createUnidirectionalStream")?).await?;
+    let writer = call0(&stream, "getWriter")?;
+    let write: js_sys::Function = get(&writer, "write")?.dyn_into()?;
+    await_value(write.call1(&writer, &js_sys::Uint8Array::from(text.as_bytes()))?).await?;
+    await_value(call0(&writer, "close")?).await?;
+    Ok(())
+}
+
+impl WebTransportTransport {
+    // Open a session and wait until it is ready; the caller reports `opened` once
+    // the transport is installed
+    pub async fn connect(url: &str, events: TransportEvents) -> Result<WebTransportTransport, JsValue> {
+        if !is_supported() {
+            return Err(JsValue::from_str("WebTransport is not available in this runtime"));
+        }
+        let ctor: js_sys::Function = get(&js_sys::global(), "WebTransport")?.dyn_into()?;
+        let session = js_sys::Reflect::construct(&ctor, &js_sys::Array::of1(&JsValue::from_str(url)))?;
+        await_value(get(&session, "ready")?).await?;
+
+        let open = Rc::new(Cell::new(true));
+        let closed_flag = open.clone();
+        let closed_events = events.clone();
+        let closed = get(&session, "closed")?;
+        spawn_local(async move {
+            let info = await_value(closed).await;
+            closed_flag.set(false);
+            let (code, reason) = match &info {
+                Ok(info) => (
+                    get(info, "closeCode").ok().and_then(|c| c.as_f64()).unwrap_or(0.0) as u16,
+                    get(info, "reason").ok().and_then(|r| r.as_string()).unwrap_or_default(),
+                ),
+                Err(e) => (1006, format!("{:?}", e)),
+            };
+            closed_events.closed(code, &reason);
+        });
+
+        let reader_events = events.clone();
+        let reader_session = session.clone();
+        spawn_local(async move {
+            if let Err(e) = read_incoming(reader_session, reader_events.clone()).await {
+                reader_events.error(&format!("WebTransport reader stopped: {:?}", e));
+            }
+        });
+
+        Ok(WebTransportTransport { session, open, events })
+    }
+}
+
+impl Transport for WebTransportTransport {
+    fn send(&self, text: &str) -> Result<(), BridgeError> {
+        if !self.open.get() {
+            return Err(BridgeError::connection("WebTransport session is closed"));
+        }
+        let session = self.session.clone();
+        let events = self.events.clone();
+        let text = text.to_string();
+        spawn_local(async move {
+            if let Err(e) = write_message(session, text).await {
+                events.error(&format!("Failed to write WebTransport stream: {:?}", e));
+            }
+        });
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.open.set(false);
+        let _ = call0(&self.session, "close");
+    }
+
+    fn is_open(&self) -> bool {
+        self.open.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_chunks_across_boundaries() {
+        let bytes = "{\"type\":\"result\",\"payload\":\"é\"}".as_bytes().to_vec();
+        let (a, b) = bytes.split_at(29);
+        assert_eq!(decode_chunks(vec![a.to_vec(), b.to_vec()]).unwrap(), "{\"type\":\"result\",\"payload\":\"é\"}");
+        assert!(decode_chunks(vec![vec![0xff]]).is_err());
+    }
+}